@@ -0,0 +1,3 @@
+-- Add a column which flags records where the ETag in the event differs from the ETag returned by `HeadObject`.
+-- This indicates that the object may have changed between the event being emitted and its metadata being collected.
+alter table s3_object add column is_e_tag_mismatch bool not null default false;
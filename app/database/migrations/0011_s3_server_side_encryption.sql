@@ -0,0 +1,4 @@
+-- Add columns for the server-side encryption of objects, as returned by `HeadObject`. These are null if the object
+-- is not encrypted, or if the encryption is unknown.
+alter table s3_object add column server_side_encryption text;
+alter table s3_object add column sse_kms_key_id text;
@@ -0,0 +1,16 @@
+-- Add a table to store how often a bucket and prefix should be crawled, and when it was last crawled.
+create table s3_crawl_schedule (
+    -- The primary key id.
+    s3_crawl_schedule_id uuid not null primary key,
+    -- The bucket to crawl.
+    bucket text not null,
+    -- The prefix of objects to crawl. A null prefix crawls the whole bucket.
+    prefix text default null,
+    -- How often the crawl should run in seconds.
+    interval_seconds bigint not null,
+    -- When a crawl for this bucket and prefix last completed. This is null if it has never completed.
+    last_completed timestamptz default null
+);
+
+-- There should only be one schedule for a bucket and prefix, including a null prefix.
+create unique index s3_crawl_schedule_unique on s3_crawl_schedule (bucket, prefix) nulls not distinct;
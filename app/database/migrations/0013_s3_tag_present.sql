@@ -0,0 +1,3 @@
+-- Add a column recording whether the ingest id tag was present on the object in S3 when it was collected. This is false
+-- if the filemanager had to generate and assign the tag, and null if the tagging of the object is unknown.
+alter table s3_object add column tag_present boolean;
@@ -0,0 +1,3 @@
+-- Add a column recording when the temporary copy of a restored archived object expires. This is set by restore completed
+-- events, and cleared when the restore expires.
+alter table s3_object add column restore_expiry_date timestamptz default null;
@@ -0,0 +1,3 @@
+-- Add a column flagging records where another event with the same sequencer had a different ETag or size. Events
+-- sharing a sequencer should be identical, so this indicates malformed upstream data.
+alter table s3_object add column is_sequencer_conflict boolean not null default false;
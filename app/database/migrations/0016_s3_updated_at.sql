@@ -0,0 +1,40 @@
+-- Add a column recording when a record was last inserted or changed. This is maintained by a trigger so that any
+-- change to a record is reflected, and is used to determine whether list results have changed.
+--
+-- Existing records are set to the time of the migration using a non-volatile default, which only changes the table
+-- metadata rather than rewriting the table. The default is then changed so that it only applies to new records.
+alter table s3_object add column updated_at timestamptz not null default now();
+alter table s3_object alter column updated_at set default clock_timestamp();
+
+-- Only update the timestamp if the record actually changed, as re-deriving the current state updates all records
+-- of an object. Generated columns are excluded because they are not computed yet in a before trigger.
+create function set_updated_at() returns trigger as $$
+begin
+    if to_jsonb(old) - 'is_accessible' - 'updated_at' is distinct from to_jsonb(new) - 'is_accessible' - 'updated_at' then
+        new.updated_at = clock_timestamp();
+    end if;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger s3_object_updated_at before update on s3_object
+    for each row execute function set_updated_at();
+
+-- Find the most recently updated record efficiently.
+create index updated_at_index on s3_object (updated_at);
+
+-- Removed records have no `updated_at`, so record the last time that any record was removed. This has a single row.
+create table s3_object_removed (
+    removed_at timestamptz not null
+);
+insert into s3_object_removed (removed_at) values (now());
+
+create function set_removed_at() returns trigger as $$
+begin
+    update s3_object_removed set removed_at = clock_timestamp();
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger s3_object_removed_at after delete or truncate on s3_object
+    for each statement execute function set_removed_at();
@@ -0,0 +1,2 @@
+-- Create an index targeting the size and e_tag together, which is used to find records with identical content.
+create index size_e_tag_index on s3_object (size, e_tag);
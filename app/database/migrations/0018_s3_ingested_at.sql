@@ -0,0 +1,7 @@
+-- Add a column recording when a record was first ingested, which is used to compute ingestion statistics. Existing
+-- records are left null because the time that they were ingested is not known.
+alter table s3_object add column ingested_at timestamptz;
+alter table s3_object alter column ingested_at set default clock_timestamp();
+
+-- Ingestion statistics only look at recently ingested records.
+create index ingested_at_index on s3_object (ingested_at);
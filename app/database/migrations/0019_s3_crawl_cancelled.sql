@@ -0,0 +1,6 @@
+-- Add a status for crawls which were cancelled, and record the key marker that listing reached before the crawl stopped.
+alter type crawl_status add value 'Cancelled';
+alter table s3_crawl add column key_marker text;
+-- Record that a cancellation was requested, which the crawl polls between listing pages. This is stored with the crawl
+-- so that any instance of the API can cancel it.
+alter table s3_crawl add column cancel_requested boolean not null default false;
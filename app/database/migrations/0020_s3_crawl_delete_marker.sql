@@ -0,0 +1,2 @@
+-- Add a reason for deleted records produced by a crawl from the latest delete marker of a key.
+alter type reason add value 'CrawlDeleteMarker';
@@ -0,0 +1,67 @@
+-- Add a function which converts `s3_object` into a table that is partitioned by a hash of the bucket, so that queries
+-- which filter by bucket only scan the partition containing that bucket. Partitioning rewrites the whole table, so it
+-- is opt-in and is applied by calling this function after migrations rather than in this migration.
+--
+-- Primary keys and unique constraints on a partitioned table must contain the partition key, so the primary key
+-- becomes `(s3_object_id, bucket)`. All other constraints and indexes already contain the bucket, or are not unique, so
+-- they are recreated unchanged on the partitioned table. Returns false if the table is already partitioned.
+create function partition_s3_object_by_bucket(partitions integer) returns boolean as $$
+declare
+    definitions text[];
+    definition text;
+    columns text;
+begin
+    if exists (select from pg_partitioned_table where partrelid = 's3_object'::regclass) then
+        return false;
+    end if;
+
+    lock table s3_object in access exclusive mode;
+    alter table s3_object rename to s3_object_unpartitioned;
+
+    -- Collect the constraints, indexes and triggers of the existing table so that they can be recreated once it is
+    -- dropped. The primary key is excluded as it is replaced.
+    definitions := array(
+        select format('alter table s3_object add constraint %I %s', conname, pg_get_constraintdef(oid))
+        from pg_constraint
+        where conrelid = 's3_object_unpartitioned'::regclass and contype in ('u', 'c')
+    ) || array(
+        select replace(pg_get_indexdef(indexrelid), 's3_object_unpartitioned', 's3_object')
+        from pg_index
+        where indrelid = 's3_object_unpartitioned'::regclass and not exists (
+            select from pg_constraint where conindid = indexrelid
+        )
+    ) || array(
+        select replace(pg_get_triggerdef(oid), 's3_object_unpartitioned', 's3_object')
+        from pg_trigger
+        where tgrelid = 's3_object_unpartitioned'::regclass and not tgisinternal
+    );
+
+    -- Generated columns cannot be inserted, they are recomputed in the partitioned table instead.
+    select string_agg(quote_ident(attname), ', ' order by attnum) into columns
+    from pg_attribute
+    where attrelid = 's3_object_unpartitioned'::regclass and attnum > 0 and not attisdropped and attgenerated = '';
+
+    create table s3_object (
+        like s3_object_unpartitioned including defaults including generated including comments
+    ) partition by hash (bucket);
+    alter table s3_object add primary key (s3_object_id, bucket);
+
+    for i in 0..partitions - 1 loop
+        execute format(
+            'create table %I partition of s3_object for values with (modulus %s, remainder %s)',
+            's3_object_' || i,
+            partitions,
+            i
+        );
+    end loop;
+
+    execute format('insert into s3_object (%s) select %s from s3_object_unpartitioned', columns, columns);
+    drop table s3_object_unpartitioned;
+
+    foreach definition in array definitions loop
+        execute definition;
+    end loop;
+
+    return true;
+end;
+$$ language plpgsql;
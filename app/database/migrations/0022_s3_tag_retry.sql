@@ -0,0 +1,33 @@
+-- The status of an `ingest_id` tag write that failed and is retried.
+create type tag_retry_status as enum (
+    'Pending',
+    'Succeeded',
+    'DeadLetter'
+);
+
+-- Add a table to record `ingest_id` tag writes that failed, so that they can be retried after ingestion.
+create table s3_tag_retry (
+    -- The primary key id.
+    s3_tag_retry_id uuid not null primary key,
+    -- The status of the tag write.
+    status tag_retry_status not null default 'Pending',
+    -- The bucket of the object to tag.
+    bucket text not null,
+    -- The key of the object to tag.
+    key text not null,
+    -- The version id of the object to tag.
+    version_id text not null default 'null',
+    -- The ingest_id that the object should be tagged with.
+    ingest_id uuid not null,
+    -- The number of times that the tag write was retried.
+    attempts int not null default 0,
+    -- The error of the last failed tag write.
+    last_error text default null,
+    -- When the failed tag write was recorded.
+    created timestamptz not null default now(),
+    -- When the tag write was last retried.
+    last_attempted timestamptz default null
+);
+
+-- There should only be one pending tag write for an object version.
+create unique index s3_tag_retry_unique_pending on s3_tag_retry (bucket, key, version_id) where status = 'Pending';
@@ -0,0 +1,13 @@
+-- Whether a conflicting event with the same sequencer as an existing record has a different ETag or size. Events
+-- sharing a sequencer should be identical, so this indicates a sequencer conflict. This is shared by the ingester
+-- queries which resolve sequencer conflicts. The arguments are scalar rather than `s3_object` rows so that the function
+-- does not depend on the table's row type, which is replaced when partitioning the table.
+create function s3_object_is_sequencer_conflict(
+    e_tag text,
+    size bigint,
+    conflicting_e_tag text,
+    conflicting_size bigint
+) returns boolean
+language sql immutable as $$
+    select conflicting_e_tag is distinct from e_tag or conflicting_size is distinct from size
+$$;
@@ -0,0 +1,9 @@
+-- Enqueue a crawl of a bucket and prefix by making its crawl schedule due, creating the schedule with the interval if it
+-- does not exist. An existing schedule is only made due if its last crawl completed longer than the interval ago, which
+-- limits how often a crawl is enqueued. Returns the schedule if the crawl was enqueued.
+insert into s3_crawl_schedule (s3_crawl_schedule_id, bucket, prefix, interval_seconds)
+values ($1, $2, $3, $4)
+on conflict (bucket, prefix) do update
+set last_completed = null
+where s3_crawl_schedule.last_completed < now() - make_interval(secs => $4)
+returning *;
@@ -37,6 +37,12 @@ select
     ingest_id,
     attributes,
     is_current_state,
+    is_e_tag_mismatch,
+    server_side_encryption,
+    sse_kms_key_id,
+    tag_present,
+    restore_expiry_date,
+    is_sequencer_conflict,
     0::bigint as "number_reordered"
 from input
 -- Grab all objects in each input group.
@@ -0,0 +1,46 @@
+-- Compares the current state of objects in a source and destination bucket. Keys are compared relative to their
+-- prefix, so that an object under the source prefix is matched with the object under the destination prefix that
+-- has the same remaining key. Only keys which are missing from one of the buckets, or which have a different size
+-- or ETag are returned.
+
+-- Current objects under the source prefix.
+with source as (
+    select
+        substr(key, length($2) + 1) as relative_key,
+        key,
+        size,
+        e_tag
+    from s3_object
+    where
+        bucket = $1 and
+        is_current_state = true and
+        starts_with(key, $2)
+),
+-- Current objects under the destination prefix.
+destination as (
+    select
+        substr(key, length($4) + 1) as relative_key,
+        key,
+        size,
+        e_tag
+    from s3_object
+    where
+        bucket = $3 and
+        is_current_state = true and
+        starts_with(key, $4)
+)
+select
+    source.key as source_key,
+    destination.key as destination_key,
+    source.size as source_size,
+    destination.size as destination_size,
+    source.e_tag as source_e_tag,
+    destination.e_tag as destination_e_tag
+from source
+full outer join destination on source.relative_key = destination.relative_key
+where
+    source.key is null or
+    destination.key is null or
+    source.size is distinct from destination.size or
+    source.e_tag is distinct from destination.e_tag
+order by coalesce(source.relative_key, destination.relative_key);
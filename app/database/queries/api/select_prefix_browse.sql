@@ -0,0 +1,29 @@
+-- Groups all records under a prefix in a bucket by the next path segment after the prefix, including records which are
+-- not current such as deleted objects. Keys which contain a `/` after the prefix are grouped into a child prefix ending
+-- in `/`, and other keys are grouped by the key itself. Counts and sizes are computed for each child.
+
+-- Records under the prefix, with the next segment after the prefix.
+with children as (
+    select
+        key,
+        size,
+        is_current_state,
+        is_delete_marker,
+        split_part(substr(key, length($2) + 1), '/', 1) as segment,
+        strpos(substr(key, length($2) + 1), '/') > 0 as is_prefix
+    from s3_object
+    where
+        bucket = $1 and
+        starts_with(key, $2)
+)
+select
+    case when is_prefix then $2 || segment || '/' else key end as child,
+    is_prefix,
+    count(distinct key) as n_keys,
+    count(*) as n_records,
+    count(*) filter (where is_current_state) as n_current,
+    count(*) filter (where is_delete_marker) as n_delete_markers,
+    coalesce(sum(size) filter (where is_current_state), 0)::bigint as current_size
+from children
+group by child, is_prefix
+order by is_prefix desc, child;
@@ -0,0 +1,66 @@
+-- Lists a page of the immediate children of a prefix in the current state of a bucket. Keys under the prefix which
+-- contain a `/` after the prefix are grouped into a child prefix ending in `/`. Keys which do not are returned as objects.
+-- Keys where the current state is a delete marker are excluded, because the object does not exist. Child prefixes are
+-- ordered before objects, and the total number of children is returned on every row. If the page is empty, a single
+-- row with only the total number of children is returned. Children can be restricted to those after a start name and
+-- up to and including an end name, using the byte order of names that S3 lists keys in.
+
+-- Current objects under the prefix, with the position of the next `/` after the prefix.
+with children as (
+    select
+        key,
+        version_id,
+        size,
+        e_tag,
+        strpos(substr(key, length($2) + 1), '/') as delimiter_position
+    from s3_object
+    where
+        bucket = $1 and
+        is_current_state = true and
+        is_delete_marker = false and
+        starts_with(key, $2) and
+        -- Every key under a child after the start name is also after the start name.
+        ($5::text is null or key collate "C" > $5)
+),
+listing as (
+    -- The child prefixes.
+    select distinct
+        substr(key, 1, length($2) + delimiter_position) as prefix,
+        null as key,
+        null as version_id,
+        null::bigint as size,
+        null as e_tag
+    from children
+    where delimiter_position > 0
+    union all
+    -- The child objects.
+    select
+        null as prefix,
+        key,
+        version_id,
+        size,
+        e_tag
+    from children
+    where delimiter_position = 0
+),
+in_range as (
+    select * from listing
+    where
+        ($5::text is null or coalesce(prefix, key) collate "C" > $5) and
+        ($6::text is null or coalesce(prefix, key) collate "C" <= $6)
+)
+select
+    page.prefix,
+    page.key,
+    page.version_id,
+    page.size,
+    page.e_tag,
+    total.n_children
+from (select count(*) as n_children from in_range) as total
+left join (
+    select * from in_range
+    order by prefix, key
+    limit $3
+    offset $4
+) as page on true
+order by page.prefix, page.key;
@@ -1,4 +1,6 @@
--- Bulk insert of s3 objects.
+-- Bulk insert of s3 objects, counting duplicate events unless the reason is one of the uncounted reasons. If sequencer
+-- conflicts are resolved, a duplicate event with a different ETag or size flags the record as a sequencer conflict, and
+-- the content of the event with the later event time is kept.
 insert into s3_object (
     s3_object_id,
     bucket,
@@ -16,7 +18,13 @@ insert into s3_object (
     archive_status,
     event_type,
     ingest_id,
-    attributes
+    attributes,
+    is_e_tag_mismatch,
+    server_side_encryption,
+    sse_kms_key_id,
+    tag_present,
+    restore_expiry_date,
+    is_sequencer_conflict
 )
 values (
     unnest($1::uuid[]),
@@ -35,7 +43,34 @@ values (
     unnest($14::archive_status[]),
     unnest($15::event_type[]),
     unnest($16::uuid[]),
-    unnest($17::jsonb[])
+    unnest($17::jsonb[]),
+    unnest($18::boolean[]),
+    unnest($19::text[]),
+    unnest($20::text[]),
+    unnest($21::boolean[]),
+    unnest($22::timestamptz[]),
+    unnest($23::boolean[])
 ) on conflict on constraint sequencer_unique do update
-    set number_duplicate_events = s3_object.number_duplicate_events + 1
+    -- Duplicate events with a reason in the uncounted reasons are not counted.
+    set number_duplicate_events = s3_object.number_duplicate_events +
+        case when excluded.reason = any($24::reason[]) then 0 else 1 end,
+    -- The conflict is resolved once per row, and the content is only replaced if the conflicting event is later. A
+    -- null event time is never later.
+    (is_sequencer_conflict, e_tag, size, sha256, last_modified_date, event_time) = (
+        select
+            s3_object.is_sequencer_conflict or excluded.is_sequencer_conflict or conflict.is_conflict,
+            case when conflict.is_later then excluded.e_tag else s3_object.e_tag end,
+            case when conflict.is_later then excluded.size else s3_object.size end,
+            case when conflict.is_later then excluded.sha256 else s3_object.sha256 end,
+            case when conflict.is_later then excluded.last_modified_date else s3_object.last_modified_date end,
+            case when conflict.is_later then excluded.event_time else s3_object.event_time end
+        from (
+            select is_conflict, is_conflict and excluded.event_time > s3_object.event_time as is_later
+            from (
+                select $25::boolean and s3_object_is_sequencer_conflict(
+                    s3_object.e_tag, s3_object.size, excluded.e_tag, excluded.size
+                ) as is_conflict
+            ) as differs
+        ) as conflict
+    )
     returning s3_object_id, number_duplicate_events;
@@ -0,0 +1,11 @@
+-- Select the records which have a null sequencer, which were inserted by older ingestion logic. These are ordered
+-- deterministically within each bucket, key and version_id so that generated sequencers are consistent. Records are
+-- restricted to the buckets if they are not null.
+select
+    s3_object_id,
+    bucket,
+    key,
+    version_id
+from s3_object
+where sequencer is null and ($1::text[] is null or bucket = any($1))
+order by bucket, key, version_id, event_time nulls first, s3_object_id;
@@ -0,0 +1,6 @@
+-- Set generated sequencers on records which have a null sequencer.
+update s3_object
+set sequencer = input.sequencer
+from unnest($1::uuid[], $2::text[]) as input (s3_object_id, sequencer)
+where s3_object.s3_object_id = input.s3_object_id and s3_object.sequencer is null
+returning s3_object.s3_object_id;
@@ -22,7 +22,11 @@ with input as (
         $14::archive_status[],
         $15::event_type[],
         $16::uuid[],
-        $17::jsonb[]
+        $17::jsonb[],
+        $18::boolean[],
+        $19::text[],
+        $20::text[],
+        $21::boolean[]
     ) as input (
         s3_object_id,
         bucket,
@@ -40,7 +44,11 @@ with input as (
         archive_status,
         event_type,
         ingest_id,
-        attributes
+        attributes,
+        is_e_tag_mismatch,
+        server_side_encryption,
+        sse_kms_key_id,
+        tag_present
     )
 ),
 -- Then, select the objects that need to be updated.
@@ -62,7 +70,11 @@ current_objects as (
         input.reason as input_reason,
         input.archive_status as input_archive_status,
         input.event_type as input_event_type,
-        input.ingest_id as input_ingest_id
+        input.ingest_id as input_ingest_id,
+        input.is_e_tag_mismatch as input_is_e_tag_mismatch,
+        input.server_side_encryption as input_server_side_encryption,
+        input.sse_kms_key_id as input_sse_kms_key_id,
+        input.tag_present as input_tag_present
     from s3_object
     -- Grab the relevant values to update with.
     join input on
@@ -114,6 +126,10 @@ update as (
         storage_class = objects_to_update.input_storage_class,
         event_type = objects_to_update.input_event_type,
         ingest_id = objects_to_update.input_ingest_id,
+        is_e_tag_mismatch = objects_to_update.input_is_e_tag_mismatch,
+        server_side_encryption = objects_to_update.input_server_side_encryption,
+        sse_kms_key_id = objects_to_update.input_sse_kms_key_id,
+        tag_present = objects_to_update.input_tag_present,
         number_reordered = s3_object.number_reordered +
             -- Note the asymmetry between this and the reorder for deleted query.
             case when objects_to_update.deleted_sequencer is not null or objects_to_update.sequencer is not null then
@@ -146,6 +162,12 @@ select
     ingest_id,
     is_current_state,
     attributes,
+    is_e_tag_mismatch,
+    server_side_encryption,
+    sse_kms_key_id,
+    tag_present,
+    restore_expiry_date,
+    is_sequencer_conflict,
     -- This is used to simplify re-constructing the FlatS3EventMessages in the Lambda. I.e. this update detected an
     -- out of order created event, so return a created event back.
     'Created'::event_type as "event_type"
@@ -103,6 +103,12 @@ select
     archive_status,
     is_current_state,
     attributes,
+    is_e_tag_mismatch,
+    server_side_encryption,
+    sse_kms_key_id,
+    tag_present,
+    restore_expiry_date,
+    is_sequencer_conflict,
     -- This is used to simplify re-constructing the FlatS3EventMessages in the Lambda. I.e. this update detected an
     -- out of order deleted event, so return a deleted event back.
     'Deleted'::event_type as "event_type"
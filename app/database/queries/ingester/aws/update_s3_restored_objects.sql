@@ -0,0 +1,35 @@
+-- Update the current record of objects which have had a restore complete or expire, rather than inserting new
+-- events. A completed restore clears the archive status and sets the restore expiry date, and an expired restore
+-- clears the restore expiry date. The reason is updated so that `is_accessible` reflects the restore.
+
+-- Unnest input.
+with input as (
+    select
+        *
+    from unnest(
+        $1::text[],
+        $2::text[],
+        $3::text[],
+        $4::reason[],
+        $5::timestamptz[]
+    ) as input (
+        bucket,
+        key,
+        version_id,
+        reason,
+        restore_expiry_date
+    )
+)
+update s3_object
+set
+    reason = input.reason,
+    archive_status = case when input.reason = 'Restored' then null else s3_object.archive_status end,
+    restore_expiry_date = case when input.reason = 'Restored' then input.restore_expiry_date else null end
+from input
+where
+    input.bucket = s3_object.bucket and
+    input.key = s3_object.key and
+    input.version_id = s3_object.version_id and
+    s3_object.is_current_state = true and
+    s3_object.event_type = 'Created'
+returning s3_object.bucket, s3_object.key, s3_object.version_id;
@@ -122,7 +122,10 @@ async fn main() -> Result<()> {
     }
 
     if args.migrate {
-        Migration::new(client).migrate().await?;
+        Migration::new(client)
+            .with_partitions(config.database_partitions())
+            .migrate()
+            .await?;
     }
 
     let app = router(state)?;
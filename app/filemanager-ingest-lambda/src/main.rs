@@ -1,4 +1,4 @@
-use aws_lambda_events::sqs::SqsEvent;
+use aws_lambda_events::sqs::{SqsBatchResponse, SqsEvent};
 use lambda_runtime::{Error, LambdaEvent, run, service_fn};
 
 use filemanager::clients::aws::s3::Client;
@@ -16,7 +16,7 @@ async fn main() -> Result<(), Error> {
     run(service_fn(|event: LambdaEvent<SqsEvent>| async move {
         update_credentials(options, config).await?;
 
-        ingest_event(
+        let (_, response) = ingest_event(
             event.payload,
             Client::with_defaults().await,
             DbClient::new(options.clone()),
@@ -24,7 +24,7 @@ async fn main() -> Result<(), Error> {
         )
         .await?;
 
-        Ok::<(), Error>(())
+        Ok::<SqsBatchResponse, Error>(response)
     }))
     .await
 }
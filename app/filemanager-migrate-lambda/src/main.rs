@@ -32,6 +32,7 @@ async fn main() -> Result<(), Error> {
 
                     Ok::<_, Error>(
                         Migration::new(DbClient::new(options.clone()))
+                            .with_partitions(config.database_partitions())
                             .migrate()
                             .await?,
                     )
@@ -62,6 +63,7 @@ async fn main() -> Result<(), Error> {
                         if let StackStatus::UpdateInProgress = status {
                             return Ok::<_, Error>(
                                 Migration::new(DbClient::new(options.clone()))
+                                    .with_partitions(config.database_partitions())
                                     .migrate()
                                     .await?,
                             );
@@ -2,21 +2,41 @@
 //!
 
 use std::result;
+use std::sync::Arc;
 
 use aws_sdk_s3 as s3;
 use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::abort_multipart_upload::{
+    AbortMultipartUploadError, AbortMultipartUploadOutput,
+};
+use aws_sdk_s3::operation::complete_multipart_upload::{
+    CompleteMultipartUploadError, CompleteMultipartUploadOutput,
+};
+use aws_sdk_s3::operation::create_multipart_upload::{
+    CreateMultipartUploadError, CreateMultipartUploadOutput,
+};
+use aws_sdk_s3::operation::get_bucket_location::{GetBucketLocationError, GetBucketLocationOutput};
 use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+use aws_sdk_s3::operation::get_object_attributes::{
+    GetObjectAttributesError, GetObjectAttributesOutput,
+};
 use aws_sdk_s3::operation::get_object_tagging::{GetObjectTaggingError, GetObjectTaggingOutput};
 use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
 use aws_sdk_s3::operation::list_buckets::{ListBucketsError, ListBucketsOutput};
 use aws_sdk_s3::operation::list_object_versions::{
     ListObjectVersionsError, ListObjectVersionsOutput,
 };
+use aws_sdk_s3::operation::put_object::{PutObjectError, PutObjectOutput};
 use aws_sdk_s3::operation::put_object_tagging::{PutObjectTaggingError, PutObjectTaggingOutput};
+use aws_sdk_s3::operation::upload_part::{UploadPartError, UploadPartOutput};
 use aws_sdk_s3::presigning::{PresignedRequest, PresigningConfig};
+use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::types::ChecksumMode::Enabled;
-use aws_sdk_s3::types::{OptionalObjectAttributes, Tagging};
+use aws_sdk_s3::types::{
+    CompletedMultipartUpload, CompletedPart, ObjectAttributes, OptionalObjectAttributes, Tagging,
+};
 use chrono::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
 
 use crate::clients::aws::config::Config;
 use crate::events::aws::message::default_version_id;
@@ -26,10 +46,25 @@ pub const MAX_LIST_ITERATIONS: usize = 1000000;
 
 pub type Result<T, E> = result::Result<T, SdkError<E>>;
 
+/// The result of a paginated `ListObjectVersions` operation which may have failed part-way.
+#[derive(Debug)]
+pub struct PartialListObjects {
+    /// The merged output of all pages that were successfully fetched.
+    pub output: ListObjectVersionsOutput,
+    /// The error of the page that failed, if any.
+    pub error: Option<SdkError<ListObjectVersionsError>>,
+    /// The key marker of the page that failed, or the next page if listing was stopped.
+    pub key_marker: Option<String>,
+    /// The version id marker of the page that failed, or the next page if listing was stopped.
+    pub version_id_marker: Option<String>,
+}
+
 /// A wrapper around an S3 client which can be mocked.
 #[derive(Debug, Clone)]
 pub struct Client {
     inner: s3::Client,
+    default_version_id: String,
+    semaphore: Option<Arc<Semaphore>>,
 }
 
 /// Override settings related to response headers.
@@ -73,7 +108,51 @@ impl ResponseHeaders {
 impl Client {
     /// Create a new S3 client.
     pub fn new(inner: s3::Client) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            default_version_id: default_version_id(),
+            semaphore: None,
+        }
+    }
+
+    /// Limit the number of concurrent requests made using this client. The limit is shared
+    /// with all clones of the client, so it bounds requests across all operations that use it.
+    pub fn with_max_concurrency(mut self, max_concurrency: Option<usize>) -> Self {
+        self.semaphore = max_concurrency.map(|max| Arc::new(Semaphore::new(max)));
+        self
+    }
+
+    /// Get the number of requests that can currently be made before the concurrency limit is
+    /// reached, if there is a limit.
+    pub fn available_concurrency(&self) -> Option<usize> {
+        self.semaphore
+            .as_ref()
+            .map(|semaphore| semaphore.available_permits())
+    }
+
+    /// Wait until a request can be made within the concurrency limit. The returned permit
+    /// should be held until the request completes.
+    async fn permit(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.semaphore {
+            Some(semaphore) => semaphore.acquire().await.ok(),
+            None => None,
+        }
+    }
+
+    /// Set the version id that represents an unversioned object.
+    pub fn with_default_version_id(mut self, default_version_id: impl Into<String>) -> Self {
+        self.default_version_id = default_version_id.into();
+        self
+    }
+
+    /// Get the version id that represents an unversioned object.
+    pub fn default_version_id(&self) -> &str {
+        &self.default_version_id
+    }
+
+    /// Get the region that this client is configured for, if it is known.
+    pub fn region(&self) -> Option<&str> {
+        self.inner.config().region().map(|region| region.as_ref())
     }
 
     /// Create an S3 client with default config.
@@ -83,29 +162,107 @@ impl Client {
 
     /// Execute the `ListBuckets` operation.
     pub async fn list_buckets(&self) -> Result<ListBucketsOutput, ListBucketsError> {
+        let _permit = self.permit().await;
         self.inner.list_buckets().send().await
     }
 
+    /// Execute a single `ListObjectVersions` operation starting from the key and version id
+    /// markers. The next markers in the output can be used to fetch the following page, which
+    /// allows callers to page through large prefixes without buffering all records. If a
+    /// delimiter is set, keys which contain it after the prefix are grouped into common prefixes.
+    pub async fn list_objects_page(
+        &self,
+        bucket: &str,
+        prefix: Option<String>,
+        delimiter: Option<String>,
+        key_marker: Option<String>,
+        version_id_marker: Option<String>,
+        max_keys: Option<i32>,
+    ) -> Result<ListObjectVersionsOutput, ListObjectVersionsError> {
+        let _permit = self.permit().await;
+        self.inner
+            .list_object_versions()
+            .bucket(bucket)
+            .set_prefix(prefix)
+            .set_delimiter(delimiter)
+            .set_version_id_marker(version_id_marker)
+            .set_key_marker(key_marker)
+            .set_max_keys(max_keys)
+            .optional_object_attributes(OptionalObjectAttributes::RestoreStatus)
+            .send()
+            .await
+    }
+
     /// Execute the `ListObjectVersions` operation, and handle pagination to produce all possible
     /// records.
     pub async fn list_objects(
         &self,
         bucket: &str,
         prefix: Option<String>,
+        delimiter: Option<String>,
     ) -> Result<ListObjectVersionsOutput, ListObjectVersionsError> {
-        let list = |key_marker, version_id_marker| async {
-            self.inner
-                .list_object_versions()
-                .bucket(bucket)
-                .set_prefix(prefix.clone())
-                .set_version_id_marker(version_id_marker)
-                .set_key_marker(key_marker)
-                .optional_object_attributes(OptionalObjectAttributes::RestoreStatus)
-                .send()
-                .await
+        let list = self
+            .list_objects_partial(
+                bucket,
+                prefix,
+                delimiter,
+                None,
+                None,
+                |_| {},
+                || async { false },
+            )
+            .await;
+
+        match list.error {
+            Some(err) => Err(err),
+            None => Ok(list.output),
+        }
+    }
+
+    /// Execute the `ListObjectVersions` operation, handling pagination starting from the key and
+    /// version id markers. Unlike `list_objects`, if a page fails part-way through, the pages that
+    /// were successfully fetched are returned along with the error and the markers of the failed
+    /// page, so that listing can be retried from where it stopped. The `should_stop` condition is
+    /// awaited between pages, and listing stops without an error once it is true, returning the
+    /// markers of the next page to resume from. For example, this can be used to stop listing
+    /// after a deadline. `on_page` is called with each page once it is fetched, after the
+    /// concurrency permit is released.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list_objects_partial<F: Future<Output = bool>>(
+        &self,
+        bucket: &str,
+        prefix: Option<String>,
+        delimiter: Option<String>,
+        key_marker: Option<String>,
+        version_id_marker: Option<String>,
+        on_page: impl Fn(&ListObjectVersionsOutput),
+        should_stop: impl Fn() -> F,
+    ) -> PartialListObjects {
+        let list = |key_marker, version_id_marker| {
+            self.list_objects_page(
+                bucket,
+                prefix.clone(),
+                delimiter.clone(),
+                key_marker,
+                version_id_marker,
+                None,
+            )
         };
 
-        let mut result = list(None, None).await?;
+        let mut result = match list(key_marker.clone(), version_id_marker.clone()).await {
+            Ok(result) => {
+                on_page(&result);
+                result
+            }
+            Err(err) => {
+                return PartialListObjects {
+                    output: ListObjectVersionsOutput::builder().build(),
+                    error: Some(err),
+                    key_marker,
+                    version_id_marker,
+                };
+            }
+        };
 
         for _ in 0..MAX_LIST_ITERATIONS {
             if !result
@@ -115,7 +272,32 @@ impl Client {
                 break;
             }
 
-            let mut next = list(result.next_key_marker, result.next_version_id_marker).await?;
+            let (key_marker, version_id_marker) = (
+                result.next_key_marker.clone(),
+                result.next_version_id_marker.clone(),
+            );
+            if should_stop().await {
+                return PartialListObjects {
+                    output: result,
+                    error: None,
+                    key_marker,
+                    version_id_marker,
+                };
+            }
+            let mut next = match list(key_marker.clone(), version_id_marker.clone()).await {
+                Ok(next) => {
+                    on_page(&next);
+                    next
+                }
+                Err(err) => {
+                    return PartialListObjects {
+                        output: result,
+                        error: Some(err),
+                        key_marker,
+                        version_id_marker,
+                    };
+                }
+            };
 
             next.versions
                 .get_or_insert_default()
@@ -132,11 +314,16 @@ impl Client {
             result = next;
         }
 
-        Ok(result)
+        PartialListObjects {
+            output: result,
+            error: None,
+            key_marker: None,
+            version_id_marker: None,
+        }
     }
 
-    fn get_version_id(version_id: &str) -> Option<String> {
-        if version_id == default_version_id() {
+    fn get_version_id(&self, version_id: &str) -> Option<String> {
+        if version_id == self.default_version_id {
             None
         } else {
             Some(version_id.to_string())
@@ -150,12 +337,31 @@ impl Client {
         bucket: &str,
         version_id: &str,
     ) -> Result<HeadObjectOutput, HeadObjectError> {
+        let _permit = self.permit().await;
         self.inner
             .head_object()
             .checksum_mode(Enabled)
             .key(key)
             .bucket(bucket)
-            .set_version_id(Self::get_version_id(version_id))
+            .set_version_id(self.get_version_id(version_id))
+            .send()
+            .await
+    }
+
+    /// Execute the `HeadObject` operation without requesting checksums. This can be used if the
+    /// object is archived and checksum retrieval fails with `InvalidObjectState`.
+    pub async fn head_object_without_checksum(
+        &self,
+        key: &str,
+        bucket: &str,
+        version_id: &str,
+    ) -> Result<HeadObjectOutput, HeadObjectError> {
+        let _permit = self.permit().await;
+        self.inner
+            .head_object()
+            .key(key)
+            .bucket(bucket)
+            .set_version_id(self.get_version_id(version_id))
             .send()
             .await
     }
@@ -167,16 +373,69 @@ impl Client {
         bucket: &str,
         version_id: &str,
     ) -> Result<GetObjectOutput, GetObjectError> {
+        let _permit = self.permit().await;
         self.inner
             .get_object()
             .checksum_mode(Enabled)
             .key(key)
             .bucket(bucket)
-            .set_version_id(Self::get_version_id(version_id))
+            .set_version_id(self.get_version_id(version_id))
+            .send()
+            .await
+    }
+
+    /// Execute the `GetObjectAttributes` operation, fetching a page of at most `max_parts` parts
+    /// of a multipart object, starting after the `part_number_marker`.
+    pub async fn get_object_parts(
+        &self,
+        key: &str,
+        bucket: &str,
+        version_id: &str,
+        max_parts: i32,
+        part_number_marker: Option<String>,
+    ) -> Result<GetObjectAttributesOutput, GetObjectAttributesError> {
+        let _permit = self.permit().await;
+        self.inner
+            .get_object_attributes()
+            .object_attributes(ObjectAttributes::ObjectParts)
+            .key(key)
+            .bucket(bucket)
+            .set_version_id(self.get_version_id(version_id))
+            .max_parts(max_parts)
+            .set_part_number_marker(part_number_marker)
+            .send()
+            .await
+    }
+
+    /// Execute the `GetObjectAttributes` operation, fetching the checksum and the number of parts
+    /// of the object.
+    pub async fn get_object_checksum(
+        &self,
+        key: &str,
+        bucket: &str,
+        version_id: &str,
+    ) -> Result<GetObjectAttributesOutput, GetObjectAttributesError> {
+        let _permit = self.permit().await;
+        self.inner
+            .get_object_attributes()
+            .object_attributes(ObjectAttributes::Checksum)
+            .object_attributes(ObjectAttributes::ObjectParts)
+            .key(key)
+            .bucket(bucket)
+            .set_version_id(self.get_version_id(version_id))
             .send()
             .await
     }
 
+    /// Execute the `GetBucketLocation` operation.
+    pub async fn get_bucket_location(
+        &self,
+        bucket: &str,
+    ) -> Result<GetBucketLocationOutput, GetBucketLocationError> {
+        let _permit = self.permit().await;
+        self.inner.get_bucket_location().bucket(bucket).send().await
+    }
+
     /// Execute the `GetObjectTagging` operation.
     pub async fn get_object_tagging(
         &self,
@@ -184,11 +443,12 @@ impl Client {
         bucket: &str,
         version_id: &str,
     ) -> Result<GetObjectTaggingOutput, GetObjectTaggingError> {
+        let _permit = self.permit().await;
         self.inner
             .get_object_tagging()
             .key(key)
             .bucket(bucket)
-            .set_version_id(Self::get_version_id(version_id))
+            .set_version_id(self.get_version_id(version_id))
             .send()
             .await
     }
@@ -201,16 +461,114 @@ impl Client {
         version_id: &str,
         tagging: Tagging,
     ) -> Result<PutObjectTaggingOutput, PutObjectTaggingError> {
+        let _permit = self.permit().await;
         self.inner
             .put_object_tagging()
             .key(key)
             .bucket(bucket)
-            .set_version_id(Self::get_version_id(version_id))
+            .set_version_id(self.get_version_id(version_id))
             .tagging(tagging)
             .send()
             .await
     }
 
+    /// Execute the `PutObject` operation.
+    pub async fn put_object(
+        &self,
+        key: &str,
+        bucket: &str,
+        body: ByteStream,
+        content_type: &str,
+    ) -> Result<PutObjectOutput, PutObjectError> {
+        let _permit = self.permit().await;
+        self.inner
+            .put_object()
+            .key(key)
+            .bucket(bucket)
+            .content_type(content_type)
+            .body(body)
+            .send()
+            .await
+    }
+
+    /// Execute the `CreateMultipartUpload` operation.
+    pub async fn create_multipart_upload(
+        &self,
+        key: &str,
+        bucket: &str,
+        content_type: &str,
+    ) -> Result<CreateMultipartUploadOutput, CreateMultipartUploadError> {
+        let _permit = self.permit().await;
+        self.inner
+            .create_multipart_upload()
+            .key(key)
+            .bucket(bucket)
+            .content_type(content_type)
+            .send()
+            .await
+    }
+
+    /// Execute the `UploadPart` operation.
+    pub async fn upload_part(
+        &self,
+        key: &str,
+        bucket: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: ByteStream,
+    ) -> Result<UploadPartOutput, UploadPartError> {
+        let _permit = self.permit().await;
+        self.inner
+            .upload_part()
+            .key(key)
+            .bucket(bucket)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(body)
+            .send()
+            .await
+    }
+
+    /// Execute the `CompleteMultipartUpload` operation with the uploaded parts.
+    pub async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        bucket: &str,
+        upload_id: &str,
+        parts: Vec<CompletedPart>,
+    ) -> Result<CompleteMultipartUploadOutput, CompleteMultipartUploadError> {
+        let _permit = self.permit().await;
+        self.inner
+            .complete_multipart_upload()
+            .key(key)
+            .bucket(bucket)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await
+    }
+
+    /// Execute the `AbortMultipartUpload` operation.
+    pub async fn abort_multipart_upload(
+        &self,
+        key: &str,
+        bucket: &str,
+        upload_id: &str,
+    ) -> Result<AbortMultipartUploadOutput, AbortMultipartUploadError> {
+        let _permit = self.permit().await;
+        self.inner
+            .abort_multipart_upload()
+            .key(key)
+            .bucket(bucket)
+            .upload_id(upload_id)
+            .send()
+            .await
+    }
+
     /// Execute the `GetObject` operation and generate a presigned url for the object.
     pub async fn presign_url(
         &self,
@@ -220,6 +578,7 @@ impl Client {
         response_headers: ResponseHeaders,
         expires_in: Duration,
     ) -> Result<PresignedRequest, GetObjectError> {
+        let _permit = self.permit().await;
         self.inner
             .get_object()
             .response_content_disposition(response_headers.content_disposition)
@@ -239,3 +598,67 @@ impl Client {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingOutput;
+    use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+    use aws_smithy_mocks::{RuleMode, mock, mock_client};
+    use tokio::time::{Duration, timeout};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn max_concurrency() {
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::head_object)
+                    .then_output(|| HeadObjectOutput::builder().build()),
+                mock!(aws_sdk_s3::Client::get_object_tagging).then_output(|| {
+                    GetObjectTaggingOutput::builder()
+                        .set_tag_set(Some(vec![]))
+                        .build()
+                        .unwrap()
+                }),
+            ]
+        ))
+        .with_max_concurrency(Some(2));
+        let other = client.clone();
+
+        // Different operations on clones of the client share the same limit, so requests wait
+        // while all permits are held.
+        let first = client.permit().await;
+        let second = other.permit().await;
+        assert_eq!(client.available_concurrency(), Some(0));
+        assert!(
+            timeout(
+                Duration::from_secs(1),
+                client.head_object("key", "bucket", "null")
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            timeout(
+                Duration::from_secs(1),
+                other.get_object_tagging("key", "bucket", "null")
+            )
+            .await
+            .is_err()
+        );
+
+        drop(first);
+        assert!(client.head_object("key", "bucket", "null").await.is_ok());
+        assert!(
+            other
+                .get_object_tagging("key", "bucket", "null")
+                .await
+                .is_ok()
+        );
+
+        drop(second);
+        assert_eq!(client.available_concurrency(), Some(2));
+    }
+}
@@ -45,7 +45,13 @@ impl Client {
     pub async fn send_message(
         &self,
         queue_url: &str,
+        message_body: &str,
     ) -> Result<SendMessageOutput, SendMessageError> {
-        self.inner.send_message().queue_url(queue_url).send().await
+        self.inner
+            .send_message()
+            .queue_url(queue_url)
+            .message_body(message_body)
+            .send()
+            .await
     }
 }
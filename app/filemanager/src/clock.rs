@@ -0,0 +1,40 @@
+//! Logic for getting the current time.
+//!
+
+use std::fmt::Debug;
+
+use chrono::{DateTime, Utc};
+
+/// A source of the current time. This is used when the filemanager generates event times
+/// itself, such as for crawl events, so that a fixed time can be supplied for testing.
+pub trait Clock: Debug + Send + Sync {
+    /// Get the current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// A clock that uses the system time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that always returns the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(DateTime<Utc>);
+
+impl FixedClock {
+    /// Create a new fixed clock.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self(now)
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
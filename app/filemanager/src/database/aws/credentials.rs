@@ -119,6 +119,18 @@ impl IamGenerator {
 
     /// Generate an RDS IAM token which can be used as the password to connect to the RDS database.
     pub async fn generate_iam_token(&self) -> Result<String> {
+        self.generate_iam_token_for(&self.host, self.port, &self.user)
+            .await
+    }
+
+    /// Generate an RDS IAM token for another host, port and user using the same credentials.
+    /// This can be used to connect to a read-replica of the RDS database.
+    pub async fn generate_iam_token_for(
+        &self,
+        host: &str,
+        port: u16,
+        user: &str,
+    ) -> Result<String> {
         let identity = self
             .config
             .provide_credentials()
@@ -144,10 +156,7 @@ impl IamGenerator {
             .build()
             .map_err(|err| CredentialGeneratorError(err.to_string()))?;
 
-        let url = format!(
-            "https://{}:{}/?Action=connect&DBUser={}",
-            self.host, self.port, self.user
-        );
+        let url = format!("https://{}:{}/?Action=connect&DBUser={}", host, port, user);
 
         let signable_request = SignableRequest::new("GET", &url, empty(), SignableBody::Bytes(&[]))
             .map_err(|err| CredentialGeneratorError(err.to_string()))?;
@@ -170,6 +179,10 @@ impl CredentialGenerator for IamGenerator {
     async fn generate_password(&self) -> Result<String> {
         self.generate_iam_token().await
     }
+
+    async fn generate_password_for(&self, host: &str, port: u16, user: &str) -> Result<String> {
+        self.generate_iam_token_for(host, port, user).await
+    }
 }
 
 #[cfg(test)]
@@ -1,10 +1,13 @@
 //! This module handles logic associated with event ingestion.
 //!
 
-use sqlx::{PgConnection, query};
+use itertools::Itertools;
+use sqlx::{PgConnection, query, query_as};
 use tracing::debug;
+use uuid::Uuid;
 
 use crate::database::aws::query::Query;
+use crate::database::entities::sea_orm_active_enums::Reason;
 use crate::database::{Client, CredentialGenerator};
 use crate::env::Config;
 use crate::error::Error::ParseError;
@@ -28,7 +31,7 @@ impl Ingester {
 
     /// Create a new ingester with a default database client.
     pub async fn with_defaults(
-        generator: Option<impl CredentialGenerator>,
+        generator: Option<impl CredentialGenerator + Sync>,
         config: &Config,
     ) -> Result<Self> {
         Ok(Self::new(Client::from_generator(generator, config).await?))
@@ -133,6 +136,8 @@ impl Ingester {
 
     pub(crate) async fn ingest_query(
         events: &TransposedS3EventMessages,
+        uncounted_duplicate_reasons: &[Reason],
+        resolve_sequencer_conflicts: bool,
         conn: &mut PgConnection,
     ) -> Result<()> {
         query(include_str!(
@@ -155,19 +160,81 @@ impl Ingester {
         .bind(&events.event_types)
         .bind(&events.ingest_ids)
         .bind(&events.attributes)
+        .bind(&events.is_e_tag_mismatches)
+        .bind(&events.server_side_encryptions)
+        .bind(&events.sse_kms_key_ids)
+        .bind(&events.tag_presents)
+        .bind(&events.restore_expiry_dates)
+        .bind(&events.is_sequencer_conflicts)
+        .bind(uncounted_duplicate_reasons)
+        .bind(resolve_sequencer_conflicts)
         .fetch_all(conn)
         .await?;
 
         Ok(())
     }
 
+    /// Update the current records of objects with restore completed or restore expired events.
+    /// This returns the events which did not have a current record to update, which should be
+    /// inserted instead.
+    pub(crate) async fn update_restored(
+        restored: FlatS3EventMessages,
+        conn: &mut PgConnection,
+    ) -> Result<FlatS3EventMessages> {
+        if restored.0.is_empty() {
+            return Ok(restored);
+        }
+
+        let events = TransposedS3EventMessages::from(restored);
+        let updated: Vec<(String, String, String)> = query_as(include_str!(
+            "../../../../database/queries/ingester/aws/update_s3_restored_objects.sql"
+        ))
+        .bind(&events.buckets)
+        .bind(&events.keys)
+        .bind(&events.version_ids)
+        .bind(&events.reasons)
+        .bind(&events.restore_expiry_dates)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        debug!(?updated, "updated current records for restore events");
+
+        Ok(FlatS3EventMessages(
+            FlatS3EventMessages::from(events)
+                .into_inner()
+                .into_iter()
+                .filter(|event| {
+                    !updated.iter().any(|(bucket, key, version_id)| {
+                        &event.bucket == bucket
+                            && &event.key == key
+                            && &event.version_id == version_id
+                    })
+                })
+                .collect(),
+        ))
+    }
+
     /// Ingest the events into the database by calling the insert and update queries.
+    ///
+    /// After inserting, `is_current_state` is re-derived for all the affected buckets and keys
+    /// from their full sequencer history, rather than incrementally from the inserted events.
+    /// This means that backfilling historical events out of order results in the correct
+    /// current state.
+    ///
+    /// Restore completed and restore expired events update the existing current record rather
+    /// than inserting new events. They are only inserted if there is no current record.
     pub async fn ingest_events(self, events: TransposedS3EventMessages) -> Result<()> {
         let mut tx = self.client().pool().begin().await?;
 
         let query = Query::new(self.client.clone());
 
-        let mut events = events;
+        let (restored, events) = events.partition_by(|event| {
+            matches!(event.reason, Reason::Restored | Reason::RestoreExpired)
+        });
+        let mut events = events.into_inner();
+        events.extend(Self::update_restored(restored, &mut tx).await?.into_inner());
+        let mut events = TransposedS3EventMessages::from(FlatS3EventMessages(events));
+
         // If there are any null sequencers, they should be converted to proper sequencers
         // first to ensure correct event ordering.
         if events
@@ -182,7 +249,13 @@ impl Ingester {
             s3_object_ids = ?events.s3_object_ids,
             "inserting events into s3_object table"
         );
-        Self::ingest_query(&events, &mut tx).await?;
+        Self::ingest_query(
+            &events,
+            self.client.uncounted_duplicate_reasons(),
+            self.client.resolve_sequencer_conflicts(),
+            &mut tx,
+        )
+        .await?;
 
         // Reset state for records which represent the new state.
         query
@@ -194,6 +267,91 @@ impl Ingester {
         Ok(())
     }
 
+    /// Get the incremented value of a padded sequencer which starts from the default sequencer,
+    /// i.e. the sequencers generated for null values without a previous AWS-native sequencer.
+    fn default_padded_value(sequencer: &str) -> Option<u64> {
+        let right = sequencer.strip_prefix(&format!("{}-", Self::default_sequencer()))?;
+        let decoded = hex::decode(right).ok()?;
+
+        Some(u64::from_le_bytes(decoded.try_into().ok()?))
+    }
+
+    /// Regenerate sequencers for all records which have a null sequencer, which can exist from
+    /// older ingestion logic. Sequencers are generated in the same way as a crawl, by padding the
+    /// default sequencer so that they order before any AWS-native sequencer. Null-sequencer
+    /// records within a bucket, key and version_id are ordered by event time and then by
+    /// `s3_object_id`, and are assigned successive values starting after any padded default
+    /// sequencer that already exists for that group, so the same data always produces the same
+    /// sequencers. `is_current_state` is re-derived for the affected buckets and keys. Only
+    /// records in the `buckets` are normalized if they are set. This happens in a single
+    /// transaction, and returns the number of records updated.
+    pub async fn normalize_null_sequencers(&self, buckets: Option<&[String]>) -> Result<u64> {
+        let mut tx = self.client().pool().begin().await?;
+        let query = Query::new(self.client.clone());
+
+        let null_sequencers: Vec<(Uuid, String, String, String)> = query_as(include_str!(
+            "../../../../database/queries/ingester/aws/select_null_sequencers.sql"
+        ))
+        .bind(buckets)
+        .fetch_all(&mut *tx)
+        .await?;
+        if null_sequencers.is_empty() {
+            return Ok(0);
+        }
+
+        let (buckets, keys, version_ids): (Vec<_>, Vec<_>, Vec<_>) = null_sequencers
+            .iter()
+            .map(|(_, bucket, key, version_id)| (bucket.clone(), key.clone(), version_id.clone()))
+            .unique()
+            .multiunzip();
+        let existing = query
+            .select_all_by_bucket_key(&mut tx, &buckets, &keys, &version_ids)
+            .await?;
+
+        let mut ids = Vec::with_capacity(null_sequencers.len());
+        let mut sequencers = Vec::with_capacity(null_sequencers.len());
+        for group in null_sequencers.chunk_by(|a, b| a.1 == b.1 && a.2 == b.2 && a.3 == b.3) {
+            let (_, bucket, key, version_id) = &group[0];
+
+            // Start after the greatest generated sequencer so that values do not collide.
+            let mut current_sequencer = existing
+                .0
+                .iter()
+                .filter(|record| {
+                    &record.bucket == bucket
+                        && &record.key == key
+                        && &record.version_id == version_id
+                })
+                .filter_map(|record| record.sequencer.clone())
+                .filter_map(|sequencer| Some((Self::default_padded_value(&sequencer)?, sequencer)))
+                .max()
+                .map(|(_, sequencer)| sequencer);
+
+            for (s3_object_id, _, _, _) in group {
+                let sequencer = Self::increment_sequencer(current_sequencer)?;
+                ids.push(*s3_object_id);
+                sequencers.push(sequencer.clone());
+                current_sequencer = Some(sequencer);
+            }
+        }
+
+        let updated: Vec<(Uuid,)> = query_as(include_str!(
+            "../../../../database/queries/ingester/aws/update_null_sequencers.sql"
+        ))
+        .bind(&ids)
+        .bind(&sequencers)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        query.reset_current_state(&mut tx, buckets, keys).await?;
+
+        tx.commit().await?;
+
+        debug!(n_records = updated.len(), "normalized null sequencers");
+
+        Ok(updated.len() as u64)
+    }
+
     /// Get a reference to the database client.
     pub fn client(&self) -> &Client {
         &self.client
@@ -202,8 +360,9 @@ impl Ingester {
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use chrono::{DateTime, Utc};
+    use chrono::{DateTime, Days, Utc};
     use itertools::Itertools;
+    use serde_json::{Value, json};
     use sqlx::postgres::PgRow;
     use sqlx::{Executor, PgPool, Row};
     use tokio::time::Instant;
@@ -216,12 +375,13 @@ pub(crate) mod tests {
     use crate::events::EventSourceType;
     use crate::events::EventSourceType::S3;
     use crate::events::aws::message::EventType::{Created, Deleted};
-    use crate::events::aws::message::{EventType, default_version_id};
+    use crate::events::aws::message::{EventType, Record, default_version_id};
     use crate::events::aws::tests::{
         EXPECTED_QUOTED_E_TAG, EXPECTED_SEQUENCER_CREATED_ONE, EXPECTED_SEQUENCER_CREATED_TWO,
         EXPECTED_SEQUENCER_CREATED_ZERO, EXPECTED_SEQUENCER_DELETED_ONE,
         EXPECTED_SEQUENCER_DELETED_TWO, EXPECTED_SHA256, EXPECTED_VERSION_ID,
-        expected_events_simple, expected_events_simple_delete_marker, expected_flat_events_simple,
+        expected_event_bridge_record, expected_events_simple, expected_events_simple_delete_marker,
+        expected_flat_events_simple,
     };
     use crate::events::aws::{
         FlatS3EventMessage, FlatS3EventMessages, StorageClass, TransposedS3EventMessages,
@@ -430,7 +590,9 @@ pub(crate) mod tests {
 
         // Insert a null sequencer without changing it's value to simulate an old event.
         let mut tx = pool.begin().await.unwrap();
-        Ingester::ingest_query(&events, &mut tx).await.unwrap();
+        Ingester::ingest_query(&events, &[], false, &mut tx)
+            .await
+            .unwrap();
         tx.commit().await.unwrap();
 
         let mut events = test_events(Some(Created));
@@ -677,6 +839,184 @@ pub(crate) mod tests {
         );
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn ingest_duplicates_uncounted_reason(pool: PgPool) {
+        let storage_class_changed = || {
+            let mut events = test_events(Some(Created));
+            events.storage_classes[0] = Some(StorageClass::Glacier);
+            events.reasons[0] = Reason::StorageClassChanged;
+            events
+        };
+
+        // By default, a storage class change with the same sequencer is counted as a duplicate.
+        let ingester = test_ingester(pool.clone());
+        ingester
+            .ingest(S3(test_events(Some(Created))))
+            .await
+            .unwrap();
+        ingester.ingest(S3(storage_class_changed())).await.unwrap();
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            1,
+            s3_object_results[0].get::<i64, _>("number_duplicate_events")
+        );
+
+        pool.execute("truncate s3_object").await.unwrap();
+
+        // It is not counted when the reason is configured as uncounted.
+        let ingester =
+            test_ingester(pool).with_uncounted_duplicate_reasons(vec![Reason::StorageClassChanged]);
+        ingester
+            .ingest(S3(test_events(Some(Created))))
+            .await
+            .unwrap();
+        ingester.ingest(S3(storage_class_changed())).await.unwrap();
+        ingester
+            .ingest(S3(test_events(Some(Created))))
+            .await
+            .unwrap();
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            1,
+            s3_object_results[0].get::<i64, _>("number_duplicate_events")
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn ingest_sequencer_conflict(pool: PgPool) {
+        let earlier = test_events(Some(Created));
+        let mut later = earlier.clone();
+        later.e_tags[0] = Some("\"later\"".to_string());
+        later.sizes[0] = Some(1);
+        later.event_times[0] = later.event_times[0].map(|time| time + Days::new(1));
+
+        // Without resolving conflicts, the first event is kept and not flagged.
+        let ingester = test_ingester(pool.clone());
+        ingester.ingest(S3(earlier.clone())).await.unwrap();
+        ingester.ingest(S3(later.clone())).await.unwrap();
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            s3_object_results[0].get::<Option<String>, _>("e_tag"),
+            Some(EXPECTED_QUOTED_E_TAG.to_string())
+        );
+        assert!(!s3_object_results[0].get::<bool, _>("is_sequencer_conflict"));
+
+        pool.execute("truncate s3_object").await.unwrap();
+
+        // Across batches, the later event is kept regardless of the ingestion order.
+        let ingester = test_ingester(pool.clone()).with_resolve_sequencer_conflicts(true);
+        ingester.ingest(S3(later.clone())).await.unwrap();
+        ingester.ingest(S3(earlier.clone())).await.unwrap();
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_sequencer_conflict(&s3_object_results[0], &later);
+        assert_eq!(
+            1,
+            s3_object_results[0].get::<i64, _>("number_duplicate_events")
+        );
+
+        pool.execute("truncate s3_object").await.unwrap();
+
+        // Within a batch, the later event is kept and flagged before ingesting.
+        let events = FlatS3EventMessages(
+            [
+                FlatS3EventMessages::from(earlier).into_inner(),
+                FlatS3EventMessages::from(later.clone()).into_inner(),
+            ]
+            .concat(),
+        )
+        .resolve_sequencer_conflicts()
+        .sort_and_dedup();
+        assert_eq!(events.0.len(), 1);
+        assert!(events.0[0].is_sequencer_conflict);
+
+        ingester.ingest(S3(events.into())).await.unwrap();
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_sequencer_conflict(&s3_object_results[0], &later);
+    }
+
+    fn assert_sequencer_conflict(row: &PgRow, expected: &TransposedS3EventMessages) {
+        assert_eq!(row.get::<Option<String>, _>("e_tag"), expected.e_tags[0]);
+        assert_eq!(row.get::<Option<i64>, _>("size"), expected.sizes[0]);
+        assert_eq!(
+            row.get::<Option<DateTime<Utc>>, _>("event_time"),
+            expected.event_times[0]
+        );
+        assert!(row.get::<bool, _>("is_sequencer_conflict"));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn ingest_restore_events(pool: PgPool) {
+        let mut events = test_events(Some(Created));
+        events.storage_classes[0] = Some(StorageClass::IntelligentTiering);
+        events.archive_statuses[0] = Some(ArchiveStatus::DeepArchiveAccess);
+
+        let ingester = test_ingester(pool);
+        ingester.ingest(S3(events)).await.unwrap();
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+        assert!(!s3_object_results[0].get::<bool, _>("is_accessible"));
+
+        let restore_event = |detail_type: &str| {
+            let mut record = expected_event_bridge_record(false);
+            record["detail-type"] = json!(detail_type);
+            record["detail"]["object"]["sequencer"] = Value::Null;
+            record["detail"]["restore-expiry-time"] = json!("1970-01-02T00:00:00Z");
+
+            let record: Record = serde_json::from_value(record).unwrap();
+            TransposedS3EventMessages::from(FlatS3EventMessages::from(record))
+        };
+
+        // A completed restore updates the existing record rather than inserting a new one.
+        ingester
+            .ingest(S3(restore_event("Object Restore Completed")))
+            .await
+            .unwrap();
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            s3_object_results[0].get::<Reason, _>("reason"),
+            Reason::Restored
+        );
+        assert_eq!(
+            s3_object_results[0].get::<Option<ArchiveStatus>, _>("archive_status"),
+            None
+        );
+        assert_eq!(
+            s3_object_results[0].get::<Option<DateTime<Utc>>, _>("restore_expiry_date"),
+            Some(DateTime::default() + Days::new(1))
+        );
+        assert!(s3_object_results[0].get::<bool, _>("is_accessible"));
+
+        // An expired restore clears the expiry date.
+        ingester
+            .ingest(S3(restore_event("Object Restore Expired")))
+            .await
+            .unwrap();
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            s3_object_results[0].get::<Reason, _>("reason"),
+            Reason::RestoreExpired
+        );
+        assert_eq!(
+            s3_object_results[0].get::<Option<DateTime<Utc>>, _>("restore_expiry_date"),
+            None
+        );
+        assert!(s3_object_results[0].get::<bool, _>("is_current_state"));
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn ingest_duplicate_except_created_event_type(pool: PgPool) {
         let ingester = test_ingester(pool);
@@ -1126,6 +1466,39 @@ pub(crate) mod tests {
         );
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn ingest_backfill_reverse_order_current_state(pool: PgPool) {
+        let ingester = test_ingester(pool);
+
+        let events_one = test_events(Some(Created));
+        let events_two = test_events(Some(Deleted));
+        let mut events_three = test_events(Some(Created));
+        events_three.sequencers[0] = Some(EXPECTED_SEQUENCER_CREATED_TWO.to_string());
+
+        // Backfill in reverse sequencer order, one batch at a time.
+        for events in [events_three, events_two, events_one] {
+            ingester.ingest(S3(events)).await.unwrap();
+        }
+
+        let s3_object_results = fetch_results_ordered(&ingester).await;
+
+        assert_eq!(s3_object_results.len(), 3);
+        assert_eq!(
+            s3_object_results
+                .iter()
+                .map(|row| (
+                    row.get::<String, _>("sequencer"),
+                    row.get::<bool, _>("is_current_state")
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (EXPECTED_SEQUENCER_CREATED_ONE.to_string(), false),
+                (EXPECTED_SEQUENCER_DELETED_ONE.to_string(), false),
+                (EXPECTED_SEQUENCER_CREATED_TWO.to_string(), true),
+            ]
+        );
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn ingest_objects_reset_current_state_versioned(pool: PgPool) {
         let ingester = test_ingester(pool);
@@ -26,7 +26,7 @@ impl IngesterPaired {
 
     /// Create a new ingester with a default database client.
     pub async fn with_defaults(
-        generator: Option<impl CredentialGenerator>,
+        generator: Option<impl CredentialGenerator + Sync>,
         config: &Config,
     ) -> Result<Self> {
         Ok(Self::new(Client::from_generator(generator, config).await?))
@@ -105,6 +105,10 @@ impl IngesterPaired {
         .bind(vec![Other; object_created.s3_object_ids.len()])
         .bind(&object_created.ingest_ids)
         .bind(&object_created.attributes)
+        .bind(&object_created.is_e_tag_mismatches)
+        .bind(&object_created.server_side_encryptions)
+        .bind(&object_created.sse_kms_key_ids)
+        .bind(&object_created.tag_presents)
         .fetch_all(&mut *tx)
         .await?;
 
@@ -129,6 +133,12 @@ impl IngesterPaired {
         .bind(vec![Other; object_created.s3_object_ids.len()])
         .bind(&object_created.ingest_ids)
         .bind(&object_created.attributes)
+        .bind(&object_created.is_e_tag_mismatches)
+        .bind(&object_created.server_side_encryptions)
+        .bind(&object_created.sse_kms_key_ids)
+        .bind(&object_created.tag_presents)
+        .bind(&object_created.is_sequencer_conflicts)
+        .bind(self.client.resolve_sequencer_conflicts())
         .fetch_all(&mut *tx)
         .await?;
 
@@ -204,7 +214,7 @@ pub(crate) mod tests {
         EXPECTED_VERSION_ID,
     };
     use crate::events::aws::{Events, FlatS3EventMessage, FlatS3EventMessages};
-    use chrono::{DateTime, Utc};
+    use chrono::{DateTime, Days, Utc};
     use itertools::Itertools;
     use sqlx::postgres::PgRow;
     use sqlx::{Executor, PgPool, Row};
@@ -354,6 +364,64 @@ pub(crate) mod tests {
         assert_ingest_events(&s3_object_results[0], EXPECTED_VERSION_ID);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn ingest_sequencer_conflict(pool: PgPool) {
+        let earlier = test_created_events();
+        let mut later = earlier.clone();
+        later.object_created.e_tags[0] = Some("\"later\"".to_string());
+        later.object_created.sizes[0] = Some(1);
+        later.object_created.event_times[0] =
+            later.object_created.event_times[0].map(|time| time + Days::new(1));
+
+        // Without resolving conflicts, the first event is kept and not flagged.
+        let ingester = test_ingester(pool.clone());
+        ingester.ingest(S3Paired(earlier.clone())).await.unwrap();
+        ingester.ingest(S3Paired(later.clone())).await.unwrap();
+
+        let s3_object_results = fetch_results(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_created(&s3_object_results[0]);
+        assert!(!s3_object_results[0].get::<bool, _>("is_sequencer_conflict"));
+
+        pool.execute("truncate s3_object").await.unwrap();
+
+        // The later event is kept regardless of the ingestion order.
+        let ingester = test_ingester(pool.clone()).with_resolve_sequencer_conflicts(true);
+        ingester.ingest(S3Paired(later.clone())).await.unwrap();
+        ingester.ingest(S3Paired(earlier.clone())).await.unwrap();
+
+        let s3_object_results = fetch_results(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            s3_object_results[0].get::<Option<String>, _>("e_tag"),
+            later.object_created.e_tags[0]
+        );
+        assert_eq!(
+            s3_object_results[0].get::<Option<i64>, _>("size"),
+            later.object_created.sizes[0]
+        );
+        assert_eq!(
+            s3_object_results[0].get::<Option<DateTime<Utc>>, _>("event_time"),
+            later.object_created.event_times[0]
+        );
+        assert!(s3_object_results[0].get::<bool, _>("is_sequencer_conflict"));
+        assert_eq!(
+            1,
+            s3_object_results[0].get::<i64, _>("number_duplicate_events")
+        );
+
+        pool.execute("truncate s3_object").await.unwrap();
+
+        // Conflicts resolved within a batch are flagged.
+        let mut events = test_created_events();
+        events.object_created.is_sequencer_conflicts[0] = true;
+        ingester.ingest(S3Paired(events)).await.unwrap();
+
+        let s3_object_results = fetch_results(&ingester).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert!(s3_object_results[0].get::<bool, _>("is_sequencer_conflict"));
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn ingest_created_multiple_object_ids(pool: PgPool) {
         let ingester = test_ingester(pool);
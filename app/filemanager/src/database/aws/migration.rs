@@ -4,7 +4,7 @@
 use async_trait::async_trait;
 use sqlx::migrate;
 use sqlx::migrate::Migrator;
-use tracing::trace;
+use tracing::{debug, trace};
 
 use crate::database::{Client, CredentialGenerator, Migrate};
 use crate::env::Config;
@@ -15,20 +15,32 @@ use crate::error::Result;
 #[derive(Debug)]
 pub struct Migration {
     client: Client,
+    partitions: Option<u32>,
 }
 
 impl Migration {
     /// Create a new migration.
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            partitions: None,
+        }
+    }
+
+    /// Partition the `s3_object` table by a hash of the bucket into the number of partitions
+    /// after migrating. This only has an effect the first time that the table is partitioned.
+    pub fn with_partitions(mut self, partitions: Option<u32>) -> Self {
+        self.partitions = partitions;
+        self
     }
 
     /// Create a new migration with a default database client.
     pub async fn with_defaults(
-        generator: Option<impl CredentialGenerator>,
+        generator: Option<impl CredentialGenerator + Sync>,
         config: &Config,
     ) -> Result<Self> {
-        Ok(Self::new(Client::from_generator(generator, config).await?))
+        Ok(Self::new(Client::from_generator(generator, config).await?)
+            .with_partitions(config.database_partitions()))
     }
 
     /// Get the underlying sqlx migrator for the migrations.
@@ -40,6 +52,23 @@ impl Migration {
     pub fn client(&self) -> &Client {
         &self.client
     }
+
+    /// Partition the `s3_object` table by a hash of the bucket, returning whether the table
+    /// was partitioned, or false if it was already partitioned.
+    pub async fn partition(&self, partitions: u32) -> Result<bool> {
+        if partitions == 0 {
+            return Err(MigrateError(
+                "the number of partitions must be greater than zero".to_string(),
+            ));
+        }
+
+        Ok(
+            sqlx::query_scalar("select partition_s3_object_by_bucket($1)")
+                .bind(i32::try_from(partitions).map_err(|err| MigrateError(err.to_string()))?)
+                .fetch_one(self.client().pool())
+                .await?,
+        )
+    }
 }
 
 #[async_trait]
@@ -49,7 +78,14 @@ impl Migrate for Migration {
         Self::migrator()
             .run(self.client().pool())
             .await
-            .map_err(|err| MigrateError(err.to_string()))
+            .map_err(|err| MigrateError(err.to_string()))?;
+
+        if let Some(partitions) = self.partitions {
+            let partitioned = self.partition(partitions).await?;
+            debug!(partitions, partitioned, "partitioning s3_object by bucket");
+        }
+
+        Ok(())
     }
 }
 
@@ -63,7 +99,14 @@ pub(crate) mod tests {
     use uuid::Uuid;
 
     use super::*;
-    use crate::database::Migrate;
+    use crate::database::entities::s3_object;
+    use crate::database::{Ingest, Migrate};
+    use crate::events::EventSourceType;
+    use crate::events::aws::message::EventType;
+    use crate::events::aws::{FlatS3EventMessage, FlatS3EventMessages, TransposedS3EventMessages};
+    use crate::queries::list::ListQueryBuilder;
+    use crate::routes::filter::S3ObjectsFilter;
+    use crate::routes::filter::wildcard::Wildcard;
     use crate::uuid::UuidGenerator;
 
     lazy_static! {
@@ -160,6 +203,84 @@ pub(crate) mod tests {
         assert!(!get_current_state(&pool, v2_deleted).await);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_partition_by_bucket(pool: PgPool) {
+        let client = Client::from_pool(pool.clone());
+        let event = |bucket: &str, key: &str, sequencer: &str, event_type: EventType| {
+            let is_current_state = event_type == EventType::Created;
+            FlatS3EventMessage::new_with_generated_id()
+                .with_bucket(bucket.to_string())
+                .with_key(key.to_string())
+                .with_sequencer(Some(sequencer.to_string()))
+                .with_event_type(event_type)
+                .with_is_current_state(is_current_state)
+        };
+        let ingest = |events: Vec<FlatS3EventMessage>| {
+            client.ingest(EventSourceType::S3(TransposedS3EventMessages::from(
+                FlatS3EventMessages(events),
+            )))
+        };
+
+        // Records that exist before partitioning are kept.
+        ingest(vec![event("bucket0", "key", "1", EventType::Created)])
+            .await
+            .unwrap();
+
+        let migration = Migration::new(client.clone());
+        assert!(migration.partition(4).await.unwrap());
+        assert!(!migration.partition(4).await.unwrap());
+
+        let buckets = (0..8).map(|i| format!("bucket{i}")).collect::<Vec<_>>();
+        ingest(
+            buckets
+                .iter()
+                .map(|bucket| event(bucket, "key", "2", EventType::Created))
+                .collect(),
+        )
+        .await
+        .unwrap();
+        // A duplicate event and a deleted event for the existing record.
+        ingest(vec![
+            event("bucket1", "key", "2", EventType::Created),
+            event("bucket0", "key", "3", EventType::Deleted),
+        ])
+        .await
+        .unwrap();
+
+        let partitions: i64 = sqlx::query_scalar("select count(distinct tableoid) from s3_object")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(partitions > 1);
+
+        let records = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+            .all()
+            .await
+            .unwrap();
+        assert_eq!(records.len(), 10);
+
+        let current = |bucket: &str| {
+            ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+                .filter_all(
+                    S3ObjectsFilter {
+                        bucket: Wildcard::new(bucket.to_string()).into(),
+                        ..Default::default()
+                    },
+                    true,
+                    true,
+                )
+                .unwrap()
+                .all()
+        };
+        assert!(current("bucket0").await.unwrap().is_empty());
+        let bucket1 = current("bucket1").await.unwrap();
+        assert_eq!(bucket1.len(), 1);
+        assert_eq!(bucket1[0].number_duplicate_events, 1);
+        for bucket in &buckets[2..] {
+            assert_eq!(current(bucket).await.unwrap().len(), 1);
+        }
+    }
+
     async fn check_table_exists(migration: &Migration, table_name: &str) -> PgRow {
         sqlx::query(&format!(
             "select exists (select from information_schema.tables where table_name = '{table_name}')"
@@ -148,7 +148,7 @@ mod tests {
         different_key_and_date.keys[0] = new_key.to_string();
         different_key_and_date.sequencers[0].clone_from(&new_sequencer);
 
-        Ingester::ingest_query(&events, &mut pool.acquire().await.unwrap())
+        Ingester::ingest_query(&events, &[], false, &mut pool.acquire().await.unwrap())
             .await
             .unwrap();
         Query::new(Client::from_pool(pool.clone()))
@@ -160,9 +160,14 @@ mod tests {
             .await
             .unwrap();
 
-        Ingester::ingest_query(&increase_date, &mut pool.acquire().await.unwrap())
-            .await
-            .unwrap();
+        Ingester::ingest_query(
+            &increase_date,
+            &[],
+            false,
+            &mut pool.acquire().await.unwrap(),
+        )
+        .await
+        .unwrap();
         Query::new(Client::from_pool(pool.clone()))
             .reset_current_state(
                 &mut pool.acquire().await.unwrap(),
@@ -172,9 +177,14 @@ mod tests {
             .await
             .unwrap();
 
-        Ingester::ingest_query(&different_key, &mut pool.acquire().await.unwrap())
-            .await
-            .unwrap();
+        Ingester::ingest_query(
+            &different_key,
+            &[],
+            false,
+            &mut pool.acquire().await.unwrap(),
+        )
+        .await
+        .unwrap();
         Query::new(Client::from_pool(pool.clone()))
             .reset_current_state(
                 &mut pool.acquire().await.unwrap(),
@@ -184,9 +194,14 @@ mod tests {
             .await
             .unwrap();
 
-        Ingester::ingest_query(&different_key_and_date, &mut pool.acquire().await.unwrap())
-            .await
-            .unwrap();
+        Ingester::ingest_query(
+            &different_key_and_date,
+            &[],
+            false,
+            &mut pool.acquire().await.unwrap(),
+        )
+        .await
+        .unwrap();
         Query::new(Client::from_pool(pool.clone()))
             .reset_current_state(
                 &mut pool.acquire().await.unwrap(),
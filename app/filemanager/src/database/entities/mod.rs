@@ -1,5 +1,7 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.19
 pub mod prelude;
 pub mod s3_crawl;
+pub mod s3_crawl_schedule;
 pub mod s3_object;
+pub mod s3_tag_retry;
 pub mod sea_orm_active_enums;
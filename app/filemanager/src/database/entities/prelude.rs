@@ -1,3 +1,5 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.19
 pub use super::s3_crawl::Entity as S3Crawl;
+pub use super::s3_crawl_schedule::Entity as S3CrawlSchedule;
 pub use super::s3_object::Entity as S3Object;
+pub use super::s3_tag_retry::Entity as S3TagRetry;
@@ -19,6 +19,9 @@ pub struct Model {
     pub prefix: Option<String>,
     pub execution_time_seconds: Option<i32>,
     pub n_objects: Option<i64>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub key_marker: Option<String>,
+    pub cancel_requested: bool,
 }
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
@@ -5,9 +5,7 @@ use super::sea_orm_active_enums::Reason;
 use super::sea_orm_active_enums::StorageClass;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
-#[derive(
-    Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, utoipa::ToSchema,
-)]
+#[derive(Clone, Debug, DeriveEntityModel, Serialize, Deserialize, utoipa::ToSchema)]
 #[sea_orm(table_name = "s3_object")]
 #[serde(rename_all = "camelCase")]
 #[schema(as = S3)]
@@ -44,7 +42,89 @@ pub struct Model {
     pub reason: Reason,
     pub archive_status: Option<ArchiveStatus>,
     pub is_accessible: bool,
+    pub is_e_tag_mismatch: bool,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub server_side_encryption: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub sse_kms_key_id: Option<String>,
+    pub tag_present: Option<bool>,
+    pub restore_expiry_date: Option<chrono::DateTime<chrono::FixedOffset>>,
+    pub is_sequencer_conflict: bool,
+    pub updated_at: chrono::DateTime<chrono::FixedOffset>,
+    pub ingested_at: Option<chrono::DateTime<chrono::FixedOffset>>,
 }
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 impl ActiveModelBehavior for ActiveModel {}
+
+/// Records are compared by their content. The `updated_at` and `ingested_at` columns are excluded
+/// because they are set by the database when a record is inserted or changed.
+impl PartialEq for Model {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            s3_object_id,
+            event_type,
+            bucket,
+            key,
+            version_id,
+            event_time,
+            size,
+            sha256,
+            last_modified_date,
+            e_tag,
+            storage_class,
+            sequencer,
+            is_delete_marker,
+            number_duplicate_events,
+            attributes,
+            deleted_date,
+            deleted_sequencer,
+            number_reordered,
+            ingest_id,
+            is_current_state,
+            reason,
+            archive_status,
+            is_accessible,
+            is_e_tag_mismatch,
+            server_side_encryption,
+            sse_kms_key_id,
+            tag_present,
+            restore_expiry_date,
+            is_sequencer_conflict,
+            updated_at: _,
+            ingested_at: _,
+        } = self;
+
+        *s3_object_id == other.s3_object_id
+            && *event_type == other.event_type
+            && *bucket == other.bucket
+            && *key == other.key
+            && *version_id == other.version_id
+            && *event_time == other.event_time
+            && *size == other.size
+            && *sha256 == other.sha256
+            && *last_modified_date == other.last_modified_date
+            && *e_tag == other.e_tag
+            && *storage_class == other.storage_class
+            && *sequencer == other.sequencer
+            && *is_delete_marker == other.is_delete_marker
+            && *number_duplicate_events == other.number_duplicate_events
+            && *attributes == other.attributes
+            && *deleted_date == other.deleted_date
+            && *deleted_sequencer == other.deleted_sequencer
+            && *number_reordered == other.number_reordered
+            && *ingest_id == other.ingest_id
+            && *is_current_state == other.is_current_state
+            && *reason == other.reason
+            && *archive_status == other.archive_status
+            && *is_accessible == other.is_accessible
+            && *is_e_tag_mismatch == other.is_e_tag_mismatch
+            && *server_side_encryption == other.server_side_encryption
+            && *sse_kms_key_id == other.sse_kms_key_id
+            && *tag_present == other.tag_present
+            && *restore_expiry_date == other.restore_expiry_date
+            && *is_sequencer_conflict == other.is_sequencer_conflict
+    }
+}
+
+impl Eq for Model {}
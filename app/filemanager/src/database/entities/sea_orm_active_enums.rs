@@ -48,6 +48,8 @@ pub enum CrawlStatus {
     Completed,
     #[sea_orm(string_value = "Failed")]
     Failed,
+    #[sea_orm(string_value = "Cancelled")]
+    Cancelled,
 }
 #[derive(
     Debug,
@@ -116,6 +118,8 @@ pub enum Reason {
     Unknown,
     #[sea_orm(string_value = "CrawlRestored")]
     CrawlRestored,
+    #[sea_orm(string_value = "CrawlDeleteMarker")]
+    CrawlDeleteMarker,
 }
 #[derive(
     Debug,
@@ -156,3 +160,28 @@ pub enum StorageClass {
     #[sea_orm(string_value = "StandardIa")]
     StandardIa,
 }
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    strum::FromRepr,
+    strum::EnumCount,
+    sqlx::Decode,
+    sqlx::Encode,
+    Hash,
+    utoipa::ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "tag_retry_status")]
+pub enum TagRetryStatus {
+    #[sea_orm(string_value = "Pending")]
+    Pending,
+    #[sea_orm(string_value = "Succeeded")]
+    Succeeded,
+    #[sea_orm(string_value = "DeadLetter")]
+    DeadLetter,
+}
@@ -6,21 +6,31 @@ use axum::http::header::AUTHORIZATION;
 use chrono::Duration;
 use envy::from_env;
 use serde::de::Error;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
 use serde_with::serde_as;
+use std::collections::HashMap;
 use std::result;
 use std::str::FromStr;
 use url::Url;
 
+use crate::database::entities::sea_orm_active_enums::Reason;
 use crate::error::Error::ConfigError;
 use crate::error::Result;
+use crate::events::aws::collecter::VersionMismatchMode;
+use crate::events::aws::message::default_version_id;
+use crate::routes::key_path::KeyPathMode;
 
 /// Configuration environment variables for filemanager.
 #[serde_as]
-#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct Config {
     pub(crate) database_url: Option<String>,
+    #[serde(rename = "filemanager_database_read_url")]
+    pub(crate) database_read_url: Option<String>,
+    #[serde(rename = "filemanager_database_partitions")]
+    pub(crate) database_partitions: Option<u32>,
     pub(crate) pgpassword: Option<String>,
     pub(crate) pghost: Option<String>,
     pub(crate) pgport: Option<u16>,
@@ -33,6 +43,34 @@ pub struct Config {
     pub(crate) ingester_track_moves: bool,
     #[serde(rename = "filemanager_ingester_tag_name")]
     pub(crate) ingester_tag_name: String,
+    #[serde(rename = "filemanager_ingester_default_version_id")]
+    pub(crate) ingester_default_version_id: String,
+    #[serde(rename = "filemanager_ingester_uncounted_duplicate_reasons")]
+    pub(crate) ingester_uncounted_duplicate_reasons: Vec<Reason>,
+    #[serde(rename = "filemanager_ingester_resolve_sequencer_conflicts")]
+    pub(crate) ingester_resolve_sequencer_conflicts: bool,
+    #[serde(
+        rename = "filemanager_ingester_default_attributes",
+        deserialize_with = "parse_default_attributes"
+    )]
+    pub(crate) ingester_default_attributes: Vec<DefaultAttributes>,
+    #[serde(rename = "filemanager_ingester_tag_attributes")]
+    pub(crate) ingester_tag_attributes: Vec<String>,
+    #[serde(rename = "filemanager_ingester_version_mismatch_mode")]
+    pub(crate) ingester_version_mismatch_mode: VersionMismatchMode,
+    #[serde(rename = "filemanager_ingester_multipart_checksum_max_parts")]
+    pub(crate) ingester_multipart_checksum_max_parts: Option<usize>,
+    #[serde(
+        rename = "filemanager_ingester_max_event_age",
+        deserialize_with = "parse_threshold"
+    )]
+    pub(crate) ingester_max_event_age: Option<Duration>,
+    #[serde(rename = "filemanager_ingester_auto_tag")]
+    pub(crate) ingester_auto_tag: bool,
+    #[serde(rename = "filemanager_ingester_tag_retry")]
+    pub(crate) ingester_tag_retry: bool,
+    #[serde(rename = "filemanager_ingester_tag_retry_attempts")]
+    pub(crate) ingester_tag_retry_attempts: u32,
     #[serde(default, rename = "filemanager_api_links_url")]
     pub(crate) api_links_url: Option<Url>,
     #[serde(
@@ -53,11 +91,125 @@ pub struct Config {
     pub(crate) api_cors_allow_headers: Vec<String>,
     #[serde(rename = "filemanager_access_key_secret_id")]
     pub(crate) access_key_secret_id: Option<String>,
+    #[serde(
+        rename = "filemanager_api_slow_query_threshold",
+        deserialize_with = "parse_threshold"
+    )]
+    pub(crate) api_slow_query_threshold: Option<Duration>,
+    #[serde(
+        rename = "filemanager_api_statement_timeout",
+        deserialize_with = "parse_threshold"
+    )]
+    pub(crate) api_statement_timeout: Option<Duration>,
+    #[serde(rename = "filemanager_api_conditional_list")]
+    pub(crate) api_conditional_list: bool,
+    #[serde(rename = "filemanager_api_standard_price_per_gb")]
+    pub(crate) api_standard_price_per_gb: f64,
+    #[serde(rename = "filemanager_api_intelligent_tiering_price_per_gb")]
+    pub(crate) api_intelligent_tiering_price_per_gb: f64,
+    #[serde(rename = "filemanager_api_glacier_price_per_gb")]
+    pub(crate) api_glacier_price_per_gb: f64,
+    #[serde(rename = "filemanager_api_enforce_attribute_types")]
+    pub(crate) api_enforce_attribute_types: bool,
+    #[serde(rename = "filemanager_api_denied_attribute_keys")]
+    pub(crate) api_denied_attribute_keys: Vec<String>,
+    #[serde(
+        rename = "filemanager_api_max_attributes_size",
+        deserialize_with = "parse_size"
+    )]
+    pub(crate) api_max_attributes_size: u64,
+    #[serde(rename = "filemanager_api_checksum_backfill")]
+    pub(crate) api_checksum_backfill: bool,
+    #[serde(rename = "filemanager_api_exclude_folder_placeholders")]
+    pub(crate) api_exclude_folder_placeholders: bool,
+    #[serde(rename = "filemanager_api_conditional_tag_writes")]
+    pub(crate) api_conditional_tag_writes: bool,
+    #[serde(rename = "filemanager_api_drift_crawl_threshold")]
+    pub(crate) api_drift_crawl_threshold: Option<f64>,
+    #[serde(
+        rename = "filemanager_api_drift_crawl_interval",
+        deserialize_with = "parse_expiry"
+    )]
+    pub(crate) api_drift_crawl_interval: Duration,
+    #[serde(
+        rename = "filemanager_api_tenant_buckets",
+        deserialize_with = "parse_tenant_buckets"
+    )]
+    pub(crate) api_tenant_buckets: HashMap<String, Vec<String>>,
+    #[serde(rename = "filemanager_api_tenant_header")]
+    pub(crate) api_tenant_header: String,
+    #[serde(rename = "filemanager_api_export_buckets")]
+    pub(crate) api_export_buckets: Vec<String>,
+    #[serde(
+        rename = "filemanager_s3_max_concurrency",
+        deserialize_with = "parse_concurrency"
+    )]
+    pub(crate) s3_max_concurrency: Option<usize>,
+    #[serde(rename = "filemanager_api_key_path_mode")]
+    pub(crate) api_key_path_mode: KeyPathMode,
+    #[serde(rename = "filemanager_crawl_flush_threshold")]
+    pub(crate) crawl_flush_threshold: Option<usize>,
+}
+
+/// Attributes which are added to new records in a bucket, optionally restricted to keys under a
+/// prefix.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DefaultAttributes {
+    pub(crate) bucket: String,
+    #[serde(default)]
+    pub(crate) prefix: Option<String>,
+    pub(crate) attributes: Map<String, Value>,
+}
+
+impl DefaultAttributes {
+    /// Create new default attributes.
+    pub fn new(bucket: String, prefix: Option<String>, attributes: Map<String, Value>) -> Self {
+        Self {
+            bucket,
+            prefix,
+            attributes,
+        }
+    }
+
+    /// Whether the default attributes apply to the bucket and key.
+    pub fn matches(&self, bucket: &str, key: &str) -> bool {
+        self.bucket == bucket
+            && self
+                .prefix
+                .as_ref()
+                .is_none_or(|prefix| key.starts_with(prefix))
+    }
+
+    /// Get the attributes.
+    pub fn attributes(&self) -> &Map<String, Value> {
+        &self.attributes
+    }
 }
 
 /// Default presigned URL expiry time, 7 days.
 pub const DEFAULT_PRESIGN_EXPIRY: Duration = Duration::days(7);
 
+/// Default monthly price per GB of the `Standard` storage class in USD.
+pub const DEFAULT_STANDARD_PRICE_PER_GB: f64 = 0.023;
+
+/// Default monthly price per GB of the `IntelligentTiering` storage class in USD. This uses the
+/// infrequent access tier price, which objects move to after 30 days without access.
+pub const DEFAULT_INTELLIGENT_TIERING_PRICE_PER_GB: f64 = 0.0125;
+
+/// Default monthly price per GB of the `Glacier` storage class in USD.
+pub const DEFAULT_GLACIER_PRICE_PER_GB: f64 = 0.0036;
+
+/// Default maximum serialized size of a record's attributes, 64 KiB.
+pub const DEFAULT_MAX_ATTRIBUTES_SIZE: u64 = 64 * 1024;
+
+/// Default minimum time after a crawl of a prefix completes before another crawl is enqueued
+/// automatically when drift is detected.
+pub const DEFAULT_DRIFT_CRAWL_INTERVAL: Duration = Duration::hours(1);
+/// Default number of times a failed tag write is retried before it is dead-lettered.
+pub const DEFAULT_TAG_RETRY_ATTEMPTS: u32 = 5;
+/// Default header which identifies the tenant of a request.
+pub const DEFAULT_TENANT_HEADER: &str = "x-tenant-id";
+
 fn parse_limit<'de, D>(deserializer: D) -> result::Result<Option<u64>, D::Error>
 where
     D: Deserializer<'de>,
@@ -68,6 +220,46 @@ where
         .map_err(Error::custom)
 }
 
+fn parse_size<'de, D>(deserializer: D) -> result::Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let str = String::deserialize(deserializer)?;
+    parse_size::parse_size(str).map_err(Error::custom)
+}
+
+fn parse_default_attributes<'de, D>(
+    deserializer: D,
+) -> result::Result<Vec<DefaultAttributes>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let str = String::deserialize(deserializer)?;
+    serde_json::from_str(&str).map_err(Error::custom)
+}
+
+fn parse_tenant_buckets<'de, D>(
+    deserializer: D,
+) -> result::Result<HashMap<String, Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let str = String::deserialize(deserializer)?;
+    serde_json::from_str(&str).map_err(Error::custom)
+}
+
+fn parse_concurrency<'de, D>(deserializer: D) -> result::Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match <Option<usize>>::deserialize(deserializer)? {
+        Some(0) => Err(Error::custom(
+            "the maximum concurrency must be greater than zero",
+        )),
+        concurrency => Ok(concurrency),
+    }
+}
+
 fn parse_expiry<'de, D>(deserializer: D) -> result::Result<Duration, D::Error>
 where
     D: Deserializer<'de>,
@@ -78,10 +270,24 @@ where
     Duration::from_std(*duration).map_err(Error::custom)
 }
 
+fn parse_threshold<'de, D>(deserializer: D) -> result::Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    <Option<String>>::deserialize(deserializer)?
+        .map(|str| {
+            let duration = humantime::Duration::from_str(&str).map_err(Error::custom)?;
+            Duration::from_std(*duration).map_err(Error::custom)
+        })
+        .transpose()
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             database_url: None,
+            database_read_url: None,
+            database_partitions: None,
             pgpassword: None,
             pghost: None,
             pgport: None,
@@ -90,6 +296,17 @@ impl Default for Config {
             paired_ingest_mode: false,
             ingester_track_moves: true,
             ingester_tag_name: "ingest_id".to_string(),
+            ingester_default_version_id: default_version_id(),
+            ingester_uncounted_duplicate_reasons: vec![],
+            ingester_resolve_sequencer_conflicts: false,
+            ingester_default_attributes: vec![],
+            ingester_tag_attributes: vec![],
+            ingester_version_mismatch_mode: VersionMismatchMode::default(),
+            ingester_multipart_checksum_max_parts: None,
+            ingester_max_event_age: None,
+            ingester_auto_tag: false,
+            ingester_tag_retry: false,
+            ingester_tag_retry_attempts: DEFAULT_TAG_RETRY_ATTEMPTS,
             api_links_url: None,
             api_presign_limit: None,
             api_presign_expiry: DEFAULT_PRESIGN_EXPIRY,
@@ -103,6 +320,26 @@ impl Default for Config {
             ],
             api_cors_allow_headers: vec![AUTHORIZATION.to_string()],
             access_key_secret_id: None,
+            api_slow_query_threshold: None,
+            api_statement_timeout: None,
+            api_conditional_list: false,
+            api_standard_price_per_gb: DEFAULT_STANDARD_PRICE_PER_GB,
+            api_intelligent_tiering_price_per_gb: DEFAULT_INTELLIGENT_TIERING_PRICE_PER_GB,
+            api_glacier_price_per_gb: DEFAULT_GLACIER_PRICE_PER_GB,
+            api_enforce_attribute_types: false,
+            api_denied_attribute_keys: vec![],
+            api_max_attributes_size: DEFAULT_MAX_ATTRIBUTES_SIZE,
+            api_checksum_backfill: false,
+            api_exclude_folder_placeholders: false,
+            api_conditional_tag_writes: false,
+            api_drift_crawl_threshold: None,
+            api_drift_crawl_interval: DEFAULT_DRIFT_CRAWL_INTERVAL,
+            api_tenant_buckets: HashMap::new(),
+            api_tenant_header: DEFAULT_TENANT_HEADER.to_string(),
+            api_export_buckets: vec![],
+            s3_max_concurrency: None,
+            api_key_path_mode: KeyPathMode::default(),
+            crawl_flush_threshold: None,
         }
     }
 }
@@ -129,6 +366,17 @@ impl Config {
         self.database_url.as_deref()
     }
 
+    /// Get the read-replica database url.
+    pub fn database_read_url(&self) -> Option<&str> {
+        self.database_read_url.as_deref()
+    }
+
+    /// Get the number of bucket hash partitions of the `s3_object` table, if it should be
+    /// partitioned when migrating.
+    pub fn database_partitions(&self) -> Option<u32> {
+        self.database_partitions
+    }
+
     /// Get the pg password.
     pub fn pg_password(&self) -> Option<&str> {
         self.pgpassword.as_deref()
@@ -169,6 +417,65 @@ impl Config {
         &self.ingester_tag_name
     }
 
+    /// Get the version id that represents an unversioned object.
+    pub fn ingester_default_version_id(&self) -> &str {
+        &self.ingester_default_version_id
+    }
+
+    /// Get the event reasons which do not increment the number of duplicate events.
+    pub fn ingester_uncounted_duplicate_reasons(&self) -> &[Reason] {
+        &self.ingester_uncounted_duplicate_reasons
+    }
+
+    /// Whether events which share a sequencer but have a different ETag or size are resolved by
+    /// keeping the event with the later event time and flagging the record as a conflict.
+    pub fn ingester_resolve_sequencer_conflicts(&self) -> bool {
+        self.ingester_resolve_sequencer_conflicts
+    }
+
+    /// Get the default attributes which are added to new records.
+    pub fn ingester_default_attributes(&self) -> &[DefaultAttributes] {
+        &self.ingester_default_attributes
+    }
+
+    /// Get the S3 tag keys which are copied into the attributes of new records.
+    pub fn ingester_tag_attributes(&self) -> &[String] {
+        &self.ingester_tag_attributes
+    }
+
+    /// Get how a `HeadObject` response with a different version id to the event is handled.
+    pub fn ingester_version_mismatch_mode(&self) -> VersionMismatchMode {
+        self.ingester_version_mismatch_mode
+    }
+
+    /// Get the maximum number of parts of a multipart object that are fetched to reconstruct
+    /// its checksum, if checksums should be reconstructed from parts.
+    pub fn ingester_multipart_checksum_max_parts(&self) -> Option<usize> {
+        self.ingester_multipart_checksum_max_parts
+    }
+
+    /// Get the maximum age of an event before it is dropped as a stale replay, if events
+    /// should be dropped based on their age.
+    pub fn ingester_max_event_age(&self) -> Option<Duration> {
+        self.ingester_max_event_age
+    }
+
+    /// Whether new objects whose existing tags could not be read are recorded so that they are
+    /// tagged with an ingest_id later, instead of being left without an ingest_id.
+    pub fn ingester_auto_tag(&self) -> bool {
+        self.ingester_auto_tag
+    }
+
+    /// Whether failed ingest_id tag writes are recorded so that they can be retried later.
+    pub fn ingester_tag_retry(&self) -> bool {
+        self.ingester_tag_retry
+    }
+
+    /// Get the number of times a failed tag write is retried before it is dead-lettered.
+    pub fn ingester_tag_retry_attempts(&self) -> u32 {
+        self.ingester_tag_retry_attempts
+    }
+
     /// Get the base URL for generating pagination links.
     pub fn api_links_url(&self) -> Option<&Url> {
         self.api_links_url.as_ref()
@@ -204,6 +511,116 @@ impl Config {
         self.access_key_secret_id.as_deref()
     }
 
+    /// Get the duration after which a query is logged as slow.
+    pub fn api_slow_query_threshold(&self) -> Option<Duration> {
+        self.api_slow_query_threshold
+    }
+
+    /// Get the statement timeout applied to API query transactions, after which a query is
+    /// cancelled.
+    pub fn api_statement_timeout(&self) -> Option<Duration> {
+        self.api_statement_timeout
+    }
+
+    /// Whether list responses include `ETag` and `Last-Modified` headers and support conditional
+    /// requests. This runs an extra aggregate query on each list request.
+    pub fn api_conditional_list(&self) -> bool {
+        self.api_conditional_list
+    }
+
+    /// Get the monthly price per GB of the `Standard` storage class.
+    pub fn api_standard_price_per_gb(&self) -> f64 {
+        self.api_standard_price_per_gb
+    }
+
+    /// Get the monthly price per GB of the `IntelligentTiering` storage class.
+    pub fn api_intelligent_tiering_price_per_gb(&self) -> f64 {
+        self.api_intelligent_tiering_price_per_gb
+    }
+
+    /// Get the monthly price per GB of the `Glacier` storage class.
+    pub fn api_glacier_price_per_gb(&self) -> f64 {
+        self.api_glacier_price_per_gb
+    }
+
+    /// Whether attribute updates should keep a consistent JSON type for each attribute key within a
+    /// bucket.
+    pub fn api_enforce_attribute_types(&self) -> bool {
+        self.api_enforce_attribute_types
+    }
+
+    /// Get the attribute keys which cannot be modified using the API.
+    pub fn api_denied_attribute_keys(&self) -> &[String] {
+        &self.api_denied_attribute_keys
+    }
+
+    /// Get the maximum serialized size in bytes of a record's attributes after an update.
+    pub fn api_max_attributes_size(&self) -> u64 {
+        self.api_max_attributes_size
+    }
+
+    /// Whether to backfill missing checksums of current records that are read through the API.
+    pub fn api_checksum_backfill(&self) -> bool {
+        self.api_checksum_backfill
+    }
+
+    /// Whether folder placeholders are excluded when listing or counting records, unless the
+    /// request sets `excludeFolderPlaceholders`.
+    pub fn api_exclude_folder_placeholders(&self) -> bool {
+        self.api_exclude_folder_placeholders
+    }
+
+    /// Whether to fetch the current tags of an object before updating its `ingestId` tag, and
+    /// skip the write if the tag already matches.
+    pub fn api_conditional_tag_writes(&self) -> bool {
+        self.api_conditional_tag_writes
+    }
+
+    /// Get the fraction of drifted objects under a prefix above which a crawl of the prefix is
+    /// enqueued automatically by the drift report.
+    pub fn api_drift_crawl_threshold(&self) -> Option<f64> {
+        self.api_drift_crawl_threshold
+    }
+
+    /// Get the minimum time after a crawl of a prefix completes before another crawl is enqueued
+    /// automatically. This is also the interval of crawl schedules created by the drift report.
+    pub fn api_drift_crawl_interval(&self) -> Duration {
+        self.api_drift_crawl_interval
+    }
+
+    /// Get the buckets that each tenant is allowed to see. If this is empty, requests are not
+    /// scoped to a tenant.
+    pub fn api_tenant_buckets(&self) -> &HashMap<String, Vec<String>> {
+        &self.api_tenant_buckets
+    }
+
+    /// Get the header which identifies the tenant of a request.
+    pub fn api_tenant_header(&self) -> &str {
+        &self.api_tenant_header
+    }
+
+    /// Get the buckets which query results can be exported to.
+    pub fn api_export_buckets(&self) -> &[String] {
+        &self.api_export_buckets
+    }
+
+    /// Get the maximum number of concurrent S3 requests, shared by all operations.
+    pub fn s3_max_concurrency(&self) -> Option<usize> {
+        self.s3_max_concurrency
+    }
+
+    /// Get how keys containing path traversal sequences are handled when they are used as paths.
+    /// See [`KeyPathMode`] for the routes that this applies to.
+    pub fn api_key_path_mode(&self) -> KeyPathMode {
+        self.api_key_path_mode
+    }
+
+    /// Get the number of crawl messages that are buffered in memory before they are ingested
+    /// in a chunk, if crawls should be ingested incrementally.
+    pub fn crawl_flush_threshold(&self) -> Option<usize> {
+        self.crawl_flush_threshold
+    }
+
     /// Get the value from an optional, or else try and get a different value, unwrapping into a Result.
     pub fn value_or_else<T>(value: Option<T>, or_else: Option<T>) -> Result<T> {
         value
@@ -227,6 +644,8 @@ mod tests {
     fn test_environment() {
         let data = vec![
             ("DATABASE_URL", "url"),
+            ("FILEMANAGER_DATABASE_READ_URL", "read_url"),
+            ("FILEMANAGER_DATABASE_PARTITIONS", "16"),
             ("PGPASSWORD", "password"),
             ("PGHOST", "host"),
             ("PGPORT", "1234"),
@@ -235,6 +654,23 @@ mod tests {
             ("FILEMANAGER_PAIRED_INGEST_MODE", "true"),
             ("FILEMANAGER_INGESTER_TRACK_MOVES", "false"),
             ("FILEMANAGER_INGESTER_TAG_NAME", "tag"),
+            ("FILEMANAGER_INGESTER_DEFAULT_VERSION_ID", "unversioned"),
+            (
+                "FILEMANAGER_INGESTER_UNCOUNTED_DUPLICATE_REASONS",
+                "StorageClassChanged,Restored",
+            ),
+            ("FILEMANAGER_INGESTER_RESOLVE_SEQUENCER_CONFLICTS", "true"),
+            (
+                "FILEMANAGER_INGESTER_DEFAULT_ATTRIBUTES",
+                r#"[{"bucket":"bucket","prefix":"project/","attributes":{"env":"dev"}}]"#,
+            ),
+            ("FILEMANAGER_INGESTER_TAG_ATTRIBUTES", "project,sampleId"),
+            ("FILEMANAGER_INGESTER_VERSION_MISMATCH_MODE", "flag"),
+            ("FILEMANAGER_INGESTER_MULTIPART_CHECKSUM_MAX_PARTS", "100"),
+            ("FILEMANAGER_INGESTER_MAX_EVENT_AGE", "30 days"),
+            ("FILEMANAGER_INGESTER_AUTO_TAG", "true"),
+            ("FILEMANAGER_INGESTER_TAG_RETRY", "true"),
+            ("FILEMANAGER_INGESTER_TAG_RETRY_ATTEMPTS", "3"),
             ("FILEMANAGER_API_LINKS_URL", "https://localhost:8000"),
             ("FILEMANAGER_API_PRESIGN_LIMIT", "1 MB"),
             ("FILEMANAGER_API_PRESIGN_EXPIRY", "12 hours"),
@@ -245,6 +681,32 @@ mod tests {
             ("FILEMANAGER_API_CORS_ALLOW_METHODS", "GET,POST"),
             ("FILEMANAGER_API_CORS_ALLOW_HEADERS", "Authorization,Accept"),
             ("FILEMANAGER_ACCESS_KEY_SECRET_ID", "id"),
+            ("FILEMANAGER_API_SLOW_QUERY_THRESHOLD", "500ms"),
+            ("FILEMANAGER_API_STATEMENT_TIMEOUT", "30s"),
+            ("FILEMANAGER_API_CONDITIONAL_LIST", "true"),
+            ("FILEMANAGER_API_STANDARD_PRICE_PER_GB", "1"),
+            ("FILEMANAGER_API_INTELLIGENT_TIERING_PRICE_PER_GB", "0.5"),
+            ("FILEMANAGER_API_GLACIER_PRICE_PER_GB", "0.25"),
+            ("FILEMANAGER_API_ENFORCE_ATTRIBUTE_TYPES", "true"),
+            (
+                "FILEMANAGER_API_DENIED_ATTRIBUTE_KEYS",
+                "ingestId,portalRunId",
+            ),
+            ("FILEMANAGER_API_MAX_ATTRIBUTES_SIZE", "1 KiB"),
+            ("FILEMANAGER_API_CHECKSUM_BACKFILL", "true"),
+            ("FILEMANAGER_API_EXCLUDE_FOLDER_PLACEHOLDERS", "true"),
+            ("FILEMANAGER_API_CONDITIONAL_TAG_WRITES", "true"),
+            ("FILEMANAGER_API_DRIFT_CRAWL_THRESHOLD", "0.5"),
+            ("FILEMANAGER_API_DRIFT_CRAWL_INTERVAL", "10 minutes"),
+            (
+                "FILEMANAGER_API_TENANT_BUCKETS",
+                r#"{"tenant":["bucket","other"]}"#,
+            ),
+            ("FILEMANAGER_API_TENANT_HEADER", "x-tenant"),
+            ("FILEMANAGER_API_EXPORT_BUCKETS", "exports,reports"),
+            ("FILEMANAGER_S3_MAX_CONCURRENCY", "10"),
+            ("FILEMANAGER_API_KEY_PATH_MODE", "canonicalize"),
+            ("FILEMANAGER_CRAWL_FLUSH_THRESHOLD", "1000"),
         ]
         .into_iter()
         .map(|(key, value)| (key.to_string(), value.to_string()));
@@ -255,6 +717,8 @@ mod tests {
             config,
             Config {
                 database_url: Some("url".to_string()),
+                database_read_url: Some("read_url".to_string()),
+                database_partitions: Some(16),
                 pgpassword: Some("password".to_string()),
                 pghost: Some("host".to_string()),
                 pgport: Some(1234),
@@ -263,6 +727,24 @@ mod tests {
                 paired_ingest_mode: true,
                 ingester_track_moves: false,
                 ingester_tag_name: "tag".to_string(),
+                ingester_default_version_id: "unversioned".to_string(),
+                ingester_uncounted_duplicate_reasons: vec![
+                    Reason::StorageClassChanged,
+                    Reason::Restored
+                ],
+                ingester_resolve_sequencer_conflicts: true,
+                ingester_default_attributes: vec![DefaultAttributes::new(
+                    "bucket".to_string(),
+                    Some("project/".to_string()),
+                    Map::from_iter([("env".to_string(), Value::from("dev"))]),
+                )],
+                ingester_tag_attributes: vec!["project".to_string(), "sampleId".to_string()],
+                ingester_version_mismatch_mode: VersionMismatchMode::Flag,
+                ingester_multipart_checksum_max_parts: Some(100),
+                ingester_max_event_age: Some(Duration::days(30)),
+                ingester_auto_tag: true,
+                ingester_tag_retry: true,
+                ingester_tag_retry_attempts: 3,
                 api_links_url: Some("https://localhost:8000".parse().unwrap()),
                 api_presign_limit: Some(1000000),
                 api_presign_expiry: Duration::hours(12),
@@ -272,11 +754,43 @@ mod tests {
                 ]),
                 api_cors_allow_methods: vec!["GET".to_string(), "POST".to_string()],
                 api_cors_allow_headers: vec!["Authorization".to_string(), "Accept".to_string()],
-                access_key_secret_id: Some("id".to_string())
+                access_key_secret_id: Some("id".to_string()),
+                api_slow_query_threshold: Some(Duration::milliseconds(500)),
+                api_statement_timeout: Some(Duration::seconds(30)),
+                api_conditional_list: true,
+                api_standard_price_per_gb: 1.0,
+                api_intelligent_tiering_price_per_gb: 0.5,
+                api_glacier_price_per_gb: 0.25,
+                api_enforce_attribute_types: true,
+                api_denied_attribute_keys: vec!["ingestId".to_string(), "portalRunId".to_string()],
+                api_max_attributes_size: 1024,
+                api_checksum_backfill: true,
+                api_exclude_folder_placeholders: true,
+                api_conditional_tag_writes: true,
+                api_drift_crawl_threshold: Some(0.5),
+                api_drift_crawl_interval: Duration::minutes(10),
+                api_tenant_buckets: HashMap::from_iter([(
+                    "tenant".to_string(),
+                    vec!["bucket".to_string(), "other".to_string()]
+                )]),
+                api_tenant_header: "x-tenant".to_string(),
+                api_export_buckets: vec!["exports".to_string(), "reports".to_string()],
+                s3_max_concurrency: Some(10),
+                api_key_path_mode: KeyPathMode::Canonicalize,
+                crawl_flush_threshold: Some(1000),
             }
         )
     }
 
+    #[test]
+    fn test_environment_zero_concurrency() {
+        let data = [("FILEMANAGER_S3_MAX_CONCURRENCY", "0")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+
+        assert!(from_iter::<_, Config>(data).is_err());
+    }
+
     #[test]
     fn test_environment_defaults() {
         let config: Config = from_iter(vec![]).unwrap();
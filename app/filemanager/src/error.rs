@@ -2,11 +2,16 @@
 //!
 
 use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::operation::complete_multipart_upload::CompleteMultipartUploadError;
+use aws_sdk_s3::operation::create_multipart_upload::CreateMultipartUploadError;
+use aws_sdk_s3::operation::get_bucket_location::GetBucketLocationError;
 use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingError;
 use aws_sdk_s3::operation::head_object::HeadObjectError;
 use aws_sdk_s3::operation::list_object_versions::ListObjectVersionsError;
+use aws_sdk_s3::operation::put_object::PutObjectError;
 use aws_sdk_s3::operation::put_object_tagging::PutObjectTaggingError;
+use aws_sdk_s3::operation::upload_part::UploadPartError;
 use aws_sdk_sqs::operation::receive_message::ReceiveMessageError;
 use aws_sdk_sqs::operation::send_message::SendMessageError;
 use sea_orm::{DbErr, RuntimeErr};
@@ -58,19 +63,39 @@ pub enum Error {
     MigrateError(String),
     #[error("Crawl error: `{0}`")]
     CrawlError(String),
+    #[error("Crawl cancelled at key marker: `{0:?}`")]
+    CrawlCancelled(Option<String>),
     #[error("Secrets manager error: `{0}`")]
     SecretsManagerError(String),
+    #[error("tenant error: `{0}`")]
+    TenantError(String),
 }
 
 impl From<sqlx::Error> for Error {
     fn from(err: sqlx::Error) -> Self {
-        Self::DatabaseError(DbErr::Query(RuntimeErr::SqlxError(err)))
+        DbErr::Query(RuntimeErr::SqlxError(err)).into()
     }
 }
 
+/// The Postgres error code for a query which was cancelled, e.g. by the statement timeout.
+const QUERY_CANCELED_CODE: &str = "57014";
+
 impl From<DbErr> for Error {
     fn from(err: DbErr) -> Self {
-        Self::DatabaseError(err)
+        let is_canceled = match &err {
+            DbErr::Query(RuntimeErr::SqlxError(err)) | DbErr::Exec(RuntimeErr::SqlxError(err)) => {
+                err.as_database_error()
+                    .and_then(|err| err.code())
+                    .is_some_and(|code| code == QUERY_CANCELED_CODE)
+            }
+            _ => false,
+        };
+
+        if is_canceled {
+            Self::QueryError(format!("query exceeded the statement timeout: {err}"))
+        } else {
+            Self::DatabaseError(err)
+        }
     }
 }
 
@@ -147,6 +172,11 @@ generate_aws_error_impl!(HeadObjectError);
 generate_aws_error_impl!(GetObjectError);
 generate_aws_error_impl!(ListObjectVersionsError);
 generate_aws_error_impl!(GetObjectTaggingError);
+generate_aws_error_impl!(GetBucketLocationError);
 generate_aws_error_impl!(PutObjectTaggingError);
+generate_aws_error_impl!(PutObjectError);
+generate_aws_error_impl!(CreateMultipartUploadError);
+generate_aws_error_impl!(UploadPartError);
+generate_aws_error_impl!(CompleteMultipartUploadError);
 generate_aws_error_impl!(ReceiveMessageError);
 generate_aws_error_impl!(SendMessageError);
@@ -3,13 +3,16 @@
 
 use crate::clients::aws::s3::Client as S3Client;
 use crate::clients::aws::sqs::Client as SQSClient;
+use crate::clock::{Clock, SystemClock};
 use crate::database;
 use crate::database::aws::ingester::Ingester;
-use crate::database::entities::s3_object;
 use crate::database::entities::sea_orm_active_enums::{ArchiveStatus, Reason};
+use crate::database::entities::{s3_object, s3_tag_retry, sea_orm_active_enums};
 use crate::env::Config;
 use crate::error::Error::{CrawlError, S3Error, SQSError, SerdeError};
 use crate::error::{Error, Result};
+use crate::events::aws::crawl::{CrawlKeyRange, CrawlScope};
+use crate::events::aws::message::quote_e_tag;
 use crate::events::aws::{
     DiffCrawlCreatedMessage, DiffCrawlDeletedMessage, EventType, FlatS3EventMessage,
     FlatS3EventMessages, StorageClass, TransposedS3EventMessages,
@@ -20,21 +23,89 @@ use crate::routes::filter::S3ObjectsFilter;
 use crate::routes::filter::wildcard::Wildcard;
 use crate::uuid::UuidGenerator;
 use async_trait::async_trait;
-use aws_sdk_s3::error::BuildError;
+use aws_sdk_s3::error::{BuildError, ProvideErrorMetadata, SdkError};
 use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingOutput;
 use aws_sdk_s3::operation::head_object::HeadObjectOutput;
 use aws_sdk_s3::primitives;
 use aws_sdk_s3::types::StorageClass::Standard;
 use aws_sdk_s3::types::{Tag, Tagging};
-use chrono::{DateTime, Utc};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use chrono::{DateTime, Duration, Utc};
 use futures::TryFutureExt;
-use futures::future::join_all;
+use futures::future::{join_all, try_join_all};
 use itertools::Itertools;
-use std::collections::HashSet;
+use sea_orm::ActiveValue::{Set, Unchanged};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait, QueryFilter,
+    QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::sync::Arc;
 use tracing::{trace, warn};
 use uuid::Uuid;
 
+/// The error code returned by S3 when an operation is not valid for an archived object.
+pub const INVALID_OBJECT_STATE: &str = "InvalidObjectState";
+
+/// The version id that S3 returns for objects without a version.
+pub const NULL_VERSION_ID: &str = "null";
+
+/// The maximum number of parts that `GetObjectAttributes` returns per call.
+pub const MAX_PARTS_PER_PAGE: usize = 1000;
+
+/// The maximum number of existing records that are re-collected concurrently.
+pub const MAX_COLLECT_CONCURRENCY: usize = 10;
+
+/// How to handle a `HeadObject` response which has a different version id to the event. This
+/// can occur if a new version of the object is created before the metadata for the latest
+/// version is collected, in which case the metadata belongs to a different version.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum VersionMismatchMode {
+    /// Do not update the event with the `HeadObject` metadata.
+    #[default]
+    Skip,
+    /// Update the event with the `HeadObject` metadata, and flag the record using
+    /// `is_e_tag_mismatch`, as the object changed after the event was emitted.
+    Flag,
+}
+
+/// Options which control how crawl events are reconciled with the database state.
+#[derive(Debug, Clone)]
+pub struct CrawlOptions {
+    /// Only update existing records if the S3 object is newer than the database record.
+    pub only_newer: bool,
+    /// Do not create deleted events for records in the database that are missing from the
+    /// crawl. This is useful when the crawl does not cover everything under the crawl prefix,
+    /// so a missing object does not imply that it was deleted.
+    pub skip_deletes: bool,
+    /// The clock used for the event time of deleted events. By default, this is the system time.
+    pub clock: Arc<dyn Clock>,
+    /// Only compare the crawl against records with keys in this range. This is used when a crawl
+    /// is ingested in chunks, so that records covered by other chunks are not deleted.
+    pub key_range: CrawlKeyRange,
+    /// The keys and modification times that the crawl covers. Records outside the scope are not
+    /// deleted, because the crawl does not list their objects.
+    pub scope: CrawlScope,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            only_newer: false,
+            skip_deletes: false,
+            clock: Arc::new(SystemClock),
+            key_range: CrawlKeyRange::default(),
+            scope: CrawlScope::default(),
+        }
+    }
+}
+
 /// Build an AWS collector struct.
 #[derive(Default, Debug)]
 pub struct CollecterBuilder {
@@ -43,6 +114,7 @@ pub struct CollecterBuilder {
     sqs_url: Option<String>,
     crawl_bucket: Option<String>,
     crawl_prefix: Option<String>,
+    crawl_options: CrawlOptions,
 }
 
 impl CollecterBuilder {
@@ -75,6 +147,38 @@ impl CollecterBuilder {
         self
     }
 
+    /// Only update existing records during a crawl if the S3 object is newer than the database
+    /// record.
+    pub fn with_crawl_only_newer(mut self, only_newer: bool) -> Self {
+        self.crawl_options.only_newer = only_newer;
+        self
+    }
+
+    /// Do not create deleted events during a crawl for records that are missing from S3.
+    pub fn with_crawl_skip_deletes(mut self, skip_deletes: bool) -> Self {
+        self.crawl_options.skip_deletes = skip_deletes;
+        self
+    }
+
+    /// Set the clock used for the event time of crawl events.
+    pub fn with_crawl_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.crawl_options.clock = Arc::new(clock);
+        self
+    }
+
+    /// Only compare the crawl against records with keys in the range.
+    pub fn with_crawl_key_range(mut self, key_range: CrawlKeyRange) -> Self {
+        self.crawl_options.key_range = key_range;
+        self
+    }
+
+    /// Only delete records covered by the scope of the crawl, e.g. the scope of a crawl with
+    /// include or exclude patterns.
+    pub fn with_crawl_scope(mut self, scope: CrawlScope) -> Self {
+        self.crawl_options.scope = scope;
+        self
+    }
+
     /// Set the SQS url to build with.
     pub fn set_sqs_url(mut self, url: Option<impl Into<String>>) -> Self {
         self.sqs_url = url.map(|url| url.into());
@@ -88,25 +192,24 @@ impl CollecterBuilder {
         config: &'a Config,
         client: &'a database::Client,
     ) -> Collecter<'a> {
-        if let Some(s3_client) = self.s3_client {
-            Collecter::new(
-                s3_client,
-                client,
-                raw_events,
-                config,
-                self.crawl_bucket,
-                self.crawl_prefix,
-            )
+        let s3_client = if let Some(s3_client) = self.s3_client {
+            s3_client
         } else {
-            Collecter::new(
-                S3Client::with_defaults().await,
-                client,
-                raw_events,
-                config,
-                self.crawl_bucket,
-                self.crawl_prefix,
-            )
-        }
+            S3Client::with_defaults()
+                .await
+                .with_max_concurrency(config.s3_max_concurrency())
+        };
+
+        let mut collecter = Collecter::new(
+            s3_client,
+            client,
+            raw_events,
+            config,
+            self.crawl_bucket,
+            self.crawl_prefix,
+        );
+        collecter.set_crawl_options(self.crawl_options);
+        collecter
     }
 
     /// Manually call the receive function to retrieve events from the SQS queue.
@@ -179,6 +282,7 @@ pub struct Collecter<'a> {
     n_records: Option<usize>,
     crawl_bucket: Option<String>,
     crawl_prefix: Option<String>,
+    crawl_options: CrawlOptions,
 }
 
 impl<'a> Collecter<'a> {
@@ -199,6 +303,7 @@ impl<'a> Collecter<'a> {
             n_records: None,
             crawl_bucket,
             crawl_prefix,
+            crawl_options: Default::default(),
         }
     }
 
@@ -212,6 +317,7 @@ impl<'a> Collecter<'a> {
         &'a Config,
         Option<String>,
         Option<String>,
+        CrawlOptions,
     ) {
         (
             self.client,
@@ -220,6 +326,7 @@ impl<'a> Collecter<'a> {
             self.config,
             self.crawl_bucket,
             self.crawl_prefix,
+            self.crawl_options,
         )
     }
 
@@ -238,6 +345,21 @@ impl<'a> Collecter<'a> {
         self.crawl_prefix = prefix;
     }
 
+    /// Set whether a crawl should only update existing records if the S3 object is newer.
+    pub fn set_crawl_only_newer(&mut self, only_newer: bool) {
+        self.crawl_options.only_newer = only_newer;
+    }
+
+    /// Set whether a crawl should skip creating deleted events for records missing from S3.
+    pub fn set_crawl_skip_deletes(&mut self, skip_deletes: bool) {
+        self.crawl_options.skip_deletes = skip_deletes;
+    }
+
+    /// Set the crawl options.
+    pub fn set_crawl_options(&mut self, options: CrawlOptions) {
+        self.crawl_options = options;
+    }
+
     /// Get the S3 client.
     pub fn client(&self) -> &S3Client {
         &self.client
@@ -257,27 +379,197 @@ impl<'a> Collecter<'a> {
         }
     }
 
+    /// Check whether the ETag of an event differs from the ETag returned by `HeadObject`.
+    /// Returns false if either ETag is unknown.
+    pub fn is_e_tag_mismatch(event_e_tag: Option<&str>, head_e_tag: Option<&str>) -> bool {
+        match (event_e_tag, head_e_tag) {
+            (Some(event_e_tag), Some(head_e_tag)) => {
+                quote_e_tag(event_e_tag.to_string()) != quote_e_tag(head_e_tag.to_string())
+            }
+            _ => false,
+        }
+    }
+
+    /// Check whether the version id returned by `HeadObject` differs from the version id of the
+    /// event. Returns false if the returned version id is unknown, or if it is `null` for an event
+    /// with the default version id, as this is the version id of unversioned objects.
+    pub fn is_version_id_mismatch(
+        event_version_id: &str,
+        head_version_id: Option<&str>,
+        default_version_id: &str,
+    ) -> bool {
+        match head_version_id {
+            Some(NULL_VERSION_ID) if event_version_id == default_version_id => false,
+            Some(head_version_id) => head_version_id != event_version_id,
+            None => false,
+        }
+    }
+
+    /// Compute the composite SHA256 checksum of a multipart object from the base64 encoded SHA256
+    /// checksums of its parts, in part order. This is the checksum of the concatenated part
+    /// checksums followed by the number of parts, which is the format that S3 uses for multipart
+    /// objects. Returns `None` if there are no parts or a part checksum is not valid base64.
+    pub fn composite_checksum(part_checksums: &[String]) -> Option<String> {
+        if part_checksums.is_empty() {
+            return None;
+        }
+
+        let mut hasher = Sha256::new();
+        for checksum in part_checksums {
+            hasher.update(BASE64_STANDARD.decode(checksum).ok()?);
+        }
+
+        Some(format!(
+            "{}-{}",
+            BASE64_STANDARD.encode(hasher.finalize()),
+            part_checksums.len()
+        ))
+    }
+
+    /// Get the number of parts of a multipart object from its ETag, which has a `-N` suffix for
+    /// multipart objects. Returns `None` if the ETag does not belong to a multipart object.
+    pub fn multipart_parts_count(e_tag: &str) -> Option<usize> {
+        let (_, parts) = e_tag.trim_matches('"').rsplit_once('-')?;
+        parts.parse().ok().filter(|parts| *parts > 0)
+    }
+
+    /// Reconstruct the SHA256 checksum of a multipart object from the checksums of its parts,
+    /// which are fetched using `GetObjectAttributes`. The number of parts is known from the ETag,
+    /// so no request is made for objects which are not multipart objects or which have more than
+    /// `max_parts` parts, and the pages of parts are fetched concurrently. This returns `None` if
+    /// the object is not a multipart object, has more than `max_parts` parts, or has a part
+    /// without a SHA256 checksum.
+    pub async fn multipart_checksum(
+        client: &S3Client,
+        event: &FlatS3EventMessage,
+        max_parts: usize,
+    ) -> Result<Option<String>> {
+        Self::multipart_checksum_pages(client, event, max_parts, MAX_PARTS_PER_PAGE).await
+    }
+
+    async fn multipart_checksum_pages(
+        client: &S3Client,
+        event: &FlatS3EventMessage,
+        max_parts: usize,
+        page_size: usize,
+    ) -> Result<Option<String>> {
+        let Some(n_parts) = event.e_tag.as_deref().and_then(Self::multipart_parts_count) else {
+            return Ok(None);
+        };
+        if n_parts > max_parts {
+            return Ok(None);
+        }
+
+        // Each page starts after the last part number of the previous page.
+        let max_page_parts = i32::try_from(page_size)?;
+        let pages = try_join_all((0..n_parts).step_by(page_size).map(|start| {
+            client
+                .get_object_parts(
+                    &event.key,
+                    &event.bucket,
+                    &event.version_id,
+                    max_page_parts,
+                    (start > 0).then(|| start.to_string()),
+                )
+                .map_err(|err| Error::from((err, "GetObjectAttributes".to_string())))
+        }))
+        .await?;
+
+        let mut checksums = Vec::with_capacity(n_parts);
+        let mut last_part_number = 0;
+        for output in pages {
+            let Some(parts) = output.object_parts else {
+                return Ok(None);
+            };
+
+            for part in parts.parts() {
+                // Parts must be contiguous and in order, otherwise the pages overlap.
+                let (Some(part_number), Some(checksum)) =
+                    (part.part_number(), part.checksum_sha256())
+                else {
+                    return Ok(None);
+                };
+                if part_number <= last_part_number {
+                    return Ok(None);
+                }
+
+                last_part_number = part_number;
+                checksums.push(checksum.to_string());
+            }
+        }
+
+        if checksums.len() != n_parts {
+            return Ok(None);
+        }
+
+        Ok(Self::composite_checksum(&checksums))
+    }
+
+    /// Check whether an S3 error has the HTTP status code.
+    pub fn has_status<E>(err: &SdkError<E>, status: u16) -> bool {
+        err.raw_response()
+            .is_some_and(|response| response.status().as_u16() == status)
+    }
+
+    /// Check whether an S3 error occurred because the object does not exist. `HeadObject`
+    /// responses have no body, so this is determined by the HTTP status rather than an error code.
+    pub fn is_not_found<E>(err: &SdkError<E>) -> bool {
+        Self::has_status(err, 404)
+    }
+
+    /// Check whether an S3 error occurred because the object is archived. `HeadObject` errors
+    /// do not carry the `InvalidObjectState` code, so a 403 status is also matched. Callers
+    /// retry without checksums, which distinguishes an archived object from a forbidden one.
+    pub fn is_invalid_object_state<E: ProvideErrorMetadata>(err: &SdkError<E>) -> bool {
+        err.code() == Some(INVALID_OBJECT_STATE) || Self::has_status(err, 403)
+    }
+
     /// Gets S3 metadata from HeadObject such as creation/archival timestamps and statuses.
-    pub async fn head(client: &S3Client, event: FlatS3EventMessage) -> FlatS3EventMessage {
-        let head = client
-            .head_object(&event.key, &event.bucket, &event.version_id)
+    pub async fn head(
+        config: &Config,
+        client: &S3Client,
+        event: FlatS3EventMessage,
+    ) -> FlatS3EventMessage {
+        // Race condition: it's possible that an object gets deleted so quickly that it
+        // occurs before calling head/tagging. This means that there may be cases where the
+        // storage class and other fields are not known, or object moves cannot be tracked.
+        Self::try_head(config, client, event.clone())
+            .await
             .inspect_err(|err| {
                 warn!(
                     "Ingester Warning for {} in {}: {}",
-                    event.key,
-                    event.bucket,
-                    Error::from((err, "HeadObject".to_string()))
+                    event.key, event.bucket, err
                 )
             })
-            .await
-            .ok();
+            .unwrap_or(event)
+    }
 
-        // Race condition: it's possible that an object gets deleted so quickly that it
-        // occurs before calling head/tagging. This means that there may be cases where the
-        // storage class and other fields are not known, or object moves cannot be tracked.
-        let Some(head) = head else {
-            return event;
-        };
+    /// Gets S3 metadata from HeadObject, returning an error if the HeadObject call fails.
+    pub async fn try_head(
+        config: &Config,
+        client: &S3Client,
+        event: FlatS3EventMessage,
+    ) -> Result<FlatS3EventMessage> {
+        let head = match client
+            .head_object(&event.key, &event.bucket, &event.version_id)
+            .await
+        {
+            // An object which has moved to an archive tier cannot have its checksum retrieved.
+            // In this case, the rest of the metadata, including the archive status, is still
+            // available without the checksum.
+            Err(err) if Self::is_invalid_object_state(&err) => {
+                trace!(
+                    key = ?event.key,
+                    bucket = ?event.bucket,
+                    "object is archived, skipping checksum retrieval"
+                );
+                client
+                    .head_object_without_checksum(&event.key, &event.bucket, &event.version_id)
+                    .await
+            }
+            head => head,
+        }
+        .map_err(|err| Error::from((err, "HeadObject".to_string())))?;
 
         trace!(head = ?head, "received HeadObject output");
 
@@ -289,19 +581,70 @@ impl<'a> Collecter<'a> {
             checksum_sha256,
             delete_marker,
             archive_status,
+            server_side_encryption,
+            ssekms_key_id,
+            version_id,
             ..
         } = head;
 
+        // If the version id differs from the event, then the metadata belongs to another version
+        // of the object, and should not be silently attached to this record.
+        let is_version_id_mismatch = Self::is_version_id_mismatch(
+            &event.version_id,
+            version_id.as_deref(),
+            client.default_version_id(),
+        );
+        if is_version_id_mismatch {
+            warn!(
+                "Ingester Warning for {} in {}: event version id {:?} does not match HeadObject version id {:?}",
+                event.key, event.bucket, event.version_id, version_id
+            );
+
+            if config.ingester_version_mismatch_mode() == VersionMismatchMode::Skip {
+                return Ok(event);
+            }
+        }
+
+        // If the ETag differs from the event, then the object may have changed between the
+        // event being emitted and the metadata being collected, so the record is flagged.
+        let is_e_tag_mismatch = Self::is_e_tag_mismatch(event.e_tag.as_deref(), e_tag.as_deref())
+            || is_version_id_mismatch;
+        if is_e_tag_mismatch {
+            warn!(
+                "Ingester Warning for {} in {}: event ETag {:?} does not match HeadObject ETag {:?}",
+                event.key, event.bucket, event.e_tag, e_tag
+            );
+        }
+
         // S3 does not return a storage class for standard, which means this is the
         // default. See https://docs.aws.amazon.com/AmazonS3/latest/API/API_HeadObject.html#API_HeadObject_ResponseSyntax
-        event
+        let event = event
+            .with_is_e_tag_mismatch(is_e_tag_mismatch)
+            .with_server_side_encryption(server_side_encryption.map(|sse| sse.as_str().to_string()))
+            .with_sse_kms_key_id(ssekms_key_id)
             .update_storage_class(StorageClass::from_aws(storage_class.unwrap_or(Standard)))
             .update_last_modified_date(Self::convert_datetime(last_modified))
             .update_size(content_length)
             .update_e_tag(e_tag)
             .update_sha256(checksum_sha256)
             .update_delete_marker(delete_marker)
-            .update_archive_status(archive_status.and_then(ArchiveStatus::from_aws))
+            .update_archive_status(archive_status.and_then(ArchiveStatus::from_aws));
+
+        // If the checksum is still unknown, it can be reconstructed from the checksums of the
+        // parts if the object is a multipart object.
+        if event.sha256.is_none()
+            && let Some(max_parts) = config.ingester_multipart_checksum_max_parts()
+        {
+            match Self::multipart_checksum(client, &event, max_parts).await {
+                Ok(sha256) => return Ok(event.update_sha256(sha256)),
+                Err(err) => warn!(
+                    "Ingester Warning for {} in {}: failed to reconstruct multipart checksum: {}",
+                    event.key, event.bucket, err
+                ),
+            }
+        }
+
+        Ok(event)
     }
 
     /// Gets S3 tags from objects.
@@ -313,64 +656,233 @@ impl<'a> Collecter<'a> {
     ) -> Result<FlatS3EventMessage> {
         let tagging = client
             .get_object_tagging(&event.key, &event.bucket, &event.version_id)
+            .await
+            .map_err(|err| Error::from((err, "GetObjectTagging".to_string())))
+            .inspect_err(|err| {
+                warn!(
+                    "Ingester Warning for {} in {}: {}",
+                    event.key, event.bucket, err
+                )
+            });
+
+        let tagging = match tagging {
+            Ok(tagging) => tagging,
+            Err(err) => {
+                // The existing tags are unknown, so writing a tag could replace them. Instead, if
+                // auto-tagging is enabled, the object is recorded so that the tag write is retried
+                // once its tags can be read.
+                if config.ingester_auto_tag() && event.ingest_id.is_none() {
+                    Self::record_tag_retry(
+                        database_client,
+                        &event,
+                        UuidGenerator::generate(),
+                        err.to_string(),
+                    )
+                    .await
+                    .unwrap_or_else(|err| {
+                        warn!(
+                            "Ingester Warning for {} in {}: failed to record tag retry: {}",
+                            event.key, event.bucket, err
+                        )
+                    });
+                }
+
+                return Ok(event);
+            }
+        };
+
+        trace!(tagging = ?tagging, "received tagging output");
+
+        let GetObjectTaggingOutput { tag_set, .. } = tagging;
+
+        // Copy the configured tags into attributes after handling the ingest_id, so that
+        // attributes from a moved object take precedence over the tags.
+        let tag_attributes = Self::tag_attributes(config, &tag_set);
+        let event =
+            Self::ingest_id_tagging(config, client, database_client, event, tag_set).await?;
+
+        Ok(Self::merge_attributes(event, &tag_attributes))
+    }
+
+    /// Get the configured S3 tags as attributes.
+    pub fn tag_attributes(config: &Config, tag_set: &[Tag]) -> Map<String, Value> {
+        tag_set
+            .iter()
+            .filter(|tag| {
+                config
+                    .ingester_tag_attributes()
+                    .iter()
+                    .any(|name| name == tag.key())
+            })
+            .map(|tag| (tag.key().to_string(), Value::from(tag.value())))
+            .collect()
+    }
+
+    /// Generate a new ingest_id and add it to the `tag_set` of the object in S3. The ingest_id
+    /// is only added to the record if the tagging was successful, failures are logged and do not
+    /// fail ingestion. If tag retries or auto-tagging are enabled, failures are recorded so that
+    /// they can be retried later.
+    async fn put_ingest_id_tag(
+        config: &Config,
+        client: &S3Client,
+        database_client: &database::Client,
+        event: FlatS3EventMessage,
+        mut tag_set: Vec<Tag>,
+    ) -> Result<FlatS3EventMessage> {
+        let ingest_id = UuidGenerator::generate();
+        let tag = Tag::builder()
+            .key(config.ingester_tag_name())
+            .value(ingest_id)
+            .build()?;
+        tag_set.push(tag);
+
+        // Try to push the tags to S3, only proceed if successful.
+        let result = client
+            .put_object_tagging(
+                &event.key,
+                &event.bucket,
+                &event.version_id,
+                Tagging::builder().set_tag_set(Some(tag_set)).build()?,
+            )
+            .await
             .inspect_err(|err| {
                 warn!(
                     "Ingester Warning for {} in {}: {}",
                     event.key,
                     event.bucket,
-                    Error::from((err, "GetObjectTagging".to_string()))
+                    Error::from((err, "PutObjectTagging".to_string()))
                 )
-            })
+            });
+
+        // Only add a ingest_id to the new record if the tagging was successful.
+        match result {
+            Ok(_) => Ok(event.with_ingest_id(Some(ingest_id))),
+            Err(err) => {
+                if config.ingester_tag_retry() || config.ingester_auto_tag() {
+                    let err = Error::from((&err, "PutObjectTagging".to_string()));
+                    Self::record_tag_retry(database_client, &event, ingest_id, err.to_string())
+                        .await
+                        .unwrap_or_else(|err| {
+                            warn!(
+                                "Ingester Warning for {} in {}: failed to record tag retry: {}",
+                                event.key, event.bucket, err
+                            )
+                        });
+                }
+
+                Ok(event)
+            }
+        }
+    }
+
+    /// Record a failed ingest_id tag write so that it can be retried. Only one pending retry is
+    /// kept for each object version.
+    async fn record_tag_retry(
+        database_client: &database::Client,
+        event: &FlatS3EventMessage,
+        ingest_id: Uuid,
+        error: String,
+    ) -> Result<()> {
+        s3_tag_retry::Entity::insert(s3_tag_retry::ActiveModel {
+            s3_tag_retry_id: Set(UuidGenerator::generate()),
+            bucket: Set(event.bucket.to_string()),
+            key: Set(event.key.to_string()),
+            version_id: Set(event.version_id.to_string()),
+            ingest_id: Set(ingest_id),
+            last_error: Set(Some(error)),
+            ..Default::default()
+        })
+        .on_conflict(OnConflict::new().do_nothing().to_owned())
+        .do_nothing()
+        .exec(database_client.connection_ref())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retry a failed ingest_id tag write. If the object already has a valid ingest_id tag,
+    /// then that is used instead of tagging the object again. The `Created` records of the object
+    /// without an ingest_id are updated with the ingest_id, which is returned.
+    pub async fn retry_ingest_id_tag(
+        config: &Config,
+        client: &S3Client,
+        database_client: &database::Client,
+        retry: &s3_tag_retry::Model,
+    ) -> Result<Uuid> {
+        let GetObjectTaggingOutput { mut tag_set, .. } = client
+            .get_object_tagging(&retry.key, &retry.bucket, &retry.version_id)
             .await
-            .ok();
+            .map_err(|err| Error::from((&err, "GetObjectTagging".to_string())))?;
+
+        let existing = tag_set
+            .iter()
+            .find(|tag| tag.key == config.ingester_tag_name())
+            .and_then(|tag| Uuid::from_str(tag.value()).ok());
+
+        let ingest_id = match existing {
+            Some(ingest_id) => ingest_id,
+            None => {
+                tag_set.retain(|tag| tag.key != config.ingester_tag_name());
+                tag_set.push(
+                    Tag::builder()
+                        .key(config.ingester_tag_name())
+                        .value(retry.ingest_id)
+                        .build()?,
+                );
+
+                client
+                    .put_object_tagging(
+                        &retry.key,
+                        &retry.bucket,
+                        &retry.version_id,
+                        Tagging::builder().set_tag_set(Some(tag_set)).build()?,
+                    )
+                    .await
+                    .map_err(|err| Error::from((&err, "PutObjectTagging".to_string())))?;
 
-        let Some(tagging) = tagging else {
-            return Ok(event);
+                retry.ingest_id
+            }
         };
 
-        trace!(tagging = ?tagging, "received tagging output");
-
-        let GetObjectTaggingOutput { mut tag_set, .. } = tagging;
+        s3_object::Entity::update_many()
+            .col_expr(s3_object::Column::IngestId, Expr::value(ingest_id))
+            .col_expr(
+                s3_object::Column::TagPresent,
+                Expr::value(existing.is_some()),
+            )
+            .filter(s3_object::Column::Bucket.eq(&retry.bucket))
+            .filter(s3_object::Column::Key.eq(&retry.key))
+            .filter(s3_object::Column::VersionId.eq(&retry.version_id))
+            .filter(s3_object::Column::EventType.eq(sea_orm_active_enums::EventType::Created))
+            .filter(s3_object::Column::IngestId.is_null())
+            .exec(database_client.connection_ref())
+            .await?;
+
+        Ok(ingest_id)
+    }
 
+    /// Find or assign the ingest_id tag, copying attributes from a moved object if the tag
+    /// already exists.
+    async fn ingest_id_tagging(
+        config: &Config,
+        client: &S3Client,
+        database_client: &database::Client,
+        event: FlatS3EventMessage,
+        tag_set: Vec<Tag>,
+    ) -> Result<FlatS3EventMessage> {
         // Check if the object contains the ingest_id tag.
         let tag = tag_set
             .clone()
             .into_iter()
             .find(|tag| tag.key == config.ingester_tag_name());
 
+        // Record whether the tag was present, so that objects with an ingest_id assigned by the
+        // filemanager can be found later without querying S3.
+        let event = event.with_tag_present(Some(tag.is_some()));
+
         let Some(tag) = tag else {
             // If it doesn't, then a new tag needs to be generated.
-            let ingest_id = UuidGenerator::generate();
-            let tag = Tag::builder()
-                .key(config.ingester_tag_name())
-                .value(ingest_id)
-                .build()?;
-            tag_set.push(tag);
-
-            // Try to push the tags to S3, only proceed if successful.
-            let result = client
-                .put_object_tagging(
-                    &event.key,
-                    &event.bucket,
-                    &event.version_id,
-                    Tagging::builder().set_tag_set(Some(tag_set)).build()?,
-                )
-                .await
-                .inspect_err(|err| {
-                    warn!(
-                        "Ingester Warning for {} in {}: {}",
-                        event.key,
-                        event.bucket,
-                        Error::from((err, "PutObjectTagging".to_string()))
-                    )
-                });
-
-            // Only add a ingest_id to the new record if the tagging was successful.
-            return if result.is_ok() {
-                Ok(event.with_ingest_id(Some(ingest_id)))
-            } else {
-                Ok(event)
-            };
+            return Self::put_ingest_id_tag(config, client, database_client, event, tag_set).await;
         };
 
         // The object has an ingest_id tag. Grab the existing the tag, returning a new record without
@@ -414,44 +926,183 @@ impl<'a> Collecter<'a> {
         }
     }
 
-    /// Updates events that are crawls to take into account the existing database state.
+    /// Add the configured default attributes for the bucket and key to the event. Attributes which
+    /// are already set on the event, such as those copied from a moved object, take precedence.
+    pub fn default_attributes(config: &Config, event: FlatS3EventMessage) -> FlatS3EventMessage {
+        let defaults = config
+            .ingester_default_attributes()
+            .iter()
+            .filter(|defaults| defaults.matches(&event.bucket, &event.key))
+            .collect_vec();
+
+        defaults.into_iter().fold(event, |event, defaults| {
+            Self::merge_attributes(event, defaults.attributes())
+        })
+    }
+
+    /// Add attributes to the event, keeping any attributes which are already set.
+    fn merge_attributes(
+        mut event: FlatS3EventMessage,
+        attributes: &Map<String, Value>,
+    ) -> FlatS3EventMessage {
+        if attributes.is_empty() {
+            return event;
+        }
+
+        let existing = event
+            .attributes
+            .get_or_insert_with(|| Value::Object(Map::new()));
+        if let Some(existing) = existing.as_object_mut() {
+            for (key, value) in attributes {
+                existing
+                    .entry(key.to_string())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+
+        event
+    }
+
+    /// Get the ingest_id from the S3 tags without writing any tags. A missing or invalid
+    /// ingest_id tag leaves the ingest_id of the event unchanged.
+    pub async fn read_tagging(
+        config: &Config,
+        client: &S3Client,
+        event: FlatS3EventMessage,
+    ) -> Result<FlatS3EventMessage> {
+        let GetObjectTaggingOutput { tag_set, .. } = client
+            .get_object_tagging(&event.key, &event.bucket, &event.version_id)
+            .await
+            .map_err(|err| Error::from((err, "GetObjectTagging".to_string())))?;
+
+        let tag = tag_set
+            .iter()
+            .find(|tag| tag.key == config.ingester_tag_name());
+        let event = event.with_tag_present(Some(tag.is_some()));
+
+        match tag.and_then(|tag| Uuid::from_str(tag.value()).ok()) {
+            Some(ingest_id) => Ok(event.with_ingest_id(Some(ingest_id))),
+            None => Ok(event),
+        }
+    }
+
+    /// Re-run `HeadObject` and `GetObjectTagging` on an existing record, updating its mutable
+    /// fields in place. This does not create any new records, and does not write S3 tags, so
+    /// objects without an ingest_id tag keep their existing ingest_id.
+    pub async fn recollect<C: ConnectionTrait>(
+        config: &Config,
+        client: &S3Client,
+        connection: &C,
+        record: s3_object::Model,
+    ) -> Result<()> {
+        let id = record.s3_object_id;
+        let event = Self::try_head(config, client, FlatS3EventMessage::from(record)).await?;
+        let event = Self::read_tagging(config, client, event).await?;
+
+        s3_object::ActiveModel {
+            s3_object_id: Unchanged(id),
+            size: Set(event.size),
+            e_tag: Set(event.e_tag),
+            sha256: Set(event.sha256),
+            storage_class: Set(event.storage_class.map(|class| class.to_database())),
+            last_modified_date: Set(event.last_modified_date.map(Into::into)),
+            is_delete_marker: Set(event.is_delete_marker),
+            archive_status: Set(event.archive_status),
+            ingest_id: Set(event.ingest_id),
+            is_e_tag_mismatch: Set(event.is_e_tag_mismatch),
+            server_side_encryption: Set(event.server_side_encryption),
+            sse_kms_key_id: Set(event.sse_kms_key_id),
+            tag_present: Set(event.tag_present),
+            ..Default::default()
+        }
+        .update(connection)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Updates events that are crawls to take into account the existing database state. If
+    /// `only_newer` is set, existing records are only updated if the `last_modified_date` of the
+    /// S3 object is newer than the database record. This avoids a crawl with stale listing data
+    /// overwriting records from more recent events. If `skip_deletes` is set, records that are
+    /// missing from the crawl are left untouched rather than being deleted. Only records with
+    /// keys in the `key_range` are compared, so that a crawl can be updated in chunks, and only
+    /// records covered by the `scope` of the crawl are deleted. Default
+    /// attributes are only added to object versions that do not have a database record yet, after
+    /// the comparison.
     pub async fn update_crawl_events(
+        config: &Config,
         database_client: &database::Client,
         events: FlatS3EventMessages,
         crawl_bucket: String,
         crawl_prefix: Option<String>,
+        options: CrawlOptions,
     ) -> Result<FlatS3EventMessages> {
-        // Get crawl list object details ensuring that all object versions are taken into account.
-        // Note that this fetches non-current objects too in order to crawl old object versions.
-        // This gets the most current record for each object version.
-        let database_state: Vec<FlatS3EventMessage> =
-            ListQueryBuilder::<_, s3_object::Entity>::new(database_client.connection_ref())
-                .filter_all(
-                    S3ObjectsFilter {
-                        bucket: Wildcard::new(crawl_bucket).into(),
-                        key: Wildcard::new(format!("{}{}", crawl_prefix.unwrap_or_default(), "*"))
-                            .into(),
-                        ..Default::default()
-                    },
-                    true,
-                    false,
-                )?
-                .all()
-                .await?
-                .into_iter()
-                .map(FlatS3EventMessage::from)
-                .filter(|object| object.event_type == EventType::Created)
-                .chunk_by(|object| format!("{}{}{}", object.bucket, object.key, object.version_id))
-                .into_iter()
-                .map(|(_, objects)| {
-                    FlatS3EventMessages(objects.collect_vec())
-                        .sort()
-                        .0
-                        .into_iter()
-                        .last()
-                        .ok_or_else(|| CrawlError("expected at least one element".to_string()))
-                })
-                .collect::<Result<_>>()?;
+        // Delete markers are not part of the state comparison, because they represent deleted
+        // objects rather than objects that exist in S3.
+        let (delete_markers, events): (Vec<_>, Vec<_>) = events
+            .into_inner()
+            .into_iter()
+            .partition(|event| event.is_delete_marker);
+        let events = FlatS3EventMessages(events);
+
+        let records = Self::crawl_records(
+            database_client,
+            crawl_bucket,
+            crawl_prefix,
+            &options.key_range,
+        )
+        .await?;
+
+        // Delete markers that already have a record do not need to be ingested again.
+        let existing_delete_markers: HashSet<_> = records
+            .iter()
+            .filter(|object| object.is_delete_marker)
+            .map(|object| {
+                (
+                    object.bucket.clone(),
+                    object.key.clone(),
+                    object.version_id.clone(),
+                )
+            })
+            .collect();
+        let delete_markers = delete_markers
+            .into_iter()
+            .filter(|marker| {
+                !existing_delete_markers.contains(&(
+                    marker.bucket.clone(),
+                    marker.key.clone(),
+                    marker.version_id.clone(),
+                ))
+            })
+            .map(DiffCrawlCreatedMessage)
+            .collect_vec();
+
+        let database_state = Self::crawl_database_state(records)?;
+
+        // The last modified date of each object version, used to skip stale crawl updates.
+        let last_modified_dates: HashMap<_, _> = database_state
+            .iter()
+            .map(|object| {
+                (
+                    (
+                        object.bucket.clone(),
+                        object.key.clone(),
+                        object.version_id.clone(),
+                    ),
+                    object.last_modified_date,
+                )
+            })
+            .collect();
+        let is_newer = |event: &DiffCrawlCreatedMessage| {
+            let database = last_modified_dates.get(&(
+                event.0.bucket.clone(),
+                event.0.key.clone(),
+                event.0.version_id.clone(),
+            ));
+
+            Self::is_newer(event.0.last_modified_date, database.copied().flatten())
+        };
 
         // The difference keeps the records that need to be deleted from the database.
         // All new crawl events should be appended to the database, this could have efficiency
@@ -508,43 +1159,277 @@ impl<'a> Collecter<'a> {
         // The state in S3 minus the state in the database represents new records that should be
         // inserted.
         let diff_created = s3_state.difference(&database_state).cloned().collect_vec();
+        let diff_deleted = Self::crawl_deleted(database_state, &s3_state, &options);
+
+        let (always_update, diff_created) = if options.only_newer {
+            (
+                always_update.into_iter().filter(is_newer).collect_vec(),
+                diff_created.into_iter().filter(is_newer).collect_vec(),
+            )
+        } else {
+            (always_update.into_iter().collect_vec(), diff_created)
+        };
+        let diff_created = diff_created
+            .into_iter()
+            .map(|event| {
+                let key = (
+                    event.0.bucket.clone(),
+                    event.0.key.clone(),
+                    event.0.version_id.clone(),
+                );
+                if last_modified_dates.contains_key(&key) {
+                    event
+                } else {
+                    DiffCrawlCreatedMessage(Self::default_attributes(config, event.0))
+                }
+            })
+            .collect_vec();
+
+        let diff = [
+            always_update,
+            diff_created,
+            diff_deleted
+                .into_iter()
+                .map(DiffCrawlCreatedMessage::from)
+                .collect_vec(),
+            delete_markers,
+        ]
+        .concat();
+
+        Ok(FlatS3EventMessages::from(diff))
+    }
+
+    /// Get the database records that a crawl of the bucket and prefix is compared against. This
+    /// fetches non-current records too in order to crawl old object versions. Only records with
+    /// keys in the `key_range` are returned.
+    async fn crawl_records(
+        database_client: &database::Client,
+        crawl_bucket: String,
+        crawl_prefix: Option<String>,
+        key_range: &CrawlKeyRange,
+    ) -> Result<Vec<FlatS3EventMessage>> {
+        Ok(
+            ListQueryBuilder::<_, s3_object::Entity>::new(database_client.connection_ref())
+                .filter_all(
+                    S3ObjectsFilter {
+                        bucket: Wildcard::new(crawl_bucket).into(),
+                        key: Wildcard::new(format!("{}{}", crawl_prefix.unwrap_or_default(), "*"))
+                            .into(),
+                        ..Default::default()
+                    },
+                    true,
+                    false,
+                )?
+                .filter_key_range(key_range.start.clone(), key_range.end.clone())
+                .all()
+                .await?
+                .into_iter()
+                .map(FlatS3EventMessage::from)
+                .collect(),
+        )
+    }
+
+    /// Get the most current `Created` record for each object version.
+    fn crawl_database_state(records: Vec<FlatS3EventMessage>) -> Result<Vec<FlatS3EventMessage>> {
+        records
+            .into_iter()
+            .filter(|object| object.event_type == EventType::Created)
+            .chunk_by(|object| format!("{}{}{}", object.bucket, object.key, object.version_id))
+            .into_iter()
+            .map(|(_, objects)| {
+                FlatS3EventMessages(objects.collect_vec())
+                    .sort()
+                    .0
+                    .into_iter()
+                    .last()
+                    .ok_or_else(|| CrawlError("expected at least one element".to_string()))
+            })
+            .collect()
+    }
+
+    /// Whether an S3 object with the `last_modified` date is newer than the database record.
+    /// Records that are not in the database, or that cannot be compared, are considered newer.
+    pub fn is_newer(
+        last_modified: Option<DateTime<Utc>>,
+        database_last_modified: Option<DateTime<Utc>>,
+    ) -> bool {
+        match (last_modified, database_last_modified) {
+            (Some(s3), Some(database)) => s3 > database,
+            _ => true,
+        }
+    }
+
+    /// Find the records in the `database_state` that are missing from the `s3_state`, as
+    /// `Deleted` events. If deletes are skipped, missing records are left untouched instead.
+    /// Records outside the scope of the crawl are never listed, so they are also left untouched.
+    fn crawl_deleted(
+        database_state: impl IntoIterator<Item = DiffCrawlCreatedMessage>,
+        s3_state: &HashSet<DiffCrawlCreatedMessage>,
+        options: &CrawlOptions,
+    ) -> Vec<DiffCrawlDeletedMessage> {
+        if options.skip_deletes {
+            return vec![];
+        }
+
         // All records that are not in the crawl, but are in the database represent records that
         // should be deleted from the database. This is represented by the difference between the
         // database state and the crawl state.
-        let diff_deleted = HashSet::<DiffCrawlDeletedMessage>::from_iter(
+        HashSet::<DiffCrawlDeletedMessage>::from_iter(
             database_state
                 .into_iter()
+                .filter(|state| {
+                    options
+                        .scope
+                        .covers(&state.0.key, state.0.last_modified_date)
+                })
                 .map(DiffCrawlDeletedMessage::from),
         )
         .difference(&HashSet::from_iter(
-            s3_state.into_iter().map(DiffCrawlDeletedMessage::from),
+            s3_state
+                .iter()
+                .map(|state| DiffCrawlDeletedMessage(state.0.clone())),
         ))
         .cloned()
         .map(|mut record| {
             // Update these to deleted events, as these should be removed from the database.
             record.0.is_current_state = false;
             record.0.event_type = EventType::Deleted;
-            // This needs to be like a crawl event, so the s3 object id, sequencer, time and reason
-            // should be refreshed.
+            // This needs to be like a crawl event, so the s3 object id, sequencer, time and
+            // reason should be refreshed.
             record.0.s3_object_id = UuidGenerator::generate();
-            record.0.event_time = Some(Utc::now());
+            record.0.event_time = Some(options.clock.now());
             record.0.sequencer = None;
             record.0.reason = Reason::Crawl;
             record
         })
-        .collect_vec();
+        .collect_vec()
+    }
 
-        let diff = [
-            always_update.into_iter().collect_vec(),
-            diff_created,
-            diff_deleted
+    /// Find the records that a crawl with the listed `events` would delete, without ingesting
+    /// anything. This uses the same comparison as `update_crawl_events`.
+    pub async fn crawl_deletes(
+        database_client: &database::Client,
+        events: FlatS3EventMessages,
+        crawl_bucket: String,
+        crawl_prefix: Option<String>,
+        options: &CrawlOptions,
+    ) -> Result<Vec<FlatS3EventMessage>> {
+        let records = Self::crawl_records(
+            database_client,
+            crawl_bucket,
+            crawl_prefix,
+            &options.key_range,
+        )
+        .await?;
+        let database_state = Self::crawl_database_state(records)?;
+
+        let s3_state =
+            HashSet::from_iter(Vec::<DiffCrawlCreatedMessage>::from(FlatS3EventMessages(
+                events
+                    .into_inner()
+                    .into_iter()
+                    .filter(|event| !event.is_delete_marker)
+                    .collect(),
+            )));
+
+        Ok(Self::crawl_deleted(
+            database_state.into_iter().map(DiffCrawlCreatedMessage),
+            &s3_state,
+            options,
+        )
+        .into_iter()
+        .map(|record| record.0)
+        .collect())
+    }
+
+    /// Drop events with an `event_time` that is older than the `max_event_age`, as these are
+    /// likely to be replays of old events. An old event is kept if it matches an existing record
+    /// with an equal or newer sequencer, because ingesting it then only deduplicates or reorders
+    /// events that the database already knows about.
+    pub async fn drop_stale_events(
+        database_client: &database::Client,
+        events: FlatS3EventMessages,
+        max_event_age: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<FlatS3EventMessages> {
+        let cutoff = now - max_event_age;
+        let is_stale = |event: &FlatS3EventMessage| {
+            event
+                .event_time
+                .is_some_and(|event_time| event_time < cutoff)
+        };
+
+        let condition = events.0.iter().filter(|event| is_stale(event)).fold(
+            Condition::any(),
+            |condition, event| {
+                condition.add(
+                    s3_object::Column::Bucket
+                        .eq(&event.bucket)
+                        .and(s3_object::Column::Key.eq(&event.key))
+                        .and(s3_object::Column::VersionId.eq(&event.version_id)),
+                )
+            },
+        );
+        if condition.is_empty() {
+            return Ok(events);
+        }
+
+        // The newest sequencer of each existing object version that has a stale event.
+        let sequencers: HashMap<_, _> = s3_object::Entity::find()
+            .select_only()
+            .columns([
+                s3_object::Column::Bucket,
+                s3_object::Column::Key,
+                s3_object::Column::VersionId,
+            ])
+            .column_as(s3_object::Column::Sequencer.max(), "sequencer")
+            .filter(condition)
+            .group_by(s3_object::Column::Bucket)
+            .group_by(s3_object::Column::Key)
+            .group_by(s3_object::Column::VersionId)
+            .into_tuple::<(String, String, String, Option<String>)>()
+            .all(database_client.connection_ref())
+            .await?
+            .into_iter()
+            .filter_map(|(bucket, key, version_id, sequencer)| {
+                Some(((bucket, key, version_id), sequencer?))
+            })
+            .collect();
+
+        Ok(FlatS3EventMessages(
+            events
+                .into_inner()
                 .into_iter()
-                .map(DiffCrawlCreatedMessage::from)
-                .collect_vec(),
-        ]
-        .concat();
+                .filter(|event| {
+                    if !is_stale(event) {
+                        return true;
+                    }
 
-        Ok(FlatS3EventMessages::from(diff))
+                    let existing = sequencers.get(&(
+                        event.bucket.clone(),
+                        event.key.clone(),
+                        event.version_id.clone(),
+                    ));
+                    let keep = existing.is_some_and(|existing| {
+                        event
+                            .sequencer
+                            .as_ref()
+                            .is_some_and(|sequencer| existing >= sequencer)
+                    });
+                    if !keep {
+                        warn!(
+                            key = ?event.key,
+                            bucket = ?event.bucket,
+                            version_id = ?event.version_id,
+                            event_time = ?event.event_time,
+                            "dropping event older than the max event age"
+                        );
+                    }
+
+                    keep
+                })
+                .collect(),
+        ))
     }
 
     /// Process events and add header and datetime fields.
@@ -555,6 +1440,7 @@ impl<'a> Collecter<'a> {
         events: FlatS3EventMessages,
         crawl_bucket: Option<String>,
         crawl_prefix: Option<String>,
+        crawl_options: CrawlOptions,
     ) -> Result<FlatS3EventMessages> {
         let events = FlatS3EventMessages(
             join_all(events.into_inner().into_iter().map(|event| async move {
@@ -566,7 +1452,7 @@ impl<'a> Collecter<'a> {
 
                 trace!(key = ?event.key, bucket = ?event.bucket, "updating event");
 
-                let event = Self::head(client, event).await;
+                let event = Self::head(config, client, event).await;
                 Self::tagging(config, client, database_client, event).await
             }))
             .await
@@ -575,9 +1461,28 @@ impl<'a> Collecter<'a> {
         );
 
         if let Some(crawl_bucket) = crawl_bucket {
-            Self::update_crawl_events(database_client, events, crawl_bucket, crawl_prefix).await
+            // Default attributes are applied after the diff, so that they do not cause existing
+            // records to be seen as changed.
+            Self::update_crawl_events(
+                config,
+                database_client,
+                events,
+                crawl_bucket,
+                crawl_prefix,
+                crawl_options,
+            )
+            .await
         } else {
-            Ok(events)
+            Ok(FlatS3EventMessages(
+                events
+                    .into_inner()
+                    .into_iter()
+                    .map(|event| match event.event_type {
+                        EventType::Created => Self::default_attributes(config, event),
+                        _ => event,
+                    })
+                    .collect(),
+            ))
         }
     }
 
@@ -596,10 +1501,19 @@ impl From<BuildError> for Error {
 #[async_trait]
 impl Collect for Collecter<'_> {
     async fn collect(mut self) -> Result<EventSource> {
-        let (client, database_client, events, config, crawl_bucket, crawl_prefix) =
+        let (client, database_client, events, config, crawl_bucket, crawl_prefix, crawl_options) =
             self.into_inner();
 
-        let events = events.sort_and_dedup();
+        let client = client.with_default_version_id(config.ingester_default_version_id());
+        let mut events = events.replace_default_version_id(config.ingester_default_version_id());
+        if config.ingester_resolve_sequencer_conflicts() {
+            events = events.resolve_sequencer_conflicts();
+        }
+        let mut events = events.sort_and_dedup();
+        if let Some(max_event_age) = config.ingester_max_event_age() {
+            events =
+                Self::drop_stale_events(database_client, events, max_event_age, Utc::now()).await?;
+        }
 
         let events = Self::update_events(
             config,
@@ -608,6 +1522,7 @@ impl Collect for Collecter<'_> {
             events,
             crawl_bucket,
             crawl_prefix,
+            crawl_options,
         )
         .await?;
         // Get only the known event types.
@@ -637,27 +1552,35 @@ pub(crate) mod tests {
     use crate::database::aws::migration::tests::MIGRATOR;
     use crate::events::aws::StorageClass::IntelligentTiering;
     use crate::events::aws::tests::{
-        EXPECTED_SHA256, EXPECTED_VERSION_ID, expected_event_record_simple,
-        expected_flat_events_simple,
+        EXPECTED_E_TAG, EXPECTED_QUOTED_E_TAG, EXPECTED_SHA256, EXPECTED_VERSION_ID,
+        expected_event_record_simple, expected_flat_events_simple,
     };
 
+    use aws_sdk_s3::operation::get_object_attributes::GetObjectAttributesOutput;
     use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingError;
-    use aws_sdk_s3::operation::head_object::HeadObjectError;
-    use aws_sdk_s3::operation::put_object_tagging::PutObjectTaggingOutput;
+    use aws_sdk_s3::operation::put_object_tagging::{
+        PutObjectTaggingError, PutObjectTaggingOutput,
+    };
+    use aws_sdk_s3::types::{GetObjectAttributesParts, ObjectPart};
 
     use aws_sdk_s3::primitives::DateTimeFormat;
+    use aws_sdk_s3::primitives::SdkBody;
     use aws_sdk_s3::types;
+    use aws_sdk_s3::types::ChecksumMode;
     use aws_sdk_s3::types::builders::TagBuilder;
-    use aws_sdk_s3::types::error::NotFound;
     use aws_sdk_sqs::operation::receive_message::ReceiveMessageOutput;
     use aws_sdk_sqs::types::builders::MessageBuilder;
     use aws_smithy_mocks::mock_client;
+    use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
+    use aws_smithy_runtime_api::http::StatusCode;
     use sea_orm::prelude::Json;
     use serde_json::json;
     use sqlx::{PgPool, Row};
 
     use super::*;
+    use crate::database::entities::sea_orm_active_enums::TagRetryStatus;
     use crate::database::{Client, Ingest};
+    use crate::env::DefaultAttributes;
     use crate::events::aws::message::EventType::Created;
     use crate::events::aws::message::default_version_id;
     use crate::handlers::aws::tests::s3_object_results;
@@ -726,6 +1649,7 @@ pub(crate) mod tests {
         )]);
 
         let result = Collecter::head(
+            &config,
             &collecter.client,
             expected_s3_event_message().with_version_id(default_version_id()),
         )
@@ -751,9 +1675,10 @@ pub(crate) mod tests {
                     && req.bucket() == Some("bucket")
                     && req.version_id().is_none()
             })
-            .then_error(expected_head_object_not_found)]);
+            .then_http_response(|| expected_head_object_status(404))]);
 
         let result = Collecter::head(
+            &config,
             &collecter.client,
             expected_s3_event_message().with_version_id(default_version_id()),
         )
@@ -765,135 +1690,95 @@ pub(crate) mod tests {
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn update_events(pool: PgPool) {
-        let config = Default::default();
-        let client = Client::from_pool(pool);
-        let mut collecter = test_collecter(&config, &client).await;
-
-        let events = expected_flat_events_simple().sort_and_dedup();
-
-        collecter.client = s3_client_expectations();
-
-        let mut result =
-            Collecter::update_events(&config, &collecter.client, &client, events, None, None)
-                .await
-                .unwrap()
-                .into_inner()
-                .into_iter();
-
-        let first = result.next().unwrap();
-        assert_eq!(first.storage_class, Some(IntelligentTiering));
-        assert_eq!(first.last_modified_date, Some(Default::default()));
-
-        let second = result.next().unwrap();
-        assert_eq!(second.storage_class, None);
-        assert_eq!(second.last_modified_date, None);
-    }
-
-    #[sqlx::test(migrator = "MIGRATOR")]
-    async fn tagging_without_move(pool: PgPool) {
+    async fn head_invalid_object_state(pool: PgPool) {
         let config = Default::default();
         let client = Client::from_pool(pool.clone());
         let mut collecter = test_collecter(&config, &client).await;
 
         collecter.raw_events = FlatS3EventMessages(vec![
-            expected_s3_event_message().with_version_id(EXPECTED_VERSION_ID.to_string()),
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+        collecter.client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::head_object)
+                .match_requests(|req| req.checksum_mode() == Some(&ChecksumMode::Enabled))
+                .then_http_response(|| expected_head_object_status(403)),
+            mock!(aws_sdk_s3::Client::head_object)
+                .match_requests(|req| {
+                    req.key() == Some("key")
+                        && req.bucket() == Some("bucket")
+                        && req.checksum_mode().is_none()
+                })
+                .then_output(|| {
+                    HeadObjectOutput::builder()
+                        .storage_class(types::StorageClass::IntelligentTiering)
+                        .archive_status(types::ArchiveStatus::ArchiveAccess)
+                        .build()
+                }),
+            get_tagging_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_get_object_tagging(Some(Uuid::default())),
+            ),
         ]);
-        collecter.client = s3_client_expectations();
-
-        let mut result = collecter.collect().await.unwrap();
-        let EventSourceType::S3(events) = &mut result.event_type else {
-            panic!();
-        };
-        assert!(events.ingest_ids[0].is_some());
 
+        let result = collecter.collect().await.unwrap();
         client.ingest(result.event_type).await.unwrap();
 
         let s3_object_results = s3_object_results(&pool).await;
         assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            s3_object_results[0].get::<Option<ArchiveStatus>, _>("archive_status"),
+            Some(ArchiveStatus::ArchiveAccess)
+        );
+        assert_eq!(
+            s3_object_results[0].get::<Option<StorageClass>, _>("storage_class"),
+            Some(IntelligentTiering)
+        );
         assert!(
             s3_object_results[0]
-                .get::<Option<Uuid>, _>("ingest_id")
-                .is_some()
+                .get::<Option<String>, _>("sha256")
+                .is_none()
         );
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn tagging_with_move(pool: PgPool) {
+    async fn head_e_tag_mismatch(pool: PgPool) {
         let config = Default::default();
         let client = Client::from_pool(pool.clone());
         let mut collecter = test_collecter(&config, &client).await;
 
-        let ingest_id = UuidGenerator::generate();
-        EntriesBuilder::default()
-            .with_ingest_id(ingest_id)
-            .with_n(1)
-            .build(&client)
-            .await
-            .unwrap();
-
         collecter.raw_events = FlatS3EventMessages(vec![
-            expected_s3_event_message().with_version_id(default_version_id()),
+            expected_s3_event_message()
+                .with_version_id(default_version_id())
+                .with_e_tag(Some(EXPECTED_E_TAG.to_string())),
         ]);
         collecter.client = mock_s3(&[
             head_expectation(
                 "key".to_string(),
                 default_version_id(),
-                expected_head_object(),
+                HeadObjectOutput::builder().e_tag("\"changed\"").build(),
             ),
             get_tagging_expectation(
                 "key".to_string(),
                 default_version_id(),
-                GetObjectTaggingOutput::builder()
-                    .set_tag_set(Some(vec![
-                        Tag::builder()
-                            .key("ingest_id")
-                            .value(ingest_id.to_string())
-                            .build()
-                            .unwrap(),
-                    ]))
-                    .build()
-                    .unwrap(),
+                expected_get_object_tagging(Some(Uuid::default())),
             ),
         ]);
 
-        let mut result = collecter.collect().await.unwrap();
-        let EventSourceType::S3(events) = &mut result.event_type else {
-            panic!();
-        };
-        assert!(events.ingest_ids[0].is_some());
-
+        let result = collecter.collect().await.unwrap();
         client.ingest(result.event_type).await.unwrap();
 
         let s3_object_results = s3_object_results(&pool).await;
-        assert_eq!(s3_object_results.len(), 2);
-        assert_eq!(
-            s3_object_results[0].get::<Option<Uuid>, _>("ingest_id"),
-            Some(ingest_id)
-        );
-        assert_eq!(
-            s3_object_results[1].get::<Option<Uuid>, _>("ingest_id"),
-            Some(ingest_id)
-        );
-
-        let expected_attributes = json!({
-            "attributeId": "0",
-            "nestedId": {
-                "attributeId": "0"
-            }
-        });
-        assert_eq!(
-            s3_object_results[0].get::<Option<Json>, _>("attributes"),
-            Some(expected_attributes.clone())
-        );
+        assert_eq!(s3_object_results.len(), 1);
+        assert!(s3_object_results[0].get::<bool, _>("is_e_tag_mismatch"));
         assert_eq!(
-            s3_object_results[1].get::<Option<Json>, _>("attributes"),
-            Some(expected_attributes)
+            s3_object_results[0].get::<Option<String>, _>("e_tag"),
+            Some("\"changed\"".to_string())
         );
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn tagging_on_fail(pool: PgPool) {
+    async fn head_server_side_encryption(pool: PgPool) {
         let config = Default::default();
         let client = Client::from_pool(pool.clone());
         let mut collecter = test_collecter(&config, &client).await;
@@ -901,36 +1786,869 @@ pub(crate) mod tests {
         collecter.raw_events = FlatS3EventMessages(vec![
             expected_s3_event_message().with_version_id(default_version_id()),
         ]);
-
         collecter.client = mock_s3(&[
             head_expectation(
                 "key".to_string(),
                 default_version_id(),
-                expected_head_object(),
+                HeadObjectOutput::builder()
+                    .server_side_encryption(types::ServerSideEncryption::AwsKms)
+                    .ssekms_key_id("kms_key_id")
+                    .build(),
+            ),
+            get_tagging_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_get_object_tagging(Some(Uuid::default())),
             ),
-            mock!(aws_sdk_s3::Client::get_object_tagging)
-                .match_requests(move |req| {
-                    req.key() == Some("key")
-                        && req.bucket() == Some("bucket")
-                        && req.version_id().is_none()
-                })
-                .then_error(move || GetObjectTaggingError::unhandled("unhandled")),
         ]);
 
-        let mut result = collecter.collect().await.unwrap();
-        let EventSourceType::S3(events) = &mut result.event_type else {
-            panic!();
-        };
-        assert!(events.ingest_ids[0].is_none());
-
+        let result = collecter.collect().await.unwrap();
         client.ingest(result.event_type).await.unwrap();
 
         let s3_object_results = s3_object_results(&pool).await;
         assert_eq!(s3_object_results.len(), 1);
-        assert!(
-            s3_object_results[0]
-                .get::<Option<Uuid>, _>("ingest_id")
-                .is_none()
+        assert_eq!(
+            s3_object_results[0].get::<Option<String>, _>("server_side_encryption"),
+            Some("aws:kms".to_string())
+        );
+        assert_eq!(
+            s3_object_results[0].get::<Option<String>, _>("sse_kms_key_id"),
+            Some("kms_key_id".to_string())
+        );
+    }
+
+    #[test]
+    fn is_e_tag_mismatch() {
+        assert!(!Collecter::is_e_tag_mismatch(
+            Some(EXPECTED_E_TAG),
+            Some(EXPECTED_QUOTED_E_TAG)
+        ));
+        assert!(!Collecter::is_e_tag_mismatch(None, Some("\"changed\"")));
+        assert!(!Collecter::is_e_tag_mismatch(Some(EXPECTED_E_TAG), None));
+        assert!(Collecter::is_e_tag_mismatch(
+            Some(EXPECTED_QUOTED_E_TAG),
+            Some("\"changed\"")
+        ));
+    }
+
+    #[test]
+    fn is_version_id_mismatch() {
+        assert!(!Collecter::is_version_id_mismatch(
+            "version",
+            Some("version"),
+            "null"
+        ));
+        assert!(!Collecter::is_version_id_mismatch("version", None, "null"));
+        assert!(!Collecter::is_version_id_mismatch(
+            "null",
+            Some("null"),
+            "null"
+        ));
+        assert!(!Collecter::is_version_id_mismatch(
+            "unversioned",
+            Some("null"),
+            "unversioned"
+        ));
+        assert!(Collecter::is_version_id_mismatch(
+            "null",
+            Some("version"),
+            "null"
+        ));
+        assert!(Collecter::is_version_id_mismatch(
+            "version",
+            Some("changed"),
+            "null"
+        ));
+    }
+
+    #[tokio::test]
+    async fn head_version_id_mismatch() {
+        let head = || {
+            mock_s3(&[head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                HeadObjectOutput::builder()
+                    .version_id("changed")
+                    .e_tag("\"changed\"")
+                    .content_length(1)
+                    .build(),
+            )])
+        };
+        let event = expected_s3_event_message()
+            .with_version_id(default_version_id())
+            .with_e_tag(Some(EXPECTED_E_TAG.to_string()));
+
+        // By default, the metadata from the other version is not attached to the event.
+        let result = Collecter::head(&Default::default(), &head(), event.clone()).await;
+        assert_eq!(result, event);
+
+        let config = Config {
+            ingester_version_mismatch_mode: VersionMismatchMode::Flag,
+            ..Default::default()
+        };
+        let result = Collecter::head(&config, &head(), event.clone()).await;
+        assert!(result.is_e_tag_mismatch);
+        assert_eq!(result.e_tag, Some("\"changed\"".to_string()));
+        assert_eq!(result.size, Some(1));
+    }
+
+    #[tokio::test]
+    async fn head_multipart_checksum() {
+        let page = multipart_page(None, &[(1, "a"), (2, "b"), (3, "c")]);
+        let client = |e_tag: &str| {
+            mock_s3(&[
+                head_expectation(
+                    "key".to_string(),
+                    default_version_id(),
+                    HeadObjectOutput::builder()
+                        .content_length(3)
+                        .e_tag(e_tag)
+                        .build(),
+                ),
+                page.clone(),
+            ])
+        };
+        let event = expected_s3_event_message().with_version_id(default_version_id());
+        let config = Config {
+            ingester_multipart_checksum_max_parts: Some(3),
+            ..Default::default()
+        };
+
+        // The checksum is the checksum of the concatenated part checksums.
+        let result = Collecter::head(&config, &client("\"abc-3\""), event.clone()).await;
+        assert_eq!(result.sha256, Some(expected_multipart_checksum()));
+        assert_eq!(page.num_calls(), 1);
+
+        // Objects with more parts than the maximum are not reconstructed.
+        let result = Collecter::head(&config, &client("\"abc-4\""), event.clone()).await;
+        assert_eq!(result.sha256, None);
+
+        // Objects which are not multipart objects are not reconstructed.
+        let result = Collecter::head(&config, &client("\"abc\""), event.clone()).await;
+        assert_eq!(result.sha256, None);
+
+        // Reconstruction is disabled by default.
+        let result = Collecter::head(&Default::default(), &client("\"abc-3\""), event).await;
+        assert_eq!(result.sha256, None);
+
+        // Only the object with 3 parts within the maximum fetches its parts.
+        assert_eq!(page.num_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn multipart_checksum_pages() {
+        let first = multipart_page(None, &[(1, "a"), (2, "b")]);
+        let second = multipart_page(Some("2"), &[(3, "c")]);
+        let client = mock_s3(&[first.clone(), second.clone()]);
+        let event = expected_s3_event_message()
+            .with_version_id(default_version_id())
+            .with_e_tag(Some("\"abc-3\"".to_string()));
+
+        // Each page is fetched once, starting after the parts of the previous page.
+        let result = Collecter::multipart_checksum_pages(&client, &event, 3, 2)
+            .await
+            .unwrap();
+        assert_eq!(result, Some(expected_multipart_checksum()));
+        assert_eq!(first.num_calls(), 1);
+        assert_eq!(second.num_calls(), 1);
+
+        // Parts that are not contiguous overlap between pages, so the checksum is not known.
+        let client = mock_s3(&[
+            multipart_page(None, &[(1, "a"), (3, "c")]),
+            multipart_page(Some("2"), &[(3, "c")]),
+        ]);
+        let result = Collecter::multipart_checksum_pages(&client, &event, 3, 2)
+            .await
+            .unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn multipart_parts_count() {
+        assert_eq!(Collecter::multipart_parts_count("\"abc-3\""), Some(3));
+        assert_eq!(Collecter::multipart_parts_count("abc-10"), Some(10));
+        assert_eq!(Collecter::multipart_parts_count("\"abc\""), None);
+        assert_eq!(Collecter::multipart_parts_count("\"abc-0\""), None);
+        assert_eq!(Collecter::multipart_parts_count("\"abc-x\""), None);
+    }
+
+    /// A `GetObjectAttributes` rule which returns the parts after the `marker`, with the SHA256
+    /// checksum of the data of each part.
+    fn multipart_page(marker: Option<&'static str>, parts: &[(i32, &str)]) -> Rule {
+        let parts = parts
+            .iter()
+            .map(|(number, data)| {
+                ObjectPart::builder()
+                    .part_number(*number)
+                    .checksum_sha256(BASE64_STANDARD.encode(Sha256::digest(data)))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        mock!(aws_sdk_s3::Client::get_object_attributes)
+            .match_requests(move |req| {
+                req.key() == Some("key") && req.part_number_marker() == marker
+            })
+            .then_output(move || {
+                GetObjectAttributesOutput::builder()
+                    .object_parts(
+                        GetObjectAttributesParts::builder()
+                            .set_parts(Some(parts.clone()))
+                            .build(),
+                    )
+                    .build()
+            })
+    }
+
+    /// The composite checksum of an object with the parts "a", "b" and "c".
+    fn expected_multipart_checksum() -> String {
+        format!(
+            "{}-3",
+            BASE64_STANDARD.encode(Sha256::digest(
+                [
+                    Sha256::digest("a"),
+                    Sha256::digest("b"),
+                    Sha256::digest("c")
+                ]
+                .concat()
+            ))
+        )
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn collect_custom_default_version_id(pool: PgPool) {
+        let config = Config {
+            ingester_default_version_id: "unversioned".to_string(),
+            ..Default::default()
+        };
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+        // The sentinel should still be sent to S3 as an unversioned request.
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            get_tagging_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_get_object_tagging(Some(Uuid::default())),
+            ),
+        ]);
+
+        let result = collecter.collect().await.unwrap();
+        client.ingest(result.event_type).await.unwrap();
+
+        let results = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+            .filter_all(
+                S3ObjectsFilter {
+                    version_id: Wildcard::new("unversioned".to_string()).into(),
+                    ..Default::default()
+                },
+                true,
+                false,
+            )
+            .unwrap()
+            .all()
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].version_id, "unversioned");
+        assert_eq!(results[0].sha256, Some(EXPECTED_SHA256.to_string()));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn collect_max_event_age(pool: PgPool) {
+        let config = Config {
+            ingester_max_event_age: Some(Duration::days(30)),
+            ..Default::default()
+        };
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        let now = Utc::now();
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message()
+                .with_key("fresh".to_string())
+                .with_version_id(default_version_id())
+                .with_event_time(Some(now)),
+            expected_s3_event_message()
+                .with_key("stale".to_string())
+                .with_version_id(default_version_id())
+                .with_event_time(Some(now - Duration::days(31))),
+        ]);
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "fresh".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            get_tagging_expectation(
+                "fresh".to_string(),
+                default_version_id(),
+                expected_get_object_tagging(Some(Uuid::default())),
+            ),
+        ]);
+
+        let result = collecter.collect().await.unwrap();
+        assert_eq!(result.n_records, 1);
+        client.ingest(result.event_type).await.unwrap();
+
+        let results = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+            .all()
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].key, "fresh");
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn drop_stale_events(pool: PgPool) {
+        let config = Default::default();
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        let now = Utc::now();
+        let stale = expected_s3_event_message()
+            .with_version_id(default_version_id())
+            .with_event_time(Some(now - Duration::days(31)));
+        collecter.raw_events =
+            FlatS3EventMessages(vec![stale.clone().with_sequencer(Some("2".to_string()))]);
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            get_tagging_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_get_object_tagging(Some(Uuid::default())),
+            ),
+        ]);
+        let result = collecter.collect().await.unwrap();
+        client.ingest(result.event_type).await.unwrap();
+
+        // Only stale events with an existing newer sequenced record are kept.
+        let result = Collecter::drop_stale_events(
+            &client,
+            FlatS3EventMessages(vec![
+                stale.clone().with_sequencer(Some("1".to_string())),
+                stale.clone().with_sequencer(Some("3".to_string())),
+                stale.clone().with_key("other".to_string()),
+                stale.clone().with_event_time(Some(now)),
+            ]),
+            Duration::days(30),
+            now,
+        )
+        .await
+        .unwrap()
+        .into_inner();
+
+        assert_eq!(
+            result,
+            vec![
+                stale.clone().with_sequencer(Some("1".to_string())),
+                stale.with_event_time(Some(now)),
+            ]
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn update_events(pool: PgPool) {
+        let config = Default::default();
+        let client = Client::from_pool(pool);
+        let mut collecter = test_collecter(&config, &client).await;
+
+        let events = expected_flat_events_simple().sort_and_dedup();
+
+        collecter.client = s3_client_expectations();
+
+        let mut result = Collecter::update_events(
+            &config,
+            &collecter.client,
+            &client,
+            events,
+            None,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap()
+        .into_inner()
+        .into_iter();
+
+        let first = result.next().unwrap();
+        assert_eq!(first.storage_class, Some(IntelligentTiering));
+        assert_eq!(first.last_modified_date, Some(Default::default()));
+
+        let second = result.next().unwrap();
+        assert_eq!(second.storage_class, None);
+        assert_eq!(second.last_modified_date, None);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn collect_default_attributes(pool: PgPool) {
+        let config = Config {
+            ingester_default_attributes: vec![
+                DefaultAttributes::new(
+                    "bucket".to_string(),
+                    None,
+                    Map::from_iter([
+                        ("project".to_string(), json!("project")),
+                        ("env".to_string(), json!("dev")),
+                    ]),
+                ),
+                DefaultAttributes::new(
+                    "bucket".to_string(),
+                    Some("other/".to_string()),
+                    Map::from_iter([("other".to_string(), json!("other"))]),
+                ),
+            ],
+            ..Default::default()
+        };
+        let client = Client::from_pool(pool);
+        let mut collecter = test_collecter(&config, &client).await;
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message()
+                .with_version_id(EXPECTED_VERSION_ID.to_string())
+                .with_attributes(Some(json!({ "env": "prod" }))),
+        ]);
+        collecter.client = s3_client_expectations();
+
+        let result = collecter.collect().await.unwrap();
+        let EventSourceType::S3(events) = result.event_type else {
+            panic!();
+        };
+        // Attributes from the event take precedence over the defaults.
+        assert_eq!(
+            events.attributes[0],
+            Some(json!({ "project": "project", "env": "prod" }))
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tagging_without_move(pool: PgPool) {
+        let config = Default::default();
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(EXPECTED_VERSION_ID.to_string()),
+        ]);
+        collecter.client = s3_client_expectations();
+
+        let mut result = collecter.collect().await.unwrap();
+        let EventSourceType::S3(events) = &mut result.event_type else {
+            panic!();
+        };
+        assert!(events.ingest_ids[0].is_some());
+        // The object was not tagged, so the ingest_id is assigned by the filemanager.
+        assert_eq!(events.tag_presents[0], Some(false));
+
+        client.ingest(result.event_type).await.unwrap();
+
+        let s3_object_results = s3_object_results(&pool).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert!(
+            s3_object_results[0]
+                .get::<Option<Uuid>, _>("ingest_id")
+                .is_some()
+        );
+        assert_eq!(
+            s3_object_results[0].get::<Option<bool>, _>("tag_present"),
+            Some(false)
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tagging_with_move(pool: PgPool) {
+        let config = Default::default();
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        let ingest_id = UuidGenerator::generate();
+        EntriesBuilder::default()
+            .with_ingest_id(ingest_id)
+            .with_n(1)
+            .build(&client)
+            .await
+            .unwrap();
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            get_tagging_expectation(
+                "key".to_string(),
+                default_version_id(),
+                GetObjectTaggingOutput::builder()
+                    .set_tag_set(Some(vec![
+                        Tag::builder()
+                            .key("ingest_id")
+                            .value(ingest_id.to_string())
+                            .build()
+                            .unwrap(),
+                    ]))
+                    .build()
+                    .unwrap(),
+            ),
+        ]);
+
+        let mut result = collecter.collect().await.unwrap();
+        let EventSourceType::S3(events) = &mut result.event_type else {
+            panic!();
+        };
+        assert!(events.ingest_ids[0].is_some());
+        // The object already had a tag.
+        assert_eq!(events.tag_presents[0], Some(true));
+
+        client.ingest(result.event_type).await.unwrap();
+
+        let s3_object_results = s3_object_results(&pool).await;
+        assert_eq!(s3_object_results.len(), 2);
+        assert_eq!(
+            s3_object_results[0].get::<Option<Uuid>, _>("ingest_id"),
+            Some(ingest_id)
+        );
+        assert_eq!(
+            s3_object_results[1].get::<Option<Uuid>, _>("ingest_id"),
+            Some(ingest_id)
+        );
+
+        let expected_attributes = json!({
+            "attributeId": "0",
+            "nestedId": {
+                "attributeId": "0"
+            }
+        });
+        assert_eq!(
+            s3_object_results[0].get::<Option<Json>, _>("attributes"),
+            Some(expected_attributes.clone())
+        );
+        assert_eq!(
+            s3_object_results[1].get::<Option<Json>, _>("attributes"),
+            Some(expected_attributes)
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tagging_attributes(pool: PgPool) {
+        let config = Config {
+            ingester_tag_attributes: vec!["project".to_string(), "missing".to_string()],
+            ..Default::default()
+        };
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        let tag = |key: &str, value: &str| Tag::builder().key(key).value(value).build().unwrap();
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            get_tagging_expectation(
+                "key".to_string(),
+                default_version_id(),
+                GetObjectTaggingOutput::builder()
+                    .set_tag_set(Some(vec![
+                        tag("ingest_id", &UuidGenerator::generate().to_string()),
+                        tag("project", "project"),
+                        tag("sampleId", "sample"),
+                    ]))
+                    .build()
+                    .unwrap(),
+            ),
+        ]);
+
+        let mut result = collecter.collect().await.unwrap();
+        let EventSourceType::S3(events) = &mut result.event_type else {
+            panic!();
+        };
+        // Only the configured tags are copied into the attributes.
+        assert_eq!(events.attributes[0], Some(json!({ "project": "project" })));
+
+        client.ingest(result.event_type).await.unwrap();
+
+        let s3_object_results = s3_object_results(&pool).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            s3_object_results[0].get::<Option<Json>, _>("attributes"),
+            Some(json!({ "project": "project" }))
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tagging_on_fail(pool: PgPool) {
+        let config = Default::default();
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .match_requests(move |req| {
+                    req.key() == Some("key")
+                        && req.bucket() == Some("bucket")
+                        && req.version_id().is_none()
+                })
+                .then_error(move || GetObjectTaggingError::unhandled("unhandled")),
+        ]);
+
+        let mut result = collecter.collect().await.unwrap();
+        let EventSourceType::S3(events) = &mut result.event_type else {
+            panic!();
+        };
+        assert!(events.ingest_ids[0].is_none());
+        // It is unknown whether the tag was present.
+        assert_eq!(events.tag_presents[0], None);
+
+        client.ingest(result.event_type).await.unwrap();
+
+        let s3_object_results = s3_object_results(&pool).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert!(
+            s3_object_results[0]
+                .get::<Option<Uuid>, _>("ingest_id")
+                .is_none()
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tagging_on_fail_auto_tag(pool: PgPool) {
+        let config = Config {
+            ingester_auto_tag: true,
+            ..Default::default()
+        };
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+
+        let put_tagging = put_tagging_expectation(
+            "key".to_string(),
+            default_version_id(),
+            expected_put_object_tagging(),
+        );
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .match_requests(move |req| {
+                    req.key() == Some("key")
+                        && req.bucket() == Some("bucket")
+                        && req.version_id().is_none()
+                })
+                .then_error(move || GetObjectTaggingError::unhandled("unhandled")),
+            put_tagging.clone(),
+        ]);
+
+        let mut result = collecter.collect().await.unwrap();
+        let EventSourceType::S3(events) = &mut result.event_type else {
+            panic!();
+        };
+        // The existing tags are unknown, so the tag is not written, which would replace them.
+        assert_eq!(put_tagging.num_calls(), 0);
+        assert!(events.ingest_ids[0].is_none());
+        assert_eq!(events.tag_presents[0], None);
+
+        client.ingest(result.event_type).await.unwrap();
+
+        // Instead, the object is recorded so that the tag write is retried.
+        let retries = s3_tag_retry::Entity::find()
+            .all(client.connection_ref())
+            .await
+            .unwrap();
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].status, TagRetryStatus::Pending);
+        assert_eq!(retries[0].key, "key");
+        assert_eq!(retries[0].version_id, default_version_id());
+        assert!(retries[0].last_error.is_some());
+
+        // The retry reads the existing tags before writing the ingest_id tag.
+        let s3_client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .then_output(|| expected_get_object_tagging(None)),
+            put_tagging.clone(),
+        ]);
+        let ingest_id = Collecter::retry_ingest_id_tag(&config, &s3_client, &client, &retries[0])
+            .await
+            .unwrap();
+        assert_eq!(put_tagging.num_calls(), 1);
+
+        let s3_object_results = s3_object_results(&pool).await;
+        assert_eq!(s3_object_results.len(), 1);
+        assert_eq!(
+            s3_object_results[0].get::<Option<Uuid>, _>("ingest_id"),
+            Some(ingest_id)
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tagging_auto_tag(pool: PgPool) {
+        let config = Config {
+            ingester_auto_tag: true,
+            ..Default::default()
+        };
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+
+        let put_tagging = put_tagging_expectation(
+            "key".to_string(),
+            default_version_id(),
+            expected_put_object_tagging(),
+        );
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .then_output(|| expected_get_object_tagging(None)),
+            put_tagging.clone(),
+        ]);
+
+        let mut result = collecter.collect().await.unwrap();
+        let EventSourceType::S3(events) = &mut result.event_type else {
+            panic!();
+        };
+        // A new object without an ingest_id tag is tagged during ingestion.
+        assert_eq!(put_tagging.num_calls(), 1);
+        assert!(events.ingest_ids[0].is_some());
+        assert_eq!(events.tag_presents[0], Some(false));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tagging_on_put_fail_auto_tag(pool: PgPool) {
+        let config = Config {
+            ingester_auto_tag: true,
+            ..Default::default()
+        };
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .then_output(|| expected_get_object_tagging(None)),
+            mock!(aws_sdk_s3::Client::put_object_tagging)
+                .then_error(|| PutObjectTaggingError::unhandled("unhandled")),
+        ]);
+
+        let result = collecter.collect().await.unwrap();
+        client.ingest(result.event_type).await.unwrap();
+
+        // A failed tag write is recorded when auto-tagging, even if tag retries are not enabled.
+        let retries = s3_tag_retry::Entity::find()
+            .all(client.connection_ref())
+            .await
+            .unwrap();
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].key, "key");
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tagging_on_fail_records_retry(pool: PgPool) {
+        let config = Config {
+            ingester_tag_retry: true,
+            ..Default::default()
+        };
+        let client = Client::from_pool(pool.clone());
+        let mut collecter = test_collecter(&config, &client).await;
+
+        collecter.raw_events = FlatS3EventMessages(vec![
+            expected_s3_event_message().with_version_id(default_version_id()),
+        ]);
+        collecter.client = mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                default_version_id(),
+                expected_head_object(),
+            ),
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .then_output(|| expected_get_object_tagging(None)),
+            mock!(aws_sdk_s3::Client::put_object_tagging)
+                .then_error(|| PutObjectTaggingError::unhandled("unhandled")),
+        ]);
+
+        let result = collecter.collect().await.unwrap();
+        client.ingest(result.event_type).await.unwrap();
+
+        // The failed tag write is recorded, and the record has no ingest_id.
+        let retries = s3_tag_retry::Entity::find()
+            .all(client.connection_ref())
+            .await
+            .unwrap();
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].status, TagRetryStatus::Pending);
+        assert_eq!(retries[0].key, "key");
+        assert_eq!(retries[0].version_id, default_version_id());
+        assert!(retries[0].last_error.is_some());
+        let before = s3_object_results(&pool).await;
+        assert!(before[0].get::<Option<Uuid>, _>("ingest_id").is_none());
+
+        // Retrying the tag write succeeds and updates the record.
+        let put_tagging = put_tagging_expectation(
+            "key".to_string(),
+            default_version_id(),
+            expected_put_object_tagging(),
+        );
+        let s3_client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .then_output(|| expected_get_object_tagging(None)),
+            put_tagging.clone(),
+        ]);
+        let ingest_id = Collecter::retry_ingest_id_tag(&config, &s3_client, &client, &retries[0])
+            .await
+            .unwrap();
+        assert_eq!(ingest_id, retries[0].ingest_id);
+        assert_eq!(put_tagging.num_calls(), 1);
+
+        let after = s3_object_results(&pool).await;
+        assert_eq!(
+            after[0].get::<Option<Uuid>, _>("ingest_id"),
+            Some(ingest_id)
         );
     }
 
@@ -1090,8 +2808,9 @@ pub(crate) mod tests {
         mock_sqs(&[sqs_expectation()])
     }
 
-    pub(crate) fn expected_head_object_not_found() -> HeadObjectError {
-        HeadObjectError::NotFound(NotFound::builder().build())
+    /// A `HeadObject` error response, which has a status code but no body.
+    pub(crate) fn expected_head_object_status(status: u16) -> HttpResponse {
+        HttpResponse::new(StatusCode::try_from(status).unwrap(), SdkBody::empty())
     }
 
     pub(crate) async fn test_collecter<'a>(
@@ -2,24 +2,331 @@
 //!
 
 use crate::clients::aws::s3::Client;
+use crate::clock::{Clock, SystemClock};
+use crate::database;
+use crate::database::aws::query::Query;
 use crate::database::entities::sea_orm_active_enums::Reason;
-use crate::error::Result;
+use crate::error::Error::{CrawlCancelled, CrawlError};
+use crate::error::{Error, Result};
+use crate::events::aws::collecter::{Collecter, CrawlOptions};
+use crate::events::aws::inventory::Inventory;
 use crate::events::aws::message::{EventType, default_version_id, quote_e_tag};
-use crate::events::aws::{FlatS3EventMessage, FlatS3EventMessages};
+use crate::events::aws::{FlatS3EventMessage, FlatS3EventMessages, StorageClass};
 use crate::uuid::UuidGenerator;
-use aws_sdk_s3::types::ObjectVersion;
-use chrono::Utc;
+use aws_sdk_s3::operation::get_object_attributes::GetObjectAttributesOutput;
+use aws_sdk_s3::operation::list_object_versions::ListObjectVersionsOutput;
+use aws_sdk_s3::primitives;
+use aws_sdk_s3::types::StorageClass as AwsStorageClass;
+use aws_sdk_s3::types::{ChecksumType, DeleteMarkerEntry, ObjectVersion};
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt, stream};
+use glob::Pattern;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use uuid::Uuid;
+
+/// The default number of prefixes that are crawled concurrently.
+pub const DEFAULT_CRAWL_CONCURRENCY: usize = 8;
+
+/// Which object versions a crawl produces messages for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlMode {
+    /// Only crawl the latest version of each object.
+    #[default]
+    CurrentOnly,
+    /// Crawl every version of each object, so that the non-current versions in a versioned
+    /// bucket are also reconciled. Only the latest version has `is_current_state` set.
+    AllVersions,
+}
+
+/// The progress of a crawl, reported after each listing page is fetched.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CrawlProgress {
+    /// The number of object versions and delete markers listed so far.
+    pub objects: usize,
+    /// The number of listing pages fetched so far.
+    pub pages: usize,
+    /// The key marker of the next page, or `None` if this was the last page.
+    pub next_key_marker: Option<String>,
+}
+
+impl CrawlProgress {
+    /// Add a listing page to the progress.
+    fn add_page(&mut self, page: &ListObjectVersionsOutput) -> &Self {
+        self.objects += page.versions().len() + page.delete_markers().len();
+        self.pages += 1;
+        self.next_key_marker = page.next_key_marker.clone();
+        self
+    }
+}
+
+/// A callback which receives the progress of a crawl.
+#[derive(Clone)]
+struct CrawlProgressCallback(Arc<dyn Fn(CrawlProgress) + Send + Sync>);
+
+impl Debug for CrawlProgressCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("CrawlProgressCallback")
+    }
+}
+
+/// A check of whether a crawl should be cancelled, which is polled between listing pages.
+#[derive(Clone)]
+struct CrawlCancelCheck(Arc<dyn Fn() -> BoxFuture<'static, bool> + Send + Sync>);
+
+impl Debug for CrawlCancelCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("CrawlCancelCheck")
+    }
+}
 
 /// Represents crawl operations.
 #[derive(Debug)]
 pub struct Crawl {
     client: Client,
+    mode: CrawlMode,
+    deadline: Option<DateTime<Utc>>,
+    cancellation: CancellationToken,
+    cancel_check: Option<CrawlCancelCheck>,
+    clock: Arc<dyn Clock>,
+    scope: CrawlScope,
+    concurrency: usize,
+    progress: Option<CrawlProgressCallback>,
+    checksums: bool,
 }
 
 impl Crawl {
     /// Create a new crawl.
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            mode: CrawlMode::default(),
+            deadline: None,
+            cancellation: CancellationToken::new(),
+            cancel_check: None,
+            clock: Arc::new(SystemClock),
+            scope: CrawlScope::default(),
+            concurrency: DEFAULT_CRAWL_CONCURRENCY,
+            progress: None,
+            checksums: false,
+        }
+    }
+
+    /// Set which object versions are crawled. By default, only the latest versions are crawled.
+    pub fn with_mode(mut self, mode: CrawlMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the clock used for the event time of crawl events. By default, this is the system time.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Set a time budget for the crawl, starting from now. Once the budget has been used, the
+    /// crawl stops between listing pages and between update chunks, and returns the markers
+    /// that the crawl can be resumed from.
+    pub fn with_time_budget(mut self, budget: TimeDelta) -> Self {
+        self.deadline = Some(Utc::now() + budget);
+        self
+    }
+
+    /// Get the deadline of the time budget, if any.
+    pub fn deadline(&self) -> Option<DateTime<Utc>> {
+        self.deadline
+    }
+
+    /// Whether the time budget has been used.
+    fn is_past_deadline(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Utc::now() >= deadline)
+    }
+
+    /// Set a token that cancels the crawl. Once it is cancelled, the crawl stops between listing
+    /// pages and returns a `CrawlCancelled` error with the key marker that listing reached.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Set a check which is polled between listing pages, and which cancels the crawl in the same
+    /// way as `with_cancellation` once it returns true. This allows a cancellation to be requested
+    /// outside the process running the crawl, e.g. by storing it with the crawl in the database.
+    pub fn with_cancel_check<F, Fut>(mut self, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        self.cancel_check = Some(CrawlCancelCheck(Arc::new(move || check().boxed())));
+        self
+    }
+
+    /// Whether the crawl has been cancelled.
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Whether the crawl has been cancelled, polling the cancel check if it is not cancelled yet.
+    async fn poll_cancelled(&self) -> bool {
+        if !self.is_cancelled()
+            && let Some(CrawlCancelCheck(check)) = &self.cancel_check
+            && check().await
+        {
+            self.cancellation.cancel();
+        }
+
+        self.is_cancelled()
+    }
+
+    /// Only crawl keys which match at least one of the glob patterns, e.g. `*.bam`. This takes
+    /// precedence over `with_exclude`, so a key which matches an include pattern is crawled
+    /// even if it also matches an exclude pattern.
+    pub fn with_include(mut self, patterns: &[String]) -> Result<Self> {
+        self.scope = self.scope.with_include(patterns)?;
+        Ok(self)
+    }
+
+    /// Skip keys which match any of the glob patterns, e.g. `*/cache/*` or `*.tmp`. Skipped keys
+    /// never become crawl messages, so the `scope` of the crawl should be used when the crawl is
+    /// compared with the database, so that their records are not deleted.
+    pub fn with_exclude(mut self, patterns: &[String]) -> Result<Self> {
+        self.scope = self.scope.with_exclude(patterns)?;
+        Ok(self)
+    }
+
+    /// Only crawl object versions and delete markers that were last modified at or after
+    /// `modified_since`, so that a crawl only reconciles recently changed objects. Versions without
+    /// a last modified date are always crawled. Unmodified objects are missing from the crawl, so
+    /// the `scope` of the crawl should be used when the crawl is compared with the database, so
+    /// that their records are not deleted. By default, all objects are crawled.
+    pub fn with_modified_since(mut self, modified_since: Option<DateTime<Utc>>) -> Self {
+        self.scope = self.scope.with_modified_since(modified_since);
+        self
+    }
+
+    /// Get the keys and modification times that the crawl covers.
+    pub fn scope(&self) -> &CrawlScope {
+        &self.scope
+    }
+
+    /// Whether an object with the `last_modified` date was modified since the cutoff, if any.
+    fn is_modified(&self, last_modified: Option<primitives::DateTime>) -> bool {
+        self.is_modified_since(Collecter::convert_datetime(last_modified))
+    }
+
+    /// Whether an object with the `last_modified` date was modified since the cutoff, if any.
+    fn is_modified_since(&self, last_modified: Option<DateTime<Utc>>) -> bool {
+        self.scope.is_modified_since(last_modified)
+    }
+
+    /// Set the number of prefixes that `crawl_s3_prefixes` lists concurrently. By default, this
+    /// is `DEFAULT_CRAWL_CONCURRENCY`.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set a callback which is called with the progress of the crawl after each listing page is
+    /// fetched, e.g. to log the progress of a long crawl. The callback is called without holding
+    /// the S3 client's concurrency permit. When crawling multiple prefixes, the progress of each
+    /// prefix is reported separately.
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(CrawlProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(CrawlProgressCallback(Arc::new(progress)));
+        self
+    }
+
+    /// Report a listing page to the progress callback, if any.
+    fn report_progress(&self, progress: &Mutex<CrawlProgress>, page: &ListObjectVersionsOutput) {
+        if let Some(CrawlProgressCallback(callback)) = &self.progress {
+            let progress = progress
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .add_page(page)
+                .clone();
+            callback(progress);
+        }
+    }
+
+    /// Set whether the SHA256 checksum of each crawled object is fetched using
+    /// `GetObjectAttributes`. Listing does not return checksums, so by default crawled records
+    /// have no `sha256`. This makes an extra request per object, so it is disabled by default.
+    pub fn with_checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Fetch the checksums of the created messages if checksums are enabled. A failure to fetch
+    /// a checksum is logged, and the message is left without a `sha256`.
+    async fn fill_checksums(&self, messages: Vec<FlatS3EventMessage>) -> Vec<FlatS3EventMessage> {
+        if !self.checksums {
+            return messages;
+        }
+
+        stream::iter(messages)
+            .map(|message| async move {
+                if message.event_type != EventType::Created || message.sha256.is_some() {
+                    return message;
+                }
+
+                match self
+                    .client
+                    .get_object_checksum(&message.key, &message.bucket, &message.version_id)
+                    .await
+                {
+                    Ok(output) => {
+                        let sha256 = Self::sha256(&output);
+                        message.update_sha256(sha256)
+                    }
+                    Err(err) => {
+                        warn!(
+                            "Crawl Warning for {} in {}: {}",
+                            message.key,
+                            message.bucket,
+                            Error::from((err, "GetObjectAttributes".to_string()))
+                        );
+                        message
+                    }
+                }
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await
+    }
+
+    /// Get the SHA256 checksum from a `GetObjectAttributes` response. Unlike `HeadObject`, the
+    /// composite checksum of a multipart object does not contain the number of parts, so it is
+    /// added to match the checksums collected during ingestion.
+    fn sha256(output: &GetObjectAttributesOutput) -> Option<String> {
+        let checksum = output.checksum()?;
+        let sha256 = checksum.checksum_sha256()?;
+
+        let parts = output
+            .object_parts()
+            .and_then(|parts| parts.total_parts_count());
+        match (checksum.checksum_type(), parts) {
+            (Some(ChecksumType::Composite), Some(parts)) if !sha256.contains('-') => {
+                Some(format!("{sha256}-{parts}"))
+            }
+            _ => Some(sha256.to_string()),
+        }
+    }
+
+    /// Whether the key should be crawled according to the include and exclude patterns.
+    fn is_crawled(&self, key: &str) -> bool {
+        self.scope.is_crawled(key)
     }
 
     /// Create a new crawl with a default s3 client.
@@ -27,28 +334,749 @@ impl Crawl {
         Self::new(Client::with_defaults().await)
     }
 
-    /// Crawl S3 and produce the event messages that should be ingested.
+    /// Crawl S3 and produce the event messages that should be ingested. This is an error if
+    /// the crawl does not complete within the time budget or is cancelled, use
+    /// `crawl_s3_partial` to get the messages gathered so far instead.
     pub async fn crawl_s3(
         self,
         bucket: &str,
         prefix: Option<String>,
     ) -> Result<FlatS3EventMessages> {
-        let list = self.client.list_objects(bucket, prefix).await?;
-        let versions = list.versions.unwrap_or_default();
+        let crawl = self.crawl_s3_partial(bucket, prefix, None, None).await;
+        self.complete(bucket, crawl)
+    }
 
-        // We only want to crawl current objects.
-        let messages: Vec<FlatS3EventMessage> = versions
+    /// Crawl each of the prefixes concurrently and merge the event messages. Prefixes can overlap,
+    /// in which case the messages of an object are only included once. This is an error if any
+    /// prefix fails to crawl in the same way as `crawl_s3`.
+    pub async fn crawl_s3_prefixes(
+        self,
+        bucket: &str,
+        prefixes: Vec<String>,
+    ) -> Result<FlatS3EventMessages> {
+        let crawl = &self;
+        let crawls: Vec<_> = stream::iter(prefixes)
+            .map(|prefix| async move {
+                let partial = crawl
+                    .crawl_s3_partial(bucket, Some(prefix), None, None)
+                    .await;
+                crawl.complete(bucket, partial)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        // Messages of each prefix are kept together, so the order of versions within a key is
+        // preserved. Sequencers are generated per key during ingestion, and ids are generated
+        // by UUIDv7, so both remain unique after merging.
+        let messages = crawls
             .into_iter()
-            .filter(|object| object.is_latest.is_some_and(|latest| latest))
-            .map(|object| FlatS3EventMessage::from(object).with_bucket(bucket.to_string()))
+            .map(|crawl| crawl.map(FlatS3EventMessages::into_inner))
+            .flatten_ok()
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unique_by(|message| {
+                (
+                    message.key.clone(),
+                    message.version_id.clone(),
+                    message.event_type.clone(),
+                )
+            })
             .collect();
 
         Ok(FlatS3EventMessages(messages))
     }
+
+    /// Get the messages of a completed crawl, or an error if it did not complete.
+    fn complete(&self, bucket: &str, crawl: PartialCrawl) -> Result<FlatS3EventMessages> {
+        match crawl.error {
+            Some(err) => Err(err),
+            None if !crawl.is_complete() && self.is_cancelled() => {
+                Err(CrawlCancelled(crawl.key_marker))
+            }
+            None if !crawl.is_complete() => Err(CrawlError(format!(
+                "crawl of {bucket} did not complete within the time budget"
+            ))),
+            None => Ok(crawl.messages),
+        }
+    }
+
+    /// Crawl S3 starting from the key and version id markers. If listing fails part-way through,
+    /// the time budget is used, or the crawl is cancelled, this returns the messages from the
+    /// pages that were fetched, along with any error and the markers that the crawl can be
+    /// resumed from.
+    pub async fn crawl_s3_partial(
+        &self,
+        bucket: &str,
+        prefix: Option<String>,
+        key_marker: Option<String>,
+        version_id_marker: Option<String>,
+    ) -> PartialCrawl {
+        let progress = Mutex::new(CrawlProgress::default());
+        let list = self
+            .client
+            .list_objects_partial(
+                bucket,
+                prefix,
+                None,
+                key_marker,
+                version_id_marker,
+                |page| self.report_progress(&progress, page),
+                || async { self.is_past_deadline() || self.poll_cancelled().await },
+            )
+            .await;
+        let messages = self
+            .fill_checksums(self.messages(bucket, list.output, self.clock.now()))
+            .await;
+
+        PartialCrawl {
+            messages: FlatS3EventMessages(messages),
+            error: list.error.map(Into::into),
+            key_marker: list.key_marker,
+            version_id_marker: list.version_id_marker,
+        }
+    }
+
+    /// Crawl a single listing page, starting from the continuation token, or from the start if
+    /// there is no token. Returns the messages of the page and the token of the next page, which
+    /// is `None` once the last page has been crawled. The token can be stored so that a crawl
+    /// which is interrupted, e.g. by a Lambda timeout, can resume from the last page. Chaining
+    /// the pages produces the same messages as `crawl_s3`.
+    pub async fn crawl_s3_page(
+        &self,
+        bucket: &str,
+        prefix: Option<String>,
+        token: Option<CrawlToken>,
+    ) -> Result<(FlatS3EventMessages, Option<CrawlToken>)> {
+        let (key_marker, version_id_marker) = match token {
+            Some(token) if token.bucket != bucket || token.prefix != prefix => {
+                return Err(CrawlError(format!(
+                    "crawl token does not belong to a crawl of {bucket}"
+                )));
+            }
+            Some(token) => (token.key_marker, token.version_id_marker),
+            None => (None, None),
+        };
+
+        let output = self
+            .client
+            .list_objects_page(
+                bucket,
+                prefix.clone(),
+                None,
+                key_marker,
+                version_id_marker,
+                None,
+            )
+            .await?;
+
+        let token = output
+            .is_truncated()
+            .unwrap_or_default()
+            .then(|| CrawlToken {
+                bucket: bucket.to_string(),
+                prefix,
+                key_marker: output.next_key_marker.clone(),
+                version_id_marker: output.next_version_id_marker.clone(),
+            });
+        let messages = self
+            .fill_checksums(self.messages(bucket, output, self.clock.now()))
+            .await;
+
+        Ok((FlatS3EventMessages(messages), token))
+    }
+
+    /// Crawl using an S3 Inventory report rather than listing the bucket, which is faster and
+    /// cheaper for buckets with many objects. The `manifest_url` is an `s3://bucket/key` url of
+    /// the `manifest.json` or `manifest.checksum` of the report, and CSV, Parquet and ORC
+    /// reports are supported. The include, exclude and modified since options are applied to the
+    /// records of the report, however, all versions in the report are crawled regardless of the
+    /// crawl mode, as the report is parsed in the same way as inventory ingestion.
+    pub async fn crawl_from_inventory(&self, manifest_url: &str) -> Result<FlatS3EventMessages> {
+        let (bucket, key) = manifest_url
+            .strip_prefix("s3://")
+            .and_then(|url| url.split_once('/'))
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| {
+                CrawlError(format!(
+                    "invalid inventory manifest url `{manifest_url}`, expected `s3://bucket/key`"
+                ))
+            })?;
+
+        let records = Inventory::new(self.client.clone())
+            .parse_manifest_key(key, bucket)
+            .await?;
+
+        let event_time = self.clock.now();
+        let messages = FlatS3EventMessages::from(records)
+            .replace_default_version_id(self.client.default_version_id())
+            .into_inner()
+            .into_iter()
+            .filter(|message| self.is_crawled(&message.key))
+            .filter(|message| self.is_modified_since(message.last_modified_date))
+            .map(|message| message.with_event_time(Some(event_time)))
+            .collect();
+
+        Ok(FlatS3EventMessages(self.fill_checksums(messages).await))
+    }
+
+    /// Crawl S3 one page at a time, passing the messages to `flush` in chunks once at least
+    /// `threshold` messages are buffered, rather than buffering the whole crawl in memory. Each
+    /// chunk is passed with the range of keys that it covers. The ranges cover all keys without
+    /// overlapping, so the messages of a key are never split across chunks, and chunks are
+    /// flushed in key order. The last chunk is always flushed, even if it is empty. Returns the
+    /// total number of messages. If the crawl is cancelled, chunks that were already flushed are
+    /// kept, and the buffered messages are dropped.
+    pub async fn crawl_s3_chunked<F, Fut>(
+        &self,
+        bucket: &str,
+        prefix: Option<String>,
+        threshold: usize,
+        flush: F,
+    ) -> Result<usize>
+    where
+        F: Fn(FlatS3EventMessages, CrawlKeyRange) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let event_time = self.clock.now();
+        let mut buffer: Vec<FlatS3EventMessage> = vec![];
+        let mut start = None;
+        let mut n_messages = 0;
+        let (mut key_marker, mut version_id_marker) = (None, None);
+        let progress = Mutex::new(CrawlProgress::default());
+
+        loop {
+            if self.poll_cancelled().await {
+                return Err(CrawlCancelled(key_marker));
+            }
+            if self.is_past_deadline() {
+                return Err(CrawlError(format!(
+                    "crawl of {bucket} did not complete within the time budget"
+                )));
+            }
+
+            let output = self
+                .client
+                .list_objects_page(
+                    bucket,
+                    prefix.clone(),
+                    None,
+                    key_marker,
+                    version_id_marker,
+                    None,
+                )
+                .await?;
+            self.report_progress(&progress, &output);
+            let is_truncated = output.is_truncated.is_some_and(|is_truncated| is_truncated);
+            key_marker = output.next_key_marker.clone();
+            version_id_marker = output.next_version_id_marker.clone();
+            buffer.extend(
+                self.fill_checksums(self.messages(bucket, output, event_time))
+                    .await,
+            );
+
+            if !is_truncated {
+                break;
+            }
+
+            // The key at the marker can have more versions on the next page, so it is kept in
+            // the buffer for the next chunk.
+            if buffer.len() >= threshold
+                && let Some(end) = &key_marker
+            {
+                let (chunk, rest): (Vec<_>, Vec<_>) =
+                    buffer.into_iter().partition(|message| message.key < *end);
+                buffer = rest;
+
+                let range = CrawlKeyRange::new(start.replace(end.clone()), Some(end.clone()));
+                n_messages += chunk.len();
+                flush(FlatS3EventMessages(chunk), range).await?;
+            }
+        }
+
+        n_messages += buffer.len();
+        flush(FlatS3EventMessages(buffer), CrawlKeyRange::new(start, None)).await?;
+
+        Ok(n_messages)
+    }
+
+    /// Crawl S3 and compare the listing against the current records in the database, without
+    /// ingesting anything. This returns the objects that a crawl would create, update or delete.
+    /// The storage class and last modified date are taken from the listing, as no `HeadObject`
+    /// calls are made, so an object is only updated if its size, ETag or storage class differs from the record. The
+    /// `options` apply in the same way as when the crawl is ingested, and deletes are found using
+    /// the same comparison, with the scope of this crawl.
+    pub async fn crawl_s3_dry_run(
+        self,
+        database_client: &database::Client,
+        bucket: &str,
+        prefix: Option<String>,
+        options: CrawlOptions,
+    ) -> Result<CrawlDryRun> {
+        let output = self
+            .client
+            .list_objects(bucket, prefix.clone(), None)
+            .await?;
+
+        // The storage class of each object version, which is otherwise fetched by `HeadObject`.
+        let storage_classes: HashMap<_, _> = output
+            .versions()
+            .iter()
+            .filter_map(|version| {
+                let storage_class = StorageClass::from_aws(AwsStorageClass::from(
+                    version.storage_class()?.as_str(),
+                ))?;
+                let version_id = version
+                    .version_id()
+                    .unwrap_or(self.client.default_version_id());
+
+                Some((
+                    (version.key()?.to_string(), version_id.to_string()),
+                    storage_class,
+                ))
+            })
+            .collect();
+        // The last modified date of each object version, used to compare with existing records.
+        let last_modified_dates: HashMap<_, _> = output
+            .versions()
+            .iter()
+            .filter_map(|version| {
+                let version_id = version
+                    .version_id()
+                    .unwrap_or(self.client.default_version_id());
+
+                Some((
+                    (version.key()?.to_string(), version_id.to_string()),
+                    Collecter::convert_datetime(version.last_modified)?,
+                ))
+            })
+            .collect();
+
+        let proposed = self
+            .messages(bucket, output, self.clock.now())
+            .into_iter()
+            .filter(|message| {
+                message.event_type == EventType::Created && options.key_range.contains(&message.key)
+            })
+            .map(|message| {
+                let key = (message.key.clone(), message.version_id.clone());
+                let storage_class = storage_classes.get(&key).cloned();
+                let last_modified_date = last_modified_dates.get(&key).copied();
+                message
+                    .update_storage_class(storage_class)
+                    .update_last_modified_date(last_modified_date)
+            })
+            .collect::<Vec<_>>();
+
+        // The existing state of the objects in the listing.
+        let query = Query::new(database_client.clone());
+        let mut tx = query.transaction().await?;
+        let (buckets, keys, version_ids): (Vec<_>, Vec<_>, Vec<_>) = proposed
+            .iter()
+            .map(|message| {
+                (
+                    message.bucket.clone(),
+                    message.key.clone(),
+                    message.version_id.clone(),
+                )
+            })
+            .collect();
+        let existing: HashMap<_, _> = query
+            .select_current_by_bucket_key(&mut tx, &buckets, &keys, &version_ids)
+            .await?
+            .into_inner()
+            .into_iter()
+            .map(|record| ((record.key.clone(), record.version_id.clone()), record))
+            .collect();
+        tx.commit().await?;
+
+        let mut dry_run = CrawlDryRun::default();
+        for message in &proposed {
+            match existing.get(&(message.key.clone(), message.version_id.clone())) {
+                None => dry_run
+                    .created
+                    .push(CrawlChange::new(message, None, Some(message))),
+                Some(record)
+                    if (record.size != message.size
+                        || record.e_tag != message.e_tag
+                        || message
+                            .storage_class
+                            .as_ref()
+                            .is_some_and(|class| record.storage_class.as_ref() != Some(class)))
+                        && (!options.only_newer
+                            || Collecter::is_newer(
+                                message.last_modified_date,
+                                record.last_modified_date,
+                            )) =>
+                {
+                    // The ingest id is kept, as it is tracked using the object tags.
+                    let message = message.clone().with_ingest_id(record.ingest_id);
+                    dry_run
+                        .updated
+                        .push(CrawlChange::new(&message, Some(record), Some(&message)));
+                }
+                Some(_) => {}
+            }
+        }
+
+        // Records which are missing from the listing would be deleted, using the same comparison
+        // as an ingested crawl.
+        let options = CrawlOptions {
+            scope: self.scope.clone(),
+            ..options
+        };
+        dry_run.deleted = Collecter::crawl_deletes(
+            database_client,
+            FlatS3EventMessages(proposed),
+            bucket.to_string(),
+            prefix,
+            &options,
+        )
+        .await?
+        .iter()
+        .map(|record| CrawlChange::new(record, Some(record), None))
+        .collect();
+
+        Ok(dry_run)
+    }
+
+    /// Convert a listing into crawl messages for the current objects and delete markers.
+    fn messages(
+        &self,
+        bucket: &str,
+        output: ListObjectVersionsOutput,
+        event_time: DateTime<Utc>,
+    ) -> Vec<FlatS3EventMessage> {
+        // Keys where the latest version is a delete marker do not currently exist, so they
+        // should not produce created records. This can happen if a key is deleted between
+        // listing pages, where an older page still reports a version as the latest.
+        let delete_markers: Vec<DeleteMarkerEntry> = output
+            .delete_markers
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|marker| marker.is_latest.is_some_and(|latest| latest))
+            .filter(|marker| marker.key.as_ref().is_none_or(|key| self.is_crawled(key)))
+            .collect();
+        let deleted_keys: HashSet<String> = delete_markers
+            .iter()
+            .filter_map(|marker| marker.key.clone())
+            .collect();
+        let delete_markers = delete_markers
+            .into_iter()
+            .filter(|marker| self.is_modified(marker.last_modified));
+
+        let versions = output
+            .versions
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|object| object.key.as_ref().is_none_or(|key| self.is_crawled(key)))
+            .filter(|object| self.is_modified(object.last_modified))
+            .filter_map(|object| {
+                let is_deleted = object
+                    .key
+                    .as_ref()
+                    .is_some_and(|key| deleted_keys.contains(key));
+                let is_latest = object.is_latest.is_some_and(|latest| latest) && !is_deleted;
+
+                // Non-current versions are only crawled when crawling all versions. A version
+                // of a deleted key is never current.
+                if !is_latest && self.mode == CrawlMode::CurrentOnly {
+                    return None;
+                }
+
+                Some(
+                    FlatS3EventMessage::from_object_version(
+                        object,
+                        self.client.default_version_id(),
+                        event_time,
+                    )
+                    .with_bucket(bucket.to_string())
+                    .with_is_current_state(is_latest),
+                )
+            });
+
+        // The latest delete markers produce deleted records, so that keys which are deleted in
+        // S3 are also deleted in the database.
+        let delete_markers = delete_markers.map(|marker| {
+            FlatS3EventMessage::from_delete_marker(
+                marker,
+                self.client.default_version_id(),
+                event_time,
+            )
+            .with_bucket(bucket.to_string())
+        });
+
+        versions.chain(delete_markers).collect()
+    }
+
+    /// Update the messages of a partial crawl in chunks of at least `chunk_size`, for example,
+    /// using `Collecter::update_events`. The messages of a key are never split across chunks,
+    /// so that all versions of a key are updated together. The time budget is checked between
+    /// chunks. If it has been used, the remaining messages are dropped and the markers are set
+    /// so that the crawl resumes after the last updated key.
+    pub async fn update_partial<F, Fut>(
+        &self,
+        mut crawl: PartialCrawl,
+        chunk_size: usize,
+        update: F,
+    ) -> Result<PartialCrawl>
+    where
+        F: Fn(FlatS3EventMessages) -> Fut,
+        Fut: Future<Output = Result<FlatS3EventMessages>>,
+    {
+        // Listing returns keys in order, however pages are merged in reverse.
+        let mut messages = crawl.messages.into_inner();
+        messages.sort_by(|a, b| a.key.cmp(&b.key));
+
+        // Versions within a key are not in listing order, so a chunk can only end at a key
+        // boundary for the key marker to resume from the next key without skipping versions.
+        let mut chunks: Vec<Vec<FlatS3EventMessage>> = vec![];
+        for (_, versions) in &messages.into_iter().chunk_by(|message| message.key.clone()) {
+            match chunks.last_mut() {
+                Some(chunk) if chunk.len() < chunk_size => chunk.extend(versions),
+                _ => chunks.push(versions.collect_vec()),
+            }
+        }
+
+        let mut updated = vec![];
+        let mut chunks = chunks.into_iter().peekable();
+        while let Some(chunk) = chunks.next() {
+            let key_marker = chunk.last().map(|message| message.key.to_string());
+            updated.extend(update(FlatS3EventMessages(chunk)).await?.into_inner());
+
+            if chunks.peek().is_some() && self.is_past_deadline() {
+                crawl.key_marker = key_marker;
+                crawl.version_id_marker = None;
+                break;
+            }
+        }
+
+        crawl.messages = FlatS3EventMessages(updated);
+        Ok(crawl)
+    }
 }
 
-impl From<ObjectVersion> for FlatS3EventMessage {
-    fn from(object: ObjectVersion) -> Self {
+/// An opaque token which continues a crawl from the next listing page. This is encoded as
+/// base64, and can only be used to continue a crawl of the same bucket and prefix.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrawlToken {
+    bucket: String,
+    prefix: Option<String>,
+    key_marker: Option<String>,
+    version_id_marker: Option<String>,
+}
+
+impl CrawlToken {
+    /// Encode the token as opaque base64.
+    pub fn encode(&self) -> Result<String> {
+        Ok(BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?))
+    }
+
+    /// Decode a token that was encoded with `encode`.
+    pub fn decode(token: &str) -> Result<Self> {
+        BASE64_URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|token| serde_json::from_slice(&token).ok())
+            .ok_or_else(|| CrawlError("invalid crawl token".to_string()))
+    }
+}
+
+/// The keys and modification times that a crawl covers, according to the include, exclude and
+/// modified since options of the crawl. Objects outside the scope are never crawled, so their
+/// records are not deleted when the crawl is compared with the database.
+#[derive(Debug, Default, Clone)]
+pub struct CrawlScope {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    modified_since: Option<DateTime<Utc>>,
+}
+
+impl CrawlScope {
+    /// Only cover keys which match at least one of the glob patterns.
+    pub fn with_include(mut self, patterns: &[String]) -> Result<Self> {
+        self.include = Self::patterns(patterns)?;
+        Ok(self)
+    }
+
+    /// Do not cover keys which match any of the glob patterns.
+    pub fn with_exclude(mut self, patterns: &[String]) -> Result<Self> {
+        self.exclude = Self::patterns(patterns)?;
+        Ok(self)
+    }
+
+    /// Only cover objects that were last modified at or after `modified_since`.
+    pub fn with_modified_since(mut self, modified_since: Option<DateTime<Utc>>) -> Self {
+        self.modified_since = modified_since;
+        self
+    }
+
+    /// Compile glob patterns.
+    fn patterns(patterns: &[String]) -> Result<Vec<Pattern>> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .map_err(|err| CrawlError(format!("invalid glob pattern `{pattern}`: {err}")))
+            })
+            .collect()
+    }
+
+    /// Whether the key is crawled according to the include and exclude patterns.
+    pub fn is_crawled(&self, key: &str) -> bool {
+        if !self.include.is_empty() {
+            return self.include.iter().any(|pattern| pattern.matches(key));
+        }
+
+        !self.exclude.iter().any(|pattern| pattern.matches(key))
+    }
+
+    /// Whether an object with the `last_modified` date was modified since the cutoff, if any.
+    /// Objects without a last modified date are always crawled.
+    pub fn is_modified_since(&self, last_modified: Option<DateTime<Utc>>) -> bool {
+        match (self.modified_since, last_modified) {
+            (Some(modified_since), Some(last_modified)) => last_modified >= modified_since,
+            _ => true,
+        }
+    }
+
+    /// Whether a database record with the key and `last_modified` date is covered by the crawl,
+    /// so that it can be deleted if it is missing from the crawl. A record without a last
+    /// modified date is not covered if there is a cutoff, because the object in S3 may not have
+    /// been modified since the cutoff.
+    pub fn covers(&self, key: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+        let is_modified = match (self.modified_since, last_modified) {
+            (Some(modified_since), Some(last_modified)) => last_modified >= modified_since,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        self.is_crawled(key) && is_modified
+    }
+}
+
+/// A range of keys covered by a chunk of a crawl, from the `start` key inclusive to the `end` key
+/// exclusive. A bound that is not set is unbounded.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CrawlKeyRange {
+    /// The first key in the range.
+    pub start: Option<String>,
+    /// The key after the end of the range.
+    pub end: Option<String>,
+}
+
+impl CrawlKeyRange {
+    /// Create a new key range.
+    pub fn new(start: Option<String>, end: Option<String>) -> Self {
+        Self { start, end }
+    }
+
+    /// Whether the key is in the range.
+    pub fn contains(&self, key: &str) -> bool {
+        self.start.as_deref().is_none_or(|start| key >= start)
+            && self.end.as_deref().is_none_or(|end| key < end)
+    }
+}
+
+/// The state of an object that is compared by a crawl dry run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrawlObjectState {
+    /// The storage class of the object.
+    pub storage_class: Option<StorageClass>,
+    /// The size of the object.
+    pub size: Option<i64>,
+    /// The ingest id of the object.
+    pub ingest_id: Option<Uuid>,
+}
+
+impl From<&FlatS3EventMessage> for CrawlObjectState {
+    fn from(message: &FlatS3EventMessage) -> Self {
+        Self {
+            storage_class: message.storage_class.clone(),
+            size: message.size,
+            ingest_id: message.ingest_id,
+        }
+    }
+}
+
+/// An object that a crawl would change, with the existing state in the database and the state
+/// that the crawl would propose.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrawlChange {
+    /// The bucket of the object.
+    pub bucket: String,
+    /// The key of the object.
+    pub key: String,
+    /// The version id of the object.
+    pub version_id: String,
+    /// The existing state, which is not set for created objects.
+    pub existing: Option<CrawlObjectState>,
+    /// The proposed state, which is not set for deleted objects.
+    pub proposed: Option<CrawlObjectState>,
+}
+
+impl CrawlChange {
+    /// Create a change for the object of the message.
+    pub fn new(
+        message: &FlatS3EventMessage,
+        existing: Option<&FlatS3EventMessage>,
+        proposed: Option<&FlatS3EventMessage>,
+    ) -> Self {
+        Self {
+            bucket: message.bucket.clone(),
+            key: message.key.clone(),
+            version_id: message.version_id.clone(),
+            existing: existing.map(Into::into),
+            proposed: proposed.map(Into::into),
+        }
+    }
+}
+
+/// The result of a crawl dry run, describing the objects that a crawl would create, update or
+/// delete.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CrawlDryRun {
+    /// Objects in S3 which do not have a current record.
+    pub created: Vec<CrawlChange>,
+    /// Objects in S3 with a current record that has a different size, ETag or storage class.
+    pub updated: Vec<CrawlChange>,
+    /// Current records which are missing from S3.
+    pub deleted: Vec<CrawlChange>,
+}
+
+/// The result of a crawl which may have failed part-way through listing objects.
+#[derive(Debug)]
+pub struct PartialCrawl {
+    /// The messages from the pages that were successfully listed.
+    pub messages: FlatS3EventMessages,
+    /// The error that stopped the crawl, if any.
+    pub error: Option<Error>,
+    /// The key marker to resume the crawl from.
+    pub key_marker: Option<String>,
+    /// The version id marker to resume the crawl from.
+    pub version_id_marker: Option<String>,
+}
+
+impl PartialCrawl {
+    /// Whether the crawl listed all objects without an error or running out of time.
+    pub fn is_complete(&self) -> bool {
+        self.error.is_none() && self.key_marker.is_none() && self.version_id_marker.is_none()
+    }
+}
+
+impl FlatS3EventMessage {
+    /// Convert an object version into a crawl message, using the `default_version_id` for
+    /// unversioned objects. The `event_time` is the time of the crawl, as object versions do
+    /// not have an event time.
+    pub fn from_object_version(
+        object: ObjectVersion,
+        default_version_id: &str,
+        event_time: DateTime<Utc>,
+    ) -> Self {
         let ObjectVersion {
             key,
             e_tag,
@@ -66,7 +1094,7 @@ impl From<ObjectVersion> for FlatS3EventMessage {
 
         Self {
             s3_object_id: UuidGenerator::generate(),
-            event_time: Some(Utc::now()),
+            event_time: Some(event_time),
             // This is set later.
             bucket: "".to_string(),
             key: key.unwrap_or_default(),
@@ -74,7 +1102,7 @@ impl From<ObjectVersion> for FlatS3EventMessage {
             e_tag: e_tag.map(quote_e_tag),
             // Set this to null to generate a sequencer.
             sequencer: None,
-            version_id: version_id.unwrap_or_else(default_version_id),
+            version_id: version_id.unwrap_or_else(|| default_version_id.to_string()),
             // Head fields are fetched later.
             storage_class: None,
             last_modified_date: None,
@@ -87,15 +1115,52 @@ impl From<ObjectVersion> for FlatS3EventMessage {
             archive_status: None,
             ingest_id: None,
             attributes: None,
+            is_e_tag_mismatch: false,
+            server_side_encryption: None,
+            sse_kms_key_id: None,
+            tag_present: None,
+            restore_expiry_date: None,
+            is_sequencer_conflict: false,
             number_duplicate_events: 0,
             number_reordered: 0,
         }
+        .replace_default_version_id(default_version_id)
+    }
+
+    /// Convert the latest delete marker of a key into a deleted crawl message, using the
+    /// `default_version_id` if the marker has no version id. The `event_time` is the time of the
+    /// crawl.
+    pub fn from_delete_marker(
+        marker: DeleteMarkerEntry,
+        default_version_id: &str,
+        event_time: DateTime<Utc>,
+    ) -> Self {
+        let DeleteMarkerEntry {
+            key, version_id, ..
+        } = marker;
+
+        FlatS3EventMessage::new_with_generated_id()
+            .with_event_time(Some(event_time))
+            .with_key(key.unwrap_or_default())
+            .with_version_id(version_id.unwrap_or_else(|| default_version_id.to_string()))
+            .with_event_type(EventType::Deleted)
+            .with_is_delete_marker(true)
+            .with_is_current_state(false)
+            .with_reason(Reason::CrawlDeleteMarker)
+            .replace_default_version_id(default_version_id)
+    }
+}
+
+impl From<ObjectVersion> for FlatS3EventMessage {
+    fn from(object: ObjectVersion) -> Self {
+        Self::from_object_version(object, &default_version_id(), SystemClock.now())
     }
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
+    use crate::clock::FixedClock;
     use crate::database;
     use crate::database::Ingest;
     use crate::database::aws::ingester::tests::test_ingester;
@@ -104,22 +1169,28 @@ pub(crate) mod tests {
     use crate::database::entities::s3_object::{ActiveModel, Column, Entity};
     use crate::database::entities::sea_orm_active_enums;
     use crate::database::entities::sea_orm_active_enums::ArchiveStatus;
-    use crate::env::Config;
+    use crate::env::{Config, DefaultAttributes};
     use crate::events::Collect;
     use crate::events::EventSourceType;
     use crate::events::aws::StorageClass::{IntelligentTiering, Standard};
-    use crate::events::aws::collecter::CollecterBuilder;
     use crate::events::aws::collecter::tests::{
         expected_put_object_tagging, get_tagging_expectation, head_expectation, mock_s3,
         put_tagging_expectation, test_collecter,
     };
+    use crate::events::aws::collecter::{CollecterBuilder, CrawlOptions};
+    use crate::events::aws::inventory::tests::{
+        MANIFEST_BUCKET, csv_manifest_from_key_expectations,
+    };
     use crate::events::aws::message::EventType::{Created, Deleted};
     use crate::events::aws::tests::{EXPECTED_QUOTED_E_TAG, EXPECTED_SHA256};
     use crate::events::aws::{StorageClass, TransposedS3EventMessages};
     use crate::routes::crawl::tests::crawl_expectations;
+    use aws_sdk_s3::operation::get_object_attributes::GetObjectAttributesError;
     use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingOutput;
     use aws_sdk_s3::operation::head_object::HeadObjectOutput;
-    use aws_sdk_s3::operation::list_object_versions::ListObjectVersionsOutput;
+    use aws_sdk_s3::operation::list_object_versions::{
+        ListObjectVersionsError, ListObjectVersionsOutput,
+    };
     use aws_sdk_s3::types;
     use aws_sdk_s3::types::Tag;
     use aws_smithy_mocks::{Rule, RuleMode};
@@ -127,11 +1198,255 @@ pub(crate) mod tests {
     use itertools::Itertools;
     use sea_orm::{EntityTrait, QueryOrder};
     use sea_orm::{NotSet, Set};
-    use serde_json::json;
+    use serde_json::{Map, json};
     use sqlx::{Executor, PgPool, Row};
     use std::str::FromStr;
     use uuid::Uuid;
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_messages_fixed_clock(pool: PgPool) {
+        let client = database::Client::from_pool(pool);
+        let now: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+
+        // An existing record which is missing from the crawl, so that it gets deleted.
+        client
+            .ingest(EventSourceType::S3(TransposedS3EventMessages::from(
+                FlatS3EventMessages(vec![
+                    FlatS3EventMessage::new_with_generated_id()
+                        .with_key("key2".to_string())
+                        .with_bucket("bucket".to_string())
+                        .with_sequencer(Some("1".to_string()))
+                        .with_event_time(Some(DateTime::default()))
+                        .with_version_id(default_version_id())
+                        .with_is_current_state(true),
+                ]),
+            )))
+            .await
+            .unwrap();
+
+        let config = Config::default();
+        let mut collecter = test_collecter(&config, &client).await;
+        collecter.set_client(crawl_expectations(vec![default_version_id()]));
+        collecter.set_crawl_bucket("bucket".to_string());
+        collecter.set_crawl_options(CrawlOptions {
+            clock: Arc::new(FixedClock::new(now)),
+            ..Default::default()
+        });
+
+        let result = Crawl::new(collecter.client().clone())
+            .with_clock(FixedClock::new(now))
+            .crawl_s3("bucket", None)
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(result.iter().all(|event| event.event_time == Some(now)));
+
+        collecter.set_raw_events(FlatS3EventMessages(result));
+        let result = collecter.collect().await.unwrap();
+        client.ingest(result.event_type).await.unwrap();
+
+        // All crawl events, including the deleted event, have the fixed event time.
+        let results = fetch_results(&client).await;
+        let crawled = results
+            .iter()
+            .filter(|result| result.reason == Reason::Crawl)
+            .collect_vec();
+        assert_eq!(crawled.len(), 3);
+        assert!(crawled.iter().all(|result| result.event_time == Some(now)));
+        assert!(
+            crawled
+                .iter()
+                .any(|result| result.key == "key2" && result.event_type == Deleted)
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_dry_run(pool: PgPool) {
+        let client = database::Client::from_pool(pool);
+
+        let existing = |key: &str, size| {
+            FlatS3EventMessage::new_with_generated_id()
+                .with_key(key.to_string())
+                .with_bucket("bucket".to_string())
+                .with_sequencer(Some("1".to_string()))
+                .with_event_time(Some(DateTime::default()))
+                .with_e_tag(Some(EXPECTED_QUOTED_E_TAG.to_string()))
+                .with_version_id(default_version_id())
+                .with_size(Some(size))
+                .with_storage_class(Some(Standard))
+                .with_ingest_id(Some(Uuid::default()))
+                .with_is_current_state(true)
+        };
+        client
+            .ingest(EventSourceType::S3(TransposedS3EventMessages::from(
+                FlatS3EventMessages(vec![existing("key1", 5), existing("key2", 1)]),
+            )))
+            .await
+            .unwrap();
+
+        let result = Crawl::new(list_object_expectations(&[], vec![default_version_id()]))
+            .crawl_s3_dry_run(&client, "bucket", None, CrawlOptions::default())
+            .await
+            .unwrap();
+
+        let state = |size, storage_class| CrawlObjectState {
+            storage_class,
+            size: Some(size),
+            ingest_id: Some(Uuid::default()),
+        };
+        assert_eq!(
+            result.created,
+            vec![CrawlChange {
+                bucket: "bucket".to_string(),
+                key: "key".to_string(),
+                version_id: default_version_id(),
+                existing: None,
+                proposed: Some(CrawlObjectState {
+                    storage_class: None,
+                    size: Some(1),
+                    ingest_id: None,
+                }),
+            }]
+        );
+        assert_eq!(
+            result.updated,
+            vec![CrawlChange {
+                bucket: "bucket".to_string(),
+                key: "key1".to_string(),
+                version_id: default_version_id(),
+                existing: Some(state(5, Some(Standard))),
+                proposed: Some(state(2, None)),
+            }]
+        );
+        assert_eq!(
+            result.deleted,
+            vec![CrawlChange {
+                bucket: "bucket".to_string(),
+                key: "key2".to_string(),
+                version_id: default_version_id(),
+                existing: Some(state(1, Some(Standard))),
+                proposed: None,
+            }]
+        );
+
+        // Nothing is ingested by a dry run.
+        assert_eq!(fetch_results(&client).await.len(), 2);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_dry_run_options(pool: PgPool) {
+        let client = database::Client::from_pool(pool);
+
+        let existing = |key: &str, size| {
+            FlatS3EventMessage::new_with_generated_id()
+                .with_key(key.to_string())
+                .with_bucket("bucket".to_string())
+                .with_sequencer(Some("1".to_string()))
+                .with_event_time(Some(DateTime::default()))
+                .with_e_tag(Some(EXPECTED_QUOTED_E_TAG.to_string()))
+                .with_version_id(default_version_id())
+                .with_size(Some(size))
+                .with_last_modified_date(DateTime::from_timestamp(4102444800, 0))
+                .with_is_current_state(true)
+        };
+        client
+            .ingest(EventSourceType::S3(TransposedS3EventMessages::from(
+                FlatS3EventMessages(vec![existing("key1", 5), existing("key2", 1)]),
+            )))
+            .await
+            .unwrap();
+
+        let dry_run = |crawl: Crawl, options| {
+            let client = client.clone();
+            async move {
+                crawl
+                    .crawl_s3_dry_run(&client, "bucket", None, options)
+                    .await
+                    .unwrap()
+            }
+        };
+        let crawl = || Crawl::new(list_object_expectations(&[], vec![default_version_id()]));
+        let keys = |changes: Vec<CrawlChange>| {
+            changes
+                .into_iter()
+                .map(|change| change.key)
+                .collect::<Vec<_>>()
+        };
+
+        // Records that are missing from the crawl are not deleted if deletes are skipped.
+        let result = dry_run(
+            crawl(),
+            CrawlOptions {
+                skip_deletes: true,
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(keys(result.updated), vec!["key1"]);
+        assert!(result.deleted.is_empty());
+
+        // Records outside the scope of the crawl are not deleted.
+        let result = dry_run(
+            crawl().with_exclude(&["key2".to_string()]).unwrap(),
+            Default::default(),
+        )
+        .await;
+        assert!(result.deleted.is_empty());
+        let result = dry_run(
+            crawl().with_modified_since(DateTime::from_timestamp(4102444800, 1)),
+            Default::default(),
+        )
+        .await;
+        assert!(result.deleted.is_empty());
+
+        // Only keys in the key range are compared.
+        let result = dry_run(
+            crawl(),
+            CrawlOptions {
+                key_range: CrawlKeyRange::new(None, Some("key2".to_string())),
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(keys(result.created), vec!["key"]);
+        assert_eq!(keys(result.updated), vec!["key1"]);
+        assert!(result.deleted.is_empty());
+
+        // Records are not updated if they are newer than the listing.
+        let older = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::list_object_versions).then_output(|| {
+                    ListObjectVersionsOutput::builder()
+                        .versions(
+                            ObjectVersion::builder()
+                                .key("key1")
+                                .size(2)
+                                .is_latest(true)
+                                .e_tag(EXPECTED_QUOTED_E_TAG)
+                                .last_modified(primitives::DateTime::from_secs(0))
+                                .build(),
+                        )
+                        .build()
+                })
+            ]
+        ));
+        let options = || CrawlOptions {
+            only_newer: true,
+            ..Default::default()
+        };
+        let result = dry_run(Crawl::new(older), options()).await;
+        assert!(result.updated.is_empty());
+        assert_eq!(keys(result.deleted), vec!["key2"]);
+
+        // Records that cannot be compared are updated.
+        let result = dry_run(crawl(), options()).await;
+        assert_eq!(keys(result.updated), vec!["key1"]);
+
+        assert_eq!(fetch_results(&client).await.len(), 2);
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn crawl_messages(pool: PgPool) {
         let client = database::Client::from_pool(pool);
@@ -188,7 +1503,8 @@ pub(crate) mod tests {
                 .with_sequencer(Some(
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
-                .with_reason(Reason::Crawl),
+                .with_reason(Reason::Crawl)
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(results[1].clone(), expected_unaffected_record_two());
     }
@@ -255,6 +1571,49 @@ pub(crate) mod tests {
         assert_eq_event(results[1].clone(), expected_unaffected_record_two());
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_messages_default_attributes(pool: PgPool) {
+        let client = database::Client::from_pool(pool);
+        let config = Config {
+            ingester_default_attributes: vec![DefaultAttributes::new(
+                "bucket".to_string(),
+                None,
+                Map::from_iter([("project".to_string(), json!("project"))]),
+            )],
+            ..Default::default()
+        };
+
+        let event = FlatS3EventMessage::new_with_generated_id()
+            .with_key("key".to_string())
+            .with_bucket("bucket".to_string())
+            .with_sequencer(Some("000000000000000000000000000000".to_string()))
+            .with_storage_class(Some(StorageClass::IntelligentTiering))
+            .with_ingest_id(Some(Uuid::default()))
+            .with_archive_status(Some(ArchiveStatus::DeepArchiveAccess))
+            .with_e_tag(Some(EXPECTED_QUOTED_E_TAG.to_string()))
+            .with_last_modified_date(Some("1970-01-01 00:00:00.000000 +00:00".parse().unwrap()))
+            .with_version_id(default_version_id())
+            .with_size(Some(1))
+            .with_is_current_state(true)
+            .with_sha256(Some(EXPECTED_SHA256.to_string()));
+        let results = ingest_crawl_with_config(
+            client,
+            event.clone(),
+            vec![default_version_id()],
+            Default::default(),
+            &config,
+        )
+        .await;
+
+        // The existing record is unchanged, and only the new record gets the defaults.
+        assert_eq!(results.len(), 2);
+        assert_eq_event(results[0].clone(), event);
+        assert_eq_event(
+            results[1].clone(),
+            expected_unaffected_record_two().with_attributes(Some(json!({ "project": "project" }))),
+        );
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn crawl_messages_update_field(pool: PgPool) {
         let client = database::Client::from_pool(pool);
@@ -282,7 +1641,8 @@ pub(crate) mod tests {
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
                 .with_reason(Reason::Crawl)
-                .with_storage_class(Some(StorageClass::IntelligentTiering)),
+                .with_storage_class(Some(StorageClass::IntelligentTiering))
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(results[2].clone(), expected_unaffected_record_two());
 
@@ -309,7 +1669,8 @@ pub(crate) mod tests {
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
                 .with_reason(Reason::Crawl)
-                .with_ingest_id(Some(Uuid::default())),
+                .with_ingest_id(Some(Uuid::default()))
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(results[2].clone(), expected_unaffected_record_two());
 
@@ -336,6 +1697,7 @@ pub(crate) mod tests {
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
                 .with_reason(Reason::Crawl)
+                .with_tag_present(Some(true))
                 .with_archive_status(Some(ArchiveStatus::DeepArchiveAccess)),
         );
         assert_eq_event(results[2].clone(), expected_unaffected_record_two());
@@ -363,6 +1725,7 @@ pub(crate) mod tests {
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
                 .with_reason(Reason::Crawl)
+                .with_tag_present(Some(true))
                 .with_e_tag(Some(EXPECTED_QUOTED_E_TAG.to_string())),
         );
         assert_eq_event(results[2].clone(), expected_unaffected_record_two());
@@ -390,7 +1753,8 @@ pub(crate) mod tests {
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
                 .with_last_modified_date(Some("1970-01-01 00:00:00.000000 +00:00".parse().unwrap()))
-                .with_reason(Reason::Crawl),
+                .with_reason(Reason::Crawl)
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(results[2].clone(), expected_unaffected_record_two());
 
@@ -417,7 +1781,8 @@ pub(crate) mod tests {
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
                 .with_size(Some(1))
-                .with_reason(Reason::Crawl),
+                .with_reason(Reason::Crawl)
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(results[2].clone(), expected_unaffected_record_two());
 
@@ -444,6 +1809,7 @@ pub(crate) mod tests {
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
                 .with_reason(Reason::Crawl)
+                .with_tag_present(Some(true))
                 .with_sha256(Some(EXPECTED_SHA256.to_string())),
         );
         assert_eq_event(results[2].clone(), expected_unaffected_record_two());
@@ -473,10 +1839,62 @@ pub(crate) mod tests {
                     "000000000000000000000000000000-0100000000000000".to_string(),
                 ))
                 .with_reason(Reason::Crawl)
+                .with_tag_present(Some(true))
                 .with_attributes(Some(json!({ "attribute": "1" }))),
         );
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_messages_only_newer(pool: PgPool) {
+        let client = database::Client::from_pool(pool);
+
+        // The listed object has the same last modified date as the database record, so it is
+        // skipped even though the storage class differs.
+        let event = FlatS3EventMessage::new_with_generated_id()
+            .with_key("key".to_string())
+            .with_bucket("bucket".to_string())
+            .with_sequencer(Some("000000000000000000000000000000".to_string()))
+            .with_storage_class(None)
+            .with_ingest_id(Some(Uuid::default()))
+            .with_archive_status(Some(ArchiveStatus::DeepArchiveAccess))
+            .with_e_tag(Some(EXPECTED_QUOTED_E_TAG.to_string()))
+            .with_last_modified_date(Some("1970-01-01 00:00:00.000000 +00:00".parse().unwrap()))
+            .with_version_id(default_version_id())
+            .with_size(Some(1))
+            .with_is_current_state(true)
+            .with_sha256(Some(EXPECTED_SHA256.to_string()));
+        let options = CrawlOptions {
+            only_newer: true,
+            ..Default::default()
+        };
+        let results = ingest_crawl_with_options(
+            client.clone(),
+            event.clone(),
+            vec![default_version_id()],
+            options.clone(),
+        )
+        .await;
+        assert_eq!(results.len(), 2);
+        assert_eq_event(results[0].clone(), event.clone());
+        assert_eq_event(results[1].clone(), expected_unaffected_record_two());
+
+        // The database record is older, so the listed object updates it.
+        let event = event
+            .with_last_modified_date(Some("1969-12-31 00:00:00.000000 +00:00".parse().unwrap()));
+        let results = ingest_crawl_with_options(
+            client.clone(),
+            event.clone(),
+            vec![default_version_id()],
+            options,
+        )
+        .await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], event.with_is_current_state(false));
+        assert!(results.iter().any(|result| result.key == "key"
+            && result.is_current_state
+            && result.reason == Reason::Crawl));
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn crawl_messages_delete_from_database(pool: PgPool) {
         let client = database::Client::from_pool(pool);
@@ -501,22 +1919,149 @@ pub(crate) mod tests {
         assert_eq_event(results[1].clone(), expected_unaffected_record_one());
         assert_eq_event(results[2].clone(), expected_unaffected_record_two());
 
-        let mut deleted = results[3].clone();
-        assert!(deleted.event_time.is_some());
-        deleted.event_time = None;
-        deleted.s3_object_id = event.s3_object_id;
+        let mut deleted = results[3].clone();
+        assert!(deleted.event_time.is_some());
+        deleted.event_time = None;
+        deleted.s3_object_id = event.s3_object_id;
+
+        assert_eq!(
+            deleted,
+            event
+                .clone()
+                .with_event_type(Deleted)
+                .with_is_current_state(false)
+                .with_sequencer(Some(
+                    "000000000000000000000000000000-0100000000000000".to_string()
+                ))
+                .with_reason(Reason::Crawl)
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_messages_skip_deletes(pool: PgPool) {
+        let client = database::Client::from_pool(pool);
+
+        let event = FlatS3EventMessage::new_with_generated_id()
+            .with_key("key2".to_string())
+            .with_bucket("bucket".to_string())
+            .with_sequencer(Some("000000000000000000000000000000".to_string()))
+            .with_storage_class(None)
+            .with_ingest_id(Some(Uuid::default()))
+            .with_archive_status(Some(ArchiveStatus::DeepArchiveAccess))
+            .with_e_tag(Some(EXPECTED_QUOTED_E_TAG.to_string()))
+            .with_last_modified_date(Some("1970-01-01 00:00:00.000000 +00:00".parse().unwrap()))
+            .with_version_id(default_version_id())
+            .with_size(Some(1))
+            .with_is_current_state(true)
+            .with_sha256(Some(EXPECTED_SHA256.to_string()));
+        let results = ingest_crawl_with_options(
+            client.clone(),
+            event.clone(),
+            vec![default_version_id()],
+            CrawlOptions {
+                skip_deletes: true,
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // The record which is missing from the crawl is not deleted.
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], event);
+        assert!(results.iter().all(|result| result.event_type == Created));
+
+        assert_eq_event(results[1].clone(), expected_unaffected_record_one());
+        assert_eq_event(results[2].clone(), expected_unaffected_record_two());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_messages_key_range(pool: PgPool) {
+        let client = database::Client::from_pool(pool);
+
+        let event = FlatS3EventMessage::new_with_generated_id()
+            .with_key("key2".to_string())
+            .with_bucket("bucket".to_string())
+            .with_sequencer(Some("000000000000000000000000000000".to_string()))
+            .with_storage_class(None)
+            .with_ingest_id(Some(Uuid::default()))
+            .with_archive_status(Some(ArchiveStatus::DeepArchiveAccess))
+            .with_e_tag(Some(EXPECTED_QUOTED_E_TAG.to_string()))
+            .with_last_modified_date(Some("1970-01-01 00:00:00.000000 +00:00".parse().unwrap()))
+            .with_version_id(default_version_id())
+            .with_size(Some(1))
+            .with_is_current_state(true)
+            .with_sha256(Some(EXPECTED_SHA256.to_string()));
+        let results = ingest_crawl_with_options(
+            client.clone(),
+            event.clone(),
+            vec![default_version_id()],
+            CrawlOptions {
+                key_range: CrawlKeyRange::new(None, Some("key2".to_string())),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // The record which is missing from the crawl is outside the key range, so it belongs to
+        // another chunk and is not deleted.
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], event);
+        assert!(results.iter().all(|result| result.event_type == Created));
+
+        assert_eq_event(results[1].clone(), expected_unaffected_record_one());
+        assert_eq_event(results[2].clone(), expected_unaffected_record_two());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_messages_scope(pool: PgPool) {
+        let client = database::Client::from_pool(pool);
+
+        let event = FlatS3EventMessage::new_with_generated_id()
+            .with_key("key2".to_string())
+            .with_bucket("bucket".to_string())
+            .with_sequencer(Some("000000000000000000000000000000".to_string()))
+            .with_storage_class(None)
+            .with_ingest_id(Some(Uuid::default()))
+            .with_archive_status(Some(ArchiveStatus::DeepArchiveAccess))
+            .with_e_tag(Some(EXPECTED_QUOTED_E_TAG.to_string()))
+            .with_last_modified_date(Some("1970-01-01 00:00:00.000000 +00:00".parse().unwrap()))
+            .with_version_id(default_version_id())
+            .with_size(Some(1))
+            .with_is_current_state(true)
+            .with_sha256(Some(EXPECTED_SHA256.to_string()));
+
+        // The record which is missing from the crawl is excluded by a pattern, or was not
+        // modified since the cutoff, so the crawl does not cover it and it is not deleted.
+        for scope in [
+            CrawlScope::default()
+                .with_exclude(&["*2".to_string()])
+                .unwrap(),
+            CrawlScope::default()
+                .with_include(&["key".to_string()])
+                .unwrap(),
+            CrawlScope::default().with_modified_since(DateTime::from_timestamp(100, 0)),
+        ] {
+            let results = ingest_crawl_with_options(
+                client.clone(),
+                event.clone(),
+                vec![default_version_id()],
+                CrawlOptions {
+                    scope,
+                    ..Default::default()
+                },
+            )
+            .await;
+
+            assert_eq!(results.len(), 3);
+            assert_eq!(results[0], event);
+            assert!(results.iter().all(|result| result.event_type == Created));
+        }
 
-        assert_eq!(
-            deleted,
-            event
-                .clone()
-                .with_event_type(Deleted)
-                .with_is_current_state(false)
-                .with_sequencer(Some(
-                    "000000000000000000000000000000-0100000000000000".to_string()
-                ))
-                .with_reason(Reason::Crawl)
-        );
+        // A record without a last modified date may not have been modified since the cutoff.
+        let scope = CrawlScope::default().with_modified_since(DateTime::from_timestamp(100, 0));
+        assert!(!scope.covers("key2", None));
+        assert!(scope.covers("key2", DateTime::from_timestamp(100, 0)));
+        assert!(CrawlScope::default().covers("key2", None));
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
@@ -618,7 +2163,8 @@ pub(crate) mod tests {
                 .with_reason(Reason::Crawl)
                 .with_sequencer(Some(
                     "000000000000000000000000000000-0100000000000000".to_string(),
-                )),
+                ))
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(
             results[2].clone(),
@@ -627,7 +2173,8 @@ pub(crate) mod tests {
                 .with_reason(Reason::Crawl)
                 .with_sequencer(Some(
                     "000000000000000000000000000000-0200000000000000".to_string(),
-                )),
+                ))
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(
             results[3].clone(),
@@ -736,7 +2283,8 @@ pub(crate) mod tests {
                 .with_reason(Reason::Crawl)
                 .with_sequencer(Some(
                     "000000000000000000000000000000-0100000000000000".to_string(),
-                )),
+                ))
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(
             results[1].clone(),
@@ -754,7 +2302,8 @@ pub(crate) mod tests {
                 .with_reason(Reason::Crawl)
                 .with_sequencer(Some(
                     "000000000000000000000000000000-0200000000000000".to_string(),
-                )),
+                ))
+                .with_tag_present(Some(true)),
         );
         assert_eq_event(
             results[3].clone(),
@@ -789,6 +2338,955 @@ pub(crate) mod tests {
         test_crawl_record_states(pool, Some("version_id".to_string())).await
     }
 
+    #[tokio::test]
+    async fn list_objects_page() {
+        let page = |key: &'static str, next: Option<&'static str>| {
+            move || {
+                ListObjectVersionsOutput::builder()
+                    .versions(ObjectVersion::builder().key(key).is_latest(true).build())
+                    .is_truncated(next.is_some())
+                    .set_next_key_marker(next.map(|next| next.to_string()))
+                    .set_next_version_id_marker(next.map(|_| "null".to_string()))
+                    .build()
+            }
+        };
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker().is_none())
+                    .then_output(page("key0", Some("key0"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key0"))
+                    .then_output(page("key1", Some("key1"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key1"))
+                    .then_output(page("key2", None)),
+            ]
+        ));
+
+        // Page through each result using the markers.
+        let mut keys = vec![];
+        let (mut key_marker, mut version_id_marker) = (None, None);
+        loop {
+            let page = client
+                .list_objects_page("bucket", None, None, key_marker, version_id_marker, Some(1))
+                .await
+                .unwrap();
+            keys.extend(
+                page.versions()
+                    .iter()
+                    .map(|version| version.key().unwrap().to_string()),
+            );
+
+            if !page.is_truncated().unwrap_or_default() {
+                break;
+            }
+            key_marker = page.next_key_marker;
+            version_id_marker = page.next_version_id_marker;
+        }
+        assert_eq!(keys, vec!["key0", "key1", "key2"]);
+
+        // The unpaged variant should produce the same objects.
+        let all = client.list_objects("bucket", None, None).await.unwrap();
+        assert_eq!(all.versions().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_progress() {
+        let page = |key: &'static str, next: Option<&'static str>| {
+            move || {
+                ListObjectVersionsOutput::builder()
+                    .versions(ObjectVersion::builder().key(key).is_latest(true).build())
+                    .is_truncated(next.is_some())
+                    .set_next_key_marker(next.map(|next| next.to_string()))
+                    .set_next_version_id_marker(next.map(|_| "null".to_string()))
+                    .build()
+            }
+        };
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker().is_none())
+                    .then_output(page("key0", Some("key0"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key0"))
+                    .then_output(page("key1", Some("key1"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key1"))
+                    .then_output(page("key2", None)),
+            ]
+        ));
+
+        let progress = Arc::new(Mutex::new(vec![]));
+        let result = Crawl::new(client)
+            .with_progress({
+                let progress = progress.clone();
+                move |crawl_progress| progress.lock().unwrap().push(crawl_progress)
+            })
+            .crawl_s3("bucket", None)
+            .await
+            .unwrap();
+        assert_eq!(result.into_inner().len(), 3);
+
+        // The callback is called once for each page.
+        let expected = |pages, next_key_marker: Option<&str>| CrawlProgress {
+            objects: pages,
+            pages,
+            next_key_marker: next_key_marker.map(|marker| marker.to_string()),
+        };
+        assert_eq!(
+            *progress.lock().unwrap(),
+            vec![
+                expected(1, Some("key0")),
+                expected(2, Some("key1")),
+                expected(3, None)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_page() {
+        let page = |key: &'static str, next: Option<&'static str>| {
+            move || {
+                ListObjectVersionsOutput::builder()
+                    .versions(ObjectVersion::builder().key(key).is_latest(true).build())
+                    .delete_markers(
+                        DeleteMarkerEntry::builder()
+                            .key(format!("{key}-deleted"))
+                            .is_latest(true)
+                            .build(),
+                    )
+                    .is_truncated(next.is_some())
+                    .set_next_key_marker(next.map(|next| next.to_string()))
+                    .set_next_version_id_marker(next.map(|_| "null".to_string()))
+                    .build()
+            }
+        };
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker().is_none())
+                    .then_output(page("key0", Some("key0"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key0"))
+                    .then_output(page("key1", Some("key1"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key1"))
+                    .then_output(page("key2", None)),
+            ]
+        ));
+        let crawl = || Crawl::new(client.clone()).with_clock(FixedClock::new(DateTime::default()));
+        let fields = |messages: Vec<FlatS3EventMessage>| {
+            messages
+                .into_iter()
+                .map(|message| {
+                    (
+                        message.key,
+                        message.version_id,
+                        message.event_type,
+                        message.is_current_state,
+                        message.event_time,
+                    )
+                })
+                .sorted()
+                .collect_vec()
+        };
+
+        // Chain the pages using the encoded token, as if it was stored between invocations.
+        let mut messages = vec![];
+        let mut token = None;
+        let mut pages = 0;
+        loop {
+            let (page, next) = crawl().crawl_s3_page("bucket", None, token).await.unwrap();
+            messages.extend(page.into_inner());
+            pages += 1;
+
+            match next {
+                Some(next) => token = Some(CrawlToken::decode(&next.encode().unwrap()).unwrap()),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+
+        let expected = crawl().crawl_s3("bucket", None).await.unwrap().into_inner();
+        assert_eq!(messages.len(), 6);
+        assert_eq!(fields(messages), fields(expected));
+
+        // A token can only continue a crawl of the same bucket and prefix.
+        let (_, token) = crawl().crawl_s3_page("bucket", None, None).await.unwrap();
+        assert!(
+            crawl()
+                .crawl_s3_page("bucket", Some("prefix".to_string()), token)
+                .await
+                .is_err()
+        );
+        assert!(CrawlToken::decode("invalid").is_err());
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_partial() {
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker().is_none())
+                    .then_output(|| {
+                        ListObjectVersionsOutput::builder()
+                            .versions(ObjectVersion::builder().key("key0").is_latest(true).build())
+                            .is_truncated(true)
+                            .next_key_marker("key0")
+                            .next_version_id_marker("null")
+                            .build()
+                    }),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key0"))
+                    .then_error(|| ListObjectVersionsError::unhandled("unhandled")),
+            ]
+        ));
+
+        let result = Crawl::new(client.clone())
+            .crawl_s3_partial("bucket", None, None, None)
+            .await;
+
+        // The first page is returned, along with the markers of the failed page.
+        assert_eq!(
+            result
+                .messages
+                .0
+                .iter()
+                .map(|message| message.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["key0"]
+        );
+        assert!(result.error.is_some());
+        assert_eq!(result.key_marker, Some("key0".to_string()));
+        assert_eq!(result.version_id_marker, Some("null".to_string()));
+
+        // The non-partial crawl fails.
+        assert!(Crawl::new(client).crawl_s3("bucket", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_checksums() {
+        let attributes = |key: &'static str, output: fn() -> GetObjectAttributesOutput| {
+            mock!(aws_sdk_s3::Client::get_object_attributes)
+                .match_requests(move |req| req.key() == Some(key))
+                .then_output(output)
+        };
+        let rules = [
+            mock!(aws_sdk_s3::Client::list_object_versions).then_output(|| {
+                ListObjectVersionsOutput::builder()
+                    .versions(ObjectVersion::builder().key("key").is_latest(true).build())
+                    .versions(
+                        ObjectVersion::builder()
+                            .key("multipart")
+                            .is_latest(true)
+                            .build(),
+                    )
+                    .versions(
+                        ObjectVersion::builder()
+                            .key("error")
+                            .is_latest(true)
+                            .build(),
+                    )
+                    .build()
+            }),
+            attributes("key", || {
+                GetObjectAttributesOutput::builder()
+                    .checksum(
+                        types::Checksum::builder()
+                            .checksum_sha256(EXPECTED_SHA256)
+                            .checksum_type(types::ChecksumType::FullObject)
+                            .build(),
+                    )
+                    .build()
+            }),
+            attributes("multipart", || {
+                GetObjectAttributesOutput::builder()
+                    .checksum(
+                        types::Checksum::builder()
+                            .checksum_sha256(EXPECTED_SHA256)
+                            .checksum_type(types::ChecksumType::Composite)
+                            .build(),
+                    )
+                    .object_parts(
+                        types::GetObjectAttributesParts::builder()
+                            .total_parts_count(3)
+                            .build(),
+                    )
+                    .build()
+            }),
+            mock!(aws_sdk_s3::Client::get_object_attributes)
+                .match_requests(|req| req.key() == Some("error"))
+                .then_error(|| GetObjectAttributesError::unhandled("unhandled")),
+        ];
+        let client = Client::new(mock_client!(aws_sdk_s3, RuleMode::MatchAny, &rules));
+
+        let sha256 = |messages: FlatS3EventMessages| {
+            messages
+                .into_inner()
+                .into_iter()
+                .map(|message| (message.key, message.sha256))
+                .collect::<Vec<_>>()
+        };
+
+        // Checksums are not fetched by default.
+        let result = Crawl::new(client.clone())
+            .crawl_s3("bucket", None)
+            .await
+            .unwrap();
+        assert!(sha256(result).iter().all(|(_, sha256)| sha256.is_none()));
+        assert_eq!(rules[1].num_calls(), 0);
+
+        // The composite checksum of a multipart object includes the number of parts, and a
+        // failure leaves the checksum unknown.
+        let result = Crawl::new(client)
+            .with_checksums(true)
+            .crawl_s3("bucket", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            sha256(result),
+            vec![
+                ("key".to_string(), Some(EXPECTED_SHA256.to_string())),
+                (
+                    "multipart".to_string(),
+                    Some(format!("{EXPECTED_SHA256}-3"))
+                ),
+                ("error".to_string(), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_from_inventory() {
+        let crawl = || {
+            Crawl::new(csv_manifest_from_key_expectations())
+                .with_include(&["inventory_test/key*".to_string()])
+                .unwrap()
+        };
+
+        let result = crawl()
+            .crawl_from_inventory(&format!("s3://{MANIFEST_BUCKET}/manifest.json"))
+            .await
+            .unwrap();
+        assert_eq!(
+            result
+                .into_inner()
+                .into_iter()
+                .map(|message| (message.key, message.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                ("inventory_test/key1".to_string(), Reason::Crawl),
+                ("inventory_test/key2".to_string(), Reason::Crawl),
+            ]
+        );
+
+        for url in ["manifest.json", "s3://bucket", "s3:///manifest.json"] {
+            assert!(crawl().crawl_from_inventory(url).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_prefixes() {
+        let prefix = |prefix: &'static str| {
+            mock!(aws_sdk_s3::Client::list_object_versions)
+                .match_requests(move |req| req.prefix() == Some(prefix))
+                .then_output(move || {
+                    ListObjectVersionsOutput::builder()
+                        .versions(
+                            ObjectVersion::builder()
+                                .key(format!("{prefix}key"))
+                                .is_latest(true)
+                                .build(),
+                        )
+                        .versions(
+                            ObjectVersion::builder()
+                                .key(format!("{prefix}key1"))
+                                .is_latest(true)
+                                .build(),
+                        )
+                        .build()
+                })
+        };
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[prefix("a/"), prefix("b/"), prefix("c/")]
+        ));
+
+        let result = Crawl::new(client)
+            .with_concurrency(2)
+            .crawl_s3_prefixes(
+                "bucket",
+                vec!["a/".to_string(), "b/".to_string(), "c/".to_string()],
+            )
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            result
+                .iter()
+                .map(|message| message.key.as_str())
+                .sorted()
+                .collect_vec(),
+            vec!["a/key", "a/key1", "b/key", "b/key1", "c/key", "c/key1"]
+        );
+        assert!(result.iter().all(|message| message.bucket == "bucket"
+            && message.sequencer.is_none()
+            && message.event_type == Created));
+        assert_eq!(
+            result
+                .iter()
+                .map(|message| message.s3_object_id)
+                .unique()
+                .count(),
+            result.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_delete_markers() {
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::list_object_versions).then_output(|| {
+                    ListObjectVersionsOutput::builder()
+                        .versions(
+                            ObjectVersion::builder()
+                                .key("key0")
+                                .version_id("version0")
+                                .size(0)
+                                .is_latest(true)
+                                .build(),
+                        )
+                        .versions(
+                            ObjectVersion::builder()
+                                .key("key1")
+                                .version_id("version0")
+                                .size(0)
+                                .is_latest(false)
+                                .build(),
+                        )
+                        .versions(
+                            ObjectVersion::builder()
+                                .key("key2")
+                                .version_id("version0")
+                                .size(0)
+                                .is_latest(true)
+                                .build(),
+                        )
+                        .delete_markers(
+                            types::DeleteMarkerEntry::builder()
+                                .key("key1")
+                                .version_id("version1")
+                                .is_latest(true)
+                                .build(),
+                        )
+                        .delete_markers(
+                            types::DeleteMarkerEntry::builder()
+                                .key("key2")
+                                .version_id("version1")
+                                .is_latest(true)
+                                .build(),
+                        )
+                        .build()
+                })
+            ]
+        ));
+
+        let result = Crawl::new(client).crawl_s3("bucket", None).await.unwrap();
+
+        // Only the genuine zero-size object is crawled, and no created record is produced for
+        // keys where the latest version is a delete marker. Instead, the latest delete markers
+        // produce deleted records.
+        assert_eq!(
+            result
+                .0
+                .iter()
+                .map(|message| (
+                    message.key.as_str(),
+                    message.version_id.as_str(),
+                    &message.event_type,
+                    message.is_delete_marker
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("key0", "version0", &Created, false),
+                ("key1", "version1", &Deleted, true),
+                ("key2", "version1", &Deleted, true)
+            ]
+        );
+        assert!(
+            result
+                .0
+                .iter()
+                .filter(|message| message.is_delete_marker)
+                .all(|message| message.reason == Reason::CrawlDeleteMarker
+                    && !message.is_current_state
+                    && message.bucket == "bucket")
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_include_exclude() {
+        let keys = [
+            "a/cache/key0",
+            "a/key1.tmp",
+            "a/key2.bam",
+            "b/cache/key3.bam",
+            "b/key4",
+        ];
+        let client = || {
+            Client::new(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                &[
+                    mock!(aws_sdk_s3::Client::list_object_versions).then_output(move || {
+                        ListObjectVersionsOutput::builder()
+                            .set_versions(Some(
+                                keys.iter()
+                                    .map(|key| {
+                                        ObjectVersion::builder().key(*key).is_latest(true).build()
+                                    })
+                                    .collect(),
+                            ))
+                            .build()
+                    })
+                ]
+            ))
+        };
+        let crawled = |crawl: Crawl| async move {
+            crawl
+                .crawl_s3("bucket", None)
+                .await
+                .unwrap()
+                .into_inner()
+                .into_iter()
+                .map(|message| message.key)
+                .collect::<Vec<_>>()
+        };
+        let patterns = |patterns: &[&str]| {
+            patterns
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let exclude = Crawl::new(client())
+            .with_exclude(&patterns(&["*/cache/*", "*.tmp"]))
+            .unwrap();
+        assert_eq!(crawled(exclude).await, vec!["a/key2.bam", "b/key4"]);
+
+        let include = Crawl::new(client())
+            .with_include(&patterns(&["*.bam"]))
+            .unwrap();
+        assert_eq!(
+            crawled(include).await,
+            vec!["a/key2.bam", "b/cache/key3.bam"]
+        );
+
+        // The include patterns take precedence over the exclude patterns.
+        let both = Crawl::new(client())
+            .with_include(&patterns(&["*.bam"]))
+            .unwrap()
+            .with_exclude(&patterns(&["*/cache/*"]))
+            .unwrap();
+        assert_eq!(crawled(both).await, vec!["a/key2.bam", "b/cache/key3.bam"]);
+
+        assert!(
+            Crawl::new(client())
+                .with_exclude(&patterns(&["a/[cache"]))
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_all_versions() {
+        let client = || {
+            Client::new(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                &[
+                    mock!(aws_sdk_s3::Client::list_object_versions).then_output(|| {
+                        let version = |key: &str, version_id: &str, is_latest: bool| {
+                            ObjectVersion::builder()
+                                .key(key)
+                                .version_id(version_id)
+                                .is_latest(is_latest)
+                                .build()
+                        };
+
+                        ListObjectVersionsOutput::builder()
+                            .versions(version("key0", "version2", true))
+                            .versions(version("key0", "version1", false))
+                            .versions(version("key0", "version0", false))
+                            .versions(version("key1", "version0", false))
+                            .delete_markers(
+                                types::DeleteMarkerEntry::builder()
+                                    .key("key1")
+                                    .version_id("version1")
+                                    .is_latest(true)
+                                    .build(),
+                            )
+                            .build()
+                    })
+                ]
+            ))
+        };
+        let crawled = |result: FlatS3EventMessages| {
+            result
+                .into_inner()
+                .into_iter()
+                .map(|message| (message.key, message.version_id, message.is_current_state))
+                .collect::<Vec<_>>()
+        };
+
+        let result = Crawl::new(client()).crawl_s3("bucket", None).await.unwrap();
+        assert_eq!(
+            crawled(result),
+            vec![
+                ("key0".to_string(), "version2".to_string(), true),
+                ("key1".to_string(), "version1".to_string(), false),
+            ]
+        );
+
+        // Every version keeps its own version id, and only the latest version of a key that
+        // is not deleted is current.
+        let result = Crawl::new(client())
+            .with_mode(CrawlMode::AllVersions)
+            .crawl_s3("bucket", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            crawled(result),
+            vec![
+                ("key0".to_string(), "version2".to_string(), true),
+                ("key0".to_string(), "version1".to_string(), false),
+                ("key0".to_string(), "version0".to_string(), false),
+                ("key1".to_string(), "version0".to_string(), false),
+                ("key1".to_string(), "version1".to_string(), false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_modified_since() {
+        let client = || {
+            Client::new(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                &[
+                    mock!(aws_sdk_s3::Client::list_object_versions).then_output(|| {
+                        let version = |key: &str, last_modified: Option<i64>| {
+                            ObjectVersion::builder()
+                                .key(key)
+                                .is_latest(true)
+                                .set_last_modified(
+                                    last_modified.map(primitives::DateTime::from_secs),
+                                )
+                                .build()
+                        };
+
+                        ListObjectVersionsOutput::builder()
+                            .versions(version("key0", Some(0)))
+                            .versions(version("key1", Some(100)))
+                            .versions(version("key2", Some(200)))
+                            .versions(version("key3", None))
+                            .versions(version("key4", Some(0)))
+                            .delete_markers(
+                                types::DeleteMarkerEntry::builder()
+                                    .key("key4")
+                                    .version_id("version1")
+                                    .is_latest(true)
+                                    .last_modified(primitives::DateTime::from_secs(300))
+                                    .build(),
+                            )
+                            .build()
+                    })
+                ]
+            ))
+        };
+        let crawled = |result: FlatS3EventMessages| {
+            result
+                .into_inner()
+                .into_iter()
+                .map(|message| (message.key, message.event_type))
+                .collect::<Vec<_>>()
+        };
+
+        // Without a cutoff, everything is crawled.
+        let result = Crawl::new(client())
+            .with_modified_since(None)
+            .crawl_s3("bucket", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            crawled(result),
+            vec![
+                ("key0".to_string(), Created),
+                ("key1".to_string(), Created),
+                ("key2".to_string(), Created),
+                ("key3".to_string(), Created),
+                ("key4".to_string(), Deleted),
+            ]
+        );
+
+        // Objects modified before the cutoff are skipped, and objects modified at or after it,
+        // or without a last modified date, are kept.
+        let result = Crawl::new(client())
+            .with_modified_since(DateTime::from_timestamp(100, 0))
+            .crawl_s3("bucket", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            crawled(result),
+            vec![
+                ("key1".to_string(), Created),
+                ("key2".to_string(), Created),
+                ("key3".to_string(), Created),
+                ("key4".to_string(), Deleted),
+            ]
+        );
+
+        let result = Crawl::new(client())
+            .with_modified_since(DateTime::from_timestamp(400, 0))
+            .crawl_s3("bucket", None)
+            .await
+            .unwrap();
+        assert_eq!(crawled(result), vec![("key3".to_string(), Created)]);
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_time_budget() {
+        let page = |key: &'static str, next: Option<&'static str>| {
+            move || {
+                ListObjectVersionsOutput::builder()
+                    .versions(ObjectVersion::builder().key(key).is_latest(true).build())
+                    .is_truncated(next.is_some())
+                    .set_next_key_marker(next.map(|next| next.to_string()))
+                    .set_next_version_id_marker(next.map(|_| "null".to_string()))
+                    .build()
+            }
+        };
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker().is_none())
+                    .then_output(page("key0", Some("key0"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key0"))
+                    .then_output(page("key1", Some("key1"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key1"))
+                    .then_output(page("key2", None)),
+            ]
+        ));
+        let keys = |crawl: &PartialCrawl| {
+            crawl
+                .messages
+                .0
+                .iter()
+                .map(|message| message.key.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // With no time left, only the first page is listed.
+        let crawl = Crawl::new(client.clone()).with_time_budget(TimeDelta::zero());
+        let result = crawl.crawl_s3_partial("bucket", None, None, None).await;
+        assert!(!result.is_complete());
+        assert!(result.error.is_none());
+        assert_eq!(keys(&result), vec!["key0"]);
+        assert_eq!(result.key_marker, Some("key0".to_string()));
+        assert_eq!(result.version_id_marker, Some("null".to_string()));
+        assert!(crawl.crawl_s3("bucket", None).await.is_err());
+
+        // Updating stops after the first chunk, and resumes after the last updated key.
+        let result = Crawl::new(client.clone())
+            .crawl_s3_partial("bucket", None, None, None)
+            .await;
+        assert!(result.is_complete());
+        let result = Crawl::new(client.clone())
+            .with_time_budget(TimeDelta::zero())
+            .update_partial(result, 2, |messages| async { Ok(messages) })
+            .await
+            .unwrap();
+        assert!(!result.is_complete());
+        assert_eq!(keys(&result), vec!["key0", "key1"]);
+        assert_eq!(result.key_marker, Some("key1".to_string()));
+        assert_eq!(result.version_id_marker, None);
+
+        // Resuming from the marker crawls the remaining keys.
+        let result = Crawl::new(client.clone())
+            .crawl_s3_partial("bucket", None, result.key_marker, None)
+            .await;
+        assert!(result.is_complete());
+        assert_eq!(keys(&result), vec!["key2"]);
+
+        // All versions of a key are updated in the same chunk, so resuming does not skip any.
+        let message = |key: &str, version_id: &str| {
+            FlatS3EventMessage::new_with_generated_id()
+                .with_key(key.to_string())
+                .with_version_id(version_id.to_string())
+        };
+        let crawl = PartialCrawl {
+            messages: FlatS3EventMessages(vec![
+                message("key1", "0"),
+                message("key0", "0"),
+                message("key0", "1"),
+            ]),
+            error: None,
+            key_marker: None,
+            version_id_marker: None,
+        };
+        let result = Crawl::new(client)
+            .with_time_budget(TimeDelta::zero())
+            .update_partial(crawl, 1, |messages| async { Ok(messages) })
+            .await
+            .unwrap();
+        assert_eq!(keys(&result), vec!["key0", "key0"]);
+        assert_eq!(result.key_marker, Some("key0".to_string()));
+        assert_eq!(result.version_id_marker, None);
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_chunked() {
+        let page = |keys: &'static [&'static str], next: Option<&'static str>| {
+            move || {
+                ListObjectVersionsOutput::builder()
+                    .set_versions(Some(
+                        keys.iter()
+                            .map(|key| ObjectVersion::builder().key(*key).is_latest(true).build())
+                            .collect(),
+                    ))
+                    .is_truncated(next.is_some())
+                    .set_next_key_marker(next.map(|next| next.to_string()))
+                    .set_next_version_id_marker(next.map(|_| "null".to_string()))
+                    .build()
+            }
+        };
+        let client = Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker().is_none())
+                    .then_output(page(&["key0", "key1"], Some("key1"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key1"))
+                    .then_output(page(&["key2", "key3"], Some("key3"))),
+                mock!(aws_sdk_s3::Client::list_object_versions)
+                    .match_requests(|req| req.key_marker() == Some("key3"))
+                    .then_output(page(&["key4"], None)),
+            ]
+        ));
+
+        let flushes = Mutex::new(vec![]);
+        let n_messages = Crawl::new(client)
+            .crawl_s3_chunked("bucket", None, 2, |messages, key_range| {
+                flushes.lock().unwrap().push((
+                    messages
+                        .into_inner()
+                        .into_iter()
+                        .map(|message| message.key)
+                        .collect::<Vec<_>>(),
+                    key_range,
+                ));
+                async { Ok(()) }
+            })
+            .await
+            .unwrap();
+
+        // The messages are flushed in multiple chunks, and the key at each marker is kept for the
+        // next chunk because it could have more versions on the next page.
+        let range = |start: Option<&str>, end: Option<&str>| {
+            CrawlKeyRange::new(start.map(ToString::to_string), end.map(ToString::to_string))
+        };
+        assert_eq!(n_messages, 5);
+        assert_eq!(
+            flushes.into_inner().unwrap(),
+            vec![
+                (vec!["key0".to_string()], range(None, Some("key1"))),
+                (
+                    vec!["key1".to_string(), "key2".to_string()],
+                    range(Some("key1"), Some("key3"))
+                ),
+                (
+                    vec!["key3".to_string(), "key4".to_string()],
+                    range(Some("key3"), None)
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_s3_cancelled() {
+        // Cancel the crawl while the first page is being listed.
+        let crawl = || {
+            let cancellation = CancellationToken::new();
+            let page = |key: &'static str, next: Option<&'static str>| {
+                let cancellation = cancellation.clone();
+                move || {
+                    cancellation.cancel();
+                    ListObjectVersionsOutput::builder()
+                        .versions(ObjectVersion::builder().key(key).is_latest(true).build())
+                        .is_truncated(next.is_some())
+                        .set_next_key_marker(next.map(|next| next.to_string()))
+                        .set_next_version_id_marker(next.map(|_| "null".to_string()))
+                        .build()
+                }
+            };
+            let next_page = mock!(aws_sdk_s3::Client::list_object_versions)
+                .match_requests(|req| req.key_marker().is_some())
+                .then_output(page("key1", None));
+            let client = Client::new(mock_client!(
+                aws_sdk_s3,
+                RuleMode::MatchAny,
+                &[
+                    mock!(aws_sdk_s3::Client::list_object_versions)
+                        .match_requests(|req| req.key_marker().is_none())
+                        .then_output(page("key0", Some("key0"))),
+                    next_page.clone(),
+                ]
+            ));
+
+            (
+                Crawl::new(client).with_cancellation(cancellation),
+                next_page,
+            )
+        };
+
+        // The crawl stops after the first page, returning the marker that listing reached.
+        let (crawler, next_page) = crawl();
+        let result = crawler.crawl_s3("bucket", None).await;
+        assert!(matches!(result, Err(CrawlCancelled(Some(marker))) if marker == "key0"));
+        assert_eq!(next_page.num_calls(), 0);
+
+        // The buffered messages of a chunked crawl are dropped rather than flushed.
+        let (crawler, next_page) = crawl();
+        let flushes = Mutex::new(0);
+        let result = crawler
+            .crawl_s3_chunked("bucket", None, 10, |_, _| {
+                *flushes.lock().unwrap() += 1;
+                async { Ok(()) }
+            })
+            .await;
+        assert!(matches!(result, Err(CrawlCancelled(Some(marker))) if marker == "key0"));
+        assert_eq!(flushes.into_inner().unwrap(), 0);
+        assert_eq!(next_page.num_calls(), 0);
+    }
+
     async fn test_crawl_record_states(pool: PgPool, version_id: Option<String>) {
         let default_version_id = version_id.clone().unwrap_or(default_version_id());
         let records = crawl_record_states(default_version_id.clone());
@@ -893,6 +3391,7 @@ pub(crate) mod tests {
             .with_is_current_state(true)
             .with_sha256(Some(EXPECTED_SHA256.to_string()))
             .with_reason(Reason::Crawl)
+            .with_tag_present(Some(true))
     }
 
     fn expected_unaffected_record_two() -> FlatS3EventMessage {
@@ -912,12 +3411,32 @@ pub(crate) mod tests {
             .with_is_current_state(true)
             .with_sha256(Some(EXPECTED_SHA256.to_string()))
             .with_reason(Reason::Crawl)
+            .with_tag_present(Some(true))
     }
 
     async fn ingest_crawl(
         client: database::Client,
         event: FlatS3EventMessage,
         version_ids: Vec<String>,
+    ) -> Vec<FlatS3EventMessage> {
+        ingest_crawl_with_options(client, event, version_ids, Default::default()).await
+    }
+
+    async fn ingest_crawl_with_options(
+        client: database::Client,
+        event: FlatS3EventMessage,
+        version_ids: Vec<String>,
+        options: CrawlOptions,
+    ) -> Vec<FlatS3EventMessage> {
+        ingest_crawl_with_config(client, event, version_ids, options, &Default::default()).await
+    }
+
+    async fn ingest_crawl_with_config(
+        client: database::Client,
+        event: FlatS3EventMessage,
+        version_ids: Vec<String>,
+        options: CrawlOptions,
+        config: &Config,
     ) -> Vec<FlatS3EventMessage> {
         client
             .ingest(EventSourceType::S3(TransposedS3EventMessages::from(
@@ -926,10 +3445,10 @@ pub(crate) mod tests {
             .await
             .unwrap();
 
-        let config = Config::default();
-        let mut collecter = test_collecter(&config, &client).await;
+        let mut collecter = test_collecter(config, &client).await;
         collecter.set_client(crawl_expectations(version_ids));
         collecter.set_crawl_bucket("bucket".to_string());
+        collecter.set_crawl_options(options);
 
         let result = Crawl::new(collecter.client().clone())
             .crawl_s3("bucket", None)
@@ -7,8 +7,8 @@ use arrow_json::ArrayWriter;
 use aws_arn::ResourceName;
 use aws_arn::known::Service;
 use aws_sdk_s3::types::InventoryFormat;
-use chrono::{DateTime, NaiveDateTime, Utc};
-use csv::{ReaderBuilder, StringRecord, Trim};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use csv::{QuoteStyle, ReaderBuilder, StringRecord, Trim, WriterBuilder};
 use flate2::read::MultiGzDecoder;
 use futures::future::join_all;
 use futures::{Stream, TryStreamExt};
@@ -23,6 +23,7 @@ use std::io::{BufReader, Cursor, Read};
 use std::result;
 
 use crate::clients::aws::s3::Client;
+use crate::database::entities::s3_object;
 use crate::database::entities::sea_orm_active_enums::Reason;
 use crate::error::Error::S3Error;
 use crate::error::{Error, Result};
@@ -30,7 +31,8 @@ use crate::events::aws::message::{EventType::Created, default_version_id, quote_
 use crate::events::aws::{FlatS3EventMessage, FlatS3EventMessages, StorageClass};
 use crate::uuid::UuidGenerator;
 
-const DEFAULT_CSV_MANIFEST: &str =
+/// The default schema of CSV inventory files, which is also used when exporting records.
+pub const DEFAULT_CSV_MANIFEST: &str =
     "Bucket, Key, VersionId, IsLatest, IsDeleteMarker, Size, LastModifiedDate, ETag, StorageClass";
 
 /// Represents an S3 inventory including associated inventory fetching and parsing logic.
@@ -157,7 +159,11 @@ impl Inventory {
     async fn get_object_bytes<K: AsRef<str>>(&self, key: K, bucket: K) -> Result<Vec<u8>> {
         Ok(self
             .client
-            .get_object(key.as_ref(), bucket.as_ref(), default_version_id().as_ref())
+            .get_object(
+                key.as_ref(),
+                bucket.as_ref(),
+                self.client.default_version_id(),
+            )
             .await
             .map_err(|err| S3Error(err.to_string()))?
             .body
@@ -336,6 +342,47 @@ impl Record {
         self.is_delete_marker = Some(is_delete_marker);
         self
     }
+
+    /// Write the record as a CSV row in the `DEFAULT_CSV_MANIFEST` schema, without a header row.
+    /// Like S3 Inventory, all fields are quoted and the ETag is unquoted.
+    pub fn to_csv_row(&self, is_latest: bool) -> Result<Vec<u8>> {
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .quote_style(QuoteStyle::Always)
+            .from_writer(vec![]);
+
+        writer.serialize((
+            &self.bucket,
+            &self.key,
+            &self.version_id,
+            is_latest,
+            self.is_delete_marker,
+            self.size,
+            self.last_modified_date
+                .map(|date| date.to_rfc3339_opts(SecondsFormat::Millis, true)),
+            self.e_tag.as_deref().map(|e_tag| e_tag.trim_matches('"')),
+            &self.storage_class,
+        ))?;
+
+        writer
+            .into_inner()
+            .map_err(|err| S3Error(format!("writing CSV: {err}")))
+    }
+}
+
+impl From<s3_object::Model> for Record {
+    fn from(model: s3_object::Model) -> Self {
+        Self {
+            bucket: model.bucket,
+            key: model.key,
+            version_id: Some(model.version_id),
+            is_delete_marker: Some(model.is_delete_marker),
+            size: model.size,
+            last_modified_date: model.last_modified_date.map(Into::into),
+            e_tag: model.e_tag,
+            storage_class: model.storage_class.map(StorageClass::from_database),
+        }
+    }
 }
 
 /// A builder for an S3 inventory record.
@@ -525,6 +572,12 @@ impl From<Record> for FlatS3EventMessage {
             is_current_state: true,
             ingest_id: None,
             attributes: None,
+            is_e_tag_mismatch: false,
+            server_side_encryption: None,
+            sse_kms_key_id: None,
+            tag_present: None,
+            restore_expiry_date: None,
+            is_sequencer_conflict: false,
             number_duplicate_events: 0,
             number_reordered: 0,
         }
@@ -6,7 +6,9 @@ use crate::events::aws::{FlatS3EventMessage, FlatS3EventMessages};
 use crate::uuid::UuidGenerator;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use strum::{EnumCount, FromRepr};
+use uuid::Uuid;
 
 /// The type of S3 event.
 #[derive(
@@ -179,19 +181,42 @@ impl From<EventTypeData> for EventTypeParsed {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum EventMessage {
-    EventBridge(Record),
+    EventBridge(Box<Record>),
     SQS(Message),
 }
 
 impl From<EventMessage> for FlatS3EventMessages {
     fn from(message: EventMessage) -> Self {
         match message {
-            EventMessage::EventBridge(record) => record.into(),
+            EventMessage::EventBridge(record) => (*record).into(),
             EventMessage::SQS(message) => message.into(),
         }
     }
 }
 
+/// A request to re-collect S3 metadata for existing records. This is sent to the ingest queue
+/// by the filemanager itself, and updates the records in place rather than creating new events.
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecollectMessage {
+    pub recollect: Vec<Uuid>,
+}
+
+impl RecollectMessage {
+    /// Create a new re-collect message.
+    pub fn new(recollect: Vec<Uuid>) -> Self {
+        Self { recollect }
+    }
+}
+
+/// A message received on the ingest queue, which is either a re-collect request or S3 events.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum IngestMessage {
+    Recollect(RecollectMessage),
+    Events(Option<FlatS3EventMessages>),
+}
+
 /// An S3 event message generated by an SQS queue or EventBridge. Serde rename and alias attributes
 /// allow supporting both message types.
 ///
@@ -225,6 +250,9 @@ pub struct S3Record {
     pub object: Object,
     pub deletion_type: Option<String>,
     pub reason: Option<String>,
+    /// The time that the temporary copy of a restored object expires, for `Object Restore
+    /// Completed` events.
+    pub restore_expiry_time: Option<DateTime<Utc>>,
 }
 
 /// The bucket name in a message.
@@ -260,6 +288,7 @@ impl From<Record> for FlatS3EventMessage {
             object,
             deletion_type,
             reason,
+            restore_expiry_time,
         } = detail;
 
         let Bucket { name: bucket } = bucket;
@@ -299,6 +328,12 @@ impl From<Record> for FlatS3EventMessage {
             archive_status: None,
             ingest_id: None,
             attributes: None,
+            is_e_tag_mismatch: false,
+            server_side_encryption: None,
+            sse_kms_key_id: None,
+            tag_present: None,
+            restore_expiry_date: restore_expiry_time,
+            is_sequencer_conflict: false,
             number_duplicate_events: 0,
             number_reordered: 0,
         }
@@ -339,11 +374,35 @@ pub fn quote_e_tag(mut e_tag: String) -> String {
     e_tag
 }
 
-/// The default version id.
+/// The default version id. This matches the version id that S3 uses for unversioned objects,
+/// and can be overridden using the `FILEMANAGER_INGESTER_DEFAULT_VERSION_ID` config.
 pub fn default_version_id() -> String {
     "null".to_string()
 }
 
+/// Compare two AWS-native sequencers of the same key. Sequencers are hexadecimal values which
+/// can have different lengths, so the shorter sequencer is right-padded with zeros before
+/// comparing them.
+pub fn compare_sequencers(a: &str, b: &str) -> Ordering {
+    let len = a.len().max(b.len());
+    let pad = |sequencer: &str| format!("{:0<len$}", sequencer.to_ascii_uppercase());
+
+    pad(a).cmp(&pad(b))
+}
+
+/// The difference between two AWS-native sequencers, padded in the same way as
+/// `compare_sequencers`. This is `None` if either sequencer is not hexadecimal, or is too long
+/// to fit in a 128-bit number.
+pub fn sequencer_gap(a: &str, b: &str) -> Option<u128> {
+    let len = a.len().max(b.len());
+    if len > 32 {
+        return None;
+    }
+    let parse = |sequencer: &str| u128::from_str_radix(&format!("{sequencer:0<len$}"), 16).ok();
+
+    Some(parse(a)?.abs_diff(parse(b)?))
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::{Value, json};
@@ -351,12 +410,13 @@ mod tests {
     use crate::events::aws::EventType::Deleted;
     use crate::events::aws::FlatS3EventMessages;
     use crate::events::aws::message::EventType::Created;
-    use crate::events::aws::message::quote_e_tag;
+    use crate::events::aws::message::{compare_sequencers, quote_e_tag, sequencer_gap};
     use crate::events::aws::tests::{
         EXPECTED_E_TAG, EXPECTED_REQUEST_ID, EXPECTED_SEQUENCER_DELETED_ONE, EXPECTED_VERSION_ID,
         assert_flat_s3_event, expected_event_bridge_record,
         expected_event_bridge_record_delete_marker, expected_sqs_record,
     };
+    use std::cmp::Ordering;
 
     #[test]
     fn test_e_tag_quoting() {
@@ -380,6 +440,36 @@ mod tests {
         assert_eq!(quote_e_tag("W/\"".to_string()), "W/\"\"");
     }
 
+    #[test]
+    fn test_compare_sequencers() {
+        assert_eq!(
+            compare_sequencers("0055AED6DCD90281E4", "0055AED6DCD90281E5"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_sequencers("0055AED6DCD90281E5", "0055aed6dcd90281e4"),
+            Ordering::Greater
+        );
+        // Shorter sequencers are padded before comparing.
+        assert_eq!(
+            compare_sequencers("0055AED6DCD90281E4", "0055AED6DCD90281E400"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_sequencers("0055AED6DCD90281E4", "0055AED6DCD90281E301"),
+            Ordering::Greater
+        );
+
+        assert_eq!(
+            sequencer_gap("0055AED6DCD90281E4", "0055AED6DCD90281F0"),
+            Some(0x0C)
+        );
+        assert_eq!(sequencer_gap("00F0", "00E4"), Some(0x0C));
+        assert_eq!(sequencer_gap("00E4", "00E401"), Some(0x01));
+        assert_eq!(sequencer_gap("00E4", "sequencer"), None);
+        assert_eq!(sequencer_gap("00E4", &"0".repeat(33)), None);
+    }
+
     #[test]
     fn deserialize_large_size() {
         let message = format!(
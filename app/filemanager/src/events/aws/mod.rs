@@ -7,6 +7,7 @@ use chrono::{DateTime, Utc};
 use itertools::{Itertools, izip};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use uuid::Uuid;
 
@@ -90,6 +91,23 @@ impl StorageClass {
             sea_orm_active_enums::StorageClass::StandardIa => Self::StandardIa,
         }
     }
+
+    /// Convert from the filemanager storage class to the database representation of the storage
+    /// class.
+    pub fn to_database(self) -> sea_orm_active_enums::StorageClass {
+        match self {
+            Self::DeepArchive => sea_orm_active_enums::StorageClass::DeepArchive,
+            Self::Glacier => sea_orm_active_enums::StorageClass::Glacier,
+            Self::GlacierIr => sea_orm_active_enums::StorageClass::GlacierIr,
+            Self::IntelligentTiering => sea_orm_active_enums::StorageClass::IntelligentTiering,
+            Self::OnezoneIa => sea_orm_active_enums::StorageClass::OnezoneIa,
+            Self::Outposts => sea_orm_active_enums::StorageClass::Outposts,
+            Self::ReducedRedundancy => sea_orm_active_enums::StorageClass::ReducedRedundancy,
+            Self::Snow => sea_orm_active_enums::StorageClass::Snow,
+            Self::Standard => sea_orm_active_enums::StorageClass::Standard,
+            Self::StandardIa => sea_orm_active_enums::StorageClass::StandardIa,
+        }
+    }
 }
 
 #[allow(clippy::derivable_impls)]
@@ -156,6 +174,12 @@ pub struct TransposedS3EventMessages {
     pub ingest_ids: Vec<Option<Uuid>>,
     pub is_current_state: Vec<bool>,
     pub attributes: Vec<Option<Json>>,
+    pub is_e_tag_mismatches: Vec<bool>,
+    pub server_side_encryptions: Vec<Option<String>>,
+    pub sse_kms_key_ids: Vec<Option<String>>,
+    pub tag_presents: Vec<Option<bool>>,
+    pub restore_expiry_dates: Vec<Option<DateTime<Utc>>>,
+    pub is_sequencer_conflicts: Vec<bool>,
 }
 
 impl TransposedS3EventMessages {
@@ -180,6 +204,12 @@ impl TransposedS3EventMessages {
             ingest_ids: Vec::with_capacity(capacity),
             is_current_state: Vec::with_capacity(capacity),
             attributes: Vec::with_capacity(capacity),
+            is_e_tag_mismatches: Vec::with_capacity(capacity),
+            server_side_encryptions: Vec::with_capacity(capacity),
+            sse_kms_key_ids: Vec::with_capacity(capacity),
+            tag_presents: Vec::with_capacity(capacity),
+            restore_expiry_dates: Vec::with_capacity(capacity),
+            is_sequencer_conflicts: Vec::with_capacity(capacity),
         }
     }
 
@@ -204,6 +234,12 @@ impl TransposedS3EventMessages {
             ingest_id,
             is_current_state,
             attributes,
+            is_e_tag_mismatch,
+            server_side_encryption,
+            sse_kms_key_id,
+            tag_present,
+            restore_expiry_date,
+            is_sequencer_conflict,
             ..
         } = message;
 
@@ -225,6 +261,12 @@ impl TransposedS3EventMessages {
         self.ingest_ids.push(ingest_id);
         self.is_current_state.push(is_current_state);
         self.attributes.push(attributes);
+        self.is_e_tag_mismatches.push(is_e_tag_mismatch);
+        self.server_side_encryptions.push(server_side_encryption);
+        self.sse_kms_key_ids.push(sse_kms_key_id);
+        self.tag_presents.push(tag_present);
+        self.restore_expiry_dates.push(restore_expiry_date);
+        self.is_sequencer_conflicts.push(is_sequencer_conflict);
     }
 
     /// Partition the events by a given function.
@@ -279,6 +321,12 @@ impl From<TransposedS3EventMessages> for FlatS3EventMessages {
             messages.ingest_ids,
             messages.is_current_state,
             messages.attributes,
+            messages.is_e_tag_mismatches,
+            messages.server_side_encryptions,
+            messages.sse_kms_key_ids,
+            messages.tag_presents,
+            messages.restore_expiry_dates,
+            messages.is_sequencer_conflicts,
         )
         .map(
             |(
@@ -300,6 +348,12 @@ impl From<TransposedS3EventMessages> for FlatS3EventMessages {
                 ingest_id,
                 is_current_state,
                 attributes,
+                is_e_tag_mismatch,
+                server_side_encryption,
+                sse_kms_key_id,
+                tag_present,
+                restore_expiry_date,
+                is_sequencer_conflict,
             )| {
                 FlatS3EventMessage {
                     s3_object_id,
@@ -320,6 +374,12 @@ impl From<TransposedS3EventMessages> for FlatS3EventMessages {
                     ingest_id,
                     is_current_state,
                     attributes,
+                    is_e_tag_mismatch,
+                    server_side_encryption,
+                    sse_kms_key_id,
+                    tag_present,
+                    restore_expiry_date,
+                    is_sequencer_conflict,
                     number_duplicate_events: 0,
                     number_reordered: 0,
                 }
@@ -409,6 +469,16 @@ impl FlatS3EventMessages {
         self.0
     }
 
+    /// Replace the built-in default version id with a different unversioned sentinel.
+    pub fn replace_default_version_id(self, sentinel: &str) -> Self {
+        Self(
+            self.0
+                .into_iter()
+                .map(|record| record.replace_default_version_id(sentinel))
+                .collect(),
+        )
+    }
+
     /// Filter these messages to only the `Created` or `Deleted` events.
     pub fn filter_known(self) -> Self {
         Self(
@@ -458,6 +528,48 @@ impl FlatS3EventMessages {
         Self([null_sequencer, messages].concat())
     }
 
+    /// Resolve events which share a sequencer, event type, bucket, key and version id, but have a
+    /// different ETag or size. These should not occur, so the event with the later event time is
+    /// kept and flagged as a sequencer conflict. If the event times are equal, the first event is
+    /// kept. Events with the same content are left for `dedup` to remove.
+    pub fn resolve_sequencer_conflicts(self) -> Self {
+        let mut messages: Vec<FlatS3EventMessage> = Vec::with_capacity(self.0.len());
+        let mut seen = HashMap::new();
+
+        for event in self.into_inner() {
+            let Some(sequencer) = event.sequencer.clone() else {
+                messages.push(event);
+                continue;
+            };
+
+            let key = (
+                sequencer,
+                event.event_type.clone(),
+                event.bucket.clone(),
+                event.key.clone(),
+                event.version_id.clone(),
+            );
+            let Some(&i) = seen.get(&key) else {
+                seen.insert(key, messages.len());
+                messages.push(event);
+                continue;
+            };
+
+            let existing = &mut messages[i];
+            if existing.e_tag == event.e_tag && existing.size == event.size {
+                messages.push(event);
+                continue;
+            }
+
+            if event.event_time > existing.event_time {
+                *existing = event;
+            }
+            existing.is_sequencer_conflict = true;
+        }
+
+        Self(messages)
+    }
+
     /// Ordering is implemented so that the sequencer values are considered when the bucket, the
     /// key and the version id are the same.
     ///
@@ -567,6 +679,12 @@ pub struct FlatS3EventMessage {
     pub ingest_id: Option<Uuid>,
     pub is_current_state: bool,
     pub attributes: Option<Json>,
+    pub is_e_tag_mismatch: bool,
+    pub server_side_encryption: Option<String>,
+    pub sse_kms_key_id: Option<String>,
+    pub tag_present: Option<bool>,
+    pub restore_expiry_date: Option<DateTime<Utc>>,
+    pub is_sequencer_conflict: bool,
     pub number_duplicate_events: i64,
     pub number_reordered: i64,
 }
@@ -677,6 +795,14 @@ impl FlatS3EventMessage {
         self
     }
 
+    /// Replace the built-in default version id with a different unversioned sentinel.
+    pub fn replace_default_version_id(mut self, sentinel: &str) -> Self {
+        if self.version_id == default_version_id() {
+            self.version_id = sentinel.to_string();
+        }
+        self
+    }
+
     /// Set the size.
     pub fn with_size(mut self, size: Option<i64>) -> Self {
         self.size = size;
@@ -737,6 +863,36 @@ impl FlatS3EventMessage {
         self
     }
 
+    /// Set whether the event ETag differs from the collected ETag.
+    pub fn with_is_e_tag_mismatch(mut self, is_e_tag_mismatch: bool) -> Self {
+        self.is_e_tag_mismatch = is_e_tag_mismatch;
+        self
+    }
+
+    /// Set the server-side encryption algorithm.
+    pub fn with_server_side_encryption(mut self, server_side_encryption: Option<String>) -> Self {
+        self.server_side_encryption = server_side_encryption;
+        self
+    }
+
+    /// Set the KMS key id used for server-side encryption.
+    pub fn with_sse_kms_key_id(mut self, sse_kms_key_id: Option<String>) -> Self {
+        self.sse_kms_key_id = sse_kms_key_id;
+        self
+    }
+
+    /// Set whether the ingest id tag was present on the object when it was collected.
+    pub fn with_tag_present(mut self, tag_present: Option<bool>) -> Self {
+        self.tag_present = tag_present;
+        self
+    }
+
+    /// Set the date that the temporary copy of a restored object expires.
+    pub fn with_restore_expiry_date(mut self, restore_expiry_date: Option<DateTime<Utc>>) -> Self {
+        self.restore_expiry_date = restore_expiry_date;
+        self
+    }
+
     /// Set the attributes.
     pub fn with_attributes(mut self, attributes: Option<Json>) -> Self {
         self.attributes = attributes;
@@ -777,6 +933,12 @@ impl From<s3_object::Model> for FlatS3EventMessage {
             ingest_id: record.ingest_id,
             is_current_state: record.is_current_state,
             attributes: record.attributes,
+            is_e_tag_mismatch: record.is_e_tag_mismatch,
+            server_side_encryption: record.server_side_encryption,
+            sse_kms_key_id: record.sse_kms_key_id,
+            tag_present: record.tag_present,
+            restore_expiry_date: record.restore_expiry_date.map(DateTime::from),
+            is_sequencer_conflict: record.is_sequencer_conflict,
             number_duplicate_events: record.number_duplicate_events,
             number_reordered: record.number_reordered,
         }
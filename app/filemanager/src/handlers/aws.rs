@@ -3,22 +3,25 @@
 
 use std::collections::HashSet;
 
-use aws_lambda_events::sqs::SqsEvent;
+use aws_lambda_events::sqs::{BatchItemFailure, SqsBatchResponse, SqsEvent};
+use futures::{StreamExt, stream};
 use itertools::Itertools;
-use sea_orm::DatabaseConnection;
-use tracing::{debug, trace};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tracing::{debug, trace, warn};
+use uuid::Uuid;
 
 use crate::clients::aws::s3::Client as S3Client;
 use crate::clients::aws::sqs::Client as SQSClient;
 use crate::database::aws::credentials::IamGeneratorBuilder;
 use crate::database::aws::query::Query;
+use crate::database::entities::s3_object;
 use crate::database::{Client, Ingest};
 use crate::env::Config as EnvConfig;
 use crate::error::Error::ConfigError;
 use crate::error::Result;
-use crate::events::aws::collecter::CollecterBuilder;
+use crate::events::aws::collecter::{Collecter, CollecterBuilder, MAX_COLLECT_CONCURRENCY};
 use crate::events::aws::inventory::{Inventory, Manifest};
-use crate::events::aws::message::EventType;
+use crate::events::aws::message::{EventType, IngestMessage};
 use crate::events::aws::{DiffCrawlCreatedMessage, FlatS3EventMessages, TransposedS3EventMessages};
 use crate::events::{Collect, EventSourceType};
 
@@ -46,31 +49,38 @@ pub async fn receive_and_ingest<'a>(
     Ok(n_records)
 }
 
-/// Handle SQS events that go through an SqsEvent.
+/// Handle SQS events that go through an SqsEvent. Returns the database client along with the
+/// batch item failures, which contain the re-collect messages that had a record which could not
+/// be collected, so that only those messages are retried.
 pub async fn ingest_event(
     event: SqsEvent,
     s3_client: S3Client,
     database_client: Client,
     env_config: &EnvConfig,
-) -> Result<Client> {
+) -> Result<(Client, SqsBatchResponse)> {
     trace!("received event: {:?}", event);
 
+    let mut recollect = vec![];
     let events = event
         .records
         .into_iter()
         .filter_map(|event| {
-            event.body.map(|body| {
-                let body: Option<FlatS3EventMessages> = serde_json::from_str(&body)?;
-                Ok(body.unwrap_or_default())
+            let message_id = event.message_id;
+            event.body.map(|body| match serde_json::from_str(&body)? {
+                IngestMessage::Recollect(message) => {
+                    recollect.push((message_id, message.recollect));
+                    Ok(Default::default())
+                }
+                IngestMessage::Events(events) => Ok(events.unwrap_or_default()),
             })
         })
-        .collect::<Result<Vec<_>>>()?
+        .collect::<Result<Vec<FlatS3EventMessages>>>()?
         .into();
 
     trace!("flattened events: {:?}", events);
 
     let events = CollecterBuilder::default()
-        .with_s3_client(s3_client)
+        .with_s3_client(s3_client.clone())
         .build(events, env_config, &database_client)
         .await
         .collect()
@@ -81,7 +91,60 @@ pub async fn ingest_event(
     trace!("ingesting events: {:?}", events);
 
     database_client.ingest(events).await?;
-    Ok(database_client)
+
+    let mut response = SqsBatchResponse::default();
+    if !recollect.is_empty() {
+        let s3_object_ids = recollect
+            .iter()
+            .flat_map(|(_, ids)| ids.iter().copied())
+            .collect();
+        let failed =
+            recollect_records(s3_object_ids, &s3_client, &database_client, env_config).await?;
+
+        response.batch_item_failures = recollect
+            .into_iter()
+            .filter(|(_, ids)| ids.iter().any(|id| failed.contains(id)))
+            .filter_map(|(message_id, _)| message_id)
+            .map(|message_id| {
+                let mut failure = BatchItemFailure::default();
+                failure.item_identifier = message_id;
+                failure
+            })
+            .collect();
+    }
+
+    Ok((database_client, response))
+}
+
+/// Re-collect existing records that were requested through the ingest queue. A failure to
+/// collect one record does not affect the others. Returns the ids of the records that failed.
+pub async fn recollect_records(
+    s3_object_ids: Vec<Uuid>,
+    s3_client: &S3Client,
+    database_client: &Client,
+    env_config: &EnvConfig,
+) -> Result<HashSet<Uuid>> {
+    trace!("re-collecting records: {:?}", s3_object_ids);
+
+    let connection = database_client.connection_ref();
+    let records = s3_object::Entity::find()
+        .filter(s3_object::Column::S3ObjectId.is_in(s3_object_ids))
+        .all(connection)
+        .await?;
+
+    Ok(stream::iter(records)
+        .map(|record| async move {
+            let id = record.s3_object_id;
+            Collecter::recollect(env_config, s3_client, connection, record)
+                .await
+                .inspect_err(|err| warn!("failed to re-collect record {}: {}", id, err))
+                .err()
+                .map(|_| id)
+        })
+        .buffer_unordered(MAX_COLLECT_CONCURRENCY)
+        .filter_map(|id| async move { id })
+        .collect()
+        .await)
 }
 
 /// Handle an S3 inventory for ingestion.
@@ -99,7 +162,8 @@ pub async fn ingest_s3_inventory(
         ));
     }
 
-    let inventory = Inventory::new(s3_client);
+    let inventory =
+        Inventory::new(s3_client.with_default_version_id(env_config.ingester_default_version_id()));
 
     let records = if let Some(manifest) = manifest {
         inventory.parse_manifest(manifest).await?
@@ -112,8 +176,10 @@ pub async fn ingest_s3_inventory(
     };
     trace!("records extracted from inventory: {:#?}", records);
 
-    let transposed_events: TransposedS3EventMessages =
-        FlatS3EventMessages::from(records).sort_and_dedup().into();
+    let transposed_events: TransposedS3EventMessages = FlatS3EventMessages::from(records)
+        .replace_default_version_id(env_config.ingester_default_version_id())
+        .sort_and_dedup()
+        .into();
 
     let query = Query::new(database_client.clone());
 
@@ -213,7 +279,10 @@ pub(crate) mod tests {
     use std::future::Future;
 
     use aws_lambda_events::sqs::SqsMessage;
+    use aws_smithy_mocks::mock;
     use chrono::DateTime;
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel, PaginatorTrait};
     use sqlx::PgPool;
     use sqlx::postgres::PgRow;
 
@@ -226,19 +295,24 @@ pub(crate) mod tests {
     use crate::database::entities::sea_orm_active_enums::{ArchiveStatus, Reason};
     use crate::events::EventSourceType::S3;
     use crate::events::aws::FlatS3EventMessage;
-    use crate::events::aws::collecter::tests::{s3_client_expectations, sqs_client_expectations};
+    use crate::events::aws::collecter::tests::{
+        expected_get_object_tagging, expected_head_object, expected_head_object_status, mock_s3,
+        s3_client_expectations, sqs_client_expectations,
+    };
     use crate::events::aws::inventory::tests::{
         EXPECTED_LAST_MODIFIED_ONE, EXPECTED_LAST_MODIFIED_THREE, EXPECTED_LAST_MODIFIED_TWO,
         EXPECTED_QUOTED_E_TAG_KEY_2, MANIFEST_BUCKET, csv_manifest_from_key_expectations,
     };
     use crate::events::aws::message::EventType::Created;
     use crate::events::aws::message::EventType::Deleted;
+    use crate::events::aws::message::RecollectMessage;
     use crate::events::aws::message::default_version_id;
     use crate::events::aws::tests::{
         EXPECTED_QUOTED_E_TAG, EXPECTED_SEQUENCER_CREATED_ONE, EXPECTED_SEQUENCER_CREATED_TWO,
         EXPECTED_SEQUENCER_DELETED_ONE, EXPECTED_SHA256, EXPECTED_VERSION_ID,
         expected_event_record_simple,
     };
+    use crate::queries::EntriesBuilder;
 
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn test_receive_and_ingest(pool: PgPool) {
@@ -252,6 +326,68 @@ pub(crate) mod tests {
         .await;
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_ingest_event_recollect(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
+
+        let mut model: s3_object::ActiveModel = entries.s3_objects[0].clone().into_active_model();
+        model.sha256 = Set(None);
+        model.update(client.connection_ref()).await.unwrap();
+
+        let recollect_message = |message_id: &str, s3_object_id| {
+            let mut message = SqsMessage::default();
+            message.message_id = Some(message_id.to_string());
+            message.body =
+                Some(serde_json::to_string(&RecollectMessage::new(vec![s3_object_id])).unwrap());
+            message
+        };
+        let mut event = SqsEvent::default();
+        event.records = vec![
+            recollect_message("success", entries.s3_objects[0].s3_object_id),
+            recollect_message("failure", entries.s3_objects[2].s3_object_id),
+        ];
+
+        let s3_client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::head_object)
+                .match_requests(|req| req.key() != Some("2"))
+                .then_output(expected_head_object),
+            mock!(aws_sdk_s3::Client::head_object)
+                .match_requests(|req| req.key() == Some("2"))
+                .then_http_response(|| expected_head_object_status(404)),
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .then_output(|| expected_get_object_tagging(Some(Default::default()))),
+        ]);
+        let (client, response) = ingest_event(event, s3_client, client, &Default::default())
+            .await
+            .unwrap();
+
+        // Only the message with a record that failed to be collected is retried.
+        assert_eq!(
+            response
+                .batch_item_failures
+                .iter()
+                .map(|failure| failure.item_identifier.as_str())
+                .collect::<Vec<_>>(),
+            ["failure"]
+        );
+
+        // The existing record is updated in place, and no new records are created.
+        let record = s3_object::Entity::find_by_id(entries.s3_objects[0].s3_object_id)
+            .one(client.connection_ref())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.sha256, Some(EXPECTED_SHA256.to_string()));
+        assert_eq!(
+            s3_object::Entity::find()
+                .count(client.connection_ref())
+                .await
+                .unwrap(),
+            entries.s3_objects.len() as u64
+        );
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn test_ingest_event(pool: PgPool) {
         let s3_client = s3_client_expectations();
@@ -261,7 +397,7 @@ pub(crate) mod tests {
         message.body = Some(expected_event_record_simple(false));
         event.records = vec![message];
 
-        let ingester = ingest_event(
+        let (ingester, response) = ingest_event(
             event,
             s3_client,
             Client::from_pool(pool),
@@ -269,6 +405,7 @@ pub(crate) mod tests {
         )
         .await
         .unwrap();
+        assert!(response.batch_item_failures.is_empty());
 
         let s3_object_results = fetch_results_ordered(&ingester).await;
 
@@ -3,6 +3,7 @@
 //!
 
 pub mod clients;
+pub mod clock;
 pub mod database;
 pub mod env;
 pub mod error;
@@ -0,0 +1,79 @@
+//! Query builder for comparing the objects in two buckets.
+//!
+
+use sea_orm::{ConnectionTrait, DbBackend, FromQueryResult, Statement};
+
+use crate::error::Result;
+use crate::routes::diff::{BucketDiff, ChangedObject};
+use crate::routes::tenant::TenantScope;
+
+/// A query builder for comparing buckets.
+pub struct DiffQueryBuilder<'a, C> {
+    connection: &'a C,
+}
+
+/// A single difference between the source and destination bucket.
+#[derive(Debug, FromQueryResult)]
+struct BucketDiffRow {
+    source_key: Option<String>,
+    destination_key: Option<String>,
+    source_size: Option<i64>,
+    destination_size: Option<i64>,
+    source_e_tag: Option<String>,
+    destination_e_tag: Option<String>,
+}
+
+impl<'a, C> DiffQueryBuilder<'a, C>
+where
+    C: ConnectionTrait,
+{
+    /// Create a new query builder.
+    pub fn new(connection: &'a C) -> Self {
+        Self { connection }
+    }
+
+    /// Compare the current state of the source bucket and prefix with the destination bucket and
+    /// prefix. Keys under the source prefix are matched with keys under the destination prefix.
+    pub async fn diff_buckets(
+        &self,
+        source_bucket: &str,
+        source_prefix: &str,
+        destination_bucket: &str,
+        destination_prefix: &str,
+    ) -> Result<BucketDiff> {
+        TenantScope::check_bucket(source_bucket)?;
+        TenantScope::check_bucket(destination_bucket)?;
+
+        let rows = BucketDiffRow::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            include_str!("../../../database/queries/api/select_bucket_diff.sql"),
+            [
+                source_bucket.into(),
+                source_prefix.into(),
+                destination_bucket.into(),
+                destination_prefix.into(),
+            ],
+        ))
+        .all(self.connection)
+        .await?;
+
+        let mut diff = BucketDiff::default();
+        for row in rows {
+            match (row.source_key, row.destination_key) {
+                (Some(source_key), None) => diff.source_only.push(source_key),
+                (None, Some(destination_key)) => diff.destination_only.push(destination_key),
+                (Some(source_key), Some(destination_key)) => diff.changed.push(ChangedObject {
+                    source_key,
+                    destination_key,
+                    source_size: row.source_size,
+                    destination_size: row.destination_size,
+                    source_e_tag: row.source_e_tag,
+                    destination_e_tag: row.destination_e_tag,
+                }),
+                (None, None) => {}
+            }
+        }
+
+        Ok(diff)
+    }
+}
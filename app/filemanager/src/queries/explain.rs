@@ -0,0 +1,163 @@
+//! Query builder for explaining the current state of a key.
+//!
+
+use sea_orm::sea_query::NullOrdering;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, Order, QueryFilter, QueryOrder};
+
+use crate::database::entities::s3_object;
+use crate::database::entities::s3_object::Model as S3;
+use crate::database::entities::sea_orm_active_enums::EventType;
+use crate::error::Result;
+use crate::routes::explain::{CurrentStateExplanation, ExplainedEvent};
+use crate::routes::tenant::TenantScope;
+
+/// A query builder for explaining the current state of a key.
+pub struct ExplainQueryBuilder<'a, C> {
+    connection: &'a C,
+}
+
+impl<'a, C> ExplainQueryBuilder<'a, C>
+where
+    C: ConnectionTrait,
+{
+    /// Create a new query builder.
+    pub fn new(connection: &'a C) -> Self {
+        Self { connection }
+    }
+
+    /// Explain which record is the current state for the bucket and key. This replays the same
+    /// rules as `reset_current_state.sql` over all the records of the key.
+    pub async fn explain_current_state(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<CurrentStateExplanation> {
+        TenantScope::check_bucket(bucket)?;
+
+        let records = s3_object::Entity::find()
+            .filter(s3_object::Column::Bucket.eq(bucket))
+            .filter(s3_object::Column::Key.eq(key))
+            .order_by_with_nulls(
+                s3_object::Column::Sequencer,
+                Order::Desc,
+                NullOrdering::Last,
+            )
+            .order_by_desc(s3_object::Column::S3ObjectId)
+            .all(self.connection)
+            .await?;
+
+        Ok(Self::explain(bucket, key, records))
+    }
+
+    /// Explain the current state given all the records of a key, ordered by the latest
+    /// sequencer first.
+    fn explain(bucket: &str, key: &str, records: Vec<S3>) -> CurrentStateExplanation {
+        // The latest event for each version determines whether that version still exists.
+        let latest_for_version = |index: usize| {
+            records
+                .iter()
+                .position(|record| record.version_id == records[index].version_id)
+                .expect("record should be present")
+        };
+        let is_current_version = |index: usize| {
+            let record = &records[index];
+            latest_for_version(index) == index
+                && (record.is_delete_marker || record.event_type == EventType::Created)
+        };
+        let latest_current_version = (0..records.len()).find(|index| is_current_version(*index));
+
+        let mut events = Vec::with_capacity(records.len());
+        for (index, record) in records.iter().enumerate() {
+            let latest = &records[latest_for_version(index)];
+            let current_version = is_current_version(index);
+            let is_current_state = current_version
+                && latest_current_version == Some(index)
+                && !record.is_delete_marker;
+
+            let explanation = if latest.s3_object_id != record.s3_object_id {
+                format!(
+                    "superseded by a later event for version `{}` with sequencer `{}`",
+                    record.version_id,
+                    display_sequencer(latest)
+                )
+            } else if !current_version {
+                format!(
+                    "the latest event for version `{}` is a permanent delete, so this version no longer exists",
+                    record.version_id
+                )
+            } else if let Some(current) = latest_current_version.filter(|current| *current != index)
+            {
+                let current = &records[current];
+                format!(
+                    "version `{}` still exists, but is superseded by the later version `{}` with sequencer `{}`",
+                    record.version_id,
+                    current.version_id,
+                    display_sequencer(current)
+                )
+            } else if record.is_delete_marker {
+                "this delete marker is the latest version, so the key has no current state"
+                    .to_string()
+            } else {
+                "this is the latest event of the latest version, so it is the current state"
+                    .to_string()
+            };
+
+            events.push(ExplainedEvent {
+                s3_object_id: record.s3_object_id,
+                event_type: record.event_type.clone(),
+                version_id: record.version_id.clone(),
+                sequencer: record.sequencer.clone(),
+                event_time: record.event_time,
+                is_delete_marker: record.is_delete_marker,
+                is_current_version: current_version,
+                is_current_state,
+                stored_is_current_state: record.is_current_state,
+                explanation,
+            });
+        }
+
+        let explanation = match latest_current_version.map(|index| &records[index]) {
+            _ if records.is_empty() => "there are no records for this key".to_string(),
+            Some(current) if current.is_delete_marker => format!(
+                "the key has no current state because the latest version `{}` with sequencer `{}` is a delete marker",
+                current.version_id,
+                display_sequencer(current)
+            ),
+            Some(current) => format!(
+                "record `{}` is the current state because it is the latest event of the latest version `{}` with sequencer `{}`",
+                current.s3_object_id,
+                current.version_id,
+                display_sequencer(current)
+            ),
+            None => {
+                "the key has no current state because all versions have been permanently deleted"
+                    .to_string()
+            }
+        };
+
+        let current_s3_object_id = events
+            .iter()
+            .find(|event| event.is_current_state)
+            .map(|event| event.s3_object_id);
+        let is_consistent = events
+            .iter()
+            .all(|event| event.is_current_state == event.stored_is_current_state);
+
+        // Return the events in the order that they occurred.
+        events.reverse();
+
+        CurrentStateExplanation {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            current_s3_object_id,
+            is_consistent,
+            explanation,
+            events,
+        }
+    }
+}
+
+/// Format a sequencer for an explanation.
+fn display_sequencer(record: &S3) -> &str {
+    record.sequencer.as_deref().unwrap_or("null")
+}
@@ -1,11 +1,19 @@
 //! Query builder involving get operations on the database.
 //!
 
-use sea_orm::{ConnectionTrait, EntityTrait, Select};
+use std::collections::HashMap;
+
+use sea_orm::prelude::Json;
+use sea_orm::sea_query::{Alias, Asterisk, Expr, Func, OverStatement, WindowStatement};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, QueryFilter, QuerySelect, QueryTrait,
+    Select,
+};
 use uuid::Uuid;
 
 use crate::database::entities::{s3_crawl, s3_object};
 use crate::error::Result;
+use crate::routes::tenant::TenantScope;
 
 /// A query builder for get operations.
 pub struct GetQueryBuilder<'a, C> {
@@ -21,9 +29,13 @@ where
         Self { connection }
     }
 
-    /// Build a select query for finding an s3 object by id.
+    /// Build a select query for finding an s3 object by id, restricted to the buckets of the
+    /// tenant of the current request.
     pub fn build_s3_by_id(id: Uuid) -> Select<s3_object::Entity> {
-        s3_object::Entity::find_by_id(id)
+        s3_object::Entity::find_by_id(id).apply_if(
+            TenantScope::bucket_condition(s3_object::Column::Bucket),
+            QueryFilter::filter,
+        )
     }
 
     /// Get a specific s3 object by id.
@@ -31,9 +43,78 @@ where
         Ok(Self::build_s3_by_id(id).one(self.connection).await?)
     }
 
-    /// Build a select query for finding an crawl row by id.
+    /// Get only the attributes of a specific s3 object by id. This returns `None` if the object
+    /// does not exist, and `Some(None)` if the object exists but has null attributes.
+    pub async fn get_s3_attributes_by_id(&self, id: Uuid) -> Result<Option<Option<Json>>> {
+        Ok(Self::build_s3_by_id(id)
+            .select_only()
+            .column(s3_object::Column::Attributes)
+            .into_tuple::<Option<Json>>()
+            .one(self.connection)
+            .await?)
+    }
+
+    /// Build a select query which counts all events recorded for the bucket and key of the
+    /// objects using a window function. This produces a query similar to:
+    ///
+    /// ```sql
+    /// select s3_object_id, count(*) over (partition by bucket, key) as event_count
+    /// from s3_object
+    /// where (bucket = ... and key = ...) or ...;
+    /// ```
+    pub fn build_event_counts(objects: &[s3_object::Model]) -> Select<s3_object::Entity> {
+        let condition = objects.iter().fold(Condition::any(), |acc, object| {
+            acc.add(
+                Condition::all()
+                    .add(s3_object::Column::Bucket.eq(&object.bucket))
+                    .add(s3_object::Column::Key.eq(&object.key)),
+            )
+        });
+
+        let mut select = s3_object::Entity::find()
+            .select_only()
+            .column(s3_object::Column::S3ObjectId)
+            .filter(condition)
+            .apply_if(
+                TenantScope::bucket_condition(s3_object::Column::Bucket),
+                QueryFilter::filter,
+            );
+        QuerySelect::query(&mut select).expr_window_as(
+            Func::count(Expr::col(Asterisk)),
+            WindowStatement::new()
+                .partition_by_columns([s3_object::Column::Bucket, s3_object::Column::Key])
+                .to_owned(),
+            Alias::new("event_count"),
+        );
+
+        select
+    }
+
+    /// Get the number of events recorded for the bucket and key of each object, keyed by the
+    /// `s3_object_id`.
+    pub async fn get_event_counts(
+        &self,
+        objects: &[s3_object::Model],
+    ) -> Result<HashMap<Uuid, i64>> {
+        if objects.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        Ok(Self::build_event_counts(objects)
+            .into_tuple::<(Uuid, i64)>()
+            .all(self.connection)
+            .await?
+            .into_iter()
+            .collect())
+    }
+
+    /// Build a select query for finding an crawl row by id, restricted to the buckets of the
+    /// tenant of the current request.
     pub fn build_crawl_by_id(id: Uuid) -> Select<s3_crawl::Entity> {
-        s3_crawl::Entity::find_by_id(id)
+        s3_crawl::Entity::find_by_id(id).apply_if(
+            TenantScope::bucket_condition(s3_crawl::Column::Bucket),
+            QueryFilter::filter,
+        )
     }
 
     /// Get a specific crawl row by id.
@@ -68,6 +149,31 @@ mod tests {
         assert_eq!(result.as_ref(), Some(first));
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_get_event_counts(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default()
+            .with_n(6)
+            .with_key_divisor(3)
+            .build(&client)
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let builder = GetQueryBuilder::new(client.connection_ref());
+        let result = builder.get_event_counts(&entries[0..1]).await.unwrap();
+        assert_eq!(
+            result,
+            HashMap::from_iter(vec![
+                (entries[0].s3_object_id, 2),
+                (entries[1].s3_object_id, 2)
+            ])
+        );
+
+        let result = builder.get_event_counts(&[]).await.unwrap();
+        assert!(result.is_empty());
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn test_get_crawl(pool: PgPool) {
         let client = Client::from_pool(pool);
@@ -1,27 +1,42 @@
 //! Query builder involving list operations on the database.
 //!
 
-use sea_orm::prelude::Expr;
+use chrono::NaiveDateTime;
+use futures::{Stream, TryStreamExt};
+use sea_orm::prelude::{DateTimeWithTimeZone, Expr};
 use sea_orm::sea_query::extension::postgres::PgExpr;
 use sea_orm::sea_query::{
-    Alias, BinOper, ColumnRef, ConditionExpression, IntoColumnRef, IntoCondition, NullOrdering,
-    PostgresQueryBuilder, SimpleExpr,
+    Alias, BinOper, ColumnRef, ConditionExpression, Func, IntoColumnRef, IntoCondition,
+    NullOrdering, PostgresQueryBuilder, Query, SimpleExpr,
 };
 use sea_orm::{
-    ColumnTrait, Condition, ConnectionTrait, EntityTrait, FromQueryResult, IntoSimpleExpr,
-    JsonValue, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Select,
+    ActiveEnum, ColumnTrait, Condition, ConnectionTrait, DbBackend, EntityTrait, FromQueryResult,
+    IntoSimpleExpr, JsonValue, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait, Select, Statement, StreamTrait,
 };
+use std::collections::{BTreeMap, HashMap};
 use tracing::trace;
 use url::Url;
+use uuid::Uuid;
 
-use crate::database::entities::{s3_crawl, s3_object};
+use crate::database::entities::sea_orm_active_enums::{EventType, Reason, StorageClass};
+use crate::database::entities::{s3_crawl, s3_crawl_schedule, s3_object};
 use crate::error::Error::{OverflowError, QueryError};
 use crate::error::{Error, Result};
+use crate::routes::conditional::ListVersion;
 use crate::routes::filter::crawl::S3CrawlFilter;
 use crate::routes::filter::wildcard::{Wildcard, WildcardEither};
-use crate::routes::filter::{FilterJoinMerged, Join, S3ObjectsFilter};
-use crate::routes::list::ListCount;
-use crate::routes::pagination::{ListResponse, Pagination};
+use crate::routes::filter::{
+    Comparison, CountComparison, FilterJoinMerged, Join, Origin, S3ObjectsFilter,
+    namespace_attributes,
+};
+use crate::routes::lifecycle::{HistoryInterval, StorageClassCount, StorageClassPeriod};
+use crate::routes::list::{AttributeKey, ExtensionCount, ListCount, SizeETagCount};
+use crate::routes::pagination::{Cursor, CursorPosition, ListResponse, Pagination};
+use crate::routes::presence::KeyPresence;
+use crate::routes::sequencer::SequencedRecord;
+use crate::routes::stats::IngestionStats;
+use crate::routes::tenant::TenantScope;
 
 /// A query builder for list operations.
 #[derive(Debug, Clone)]
@@ -45,13 +60,21 @@ where
         }
     }
 
-    /// Define a select query for finding values from s3 objects.
+    /// Define a select query for finding values from s3 objects. The `s3_object_id` breaks ties
+    /// between equal sequencers so that the order is stable for keyset pagination. The query is
+    /// restricted to the buckets of the tenant of the current request.
     pub fn for_s3() -> Select<s3_object::Entity> {
-        s3_object::Entity::find().order_by_with_nulls(
-            s3_object::Column::Sequencer,
-            Order::Asc,
-            NullOrdering::First,
-        )
+        s3_object::Entity::find()
+            .apply_if(
+                TenantScope::bucket_condition(s3_object::Column::Bucket),
+                QueryFilter::filter,
+            )
+            .order_by_with_nulls(
+                s3_object::Column::Sequencer,
+                Order::Asc,
+                NullOrdering::First,
+            )
+            .order_by(s3_object::Column::S3ObjectId, Order::Asc)
     }
 
     /// Filter records by all fields in the filter variable.
@@ -94,9 +117,9 @@ where
                     .map(|v| s3_object::Column::EventType.eq(v)),
             )
             .add_option(Self::join(filter.bucket, |v| {
-                Self::filter_operation(
+                Self::filter_bucket_operation(
                     Expr::col(s3_object::Column::Bucket),
-                    WildcardEither::Wildcard::<String>(v),
+                    v,
                     case_sensitive,
                 )
             })?)
@@ -107,6 +130,9 @@ where
                     case_sensitive,
                 )
             })?)
+            .add_option(Self::join(filter.key_suffix, |v| {
+                Ok(Self::key_suffix_condition(&v, case_sensitive))
+            })?)
             .add_option(Self::join(filter.version_id, |v| {
                 Self::filter_operation(
                     Expr::col(s3_object::Column::VersionId),
@@ -142,6 +168,7 @@ where
             .add_option(Self::join(filter.reason, |v| {
                 Ok(s3_object::Column::Reason.eq(v))
             })?)
+            .add_option(filter.origin.map(Self::origin_condition))
             .add_option(
                 filter
                     .is_delete_marker
@@ -154,7 +181,54 @@ where
             )
             .add_option(Self::join(filter.ingest_id, |v| {
                 Ok(s3_object::Column::IngestId.eq(v))
-            })?);
+            })?)
+            .add_option(Self::join(filter.server_side_encryption, |v| {
+                Ok(s3_object::Column::ServerSideEncryption.eq(v))
+            })?)
+            .add_option(Self::join(filter.sse_kms_key_id, |v| {
+                Ok(s3_object::Column::SseKmsKeyId.eq(v))
+            })?)
+            .add_option(filter.is_encrypted.map(|v| {
+                if v {
+                    s3_object::Column::ServerSideEncryption.is_not_null()
+                } else {
+                    s3_object::Column::ServerSideEncryption.is_null()
+                }
+            }))
+            .add_option(filter.tag_missing.map(Self::tag_missing_condition))
+            .add_option(
+                filter
+                    .is_sequencer_conflict
+                    .map(|v| s3_object::Column::IsSequencerConflict.eq(v)),
+            )
+            .add_option(
+                filter
+                    .event_time_divergence
+                    .map(Self::event_time_divergence_condition),
+            )
+            .add_option(filter.stale_before.map(Self::stale_before_condition))
+            .add_option(
+                filter
+                    .missing_metadata
+                    .map(Self::missing_metadata_condition),
+            )
+            .add_option(
+                filter
+                    .exclude_folder_placeholders
+                    .filter(|exclude| *exclude)
+                    .map(|_| Self::exclude_folder_placeholders_condition()),
+            )
+            .add_option(
+                filter
+                    .unmodified_for_days
+                    .map(Self::unmodified_for_days_condition),
+            )
+            .add_option(
+                filter
+                    .version_count
+                    .map(Self::version_count_condition)
+                    .transpose()?,
+            );
 
         if current_state {
             condition = condition
@@ -163,6 +237,10 @@ where
         }
 
         if let Some(attributes) = filter.attributes {
+            let attributes = match filter.attribute_namespace {
+                Some(namespace) => namespace_attributes(&namespace, attributes),
+                None => attributes,
+            };
             let json_condition = JsonPathBuilder::json_condition(
                 s3_object::Column::Attributes.into_column_ref(),
                 attributes,
@@ -173,225 +251,1547 @@ where
 
         Ok(condition)
     }
-}
-
-impl<'a, C> ListQueryBuilder<'a, C, s3_crawl::Entity>
-where
-    C: ConnectionTrait,
-{
-    /// Create a new query builder.
-    pub fn new(connection: &'a C) -> Self {
-        Self {
-            connection,
-            select: Self::for_crawl(),
-        }
-    }
-
-    /// Define a select query for finding values from s3 crawl rows.
-    pub fn for_crawl() -> Select<s3_crawl::Entity> {
-        s3_crawl::Entity::find()
-            .order_by_asc(s3_crawl::Column::Status)
-            .order_by_asc(s3_crawl::Column::Bucket)
-            .order_by_asc(s3_crawl::Column::Prefix)
-    }
 
-    /// Filter records by all fields in the filter variable.
+    /// Filter records to permanently deleted objects, with an optional `event_time` range.
     ///
     /// This creates a query which is similar to:
     ///
     /// ```sql
-    /// select * from s3_crawl
-    /// where status = filter.status and
-    ///     bucket = filter.bucket and
-    ///     ...;
+    /// select * from s3_object
+    /// where event_type = 'Deleted' and
+    ///     is_delete_marker = false and
+    ///     event_time >= from and
+    ///     event_time < to and
+    ///     not exists (
+    ///         select 1 from s3_object current
+    ///         where current.bucket = s3_object.bucket and
+    ///             current.key = s3_object.key and
+    ///             current.is_current_state = true and
+    ///             current.event_type = 'Created' and
+    ///             current.is_delete_marker = false
+    ///     );
     /// ```
-    pub fn filter_all(mut self, filter: S3CrawlFilter, case_sensitive: bool) -> Result<Self> {
+    pub fn filter_permanently_deleted(
+        mut self,
+        from: Option<DateTimeWithTimeZone>,
+        to: Option<DateTimeWithTimeZone>,
+    ) -> Self {
         self.select = self
             .select
-            .filter(Self::filter_condition(filter, case_sensitive)?);
+            .filter(Self::permanently_deleted_condition(from, to));
 
-        self.trace_query("filter_all");
+        self.trace_query("filter_permanently_deleted");
 
-        Ok(self)
+        self
     }
 
-    /// Create a condition to filter a query.
-    pub fn filter_condition(filter: S3CrawlFilter, case_sensitive: bool) -> Result<Condition> {
-        let condition = Condition::all()
-            .add_option(Self::join(filter.bucket, |v| {
-                Self::filter_operation(
-                    Expr::col(s3_crawl::Column::Bucket),
-                    WildcardEither::Wildcard::<String>(v),
-                    case_sensitive,
-                )
-            })?)
-            .add_option(Self::join(filter.prefix, |v| {
-                Self::filter_operation(
-                    Expr::col(s3_crawl::Column::Prefix),
-                    WildcardEither::Wildcard::<String>(v),
-                    case_sensitive,
-                )
-            })?)
-            .add_option(Self::join(filter.started, |v| {
-                Self::filter_operation(Expr::col(s3_crawl::Column::Started), v, case_sensitive)
-            })?)
-            .add_option(Self::join(filter.status, |v| {
-                Ok(s3_crawl::Column::Status.eq(v))
-            })?);
+    /// Filter records to current `Standard` tier objects which have not been modified for
+    /// `min_age_days` and are larger than `min_size` bytes. These are candidates for moving to
+    /// a cheaper storage class.
+    pub fn filter_tiering_candidates(mut self, min_age_days: u64, min_size: i64) -> Self {
+        self.select = self
+            .select
+            .filter(Self::tiering_candidate_condition(min_age_days, min_size));
 
-        Ok(condition)
-    }
-}
+        self.trace_query("filter_tiering_candidates");
 
-impl<'a, C, E> From<(&'a C, Select<E>)> for ListQueryBuilder<'a, C, E>
-where
-    C: ConnectionTrait,
-    E: EntityTrait,
-{
-    fn from((connection, select): (&'a C, Select<E>)) -> Self {
-        Self { connection, select }
+        self
     }
-}
 
-impl<'a, C, E> ListQueryBuilder<'a, C, E>
-where
-    C: ConnectionTrait,
-    E: EntityTrait,
-{
-    /// Get the inner connection and select statement.
-    pub fn into_inner(self) -> (&'a C, Select<E>) {
-        (self.connection, self.select)
-    }
+    /// Filter records to current `Created` events where collection failed. These are records
+    /// which are missing a sha256 checksum, an ingest id or metadata from `HeadObject`.
+    pub fn filter_failed_collection(mut self) -> Self {
+        self.select = self.select.filter(Self::failed_collection_condition());
 
-    /// Get a copy of this query builder
-    pub fn cloned(&self) -> Self {
-        (self.connection, self.select.clone()).into()
+        self.trace_query("filter_failed_collection");
+
+        self
     }
 
-    /// Join a series expressions with an 'or' or 'and' statement.
-    pub fn join<T, F, I>(input: FilterJoinMerged<I>, mut map: F) -> Result<Option<Condition>>
-    where
-        T: Into<ConditionExpression>,
-        F: FnMut(I) -> Result<T>,
-    {
-        let conditions = input
-            .0
-            .into_iter()
-            .map(|(join, input)| {
-                let condition = match join {
-                    Join::And => Condition::all(),
-                    Join::Or => Condition::any(),
-                };
+    /// Filter records to the latest record for each bucket and key, using the highest sequencer
+    /// rather than the stored `is_current_state` flag.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select * from s3_object
+    /// where s3_object_id in (
+    ///     select distinct on (bucket, key) s3_object_id from s3_object
+    ///     where bucket = ? and key like ?
+    ///     order by bucket, key, sequencer desc nulls last
+    /// );
+    /// ```
+    ///
+    /// The bucket, key and key suffix filters are pushed into the subquery so that it only
+    /// scans the keys that can match.
+    pub fn filter_latest_per_key(
+        mut self,
+        filter: &S3ObjectsFilter,
+        case_sensitive: bool,
+    ) -> Result<Self> {
+        self.select = self
+            .select
+            .filter(Self::latest_per_key_condition(filter, case_sensitive)?);
 
-                let mapped = input
-                    .into_iter()
-                    .map(&mut map)
-                    .collect::<Result<Vec<_>>>()?;
-                Ok(mapped
-                    .into_iter()
-                    .fold(condition, |acc, element| acc.add(element)))
-            })
-            .collect::<Result<Vec<_>>>()?;
+        self.trace_query("filter_latest_per_key");
 
-        if conditions.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(
-                conditions
-                    .into_iter()
-                    .fold(Condition::all(), |acc, element| acc.add(element)),
-            ))
-        }
+        Ok(self)
     }
-}
 
-impl<C, E, M> ListQueryBuilder<'_, C, E>
-where
-    C: ConnectionTrait,
-    E: EntityTrait<Model = M>,
-    M: FromQueryResult + Send + Sync,
-{
-    /// Execute the prepared query, fetching all values.
-    pub async fn all(self) -> Result<Vec<M>> {
-        Ok(self.select.all(self.connection).await?)
+    /// Execute the prepared query, summing the size of all values.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select coalesce(sum(size), 0)::bigint from s3_object;
+    /// ```
+    pub async fn total_size(self) -> Result<i64> {
+        let mut select = self.select.select_only().expr_as(
+            Expr::cust_with_expr(
+                "coalesce(sum($1), 0)::bigint",
+                Expr::col(s3_object::Column::Size),
+            ),
+            "total_size",
+        );
+        QuerySelect::query(&mut select).clear_order_by();
+
+        Ok(select
+            .into_tuple::<i64>()
+            .one(self.connection)
+            .await?
+            .unwrap_or_default())
     }
 
-    /// Execute the prepared query, fetching one value.
-    pub async fn one(self) -> Result<Option<M>> {
-        Ok(self.select.one(self.connection).await?)
+    /// Filter records to keys where the storage class or archive status has changed across
+    /// the records of the bucket and key.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select * from s3_object
+    /// where (
+    ///     select count(distinct (transitions.storage_class, transitions.archive_status))
+    ///     from s3_object transitions
+    ///     where transitions.bucket = s3_object.bucket and
+    ///         transitions.key = s3_object.key and
+    ///         transitions.storage_class is not null
+    /// ) > 1;
+    /// ```
+    pub fn filter_storage_class_transitions(mut self) -> Self {
+        self.select = self
+            .select
+            .filter(Self::storage_class_transition_condition());
+
+        self.trace_query("filter_storage_class_transitions");
+
+        self
     }
 
-    /// Execute the prepared query, counting all values.
-    pub async fn count(self) -> Result<u64> {
-        Ok(self.select.count(self.connection).await?)
+    /// Filter records to those with one of the bucket and key pairs.
+    pub fn filter_keys(mut self, keys: Vec<(String, String)>) -> Self {
+        self.select = self.select.filter(
+            Expr::tuple([
+                Expr::col(s3_object::Column::Bucket).into(),
+                Expr::col(s3_object::Column::Key).into(),
+            ])
+            .in_tuples(keys),
+        );
+
+        self.trace_query("filter_keys");
+
+        self
     }
 
-    /// Paginate the query for the given page and page_size.
+    /// Filter records to keys within the range, from `start` inclusive to `end` exclusive. Keys
+    /// are compared by bytes, which matches the order that S3 lists keys in.
     ///
-    /// This produces a query similar to:
+    /// This creates a query which is similar to:
     ///
     /// ```sql
     /// select * from s3_object
-    ///     limit page_size
-    ///     offset page * page_size;
+    /// where key collate "C" >= start and key collate "C" < end;
     /// ```
-    pub async fn paginate(mut self, offset: u64, page_size: u64) -> Result<Self> {
-        let offset = offset.checked_mul(page_size).ok_or_else(|| OverflowError)?;
-        self.select = self.select.offset(offset).limit(page_size);
+    pub fn filter_key_range(mut self, start: Option<String>, end: Option<String>) -> Self {
+        let key = || {
+            Expr::expr(Expr::cust_with_expr(
+                "$1 collate \"C\"",
+                Expr::col(s3_object::Column::Key),
+            ))
+        };
+        self.select = self.select.filter(
+            Condition::all()
+                .add_option(start.map(|start| key().gte(start)))
+                .add_option(end.map(|end| key().lt(end))),
+        );
 
-        self.trace_query("paginate");
+        self.trace_query("filter_key_range");
 
-        Ok(self)
+        self
     }
 
-    /// Create a list response from pagination query parameters and a query builder.
-    pub async fn paginate_to_list_response(
-        self,
-        pagination: Pagination,
-        page_link: Url,
-        count: u64,
-    ) -> Result<ListResponse<M>> {
-        let offset = pagination.offset()?;
-        let page_size = pagination.rows_per_page();
-        let mut query = self.paginate(offset, page_size).await?;
-
-        // Always add one to the limit to see if there is additional pages that can be fetched.
-        QuerySelect::query(&mut query.select).reset_limit();
-        query.select = query
+    /// Filter records to those with one of the `ingest_id`s.
+    pub fn filter_ingest_ids(mut self, ingest_ids: Vec<Uuid>) -> Self {
+        self.select = self
             .select
-            .limit(page_size.checked_add(1).ok_or_else(|| OverflowError)?);
-
-        let mut results = query.all().await?;
+            .filter(s3_object::Column::IngestId.is_in(ingest_ids));
 
-        // Check if there are more pages using the knowledge that we fetched one additional record.
-        let next_page = if results.len()
-            <= usize::try_from(page_size).map_err(|err| Error::ConversionError(err.to_string()))?
-        {
-            None
-        } else {
-            // Remove the last element because we fetched one additional result.
-            results.pop();
-            Some(
-                pagination
-                    .page()
-                    .checked_add(1)
-                    .ok_or_else(|| OverflowError)?,
-            )
-        };
+        self.trace_query("filter_ingest_ids");
 
-        ListResponse::from_next_page(pagination, results, next_page, page_link, count)
+        self
     }
 
-    /// Create a list count from a query builder.
-    pub async fn to_list_count(self) -> Result<ListCount> {
-        Ok(ListCount::new(self.count().await?))
+    /// Execute the prepared query, finding the version of the matching records. This consists of
+    /// the number of records, the maximum `updated_at`, and the sum of all `updated_at` values,
+    /// which changes whenever any matching record is updated, even if it is not the most recent.
+    /// It also includes the time that any record was last updated or removed, which uses the
+    /// `updated_at` index and the `s3_object_removed` table.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select
+    ///     count(*),
+    ///     max(updated_at),
+    ///     sum((extract(epoch from updated_at) * 1000000)::bigint)::text,
+    ///     (
+    ///         select greatest(max(updated_at), (select removed_at from s3_object_removed))
+    ///         from s3_object
+    ///     )
+    /// from s3_object;
+    /// ```
+    pub async fn to_list_version(self) -> Result<ListVersion> {
+        let mut select = self
+            .select
+            .select_only()
+            .column_as(Expr::cust("count(*)"), "n_records")
+            .column_as(Expr::cust("max(s3_object.updated_at)"), "last_updated")
+            .column_as(
+                Expr::cust(
+                    "coalesce(sum((extract(epoch from s3_object.updated_at) * 1000000)::bigint), 0)::text",
+                ),
+                "checksum",
+            )
+            .column_as(
+                Expr::cust(
+                    "(select greatest(max(updated_at), (select removed_at from s3_object_removed)) from s3_object)",
+                ),
+                "last_modified",
+            );
+        QuerySelect::query(&mut select).clear_order_by();
+
+        let (n_records, last_updated, checksum, last_modified) = select
+            .into_tuple::<(
+                i64,
+                Option<DateTimeWithTimeZone>,
+                String,
+                Option<DateTimeWithTimeZone>,
+            )>()
+            .one(self.connection)
+            .await?
+            .unwrap_or_default();
+
+        Ok(ListVersion::new(
+            u64::try_from(n_records)?,
+            last_updated,
+            checksum,
+            last_modified,
+        ))
     }
 
-    /// Create an operation based on the wildcard. This either results in a `like`/`ilike` or `=` comparison.
-    pub fn filter_operation<S, W>(
-        expr: S,
-        wildcard: WildcardEither<W>,
-        case_sensitive: bool,
+    /// Execute the prepared query, computing ingestion statistics for records which were ingested
+    /// within the `window` of seconds before now. Records without an `ingested_at` are excluded.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select
+    ///     count(*),
+    ///     avg(extract(epoch from ingested_at - event_time))::float8,
+    ///     count(*) filter (where is_e_tag_mismatch or is_sequencer_conflict),
+    ///     count(*) filter (where event_type = 'Created'),
+    ///     count(*) filter (where event_type = 'Created' and ingest_id is null)
+    /// from s3_object where ingested_at >= now() - make_interval(secs => window);
+    /// ```
+    pub async fn ingestion_stats(self, window: u64) -> Result<IngestionStats> {
+        let mut select = self
+            .select
+            .select_only()
+            .column_as(Expr::cust("count(*)"), "n_ingested")
+            .column_as(
+                Expr::cust("avg(extract(epoch from s3_object.ingested_at - s3_object.event_time))::float8"),
+                "average_lag",
+            )
+            .column_as(
+                Expr::cust("count(*) filter (where s3_object.is_e_tag_mismatch or s3_object.is_sequencer_conflict)"),
+                "n_errors",
+            )
+            .column_as(
+                Expr::cust("count(*) filter (where s3_object.event_type = 'Created')"),
+                "n_created",
+            )
+            .column_as(
+                Expr::cust("count(*) filter (where s3_object.event_type = 'Created' and s3_object.ingest_id is null)"),
+                "n_null_ingest_id",
+            )
+            .filter(Expr::cust_with_values(
+                "s3_object.ingested_at >= now() - make_interval(secs => $1)",
+                [i64::try_from(window)?],
+            ));
+        QuerySelect::query(&mut select).clear_order_by();
+
+        let (n_ingested, average_lag, n_errors, n_created, n_null_ingest_id) = select
+            .into_tuple::<(i64, Option<f64>, i64, i64, i64)>()
+            .one(self.connection)
+            .await?
+            .unwrap_or_default();
+
+        Ok(IngestionStats::new(
+            window,
+            u64::try_from(n_ingested)?,
+            average_lag,
+            u64::try_from(n_errors)?,
+            u64::try_from(n_created)?,
+            u64::try_from(n_null_ingest_id)?,
+        ))
+    }
+
+    /// Execute the prepared query, finding the distinct non-null `ingest_id`s in ascending order,
+    /// up to the limit.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select distinct ingest_id from s3_object
+    /// where ingest_id is not null order by ingest_id limit 1000;
+    /// ```
+    pub async fn distinct_ingest_ids(self, limit: u64) -> Result<Vec<Uuid>> {
+        let mut select = self
+            .select
+            .select_only()
+            .column(s3_object::Column::IngestId)
+            .distinct()
+            .filter(s3_object::Column::IngestId.is_not_null());
+        QuerySelect::query(&mut select).clear_order_by();
+
+        Ok(select
+            .order_by(s3_object::Column::IngestId, Order::Asc)
+            .limit(limit)
+            .into_tuple::<Uuid>()
+            .all(self.connection)
+            .await?)
+    }
+
+    /// Execute the prepared query, finding the distinct bucket and key pairs in ascending order,
+    /// up to the limit.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select distinct bucket, key from s3_object order by bucket, key limit 1000;
+    /// ```
+    pub async fn distinct_keys(self, limit: u64) -> Result<Vec<(String, String)>> {
+        let mut select = self
+            .select
+            .select_only()
+            .column(s3_object::Column::Bucket)
+            .column(s3_object::Column::Key)
+            .distinct();
+        QuerySelect::query(&mut select).clear_order_by();
+
+        Ok(select
+            .order_by(s3_object::Column::Bucket, Order::Asc)
+            .order_by(s3_object::Column::Key, Order::Asc)
+            .limit(limit)
+            .into_tuple::<(String, String)>()
+            .all(self.connection)
+            .await?)
+    }
+
+    /// Execute the prepared query, counting the number of records for each `reason`.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select reason, count(*) from s3_object group by reason;
+    /// ```
+    pub async fn count_by_reason(self) -> Result<HashMap<Reason, u64>> {
+        let mut select = self
+            .select
+            .select_only()
+            .column(s3_object::Column::Reason)
+            .expr_as(Expr::cust("count(*)"), "count")
+            .group_by(s3_object::Column::Reason);
+        QuerySelect::query(&mut select).clear_order_by();
+
+        select
+            .into_tuple::<(Reason, i64)>()
+            .all(self.connection)
+            .await?
+            .into_iter()
+            .map(|(reason, count)| Ok((reason, u64::try_from(count)?)))
+            .collect()
+    }
+
+    /// Execute the prepared query, counting the number of records for each key depth. The depth
+    /// is the number of `/` characters in the key, so keys at the root of a bucket have a depth
+    /// of zero.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select length(key) - length(replace(key, '/', '')) as depth, count(*)
+    /// from s3_object group by depth;
+    /// ```
+    pub async fn count_by_key_depth(self) -> Result<BTreeMap<u64, u64>> {
+        let depth = Expr::cust_with_expr(
+            "(length($1) - length(replace($1, '/', '')))::bigint",
+            Expr::col(s3_object::Column::Key),
+        );
+        let mut select = self
+            .select
+            .select_only()
+            .expr_as(depth, "depth")
+            .expr_as(Expr::cust("count(*)"), "count")
+            .group_by(Expr::cust("depth"));
+        QuerySelect::query(&mut select).clear_order_by();
+
+        select
+            .into_tuple::<(i64, i64)>()
+            .all(self.connection)
+            .await?
+            .into_iter()
+            .map(|(depth, count)| Ok((u64::try_from(depth)?, u64::try_from(count)?)))
+            .collect()
+    }
+
+    /// Execute the prepared query, counting the number of records and their total size for each
+    /// file extension, ordered by the count descending. The extension is taken from the last
+    /// segment of the key, and is lowercased. Compression extensions such as `.gz` include the
+    /// preceding extension, so `sample.R1.fastq.gz` has the extension `.fastq.gz`. Keys without an
+    /// extension, including names that only start with a `.`, are grouped under `None`.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select
+    ///     substring(
+    ///         lower(regexp_replace(key, '^.*/', ''))
+    ///         from '[^.]\.(([^.]+\.(gz|bz2|xz|zst|zip))|[^.]+)$'
+    ///     ) as extension,
+    ///     count(*),
+    ///     coalesce(sum(size), 0)
+    /// from s3_object group by extension
+    /// order by count(*) desc, extension;
+    /// ```
+    pub async fn count_by_extension(self) -> Result<Vec<ExtensionCount>> {
+        let extension = Expr::cust_with_expr(
+            r"substring(lower(regexp_replace($1, '^.*/', '')) from '[^.]\.(([^.]+\.(gz|bz2|xz|zst|zip))|[^.]+)$')",
+            Expr::col(s3_object::Column::Key),
+        );
+        let mut select = self
+            .select
+            .select_only()
+            .expr_as(extension, "extension")
+            .expr_as(Expr::cust("count(*)"), "count")
+            .expr_as(Expr::cust("coalesce(sum(size), 0)::bigint"), "size")
+            .group_by(Expr::cust("extension"));
+        QuerySelect::query(&mut select).clear_order_by();
+
+        select
+            .order_by(Expr::cust("count"), Order::Desc)
+            .order_by(Expr::cust("extension"), Order::Asc)
+            .into_tuple::<(Option<String>, i64, i64)>()
+            .all(self.connection)
+            .await?
+            .into_iter()
+            .map(|(extension, count, size)| {
+                Ok(ExtensionCount::new(
+                    extension.map(|extension| format!(".{extension}")),
+                    u64::try_from(count)?,
+                    size,
+                ))
+            })
+            .collect()
+    }
+
+    /// Execute the prepared query, counting the number of records for each `(size, e_tag)` pair.
+    /// Only pairs that occur more than once are returned, as these are candidates for records
+    /// with identical content. Pairs are ordered by the count descending, and at most `limit`
+    /// pairs are returned.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select size, e_tag, count(*) from s3_object
+    /// where size is not null and e_tag is not null
+    /// group by size, e_tag having count(*) > 1
+    /// order by count(*) desc, size desc, e_tag
+    /// limit limit;
+    /// ```
+    pub async fn count_by_size_e_tag(self, limit: u64) -> Result<Vec<SizeETagCount>> {
+        let mut select = self
+            .select
+            .select_only()
+            .column(s3_object::Column::Size)
+            .column(s3_object::Column::ETag)
+            .expr_as(Expr::cust("count(*)"), "count")
+            .filter(s3_object::Column::Size.is_not_null())
+            .filter(s3_object::Column::ETag.is_not_null())
+            .group_by(s3_object::Column::Size)
+            .group_by(s3_object::Column::ETag)
+            .having(Expr::cust("count(*) > 1"));
+        QuerySelect::query(&mut select).clear_order_by();
+
+        select
+            .order_by(Expr::cust("count"), Order::Desc)
+            .order_by(s3_object::Column::Size, Order::Desc)
+            .order_by(s3_object::Column::ETag, Order::Asc)
+            .limit(limit)
+            .into_tuple::<(i64, String, i64)>()
+            .all(self.connection)
+            .await?
+            .into_iter()
+            .map(|(size, e_tag, count)| Ok(SizeETagCount::new(size, e_tag, u64::try_from(count)?)))
+            .collect()
+    }
+
+    /// Execute the prepared query, finding the AWS-native sequencers of records with an event
+    /// time, ordered by key and event time. Generated sequencers, which contain a `-`, are not
+    /// included. At most `limit` records are returned.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select bucket, key, s3_object_id, sequencer, event_time from s3_object
+    /// where sequencer is not null and sequencer not like '%-%' and event_time is not null
+    /// order by bucket, key, event_time, sequencer, s3_object_id
+    /// limit limit;
+    /// ```
+    pub async fn sequencers(self, limit: u64) -> Result<Vec<SequencedRecord>> {
+        let mut select = self
+            .select
+            .select_only()
+            .column(s3_object::Column::Bucket)
+            .column(s3_object::Column::Key)
+            .column(s3_object::Column::S3ObjectId)
+            .column(s3_object::Column::Sequencer)
+            .column(s3_object::Column::EventTime)
+            .filter(s3_object::Column::Sequencer.is_not_null())
+            .filter(s3_object::Column::Sequencer.not_like("%-%"))
+            .filter(s3_object::Column::EventTime.is_not_null());
+        QuerySelect::query(&mut select).clear_order_by();
+
+        Ok(select
+            .order_by(s3_object::Column::Bucket, Order::Asc)
+            .order_by(s3_object::Column::Key, Order::Asc)
+            .order_by(s3_object::Column::EventTime, Order::Asc)
+            .order_by(s3_object::Column::Sequencer, Order::Asc)
+            .order_by(s3_object::Column::S3ObjectId, Order::Asc)
+            .limit(limit)
+            .into_tuple::<(String, String, Uuid, String, DateTimeWithTimeZone)>()
+            .all(self.connection)
+            .await?
+            .into_iter()
+            .map(|(bucket, key, s3_object_id, sequencer, event_time)| {
+                SequencedRecord::new(bucket, key, s3_object_id, sequencer, event_time)
+            })
+            .collect())
+    }
+
+    /// Execute the prepared query, returning every matching record ordered by key and then by
+    /// sequencer, which is the order that the events occurred in. The `s3_object_id` breaks ties
+    /// in the order that records were ingested. At most `limit` records are returned.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select * from s3_object
+    /// order by bucket, key, sequencer nulls last, s3_object_id
+    /// limit limit;
+    /// ```
+    pub async fn event_history(self, limit: u64) -> Result<Vec<s3_object::Model>> {
+        let mut select = self.select;
+        QuerySelect::query(&mut select).clear_order_by();
+
+        Ok(select
+            .order_by(s3_object::Column::Bucket, Order::Asc)
+            .order_by(s3_object::Column::Key, Order::Asc)
+            .order_by_with_nulls(s3_object::Column::Sequencer, Order::Asc, NullOrdering::Last)
+            .order_by(s3_object::Column::S3ObjectId, Order::Asc)
+            .limit(limit)
+            .all(self.connection)
+            .await?)
+    }
+
+    /// Execute the prepared query, finding the distinct top-level `attributes` keys along with
+    /// the number of records that have each JSON value type for the key. If `sample` is set,
+    /// only that many of the matching records are scanned.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select attribute.key, jsonb_typeof(attribute.value) as value_type, count(*)
+    /// from (select attributes from s3_object limit sample) as objects
+    /// cross join lateral jsonb_each(objects.attributes) as attribute
+    /// group by attribute.key, value_type;
+    /// ```
+    pub async fn attribute_keys(self, sample: Option<u64>) -> Result<Vec<AttributeKey>> {
+        let mut select = self
+            .select
+            .select_only()
+            .column(s3_object::Column::Attributes)
+            .filter(Expr::cust_with_expr(
+                "jsonb_typeof($1) = 'object'",
+                Expr::col((s3_object::Entity, s3_object::Column::Attributes)),
+            ));
+        QuerySelect::query(&mut select).clear_order_by();
+        if let Some(sample) = sample {
+            select = select.limit(sample);
+        }
+
+        let objects = DbBackend::Postgres.build(&select.into_query());
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                "select attribute.key, jsonb_typeof(attribute.value) as value_type, count(*) \
+                from ({}) as objects \
+                cross join lateral jsonb_each(objects.attributes) as attribute \
+                group by attribute.key, value_type \
+                order by attribute.key, value_type",
+                objects.sql
+            ),
+            objects.values.map(|values| values.0).unwrap_or_default(),
+        );
+
+        let rows = AttributeKeyRow::find_by_statement(statement)
+            .all(self.connection)
+            .await?;
+
+        let mut keys: Vec<AttributeKey> = vec![];
+        for row in rows {
+            let count = u64::try_from(row.count)?;
+            match keys.last_mut() {
+                Some(key) if key.key == row.key => {
+                    key.count += count;
+                    key.types.insert(row.value_type, count);
+                }
+                _ => keys.push(AttributeKey::new(
+                    row.key,
+                    count,
+                    BTreeMap::from([(row.value_type, count)]),
+                )),
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Execute the prepared query, comparing the keys of the matching records with the `keys`.
+    /// This finds the keys which are present in both, the keys which are missing from the
+    /// matching records, and the extra keys of the matching records which are not in `keys`.
+    /// At most `extra_limit` extra keys are returned, in order, starting after `extra_after`.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select provided.key, objects.key is not null as is_matched
+    /// from (select distinct unnest(keys) as key) as provided
+    /// left join (select distinct key from s3_object) as objects
+    /// on provided.key = objects.key
+    /// order by provided.key;
+    /// ```
+    ///
+    /// And a query for the extra keys which is similar to:
+    ///
+    /// ```sql
+    /// select distinct key from s3_object
+    /// where key not in (keys) and key > extra_after
+    /// order by key
+    /// limit extra_limit + 1;
+    /// ```
+    pub async fn key_presence(
+        self,
+        keys: Vec<String>,
+        extra_after: Option<&str>,
+        extra_limit: u64,
+    ) -> Result<KeyPresence> {
+        let mut extra = self
+            .select
+            .clone()
+            .select_only()
+            .column(s3_object::Column::Key)
+            .distinct()
+            .filter(s3_object::Column::Key.is_not_in(keys.clone()))
+            .apply_if(extra_after, |select, extra_after| {
+                select.filter(s3_object::Column::Key.gt(extra_after))
+            });
+        QuerySelect::query(&mut extra).clear_order_by();
+        let mut extra = extra
+            .order_by_asc(s3_object::Column::Key)
+            .limit(extra_limit.saturating_add(1))
+            .into_tuple::<String>()
+            .all(self.connection)
+            .await?;
+
+        let mut select = self
+            .select
+            .select_only()
+            .column(s3_object::Column::Key)
+            .distinct();
+        QuerySelect::query(&mut select).clear_order_by();
+
+        let objects = DbBackend::Postgres.build(&select.into_query());
+        let mut values = objects.values.map(|values| values.0).unwrap_or_default();
+        values.push(keys.into());
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                "select provided.key, objects.key is not null as is_matched \
+                from (select distinct unnest(${}::text[]) as key) as provided \
+                left join ({}) as objects on provided.key = objects.key \
+                order by provided.key",
+                values.len(),
+                objects.sql
+            ),
+            values,
+        );
+
+        let rows = KeyPresenceRow::find_by_statement(statement)
+            .all(self.connection)
+            .await?;
+
+        let mut presence = KeyPresence::default();
+        for row in rows {
+            if row.is_matched {
+                presence.present.push(row.key);
+            } else {
+                presence.missing.push(row.key);
+            }
+        }
+
+        // An extra key past the limit means that there is another page.
+        if extra.len() as u64 > extra_limit {
+            extra.truncate(usize::try_from(extra_limit)?);
+            presence.next_extra_after = extra.last().cloned();
+        }
+        presence.extra = extra;
+
+        Ok(presence)
+    }
+
+    /// Execute the prepared query, finding the earliest `event_time` of the records.
+    pub async fn min_event_time(self) -> Result<Option<DateTimeWithTimeZone>> {
+        let mut select = self
+            .select
+            .select_only()
+            .expr(Expr::col((s3_object::Entity, s3_object::Column::EventTime)).min());
+        QuerySelect::query(&mut select).clear_order_by();
+
+        Ok(select
+            .into_tuple::<Option<DateTimeWithTimeZone>>()
+            .one(self.connection)
+            .await?
+            .flatten())
+    }
+
+    /// Execute the prepared query, computing the number and size of object versions in each
+    /// storage class at the end of each period from `start` to `end`. Each version is attributed
+    /// to its latest record as of the end of the period, ordered by `event_time` and then
+    /// `sequencer`, and versions where that record is a deleted event, a delete marker, or has no
+    /// storage class are not counted. Periods start at `start` and `end` in UTC.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// with records as (select * from s3_object where ...),
+    /// states as (
+    ///     select *, event_time as valid_from, lead(event_time) over (
+    ///         partition by bucket, key, version_id order by event_time, sequencer
+    ///     ) as valid_to
+    ///     from records where event_time is not null
+    /// ),
+    /// periods as (
+    ///     select period at time zone 'UTC' as period_start,
+    ///         (period + interval '1 day') at time zone 'UTC' as period_end
+    ///     from generate_series(start, end, interval '1 day') as period
+    /// )
+    /// select period_start, storage_class, count(states.*), coalesce(sum(size), 0)
+    /// from periods left join states on
+    ///     valid_from < period_end and (valid_to is null or valid_to >= period_end) and
+    ///     event_type = 'Created' and not is_delete_marker and storage_class is not null
+    /// group by period_start, storage_class order by period_start, storage_class;
+    /// ```
+    pub async fn storage_class_history(
+        self,
+        interval: HistoryInterval,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<StorageClassPeriod>> {
+        let mut select = self
+            .select
+            .select_only()
+            .column_as(s3_object::Column::Bucket, "bucket")
+            .column_as(s3_object::Column::Key, "key")
+            .column_as(s3_object::Column::VersionId, "version_id")
+            .column_as(s3_object::Column::EventType, "event_type")
+            .column_as(s3_object::Column::IsDeleteMarker, "is_delete_marker")
+            .column_as(s3_object::Column::StorageClass, "storage_class")
+            .column_as(s3_object::Column::Size, "size")
+            .column_as(s3_object::Column::EventTime, "event_time")
+            .column_as(s3_object::Column::Sequencer, "sequencer");
+        QuerySelect::query(&mut select).clear_order_by();
+
+        let records = DbBackend::Postgres.build(&select.into_query());
+        let mut values = records.values.map(|values| values.0).unwrap_or_default();
+        let (start_index, end_index) = (values.len() + 1, values.len() + 2);
+        values.extend([start.into(), end.into()]);
+
+        let interval = interval.as_sql_interval();
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                "with records as ({}), \
+                states as ( \
+                    select storage_class::text as storage_class, size, event_type::text as event_type, \
+                        is_delete_marker, event_time as valid_from, lead(event_time) over ( \
+                            partition by bucket, key, version_id \
+                            order by event_time, sequencer nulls first \
+                        ) as valid_to \
+                    from records where event_time is not null \
+                ), \
+                periods as ( \
+                    select period at time zone 'UTC' as period_start, \
+                        (period + interval '{interval}') at time zone 'UTC' as period_end \
+                    from generate_series( \
+                        ${start_index}::timestamp, ${end_index}::timestamp, interval '{interval}' \
+                    ) as period \
+                ) \
+                select periods.period_start, states.storage_class, count(states.*) as count, \
+                    coalesce(sum(states.size), 0)::bigint as size \
+                from periods left join states on \
+                    states.valid_from < periods.period_end and \
+                    (states.valid_to is null or states.valid_to >= periods.period_end) and \
+                    states.event_type = 'Created' and \
+                    not states.is_delete_marker and \
+                    states.storage_class is not null \
+                group by periods.period_start, states.storage_class \
+                order by periods.period_start, states.storage_class",
+                records.sql
+            ),
+            values,
+        );
+
+        let rows = StorageClassPeriodRow::find_by_statement(statement)
+            .all(self.connection)
+            .await?;
+
+        let mut periods: Vec<StorageClassPeriod> = vec![];
+        for row in rows {
+            if periods
+                .last()
+                .is_none_or(|period| period.start != row.period_start)
+            {
+                periods.push(StorageClassPeriod::new(row.period_start, vec![]));
+            }
+
+            if let Some(storage_class) = row.storage_class
+                && let Some(period) = periods.last_mut()
+            {
+                period.classes.push(StorageClassCount::new(
+                    storage_class,
+                    u64::try_from(row.count)?,
+                    row.size,
+                ));
+            }
+        }
+
+        Ok(periods)
+    }
+
+    /// Create a condition which finds records that come after the cursor position in the
+    /// `sequencer` and `s3_object_id` order, where null sequencers are first. This produces a
+    /// condition similar to:
+    ///
+    /// ```sql
+    /// select * from s3_object
+    /// where sequencer > position.sequencer or
+    ///     (sequencer = position.sequencer and s3_object_id > position.s3_object_id);
+    /// ```
+    pub fn after_cursor_condition(position: &CursorPosition) -> Condition {
+        let after_id = s3_object::Column::S3ObjectId.gt(position.s3_object_id);
+        match &position.sequencer {
+            Some(sequencer) => Condition::any()
+                .add(s3_object::Column::Sequencer.gt(sequencer))
+                .add(
+                    Condition::all()
+                        .add(s3_object::Column::Sequencer.eq(sequencer))
+                        .add(after_id),
+                ),
+            None => Condition::any()
+                .add(s3_object::Column::Sequencer.is_not_null())
+                .add(
+                    Condition::all()
+                        .add(s3_object::Column::Sequencer.is_null())
+                        .add(after_id),
+                ),
+        }
+    }
+
+    /// Create a list response using keyset pagination, which fetches the page after the cursor
+    /// rather than using an offset.
+    pub async fn paginate_with_cursor_to_list_response(
+        mut self,
+        pagination: Pagination,
+        cursor: Cursor,
+        page_link: Url,
+        count: u64,
+    ) -> Result<ListResponse<s3_object::Model>> {
+        let page_size = pagination.rows_per_page();
+        if let Some(position) = cursor.after() {
+            self.select = self.select.filter(Self::after_cursor_condition(position));
+        }
+
+        // Fetch one additional record to see if there is a next page.
+        self.select = self
+            .select
+            .limit(page_size.checked_add(1).ok_or_else(|| OverflowError)?);
+        self.trace_query("paginate_with_cursor");
+
+        let mut results = self.select.all(self.connection).await?;
+
+        let next_cursor = if results.len() > usize::try_from(page_size)? {
+            results.pop();
+            results
+                .last()
+                .map(|last| {
+                    cursor
+                        .next(CursorPosition::new(
+                            last.sequencer.clone(),
+                            last.s3_object_id,
+                        ))
+                        .encode()
+                })
+                .transpose()?
+        } else {
+            None
+        };
+
+        ListResponse::from_next_cursor(pagination, results, next_cursor, page_link, count)
+    }
+}
+
+/// The number of records with an attribute key and JSON value type.
+#[derive(Debug, FromQueryResult)]
+struct AttributeKeyRow {
+    key: String,
+    value_type: String,
+    count: i64,
+}
+
+/// A provided key, and whether it has a matching record.
+#[derive(Debug, FromQueryResult)]
+struct KeyPresenceRow {
+    key: String,
+    is_matched: bool,
+}
+
+/// The number and size of objects in a storage class at the end of a period. The storage class
+/// is null for periods without any objects.
+#[derive(Debug, FromQueryResult)]
+struct StorageClassPeriodRow {
+    period_start: DateTimeWithTimeZone,
+    storage_class: Option<StorageClass>,
+    count: i64,
+    size: i64,
+}
+
+impl<C> ListQueryBuilder<'_, C, s3_object::Entity>
+where
+    C: ConnectionTrait,
+{
+    /// Create a condition which finds records where the `last_modified_date` and `event_time`
+    /// diverge by more than `seconds`. This produces a condition similar to:
+    ///
+    /// ```sql
+    /// abs(extract(epoch from (event_time - last_modified_date))) > seconds
+    /// ```
+    pub fn event_time_divergence_condition(seconds: u64) -> SimpleExpr {
+        let difference = Expr::cust_with_exprs(
+            "extract(epoch from ($1 - $2))",
+            [
+                Expr::col(s3_object::Column::EventTime).into(),
+                Expr::col(s3_object::Column::LastModifiedDate).into(),
+            ],
+        );
+
+        Expr::expr(Func::abs(difference)).gt(seconds)
+    }
+
+    /// Create a condition which finds current state records that have an `event_time` before
+    /// the `stale_before` date.
+    pub fn stale_before_condition(stale_before: DateTimeWithTimeZone) -> Condition {
+        Condition::all()
+            .add(s3_object::Column::IsCurrentState.eq(true))
+            .add(s3_object::Column::EventTime.lt(stale_before))
+    }
+
+    /// Create a condition which finds records that originated from a crawl, or from S3 events.
+    pub fn origin_condition(origin: Origin) -> Condition {
+        let crawl = s3_object::Column::Reason.is_in(Origin::crawl_reasons());
+        match origin {
+            Origin::Crawl => Condition::all().add(crawl),
+            Origin::Event => Condition::all().add(crawl.not()),
+        }
+    }
+
+    /// Create a condition which finds records with an `ingest_id` where the ingest id tag was
+    /// not present on the object when it was collected, or the opposite if `tag_missing` is false.
+    pub fn tag_missing_condition(tag_missing: bool) -> Condition {
+        if tag_missing {
+            Condition::all()
+                .add(s3_object::Column::IngestId.is_not_null())
+                .add(s3_object::Column::TagPresent.eq(false))
+        } else {
+            Condition::any()
+                .add(s3_object::Column::IngestId.is_null())
+                .add(s3_object::Column::TagPresent.is_null())
+                .add(s3_object::Column::TagPresent.eq(true))
+        }
+    }
+
+    /// Create a condition which finds current state records that are missing metadata from
+    /// `HeadObject`, or records which have all the metadata if `missing_metadata` is false.
+    pub fn missing_metadata_condition(missing_metadata: bool) -> Condition {
+        if missing_metadata {
+            Condition::all()
+                .add(s3_object::Column::IsCurrentState.eq(true))
+                .add(
+                    Condition::any()
+                        .add(s3_object::Column::StorageClass.is_null())
+                        .add(s3_object::Column::LastModifiedDate.is_null()),
+                )
+        } else {
+            Condition::all()
+                .add(s3_object::Column::StorageClass.is_not_null())
+                .add(s3_object::Column::LastModifiedDate.is_not_null())
+        }
+    }
+
+    /// Create a condition which excludes folder placeholders, i.e. zero-byte records with a key
+    /// that ends in `/`. This produces a condition similar to:
+    ///
+    /// ```sql
+    /// key not like '%/' or size is null or size != 0
+    /// ```
+    pub fn exclude_folder_placeholders_condition() -> Condition {
+        Condition::any()
+            .add(s3_object::Column::Key.not_like("%/"))
+            .add(s3_object::Column::Size.is_null())
+            .add(s3_object::Column::Size.ne(0))
+    }
+
+    /// Create a condition which finds records with a key that ends with the suffix. Characters
+    /// which have a special meaning in `like` expressions are escaped. This produces a condition
+    /// similar to:
+    ///
+    /// ```sql
+    /// key like '%' || suffix
+    /// ```
+    pub fn key_suffix_condition(suffix: &str, case_sensitive: bool) -> SimpleExpr {
+        let escaped = suffix.chars().fold(String::from("%"), |mut escaped, c| {
+            if matches!(c, '\\' | '%' | '_') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        });
+
+        let key = Expr::col(s3_object::Column::Key);
+        if case_sensitive {
+            key.like(escaped)
+        } else {
+            key.ilike(escaped)
+        }
+    }
+
+    /// Create a condition which finds records where the `last_modified_date` is more than `days`
+    /// ago. This produces a condition similar to:
+    ///
+    /// ```sql
+    /// last_modified_date is not null and now() - last_modified_date > days * interval '1 day'
+    /// ```
+    pub fn unmodified_for_days_condition(days: u64) -> Condition {
+        Condition::all()
+            .add(s3_object::Column::LastModifiedDate.is_not_null())
+            .add(Expr::cust_with_exprs(
+                "now() - $1 > $2 * interval '1 day'",
+                [
+                    Expr::col(s3_object::Column::LastModifiedDate).into(),
+                    Expr::val(days).into(),
+                ],
+            ))
+    }
+
+    /// Create a condition which finds records where the number of distinct versions of the
+    /// bucket and key matches the comparison. Distinct counts cannot be used in window functions,
+    /// so this produces a correlated subquery similar to:
+    ///
+    /// ```sql
+    /// (
+    ///     select count(distinct versions.version_id) from s3_object versions
+    ///     where versions.bucket = s3_object.bucket and versions.key = s3_object.key
+    /// ) > count
+    /// ```
+    pub fn version_count_condition(version_count: CountComparison) -> Result<Condition> {
+        let versions = Alias::new("versions");
+        let count = Query::select()
+            .expr(Expr::cust_with_expr(
+                "count(distinct $1)",
+                Expr::col((versions.clone(), s3_object::Column::VersionId)),
+            ))
+            .from_as(s3_object::Entity, versions.clone())
+            .and_where(
+                Expr::col((versions.clone(), s3_object::Column::Bucket))
+                    .equals((s3_object::Entity, s3_object::Column::Bucket)),
+            )
+            .and_where(
+                Expr::col((versions, s3_object::Column::Key))
+                    .equals((s3_object::Entity, s3_object::Column::Key)),
+            )
+            .to_owned();
+
+        let count_expr = Expr::expr(SimpleExpr::SubQuery(
+            None,
+            Box::new(count.into_sub_query_statement()),
+        ));
+        // The count is compared as a `bigint`, which is the type returned by `count`.
+        let value = i64::try_from(version_count.count())?;
+        let condition = match version_count.comparison() {
+            Comparison::Eq => count_expr.eq(value),
+            Comparison::Gt => count_expr.gt(value),
+            Comparison::Gte => count_expr.gte(value),
+            Comparison::Lt => count_expr.lt(value),
+            Comparison::Lte => count_expr.lte(value),
+        };
+
+        Ok(Condition::all().add(condition))
+    }
+
+    /// Create a condition which finds records where the bucket and key has more than one distinct
+    /// storage class and archive status. Records without a storage class, such as deleted events,
+    /// are not counted.
+    pub fn storage_class_transition_condition() -> Condition {
+        let transitions = Alias::new("transitions");
+        let count = Query::select()
+            .expr(Expr::cust_with_exprs(
+                "count(distinct ($1, $2))",
+                [
+                    Expr::col((transitions.clone(), s3_object::Column::StorageClass)).into(),
+                    Expr::col((transitions.clone(), s3_object::Column::ArchiveStatus)).into(),
+                ],
+            ))
+            .from_as(s3_object::Entity, transitions.clone())
+            .and_where(
+                Expr::col((transitions.clone(), s3_object::Column::Bucket))
+                    .equals((s3_object::Entity, s3_object::Column::Bucket)),
+            )
+            .and_where(
+                Expr::col((transitions.clone(), s3_object::Column::Key))
+                    .equals((s3_object::Entity, s3_object::Column::Key)),
+            )
+            .and_where(Expr::col((transitions, s3_object::Column::StorageClass)).is_not_null())
+            .to_owned();
+
+        Condition::all().add(
+            Expr::expr(SimpleExpr::SubQuery(
+                None,
+                Box::new(count.into_sub_query_statement()),
+            ))
+            .gt(1),
+        )
+    }
+
+    /// Create a condition which finds current `Standard` tier objects that have not been
+    /// modified for `min_age_days` and are larger than `min_size` bytes.
+    pub fn tiering_candidate_condition(min_age_days: u64, min_size: i64) -> Condition {
+        Condition::all()
+            .add(s3_object::Column::IsCurrentState.eq(true))
+            .add(s3_object::Column::IsDeleteMarker.eq(false))
+            .add(s3_object::Column::StorageClass.eq(StorageClass::Standard))
+            .add(s3_object::Column::Size.gt(min_size))
+            .add(Self::unmodified_for_days_condition(min_age_days))
+    }
+
+    /// Create a condition which finds current `Created` records that have a null `sha256` or
+    /// `ingest_id`, or that are missing metadata from `HeadObject`.
+    pub fn failed_collection_condition() -> Condition {
+        Condition::all()
+            .add(s3_object::Column::IsCurrentState.eq(true))
+            .add(s3_object::Column::EventType.eq(EventType::Created))
+            .add(
+                Condition::any()
+                    .add(s3_object::Column::Sha256.is_null())
+                    .add(s3_object::Column::IngestId.is_null())
+                    .add(Self::missing_metadata_condition(true)),
+            )
+    }
+
+    /// Create a condition which finds the record with the highest sequencer for each bucket and
+    /// key, independent of `is_current_state`.
+    pub fn latest_per_key_condition(
+        filter: &S3ObjectsFilter,
+        case_sensitive: bool,
+    ) -> Result<Condition> {
+        let keys = S3ObjectsFilter {
+            bucket: filter.bucket.clone(),
+            key: filter.key.clone(),
+            key_suffix: filter.key_suffix.clone(),
+            ..Default::default()
+        };
+
+        let latest = Query::select()
+            .distinct_on([s3_object::Column::Bucket, s3_object::Column::Key])
+            .column(s3_object::Column::S3ObjectId)
+            .from(s3_object::Entity)
+            .cond_where(Self::filter_condition(keys, case_sensitive, false)?)
+            .order_by(s3_object::Column::Bucket, Order::Asc)
+            .order_by(s3_object::Column::Key, Order::Asc)
+            .order_by_with_nulls(
+                s3_object::Column::Sequencer,
+                Order::Desc,
+                NullOrdering::Last,
+            )
+            .to_owned();
+
+        Ok(Condition::all().add(s3_object::Column::S3ObjectId.in_subquery(latest)))
+    }
+
+    /// Create a condition which finds `Deleted` events that are not delete markers, where the
+    /// bucket and key do not have a current `Created` record. This excludes keys that were
+    /// deleted and then re-created.
+    pub fn permanently_deleted_condition(
+        from: Option<DateTimeWithTimeZone>,
+        to: Option<DateTimeWithTimeZone>,
+    ) -> Condition {
+        let current = Alias::new("current");
+        let recreated = Query::select()
+            .expr(Expr::val(1))
+            .from_as(s3_object::Entity, current.clone())
+            .and_where(
+                Expr::col((current.clone(), s3_object::Column::Bucket))
+                    .equals((s3_object::Entity, s3_object::Column::Bucket)),
+            )
+            .and_where(
+                Expr::col((current.clone(), s3_object::Column::Key))
+                    .equals((s3_object::Entity, s3_object::Column::Key)),
+            )
+            .and_where(Expr::col((current.clone(), s3_object::Column::IsCurrentState)).eq(true))
+            .and_where(
+                Expr::col((current.clone(), s3_object::Column::EventType))
+                    .eq(Expr::val(EventType::Created.to_value()).as_enum(EventType::name())),
+            )
+            .and_where(Expr::col((current, s3_object::Column::IsDeleteMarker)).eq(false))
+            .to_owned();
+
+        Condition::all()
+            .add(s3_object::Column::EventType.eq(EventType::Deleted))
+            .add(s3_object::Column::IsDeleteMarker.eq(false))
+            .add_option(from.map(|from| s3_object::Column::EventTime.gte(from)))
+            .add_option(to.map(|to| s3_object::Column::EventTime.lt(to)))
+            .add(Expr::exists(recreated).not())
+    }
+}
+
+impl<'a, C> ListQueryBuilder<'a, C, s3_crawl::Entity>
+where
+    C: ConnectionTrait,
+{
+    /// Create a new query builder.
+    pub fn new(connection: &'a C) -> Self {
+        Self {
+            connection,
+            select: Self::for_crawl(),
+        }
+    }
+
+    /// Define a select query for finding values from s3 crawl rows, restricted to the buckets
+    /// of the tenant of the current request.
+    pub fn for_crawl() -> Select<s3_crawl::Entity> {
+        s3_crawl::Entity::find()
+            .apply_if(
+                TenantScope::bucket_condition(s3_crawl::Column::Bucket),
+                QueryFilter::filter,
+            )
+            .order_by_asc(s3_crawl::Column::Status)
+            .order_by_asc(s3_crawl::Column::Bucket)
+            .order_by_asc(s3_crawl::Column::Prefix)
+    }
+
+    /// Filter records by all fields in the filter variable.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select * from s3_crawl
+    /// where status = filter.status and
+    ///     bucket = filter.bucket and
+    ///     ...;
+    /// ```
+    pub fn filter_all(mut self, filter: S3CrawlFilter, case_sensitive: bool) -> Result<Self> {
+        self.select = self
+            .select
+            .filter(Self::filter_condition(filter, case_sensitive)?);
+
+        self.trace_query("filter_all");
+
+        Ok(self)
+    }
+
+    /// Create a condition to filter a query.
+    pub fn filter_condition(filter: S3CrawlFilter, case_sensitive: bool) -> Result<Condition> {
+        let condition = Condition::all()
+            .add_option(Self::join(filter.bucket, |v| {
+                Self::filter_operation(
+                    Expr::col(s3_crawl::Column::Bucket),
+                    WildcardEither::Wildcard::<String>(v),
+                    case_sensitive,
+                )
+            })?)
+            .add_option(Self::join(filter.prefix, |v| {
+                Self::filter_operation(
+                    Expr::col(s3_crawl::Column::Prefix),
+                    WildcardEither::Wildcard::<String>(v),
+                    case_sensitive,
+                )
+            })?)
+            .add_option(Self::join(filter.started, |v| {
+                Self::filter_operation(Expr::col(s3_crawl::Column::Started), v, case_sensitive)
+            })?)
+            .add_option(Self::join(filter.status, |v| {
+                Ok(s3_crawl::Column::Status.eq(v))
+            })?);
+
+        Ok(condition)
+    }
+}
+
+impl<'a, C> ListQueryBuilder<'a, C, s3_crawl_schedule::Entity>
+where
+    C: ConnectionTrait,
+{
+    /// Create a new query builder.
+    pub fn new(connection: &'a C) -> Self {
+        Self {
+            connection,
+            select: Self::for_crawl_schedule(),
+        }
+    }
+
+    /// Define a select query for finding values from s3 crawl schedule rows, restricted to the
+    /// buckets of the tenant of the current request.
+    pub fn for_crawl_schedule() -> Select<s3_crawl_schedule::Entity> {
+        s3_crawl_schedule::Entity::find()
+            .apply_if(
+                TenantScope::bucket_condition(s3_crawl_schedule::Column::Bucket),
+                QueryFilter::filter,
+            )
+            .order_by_asc(s3_crawl_schedule::Column::Bucket)
+            .order_by_asc(s3_crawl_schedule::Column::Prefix)
+    }
+
+    /// Filter schedules to those where a crawl is due.
+    ///
+    /// This creates a query which is similar to:
+    ///
+    /// ```sql
+    /// select * from s3_crawl_schedule
+    /// where last_completed is null or
+    ///     last_completed + interval_seconds * interval '1 second' < now();
+    /// ```
+    pub fn filter_due(mut self) -> Self {
+        self.select = self.select.filter(Self::due_condition());
+
+        self.trace_query("filter_due");
+
+        self
+    }
+
+    /// Create a condition which finds schedules that have never completed, or which last
+    /// completed longer than the interval ago.
+    pub fn due_condition() -> Condition {
+        Condition::any()
+            .add(s3_crawl_schedule::Column::LastCompleted.is_null())
+            .add(Expr::cust_with_exprs(
+                "$1 + $2 * interval '1 second' < now()",
+                [
+                    Expr::col(s3_crawl_schedule::Column::LastCompleted).into(),
+                    Expr::col(s3_crawl_schedule::Column::IntervalSeconds).into(),
+                ],
+            ))
+    }
+}
+
+impl<'a, C, E> From<(&'a C, Select<E>)> for ListQueryBuilder<'a, C, E>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+{
+    fn from((connection, select): (&'a C, Select<E>)) -> Self {
+        Self { connection, select }
+    }
+}
+
+impl<'a, C, E> ListQueryBuilder<'a, C, E>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+{
+    /// Get the inner connection and select statement.
+    pub fn into_inner(self) -> (&'a C, Select<E>) {
+        (self.connection, self.select)
+    }
+
+    /// Get a copy of this query builder
+    pub fn cloned(&self) -> Self {
+        (self.connection, self.select.clone()).into()
+    }
+
+    /// Join a series expressions with an 'or' or 'and' statement.
+    pub fn join<T, F, I>(input: FilterJoinMerged<I>, mut map: F) -> Result<Option<Condition>>
+    where
+        T: Into<ConditionExpression>,
+        F: FnMut(I) -> Result<T>,
+    {
+        let conditions = input
+            .0
+            .into_iter()
+            .map(|(join, input)| {
+                let condition = match join {
+                    Join::And => Condition::all(),
+                    Join::Or => Condition::any(),
+                };
+
+                let mapped = input
+                    .into_iter()
+                    .map(&mut map)
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(mapped
+                    .into_iter()
+                    .fold(condition, |acc, element| acc.add(element)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if conditions.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(
+                conditions
+                    .into_iter()
+                    .fold(Condition::all(), |acc, element| acc.add(element)),
+            ))
+        }
+    }
+}
+
+impl<'a, C, E, M> ListQueryBuilder<'a, C, E>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Send + Sync,
+{
+    /// Execute the prepared query, fetching all values.
+    pub async fn all(self) -> Result<Vec<M>> {
+        Ok(self.select.all(self.connection).await?)
+    }
+
+    /// Execute the prepared query, streaming values instead of fetching them all at once.
+    pub async fn stream(self) -> Result<impl Stream<Item = Result<M>> + Send + 'a>
+    where
+        C: StreamTrait + Send,
+    {
+        Ok(self
+            .select
+            .stream(self.connection)
+            .await?
+            .map_err(Error::from))
+    }
+
+    /// Execute the prepared query, fetching one value.
+    pub async fn one(self) -> Result<Option<M>> {
+        Ok(self.select.one(self.connection).await?)
+    }
+
+    /// Execute the prepared query, counting all values.
+    pub async fn count(self) -> Result<u64> {
+        Ok(self.select.count(self.connection).await?)
+    }
+
+    /// Paginate the query for the given page and page_size.
+    ///
+    /// This produces a query similar to:
+    ///
+    /// ```sql
+    /// select * from s3_object
+    ///     limit page_size
+    ///     offset page * page_size;
+    /// ```
+    pub async fn paginate(mut self, offset: u64, page_size: u64) -> Result<Self> {
+        let offset = offset.checked_mul(page_size).ok_or_else(|| OverflowError)?;
+        self.select = self.select.offset(offset).limit(page_size);
+
+        self.trace_query("paginate");
+
+        Ok(self)
+    }
+
+    /// Create a list response from pagination query parameters and a query builder.
+    pub async fn paginate_to_list_response(
+        self,
+        pagination: Pagination,
+        page_link: Url,
+        count: u64,
+    ) -> Result<ListResponse<M>> {
+        let offset = pagination.offset()?;
+        let page_size = pagination.rows_per_page();
+        let mut query = self.paginate(offset, page_size).await?;
+
+        // Always add one to the limit to see if there is additional pages that can be fetched.
+        QuerySelect::query(&mut query.select).reset_limit();
+        query.select = query
+            .select
+            .limit(page_size.checked_add(1).ok_or_else(|| OverflowError)?);
+
+        let mut results = query.all().await?;
+
+        // Check if there are more pages using the knowledge that we fetched one additional record.
+        let next_page = if results.len()
+            <= usize::try_from(page_size).map_err(|err| Error::ConversionError(err.to_string()))?
+        {
+            None
+        } else {
+            // Remove the last element because we fetched one additional result.
+            results.pop();
+            Some(
+                pagination
+                    .page()
+                    .checked_add(1)
+                    .ok_or_else(|| OverflowError)?,
+            )
+        };
+
+        ListResponse::from_next_page(pagination, results, next_page, page_link, count)
+    }
+
+    /// Create a list count from a query builder.
+    pub async fn to_list_count(self) -> Result<ListCount> {
+        Ok(ListCount::new(self.count().await?))
+    }
+
+    /// Create an operation based on the wildcard. This either results in a `like`/`ilike` or `=` comparison.
+    pub fn filter_operation<S, W>(
+        expr: S,
+        wildcard: WildcardEither<W>,
+        case_sensitive: bool,
     ) -> Result<SimpleExpr>
     where
         S: Into<SimpleExpr>,
@@ -415,6 +1815,27 @@ where
         }
     }
 
+    /// Create an operation for bucket filters. This behaves like `filter_operation`, except that
+    /// a case-insensitive match always uses `ilike`, even without a wildcard. This allows matching
+    /// buckets that were ingested with inconsistent casing.
+    pub fn filter_bucket_operation<S>(
+        expr: S,
+        wildcard: Wildcard,
+        case_sensitive: bool,
+    ) -> Result<SimpleExpr>
+    where
+        S: Into<SimpleExpr>,
+    {
+        if case_sensitive {
+            Self::filter_operation(expr, WildcardEither::Wildcard::<String>(wildcard), true)
+        } else {
+            Ok(expr
+                .into()
+                .cast_as(Alias::new("text"))
+                .ilike(wildcard.to_like_expression()?))
+        }
+    }
+
     /// Trace the current query.
     pub fn trace_query(&self, message: &str) {
         trace!(
@@ -449,7 +1870,7 @@ impl JsonPathBuilder {
         // Traverses array and object expressions.
         let traverse_expr = |mut current: String, traverse, next, is_key_traversal| {
             if is_key_traversal {
-                current.push_str(&format!(".{traverse}"));
+                current.push_str(&format!(".{}", Self::json_path_key(traverse)));
             }
             Self::construct_json_path(col.clone(), current, next, case_sensitive, depth + 1)
         };
@@ -526,6 +1947,16 @@ impl JsonPathBuilder {
         Ok(result)
     }
 
+    /// Quote a key in a JSON path if it contains characters that are not valid in an unquoted
+    /// key, e.g. the `.` in a namespaced `team1.portalRunId` attribute.
+    fn json_path_key(key: String) -> String {
+        if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            key
+        } else {
+            format!("\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+    }
+
     /// Create a series of json conditions by traversing the JSON tree.
     pub fn json_condition(
         col: ColumnRef,
@@ -555,9 +1986,14 @@ pub(crate) mod tests {
         ArchiveStatus, EventType, Reason, StorageClass,
     };
     use crate::queries::EntriesBuilder;
-    use crate::queries::update::tests::{change_many, entries_many, null_attributes};
+    use crate::queries::update::tests::{
+        change_bucket, change_key, change_last_modified_date, change_many, change_reason,
+        change_sequencer_conflict, change_server_side_encryption, change_tag_present,
+        change_version_id, entries_many, null_attributes,
+    };
     use crate::routes::filter::wildcard::Wildcard;
     use crate::routes::pagination::Links;
+    use chrono::{Duration, Utc};
 
     use super::*;
 
@@ -646,46 +2082,234 @@ pub(crate) mod tests {
         let client = Client::from_pool(pool);
 
         let entries = EntriesBuilder::default()
-            .with_n(30)
-            .with_bucket_divisor(8)
-            .with_key_divisor(5)
+            .with_n(30)
+            .with_bucket_divisor(8)
+            .with_key_divisor(5)
+            .with_shuffle(true)
+            .with_generate_crawl_entries(false)
+            .build(&client)
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+            .filter_all(
+                S3ObjectsFilter {
+                    size: vec![14].into(),
+                    ..Default::default()
+                },
+                true,
+                true,
+            )
+            .unwrap();
+        let result = builder.all().await.unwrap();
+        assert_eq!(result, vec![entries[6].clone()]);
+
+        // Order of filter call shouldn't matter.
+        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+            .filter_all(
+                S3ObjectsFilter {
+                    size: vec![4].into(),
+                    ..Default::default()
+                },
+                true,
+                true,
+            )
+            .unwrap();
+        let result = builder.all().await.unwrap();
+        assert_eq!(result, vec![entries[24].clone()]);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default()
+            .with_shuffle(true)
+            .build(&client)
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref());
+        let result = builder.all().await.unwrap();
+
+        assert_eq!(result, entries);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_crawl(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default()
+            .with_shuffle(true)
+            .build(&client)
+            .await
+            .unwrap()
+            .s3_crawl;
+
+        let builder = ListQueryBuilder::<_, s3_crawl::Entity>::new(client.connection_ref());
+        let results = builder.all().await.unwrap();
+
+        assert_crawl_entries(&entries, &results);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_crawl_filter(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default()
+            .with_shuffle(true)
+            .build(&client)
+            .await
+            .unwrap()
+            .s3_crawl;
+
+        let result = ListQueryBuilder::<_, s3_crawl::Entity>::new(client.connection_ref())
+            .filter_all(
+                S3CrawlFilter {
+                    bucket: vec![Wildcard::new("0".to_string())].into(),
+                    prefix: vec![Wildcard::new("1".to_string())].into(),
+                    ..Default::default()
+                },
+                false,
+            )
+            .unwrap()
+            .all()
+            .await
+            .unwrap();
+
+        assert_eq!(result, vec![entries[1].clone()]);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_is_accessible(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default()
+            .with_shuffle(true)
+            .build(&client)
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+            .filter_all(
+                S3ObjectsFilter {
+                    bucket: vec![Wildcard::new("0".to_string())].into(),
+                    key: vec![Wildcard::new("0".to_string())].into(),
+                    is_accessible: Some(false),
+                    reason: vec![Reason::Unknown].into(),
+                    ..Default::default()
+                },
+                true,
+                true,
+            )
+            .unwrap();
+
+        let result = builder.all().await.unwrap();
+        assert_eq!(result, vec![entries[0].clone()]);
+
+        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+            .filter_all(
+                S3ObjectsFilter {
+                    bucket: vec![Wildcard::new("1".to_string())].into(),
+                    key: vec![Wildcard::new("3".to_string())].into(),
+                    is_accessible: Some(false),
+                    archive_status: vec![ArchiveStatus::DeepArchiveAccess].into(),
+                    ..Default::default()
+                },
+                true,
+                false,
+            )
+            .unwrap();
+
+        let result = builder.all().await.unwrap();
+        assert_eq!(result, vec![entries[3].clone()]);
+
+        let builder = builder_is_accessible(&client);
+        let result = builder.all().await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_is_accessible_restored(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default()
+            .with_shuffle(true)
+            .with_reason(Reason::Restored)
+            .build(&client)
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let builder = builder_is_accessible(&client);
+        let result = builder.all().await.unwrap();
+
+        let mut expected = entries[0].clone();
+        expected.is_accessible = true;
+        assert_eq!(result, vec![expected]);
+    }
+
+    fn builder_is_accessible(client: &Client) -> ListQueryBuilder<'_, DatabaseConnection, Entity> {
+        ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+            .filter_all(
+                S3ObjectsFilter {
+                    bucket: vec![Wildcard::new("0".to_string())].into(),
+                    key: vec![Wildcard::new("0".to_string())].into(),
+                    is_accessible: Some(true),
+                    ..Default::default()
+                },
+                true,
+                true,
+            )
+            .unwrap()
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_filter_event_type(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default()
+            .with_shuffle(true)
+            .build(&client)
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                event_type: Some(EventType::Created),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result.len(), 5);
+        assert_eq!(result, filter_event_type(entries, EventType::Created));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_multiple_and_filters(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default()
             .with_shuffle(true)
-            .with_generate_crawl_entries(false)
             .build(&client)
             .await
             .unwrap()
             .s3_objects;
 
-        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
-            .filter_all(
-                S3ObjectsFilter {
-                    size: vec![14].into(),
-                    ..Default::default()
-                },
-                true,
-                true,
-            )
-            .unwrap();
-        let result = builder.all().await.unwrap();
-        assert_eq!(result, vec![entries[6].clone()]);
-
-        // Order of filter call shouldn't matter.
-        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
-            .filter_all(
-                S3ObjectsFilter {
-                    size: vec![4].into(),
-                    ..Default::default()
-                },
-                true,
-                true,
-            )
-            .unwrap();
-        let result = builder.all().await.unwrap();
-        assert_eq!(result, vec![entries[24].clone()]);
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                bucket: vec![Wildcard::new("0".to_string())].into(),
+                key: vec![Wildcard::new("1".to_string())].into(),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result, vec![entries[1].clone()]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3(pool: PgPool) {
+    async fn test_list_s3_multiple_or_filters(pool: PgPool) {
         let client = Client::from_pool(pool);
         let entries = EntriesBuilder::default()
             .with_shuffle(true)
@@ -694,57 +2318,177 @@ pub(crate) mod tests {
             .unwrap()
             .s3_objects;
 
-        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref());
-        let result = builder.all().await.unwrap();
-
-        assert_eq!(result, entries);
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                key: HashMap::from_iter(vec![(
+                    Join::Or,
+                    vec![
+                        Wildcard::new("0".to_string()),
+                        Wildcard::new("1".to_string()),
+                    ],
+                )])
+                .into(),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result, vec![entries[0].clone(), entries[1].clone()]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_crawl(pool: PgPool) {
+    async fn test_list_s3_multiple_filters_same_key(pool: PgPool) {
         let client = Client::from_pool(pool);
         let entries = EntriesBuilder::default()
             .with_shuffle(true)
+            .with_prefixes(HashMap::from_iter(vec![
+                (0, "prefix".to_string()),
+                (1, "prefix".to_string()),
+                (2, "prefix".to_string()),
+                (3, "prefix".to_string()),
+            ]))
+            .with_suffixes(HashMap::from_iter(vec![
+                (0, "suffix".to_string()),
+                (1, "suffix".to_string()),
+                (4, "suffix".to_string()),
+                (5, "suffix".to_string()),
+            ]))
             .build(&client)
             .await
             .unwrap()
-            .s3_crawl;
-
-        let builder = ListQueryBuilder::<_, s3_crawl::Entity>::new(client.connection_ref());
-        let results = builder.all().await.unwrap();
+            .s3_objects;
 
-        assert_crawl_entries(&entries, &results);
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                key: HashMap::from_iter(vec![(
+                    Join::And,
+                    vec![
+                        Wildcard::new("*suffix".to_string()),
+                        Wildcard::new("prefix*".to_string()),
+                    ],
+                )])
+                .into(),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result, vec![entries[0].clone(), entries[1].clone()]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_crawl_filter(pool: PgPool) {
+    async fn test_list_s3_filter_attributes(pool: PgPool) {
         let client = Client::from_pool(pool);
         let entries = EntriesBuilder::default()
             .with_shuffle(true)
             .build(&client)
             .await
             .unwrap()
-            .s3_crawl;
-
-        let result = ListQueryBuilder::<_, s3_crawl::Entity>::new(client.connection_ref())
-            .filter_all(
-                S3CrawlFilter {
-                    bucket: vec![Wildcard::new("0".to_string())].into(),
-                    prefix: vec![Wildcard::new("1".to_string())].into(),
-                    ..Default::default()
-                },
-                false,
-            )
-            .unwrap()
-            .all()
-            .await
-            .unwrap();
+            .s3_objects;
 
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                attributes: Some(json!({
+                    "attributeId": "1"
+                })),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
         assert_eq!(result, vec![entries[1].clone()]);
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                attributes: Some(json!({
+                    "nestedId": {
+                        "attributeId": "2"
+                    }
+                })),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result, vec![entries[2].clone()]);
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                attributes: Some(json!({
+                    "nonExistentId": "1"
+                })),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert!(result.is_empty());
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                attributes: Some(json!({
+                    "attributeId": "1"
+                })),
+                key: vec![Wildcard::new("2".to_string())].into(),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert!(result.is_empty());
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                attributes: Some(json!({
+                    "attributeId": "3"
+                })),
+                key: vec![Wildcard::new("3".to_string())].into(),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result, vec![entries[3].clone()]);
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                attributes: Some(json!({
+                    "attributeId": "0",
+                    "nestedId": {
+                        "attributeId": "0"
+                    }
+                })),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result, vec![entries[0].clone()]);
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                attributes: Some(json!({
+                    "attributeId": ["0", "1"],
+                })),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result, vec![entries[0].clone(), entries[1].clone()]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3_is_accessible(pool: PgPool) {
+    async fn test_paginate_s3(pool: PgPool) {
         let client = Client::from_pool(pool);
         let entries = EntriesBuilder::default()
             .with_shuffle(true)
@@ -753,454 +2497,643 @@ pub(crate) mod tests {
             .unwrap()
             .s3_objects;
 
-        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
-            .filter_all(
-                S3ObjectsFilter {
-                    bucket: vec![Wildcard::new("0".to_string())].into(),
-                    key: vec![Wildcard::new("0".to_string())].into(),
-                    is_accessible: Some(false),
-                    reason: vec![Reason::Unknown].into(),
-                    ..Default::default()
-                },
-                true,
-                true,
-            )
-            .unwrap();
+        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref());
 
-        let result = builder.all().await.unwrap();
-        assert_eq!(result, vec![entries[0].clone()]);
+        let result = paginate_all(builder.clone(), 3, 3).await;
+        assert_eq!(result, entries[9..]);
+        // Empty result when paginating above the collection size.
+        assert!(paginate_all(builder.clone(), 10, 2).await.is_empty());
 
-        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
-            .filter_all(
-                S3ObjectsFilter {
-                    bucket: vec![Wildcard::new("1".to_string())].into(),
-                    key: vec![Wildcard::new("3".to_string())].into(),
-                    is_accessible: Some(false),
-                    archive_status: vec![ArchiveStatus::DeepArchiveAccess].into(),
-                    ..Default::default()
-                },
-                true,
-                false,
+        let result = builder
+            .paginate_to_list_response(
+                Pagination::from_u64(1, 2).unwrap(),
+                "http://example.com/s3?rowsPerPage=2&page=1"
+                    .parse()
+                    .unwrap(),
+                10,
             )
+            .await
             .unwrap();
 
-        let result = builder.all().await.unwrap();
-        assert_eq!(result, vec![entries[3].clone()]);
-
-        let builder = builder_is_accessible(&client);
-        let result = builder.all().await.unwrap();
-        assert!(result.is_empty());
+        assert_eq!(
+            result.links(),
+            &Links::new(
+                None,
+                Some(
+                    "http://example.com/s3?rowsPerPage=2&page=2"
+                        .parse()
+                        .unwrap()
+                )
+            )
+        );
+        assert_eq!(result.results(), &entries[0..2]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3_is_accessible_restored(pool: PgPool) {
+    async fn test_count_s3(pool: PgPool) {
         let client = Client::from_pool(pool);
-        let entries = EntriesBuilder::default()
+        EntriesBuilder::default()
             .with_shuffle(true)
-            .with_reason(Reason::Restored)
             .build(&client)
             .await
-            .unwrap()
-            .s3_objects;
+            .unwrap();
 
-        let builder = builder_is_accessible(&client);
-        let result = builder.all().await.unwrap();
+        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref());
 
-        let mut expected = entries[0].clone();
-        expected.is_accessible = true;
-        assert_eq!(result, vec![expected]);
+        assert_eq!(builder.clone().count().await.unwrap(), 10);
+        assert_eq!(builder.to_list_count().await.unwrap(), ListCount::new(10));
     }
 
-    fn builder_is_accessible(client: &Client) -> ListQueryBuilder<'_, DatabaseConnection, Entity> {
-        ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
-            .filter_all(
-                S3ObjectsFilter {
-                    bucket: vec![Wildcard::new("0".to_string())].into(),
-                    key: vec![Wildcard::new("0".to_string())].into(),
-                    is_accessible: Some(true),
-                    ..Default::default()
-                },
+    #[test]
+    fn test_filter_operation() {
+        let operation =
+            ListQueryBuilder::<DatabaseConnection, s3_object::Entity>::filter_operation(
+                Expr::col(s3_object::Column::StorageClass),
+                WildcardEither::Or(StorageClass::Standard),
                 true,
+            )
+            .unwrap();
+        assert!(matches!(
+            operation,
+            SimpleExpr::Binary(_, BinOper::Equal, _)
+        ));
+
+        let operation =
+            ListQueryBuilder::<DatabaseConnection, s3_object::Entity>::filter_operation(
+                Expr::col(s3_object::Column::StorageClass),
+                WildcardEither::Or(StorageClass::Standard),
+                false,
+            )
+            .unwrap();
+        assert!(matches!(
+            operation,
+            SimpleExpr::Binary(_, BinOper::Equal, _)
+        ));
+
+        let operation =
+            ListQueryBuilder::<DatabaseConnection, s3_object::Entity>::filter_operation(
+                Expr::col(s3_object::Column::StorageClass),
+                WildcardEither::Wildcard::<StorageClass>(Wildcard::new("Standar*".to_string())),
                 true,
             )
-            .unwrap()
+            .unwrap();
+        assert!(matches!(operation, SimpleExpr::Binary(_, BinOper::Like, _)));
+
+        let operation =
+            ListQueryBuilder::<DatabaseConnection, s3_object::Entity>::filter_operation(
+                Expr::col(s3_object::Column::StorageClass),
+                WildcardEither::Wildcard::<StorageClass>(Wildcard::new("Standar*".to_string())),
+                false,
+            )
+            .unwrap();
+        assert!(matches!(
+            operation,
+            SimpleExpr::Binary(_, BinOper::PgOperator(PgBinOper::ILike), _)
+        ));
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3_filter_event_type(pool: PgPool) {
+    async fn test_list_s3_filter_wildcard(pool: PgPool) {
         let client = Client::from_pool(pool);
         let entries = EntriesBuilder::default()
             .with_shuffle(true)
             .build(&client)
             .await
-            .unwrap()
-            .s3_objects;
+            .unwrap();
+        let s3_entries = entries.s3_objects.clone();
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                event_type: Some(EventType::Created),
+                event_time: vec![WildcardEither::Wildcard(Wildcard::new(
+                    "1970-01-0*".to_string(),
+                ))]
+                .into(),
                 ..Default::default()
             },
             true,
         )
         .await;
-        assert_eq!(result.len(), 5);
-        assert_eq!(result, filter_event_type(entries, EventType::Created));
+        assert_eq!(
+            result,
+            s3_entries
+                .clone()
+                .into_iter()
+                .filter(|entry| entry
+                    .event_time
+                    .unwrap()
+                    .to_string()
+                    .starts_with("1970-01-0"))
+                .collect::<Vec<_>>()
+        );
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                bucket: vec![Wildcard::new("0*".to_string())].into(),
+                ..Default::default()
+            },
+            false,
+        )
+        .await;
+        assert_eq!(result, &s3_entries[0..2]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3_multiple_and_filters(pool: PgPool) {
+    async fn test_list_s3_filter_bucket_case_insensitive(pool: PgPool) {
         let client = Client::from_pool(pool);
-        let entries = EntriesBuilder::default()
-            .with_shuffle(true)
-            .build(&client)
-            .await
-            .unwrap()
-            .s3_objects;
+        let mut entries = EntriesBuilder::default().build(&client).await.unwrap();
+
+        change_bucket(&client, &entries, 0, "Bucket".to_string()).await;
+        change_bucket(&client, &entries, 1, "bucket".to_string()).await;
+        entries.s3_objects[0].bucket = "Bucket".to_string();
+        entries.s3_objects[1].bucket = "bucket".to_string();
+        let s3_entries = entries.s3_objects.clone();
+
+        let filter = S3ObjectsFilter {
+            bucket: vec![Wildcard::new("BUCKET".to_string())].into(),
+            ..Default::default()
+        };
+        let result = filter_all_s3_from(&client, filter.clone(), true).await;
+        assert!(result.is_empty());
+
+        let result = filter_all_s3_from(&client, filter, false).await;
+        assert_eq!(result, &s3_entries[0..2]);
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                bucket: vec![Wildcard::new("0".to_string())].into(),
-                key: vec![Wildcard::new("1".to_string())].into(),
+                bucket: vec![Wildcard::new("Bucket".to_string())].into(),
                 ..Default::default()
             },
             true,
         )
         .await;
-        assert_eq!(result, vec![entries[1].clone()]);
+        assert_eq!(result, &s3_entries[0..1]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3_multiple_or_filters(pool: PgPool) {
+    async fn test_list_s3_filter_event_time_divergence(pool: PgPool) {
         let client = Client::from_pool(pool);
-        let entries = EntriesBuilder::default()
-            .with_shuffle(true)
-            .build(&client)
-            .await
-            .unwrap()
-            .s3_objects;
+        let mut entries = EntriesBuilder::default().build(&client).await.unwrap();
+
+        // Only a divergent last modified date should be returned.
+        let last_modified_date = entries.s3_objects[2]
+            .event_time
+            .map(|event_time| event_time + Duration::hours(2));
+        change_last_modified_date(&client, &entries, 2, last_modified_date).await;
+        entries.s3_objects[2].last_modified_date = last_modified_date;
+        let last_modified_date = entries.s3_objects[4]
+            .event_time
+            .map(|event_time| event_time - Duration::minutes(1));
+        change_last_modified_date(&client, &entries, 4, last_modified_date).await;
+        entries.s3_objects[4].last_modified_date = last_modified_date;
+        change_last_modified_date(&client, &entries, 6, None).await;
+        entries.s3_objects[6].last_modified_date = None;
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                key: HashMap::from_iter(vec![(
-                    Join::Or,
-                    vec![
-                        Wildcard::new("0".to_string()),
-                        Wildcard::new("1".to_string()),
-                    ],
-                )])
-                .into(),
+                event_time_divergence: Some(3600),
                 ..Default::default()
             },
             true,
         )
         .await;
-        assert_eq!(result, vec![entries[0].clone(), entries[1].clone()]);
+        assert_eq!(result, vec![entries.s3_objects[2].clone()]);
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                event_time_divergence: Some(30),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(
+            result,
+            vec![entries.s3_objects[2].clone(), entries.s3_objects[4].clone()]
+        );
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                event_time_divergence: Some(0),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(result.len(), 2);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3_multiple_filters_same_key(pool: PgPool) {
+    async fn test_list_s3_filter_server_side_encryption(pool: PgPool) {
         let client = Client::from_pool(pool);
-        let entries = EntriesBuilder::default()
-            .with_shuffle(true)
-            .with_prefixes(HashMap::from_iter(vec![
-                (0, "prefix".to_string()),
-                (1, "prefix".to_string()),
-                (2, "prefix".to_string()),
-                (3, "prefix".to_string()),
-            ]))
-            .with_suffixes(HashMap::from_iter(vec![
-                (0, "suffix".to_string()),
-                (1, "suffix".to_string()),
-                (4, "suffix".to_string()),
-                (5, "suffix".to_string()),
-            ]))
-            .build(&client)
-            .await
-            .unwrap()
-            .s3_objects;
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
+
+        let kms = Some("aws:kms".to_string());
+        change_server_side_encryption(&client, &entries, 0, kms.clone(), Some("key1".to_string()))
+            .await;
+        change_server_side_encryption(&client, &entries, 2, kms, Some("key2".to_string())).await;
+        change_server_side_encryption(&client, &entries, 4, Some("AES256".to_string()), None).await;
+        let ids = |result: Vec<s3_object::Model>| {
+            result
+                .into_iter()
+                .map(|r| r.s3_object_id)
+                .collect::<Vec<_>>()
+        };
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                key: HashMap::from_iter(vec![(
-                    Join::And,
-                    vec![
-                        Wildcard::new("*suffix".to_string()),
-                        Wildcard::new("prefix*".to_string()),
-                    ],
-                )])
-                .into(),
+                sse_kms_key_id: vec!["key2".to_string()].into(),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(ids(result), vec![entries.s3_objects[2].s3_object_id]);
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                server_side_encryption: vec!["aws:kms".to_string()].into(),
+                ..Default::default()
+            },
+            true,
+        )
+        .await;
+        assert_eq!(
+            ids(result),
+            vec![
+                entries.s3_objects[0].s3_object_id,
+                entries.s3_objects[2].s3_object_id
+            ]
+        );
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                is_encrypted: Some(false),
                 ..Default::default()
             },
             true,
         )
         .await;
-        assert_eq!(result, vec![entries[0].clone(), entries[1].clone()]);
-    }
-
-    #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3_filter_attributes(pool: PgPool) {
-        let client = Client::from_pool(pool);
-        let entries = EntriesBuilder::default()
-            .with_shuffle(true)
-            .build(&client)
-            .await
-            .unwrap()
-            .s3_objects;
+        assert_eq!(
+            ids(result),
+            ids([1, 3, 5, 6, 7, 8, 9]
+                .map(|i| entries.s3_objects[i].clone())
+                .to_vec())
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_filter_origin(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
+        let ids = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|i| entries.s3_objects[*i].s3_object_id)
+                .collect::<Vec<_>>()
+        };
+
+        change_reason(&client, &entries, 0, Reason::Crawl).await;
+        change_reason(&client, &entries, 1, Reason::CrawlRestored).await;
+        change_reason(&client, &entries, 2, Reason::Unknown).await;
+        change_reason(&client, &entries, 3, Reason::DeletedLifecycle).await;
+
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                origin: Some(Origin::Crawl),
+                ..Default::default()
+            },
+            false,
+        )
+        .await;
+        assert_eq!(
+            result
+                .into_iter()
+                .map(|r| r.s3_object_id)
+                .collect::<Vec<_>>(),
+            ids(&[0, 1])
+        );
+
+        // All other reasons, including unknown reasons, are from events.
+        let result = filter_all_s3_from(
+            &client,
+            S3ObjectsFilter {
+                origin: Some(Origin::Event),
+                ..Default::default()
+            },
+            false,
+        )
+        .await;
+        assert_eq!(
+            result
+                .into_iter()
+                .map(|r| r.s3_object_id)
+                .collect::<Vec<_>>(),
+            ids(&[2, 3, 4, 5, 6, 7, 8, 9])
+        );
 
+        // Origin can be combined with reason.
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                attributes: Some(json!({
-                    "attributeId": "1"
-                })),
+                origin: Some(Origin::Crawl),
+                reason: vec![Reason::CrawlRestored, Reason::Unknown].into(),
                 ..Default::default()
             },
-            true,
+            false,
         )
         .await;
-        assert_eq!(result, vec![entries[1].clone()]);
+        assert_eq!(
+            result
+                .into_iter()
+                .map(|r| r.s3_object_id)
+                .collect::<Vec<_>>(),
+            ids(&[1])
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_filter_tag_missing(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
+        let ids = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|i| entries.s3_objects[*i].s3_object_id)
+                .collect::<Vec<_>>()
+        };
+
+        change_tag_present(&client, &entries, 0, Some(false)).await;
+        change_tag_present(&client, &entries, 1, Some(false)).await;
+        change_tag_present(&client, &entries, 2, Some(true)).await;
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                attributes: Some(json!({
-                    "nestedId": {
-                        "attributeId": "2"
-                    }
-                })),
+                tag_missing: Some(true),
                 ..Default::default()
             },
-            true,
+            false,
         )
         .await;
-        assert_eq!(result, vec![entries[2].clone()]);
+        assert_eq!(
+            result
+                .into_iter()
+                .map(|r| r.s3_object_id)
+                .collect::<Vec<_>>(),
+            ids(&[0, 1])
+        );
 
+        // Records with an unknown tag state are included when the tag is not missing.
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                attributes: Some(json!({
-                    "nonExistentId": "1"
-                })),
+                tag_missing: Some(false),
                 ..Default::default()
             },
-            true,
+            false,
         )
         .await;
-        assert!(result.is_empty());
+        assert_eq!(
+            result
+                .into_iter()
+                .map(|r| r.s3_object_id)
+                .collect::<Vec<_>>(),
+            ids(&[2, 3, 4, 5, 6, 7, 8, 9])
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_filter_sequencer_conflict(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
+
+        change_sequencer_conflict(&client, &entries, 2, true).await;
+        change_sequencer_conflict(&client, &entries, 3, true).await;
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                attributes: Some(json!({
-                    "attributeId": "1"
-                })),
-                key: vec![Wildcard::new("2".to_string())].into(),
+                is_sequencer_conflict: Some(true),
                 ..Default::default()
             },
-            true,
+            false,
         )
         .await;
-        assert!(result.is_empty());
+        assert_eq!(
+            result
+                .into_iter()
+                .map(|r| r.s3_object_id)
+                .collect::<Vec<_>>(),
+            vec![
+                entries.s3_objects[2].s3_object_id,
+                entries.s3_objects[3].s3_object_id
+            ]
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_filter_stale_before(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
+        let ids = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|i| entries.s3_objects[*i].s3_object_id)
+                .collect::<Vec<_>>()
+        };
 
+        // Event times are one day apart, so only the earlier current state records are stale.
+        let stale_before = entries.s3_objects[5].event_time;
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                attributes: Some(json!({
-                    "attributeId": "3"
-                })),
-                key: vec![Wildcard::new("3".to_string())].into(),
+                stale_before,
                 ..Default::default()
             },
             true,
         )
         .await;
-        assert_eq!(result, vec![entries[3].clone()]);
+        assert_eq!(
+            result.iter().map(|r| r.s3_object_id).collect::<Vec<_>>(),
+            ids(&[0, 2, 4])
+        );
 
+        // Recent records are not returned when composing with a bucket filter.
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                attributes: Some(json!({
-                    "attributeId": "0",
-                    "nestedId": {
-                        "attributeId": "0"
-                    }
-                })),
+                stale_before,
+                bucket: vec![Wildcard::new("1".to_string())].into(),
                 ..Default::default()
             },
             true,
         )
         .await;
-        assert_eq!(result, vec![entries[0].clone()]);
+        assert_eq!(
+            result.iter().map(|r| r.s3_object_id).collect::<Vec<_>>(),
+            ids(&[2])
+        );
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                attributes: Some(json!({
-                    "attributeId": ["0", "1"],
-                })),
+                stale_before: entries.s3_objects[0].event_time,
                 ..Default::default()
             },
             true,
         )
         .await;
-        assert_eq!(result, vec![entries[0].clone(), entries[1].clone()]);
+        assert!(result.is_empty());
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_paginate_s3(pool: PgPool) {
+    async fn test_list_s3_filter_version_count(pool: PgPool) {
         let client = Client::from_pool(pool);
-        let entries = EntriesBuilder::default()
-            .with_shuffle(true)
-            .build(&client)
-            .await
-            .unwrap()
-            .s3_objects;
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
+        let ids = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|i| entries.s3_objects[*i].s3_object_id)
+                .collect::<Vec<_>>()
+        };
 
-        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref());
+        // Record 1 becomes a second version of the key for record 0.
+        change_key(&client, &entries, 1, "0".to_string()).await;
+        // Record 5 becomes a duplicate of the same version as record 4.
+        change_key(&client, &entries, 5, "4".to_string()).await;
+        change_version_id(&client, &entries, 5, "4".to_string()).await;
 
-        let result = paginate_all(builder.clone(), 3, 3).await;
-        assert_eq!(result, entries[9..]);
-        // Empty result when paginating above the collection size.
-        assert!(paginate_all(builder.clone(), 10, 2).await.is_empty());
+        let version_count = |comparison, count| S3ObjectsFilter {
+            version_count: Some(CountComparison::new(comparison, count)),
+            ..Default::default()
+        };
+        let filter = async |filter, current_state| {
+            ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref())
+                .filter_all(filter, true, current_state)
+                .unwrap()
+                .all()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|r| r.s3_object_id)
+                .collect::<Vec<_>>()
+        };
 
-        let result = builder
-            .paginate_to_list_response(
-                Pagination::from_u64(1, 2).unwrap(),
-                "http://example.com/s3?rowsPerPage=2&page=1"
-                    .parse()
-                    .unwrap(),
-                10,
-            )
-            .await
-            .unwrap();
+        assert_eq!(
+            filter(version_count(Comparison::Gt, 1), false).await,
+            ids(&[0, 1])
+        );
+        // Duplicate records of the same version only count once.
+        assert_eq!(
+            filter(version_count(Comparison::Eq, 1), false).await,
+            ids(&[2, 3, 4, 5, 6, 7, 8, 9])
+        );
+        assert!(
+            filter(version_count(Comparison::Gte, 3), false)
+                .await
+                .is_empty()
+        );
 
+        // Composes with current state filtering.
         assert_eq!(
-            result.links(),
-            &Links::new(
-                None,
-                Some(
-                    "http://example.com/s3?rowsPerPage=2&page=2"
-                        .parse()
-                        .unwrap()
-                )
-            )
+            filter(version_count(Comparison::Gt, 1), true).await,
+            ids(&[0])
+        );
+        assert_eq!(
+            filter(version_count(Comparison::Lte, 1), true).await,
+            ids(&[2, 4, 6, 8])
         );
-        assert_eq!(result.results(), &entries[0..2]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_count_s3(pool: PgPool) {
+    async fn test_list_s3_filter_missing_metadata(pool: PgPool) {
         let client = Client::from_pool(pool);
-        EntriesBuilder::default()
-            .with_shuffle(true)
-            .build(&client)
-            .await
-            .unwrap();
-
-        let builder = ListQueryBuilder::<_, s3_object::Entity>::new(client.connection_ref());
-
-        assert_eq!(builder.clone().count().await.unwrap(), 10);
-        assert_eq!(builder.to_list_count().await.unwrap(), ListCount::new(10));
-    }
-
-    #[test]
-    fn test_filter_operation() {
-        let operation =
-            ListQueryBuilder::<DatabaseConnection, s3_object::Entity>::filter_operation(
-                Expr::col(s3_object::Column::StorageClass),
-                WildcardEither::Or(StorageClass::Standard),
-                true,
-            )
-            .unwrap();
-        assert!(matches!(
-            operation,
-            SimpleExpr::Binary(_, BinOper::Equal, _)
-        ));
-
-        let operation =
-            ListQueryBuilder::<DatabaseConnection, s3_object::Entity>::filter_operation(
-                Expr::col(s3_object::Column::StorageClass),
-                WildcardEither::Or(StorageClass::Standard),
-                false,
-            )
-            .unwrap();
-        assert!(matches!(
-            operation,
-            SimpleExpr::Binary(_, BinOper::Equal, _)
-        ));
-
-        let operation =
-            ListQueryBuilder::<DatabaseConnection, s3_object::Entity>::filter_operation(
-                Expr::col(s3_object::Column::StorageClass),
-                WildcardEither::Wildcard::<StorageClass>(Wildcard::new("Standar*".to_string())),
-                true,
-            )
-            .unwrap();
-        assert!(matches!(operation, SimpleExpr::Binary(_, BinOper::Like, _)));
-
-        let operation =
-            ListQueryBuilder::<DatabaseConnection, s3_object::Entity>::filter_operation(
-                Expr::col(s3_object::Column::StorageClass),
-                WildcardEither::Wildcard::<StorageClass>(Wildcard::new("Standar*".to_string())),
-                false,
-            )
-            .unwrap();
-        assert!(matches!(
-            operation,
-            SimpleExpr::Binary(_, BinOper::PgOperator(PgBinOper::ILike), _)
-        ));
-    }
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
 
-    #[sqlx::test(migrator = "MIGRATOR")]
-    async fn test_list_s3_filter_wildcard(pool: PgPool) {
-        let client = Client::from_pool(pool);
-        let entries = EntriesBuilder::default()
-            .with_shuffle(true)
-            .build(&client)
-            .await
-            .unwrap();
-        let s3_entries = entries.s3_objects.clone();
+        // Only the current state record with missing metadata should be returned.
+        change_last_modified_date(&client, &entries, 2, None).await;
+        change_last_modified_date(&client, &entries, 3, None).await;
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                event_time: vec![WildcardEither::Wildcard(Wildcard::new(
-                    "1970-01-0*".to_string(),
-                ))]
-                .into(),
+                missing_metadata: Some(true),
                 ..Default::default()
             },
             true,
         )
         .await;
         assert_eq!(
-            result,
-            s3_entries
-                .clone()
-                .into_iter()
-                .filter(|entry| entry
-                    .event_time
-                    .unwrap()
-                    .to_string()
-                    .starts_with("1970-01-0"))
-                .collect::<Vec<_>>()
+            result.iter().map(|r| r.s3_object_id).collect::<Vec<_>>(),
+            vec![entries.s3_objects[2].s3_object_id]
         );
 
         let result = filter_all_s3_from(
             &client,
             S3ObjectsFilter {
-                bucket: vec![Wildcard::new("0*".to_string())].into(),
+                missing_metadata: Some(false),
                 ..Default::default()
             },
-            false,
+            true,
         )
         .await;
-        assert_eq!(result, &s3_entries[0..2]);
+        assert_eq!(
+            result.iter().map(|r| r.s3_object_id).collect::<Vec<_>>(),
+            [0, 1, 4, 5, 6, 7, 8, 9].map(|i| entries.s3_objects[i].s3_object_id)
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn test_list_s3_filter_unmodified_for_days(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let entries = EntriesBuilder::default().build(&client).await.unwrap();
+
+        let days_ago = |days| Some((Utc::now() - Duration::days(days)).into());
+        change_last_modified_date(&client, &entries, 0, days_ago(1)).await;
+        change_last_modified_date(&client, &entries, 1, days_ago(20)).await;
+        change_last_modified_date(&client, &entries, 2, None).await;
+        change_last_modified_date(&client, &entries, 8, days_ago(40)).await;
+        change_last_modified_date(&client, &entries, 9, days_ago(5)).await;
+
+        let filter = |unmodified_for_days, storage_class| S3ObjectsFilter {
+            unmodified_for_days: Some(unmodified_for_days),
+            storage_class,
+            ..Default::default()
+        };
+        let ids = |result: Vec<s3_object::Model>| {
+            result.iter().map(|r| r.s3_object_id).collect::<Vec<_>>()
+        };
+
+        let result = filter_all_s3_from(&client, filter(15, Default::default()), true).await;
+        assert_eq!(
+            ids(result),
+            [1, 3, 4, 5, 6, 7, 8].map(|i| entries.s3_objects[i].s3_object_id)
+        );
+
+        let result = filter_all_s3_from(&client, filter(30, Default::default()), true).await;
+        assert_eq!(
+            ids(result),
+            [3, 4, 5, 6, 7, 8].map(|i| entries.s3_objects[i].s3_object_id)
+        );
+
+        // Old objects in the standard tier.
+        let result = filter_all_s3_from(
+            &client,
+            filter(30, vec![StorageClass::Standard].into()),
+            true,
+        )
+        .await;
+        assert_eq!(ids(result), vec![entries.s3_objects[8].s3_object_id]);
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
@@ -1346,6 +3279,17 @@ pub(crate) mod tests {
                 .contains(r#""attributes" @? CAST(E'$.attributeId.nested ? (@ == \"1\")"#)
         );
 
+        let conditions = JsonPathBuilder::json_condition(
+            s3_object::Column::Attributes.into_column_ref(),
+            json!({ "team1.attributeId": "1" }),
+            true,
+        )
+        .unwrap();
+        assert!(
+            condition_to_string(conditions)
+                .contains(r#""attributes" @? CAST(E'$.\"team1.attributeId\" ? (@ == \"1\")"#)
+        );
+
         let conditions = JsonPathBuilder::json_condition(
             s3_object::Column::Attributes.into_column_ref(),
             json!({ "attributeId": "1", "anotherId": "2" }),
@@ -25,8 +25,12 @@ use serde_json::json;
 use strum::EnumCount;
 use uuid::Uuid;
 
+pub mod diff;
+pub mod explain;
 pub mod get;
 pub mod list;
+pub mod prefix;
+pub mod timing;
 pub mod update;
 
 /// Container for generating database entries.
@@ -199,6 +203,14 @@ impl Entries {
             deleted_sequencer: Set(None),
             number_reordered: Set(0),
             reason: Set(Reason::Unknown),
+            is_e_tag_mismatch: Set(false),
+            server_side_encryption: Set(None),
+            sse_kms_key_id: Set(None),
+            tag_present: Set(None),
+            restore_expiry_date: Set(None),
+            is_sequencer_conflict: Set(false),
+            updated_at: Set(Default::default()),
+            ingested_at: Set(None),
         }
     }
 
@@ -213,8 +225,7 @@ impl Entries {
     ) -> ActiveS3Crawl {
         ActiveS3Crawl {
             s3_crawl_id: Set(uuid),
-            status: Set(CrawlStatus::from_repr(index % (CrawlStatus::COUNT - 1))
-                .unwrap_or(CrawlStatus::InProgress)),
+            status: Set(CrawlStatus::from_repr(index % 2).unwrap_or(CrawlStatus::InProgress)),
             started: Set(DateTime::default().add(Days::new(index as u64))),
             bucket: Set((index / divisors.0).to_string()),
             prefix: Set(Some(
@@ -224,6 +235,8 @@ impl Entries {
             )),
             execution_time_seconds: Set(Some(index as i32)),
             n_objects: Set(Some(index as i64)),
+            key_marker: Set(None),
+            cancel_requested: Set(false),
         }
     }
 
@@ -273,6 +286,12 @@ impl Entries {
             ingest_id: Some(ingest_id),
             reason: Reason::Unknown,
             attributes,
+            is_e_tag_mismatch: false,
+            server_side_encryption: None,
+            sse_kms_key_id: None,
+            tag_present: None,
+            restore_expiry_date: None,
+            is_sequencer_conflict: false,
             number_duplicate_events: 0,
             number_reordered: 0,
         }
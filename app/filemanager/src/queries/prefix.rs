@@ -0,0 +1,160 @@
+//! Query builder for listing the children of a prefix.
+//!
+
+use sea_orm::{ConnectionTrait, DbBackend, FromQueryResult, Statement};
+
+use crate::error::Error::ConversionError;
+use crate::error::Result;
+use crate::routes::prefix::{BrowseChild, BrowseListing, PrefixListing, PrefixObject};
+use crate::routes::tenant::TenantScope;
+
+/// A query builder for listing prefixes.
+pub struct PrefixQueryBuilder<'a, C> {
+    connection: &'a C,
+}
+
+/// A single child prefix or object.
+#[derive(Debug, FromQueryResult)]
+struct PrefixChildRow {
+    prefix: Option<String>,
+    key: Option<String>,
+    version_id: Option<String>,
+    size: Option<i64>,
+    e_tag: Option<String>,
+    n_children: i64,
+}
+
+/// The counts and sizes of a single child prefix or object.
+#[derive(Debug, FromQueryResult)]
+struct BrowseChildRow {
+    child: String,
+    is_prefix: bool,
+    n_keys: i64,
+    n_records: i64,
+    n_current: i64,
+    n_delete_markers: i64,
+    current_size: i64,
+}
+
+impl<'a, C> PrefixQueryBuilder<'a, C>
+where
+    C: ConnectionTrait,
+{
+    /// Create a new query builder.
+    pub fn new(connection: &'a C) -> Self {
+        Self { connection }
+    }
+
+    /// List the immediate child prefixes and objects under the prefix in the current state of
+    /// the bucket, using `/` as the delimiter. Only children after `start_after` and up to and
+    /// including `end_at` are listed, if they are set. Names are compared in byte order, which
+    /// is the order that S3 lists keys in.
+    pub async fn list_children_between(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+        end_at: Option<&str>,
+    ) -> Result<PrefixListing> {
+        let (listing, _) = self
+            .select_children(bucket, prefix, start_after, end_at, 0, None)
+            .await?;
+        Ok(listing)
+    }
+
+    /// List a page of the immediate child prefixes and objects under the prefix, skipping
+    /// `offset` children and returning at most `limit` children. Child prefixes are ordered
+    /// before objects. This also returns the total number of children under the prefix.
+    pub async fn list_children_page(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> Result<(PrefixListing, u64)> {
+        self.select_children(bucket, prefix, None, None, offset, limit)
+            .await
+    }
+
+    /// Select the children in the name range, returning a page of the children along with the
+    /// total number of children in the range.
+    async fn select_children(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        start_after: Option<&str>,
+        end_at: Option<&str>,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> Result<(PrefixListing, u64)> {
+        TenantScope::check_bucket(bucket)?;
+
+        let to_i64 =
+            |value: u64| i64::try_from(value).map_err(|err| ConversionError(err.to_string()));
+        let rows = PrefixChildRow::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            include_str!("../../../database/queries/api/select_prefix_children.sql"),
+            [
+                bucket.into(),
+                prefix.into(),
+                limit.map(to_i64).transpose()?.into(),
+                to_i64(offset)?.into(),
+                start_after.into(),
+                end_at.into(),
+            ],
+        ))
+        .all(self.connection)
+        .await?;
+
+        let n_children = rows.first().map(|row| row.n_children).unwrap_or_default();
+        let mut listing = PrefixListing::default();
+        for row in rows {
+            match (row.prefix, row.key) {
+                (Some(prefix), _) => listing.prefixes.push(prefix),
+                (None, Some(key)) => listing.objects.push(PrefixObject {
+                    key,
+                    version_id: row.version_id.unwrap_or_default(),
+                    size: row.size,
+                    e_tag: row.e_tag,
+                }),
+                (None, None) => {}
+            }
+        }
+
+        Ok((listing, n_children as u64))
+    }
+
+    /// Group all records under the prefix by the next path segment, using `/` as the delimiter.
+    /// Unlike `list_children_page`, this includes records which are not current.
+    pub async fn browse(&self, bucket: &str, prefix: &str) -> Result<BrowseListing> {
+        TenantScope::check_bucket(bucket)?;
+
+        let rows = BrowseChildRow::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            include_str!("../../../database/queries/api/select_prefix_browse.sql"),
+            [bucket.into(), prefix.into()],
+        ))
+        .all(self.connection)
+        .await?;
+
+        let mut listing = BrowseListing::default();
+        for row in rows {
+            let child = BrowseChild {
+                name: row.child,
+                n_keys: row.n_keys as u64,
+                n_records: row.n_records as u64,
+                n_current: row.n_current as u64,
+                n_delete_markers: row.n_delete_markers as u64,
+                current_size: row.current_size,
+            };
+
+            if row.is_prefix {
+                listing.prefixes.push(child);
+            } else {
+                listing.objects.push(child);
+            }
+        }
+
+        Ok(listing)
+    }
+}
@@ -0,0 +1,170 @@
+//! Timing instrumentation for queries, used to log slow queries.
+//!
+
+use std::future::Future;
+use std::time::Instant;
+
+use chrono::Duration;
+use sea_orm::ConnectionTrait;
+use tracing::warn;
+
+use crate::error::Result;
+
+/// Run the query, logging a warning with the elapsed time and filter summary if it takes
+/// longer than the threshold. Nothing is logged if the threshold is `None`.
+pub async fn log_slow_query<F, T>(threshold: Option<Duration>, filter: String, query: F) -> T
+where
+    F: Future<Output = T>,
+{
+    let Some(threshold) = threshold else {
+        return query.await;
+    };
+
+    let start = Instant::now();
+    let result = query.await;
+    let elapsed = start.elapsed();
+
+    if Duration::from_std(elapsed).is_ok_and(|elapsed| elapsed > threshold) {
+        warn!(
+            elapsed_ms = elapsed.as_millis(),
+            threshold_ms = threshold.num_milliseconds(),
+            filter,
+            "slow query"
+        );
+    }
+
+    result
+}
+
+/// Set the statement timeout for the rest of the current transaction using `SET LOCAL`. Queries
+/// which take longer than the timeout are cancelled by the database. Nothing is set if the
+/// timeout is `None`.
+pub async fn set_statement_timeout<C: ConnectionTrait>(
+    connection: &C,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let Some(timeout) = timeout else {
+        return Ok(());
+    };
+
+    // A timeout of zero disables the timeout in Postgres, so use at least one millisecond.
+    connection
+        .execute_unprepared(&format!(
+            "set local statement_timeout = {}",
+            timeout.num_milliseconds().max(1)
+        ))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use sea_orm::{ConnectionTrait, DatabaseTransaction};
+    use sqlx::PgPool;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::*;
+    use crate::database::Client;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::env::Config;
+    use crate::error::Error;
+    use crate::routes::AppState;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn log_slow_query_over_threshold(pool: PgPool) {
+        let client = Client::from_pool(pool);
+        let logs = CapturedLogs::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_writer(logs.clone())
+                .with_ansi(false)
+                .finish(),
+        );
+
+        let sleep = || {
+            client
+                .connection_ref()
+                .execute_unprepared("select pg_sleep(0.1)")
+        };
+
+        log_slow_query(Some(Duration::hours(1)), "key".to_string(), sleep())
+            .await
+            .unwrap();
+        log_slow_query(None, "key".to_string(), sleep())
+            .await
+            .unwrap();
+        assert!(logs.to_string().is_empty());
+
+        log_slow_query(Some(Duration::milliseconds(10)), "key".to_string(), sleep())
+            .await
+            .unwrap();
+        let logs = logs.to_string();
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("slow query"));
+        assert!(logs.contains("threshold_ms=10"));
+        assert!(logs.contains("filter=\"key\""));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn statement_timeout_cancels_slow_query(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let sleep = |txn: DatabaseTransaction| async move {
+            let result = txn
+                .execute_unprepared("select pg_sleep(0.5)")
+                .await
+                .map_err(Error::from);
+            txn.rollback().await.unwrap();
+            result
+        };
+
+        // Queries run normally without a timeout.
+        assert!(sleep(state.begin_read().await.unwrap()).await.is_ok());
+
+        let state = state.with_config(Config {
+            api_statement_timeout: Some(Duration::milliseconds(50)),
+            ..Default::default()
+        });
+        let result = sleep(state.begin_read().await.unwrap()).await;
+        assert!(matches!(result, Err(Error::QueryError(_))));
+
+        // The timeout only applies to the transaction it was set in.
+        let result = state
+            .database_client()
+            .connection_ref()
+            .execute_unprepared("select pg_sleep(0.1)")
+            .await;
+        assert!(result.is_ok());
+    }
+
+    /// Captures formatted logs so that they can be asserted on.
+    #[derive(Debug, Default, Clone)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for CapturedLogs {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    impl std::fmt::Display for CapturedLogs {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", String::from_utf8_lossy(&self.0.lock().unwrap()))
+        }
+    }
+}
@@ -1,6 +1,8 @@
 //! Query builder to handle updating record columns.
 //!
 
+use std::collections::HashSet;
+
 use json_patch::{PatchOperation, patch};
 use sea_orm::prelude::{Expr, Json};
 use sea_orm::sea_query::{
@@ -8,13 +10,14 @@ use sea_orm::sea_query::{
     WithQuery,
 };
 use sea_orm::{
-    ColumnTrait, ConnectionTrait, EntityTrait, FromQueryResult, Iden, Iterable, ModelTrait,
-    QueryFilter, QueryTrait, StatementBuilder, Value,
+    ActiveEnum, ColumnTrait, ConnectionTrait, EntityTrait, FromQueryResult, Iden, Iterable,
+    ModelTrait, QueryFilter, QuerySelect, QueryTrait, StatementBuilder, Value,
 };
 use serde_json::json;
 use uuid::Uuid;
 
 use crate::database::entities::s3_object;
+use crate::database::entities::sea_orm_active_enums::StorageClass;
 use crate::error::Error::{InvalidQuery, QueryError};
 use crate::error::Result;
 use crate::queries::list::ListQueryBuilder;
@@ -56,6 +59,22 @@ where
         self
     }
 
+    /// Update the records of an object, matching the bucket, key and version id exactly rather
+    /// than as wildcards. All versions are matched if the version id is `None`.
+    pub fn for_object(mut self, bucket: &str, key: &str, version_id: Option<&str>) -> Self {
+        let (connection, mut select) = self.select_to_update.into_inner();
+
+        select = select
+            .filter(s3_object::Column::Bucket.eq(bucket))
+            .filter(s3_object::Column::Key.eq(key))
+            .apply_if(version_id, |select, version_id| {
+                select.filter(s3_object::Column::VersionId.eq(version_id))
+            });
+
+        self.select_to_update = (connection, select).into();
+        self
+    }
+
     /// Filter records by all fields in the filter variable.
     pub fn filter_all(
         mut self,
@@ -82,6 +101,145 @@ where
         self.update_attributes(patch, s3_object::Column::S3ObjectId, col)
             .await
     }
+
+    /// Set the storage class on the selected s3_objects. This corrects the records directly
+    /// without creating any new events. The update statement generated is similar to:
+    ///
+    /// ```sql
+    /// with update_with (id) as (<select_to_update>)
+    /// update <s3_object> set storage_class = <storage_class>
+    /// where object_id in (select id from update_with)
+    /// returning <updated_objects>
+    /// ```
+    pub fn update_s3_storage_class(self, storage_class: StorageClass) -> Self {
+        let (conn, select_to_update, _) = self.into_inner();
+
+        let cte_id = Alias::new("id");
+        let cte_name = Alias::new("update_with");
+
+        // with update_with(id) as (select object_id from <select_to_update>)
+        let (_, select) = select_to_update.cloned().into_inner();
+        let cte = CommonTableExpression::new()
+            .query(
+                select
+                    .select_only()
+                    .column(s3_object::Column::S3ObjectId)
+                    .into_query(),
+            )
+            .columns([cte_id.clone()])
+            .table_name(cte_name.clone())
+            .to_owned();
+        let with_clause = WithClause::new().cte(cte).to_owned();
+
+        // select id in update_with
+        let select_in = SelectStatement::new()
+            .column(cte_id)
+            .from(cte_name)
+            .to_owned();
+
+        let returning =
+            Query::returning().exprs(s3_object::Column::iter().map(|c| c.select_as(Expr::col(c))));
+        let update = s3_object::Entity::update_many()
+            .into_query()
+            .value(s3_object::Column::StorageClass, storage_class.as_enum())
+            .and_where(s3_object::Column::S3ObjectId.in_subquery(select_in))
+            .returning(returning)
+            .to_owned();
+
+        let self_return: Self = (conn, select_to_update, update.with(with_clause)).into();
+
+        self_return.trace_query("update_s3_storage_class");
+
+        self_return
+    }
+
+    /// Apply the patch to the selected s3_objects in memory without updating the database. This
+    /// returns the records as they would be after the update, or an error if the patch fails,
+    /// for example, because of a failed `test` operation.
+    pub async fn validate_s3_attributes(self, patch: PatchBody) -> Result<Vec<s3_object::Model>> {
+        let to_validate = self.select_to_update.cloned().all().await?;
+
+        to_validate
+            .into_iter()
+            .map(|mut model| {
+                match patch.clone() {
+                    PatchBody::NestedIngestId { .. } => {
+                        model.ingest_id = patch.extract_ingest_id()?;
+                    }
+                    PatchBody::UnnestedAttributes(attributes)
+                    | PatchBody::NestedAttributes { attributes } => {
+                        let Value::Json(json) = Self::patch_for_attributes(
+                            attributes.into_inner().0,
+                            s3_object::Column::Attributes,
+                            model.clone(),
+                        )?
+                        else {
+                            return Err(QueryError("expected JSON attribute column".to_string()));
+                        };
+                        model.attributes = json.map(|json| *json);
+                    }
+                };
+
+                Ok(model)
+            })
+            .collect()
+    }
+
+    /// Check that the top-level attribute `keys` on the `updated` records have the same JSON type
+    /// as the same keys on all other records in the bucket. This should be called after an update
+    /// within the same transaction, so that an error can roll back the update. `null` values are
+    /// not checked.
+    pub async fn verify_attribute_types(
+        connection: &C,
+        keys: &[String],
+        updated: &[s3_object::Model],
+    ) -> Result<()> {
+        let mut to_check = HashSet::new();
+        for model in updated {
+            let Some(attributes) = model.attributes.as_ref().and_then(|json| json.as_object())
+            else {
+                continue;
+            };
+
+            for key in keys {
+                if let Some(json_type) = attributes.get(key).and_then(Self::json_type) {
+                    to_check.insert((model.bucket.as_str(), key.as_str(), json_type));
+                }
+            }
+        }
+
+        for (bucket, key, json_type) in to_check {
+            let conflict = s3_object::Entity::find()
+                .filter(s3_object::Column::Bucket.eq(bucket))
+                .filter(Expr::cust_with_values(
+                    "jsonb_typeof(attributes -> $1) not in ('null', $2)",
+                    [key, json_type],
+                ))
+                .one(connection)
+                .await?;
+
+            if conflict.is_some() {
+                return Err(InvalidQuery(format!(
+                    "attribute `{key}` cannot be a `{json_type}` because it has a different type \
+                    on other records in bucket `{bucket}`"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the name of the JSON type of a value, matching the names used by `jsonb_typeof`.
+    fn json_type(value: &Json) -> Option<&'static str> {
+        match value {
+            Json::Null => None,
+            Json::Bool(_) => Some("boolean"),
+            Json::Number(_) => Some("number"),
+            Json::String(_) => Some("string"),
+            Json::Array(_) => Some("array"),
+            Json::Object(_) => Some("object"),
+        }
+    }
 }
 
 impl<'a, C, E> From<(&'a C, ListQueryBuilder<'a, C, E>, WithQuery)> for UpdateQueryBuilder<'a, C, E>
@@ -300,6 +458,7 @@ where
 pub(crate) mod tests {
     use std::ops::{Index, Range};
 
+    use sea_orm::prelude::DateTimeWithTimeZone;
     use sea_orm::{ActiveModelTrait, IntoActiveModel};
     use sea_orm::{DatabaseConnection, Set};
     use serde_json::json;
@@ -308,6 +467,7 @@ pub(crate) mod tests {
 
     use crate::database::Client;
     use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::sea_orm_active_enums::Reason;
     use crate::queries::{Entries, EntriesBuilder};
     use crate::routes::filter::wildcard::Wildcard;
     use crate::routes::filter::wildcard::WildcardEither;
@@ -950,6 +1110,104 @@ pub(crate) mod tests {
         model.update(client.connection_ref()).await.unwrap();
     }
 
+    pub(crate) async fn change_size(
+        client: &Client,
+        entries: &Entries,
+        entry: usize,
+        value: Option<i64>,
+    ) {
+        let mut model: s3_object::ActiveModel =
+            entries.s3_objects[entry].clone().into_active_model();
+        model.size = Set(value);
+        model.update(client.connection_ref()).await.unwrap();
+    }
+
+    pub(crate) async fn change_version_id(
+        client: &Client,
+        entries: &Entries,
+        entry: usize,
+        value: String,
+    ) {
+        let mut model: s3_object::ActiveModel =
+            entries.s3_objects[entry].clone().into_active_model();
+        model.version_id = Set(value);
+        model.update(client.connection_ref()).await.unwrap();
+    }
+
+    pub(crate) async fn change_bucket(
+        client: &Client,
+        entries: &Entries,
+        entry: usize,
+        value: String,
+    ) {
+        let mut model: s3_object::ActiveModel =
+            entries.s3_objects[entry].clone().into_active_model();
+        model.bucket = Set(value);
+        model.update(client.connection_ref()).await.unwrap();
+    }
+
+    pub(crate) async fn change_last_modified_date(
+        client: &Client,
+        entries: &Entries,
+        entry: usize,
+        value: Option<DateTimeWithTimeZone>,
+    ) {
+        let mut model: s3_object::ActiveModel =
+            entries.s3_objects[entry].clone().into_active_model();
+        model.last_modified_date = Set(value);
+        model.update(client.connection_ref()).await.unwrap();
+    }
+
+    pub(crate) async fn change_server_side_encryption(
+        client: &Client,
+        entries: &Entries,
+        entry: usize,
+        server_side_encryption: Option<String>,
+        sse_kms_key_id: Option<String>,
+    ) {
+        let mut model: s3_object::ActiveModel =
+            entries.s3_objects[entry].clone().into_active_model();
+        model.server_side_encryption = Set(server_side_encryption);
+        model.sse_kms_key_id = Set(sse_kms_key_id);
+        model.update(client.connection_ref()).await.unwrap();
+    }
+
+    pub(crate) async fn change_tag_present(
+        client: &Client,
+        entries: &Entries,
+        entry: usize,
+        tag_present: Option<bool>,
+    ) {
+        let mut model: s3_object::ActiveModel =
+            entries.s3_objects[entry].clone().into_active_model();
+        model.tag_present = Set(tag_present);
+        model.update(client.connection_ref()).await.unwrap();
+    }
+
+    pub(crate) async fn change_sequencer_conflict(
+        client: &Client,
+        entries: &Entries,
+        entry: usize,
+        is_sequencer_conflict: bool,
+    ) {
+        let mut model: s3_object::ActiveModel =
+            entries.s3_objects[entry].clone().into_active_model();
+        model.is_sequencer_conflict = Set(is_sequencer_conflict);
+        model.update(client.connection_ref()).await.unwrap();
+    }
+
+    pub(crate) async fn change_reason(
+        client: &Client,
+        entries: &Entries,
+        entry: usize,
+        reason: Reason,
+    ) {
+        let mut model: s3_object::ActiveModel =
+            entries.s3_objects[entry].clone().into_active_model();
+        model.reason = Set(reason);
+        model.update(client.connection_ref()).await.unwrap();
+    }
+
     /// Change attributes in the entries.
     pub(crate) fn change_attribute_entries(entries: &mut Entries, entry: usize, value: Value) {
         entries.s3_objects[entry].attributes = Some(value.clone());
@@ -0,0 +1,335 @@
+//! Route logic for auditing stored records against the live objects in S3.
+//!
+
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::types::StorageClass;
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database::entities::s3_object;
+use crate::error::{Error, Result};
+use crate::events::aws::collecter::{Collecter, MAX_COLLECT_CONCURRENCY};
+use crate::queries::list::ListQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::list::WildcardParams;
+use crate::routes::pagination::LimitParams;
+
+/// The maximum number of records that are audited per call.
+pub const MAX_AUDIT_LIMIT: u64 = 1000;
+
+/// The live accessibility of an object, determined using `HeadObject`.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+pub enum LiveAccessibility {
+    /// The object exists and can be read directly.
+    Accessible,
+    /// The object exists, but is in archive storage and has not been restored.
+    Archived,
+    /// The object does not exist.
+    NotFound,
+    /// Access to the object was denied.
+    Forbidden,
+}
+
+impl LiveAccessibility {
+    /// Determine the accessibility of an object from a successful `HeadObject` call. This mirrors
+    /// the logic used to compute `isAccessible` on records.
+    pub fn from_head(head: &HeadObjectOutput) -> Self {
+        let is_restored = head
+            .restore()
+            .is_some_and(|restore| restore.contains(r#"ongoing-request="false""#));
+
+        match head.storage_class() {
+            Some(StorageClass::Glacier) | Some(StorageClass::DeepArchive) if !is_restored => {
+                Self::Archived
+            }
+            Some(StorageClass::IntelligentTiering) if head.archive_status().is_some() => {
+                Self::Archived
+            }
+            _ => Self::Accessible,
+        }
+    }
+
+    /// Whether the object is accessible.
+    pub fn is_accessible(&self) -> bool {
+        matches!(self, Self::Accessible)
+    }
+}
+
+/// The direction of a disagreement between the stored `isAccessible` flag and the live object.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+pub enum AccessibilityDisagreement {
+    /// The record is marked accessible, but the live object is not accessible.
+    StaleAccessible,
+    /// The record is marked inaccessible, but the live object is accessible.
+    StaleInaccessible,
+}
+
+/// A record where the stored `isAccessible` flag disagrees with the live object in S3.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessibilityAudit {
+    /// The id of the record.
+    pub(crate) s3_object_id: Uuid,
+    /// The bucket of the object.
+    pub(crate) bucket: String,
+    /// The key of the object.
+    pub(crate) key: String,
+    /// The version id of the object.
+    pub(crate) version_id: String,
+    /// The `isAccessible` flag stored on the record.
+    pub(crate) is_accessible: bool,
+    /// The live accessibility of the object.
+    pub(crate) live: LiveAccessibility,
+    /// The direction of the disagreement.
+    pub(crate) disagreement: AccessibilityDisagreement,
+}
+
+impl AccessibilityAudit {
+    /// Compare the stored flag of a record with the live accessibility, returning `None` if
+    /// they agree.
+    pub fn compare(record: s3_object::Model, live: LiveAccessibility) -> Option<Self> {
+        let disagreement = match (record.is_accessible, live.is_accessible()) {
+            (true, false) => AccessibilityDisagreement::StaleAccessible,
+            (false, true) => AccessibilityDisagreement::StaleInaccessible,
+            _ => return None,
+        };
+
+        Some(Self {
+            s3_object_id: record.s3_object_id,
+            bucket: record.bucket,
+            key: record.key,
+            version_id: record.version_id,
+            is_accessible: record.is_accessible,
+            live,
+            disagreement,
+        })
+    }
+
+    /// Get the s3_object_id.
+    pub fn s3_object_id(&self) -> Uuid {
+        self.s3_object_id
+    }
+
+    /// Get the live accessibility.
+    pub fn live(&self) -> LiveAccessibility {
+        self.live
+    }
+
+    /// Get the direction of the disagreement.
+    pub fn disagreement(&self) -> AccessibilityDisagreement {
+        self.disagreement
+    }
+}
+
+/// Determine the live accessibility of a record's object version.
+async fn live_accessibility(
+    state: &AppState,
+    record: &s3_object::Model,
+) -> Result<LiveAccessibility> {
+    let client = state.s3_client();
+    let head = match client
+        .head_object(&record.key, &record.bucket, &record.version_id)
+        .await
+    {
+        // Archived objects cannot have their checksum retrieved, but the storage class and
+        // archive status are still available without it.
+        Err(err) if Collecter::is_invalid_object_state(&err) => {
+            client
+                .head_object_without_checksum(&record.key, &record.bucket, &record.version_id)
+                .await
+        }
+        head => head,
+    };
+
+    match head {
+        Ok(head) => Ok(LiveAccessibility::from_head(&head)),
+        Err(err) if Collecter::is_not_found(&err) => Ok(LiveAccessibility::NotFound),
+        Err(err)
+            if Collecter::has_status(&err, 403)
+                || matches!(err.code(), Some("Forbidden") | Some("AccessDenied")) =>
+        {
+            Ok(LiveAccessibility::Forbidden)
+        }
+        Err(err) => Err(Error::from((err, "HeadObject".to_string()))),
+    }
+}
+
+/// Audit the `isAccessible` flag of current records matching the filter. This calls
+/// `HeadObject` on each object, and returns the records where the stored flag disagrees with
+/// the live object, along with the direction of the disagreement. Objects that are missing,
+/// forbidden or in archive storage are considered inaccessible. At most `limit` records are
+/// audited per call.
+#[utoipa::path(
+    get,
+    path = "/s3/audit/accessibility",
+    responses(
+        (
+            status = OK,
+            description = "The records where the stored accessibility disagrees with S3",
+            body = Vec<AccessibilityAudit>
+        ),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, LimitParams<MAX_AUDIT_LIMIT>, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn audit_accessibility_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(audit), _): Query<LimitParams<MAX_AUDIT_LIMIT>>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<AccessibilityAudit>>> {
+    let records = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), true)?
+    .paginate(0, audit.limit())
+    .await?
+    .all()
+    .await?;
+
+    let results = stream::iter(records)
+        .map(|record| async {
+            let live = live_accessibility(&state, &record).await?;
+            Ok::<_, Error>(AccessibilityAudit::compare(record, live))
+        })
+        .buffered(MAX_COLLECT_CONCURRENCY)
+        .try_filter_map(|audit| async { Ok(audit) })
+        .try_collect()
+        .await?;
+
+    Ok(Json(results))
+}
+
+/// The router for auditing objects.
+pub fn audit_router() -> Router<AppState> {
+    Router::new().route("/s3/audit/accessibility", get(audit_accessibility_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_smithy_mocks::mock;
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::events::aws::collecter::tests::{expected_head_object_status, mock_s3};
+    use crate::queries::EntriesBuilder;
+    use crate::routes::list::tests::response_from;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn audit_accessibility_s3_api(pool: PgPool) {
+        let forbidden = mock!(aws_sdk_s3::Client::head_object)
+            .match_requests(|req| req.key() == Some("2"))
+            .then_http_response(|| expected_head_object_status(403));
+        let client = mock_s3(&[
+            forbidden.clone(),
+            mock!(aws_sdk_s3::Client::head_object)
+                .match_requests(|req| req.key() != Some("2"))
+                .then_output(|| {
+                    HeadObjectOutput::builder()
+                        .storage_class(StorageClass::Standard)
+                        .build()
+                }),
+        ]);
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(client);
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Record 0 is in `DeepArchive` storage, but the live object is accessible. Record 2 is
+        // accessible, but the live object is forbidden.
+        assert!(!entries[0].is_accessible);
+        assert!(entries[2].is_accessible);
+
+        let (status, result) = response_from::<Vec<AccessibilityAudit>>(
+            state.clone(),
+            "/s3/audit/accessibility",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        // A forbidden response is retried without checksums, in case the object is archived.
+        assert_eq!(forbidden.num_calls(), 2);
+        assert_eq!(
+            result
+                .iter()
+                .map(|audit| (audit.s3_object_id(), audit.live(), audit.disagreement()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    entries[0].s3_object_id,
+                    LiveAccessibility::Accessible,
+                    AccessibilityDisagreement::StaleInaccessible
+                ),
+                (
+                    entries[2].s3_object_id,
+                    LiveAccessibility::Forbidden,
+                    AccessibilityDisagreement::StaleAccessible
+                ),
+            ]
+        );
+
+        // Filters and the limit restrict the audited records.
+        let (_, result) = response_from::<Vec<AccessibilityAudit>>(
+            state,
+            "/s3/audit/accessibility?key=2&limit=1",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].s3_object_id(), entries[2].s3_object_id);
+    }
+
+    #[test]
+    fn live_accessibility_from_head() {
+        let head = |storage_class, restore: Option<&str>| {
+            HeadObjectOutput::builder()
+                .storage_class(storage_class)
+                .set_restore(restore.map(ToString::to_string))
+                .build()
+        };
+
+        assert_eq!(
+            LiveAccessibility::from_head(&HeadObjectOutput::builder().build()),
+            LiveAccessibility::Accessible
+        );
+        assert_eq!(
+            LiveAccessibility::from_head(&head(StorageClass::DeepArchive, None)),
+            LiveAccessibility::Archived
+        );
+        assert_eq!(
+            LiveAccessibility::from_head(&head(
+                StorageClass::Glacier,
+                Some(r#"ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT""#)
+            )),
+            LiveAccessibility::Accessible
+        );
+        assert_eq!(
+            LiveAccessibility::from_head(&head(
+                StorageClass::Glacier,
+                Some(r#"ongoing-request="true""#)
+            )),
+            LiveAccessibility::Archived
+        );
+    }
+}
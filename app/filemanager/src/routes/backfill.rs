@@ -0,0 +1,300 @@
+//! Lazily backfills missing checksums for records that are read through the API.
+//!
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use tracing::{trace, warn};
+use uuid::Uuid;
+
+use crate::database::entities::s3_object::Model as S3;
+use crate::events::aws::message::RecollectMessage;
+use crate::routes::AppState;
+use crate::routes::collect::REQUEUE_BATCH_SIZE;
+use crate::routes::tenant::TenantScope;
+
+/// How long a record is not enqueued again after it was enqueued for a checksum backfill. This
+/// avoids repeatedly backfilling objects which do not have a checksum in S3.
+pub const BACKFILL_INTERVAL: Duration = Duration::hours(1);
+
+/// The maximum number of recently enqueued records that are remembered. Records read while this
+/// many records are remembered are skipped, and can be backfilled on a later read.
+pub const MAX_BACKFILL_RECORDS: usize = 10000;
+
+/// A sink which receives records that should have their checksum backfilled.
+#[async_trait]
+pub trait BackfillSink: Debug + Send + Sync {
+    /// Schedule a checksum backfill for the records. This must not re-collect the records
+    /// itself. It runs in the background after the response is returned, so failures should be
+    /// logged rather than returned.
+    async fn schedule(&self, state: &AppState, s3_object_ids: Vec<Uuid>);
+}
+
+/// Schedule a checksum backfill for current state records in the response which have a null
+/// `sha256`, if this is enabled in the config. The backfill is spawned so that it does not delay
+/// the response.
+pub fn backfill_checksums(state: &AppState, records: &[S3]) {
+    if !state.config().api_checksum_backfill() {
+        return;
+    }
+
+    let s3_object_ids = records
+        .iter()
+        .filter(|record| record.is_current_state && record.sha256.is_none())
+        .map(|record| record.s3_object_id)
+        .collect::<Vec<_>>();
+    if !s3_object_ids.is_empty() {
+        let state = state.clone();
+        tokio::spawn(TenantScope::propagate(async move {
+            state
+                .checksum_backfill()
+                .schedule(&state, s3_object_ids)
+                .await;
+        }));
+    }
+}
+
+/// The default backfill sink, which sends re-collect requests to the ingest queue, so that the
+/// records are re-collected by the ingester rather than the API. Records which were enqueued
+/// within the `BACKFILL_INTERVAL` are skipped, and the number of remembered records is limited.
+#[derive(Debug)]
+pub struct QueueBackfillSink {
+    enqueued: Mutex<HashMap<Uuid, DateTime<Utc>>>,
+    max_records: usize,
+}
+
+impl Default for QueueBackfillSink {
+    fn default() -> Self {
+        Self::new(MAX_BACKFILL_RECORDS)
+    }
+}
+
+impl QueueBackfillSink {
+    /// Create a new sink which remembers a maximum number of enqueued records.
+    pub fn new(max_records: usize) -> Self {
+        Self {
+            enqueued: Default::default(),
+            max_records,
+        }
+    }
+
+    /// Take the records which should be enqueued at `now`, and remember them as enqueued. This
+    /// skips records that were enqueued within the `BACKFILL_INTERVAL`, and records that do not
+    /// fit within the maximum number of remembered records.
+    pub fn take_new(&self, s3_object_ids: Vec<Uuid>, now: DateTime<Utc>) -> Vec<Uuid> {
+        // The map is always left in a valid state, so a poisoned lock can be recovered.
+        let mut enqueued = self.enqueued.lock().unwrap_or_else(|err| err.into_inner());
+        enqueued.retain(|_, enqueued_at| now - *enqueued_at < BACKFILL_INTERVAL);
+
+        s3_object_ids
+            .into_iter()
+            .filter(|s3_object_id| {
+                if enqueued.contains_key(s3_object_id) || enqueued.len() >= self.max_records {
+                    return false;
+                }
+
+                enqueued.insert(*s3_object_id, now);
+                true
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl BackfillSink for QueueBackfillSink {
+    async fn schedule(&self, state: &AppState, s3_object_ids: Vec<Uuid>) {
+        let Some(url) = state.config().sqs_url() else {
+            trace!("skipping checksum backfill without an ingest queue");
+            return;
+        };
+
+        let s3_object_ids = self.take_new(s3_object_ids, Utc::now());
+        for batch in s3_object_ids.chunks(REQUEUE_BATCH_SIZE) {
+            let result = match serde_json::to_string(&RecollectMessage::new(batch.to_vec())) {
+                Ok(body) => state
+                    .sqs_client()
+                    .send_message(url, &body)
+                    .await
+                    .map_err(|err| err.into_service_error().to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+
+            if let Err(err) = result {
+                warn!(
+                    "failed to enqueue checksum backfill for {:?}: {}",
+                    batch, err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::slice;
+    use std::sync::Arc;
+
+    use aws_sdk_sqs::operation::send_message::SendMessageOutput;
+    use aws_smithy_mocks::mock;
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::s3_object;
+    use crate::env::Config;
+    use crate::events::aws::collecter::tests::mock_sqs;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::list::tests::response_from;
+    use crate::uuid::UuidGenerator;
+
+    /// A sink which records the scheduled backfills instead of running them.
+    #[derive(Debug, Default)]
+    pub(crate) struct RecordingBackfillSink(Mutex<Vec<Uuid>>);
+
+    impl RecordingBackfillSink {
+        /// Get the scheduled records.
+        pub(crate) fn scheduled(&self) -> Vec<Uuid> {
+            self.0.lock().unwrap().clone()
+        }
+
+        /// Wait until the number of scheduled records is reached, and get the scheduled records.
+        pub(crate) async fn wait_for(&self, n: usize) -> Vec<Uuid> {
+            while self.scheduled().len() < n {
+                tokio::task::yield_now().await;
+            }
+            self.scheduled()
+        }
+    }
+
+    #[async_trait]
+    impl BackfillSink for RecordingBackfillSink {
+        async fn schedule(&self, _state: &AppState, s3_object_ids: Vec<Uuid>) {
+            self.0.lock().unwrap().extend(s3_object_ids);
+        }
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn backfill_checksums_on_read(pool: PgPool) {
+        let sink = Arc::new(RecordingBackfillSink::default());
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_checksum_backfill: true,
+                ..Default::default()
+            })
+            .with_checksum_backfill(sink.clone());
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Record 2 is a current record and record 3 is not.
+        for i in [2, 3] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.sha256 = Set(None);
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let (status, _) = response_from::<Value>(
+            state.clone(),
+            "/s3?currentState=false",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            sink.wait_for(1).await,
+            vec![entries.s3_objects[2].s3_object_id]
+        );
+
+        for i in [2, 3] {
+            let (status, _) = response_from::<Value>(
+                state.clone(),
+                &format!("/s3/{}", entries.s3_objects[i].s3_object_id),
+                Method::GET,
+                Body::empty(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        assert_eq!(
+            sink.wait_for(2).await,
+            vec![
+                entries.s3_objects[2].s3_object_id,
+                entries.s3_objects[2].s3_object_id
+            ]
+        );
+
+        // Nothing is scheduled if the backfill is disabled.
+        let sink = Arc::new(RecordingBackfillSink::default());
+        let state = state
+            .with_config(Default::default())
+            .with_checksum_backfill(sink.clone());
+        response_from::<Value>(state, "/s3", Method::GET, Body::empty()).await;
+        assert!(sink.scheduled().is_empty());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn queue_backfill_sink_schedule(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                sqs_url: Some("url".to_string()),
+                ..Default::default()
+            });
+        let (a, b) = (UuidGenerator::generate(), UuidGenerator::generate());
+
+        let body = serde_json::to_string(&RecollectMessage::new(vec![a, b])).unwrap();
+        let rule = mock!(aws_sdk_sqs::Client::send_message)
+            .match_requests(move |req| {
+                req.queue_url() == Some("url") && req.message_body() == Some(body.as_str())
+            })
+            .then_output(|| SendMessageOutput::builder().build());
+        let state = state.with_sqs_client(mock_sqs(slice::from_ref(&rule)));
+
+        // Records are only enqueued once within the interval.
+        let sink = QueueBackfillSink::default();
+        sink.schedule(&state, vec![a, b]).await;
+        sink.schedule(&state, vec![a, b]).await;
+        assert_eq!(rule.num_calls(), 1);
+    }
+
+    #[test]
+    fn queue_backfill_sink_take_new() {
+        let sink = QueueBackfillSink::new(2);
+        let (a, b, c) = (
+            UuidGenerator::generate(),
+            UuidGenerator::generate(),
+            UuidGenerator::generate(),
+        );
+        let now = Utc::now();
+
+        // Records are not enqueued twice within the interval.
+        assert_eq!(sink.take_new(vec![a, a], now), vec![a]);
+        assert!(sink.take_new(vec![a], now).is_empty());
+
+        // The number of remembered records is limited.
+        assert_eq!(sink.take_new(vec![b, c], now), vec![b]);
+
+        // Records can be enqueued again after the interval.
+        assert_eq!(
+            sink.take_new(vec![a, c], now + BACKFILL_INTERVAL),
+            vec![a, c]
+        );
+    }
+}
@@ -0,0 +1,665 @@
+//! Route logic for re-running collection on existing records.
+//!
+
+use axum::extract::{Request, State};
+use axum::routing::post;
+use axum::{Router, extract};
+use axum_extra::extract::WithRejection;
+use chrono::Utc;
+use futures::{StreamExt, stream};
+use sea_orm::ActiveValue::{Set, Unchanged};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database::entities::sea_orm_active_enums::{EventType, TagRetryStatus};
+use crate::database::entities::{s3_object, s3_tag_retry};
+use crate::env::Config;
+use crate::error::Error::SQSError;
+use crate::error::Result;
+use crate::events::aws::collecter::{Collecter, MAX_COLLECT_CONCURRENCY};
+use crate::events::aws::message::RecollectMessage;
+use crate::queries::list::ListQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::header::HeaderParser;
+use crate::routes::list::{ListS3Params, WildcardParams};
+use crate::routes::pagination::{LimitParams, ListResponse, Pagination};
+use crate::routes::tenant::TenantScope;
+
+/// The maximum number of records that are enqueued for re-collection per call.
+pub const MAX_REQUEUE_LIMIT: u64 = 1000;
+
+/// The number of record ids that are sent in each re-collect message.
+pub const REQUEUE_BATCH_SIZE: usize = 100;
+
+/// The maximum number of failed tag writes that are retried per call.
+pub const MAX_TAG_RETRY_LIMIT: u64 = 100;
+
+/// The records that were enqueued for re-collection.
+#[derive(Debug, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RequeueResult {
+    /// The number of records that were enqueued.
+    n_records: usize,
+    /// The ids of the records that were enqueued.
+    s3_object_ids: Vec<Uuid>,
+}
+
+impl RequeueResult {
+    /// Create a new requeue result.
+    pub fn new(s3_object_ids: Vec<Uuid>) -> Self {
+        Self {
+            n_records: s3_object_ids.len(),
+            s3_object_ids,
+        }
+    }
+
+    /// Get the number of records.
+    pub fn n_records(&self) -> usize {
+        self.n_records
+    }
+
+    /// Get the s3_object_ids.
+    pub fn s3_object_ids(&self) -> &[Uuid] {
+        &self.s3_object_ids
+    }
+}
+
+/// The result of re-collecting a single record.
+#[derive(Debug, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectResult {
+    /// The id of the record that was collected.
+    s3_object_id: Uuid,
+    /// Whether the collection succeeded and the record was updated.
+    success: bool,
+    /// The reason that the collection failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl CollectResult {
+    /// Create a successful result.
+    pub fn success(s3_object_id: Uuid) -> Self {
+        Self {
+            s3_object_id,
+            success: true,
+            error: None,
+        }
+    }
+
+    /// Create a failed result.
+    pub fn failure(s3_object_id: Uuid, error: String) -> Self {
+        Self {
+            s3_object_id,
+            success: false,
+            error: Some(error),
+        }
+    }
+
+    /// Get the s3_object_id.
+    pub fn s3_object_id(&self) -> Uuid {
+        self.s3_object_id
+    }
+
+    /// Whether the collection succeeded.
+    pub fn is_success(&self) -> bool {
+        self.success
+    }
+
+    /// Get the error.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// The result of retrying failed tag writes.
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagRetryResult {
+    /// The ids of the tag writes that succeeded.
+    succeeded: Vec<Uuid>,
+    /// The ids of the tag writes that failed and remain pending.
+    failed: Vec<Uuid>,
+    /// The ids of the tag writes that failed too many times and were moved to the dead-letter
+    /// state.
+    dead_lettered: Vec<Uuid>,
+}
+
+impl TagRetryResult {
+    /// Get the ids of the tag writes that succeeded.
+    pub fn succeeded(&self) -> &[Uuid] {
+        &self.succeeded
+    }
+
+    /// Get the ids of the tag writes that failed and remain pending.
+    pub fn failed(&self) -> &[Uuid] {
+        &self.failed
+    }
+
+    /// Get the ids of the tag writes that were dead-lettered.
+    pub fn dead_lettered(&self) -> &[Uuid] {
+        &self.dead_lettered
+    }
+}
+
+/// Re-collect S3 metadata for the records matching the filter. This runs the same `HeadObject`
+/// and `GetObjectTagging` collection that occurs during ingestion, updating the size, ETag,
+/// sha256, storage class, last modified date, delete marker, archive status and ingest id of
+/// existing records in place. No new records are created, and no S3 tags are written, so objects
+/// without an ingest_id tag keep their existing ingest_id. Only `Created` events are collected.
+///
+/// Records are collected one page at a time, so use the `next` link to collect the remaining
+/// records. Records within a page are collected concurrently, and a failure on one record does
+/// not affect the others. The response contains the success or failure of each record.
+#[utoipa::path(
+    post,
+    path = "/s3/collect",
+    responses(
+        (
+            status = OK,
+            description = "The collection result for each matching record in the page",
+            body = ListResponse<CollectResult>
+        ),
+        ErrorStatusCode,
+    ),
+    params(Pagination, WildcardParams, ListS3Params, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "update",
+)]
+pub async fn collect_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(pagination), _): Query<Pagination>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+    request: Request,
+) -> Result<extract::Json<ListResponse<CollectResult>>> {
+    let connection = state.database_client().connection_ref();
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(connection).filter_all(
+        S3ObjectsFilter {
+            event_type: Some(EventType::Created),
+            ..filter_all
+        },
+        wildcard.case_sensitive(),
+        list.current_state(),
+    )?;
+
+    let url = if let Some(url) = state.config().api_links_url() {
+        url
+    } else {
+        &HeaderParser::parse_host_url(&request, state.use_tls_links())?
+    };
+    let url = url.join(&HeaderParser::get_uri_path(&request))?;
+
+    let count = response.cloned().count().await?;
+    let ListResponse {
+        links,
+        pagination,
+        results,
+    } = response
+        .paginate_to_list_response(pagination, url, count)
+        .await?;
+
+    let results = stream::iter(results)
+        .map(|record| async {
+            let id = record.s3_object_id;
+            Collecter::recollect(state.config(), state.s3_client(), connection, record)
+                .await
+                .map_or_else(
+                    |err| CollectResult::failure(id, err.to_string()),
+                    |_| CollectResult::success(id),
+                )
+        })
+        .buffered(MAX_COLLECT_CONCURRENCY)
+        .collect()
+        .await;
+
+    Ok(extract::Json(ListResponse::new(links, pagination, results)))
+}
+
+/// Re-queue records where collection failed by sending re-collect requests to the ingest queue.
+/// This finds current `Created` records that are missing a sha256 checksum, an ingest id, or
+/// metadata from `HeadObject`, and retries collection for them through the ingest pipeline.
+/// Additional filters can be used to restrict the records, e.g. by bucket or key. At most
+/// `limit` records are enqueued per call.
+#[utoipa::path(
+    post,
+    path = "/s3/collect/requeue",
+    responses(
+        (
+            status = OK,
+            description = "The records that were enqueued for re-collection",
+            body = RequeueResult
+        ),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, LimitParams<MAX_REQUEUE_LIMIT>, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "update",
+)]
+pub async fn requeue_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(requeue), _): Query<LimitParams<MAX_REQUEUE_LIMIT>>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<extract::Json<RequeueResult>> {
+    let url = Config::value_into_err(state.config().sqs_url())?;
+
+    let s3_object_ids = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), false)?
+    .filter_failed_collection()
+    .paginate(0, requeue.limit())
+    .await?
+    .all()
+    .await?
+    .into_iter()
+    .map(|record| record.s3_object_id)
+    .collect::<Vec<_>>();
+
+    for batch in s3_object_ids.chunks(REQUEUE_BATCH_SIZE) {
+        let body = serde_json::to_string(&RecollectMessage::new(batch.to_vec()))?;
+        state
+            .sqs_client()
+            .send_message(url, &body)
+            .await
+            .map_err(|err| SQSError(err.into_service_error().to_string()))?;
+    }
+
+    Ok(extract::Json(RequeueResult::new(s3_object_ids)))
+}
+
+/// Retry `ingest_id` tag writes that failed during ingestion. Failed tag writes are only recorded
+/// if `FILEMANAGER_INGESTER_TAG_RETRY` or `FILEMANAGER_INGESTER_AUTO_TAG` is enabled. Each pending tag write is retried, oldest
+/// first, and the records of the object are updated with the `ingest_id` if it succeeds. Tag
+/// writes which fail `FILEMANAGER_INGESTER_TAG_RETRY_ATTEMPTS` times are moved to the
+/// dead-letter state and are not retried again. At most `limit` tag writes are retried per call.
+#[utoipa::path(
+    post,
+    path = "/s3/collect/tags/retry",
+    responses(
+        (
+            status = OK,
+            description = "The outcome of retrying the failed tag writes",
+            body = TagRetryResult
+        ),
+        ErrorStatusCode,
+    ),
+    params(LimitParams<MAX_TAG_RETRY_LIMIT>),
+    context_path = "/api/v1",
+    tag = "update",
+)]
+pub async fn retry_tags_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(params), _): Query<LimitParams<MAX_TAG_RETRY_LIMIT>>,
+) -> Result<extract::Json<TagRetryResult>> {
+    let connection = state.database_client().connection_ref();
+    let retries = s3_tag_retry::Entity::find()
+        .filter(s3_tag_retry::Column::Status.eq(TagRetryStatus::Pending))
+        .apply_if(
+            TenantScope::bucket_condition(s3_tag_retry::Column::Bucket),
+            QueryFilter::filter,
+        )
+        .order_by_asc(s3_tag_retry::Column::Created)
+        .limit(params.limit())
+        .all(connection)
+        .await?;
+
+    let outcomes = stream::iter(retries)
+        .map(|retry| async {
+            let outcome = Collecter::retry_ingest_id_tag(
+                state.config(),
+                state.s3_client(),
+                state.database_client(),
+                &retry,
+            )
+            .await;
+            (retry, outcome)
+        })
+        .buffered(MAX_COLLECT_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let max_attempts = state.config().ingester_tag_retry_attempts();
+    let mut result = TagRetryResult::default();
+    for (retry, outcome) in outcomes {
+        let id = retry.s3_tag_retry_id;
+        let attempts = retry.attempts.saturating_add(1);
+        let mut model = s3_tag_retry::ActiveModel {
+            s3_tag_retry_id: Unchanged(id),
+            attempts: Set(attempts),
+            last_attempted: Set(Some(Utc::now().into())),
+            ..Default::default()
+        };
+
+        match outcome {
+            Ok(_) => {
+                model.status = Set(TagRetryStatus::Succeeded);
+                result.succeeded.push(id);
+            }
+            Err(err) => {
+                model.last_error = Set(Some(err.to_string()));
+                if u32::try_from(attempts).unwrap_or_default() >= max_attempts {
+                    model.status = Set(TagRetryStatus::DeadLetter);
+                    result.dead_lettered.push(id);
+                } else {
+                    result.failed.push(id);
+                }
+            }
+        }
+
+        model.update(connection).await?;
+    }
+
+    Ok(extract::Json(result))
+}
+
+/// The router for collecting objects.
+pub fn collect_router() -> Router<AppState> {
+    Router::new()
+        .route("/s3/collect", post(collect_s3))
+        .route("/s3/collect/requeue", post(requeue_s3))
+        .route("/s3/collect/tags/retry", post(retry_tags_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+    use aws_sdk_s3::operation::put_object_tagging::{
+        PutObjectTaggingError, PutObjectTaggingOutput,
+    };
+    use aws_sdk_s3::types;
+    use aws_sdk_sqs::operation::send_message::SendMessageOutput;
+    use aws_smithy_mocks::mock;
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, PaginatorTrait};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::sea_orm_active_enums::{ArchiveStatus, StorageClass};
+    use crate::events::aws::collecter::tests::{
+        expected_get_object_tagging, expected_head_object_status, mock_s3, mock_sqs,
+    };
+    use crate::queries::EntriesBuilder;
+    use crate::routes::list::tests::response_from;
+    use crate::uuid::UuidGenerator;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn collect_s3_api(pool: PgPool) {
+        let ingest_id = Uuid::default();
+        let put_tagging = mock!(aws_sdk_s3::Client::put_object_tagging)
+            .then_output(|| PutObjectTaggingOutput::builder().build());
+        let client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::head_object)
+                .match_requests(|req| req.key() == Some("4"))
+                .then_http_response(|| expected_head_object_status(404)),
+            mock!(aws_sdk_s3::Client::head_object)
+                .match_requests(|req| req.key() != Some("4"))
+                .then_output(|| {
+                    HeadObjectOutput::builder()
+                        .storage_class(types::StorageClass::GlacierIr)
+                        .archive_status(types::ArchiveStatus::ArchiveAccess)
+                        .content_length(5)
+                        .checksum_sha256("sha256")
+                        .build()
+                }),
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .match_requests(|req| req.key() == Some("2"))
+                .then_output(|| expected_get_object_tagging(None)),
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .match_requests(|req| req.key() != Some("2"))
+                .then_output(move || expected_get_object_tagging(Some(ingest_id))),
+            put_tagging.clone(),
+        ]);
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(client);
+
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let (status, first) = response_from::<ListResponse<CollectResult>>(
+            state.clone(),
+            "/s3/collect?currentState=false&key[]=0&key[]=1&key[]=2&key[]=4&rowsPerPage=2",
+            Method::POST,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(first.results().len(), 2);
+
+        let (status, second) = response_from::<ListResponse<CollectResult>>(
+            state.clone(),
+            "/s3/collect?currentState=false&key[]=0&key[]=1&key[]=2&key[]=4&rowsPerPage=2&page=2",
+            Method::POST,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(second.results().len(), 1);
+
+        // Only created events are collected, one page at a time.
+        let results = first
+            .results()
+            .iter()
+            .chain(second.results())
+            .collect::<Vec<_>>();
+        let collected = [0, 2, 4].map(|i| entries.s3_objects[i].s3_object_id);
+        assert_eq!(
+            results.iter().map(|r| r.s3_object_id()).collect::<Vec<_>>(),
+            collected
+        );
+        assert!(results[0].is_success());
+        assert!(results[1].is_success());
+        assert!(!results[2].is_success());
+        assert!(results[2].error().is_some());
+
+        let connection = state.database_client().connection_ref();
+        for id in &collected[..2] {
+            let record = s3_object::Entity::find_by_id(*id)
+                .one(connection)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(record.storage_class, Some(StorageClass::GlacierIr));
+            assert_eq!(record.archive_status, Some(ArchiveStatus::ArchiveAccess));
+            assert_eq!(record.size, Some(5));
+            assert_eq!(record.sha256, Some("sha256".to_string()));
+        }
+
+        // Tags are read but never written, so an object without an ingest_id tag keeps the
+        // existing ingest_id.
+        let untagged = s3_object::Entity::find_by_id(collected[1])
+            .one(connection)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(untagged.ingest_id, entries.s3_objects[2].ingest_id);
+        assert_eq!(untagged.tag_present, Some(false));
+        assert_eq!(put_tagging.num_calls(), 0);
+
+        let tagged = s3_object::Entity::find_by_id(collected[0])
+            .one(connection)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tagged.ingest_id, Some(ingest_id));
+
+        // The failed record is unchanged, and no new records are created.
+        let failed = s3_object::Entity::find_by_id(collected[2])
+            .one(connection)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.sha256, entries.s3_objects[4].sha256);
+        assert_eq!(failed.storage_class, entries.s3_objects[4].storage_class);
+        assert_eq!(failed.ingest_id, entries.s3_objects[4].ingest_id);
+        assert_eq!(
+            s3_object::Entity::find().count(connection).await.unwrap(),
+            entries.s3_objects.len() as u64
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn retry_tags_s3_api(pool: PgPool) {
+        let client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::get_object_tagging)
+                .then_output(|| expected_get_object_tagging(None)),
+            mock!(aws_sdk_s3::Client::put_object_tagging)
+                .match_requests(|req| req.key() == Some("0"))
+                .then_output(|| PutObjectTaggingOutput::builder().build()),
+            mock!(aws_sdk_s3::Client::put_object_tagging)
+                .match_requests(|req| req.key() != Some("0"))
+                .then_error(|| PutObjectTaggingError::unhandled("unhandled")),
+        ]);
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(client)
+            .with_config(Config {
+                ingester_tag_retry_attempts: 1,
+                ..Default::default()
+            });
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let connection = state.database_client().connection_ref();
+        let mut model: s3_object::ActiveModel = entries.s3_objects[0].clone().into_active_model();
+        model.ingest_id = Set(None);
+        model.update(connection).await.unwrap();
+
+        let retry = |key: &str| s3_tag_retry::ActiveModel {
+            s3_tag_retry_id: Set(UuidGenerator::generate()),
+            bucket: Set(entries.s3_objects[0].bucket.to_string()),
+            key: Set(key.to_string()),
+            version_id: Set(entries.s3_objects[0].version_id.to_string()),
+            ingest_id: Set(UuidGenerator::generate()),
+            ..Default::default()
+        };
+        let succeeded = retry("0").insert(connection).await.unwrap();
+        let dead_lettered = retry("missing").insert(connection).await.unwrap();
+
+        let (status, result) = response_from::<TagRetryResult>(
+            state.clone(),
+            "/s3/collect/tags/retry",
+            Method::POST,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.succeeded(), [succeeded.s3_tag_retry_id]);
+        assert_eq!(result.dead_lettered(), [dead_lettered.s3_tag_retry_id]);
+        assert!(result.failed().is_empty());
+
+        let record = s3_object::Entity::find_by_id(entries.s3_objects[0].s3_object_id)
+            .one(connection)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.ingest_id, Some(succeeded.ingest_id));
+
+        let retries = s3_tag_retry::Entity::find()
+            .order_by_asc(s3_tag_retry::Column::Created)
+            .all(connection)
+            .await
+            .unwrap();
+        assert_eq!(retries[0].status, TagRetryStatus::Succeeded);
+        assert_eq!(retries[1].status, TagRetryStatus::DeadLetter);
+        assert_eq!(retries[1].attempts, 1);
+        assert!(retries[1].last_error.is_some());
+
+        // Nothing is pending, so nothing is retried again.
+        let (_, result) = response_from::<TagRetryResult>(
+            state.clone(),
+            "/s3/collect/tags/retry",
+            Method::POST,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(result, TagRetryResult::default());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn requeue_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                sqs_url: Some("url".to_string()),
+                ..Default::default()
+            });
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let connection = state.database_client().connection_ref();
+        for i in [0, 1, 2, 4] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            match i {
+                // Entry 1 is not current, so it should not be enqueued.
+                0 | 1 => model.sha256 = Set(None),
+                2 => model.ingest_id = Set(None),
+                _ => model.storage_class = Set(None),
+            }
+            model.update(connection).await.unwrap();
+        }
+
+        let expected = [0, 2, 4].map(|i| entries.s3_objects[i].s3_object_id);
+        let body = serde_json::to_string(&RecollectMessage::new(expected.to_vec())).unwrap();
+        let rule = mock!(aws_sdk_sqs::Client::send_message)
+            .match_requests(move |req| {
+                req.queue_url() == Some("url") && req.message_body() == Some(body.as_str())
+            })
+            .then_output(|| SendMessageOutput::builder().build());
+        let state = state.with_sqs_client(mock_sqs(slice::from_ref(&rule)));
+
+        let (status, result) = response_from::<RequeueResult>(
+            state.clone(),
+            "/s3/collect/requeue",
+            Method::POST,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.n_records(), 3);
+        assert_eq!(result.s3_object_ids(), expected);
+        assert_eq!(rule.num_calls(), 1);
+
+        // The number of records is limited.
+        let body = serde_json::to_string(&RecollectMessage::new(expected[..2].to_vec())).unwrap();
+        let rule = mock!(aws_sdk_sqs::Client::send_message)
+            .match_requests(move |req| req.message_body() == Some(body.as_str()))
+            .then_output(|| SendMessageOutput::builder().build());
+        let state = state.with_sqs_client(mock_sqs(slice::from_ref(&rule)));
+
+        let (status, result) = response_from::<RequeueResult>(
+            state,
+            "/s3/collect/requeue?limit=2",
+            Method::POST,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.s3_object_ids(), &expected[..2]);
+        assert_eq!(rule.num_calls(), 1);
+    }
+}
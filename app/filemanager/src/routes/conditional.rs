@@ -0,0 +1,154 @@
+//! Functions related to conditional requests on list results using `ETag` and `Last-Modified`.
+//!
+
+use axum::http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use axum::http::{HeaderMap, HeaderValue};
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::DateTimeWithTimeZone;
+
+use crate::error::Error::ParseError;
+use crate::error::Result;
+use crate::routes::header::HeaderParser;
+
+/// The version of a set of list results, which is used to determine whether the results have
+/// changed between requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListVersion {
+    n_records: u64,
+    last_updated: Option<DateTimeWithTimeZone>,
+    checksum: String,
+    last_modified: Option<DateTimeWithTimeZone>,
+}
+
+impl ListVersion {
+    /// Create a new list version from the number of matching records, the time any matching
+    /// record was last updated, a checksum over the update times of all matching records, and the
+    /// time that any record was last updated or removed.
+    pub fn new(
+        n_records: u64,
+        last_updated: Option<DateTimeWithTimeZone>,
+        checksum: String,
+        last_modified: Option<DateTimeWithTimeZone>,
+    ) -> Self {
+        Self {
+            n_records,
+            last_updated,
+            checksum,
+            last_modified,
+        }
+    }
+
+    /// Get the number of records.
+    pub fn n_records(&self) -> u64 {
+        self.n_records
+    }
+
+    /// Get the time any matching record was last updated.
+    pub fn last_updated(&self) -> Option<DateTimeWithTimeZone> {
+        self.last_updated
+    }
+
+    /// Get the entity tag representing this version. This changes when any record is added,
+    /// removed or updated.
+    pub fn e_tag(&self) -> String {
+        let last_updated = self
+            .last_updated
+            .map(|last_updated| last_updated.timestamp_micros())
+            .unwrap_or_default();
+
+        format!("\"{}-{}-{}\"", self.n_records, last_updated, self.checksum)
+    }
+
+    /// Get the `Last-Modified` HTTP date, which has a precision of seconds. This is the time that
+    /// any record was last updated or removed rather than only the matching records, because
+    /// records which no longer match have no `updated_at` in the results. It changes more often
+    /// than the `ETag`, but never misses a change.
+    pub fn last_modified(&self) -> Option<String> {
+        self.last_modified.map(|last_modified| {
+            last_modified
+                .with_timezone(&Utc)
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string()
+        })
+    }
+
+    /// Get the `ETag` and `Last-Modified` headers for this version.
+    pub fn headers(&self) -> Result<HeaderMap> {
+        let to_value = |value: String| {
+            HeaderValue::from_str(&value).map_err(|err| ParseError(err.to_string()))
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, to_value(self.e_tag())?);
+        if let Some(last_modified) = self.last_modified() {
+            headers.insert(LAST_MODIFIED, to_value(last_modified)?);
+        }
+
+        Ok(headers)
+    }
+
+    /// Whether the request headers indicate that the client already has this version, in which
+    /// case a `304 Not Modified` should be returned. `If-None-Match` takes precedence over
+    /// `If-Modified-Since`, and headers which cannot be parsed are ignored.
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        let parser = HeaderParser::new(headers);
+
+        if let Ok(Some(if_none_match)) = parser.parse_header(IF_NONE_MATCH) {
+            let e_tag = self.e_tag();
+            return if_none_match.split(',').any(|candidate| {
+                let candidate = candidate.trim();
+                candidate == "*" || candidate.trim_start_matches("W/") == e_tag
+            });
+        }
+
+        match (parser.parse_header(IF_MODIFIED_SINCE), self.last_modified) {
+            (Ok(Some(if_modified_since)), Some(last_modified)) => {
+                DateTime::parse_from_rfc2822(&if_modified_since)
+                    .is_ok_and(|since| last_modified.timestamp() <= since.timestamp())
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_not_modified() {
+        let version = ListVersion::new(
+            2,
+            Some("2026-01-02T03:04:05.678+00:00".parse().unwrap()),
+            "123".to_string(),
+            Some("2026-01-02T03:04:05.678+00:00".parse().unwrap()),
+        );
+        assert_eq!(version.e_tag(), "\"2-1767323045678000-123\"");
+        assert_eq!(
+            version.last_modified().unwrap(),
+            "Fri, 02 Jan 2026 03:04:05 GMT"
+        );
+
+        let headers = |name, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+
+        assert!(version.is_not_modified(&headers(IF_NONE_MATCH, &version.e_tag())));
+        assert!(version.is_not_modified(&headers(
+            IF_NONE_MATCH,
+            &format!("\"other\", W/{}", version.e_tag())
+        )));
+        assert!(!version.is_not_modified(&headers(IF_NONE_MATCH, "\"other\"")));
+
+        assert!(
+            version.is_not_modified(&headers(IF_MODIFIED_SINCE, "Fri, 02 Jan 2026 03:04:05 GMT"))
+        );
+        assert!(
+            !version.is_not_modified(&headers(IF_MODIFIED_SINCE, "Fri, 02 Jan 2026 03:04:04 GMT"))
+        );
+        assert!(!version.is_not_modified(&headers(IF_MODIFIED_SINCE, "invalid")));
+        assert!(!version.is_not_modified(&HeaderMap::new()));
+    }
+}
@@ -1,16 +1,22 @@
 //! Adds a route to fetch all records from S3 using list operations and update the database.
 //!
 
+use crate::database;
 use crate::database::Ingest;
+use crate::database::aws::ingester::Ingester;
 use crate::database::entities::s3_crawl;
 use crate::database::entities::s3_crawl::Model as Crawl;
+use crate::database::entities::s3_crawl_schedule;
+use crate::database::entities::s3_crawl_schedule::Model as CrawlSchedule;
 use crate::database::entities::sea_orm_active_enums::CrawlStatus;
 use crate::database::entities::sea_orm_active_enums::CrawlStatus::InProgress;
-use crate::error::Error::{CrawlError, ExpectedSomeValue};
+use crate::error::Error::{CrawlCancelled, CrawlError, ExpectedSomeValue, InvalidQuery};
 use crate::error::{Error, Result};
 use crate::events::Collect;
+use crate::events::aws::FlatS3EventMessages;
 use crate::events::aws::collecter::CollecterBuilder;
 use crate::events::aws::crawl;
+use crate::events::aws::crawl::{CrawlKeyRange, CrawlScope};
 use crate::queries::get::GetQueryBuilder;
 use crate::queries::list::ListQueryBuilder;
 use crate::routes::AppState;
@@ -21,6 +27,7 @@ use crate::routes::filter::wildcard::Wildcard;
 use crate::routes::header::HeaderParser;
 use crate::routes::list::{ListCount, ListS3Params, WildcardParams};
 use crate::routes::pagination::{ListResponse, Pagination};
+use crate::routes::tenant::TenantScope;
 use crate::uuid::UuidGenerator;
 use axum::extract::{Request, State};
 use axum::response::NoContent;
@@ -29,9 +36,15 @@ use axum::{Router, extract};
 use axum_extra::extract::WithRejection;
 use chrono::{TimeDelta, Utc};
 use sea_orm::ActiveValue::Set;
-use sea_orm::{ActiveModelTrait, ConnectionTrait, EntityTrait, IntoActiveModel, TransactionTrait};
+use sea_orm::prelude::Expr;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter,
+    QueryTrait, TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
+use tracing::{debug, warn};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
@@ -49,12 +62,39 @@ pub struct CrawlRequest {
     /// Specify the prefix to crawl from. By default, crawls all files in the bucket.
     #[param(nullable = true, required = false)]
     prefix: Option<String>,
+    /// Only update existing records if the `lastModifiedDate` of the S3 object is newer than the
+    /// record in the database. This avoids overwriting records from more recent events with stale
+    /// listing data. New objects are always added. By default, all existing records are updated.
+    #[param(nullable = false, required = false, default = false)]
+    only_newer: bool,
+    /// Do not delete records that are in the database but missing from the crawl. This is useful
+    /// when crawling an incomplete scope, where a missing object does not imply that it was
+    /// deleted. By default, missing records are deleted.
+    #[param(nullable = false, required = false, default = false)]
+    skip_deletes: bool,
 }
 
 impl CrawlRequest {
     /// Create crawl params.
     pub fn new(bucket: String, prefix: Option<String>) -> Self {
-        Self { bucket, prefix }
+        Self {
+            bucket,
+            prefix,
+            only_newer: false,
+            skip_deletes: false,
+        }
+    }
+
+    /// Only update existing records if the S3 object is newer.
+    pub fn with_only_newer(mut self, only_newer: bool) -> Self {
+        self.only_newer = only_newer;
+        self
+    }
+
+    /// Do not delete records that are missing from the crawl.
+    pub fn with_skip_deletes(mut self, skip_deletes: bool) -> Self {
+        self.skip_deletes = skip_deletes;
+        self
     }
 
     /// Get the bucket.
@@ -66,11 +106,81 @@ impl CrawlRequest {
     pub fn prefix(&self) -> Option<&str> {
         self.prefix.as_deref()
     }
+
+    /// Whether only newer S3 objects should update existing records.
+    pub fn only_newer(&self) -> bool {
+        self.only_newer
+    }
+
+    /// Whether records missing from the crawl should be left untouched.
+    pub fn skip_deletes(&self) -> bool {
+        self.skip_deletes
+    }
+}
+
+/// Request for creating or updating a crawl schedule.
+#[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CrawlScheduleRequest {
+    /// The bucket to crawl.
+    bucket: String,
+    /// The prefix to crawl from. By default, the schedule applies to the whole bucket.
+    prefix: Option<String>,
+    /// How often the bucket and prefix should be crawled in seconds.
+    #[schema(minimum = 1)]
+    interval_seconds: i64,
+}
+
+impl CrawlScheduleRequest {
+    /// Create a crawl schedule request.
+    pub fn new(bucket: String, prefix: Option<String>, interval_seconds: i64) -> Self {
+        Self {
+            bucket,
+            prefix,
+            interval_seconds,
+        }
+    }
+
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Get the prefix.
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Get the interval in seconds.
+    pub fn interval_seconds(&self) -> i64 {
+        self.interval_seconds
+    }
+}
+
+/// The result of normalizing null sequencers.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizeSequencersResult {
+    /// The number of records which had a sequencer generated.
+    n_records: u64,
+}
+
+impl NormalizeSequencersResult {
+    /// Create a new normalize sequencers result.
+    pub fn new(n_records: u64) -> Self {
+        Self { n_records }
+    }
+
+    /// Get the number of records.
+    pub fn n_records(&self) -> u64 {
+        self.n_records
+    }
 }
 
 /// Crawl S3, updating existing records and adding new ones into the database based on `ListObjects`.
 /// Only one crawl can be run at a time for a specific bucket. The crawl is atomic, so if it fails,
-/// no new records will be ingested.
+/// no new records will be ingested, unless `FILEMANAGER_CRAWL_FLUSH_THRESHOLD` is set, in which
+/// case the crawl is ingested in chunks while listing.
 ///
 /// This crawl is asynchronous and will return immediately but continue processing in the background.
 /// To query the status of asynchronous crawls, use `/api/v1/s3/crawl/status`. Alternatively use
@@ -97,9 +207,9 @@ pub async fn crawl_s3(
 
     // The reference to this task is effectively lost to external callers of the API, however
     // it can be retrieved from the state internally.
-    let handle = tokio::spawn(async move {
+    let handle = tokio::spawn(TenantScope::propagate(async move {
         crawl_sync_s3(state_copy, WithRejection(extract::Json(crawl), PhantomData)).await
-    });
+    }));
 
     // let mut task = state.crawl_task.lock().await;
     // *task = Some(handle);
@@ -111,7 +221,8 @@ pub async fn crawl_s3(
 
 /// Crawl S3, updating existing records and adding new ones into the database based on `ListObjects`.
 /// Only one crawl can be run at a time for a specific bucket. The crawl is atomic, so if it fails,
-/// no new records will be ingested.
+/// no new records will be ingested, unless `FILEMANAGER_CRAWL_FLUSH_THRESHOLD` is set, in which
+/// case the crawl is ingested in chunks while listing.
 ///
 /// This crawl is synchronous and will wait until the crawl is complete before returning a response.
 /// If the crawl exceeds the timeout of the API, use `/api/v1/s3/crawl` instead.
@@ -130,8 +241,11 @@ pub async fn crawl_sync_s3(
     state: State<AppState>,
     WithRejection(extract::Json(crawl), _): Json<CrawlRequest>,
 ) -> Result<extract::Json<Crawl>> {
+    TenantScope::check_bucket(&crawl.bucket)?;
+
     let conn = state.database_client().connection_ref().begin().await?;
 
+    fail_stale_crawls(&conn).await?;
     let in_progress = ListQueryBuilder::<_, s3_crawl::Entity>::new(&conn)
         .filter_all(
             S3CrawlFilter {
@@ -145,22 +259,15 @@ pub async fn crawl_sync_s3(
         .await?;
 
     // If there is a crawl in progress already, then this is an error.
-    if let Some(in_progress) = in_progress {
-        // Just in case the crawl has been running too long, fail it here.
-        let diff = Utc::now().signed_duration_since(in_progress.started);
-        if diff < TimeDelta::zero() || diff > TimeDelta::minutes(MAX_CRAWL_TIME_MINUTES) {
-            let mut to_update = in_progress.into_active_model();
-            to_update.status = Set(CrawlStatus::Failed);
-            to_update.update(&conn).await?;
-        } else {
-            return Err(CrawlError(format!(
-                "another crawl on {} is already in progress",
-                crawl.bucket
-            )));
-        }
+    if in_progress.is_some() {
+        return Err(CrawlError(format!(
+            "another crawl on {} is already in progress",
+            crawl.bucket
+        )));
     }
 
-    // New crawl can be started.
+    // New crawl can be started. The crawl entry is committed so that it is visible while the
+    // crawl is in progress, and so that it can be cancelled.
     let uuid = UuidGenerator::generate();
     let mut crawl_execution = s3_crawl::ActiveModel {
         s3_crawl_id: Set(uuid),
@@ -170,56 +277,104 @@ pub async fn crawl_sync_s3(
         ..Default::default()
     };
     crawl_execution.clone().insert(&conn).await?;
+    conn.commit().await?;
 
     let now = Utc::now();
+    let execution_time_seconds = || {
+        i32::try_from(now.signed_duration_since(Utc::now()).abs().num_seconds())
+            .map(Some)
+            .map(Set)
+    };
     let set_failed = |mut to_update: s3_crawl::ActiveModel| async {
         to_update.status = Set(CrawlStatus::Failed);
-        Ok::<_, Error>(to_update.update(&conn).await?)
+        Ok::<_, Error>(
+            to_update
+                .update(state.database_client().connection_ref())
+                .await?,
+        )
     };
 
     // Get crawl list object details ensuring that the current database state is taken into account.
-    let crawl_result = crawl::Crawl::new(state.s3_client().clone())
-        .crawl_s3(&crawl.bucket, crawl.prefix.clone())
-        .await;
-
-    if let Err(err) = crawl_result {
-        set_failed(crawl_execution).await?;
-        return Err(err);
-    }
+    // The crawl stops once it reaches the maximum crawl time, so that it does not keep running
+    // after it is considered stale and another crawl of the bucket can start.
+    let database_client = state.database_client().clone();
+    let crawler = crawl::Crawl::new(state.s3_client().clone())
+        .with_time_budget(TimeDelta::minutes(MAX_CRAWL_TIME_MINUTES))
+        .with_cancel_check(move || is_cancel_requested(database_client.clone(), uuid))
+        .with_progress(move |progress| {
+            debug!(
+                crawl_id = %uuid,
+                objects = progress.objects,
+                pages = progress.pages,
+                next_key_marker = ?progress.next_key_marker,
+                "crawl progress"
+            )
+        });
+    let scope = crawler.scope().clone();
+    let n_events = match state.config().crawl_flush_threshold() {
+        // Ingest in chunks while listing to bound the number of messages held in memory.
+        Some(threshold) => {
+            crawler
+                .crawl_s3_chunked(
+                    &crawl.bucket,
+                    crawl.prefix.clone(),
+                    threshold,
+                    |messages, key_range| ingest_crawl(&state, &crawl, messages, key_range, &scope),
+                )
+                .await
+        }
+        None => match crawler.crawl_s3(&crawl.bucket, crawl.prefix.clone()).await {
+            Ok(messages) => {
+                let n_events = messages.0.len();
+                ingest_crawl(&state, &crawl, messages, CrawlKeyRange::default(), &scope)
+                    .await
+                    .map(|_| n_events)
+            }
+            Err(err) => Err(err),
+        },
+    };
 
-    let crawl_result = crawl_result?;
-    let n_events = i64::try_from(crawl_result.0.len())?;
+    let n_events = match n_events {
+        Ok(n_events) => i64::try_from(n_events)?,
+        Err(CrawlCancelled(key_marker)) => {
+            crawl_execution.status = Set(CrawlStatus::Cancelled);
+            crawl_execution.execution_time_seconds = execution_time_seconds()?;
+            crawl_execution.key_marker = Set(key_marker);
 
-    // Update events.
-    let events = CollecterBuilder::default()
-        .with_crawl_bucket(crawl.bucket)
-        .with_crawl_prefix(crawl.prefix)
-        .with_s3_client(state.s3_client().clone())
-        .build(crawl_result, state.config(), state.database_client())
-        .await
-        .collect()
-        .await;
-    if let Err(err) = events {
-        set_failed(crawl_execution).await?;
-        return Err(err);
-    }
-
-    let events = events?.into_inner().0;
+            return Ok(extract::Json(
+                crawl_execution
+                    .update(state.database_client().connection_ref())
+                    .await?,
+            ));
+        }
+        Err(err) => {
+            set_failed(crawl_execution).await?;
+            return Err(err);
+        }
+    };
 
-    // Ingest events.
-    if let Err(err) = state.database_client().ingest(events).await {
-        set_failed(crawl_execution).await?;
-        return Err(err);
-    }
+    let conn = state.database_client().connection_ref().begin().await?;
 
     // Update crawl entry.
     crawl_execution.status = Set(CrawlStatus::Completed);
-    crawl_execution.execution_time_seconds = Set(Some(i32::try_from(
-        now.signed_duration_since(Utc::now()).abs().num_seconds(),
-    )?));
+    crawl_execution.execution_time_seconds = execution_time_seconds()?;
     crawl_execution.n_objects = Set(Some(n_events));
     crawl_execution.clone().update(&conn).await?;
 
+    // Record the completion on the schedule for this bucket and prefix, if there is one.
+    s3_crawl_schedule::Entity::update_many()
+        .col_expr(
+            s3_crawl_schedule::Column::LastCompleted,
+            Expr::value(Utc::now()),
+        )
+        .filter(s3_crawl_schedule::Column::Bucket.eq(&crawl.bucket))
+        .filter(match &crawl.prefix {
+            Some(prefix) => s3_crawl_schedule::Column::Prefix.eq(prefix),
+            None => s3_crawl_schedule::Column::Prefix.is_null(),
+        })
+        .exec(&conn)
+        .await?;
+
     let entry = s3_crawl::Entity::find_by_id(uuid)
         .one(&conn)
         .await?
@@ -229,6 +384,69 @@ pub async fn crawl_sync_s3(
     Ok(extract::Json(entry))
 }
 
+/// Fail crawls which are still in progress after the maximum crawl time. A crawl stops once it
+/// reaches the maximum crawl time, so these crawls did not finish, e.g. because the process
+/// running them crashed or timed out. Only the crawls of the tenant of the current request are
+/// failed.
+async fn fail_stale_crawls<C: ConnectionTrait>(conn: &C) -> Result<()> {
+    let now = Utc::now();
+    s3_crawl::Entity::update_many()
+        .col_expr(s3_crawl::Column::Status, CrawlStatus::Failed.as_enum())
+        .filter(s3_crawl::Column::Status.eq(InProgress))
+        .apply_if(
+            TenantScope::bucket_condition(s3_crawl::Column::Bucket),
+            QueryFilter::filter,
+        )
+        .filter(
+            s3_crawl::Column::Started
+                .lt(now - TimeDelta::minutes(MAX_CRAWL_TIME_MINUTES))
+                .or(s3_crawl::Column::Started.gt(now)),
+        )
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether a cancellation was requested for the crawl. The request is stored with the crawl so
+/// that it can be made from any instance of the API. Errors are logged and do not cancel the
+/// crawl.
+async fn is_cancel_requested(database_client: database::Client, id: Uuid) -> bool {
+    s3_crawl::Entity::find_by_id(id)
+        .filter(s3_crawl::Column::CancelRequested.eq(true))
+        .one(database_client.connection_ref())
+        .await
+        .inspect_err(|err| warn!("failed to check for cancellation of crawl {id}: {err}"))
+        .is_ok_and(|crawl| crawl.is_some())
+}
+
+/// Update crawl messages against the database state for keys in the range and the scope of the
+/// crawl, and ingest them.
+async fn ingest_crawl(
+    state: &AppState,
+    crawl: &CrawlRequest,
+    messages: FlatS3EventMessages,
+    key_range: CrawlKeyRange,
+    scope: &CrawlScope,
+) -> Result<()> {
+    let events = CollecterBuilder::default()
+        .with_crawl_bucket(crawl.bucket.clone())
+        .with_crawl_prefix(crawl.prefix.clone())
+        .with_crawl_only_newer(crawl.only_newer)
+        .with_crawl_skip_deletes(crawl.skip_deletes)
+        .with_crawl_key_range(key_range)
+        .with_crawl_scope(scope.clone())
+        .with_s3_client(state.s3_client().clone())
+        .build(messages, state.config(), state.database_client())
+        .await
+        .collect()
+        .await?
+        .into_inner()
+        .0;
+
+    state.database_client().ingest(events).await
+}
+
 /// Get the in-progress or previous crawl executions.
 #[utoipa::path(
     get,
@@ -248,7 +466,11 @@ pub async fn list_crawl_s3(
     WithRejection(serde_qs::axum::QsQuery(filter), _): QsQuery<S3CrawlFilter>,
     request: Request,
 ) -> Result<extract::Json<ListResponse<Crawl>>> {
-    let txn = state.database_client().connection_ref().begin().await?;
+    let txn = state
+        .database_client()
+        .read_connection_ref()
+        .begin()
+        .await?;
 
     let response = ListQueryBuilder::<_, s3_crawl::Entity>::new(&txn)
         .filter_all(filter.clone(), wildcard.case_sensitive())?;
@@ -293,7 +515,12 @@ pub async fn count_crawl_s3(
     wildcard: Query<WildcardParams>,
     filter: QsQuery<S3CrawlFilter>,
 ) -> Result<extract::Json<ListCount>> {
-    count_crawl_with_connection(state.database_client().connection_ref(), wildcard, filter).await
+    count_crawl_with_connection(
+        state.database_client().read_connection_ref(),
+        wildcard,
+        filter,
+    )
+    .await
 }
 
 async fn count_crawl_with_connection<C: ConnectionTrait>(
@@ -322,7 +549,7 @@ pub async fn get_crawl_s3_by_id(
     state: State<AppState>,
     WithRejection(extract::Path(id), _): Path<Uuid>,
 ) -> Result<extract::Json<Crawl>> {
-    let query = GetQueryBuilder::new(state.database_client().connection_ref());
+    let query = GetQueryBuilder::new(state.database_client().read_connection_ref());
 
     Ok(extract::Json(
         query
@@ -332,6 +559,167 @@ pub async fn get_crawl_s3_by_id(
     ))
 }
 
+/// Cancel a crawl which is in progress. The cancellation is recorded with the crawl, which stops
+/// before listing the next page of objects, and is recorded with a `Cancelled` status and the key
+/// marker that listing reached. No records are ingested from a cancelled crawl, unless
+/// `FILEMANAGER_CRAWL_FLUSH_THRESHOLD` is set, in which case the chunks that were already ingested
+/// are kept. Crawls which are still in progress after the maximum crawl time are failed rather
+/// than cancelled, as they are no longer running. Crawls of buckets outside the tenant of the
+/// request are not found.
+#[utoipa::path(
+    post,
+    path = "/s3/crawl/{id}/cancel",
+    responses(
+        (status = NO_CONTENT, description = "The crawl was signalled to cancel"),
+        ErrorStatusCode,
+    ),
+    context_path = "/api/v1",
+    tag = "crawl",
+)]
+pub async fn cancel_crawl_s3(
+    state: State<AppState>,
+    WithRejection(extract::Path(id), _): Path<Uuid>,
+) -> Result<NoContent> {
+    let conn = state.database_client().connection_ref().begin().await?;
+
+    fail_stale_crawls(&conn).await?;
+    let result = s3_crawl::Entity::update_many()
+        .col_expr(s3_crawl::Column::CancelRequested, Expr::value(true))
+        .filter(s3_crawl::Column::S3CrawlId.eq(id))
+        .filter(s3_crawl::Column::Status.eq(InProgress))
+        .apply_if(
+            TenantScope::bucket_condition(s3_crawl::Column::Bucket),
+            QueryFilter::filter,
+        )
+        .exec(&conn)
+        .await?;
+
+    conn.commit().await?;
+
+    if result.rows_affected == 0 {
+        return Err(ExpectedSomeValue(id));
+    }
+
+    Ok(NoContent)
+}
+
+/// Create or update the crawl schedule for a bucket and prefix. The schedule is used to determine
+/// when a crawl is due using `/api/v1/s3/crawl/schedule/due`. Completed crawls update the last
+/// completed time of the schedule with the same bucket and prefix.
+#[utoipa::path(
+    post,
+    path = "/s3/crawl/schedule",
+    responses(
+        (status = OK, description = "The created or updated crawl schedule", body = CrawlSchedule),
+        ErrorStatusCode,
+    ),
+    request_body = CrawlScheduleRequest,
+    context_path = "/api/v1",
+    tag = "crawl",
+)]
+pub async fn schedule_crawl_s3(
+    state: State<AppState>,
+    WithRejection(extract::Json(schedule), _): Json<CrawlScheduleRequest>,
+) -> Result<extract::Json<CrawlSchedule>> {
+    if schedule.interval_seconds <= 0 {
+        return Err(InvalidQuery(
+            "the crawl schedule interval must be positive".to_string(),
+        ));
+    }
+    TenantScope::check_bucket(&schedule.bucket)?;
+
+    let model = s3_crawl_schedule::Entity::insert(s3_crawl_schedule::ActiveModel {
+        s3_crawl_schedule_id: Set(UuidGenerator::generate()),
+        bucket: Set(schedule.bucket),
+        prefix: Set(schedule.prefix),
+        interval_seconds: Set(schedule.interval_seconds),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([
+            s3_crawl_schedule::Column::Bucket,
+            s3_crawl_schedule::Column::Prefix,
+        ])
+        .update_column(s3_crawl_schedule::Column::IntervalSeconds)
+        .to_owned(),
+    )
+    .exec_with_returning(state.database_client().connection_ref())
+    .await?;
+
+    Ok(extract::Json(model))
+}
+
+/// Get the crawl schedules which are due. A crawl is due if it has never completed, or if the
+/// last completed crawl was longer than the interval ago.
+#[utoipa::path(
+    get,
+    path = "/s3/crawl/schedule/due",
+    responses(
+        (status = OK, description = "The crawl schedules which are due", body = ListResponse<CrawlSchedule>),
+        ErrorStatusCode,
+    ),
+    params(Pagination),
+    context_path = "/api/v1",
+    tag = "crawl",
+)]
+pub async fn list_due_crawl_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(pagination), _): Query<Pagination>,
+    request: Request,
+) -> Result<extract::Json<ListResponse<CrawlSchedule>>> {
+    let txn = state
+        .database_client()
+        .read_connection_ref()
+        .begin()
+        .await?;
+
+    let response = ListQueryBuilder::<_, s3_crawl_schedule::Entity>::new(&txn).filter_due();
+
+    let url = if let Some(url) = state.config().api_links_url() {
+        url
+    } else {
+        &HeaderParser::parse_host_url(&request, state.use_tls_links())?
+    };
+
+    let url = url.join(&HeaderParser::get_uri_path(&request))?;
+
+    let count = response.cloned().count().await?;
+    let response = response
+        .paginate_to_list_response(pagination, url, count)
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(extract::Json(response))
+}
+
+/// Generate sequencers for all records which have a null sequencer. Older ingestion logic could
+/// insert records without a sequencer, which crawls handle by generating one. This normalizes
+/// all such records in a single pass without crawling S3. Generated sequencers are deterministic
+/// and order before any AWS-native sequencer, in the same way as a crawl. The current state of
+/// the affected objects is re-derived afterwards. If the request is scoped to a tenant, only
+/// records in the buckets of the tenant are normalized.
+#[utoipa::path(
+    post,
+    path = "/s3/crawl/normalize-sequencers",
+    responses(
+        (status = OK, description = "The number of records which had a sequencer generated", body = NormalizeSequencersResult),
+        ErrorStatusCode,
+    ),
+    context_path = "/api/v1",
+    tag = "crawl",
+)]
+pub async fn normalize_sequencers_s3(
+    state: State<AppState>,
+) -> Result<extract::Json<NormalizeSequencersResult>> {
+    let buckets = TenantScope::current().map(|scope| scope.buckets().to_vec());
+    let n_records = Ingester::new(state.database_client().clone())
+        .normalize_null_sequencers(buckets.as_deref())
+        .await?;
+
+    Ok(extract::Json(NormalizeSequencersResult::new(n_records)))
+}
+
 /// The router for crawl operations.
 pub fn crawl_router() -> Router<AppState> {
     Router::new()
@@ -340,24 +728,37 @@ pub fn crawl_router() -> Router<AppState> {
         .route("/s3/crawl/status", get(list_crawl_s3))
         .route("/s3/crawl/status/count", get(count_crawl_s3))
         .route("/s3/crawl/status/{id}", get(get_crawl_s3_by_id))
+        .route("/s3/crawl/{id}/cancel", post(cancel_crawl_s3))
+        .route("/s3/crawl/schedule", post(schedule_crawl_s3))
+        .route("/s3/crawl/schedule/due", get(list_due_crawl_s3))
+        .route(
+            "/s3/crawl/normalize-sequencers",
+            post(normalize_sequencers_s3),
+        )
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
     use crate::database::aws::migration::tests::MIGRATOR;
     use aws_lambda_events::http::Method;
+    use aws_sdk_s3::operation::list_object_versions::ListObjectVersionsOutput;
+    use aws_sdk_s3::types::ObjectVersion;
+    use aws_smithy_mocks::mock;
     use axum::body::Body;
     use axum::http::StatusCode;
-    use sqlx::PgPool;
+    use chrono::DateTime;
+    use sqlx::{Connection, PgConnection, PgPool};
 
     use super::*;
     use crate::clients::aws::s3::Client;
     use crate::clients::aws::{secrets_manager, sqs};
     use crate::database;
-    use crate::database::entities::sea_orm_active_enums::CrawlStatus::Completed;
+    use crate::database::entities::s3_object;
+    use crate::database::entities::sea_orm_active_enums::CrawlStatus::{Cancelled, Completed};
+    use crate::database::entities::sea_orm_active_enums::EventType;
     use crate::events::aws::collecter::tests::{
         expected_get_object_tagging, expected_head_object, expected_put_object_tagging,
-        get_tagging_expectation, head_expectation, put_tagging_expectation,
+        get_tagging_expectation, head_expectation, mock_s3, put_tagging_expectation,
     };
     use crate::events::aws::crawl::tests::list_object_expectations;
     use crate::events::aws::message::default_version_id;
@@ -366,6 +767,8 @@ pub(crate) mod tests {
     use crate::routes::list::tests::{response_from, response_from_get};
     use crate::routes::pagination::Links;
     use itertools::Itertools;
+    use sea_orm::ActiveValue::NotSet;
+    use sea_orm::IntoActiveModel;
     use serde_json::json;
     use std::sync::Arc;
 
@@ -387,6 +790,8 @@ pub(crate) mod tests {
             .build(state.database_client())
             .await
             .unwrap();
+        let (_, schedule) = schedule(&state, "bucket", 3600).await;
+        assert_eq!(schedule.last_completed, None);
 
         let result: Crawl = response_from(
             state.clone(),
@@ -400,11 +805,141 @@ pub(crate) mod tests {
         assert_eq!(result.status, Completed);
         assert_eq!(result.n_objects, Some(2));
 
+        // The schedule for the bucket records the completed crawl.
+        let schedule = s3_crawl_schedule::Entity::find_by_id(schedule.s3_crawl_schedule_id)
+            .one(state.database_client().connection_ref())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(schedule.last_completed.is_some());
+
         let (status, _) = crawl(&state).await;
 
         assert_eq!(status, StatusCode::NO_CONTENT);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn cancel_crawl_s3_api(pool: PgPool) {
+        // Request the cancellation of crawls in progress while the first page is being listed.
+        // The mock is synchronous, so the request is made using a separate connection.
+        let options = pool.connect_options();
+        let request_cancel = move || {
+            let options = options.clone();
+            std::thread::spawn(move || {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(async {
+                        let mut conn = PgConnection::connect_with(&options).await.unwrap();
+                        sqlx::query(
+                            "update s3_crawl set cancel_requested = true where status = 'InProgress'",
+                        )
+                        .execute(&mut conn)
+                        .await
+                        .unwrap();
+                    })
+            })
+            .join()
+            .unwrap();
+        };
+        let state = AppState::from_pool(pool).await.unwrap();
+
+        let next_page = mock!(aws_sdk_s3::Client::list_object_versions)
+            .match_requests(|req| req.key_marker().is_some())
+            .then_output(|| ListObjectVersionsOutput::builder().build());
+        let client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::list_object_versions)
+                .match_requests(|req| req.key_marker().is_none())
+                .then_output(move || {
+                    request_cancel();
+
+                    ListObjectVersionsOutput::builder()
+                        .versions(ObjectVersion::builder().key("key").is_latest(true).build())
+                        .is_truncated(true)
+                        .next_key_marker("key")
+                        .next_version_id_marker("null")
+                        .build()
+                }),
+            next_page.clone(),
+        ]);
+        let state = state.with_s3_client(client);
+
+        let (status, result): (_, Crawl) = response_from(
+            state.clone(),
+            "/s3/crawl/sync",
+            Method::POST,
+            Body::from(json!({"bucket": "bucket"}).to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.status, Cancelled);
+        assert_eq!(result.key_marker, Some("key".to_string()));
+        assert_eq!(result.n_objects, None);
+        assert!(result.cancel_requested);
+        assert_eq!(next_page.num_calls(), 0);
+
+        // The cancelled crawl is recorded, and nothing was ingested.
+        let crawl = response_from_get::<Crawl>(
+            state.clone(),
+            &format!("/s3/crawl/status/{}", result.s3_crawl_id),
+        )
+        .await;
+        assert_eq!(crawl, result);
+        assert!(
+            s3_object::Entity::find()
+                .all(state.database_client().connection_ref())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // A crawl which is not in progress cannot be cancelled.
+        let (status, _) = cancel(&state, result.s3_crawl_id).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // A cancellation is recorded with a crawl in progress, which can be running anywhere.
+        let insert = |bucket: &str, started: DateTime<Utc>| {
+            s3_crawl::Entity::insert(s3_crawl::ActiveModel {
+                s3_crawl_id: Set(UuidGenerator::generate()),
+                bucket: Set(bucket.to_string()),
+                status: Set(InProgress),
+                started: Set(started.into()),
+                ..Default::default()
+            })
+            .exec_with_returning(state.database_client().connection_ref())
+        };
+        let in_progress = insert("bucket", Utc::now()).await.unwrap();
+        let (status, _) = cancel(&state, in_progress.s3_crawl_id).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let in_progress = find_crawl(&state, in_progress.s3_crawl_id).await;
+        assert_eq!(in_progress.status, InProgress);
+        assert!(in_progress.cancel_requested);
+
+        // Crawls of buckets outside the tenant of the request are not found.
+        let other = insert("other", Utc::now()).await.unwrap();
+        let (status, _) = TenantScope::new("tenant".to_string(), vec!["bucket".to_string()])
+            .scope(cancel(&state, other.s3_crawl_id))
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let other = find_crawl(&state, other.s3_crawl_id).await;
+        assert_eq!(other.status, InProgress);
+        assert!(!other.cancel_requested);
+
+        // A crawl in progress after the maximum crawl time is no longer running, so it is failed.
+        let stale = insert(
+            "stale",
+            Utc::now() - TimeDelta::minutes(MAX_CRAWL_TIME_MINUTES + 1),
+        )
+        .await
+        .unwrap();
+        let (status, _) = cancel(&state, stale.s3_crawl_id).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let stale = find_crawl(&state, stale.s3_crawl_id).await;
+        assert_eq!(stale.status, CrawlStatus::Failed);
+        assert!(!stale.cancel_requested);
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn crawl_s3_status_api(pool: PgPool) {
         let state = AppState::from_pool(pool).await.unwrap();
@@ -458,6 +993,192 @@ pub(crate) mod tests {
         assert_eq!(&result, first);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn crawl_s3_schedule_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+
+        let (status, overdue) = schedule(&state, "overdue", 3600).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, recent) = schedule(&state, "recent", 3600).await;
+        let (_, never) = schedule(&state, "never", 3600).await;
+
+        let set_last_completed = |schedule: CrawlSchedule, ago: TimeDelta| {
+            let mut model = schedule.into_active_model();
+            model.last_completed = Set(Some((Utc::now() - ago).into()));
+            model.update(state.database_client().connection_ref())
+        };
+        let overdue = set_last_completed(overdue, TimeDelta::hours(2))
+            .await
+            .unwrap();
+        let recent = set_last_completed(recent, TimeDelta::minutes(10))
+            .await
+            .unwrap();
+
+        let result: ListResponse<CrawlSchedule> =
+            response_from_get(state.clone(), "/s3/crawl/schedule/due").await;
+        assert_eq!(result.results(), vec![never.clone(), overdue.clone()]);
+        assert_eq!(result.pagination().count, 2);
+
+        // Updating the schedule keeps the same record and last completed time.
+        let (_, updated) = schedule(&state, "recent", 60).await;
+        assert_eq!(updated.s3_crawl_schedule_id, recent.s3_crawl_schedule_id);
+        assert_eq!(updated.last_completed, recent.last_completed);
+        assert_eq!(updated.interval_seconds, 60);
+
+        let result: ListResponse<CrawlSchedule> =
+            response_from_get(state.clone(), "/s3/crawl/schedule/due").await;
+        assert_eq!(result.results(), vec![never, overdue, updated]);
+
+        let (status, _) = response_from::<serde_json::Value>(
+            state,
+            "/s3/crawl/schedule",
+            Method::POST,
+            Body::from(json!({"bucket": "bucket", "intervalSeconds": 0}).to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn normalize_sequencers_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+
+        // Mimic old database logic by inserting null sequencers directly, alongside an existing
+        // generated sequencer.
+        let insert_in = |bucket: &str, key: &str, sequencer: Option<&str>, event_time: &str| {
+            let model = s3_object::ActiveModel {
+                s3_object_id: Set(UuidGenerator::generate()),
+                event_type: Set(EventType::Created),
+                bucket: Set(bucket.to_string()),
+                key: Set(key.to_string()),
+                version_id: Set(default_version_id()),
+                sequencer: sequencer.map_or(NotSet, |sequencer| Set(Some(sequencer.to_string()))),
+                event_time: Set(Some(event_time.parse().unwrap())),
+                ..Default::default()
+            };
+            s3_object::Entity::insert(model)
+                .exec_with_returning(state.database_client().connection_ref())
+        };
+        let insert = |key: &str, sequencer: Option<&str>, event_time: &str| {
+            insert_in("bucket", key, sequencer, event_time)
+        };
+        let existing = insert(
+            "key",
+            Some("000000000000000000000000000000-0100000000000000"),
+            "1970-01-01 00:00:00.000000 +00:00",
+        )
+        .await
+        .unwrap();
+        let later = insert("key", None, "1970-01-01 00:00:02.000000 +00:00")
+            .await
+            .unwrap();
+        let earlier = insert("key", None, "1970-01-01 00:00:01.000000 +00:00")
+            .await
+            .unwrap();
+        let other = insert("key1", None, "1970-01-01 00:00:01.000000 +00:00")
+            .await
+            .unwrap();
+        let other_tenant = insert_in("other", "key", None, "1970-01-01 00:00:01.000000 +00:00")
+            .await
+            .unwrap();
+
+        // Only the records of the tenant are normalized when the request is scoped to a tenant.
+        let (status, result) = TenantScope::new("tenant".to_string(), vec!["bucket".to_string()])
+            .scope(response_from::<NormalizeSequencersResult>(
+                state.clone(),
+                "/s3/crawl/normalize-sequencers",
+                Method::POST,
+                Body::empty(),
+            ))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.n_records(), 3);
+
+        let results = s3_object::Entity::find()
+            .all(state.database_client().connection_ref())
+            .await
+            .unwrap();
+        let find = |id: Uuid| {
+            results
+                .iter()
+                .find(|record| record.s3_object_id == id)
+                .unwrap()
+        };
+
+        // Sequencers are generated in event time order after the existing generated sequencer.
+        assert_eq!(
+            find(earlier.s3_object_id).sequencer.as_deref(),
+            Some("000000000000000000000000000000-0200000000000000")
+        );
+        assert_eq!(
+            find(later.s3_object_id).sequencer.as_deref(),
+            Some("000000000000000000000000000000-0300000000000000")
+        );
+        assert_eq!(
+            find(other.s3_object_id).sequencer.as_deref(),
+            Some("000000000000000000000000000000-0100000000000000")
+        );
+        assert!(
+            results
+                .iter()
+                .filter(|record| record.bucket == "bucket")
+                .all(|record| record.sequencer.is_some())
+        );
+        assert_eq!(find(other_tenant.s3_object_id).sequencer, None);
+
+        // The current state is re-derived from the generated sequencers.
+        assert!(!find(existing.s3_object_id).is_current_state);
+        assert!(!find(earlier.s3_object_id).is_current_state);
+        assert!(find(later.s3_object_id).is_current_state);
+        assert!(find(other.s3_object_id).is_current_state);
+
+        // Normalizing again only changes the records of the other tenant.
+        let normalize = || {
+            response_from::<NormalizeSequencersResult>(
+                state.clone(),
+                "/s3/crawl/normalize-sequencers",
+                Method::POST,
+                Body::empty(),
+            )
+        };
+        let (_, result) = normalize().await;
+        assert_eq!(result.n_records(), 1);
+        let (_, result) = normalize().await;
+        assert_eq!(result.n_records(), 0);
+    }
+
+    async fn schedule(
+        state: &AppState,
+        bucket: &str,
+        interval_seconds: i64,
+    ) -> (StatusCode, CrawlSchedule) {
+        response_from(
+            state.clone(),
+            "/s3/crawl/schedule",
+            Method::POST,
+            Body::from(json!({"bucket": bucket, "intervalSeconds": interval_seconds}).to_string()),
+        )
+        .await
+    }
+
+    async fn find_crawl(state: &AppState, id: Uuid) -> Crawl {
+        s3_crawl::Entity::find_by_id(id)
+            .one(state.database_client().connection_ref())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn cancel(state: &AppState, id: Uuid) -> (StatusCode, serde_json::Value) {
+        response_from(
+            state.clone(),
+            &format!("/s3/crawl/{id}/cancel"),
+            Method::POST,
+            Body::empty(),
+        )
+        .await
+    }
+
     async fn crawl(state: &AppState) -> (StatusCode, serde_json::Value) {
         response_from(
             state.clone(),
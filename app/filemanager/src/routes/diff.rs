@@ -0,0 +1,236 @@
+//! Route logic for comparing the objects in two buckets.
+//!
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::Result;
+use crate::queries::diff::DiffQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, Query};
+
+/// Params for comparing two buckets.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct DiffParams {
+    /// The source bucket to compare.
+    #[param(nullable = false, required = true)]
+    source_bucket: String,
+    /// The destination bucket to compare.
+    #[param(nullable = false, required = true)]
+    destination_bucket: String,
+    /// Only compare source objects under this prefix. Keys are compared after removing this
+    /// prefix. By default, the whole source bucket is compared.
+    #[serde(default)]
+    #[param(nullable = false, required = false)]
+    source_prefix: Option<String>,
+    /// Only compare destination objects under this prefix. Keys are compared after removing this
+    /// prefix. By default, the whole destination bucket is compared.
+    #[serde(default)]
+    #[param(nullable = false, required = false)]
+    destination_prefix: Option<String>,
+}
+
+impl DiffParams {
+    /// Create new diff params.
+    pub fn new(
+        source_bucket: String,
+        destination_bucket: String,
+        source_prefix: Option<String>,
+        destination_prefix: Option<String>,
+    ) -> Self {
+        Self {
+            source_bucket,
+            destination_bucket,
+            source_prefix,
+            destination_prefix,
+        }
+    }
+
+    /// Get the source bucket.
+    pub fn source_bucket(&self) -> &str {
+        &self.source_bucket
+    }
+
+    /// Get the destination bucket.
+    pub fn destination_bucket(&self) -> &str {
+        &self.destination_bucket
+    }
+
+    /// Get the source prefix.
+    pub fn source_prefix(&self) -> Option<&str> {
+        self.source_prefix.as_deref()
+    }
+
+    /// Get the destination prefix.
+    pub fn destination_prefix(&self) -> Option<&str> {
+        self.destination_prefix.as_deref()
+    }
+}
+
+/// An object which is present in both buckets, but has a different size or ETag.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedObject {
+    /// The key in the source bucket.
+    pub(crate) source_key: String,
+    /// The key in the destination bucket.
+    pub(crate) destination_key: String,
+    /// The size of the source object.
+    pub(crate) source_size: Option<i64>,
+    /// The size of the destination object.
+    pub(crate) destination_size: Option<i64>,
+    /// The ETag of the source object.
+    pub(crate) source_e_tag: Option<String>,
+    /// The ETag of the destination object.
+    pub(crate) destination_e_tag: Option<String>,
+}
+
+/// The differences between the current objects in a source and destination bucket.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketDiff {
+    /// Keys which are only present in the source bucket.
+    pub(crate) source_only: Vec<String>,
+    /// Keys which are only present in the destination bucket.
+    pub(crate) destination_only: Vec<String>,
+    /// Objects which are present in both buckets, but have a different size or ETag.
+    pub(crate) changed: Vec<ChangedObject>,
+}
+
+impl BucketDiff {
+    /// Get the keys which are only present in the source bucket.
+    pub fn source_only(&self) -> &[String] {
+        &self.source_only
+    }
+
+    /// Get the keys which are only present in the destination bucket.
+    pub fn destination_only(&self) -> &[String] {
+        &self.destination_only
+    }
+
+    /// Get the objects which have a different size or ETag.
+    pub fn changed(&self) -> &[ChangedObject] {
+        &self.changed
+    }
+}
+
+/// Compare the current objects in a source and destination bucket. This returns keys that are
+/// present in only one of the buckets, and objects which have a different size or ETag. Prefixes
+/// can be used to compare objects which have moved, where keys under the source prefix are matched
+/// with keys under the destination prefix. This only uses the records in the database, and does
+/// not make any calls to S3.
+#[utoipa::path(
+    get,
+    path = "/s3/diff",
+    responses(
+        (status = OK, description = "The differences between the buckets", body = BucketDiff),
+        ErrorStatusCode,
+    ),
+    params(DiffParams),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn diff_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(diff), _): Query<DiffParams>,
+) -> Result<Json<BucketDiff>> {
+    Ok(Json(
+        DiffQueryBuilder::new(state.database_client().read_connection_ref())
+            .diff_buckets(
+                &diff.source_bucket,
+                diff.source_prefix.as_deref().unwrap_or_default(),
+                &diff.destination_bucket,
+                diff.destination_prefix.as_deref().unwrap_or_default(),
+            )
+            .await?,
+    ))
+}
+
+/// The router for comparing buckets.
+pub fn diff_router() -> Router<AppState> {
+    Router::new().route("/s3/diff", get(diff_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::s3_object;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::list::tests::response_from_get;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn diff_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_n(12)
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Even entries are current, and odd entries are deleted.
+        for (i, bucket, key, size) in [
+            (0, "source", "a/same", 0),
+            (2, "source", "a/changed", 2),
+            (4, "source", "a/source_only", 4),
+            (6, "destination", "b/same", 0),
+            (8, "destination", "b/changed", 8),
+            (10, "destination", "b/destination_only", 10),
+            (1, "destination", "b/source_only", 4),
+            (3, "destination", "a/destination_only", 10),
+        ] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.bucket = Set(bucket.to_string());
+            model.key = Set(key.to_string());
+            model.size = Set(Some(size));
+            model.e_tag = Set(Some(size.to_string()));
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let result: BucketDiff = response_from_get(
+            state.clone(),
+            "/s3/diff?sourceBucket=source&destinationBucket=destination&sourcePrefix=a/&destinationPrefix=b/",
+        )
+        .await;
+        assert_eq!(result.source_only(), ["a/source_only".to_string()]);
+        assert_eq!(
+            result.destination_only(),
+            ["b/destination_only".to_string()]
+        );
+        assert_eq!(
+            result.changed(),
+            [ChangedObject {
+                source_key: "a/changed".to_string(),
+                destination_key: "b/changed".to_string(),
+                source_size: Some(2),
+                destination_size: Some(8),
+                source_e_tag: Some("2".to_string()),
+                destination_e_tag: Some("8".to_string()),
+            }]
+        );
+
+        // Without prefixes, none of the keys match.
+        let result: BucketDiff = response_from_get(
+            state,
+            "/s3/diff?sourceBucket=source&destinationBucket=destination",
+        )
+        .await;
+        assert_eq!(result.source_only().len(), 3);
+        assert_eq!(result.destination_only().len(), 3);
+        assert!(result.changed().is_empty());
+    }
+}
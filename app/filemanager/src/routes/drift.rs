@@ -0,0 +1,604 @@
+//! Route logic for reporting drift between the database and S3 under a prefix, which can
+//! automatically enqueue a crawl of the prefix when the drift exceeds a threshold.
+//!
+
+use std::collections::BTreeMap;
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use chrono::Duration;
+use itertools::{EitherOrBoth, Itertools};
+use sea_orm::{ConnectionTrait, DbBackend, EntityTrait, Statement};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::database::entities::s3_crawl_schedule;
+use crate::error::Result;
+use crate::events::aws::message::quote_e_tag;
+use crate::queries::prefix::PrefixQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, Query};
+use crate::routes::prefix::{PREFIX_DELIMITER, PrefixListing, s3_prefix_children};
+use crate::routes::tenant::TenantScope;
+use crate::uuid::UuidGenerator;
+
+/// The maximum number of keys listed from S3 for a page of the drift report. This is the most
+/// that a single `ListObjectVersions` call returns.
+pub const MAX_DRIFT_PAGE_SIZE: i32 = 1000;
+
+/// Params for reporting drift under a prefix.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct DriftParams {
+    /// The bucket to report drift for.
+    #[param(nullable = false, required = true)]
+    bucket: String,
+    /// The prefix to report drift for. This should usually end with a `/`. By default, the
+    /// root of the bucket is used.
+    #[serde(default)]
+    #[param(nullable = false, required = false)]
+    prefix: Option<String>,
+    /// Only compare children after this key marker. Set this to the `nextKeyMarker` of the
+    /// previous report to compare the next page of children.
+    #[serde(default)]
+    #[param(nullable = false, required = false)]
+    key_marker: Option<String>,
+    /// The maximum number of keys listed from S3 for this page of the report.
+    #[serde(default = "default_page_size")]
+    #[param(
+        nullable = false,
+        required = false,
+        default = 1000,
+        minimum = 1,
+        maximum = 1000
+    )]
+    page_size: i32,
+}
+
+/// The default page size of the drift report.
+pub fn default_page_size() -> i32 {
+    MAX_DRIFT_PAGE_SIZE
+}
+
+impl DriftParams {
+    /// Create new drift params.
+    pub fn new(bucket: String, prefix: Option<String>) -> Self {
+        Self {
+            bucket,
+            prefix,
+            key_marker: None,
+            page_size: MAX_DRIFT_PAGE_SIZE,
+        }
+    }
+
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Get the prefix.
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Get the key marker.
+    pub fn key_marker(&self) -> Option<&str> {
+        self.key_marker.as_deref()
+    }
+
+    /// Get the page size, which is at least one and at most `MAX_DRIFT_PAGE_SIZE`.
+    pub fn page_size(&self) -> i32 {
+        self.page_size.clamp(1, MAX_DRIFT_PAGE_SIZE)
+    }
+}
+
+/// The way a child prefix or object differs between the database and S3.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+pub enum DriftKind {
+    /// The child is current in the database, but is not present in S3.
+    DatabaseOnly,
+    /// The child is present in S3, but is not current in the database.
+    S3Only,
+    /// The object is present in both, but the version id, ETag or size differs.
+    Changed,
+}
+
+/// A child prefix or object which differs between the database and S3.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DriftedChild {
+    /// The child prefix including the trailing `/`, or the key of the object.
+    pub(crate) name: String,
+    /// How the child differs.
+    pub(crate) kind: DriftKind,
+}
+
+impl DriftedChild {
+    /// Get the name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the kind of drift.
+    pub fn kind(&self) -> DriftKind {
+        self.kind
+    }
+}
+
+/// The drift between the database and S3 for the immediate children of a prefix.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DriftReport {
+    /// The bucket of the prefix.
+    pub(crate) bucket: String,
+    /// The prefix that was compared.
+    pub(crate) prefix: String,
+    /// The number of distinct child prefixes and objects in either the database or S3.
+    pub(crate) n_children: u64,
+    /// The fraction of children which have drifted, between 0 and 1.
+    pub(crate) drift_ratio: f64,
+    /// The children which have drifted.
+    pub(crate) drifted: Vec<DriftedChild>,
+    /// Whether a crawl of the prefix was enqueued on its crawl schedule because the drift exceeded
+    /// `FILEMANAGER_API_DRIFT_CRAWL_THRESHOLD`.
+    pub(crate) crawl_enqueued: bool,
+    /// The key marker of the next page of children, if there are more children to compare.
+    pub(crate) next_key_marker: Option<String>,
+}
+
+impl DriftReport {
+    /// Compare the children of a prefix listed from the database with those listed from S3.
+    pub fn compare(
+        bucket: String,
+        prefix: String,
+        database: PrefixListing,
+        s3: PrefixListing,
+    ) -> Self {
+        // Prefixes have no state to compare, so they only drift if they are missing on one side.
+        let children = |listing: PrefixListing| {
+            listing
+                .prefixes
+                .into_iter()
+                .map(|prefix| (prefix, None))
+                .chain(listing.objects.into_iter().map(|object| {
+                    (
+                        object.key,
+                        Some((
+                            object.version_id,
+                            object.e_tag.map(quote_e_tag),
+                            object.size,
+                        )),
+                    )
+                }))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let mut n_children = 0;
+        let drifted = children(database)
+            .into_iter()
+            .merge_join_by(children(s3), |(a, _), (b, _)| a.cmp(b))
+            .filter_map(|children| {
+                n_children += 1;
+                match children {
+                    EitherOrBoth::Left((name, _)) => Some(DriftedChild {
+                        name,
+                        kind: DriftKind::DatabaseOnly,
+                    }),
+                    EitherOrBoth::Right((name, _)) => Some(DriftedChild {
+                        name,
+                        kind: DriftKind::S3Only,
+                    }),
+                    EitherOrBoth::Both((name, database), (_, s3)) if database != s3 => {
+                        Some(DriftedChild {
+                            name,
+                            kind: DriftKind::Changed,
+                        })
+                    }
+                    EitherOrBoth::Both(_, _) => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let drift_ratio = if n_children == 0 {
+            0.0
+        } else {
+            drifted.len() as f64 / n_children as f64
+        };
+
+        Self {
+            bucket,
+            prefix,
+            n_children,
+            drift_ratio,
+            drifted,
+            crawl_enqueued: false,
+            next_key_marker: None,
+        }
+    }
+
+    /// Get the number of children.
+    pub fn n_children(&self) -> u64 {
+        self.n_children
+    }
+
+    /// Get the drift ratio.
+    pub fn drift_ratio(&self) -> f64 {
+        self.drift_ratio
+    }
+
+    /// Get the drifted children.
+    pub fn drifted(&self) -> &[DriftedChild] {
+        &self.drifted
+    }
+
+    /// Whether a crawl was enqueued.
+    pub fn crawl_enqueued(&self) -> bool {
+        self.crawl_enqueued
+    }
+
+    /// Get the key marker of the next page.
+    pub fn next_key_marker(&self) -> Option<&str> {
+        self.next_key_marker.as_deref()
+    }
+}
+
+/// Enqueue a crawl of the bucket and prefix by making its crawl schedule due, so that it is run by
+/// the scheduler polling `/api/v1/s3/crawl/schedule/due`. The schedule is created with the
+/// `interval` if it does not exist. An existing schedule is only made due if its last crawl
+/// completed longer than the `interval` ago, which limits how often crawls are enqueued across
+/// all instances of the API. Returns whether the crawl was enqueued.
+pub async fn enqueue_drift_crawl<C: ConnectionTrait>(
+    connection: &C,
+    bucket: &str,
+    prefix: Option<&str>,
+    interval: Duration,
+) -> Result<bool> {
+    let schedule = s3_crawl_schedule::Entity::find()
+        .from_raw_sql(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            include_str!("../../../database/queries/api/enqueue_drift_crawl.sql"),
+            [
+                UuidGenerator::generate().into(),
+                bucket.into(),
+                prefix.into(),
+                interval.num_seconds().max(1).into(),
+            ],
+        ))
+        .one(connection)
+        .await?;
+
+    Ok(schedule.is_some())
+}
+
+/// Report the drift between the database and S3 for the immediate children of a prefix, splitting
+/// keys on `/`. Child prefixes and objects are listed from both the current state of records in
+/// the database and directly from S3, and children which are missing on either side, or objects
+/// with a different version id, ETag or size, are reported.
+///
+/// Children are compared one page of the S3 listing at a time, listing at most `pageSize` keys.
+/// If there are more children, the report contains a `nextKeyMarker` which can be passed as the
+/// `keyMarker` to compare the next page.
+///
+/// If `FILEMANAGER_API_DRIFT_CRAWL_THRESHOLD` is set and the fraction of drifted children in the
+/// page exceeds it, a crawl of the prefix is enqueued to correct the records by making its crawl schedule due.
+/// A crawl is not enqueued if the last crawl of the prefix completed within
+/// `FILEMANAGER_API_DRIFT_CRAWL_INTERVAL`.
+/// See [`KeyPathMode`](crate::routes::key_path::KeyPathMode) for how prefixes are checked.
+#[utoipa::path(
+    get,
+    path = "/s3/drift",
+    responses(
+        (status = OK, description = "The drift between the database and S3", body = DriftReport),
+        ErrorStatusCode,
+    ),
+    params(DriftParams),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn drift_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(params), _): Query<DriftParams>,
+) -> Result<Json<DriftReport>> {
+    let prefix = state
+        .config()
+        .api_key_path_mode()
+        .check(params.prefix.as_deref().unwrap_or_default())?
+        .to_string();
+
+    TenantScope::check_bucket(&params.bucket)?;
+
+    let output = state
+        .s3_client()
+        .list_objects_page(
+            &params.bucket,
+            Some(prefix.clone()),
+            Some(PREFIX_DELIMITER.to_string()),
+            params.key_marker.clone(),
+            None,
+            Some(params.page_size()),
+        )
+        .await?;
+    let next_key_marker = output
+        .is_truncated
+        .is_some_and(|truncated| truncated)
+        .then(|| output.next_key_marker.clone())
+        .flatten();
+
+    // The database children are compared over the same range of names as the S3 page.
+    let database = PrefixQueryBuilder::new(state.database_client().read_connection_ref())
+        .list_children_between(
+            &params.bucket,
+            &prefix,
+            params.key_marker(),
+            next_key_marker.as_deref(),
+        )
+        .await?;
+    let s3 = s3_prefix_children(&state, output);
+
+    let mut report = DriftReport::compare(params.bucket, prefix, database, s3);
+    report.next_key_marker = next_key_marker;
+
+    if let Some(threshold) = state.config().api_drift_crawl_threshold()
+        && report.drift_ratio > threshold
+    {
+        report.crawl_enqueued = enqueue_drift_crawl(
+            state.database_client().connection_ref(),
+            &report.bucket,
+            (!report.prefix.is_empty()).then_some(report.prefix.as_str()),
+            state.config().api_drift_crawl_interval(),
+        )
+        .await?;
+
+        if !report.crawl_enqueued {
+            debug!(
+                bucket = report.bucket,
+                prefix = report.prefix,
+                "skipping drift crawl which is already due or recently completed"
+            );
+        }
+    }
+
+    Ok(Json(report))
+}
+
+/// The router for reporting drift.
+pub fn drift_router() -> Router<AppState> {
+    Router::new().route("/s3/drift", get(drift_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_s3::operation::list_object_versions::ListObjectVersionsOutput;
+    use aws_sdk_s3::types::{CommonPrefix, ObjectVersion};
+    use aws_smithy_mocks::mock;
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::s3_crawl_schedule::Model as CrawlSchedule;
+    use crate::database::entities::s3_object;
+    use crate::env::Config;
+    use crate::events::aws::collecter::tests::mock_s3;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::list::tests::response_from_get;
+    use crate::routes::pagination::ListResponse;
+    use chrono::Utc;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn drift_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_bucket_divisor(10)
+            .with_generate_crawl_entries(false)
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Even entries are current in bucket `0`.
+        for (i, key) in [(0, "a/b/c/1"), (2, "a/b/2"), (4, "a/d/3"), (6, "a/4")] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.key = Set(key.to_string());
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let object = |key: &str, record: &s3_object::Model| {
+            ObjectVersion::builder()
+                .key(key)
+                .version_id(record.version_id.clone())
+                .set_e_tag(record.e_tag.clone())
+                .set_size(record.size)
+                .is_latest(true)
+                .build()
+        };
+        // Under `a/`, only the `a/z/` prefix is new in S3.
+        let a = ListObjectVersionsOutput::builder()
+            .common_prefixes(CommonPrefix::builder().prefix("a/b/").build())
+            .common_prefixes(CommonPrefix::builder().prefix("a/d/").build())
+            .common_prefixes(CommonPrefix::builder().prefix("a/z/").build())
+            .versions(object("a/4", &entries.s3_objects[6]))
+            .build();
+        // Under `a/b/`, `a/b/c/` is missing, `a/b/x/` is new and `a/b/2` has changed.
+        let b = ListObjectVersionsOutput::builder()
+            .common_prefixes(CommonPrefix::builder().prefix("a/b/x/").build())
+            .versions(
+                ObjectVersion::builder()
+                    .key("a/b/2")
+                    .version_id("changed_version_id")
+                    .is_latest(true)
+                    .build(),
+            )
+            .build();
+
+        let client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::list_object_versions)
+                .match_requests(|req| req.prefix() == Some("a/"))
+                .then_output(move || a.clone()),
+            mock!(aws_sdk_s3::Client::list_object_versions)
+                .match_requests(|req| req.prefix() == Some("a/b/"))
+                .then_output(move || b.clone()),
+        ]);
+        let state = state.with_s3_client(client).with_config(Config {
+            api_drift_crawl_threshold: Some(0.5),
+            ..Default::default()
+        });
+        let schedules =
+            || s3_crawl_schedule::Entity::find().all(state.database_client().connection_ref());
+
+        // The drift is below the threshold.
+        let result: DriftReport =
+            response_from_get(state.clone(), "/s3/drift?bucket=0&prefix=a/").await;
+        assert_eq!(result.n_children(), 4);
+        assert_eq!(result.drift_ratio(), 0.25);
+        assert_eq!(
+            result.drifted(),
+            [DriftedChild {
+                name: "a/z/".to_string(),
+                kind: DriftKind::S3Only
+            }]
+        );
+        assert!(!result.crawl_enqueued());
+        assert!(schedules().await.unwrap().is_empty());
+
+        // The drift exceeds the threshold.
+        let result: DriftReport =
+            response_from_get(state.clone(), "/s3/drift?bucket=0&prefix=a/b/").await;
+        assert_eq!(result.n_children(), 3);
+        assert_eq!(result.drift_ratio(), 1.0);
+        assert_eq!(
+            result
+                .drifted()
+                .iter()
+                .map(|child| (child.name(), child.kind()))
+                .collect::<Vec<_>>(),
+            [
+                ("a/b/2", DriftKind::Changed),
+                ("a/b/c/", DriftKind::DatabaseOnly),
+                ("a/b/x/", DriftKind::S3Only),
+            ]
+        );
+        assert!(result.crawl_enqueued());
+
+        // The crawl is enqueued by creating a schedule which is due.
+        let schedule = schedules().await.unwrap().pop().unwrap();
+        assert_eq!(schedule.bucket, "0");
+        assert_eq!(schedule.prefix.as_deref(), Some("a/b/"));
+        assert_eq!(schedule.last_completed, None);
+        let due: ListResponse<CrawlSchedule> =
+            response_from_get(state.clone(), "/s3/crawl/schedule/due").await;
+        assert_eq!(due.results(), vec![schedule.clone()]);
+
+        // Another crawl is not enqueued while the schedule is due.
+        let result: DriftReport =
+            response_from_get(state.clone(), "/s3/drift?bucket=0&prefix=a/b/").await;
+        assert_eq!(result.drift_ratio(), 1.0);
+        assert!(!result.crawl_enqueued());
+
+        // Or within the interval after the crawl completed.
+        let set_last_completed = |ago: Duration| {
+            let mut model = schedule.clone().into_active_model();
+            model.last_completed = Set(Some((Utc::now() - ago).into()));
+            model.update(state.database_client().connection_ref())
+        };
+        set_last_completed(Duration::minutes(10)).await.unwrap();
+        let result: DriftReport =
+            response_from_get(state.clone(), "/s3/drift?bucket=0&prefix=a/b/").await;
+        assert!(!result.crawl_enqueued());
+
+        // After the interval, the existing schedule is made due again.
+        set_last_completed(Duration::hours(2)).await.unwrap();
+        let result: DriftReport =
+            response_from_get(state.clone(), "/s3/drift?bucket=0&prefix=a/b/").await;
+        assert!(result.crawl_enqueued());
+        assert_eq!(schedules().await.unwrap(), vec![schedule]);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn drift_s3_api_paged(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_bucket_divisor(10)
+            .with_generate_crawl_entries(false)
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        for (i, key) in [(0, "a/1"), (2, "a/2"), (4, "a/3/x")] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.key = Set(key.to_string());
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let record = entries.s3_objects[0].clone();
+        let first = ListObjectVersionsOutput::builder()
+            .versions(
+                ObjectVersion::builder()
+                    .key("a/1")
+                    .version_id(record.version_id)
+                    .set_e_tag(record.e_tag)
+                    .set_size(record.size)
+                    .is_latest(true)
+                    .build(),
+            )
+            .is_truncated(true)
+            .next_key_marker("a/1")
+            .build();
+        // After the first page, `a/2` is missing and `a/4` is new.
+        let second = ListObjectVersionsOutput::builder()
+            .common_prefixes(CommonPrefix::builder().prefix("a/3/").build())
+            .versions(
+                ObjectVersion::builder()
+                    .key("a/4")
+                    .version_id("version_id")
+                    .is_latest(true)
+                    .build(),
+            )
+            .is_truncated(false)
+            .build();
+        let client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::list_object_versions)
+                .match_requests(|req| req.key_marker().is_none() && req.max_keys() == Some(1))
+                .then_output(move || first.clone()),
+            mock!(aws_sdk_s3::Client::list_object_versions)
+                .match_requests(|req| req.key_marker() == Some("a/1"))
+                .then_output(move || second.clone()),
+        ]);
+        let state = state.with_s3_client(client);
+
+        // The first page only compares children up to the next key marker.
+        let result: DriftReport =
+            response_from_get(state.clone(), "/s3/drift?bucket=0&prefix=a/&pageSize=1").await;
+        assert_eq!(result.n_children(), 1);
+        assert!(result.drifted().is_empty());
+        assert_eq!(result.next_key_marker(), Some("a/1"));
+
+        let result: DriftReport = response_from_get(
+            state,
+            "/s3/drift?bucket=0&prefix=a/&pageSize=1&keyMarker=a/1",
+        )
+        .await;
+        assert_eq!(result.n_children(), 3);
+        assert_eq!(
+            result
+                .drifted()
+                .iter()
+                .map(|child| (child.name(), child.kind()))
+                .collect::<Vec<_>>(),
+            [("a/2", DriftKind::DatabaseOnly), ("a/4", DriftKind::S3Only)]
+        );
+        assert_eq!(result.next_key_marker(), None);
+    }
+}
@@ -188,7 +188,10 @@ impl From<Error> for ErrorStatusCode {
                 Self::InternalServerError(err.to_string().into())
             }
             Error::ExpectedSomeValue(_) => Self::NotFound(err.to_string().into()),
-            Error::CrawlError(_) => Self::Conflict(err.to_string().into()),
+            Error::TenantError(_) => Self::Forbidden(err.to_string().into()),
+            Error::CrawlError(_) | Error::CrawlCancelled(_) => {
+                Self::Conflict(err.to_string().into())
+            }
             _ => Self::InternalServerError(err.to_string().into()),
         }
     }
@@ -0,0 +1,282 @@
+//! Route logic for explaining the current state of a key.
+//!
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::database::entities::sea_orm_active_enums::EventType;
+use crate::error::Result;
+use crate::queries::explain::ExplainQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, Query};
+
+/// Params for explaining the current state of a key.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct ExplainParams {
+    /// The bucket of the object.
+    #[param(nullable = false, required = true)]
+    bucket: String,
+    /// The key of the object.
+    #[param(nullable = false, required = true)]
+    key: String,
+}
+
+impl ExplainParams {
+    /// Create new explain params.
+    pub fn new(bucket: String, key: String) -> Self {
+        Self { bucket, key }
+    }
+
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Get the key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+/// A single event of a key, along with how it contributes to the current state.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainedEvent {
+    /// The id of the record.
+    pub(crate) s3_object_id: Uuid,
+    /// The type of event.
+    pub(crate) event_type: EventType,
+    /// The version id of the record.
+    pub(crate) version_id: String,
+    /// The sequencer of the record.
+    pub(crate) sequencer: Option<String>,
+    /// The time of the event.
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub(crate) event_time: Option<DateTimeWithTimeZone>,
+    /// Whether the record is a delete marker.
+    pub(crate) is_delete_marker: bool,
+    /// Whether this is the latest event of a version which still exists.
+    pub(crate) is_current_version: bool,
+    /// Whether the record should be the current state for the key.
+    pub(crate) is_current_state: bool,
+    /// The current state stored in the database, which can differ from `isCurrentState`
+    /// if the key has not been reset since the events were ingested.
+    pub(crate) stored_is_current_state: bool,
+    /// Why the record is or isn't the current state.
+    pub(crate) explanation: String,
+}
+
+impl ExplainedEvent {
+    /// Get the record id.
+    pub fn s3_object_id(&self) -> Uuid {
+        self.s3_object_id
+    }
+
+    /// Get whether this is a current version.
+    pub fn is_current_version(&self) -> bool {
+        self.is_current_version
+    }
+
+    /// Get whether this is the current state.
+    pub fn is_current_state(&self) -> bool {
+        self.is_current_state
+    }
+
+    /// Get the explanation.
+    pub fn explanation(&self) -> &str {
+        &self.explanation
+    }
+}
+
+/// An explanation of the current state of a key.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentStateExplanation {
+    /// The bucket of the object.
+    pub(crate) bucket: String,
+    /// The key of the object.
+    pub(crate) key: String,
+    /// The id of the record which is the current state, if any.
+    pub(crate) current_s3_object_id: Option<Uuid>,
+    /// Whether the stored current state matches the computed current state for all records.
+    pub(crate) is_consistent: bool,
+    /// Why the key does or doesn't have a current state.
+    pub(crate) explanation: String,
+    /// All events of the key in the order that they occurred.
+    pub(crate) events: Vec<ExplainedEvent>,
+}
+
+impl CurrentStateExplanation {
+    /// Get the current record id.
+    pub fn current_s3_object_id(&self) -> Option<Uuid> {
+        self.current_s3_object_id
+    }
+
+    /// Get whether the stored state is consistent.
+    pub fn is_consistent(&self) -> bool {
+        self.is_consistent
+    }
+
+    /// Get the explanation.
+    pub fn explanation(&self) -> &str {
+        &self.explanation
+    }
+
+    /// Get the explained events.
+    pub fn events(&self) -> &[ExplainedEvent] {
+        &self.events
+    }
+}
+
+/// Explain which record is the current state for a bucket and key. This returns all events of
+/// the key ordered by sequencer, and describes which sequencers and delete markers determine the
+/// current state. The current state is the latest event of the latest version that hasn't been
+/// permanently deleted, unless that version is a delete marker.
+#[utoipa::path(
+    get,
+    path = "/s3/explain",
+    responses(
+        (status = OK, description = "The explanation of the current state", body = CurrentStateExplanation),
+        ErrorStatusCode,
+    ),
+    params(ExplainParams),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn explain_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(explain), _): Query<ExplainParams>,
+) -> Result<Json<CurrentStateExplanation>> {
+    Ok(Json(
+        ExplainQueryBuilder::new(state.database_client().read_connection_ref())
+            .explain_current_state(&explain.bucket, &explain.key)
+            .await?,
+    ))
+}
+
+/// The router for explaining the current state.
+pub fn explain_router() -> Router<AppState> {
+    Router::new().route("/s3/explain", get(explain_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::Ingest;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::events::EventSourceType;
+    use crate::events::aws::message::EventType as MessageEventType;
+    use crate::events::aws::{FlatS3EventMessage, FlatS3EventMessages, TransposedS3EventMessages};
+    use crate::routes::list::tests::response_from_get;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn explain_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+
+        let event = |version_id: &str, sequencer: &str| {
+            FlatS3EventMessage::new_with_generated_id()
+                .with_bucket("bucket".to_string())
+                .with_key("key".to_string())
+                .with_version_id(version_id.to_string())
+                .with_sequencer(Some(sequencer.to_string()))
+                .with_event_type(MessageEventType::Created)
+        };
+
+        // Two versions, then a permanent delete of the second version and a delete marker.
+        let events = vec![
+            event("1", "1"),
+            event("2", "2"),
+            event("2", "3").with_event_type(MessageEventType::Deleted),
+            event("3", "4")
+                .with_event_type(MessageEventType::Deleted)
+                .with_is_delete_marker(true),
+        ];
+        ingest(&state, events.clone()).await;
+
+        let result: CurrentStateExplanation =
+            response_from_get(state.clone(), "/s3/explain?bucket=bucket&key=key").await;
+        assert!(result.is_consistent());
+        assert_eq!(result.current_s3_object_id(), None);
+        assert_eq!(
+            result.explanation(),
+            "the key has no current state because the latest version `3` with sequencer `4` is a delete marker"
+        );
+        assert_eq!(
+            result
+                .events()
+                .iter()
+                .map(|event| event.s3_object_id())
+                .collect::<Vec<_>>(),
+            events
+                .iter()
+                .map(|event| event.s3_object_id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            result
+                .events()
+                .iter()
+                .map(|event| (event.is_current_version(), event.is_current_state()))
+                .collect::<Vec<_>>(),
+            vec![(true, false), (false, false), (false, false), (true, false)]
+        );
+        assert_eq!(
+            result.events()[0].explanation(),
+            "version `1` still exists, but is superseded by the later version `3` with sequencer `4`"
+        );
+        assert_eq!(
+            result.events()[1].explanation(),
+            "superseded by a later event for version `2` with sequencer `3`"
+        );
+        assert_eq!(
+            result.events()[2].explanation(),
+            "the latest event for version `2` is a permanent delete, so this version no longer exists"
+        );
+
+        // Uploading a new version over the delete marker makes it current.
+        let new_version = event("4", "5");
+        ingest(&state, vec![new_version.clone()]).await;
+
+        let result: CurrentStateExplanation =
+            response_from_get(state.clone(), "/s3/explain?bucket=bucket&key=key").await;
+        assert!(result.is_consistent());
+        assert_eq!(
+            result.current_s3_object_id(),
+            Some(new_version.s3_object_id)
+        );
+        assert_eq!(
+            result.events()[4].explanation(),
+            "this is the latest event of the latest version, so it is the current state"
+        );
+        assert_eq!(
+            result.events()[3].explanation(),
+            "version `3` still exists, but is superseded by the later version `4` with sequencer `5`"
+        );
+
+        // A key without records has no current state.
+        let result: CurrentStateExplanation =
+            response_from_get(state, "/s3/explain?bucket=bucket&key=missing").await;
+        assert!(result.events().is_empty());
+        assert_eq!(result.explanation(), "there are no records for this key");
+    }
+
+    async fn ingest(state: &AppState, events: Vec<FlatS3EventMessage>) {
+        state
+            .database_client()
+            .ingest(EventSourceType::S3(TransposedS3EventMessages::from(
+                FlatS3EventMessages(events),
+            )))
+            .await
+            .unwrap();
+    }
+}
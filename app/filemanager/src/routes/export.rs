@@ -0,0 +1,1008 @@
+//! Route logic for exporting records in columnar formats, or to an S3 destination.
+//!
+
+use std::mem;
+use std::sync::{Arc, LazyLock};
+
+use arrow::array::{
+    ArrayRef, BooleanArray, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::CompletedPart;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Router, extract};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, FixedOffset};
+use futures::channel::mpsc;
+use futures::{SinkExt, TryStreamExt};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use sea_orm::ActiveEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+use url::Url;
+use utoipa::ToSchema;
+
+use crate::clients::aws::s3::{Client, ResponseHeaders};
+use crate::database::entities::s3_object;
+use crate::error::Error::{InvalidQuery, PresignedUrlError, S3Error};
+use crate::error::{Error, Result};
+use crate::queries::list::ListQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, Json, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::key_path::has_path_traversal;
+use crate::routes::list::{ListS3Params, WildcardParams};
+use crate::routes::prefix::PREFIX_DELIMITER;
+use crate::routes::tenant::TenantScope;
+
+/// The content type of Parquet responses.
+pub const PARQUET_CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+/// The number of records written to each Parquet row group.
+pub const EXPORT_BATCH_SIZE: usize = 8192;
+
+/// The number of row groups that can be buffered before waiting for the response to be read.
+const EXPORT_CHANNEL_SIZE: usize = 4;
+
+/// The size of the parts uploaded when exporting to S3. This must be at least the minimum part
+/// size of a multipart upload, which is 5 MiB.
+pub const EXPORT_PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// The timezone used for all timestamp columns.
+const EXPORT_TIMEZONE: &str = "UTC";
+
+/// The schema of exported records. Column names match the fields of the `S3` record returned
+/// by the list API, and should only ever be appended to so that downstream tables stay stable.
+pub static EXPORT_SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+    let timestamp = || DataType::Timestamp(TimeUnit::Microsecond, Some(EXPORT_TIMEZONE.into()));
+
+    Arc::new(Schema::new(vec![
+        Field::new("s3ObjectId", DataType::Utf8, false),
+        Field::new("eventType", DataType::Utf8, false),
+        Field::new("bucket", DataType::Utf8, false),
+        Field::new("key", DataType::Utf8, false),
+        Field::new("versionId", DataType::Utf8, false),
+        Field::new("eventTime", timestamp(), true),
+        Field::new("size", DataType::Int64, true),
+        Field::new("sha256", DataType::Utf8, true),
+        Field::new("lastModifiedDate", timestamp(), true),
+        Field::new("eTag", DataType::Utf8, true),
+        Field::new("storageClass", DataType::Utf8, true),
+        Field::new("sequencer", DataType::Utf8, true),
+        Field::new("isDeleteMarker", DataType::Boolean, false),
+        Field::new("numberDuplicateEvents", DataType::Int64, false),
+        Field::new("attributes", DataType::Utf8, true),
+        Field::new("deletedDate", timestamp(), true),
+        Field::new("deletedSequencer", DataType::Utf8, true),
+        Field::new("numberReordered", DataType::Int64, false),
+        Field::new("ingestId", DataType::Utf8, true),
+        Field::new("isCurrentState", DataType::Boolean, false),
+        Field::new("reason", DataType::Utf8, false),
+        Field::new("archiveStatus", DataType::Utf8, true),
+        Field::new("isAccessible", DataType::Boolean, false),
+        Field::new("isETagMismatch", DataType::Boolean, false),
+        Field::new("serverSideEncryption", DataType::Utf8, true),
+        Field::new("sseKmsKeyId", DataType::Utf8, true),
+        Field::new("tagPresent", DataType::Boolean, true),
+        Field::new("restoreExpiryDate", timestamp(), true),
+        Field::new("isSequencerConflict", DataType::Boolean, false),
+        Field::new("updatedAt", timestamp(), true),
+        Field::new("ingestedAt", timestamp(), true),
+    ]))
+});
+
+/// Convert a set of records into an arrow record batch using the export schema.
+pub fn to_record_batch(records: &[s3_object::Model]) -> Result<RecordBatch> {
+    let strings = |f: fn(&s3_object::Model) -> Option<String>| -> ArrayRef {
+        Arc::new(records.iter().map(f).collect::<StringArray>())
+    };
+    let timestamps = |f: fn(&s3_object::Model) -> Option<DateTime<FixedOffset>>| -> ArrayRef {
+        Arc::new(
+            records
+                .iter()
+                .map(|record| f(record).map(|date| date.timestamp_micros()))
+                .collect::<TimestampMicrosecondArray>()
+                .with_timezone(EXPORT_TIMEZONE),
+        )
+    };
+    let integers = |f: fn(&s3_object::Model) -> Option<i64>| -> ArrayRef {
+        Arc::new(records.iter().map(f).collect::<Int64Array>())
+    };
+    let booleans = |f: fn(&s3_object::Model) -> Option<bool>| -> ArrayRef {
+        Arc::new(records.iter().map(f).collect::<BooleanArray>())
+    };
+
+    let columns = vec![
+        strings(|r| Some(r.s3_object_id.to_string())),
+        strings(|r| Some(r.event_type.to_value())),
+        strings(|r| Some(r.bucket.clone())),
+        strings(|r| Some(r.key.clone())),
+        strings(|r| Some(r.version_id.clone())),
+        timestamps(|r| r.event_time),
+        integers(|r| r.size),
+        strings(|r| r.sha256.clone()),
+        timestamps(|r| r.last_modified_date),
+        strings(|r| r.e_tag.clone()),
+        strings(|r| r.storage_class.as_ref().map(ActiveEnum::to_value)),
+        strings(|r| r.sequencer.clone()),
+        booleans(|r| Some(r.is_delete_marker)),
+        integers(|r| Some(r.number_duplicate_events)),
+        strings(|r| {
+            r.attributes
+                .as_ref()
+                .map(|attributes| attributes.to_string())
+        }),
+        timestamps(|r| r.deleted_date),
+        strings(|r| r.deleted_sequencer.clone()),
+        integers(|r| Some(r.number_reordered)),
+        strings(|r| r.ingest_id.map(|id| id.to_string())),
+        booleans(|r| Some(r.is_current_state)),
+        strings(|r| Some(r.reason.to_value())),
+        strings(|r| r.archive_status.as_ref().map(ActiveEnum::to_value)),
+        booleans(|r| Some(r.is_accessible)),
+        booleans(|r| Some(r.is_e_tag_mismatch)),
+        strings(|r| r.server_side_encryption.clone()),
+        strings(|r| r.sse_kms_key_id.clone()),
+        booleans(|r| r.tag_present),
+        timestamps(|r| r.restore_expiry_date),
+        booleans(|r| Some(r.is_sequencer_conflict)),
+        timestamps(|r| Some(r.updated_at)),
+        timestamps(|r| r.ingested_at),
+    ];
+
+    Ok(RecordBatch::try_new(EXPORT_SCHEMA.clone(), columns)?)
+}
+
+/// Write a batch of records as a row group, and send the bytes produced so far.
+/// Returns false if the response body was dropped.
+async fn send_row_group(
+    writer: &mut ArrowWriter<Vec<u8>>,
+    records: &mut Vec<s3_object::Model>,
+    sender: &mut mpsc::Sender<Result<Vec<u8>>>,
+) -> Result<bool> {
+    writer.write(&to_record_batch(records)?)?;
+    writer.flush()?;
+    records.clear();
+
+    // The writer tracks offsets itself, so it is safe to take the bytes already written.
+    let chunk = std::mem::take(writer.inner_mut());
+    Ok(sender.send(Ok(chunk)).await.is_ok())
+}
+
+/// Write the filtered records to the sender as Parquet chunks.
+async fn send_parquet(
+    state: &AppState,
+    wildcard: WildcardParams,
+    list: ListS3Params,
+    filter: S3ObjectsFilter,
+    sender: &mut mpsc::Sender<Result<Vec<u8>>>,
+) -> Result<()> {
+    let mut records = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter, wildcard.case_sensitive(), list.current_state())?
+    .stream()
+    .await?;
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(vec![], EXPORT_SCHEMA.clone(), Some(properties))?;
+
+    let mut batch = Vec::with_capacity(EXPORT_BATCH_SIZE);
+    while let Some(record) = records.try_next().await? {
+        batch.push(record);
+
+        if batch.len() >= EXPORT_BATCH_SIZE
+            && !send_row_group(&mut writer, &mut batch, sender).await?
+        {
+            // The response body was dropped, so there is nothing left to do.
+            return Ok(());
+        }
+    }
+
+    if !batch.is_empty() && !send_row_group(&mut writer, &mut batch, sender).await? {
+        return Ok(());
+    }
+
+    // Closing the writer produces the footer, which is always required for a valid file.
+    let _ = sender.send(Ok(writer.into_inner()?)).await;
+
+    Ok(())
+}
+
+/// Export records as an Apache Parquet file. This accepts the same filtering parameters as
+/// the list API, and produces one row per record with columns named after the fields of the
+/// `S3` record. Timestamps are stored in UTC with microsecond precision, enum fields are stored
+/// as strings and `attributes` is stored as a JSON string. Records are streamed from the
+/// database, so large exports do not need to be loaded into memory.
+#[utoipa::path(
+    get,
+    path = "/s3/export/parquet",
+    responses(
+        (
+            status = OK,
+            description = "The filtered records as a Parquet file",
+            body = Vec<u8>,
+            content_type = "application/vnd.apache.parquet"
+        ),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, ListS3Params, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn export_parquet_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Response> {
+    let (mut sender, receiver) = mpsc::channel(EXPORT_CHANNEL_SIZE);
+    tokio::spawn(TenantScope::propagate(async move {
+        if let Err(err) = send_parquet(&state, wildcard, list, filter, &mut sender).await {
+            let _ = sender.send(Err::<Vec<u8>, Error>(err)).await;
+        }
+    }));
+
+    Ok((
+        [(CONTENT_TYPE, PARQUET_CONTENT_TYPE)],
+        Body::from_stream(receiver),
+    )
+        .into_response())
+}
+
+/// The format of records exported to S3.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    /// JSON Lines, with one `S3` record per line.
+    #[default]
+    Jsonl,
+    /// CSV with a header row, using the same columns as the Parquet export.
+    Csv,
+}
+
+impl ExportFormat {
+    /// Get the content type of the exported object.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "application/jsonl",
+            ExportFormat::Csv => "text/csv",
+        }
+    }
+
+    /// Write a record in this format to the buffer.
+    fn write(&self, buffer: &mut Vec<u8>, record: &s3_object::Model) -> Result<()> {
+        match self {
+            ExportFormat::Jsonl => {
+                serde_json::to_writer(&mut *buffer, record)?;
+                buffer.push(b'\n');
+            }
+            ExportFormat::Csv => {
+                // Serializing the record gives the same names as the export schema columns.
+                let value = serde_json::to_value(record)?;
+                let row = EXPORT_SCHEMA.fields().iter().map(|field| {
+                    match value.get(field.name()).unwrap_or(&Value::Null) {
+                        Value::Null => String::new(),
+                        Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    }
+                });
+
+                let mut writer = csv::Writer::from_writer(&mut *buffer);
+                writer.write_record(row)?;
+                writer.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the header of this format to the buffer, if it has one.
+    fn write_header(&self, buffer: &mut Vec<u8>) -> Result<()> {
+        if let ExportFormat::Csv = self {
+            let mut writer = csv::Writer::from_writer(&mut *buffer);
+            writer.write_record(EXPORT_SCHEMA.fields().iter().map(|field| field.name()))?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The S3 destination of an export.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportDestination {
+    /// The bucket to write the export to. This must be one of the buckets configured in
+    /// `FILEMANAGER_API_EXPORT_BUCKETS`.
+    bucket: String,
+    /// The key to write the export to. An existing object at the key is overwritten.
+    key: String,
+    /// The format of the export, either `jsonl` or `csv`.
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+impl ExportDestination {
+    /// Create a new export destination.
+    pub fn new(bucket: String, key: String, format: ExportFormat) -> Self {
+        Self {
+            bucket,
+            key,
+            format,
+        }
+    }
+
+    /// Check that the destination is an allowed export bucket, which is visible to the tenant
+    /// of the current request, and that the key is usable as a filename.
+    fn validate(&self, state: &AppState) -> Result<()> {
+        if !state.config().api_export_buckets().contains(&self.bucket) {
+            return Err(InvalidQuery(format!(
+                "bucket `{}` is not an allowed export destination",
+                self.bucket
+            )));
+        }
+        TenantScope::check_bucket(&self.bucket)?;
+
+        if self.key.is_empty() || self.key.ends_with(PREFIX_DELIMITER) {
+            return Err(InvalidQuery(format!(
+                "`{}` is not a valid export key",
+                self.key
+            )));
+        }
+        if has_path_traversal(&self.key) {
+            return Err(InvalidQuery(format!(
+                "`{}` contains a path traversal sequence",
+                self.key
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Get the filename of the exported object.
+    fn filename(&self) -> &str {
+        self.key
+            .rsplit(PREFIX_DELIMITER)
+            .next()
+            .unwrap_or(&self.key)
+    }
+}
+
+/// The result of exporting records to S3.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportResult {
+    /// The bucket that the export was written to.
+    bucket: String,
+    /// The key that the export was written to.
+    key: String,
+    /// The format of the export.
+    format: ExportFormat,
+    /// The number of records that were exported.
+    n_records: u64,
+    /// A presigned URL for downloading the export, which expires after
+    /// `FILEMANAGER_API_PRESIGN_EXPIRY`.
+    #[schema(value_type = String)]
+    url: Url,
+}
+
+impl ExportResult {
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Get the key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the number of records.
+    pub fn n_records(&self) -> u64 {
+        self.n_records
+    }
+
+    /// Get the presigned URL.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+}
+
+/// An upload of an export to S3. Records are uploaded in parts as they are written, so that
+/// large exports do not need to be held in memory. A multipart upload is only started once the
+/// export is larger than a single part, and smaller exports are uploaded with `PutObject`.
+struct ExportUpload<'a> {
+    client: &'a Client,
+    destination: &'a ExportDestination,
+    upload_id: Option<String>,
+    parts: Vec<CompletedPart>,
+}
+
+impl<'a> ExportUpload<'a> {
+    /// Create a new upload to the destination.
+    fn new(client: &'a Client, destination: &'a ExportDestination) -> Self {
+        Self {
+            client,
+            destination,
+            upload_id: None,
+            parts: vec![],
+        }
+    }
+
+    /// Upload a part, starting the multipart upload if this is the first part.
+    async fn upload_part(&mut self, body: Vec<u8>) -> Result<()> {
+        let destination = self.destination;
+        let upload_id = match &self.upload_id {
+            Some(upload_id) => upload_id.clone(),
+            None => {
+                let upload_id = self
+                    .client
+                    .create_multipart_upload(
+                        &destination.key,
+                        &destination.bucket,
+                        destination.format.content_type(),
+                    )
+                    .await?
+                    .upload_id
+                    .ok_or_else(|| S3Error("expected multipart upload id".to_string()))?;
+                self.upload_id = Some(upload_id.clone());
+                upload_id
+            }
+        };
+
+        let part_number = i32::try_from(self.parts.len() + 1)?;
+        let output = self
+            .client
+            .upload_part(
+                &destination.key,
+                &destination.bucket,
+                &upload_id,
+                part_number,
+                ByteStream::from(body),
+            )
+            .await?;
+        self.parts.push(
+            CompletedPart::builder()
+                .set_e_tag(output.e_tag)
+                .part_number(part_number)
+                .build(),
+        );
+
+        Ok(())
+    }
+
+    /// Upload the remaining `body` and complete the upload. The last part of a multipart upload
+    /// can be smaller than the minimum part size.
+    async fn complete(&mut self, body: Vec<u8>) -> Result<()> {
+        let destination = self.destination;
+        let Some(upload_id) = self.upload_id.clone() else {
+            self.client
+                .put_object(
+                    &destination.key,
+                    &destination.bucket,
+                    ByteStream::from(body),
+                    destination.format.content_type(),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        if !body.is_empty() {
+            self.upload_part(body).await?;
+        }
+        self.client
+            .complete_multipart_upload(
+                &destination.key,
+                &destination.bucket,
+                &upload_id,
+                mem::take(&mut self.parts),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Abort the multipart upload if one was started, so that the uploaded parts are removed.
+    /// Errors are logged, because the export has already failed.
+    async fn abort(&self) {
+        let Some(upload_id) = &self.upload_id else {
+            return;
+        };
+
+        let _ = self
+            .client
+            .abort_multipart_upload(&self.destination.key, &self.destination.bucket, upload_id)
+            .await
+            .inspect_err(|err| {
+                warn!(
+                    "failed to abort export upload to {} in {}: {}",
+                    self.destination.key,
+                    self.destination.bucket,
+                    Error::from((err, "AbortMultipartUpload".to_string()))
+                )
+            });
+    }
+}
+
+/// Export the filtered records to an S3 object, either as JSON Lines or as CSV. This accepts
+/// the same filtering parameters as the list API, and exports all records matching the filter
+/// rather than a single page. The destination bucket must be configured as an export bucket.
+/// Returns the destination along with a presigned URL for downloading the export, so that
+/// large results do not need to be returned in the response.
+#[utoipa::path(
+    post,
+    path = "/s3/export",
+    responses(
+        (
+            status = OK,
+            description = "The destination of the export and a presigned URL to download it",
+            body = ExportResult
+        ),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, ListS3Params, S3ObjectsFilter),
+    request_body = ExportDestination,
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn export_to_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter), _): QsQuery<S3ObjectsFilter>,
+    WithRejection(extract::Json(destination), _): Json<ExportDestination>,
+) -> Result<extract::Json<ExportResult>> {
+    destination.validate(&state)?;
+
+    let format = destination.format;
+    let mut upload = ExportUpload::new(state.s3_client(), &destination);
+    let result = async {
+        let mut records = ListQueryBuilder::<_, s3_object::Entity>::new(
+            state.database_client().read_connection_ref(),
+        )
+        .filter_all(filter, wildcard.case_sensitive(), list.current_state())?
+        .stream()
+        .await?;
+
+        let mut buffer = vec![];
+        let mut n_records = 0;
+        format.write_header(&mut buffer)?;
+        while let Some(record) = records.try_next().await? {
+            format.write(&mut buffer, &record)?;
+            n_records += 1;
+
+            if buffer.len() >= EXPORT_PART_SIZE {
+                upload.upload_part(mem::take(&mut buffer)).await?;
+            }
+        }
+
+        upload.complete(buffer).await?;
+        Ok::<_, Error>(n_records)
+    }
+    .await;
+
+    let n_records = match result {
+        Ok(n_records) => n_records,
+        Err(err) => {
+            upload.abort().await;
+            return Err(err);
+        }
+    };
+
+    let client = state.s3_client();
+    let headers = ResponseHeaders::new(
+        format!("attachment; filename=\"{}\"", destination.filename()),
+        Some(format.content_type().to_string()),
+        None,
+    );
+    let url = client
+        .presign_url(
+            &destination.key,
+            &destination.bucket,
+            None,
+            headers,
+            state.config().api_presign_expiry(),
+        )
+        .await
+        .map_err(|err| PresignedUrlError(err.into_service_error().to_string()))?
+        .uri()
+        .parse()?;
+
+    Ok(extract::Json(ExportResult {
+        bucket: destination.bucket,
+        key: destination.key,
+        format,
+        n_records,
+        url,
+    }))
+}
+
+/// The router for exporting records.
+pub fn export_router() -> Router<AppState> {
+    Router::new()
+        .route("/s3/export", post(export_to_s3))
+        .route("/s3/export/parquet", get(export_parquet_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{Int64Type, TimestampMicrosecondType};
+    use std::sync::Mutex;
+
+    use aws_sdk_s3::operation::abort_multipart_upload::AbortMultipartUploadOutput;
+    use aws_sdk_s3::operation::complete_multipart_upload::{
+        CompleteMultipartUploadError, CompleteMultipartUploadOutput,
+    };
+    use aws_sdk_s3::operation::create_multipart_upload::CreateMultipartUploadOutput;
+    use aws_sdk_s3::operation::put_object::PutObjectOutput;
+    use aws_sdk_s3::operation::upload_part::UploadPartOutput;
+    use aws_smithy_mocks::{RuleMode, mock, mock_client};
+    use axum::body::to_bytes;
+    use axum::http::{Method, Request, StatusCode};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::clients::aws::s3;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::env::Config;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::api_router;
+    use crate::routes::list::tests::{mock_get_object, response_from};
+
+    async fn export(state: AppState, uri: &str) -> Vec<RecordBatch> {
+        let response = api_router(state)
+            .unwrap()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], PARQUET_CONTENT_TYPE);
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(bytes)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        reader.map(|batch| batch.unwrap()).collect()
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn export_parquet_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let batches = export(state, "/s3/export/parquet?bucket=1&currentState=false").await;
+        let expected = entries
+            .s3_objects
+            .iter()
+            .filter(|s3| s3.bucket == "1")
+            .collect::<Vec<_>>();
+
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.schema(), *EXPORT_SCHEMA);
+        assert_eq!(batch.num_rows(), expected.len());
+
+        let mut ids = batch
+            .column_by_name("s3ObjectId")
+            .unwrap()
+            .as_string::<i32>()
+            .iter()
+            .map(|id| id.unwrap().to_string())
+            .collect::<Vec<_>>();
+        ids.sort();
+        let mut expected_ids = expected
+            .iter()
+            .map(|s3| s3.s3_object_id.to_string())
+            .collect::<Vec<_>>();
+        expected_ids.sort();
+        assert_eq!(ids, expected_ids);
+
+        for row in 0..batch.num_rows() {
+            let id = batch
+                .column_by_name("s3ObjectId")
+                .unwrap()
+                .as_string::<i32>();
+            let s3 = expected
+                .iter()
+                .find(|s3| s3.s3_object_id.to_string() == id.value(row))
+                .unwrap();
+
+            assert_eq!(
+                batch
+                    .column_by_name("key")
+                    .unwrap()
+                    .as_string::<i32>()
+                    .value(row),
+                s3.key
+            );
+            assert_eq!(
+                batch
+                    .column_by_name("size")
+                    .unwrap()
+                    .as_primitive::<Int64Type>()
+                    .value(row),
+                s3.size.unwrap()
+            );
+            assert_eq!(
+                batch
+                    .column_by_name("lastModifiedDate")
+                    .unwrap()
+                    .as_primitive::<TimestampMicrosecondType>()
+                    .value(row),
+                s3.last_modified_date.unwrap().timestamp_micros()
+            );
+            assert_eq!(
+                batch
+                    .column_by_name("eventType")
+                    .unwrap()
+                    .as_string::<i32>()
+                    .value(row),
+                s3.event_type.to_value()
+            );
+            assert_eq!(
+                batch
+                    .column_by_name("isCurrentState")
+                    .unwrap()
+                    .as_boolean()
+                    .value(row),
+                s3.is_current_state
+            );
+            let attributes = batch
+                .column_by_name("attributes")
+                .unwrap()
+                .as_string::<i32>();
+            assert_eq!(
+                attributes
+                    .is_valid(row)
+                    .then(|| serde_json::from_str::<Value>(attributes.value(row)).unwrap()),
+                s3.attributes
+            );
+        }
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn export_parquet_s3_api_empty(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let batches = export(state, "/s3/export/parquet?bucket=missing").await;
+        assert!(batches.iter().all(|batch| batch.num_rows() == 0));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn export_to_s3_api(pool: PgPool) {
+        let written = Arc::new(Mutex::new(None));
+        let put_object = mock!(aws_sdk_s3::Client::put_object)
+            .match_requests({
+                let written = written.clone();
+                move |req| {
+                    *written.lock().unwrap() = req.body().bytes().map(|bytes| bytes.to_vec());
+                    req.bucket() == Some("exports")
+                        && req.key() == Some("reports/records.csv")
+                        && req.content_type() == Some("text/csv")
+                }
+            })
+            .then_output(|| PutObjectOutput::builder().build());
+        let client = s3::Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                put_object.clone(),
+                mock_get_object("reports/records.csv", "exports", b"")
+            ]
+        ));
+
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_export_buckets: vec!["exports".to_string()],
+                ..Default::default()
+            })
+            .with_s3_client(client);
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let (status, result) = response_from::<ExportResult>(
+            state.clone(),
+            "/s3/export?bucket=1",
+            Method::POST,
+            Body::new(
+                json!({ "bucket": "exports", "key": "reports/records.csv", "format": "csv" })
+                    .to_string(),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(put_object.num_calls(), 1);
+
+        let expected = entries
+            .s3_objects
+            .iter()
+            .filter(|s3| s3.bucket == "1" && s3.is_current_state)
+            .collect::<Vec<_>>();
+        assert_eq!(result.bucket(), "exports");
+        assert_eq!(result.key(), "reports/records.csv");
+        assert_eq!(result.n_records(), expected.len() as u64);
+        assert_eq!(
+            result.url().host_str(),
+            Some("exports.s3.us-east-1.amazonaws.com")
+        );
+        assert_eq!(result.url().path(), "/reports/records.csv");
+        assert!(
+            result
+                .url()
+                .query()
+                .unwrap()
+                .contains("response-content-disposition=attachment")
+        );
+
+        // The export object has a header row, followed by one row per record.
+        let written = written.lock().unwrap().take().unwrap();
+        let mut reader = csv::Reader::from_reader(written.as_slice());
+        assert_eq!(
+            reader.headers().unwrap().iter().collect::<Vec<_>>(),
+            EXPORT_SCHEMA
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect::<Vec<_>>()
+        );
+        let mut ids = reader
+            .records()
+            .map(|record| record.unwrap()[0].to_string())
+            .collect::<Vec<_>>();
+        ids.sort();
+        let mut expected_ids = expected
+            .iter()
+            .map(|s3| s3.s3_object_id.to_string())
+            .collect::<Vec<_>>();
+        expected_ids.sort();
+        assert_eq!(ids, expected_ids);
+
+        // Destinations outside the allowed export buckets are rejected.
+        let (status, _) = response_from::<Value>(
+            state,
+            "/s3/export?bucket=1",
+            Method::POST,
+            Body::new(json!({ "bucket": "1", "key": "records.jsonl" }).to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(put_object.num_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn export_upload_multipart() {
+        let create = mock!(aws_sdk_s3::Client::create_multipart_upload)
+            .match_requests(|req| {
+                req.bucket() == Some("exports")
+                    && req.key() == Some("records.jsonl")
+                    && req.content_type() == Some("application/jsonl")
+            })
+            .then_output(|| {
+                CreateMultipartUploadOutput::builder()
+                    .upload_id("upload")
+                    .build()
+            });
+        let upload_part = mock!(aws_sdk_s3::Client::upload_part)
+            .match_requests(|req| req.upload_id() == Some("upload"))
+            .then_output(|| UploadPartOutput::builder().e_tag("\"e_tag\"").build());
+        let complete = mock!(aws_sdk_s3::Client::complete_multipart_upload)
+            .match_requests(|req| {
+                let parts = req
+                    .multipart_upload()
+                    .map(|upload| upload.parts())
+                    .unwrap_or_default();
+                req.upload_id() == Some("upload")
+                    && parts
+                        .iter()
+                        .map(|part| part.part_number())
+                        .eq([Some(1), Some(2)])
+            })
+            .then_output(|| CompleteMultipartUploadOutput::builder().build());
+        let put_object = mock!(aws_sdk_s3::Client::put_object)
+            .then_output(|| PutObjectOutput::builder().build());
+        let client = s3::Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                create.clone(),
+                upload_part.clone(),
+                complete.clone(),
+                put_object.clone()
+            ]
+        ));
+
+        // An export that fits in a single part is uploaded with `PutObject`.
+        let destination = ExportDestination::new(
+            "exports".to_string(),
+            "records.jsonl".to_string(),
+            ExportFormat::Jsonl,
+        );
+        ExportUpload::new(&client, &destination)
+            .complete(vec![0])
+            .await
+            .unwrap();
+        assert_eq!(put_object.num_calls(), 1);
+        assert_eq!(create.num_calls(), 0);
+
+        // Larger exports use a multipart upload, with the remainder uploaded as the last part.
+        let mut upload = ExportUpload::new(&client, &destination);
+        upload.upload_part(vec![0]).await.unwrap();
+        upload.complete(vec![0]).await.unwrap();
+        assert_eq!(create.num_calls(), 1);
+        assert_eq!(upload_part.num_calls(), 2);
+        assert_eq!(complete.num_calls(), 1);
+        assert_eq!(put_object.num_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn export_upload_abort() {
+        let create = mock!(aws_sdk_s3::Client::create_multipart_upload).then_output(|| {
+            CreateMultipartUploadOutput::builder()
+                .upload_id("upload")
+                .build()
+        });
+        let upload_part = mock!(aws_sdk_s3::Client::upload_part)
+            .then_output(|| UploadPartOutput::builder().e_tag("\"e_tag\"").build());
+        let complete = mock!(aws_sdk_s3::Client::complete_multipart_upload)
+            .then_error(|| CompleteMultipartUploadError::unhandled("unhandled"));
+        let abort = mock!(aws_sdk_s3::Client::abort_multipart_upload)
+            .match_requests(|req| req.upload_id() == Some("upload"))
+            .then_output(|| AbortMultipartUploadOutput::builder().build());
+        let client = s3::Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                create.clone(),
+                upload_part.clone(),
+                complete.clone(),
+                abort.clone()
+            ]
+        ));
+
+        let destination = ExportDestination::new(
+            "exports".to_string(),
+            "records.jsonl".to_string(),
+            ExportFormat::Jsonl,
+        );
+
+        // Nothing is aborted if a multipart upload was not started.
+        ExportUpload::new(&client, &destination).abort().await;
+        assert_eq!(abort.num_calls(), 0);
+
+        // A failed multipart upload is aborted so that the uploaded parts are removed.
+        let mut upload = ExportUpload::new(&client, &destination);
+        upload.upload_part(vec![0]).await.unwrap();
+        assert!(upload.complete(vec![]).await.is_err());
+        upload.abort().await;
+        assert_eq!(upload_part.num_calls(), 1);
+        assert_eq!(abort.num_calls(), 1);
+    }
+}
@@ -4,13 +4,16 @@
 use crate::database::entities::sea_orm_active_enums::{
     ArchiveStatus, EventType, Reason, StorageClass,
 };
+use crate::error::Error::InvalidQuery;
 use crate::routes::filter::wildcard::{Wildcard, WildcardEither};
 use sea_orm::prelude::{DateTimeWithTimeZone, Json};
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Map;
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 use std::collections::HashMap;
-use std::fmt::Display;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
@@ -55,6 +58,116 @@ pub enum Join {
     And,
 }
 
+/// The origin of a record, which groups reasons by whether the record was created by a crawl or
+/// by an S3 event.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ToSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Origin {
+    /// Records created by a crawl or an inventory, with a `Crawl`, `CrawlRestored` or
+    /// `CrawlDeleteMarker` reason.
+    Crawl,
+    /// Records created by S3 events, which includes all other reasons, including `Unknown`.
+    Event,
+}
+
+impl Origin {
+    /// The reasons for records that originate from a crawl.
+    pub fn crawl_reasons() -> [Reason; 3] {
+        [
+            Reason::Crawl,
+            Reason::CrawlRestored,
+            Reason::CrawlDeleteMarker,
+        ]
+    }
+}
+
+/// A comparison operator used by count filters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    #[default]
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl Comparison {
+    /// The comparison symbol, which is used as the prefix of a count filter.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Gt => ">",
+            Comparison::Gte => ">=",
+            Comparison::Lt => "<",
+            Comparison::Lte => "<=",
+        }
+    }
+}
+
+/// A count with an optional comparison operator prefix, e.g. `1`, `>1` or `<=3`. A count
+/// without an operator matches exactly.
+#[derive(SerializeDisplay, DeserializeFromStr, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountComparison {
+    comparison: Comparison,
+    count: u64,
+}
+
+impl CountComparison {
+    /// Create a new count comparison.
+    pub fn new(comparison: Comparison, count: u64) -> Self {
+        Self { comparison, count }
+    }
+
+    /// Get the comparison operator.
+    pub fn comparison(&self) -> Comparison {
+        self.comparison
+    }
+
+    /// Get the count.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl FromStr for CountComparison {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> crate::error::Result<Self> {
+        // Longer operators are checked first so that `>=` is not parsed as `>`.
+        let (comparison, count) = [
+            Comparison::Gte,
+            Comparison::Lte,
+            Comparison::Gt,
+            Comparison::Lt,
+            Comparison::Eq,
+        ]
+        .into_iter()
+        .find_map(|comparison| {
+            s.strip_prefix(comparison.symbol())
+                .map(|count| (comparison, count))
+        })
+        .unwrap_or((Comparison::Eq, s));
+
+        let count = count.trim().parse().map_err(|err| {
+            InvalidQuery(format!(
+                "expected a count with an optional `=`, `>`, `>=`, `<` or `<=` prefix: {err}"
+            ))
+        })?;
+
+        Ok(Self::new(comparison, count))
+    }
+}
+
+impl Display for CountComparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.comparison {
+            Comparison::Eq => write!(f, "{}", self.count),
+            comparison => write!(f, "{}{}", comparison.symbol(), self.count),
+        }
+    }
+}
+
 impl<T> From<FilterJoin<T>> for FilterJoinMerged<T> {
     fn from(join: FilterJoin<T>) -> Self {
         match join {
@@ -132,7 +245,8 @@ pub struct S3ObjectsFilter {
     /// Query by event type.
     #[param(nullable = false, required = false)]
     pub(crate) event_type: Option<EventType>,
-    /// Query by bucket. Supports wildcards.
+    /// Query by bucket. Supports wildcards. Setting `caseSensitive=false` matches buckets
+    /// case-insensitively, even if there is no wildcard.
     /// Repeated parameters with `[]` are joined with an `or` conditions by default.
     /// Use `[or][]` or `[and][]` to explicitly set the joining logic.
     #[param(nullable = false, required = false, value_type = FilterJoin<Wildcard>)]
@@ -142,6 +256,13 @@ pub struct S3ObjectsFilter {
     /// Use `[or][]` or `[and][]` to explicitly set the joining logic.
     #[param(nullable = false, required = false, value_type = FilterJoin<Wildcard>)]
     pub(crate) key: FilterJoinMerged<Wildcard>,
+    /// Query by the end of the key, e.g. `keySuffix=.bam` or `keySuffix=.fastq.gz`. Unlike
+    /// `key`, this does not interpret wildcards, and matches case-insensitively if
+    /// `caseSensitive=false`.
+    /// Repeated parameters with `[]` are joined with an `or` conditions by default.
+    /// Use `[or][]` or `[and][]` to explicitly set the joining logic.
+    #[param(nullable = false, required = false, value_type = FilterJoin<String>)]
+    pub(crate) key_suffix: FilterJoinMerged<String>,
     /// Query by version_id. Supports wildcards.
     /// Repeated parameters with `[]` are joined with an `or` conditions by default.
     /// Use `[or][]` or `[and][]` to explicitly set the joining logic.
@@ -186,6 +307,11 @@ pub struct S3ObjectsFilter {
     /// an `or` conditions by default. Use `[or][]` or `[and][]` to explicitly set the joining logic.
     #[param(nullable = false, required = false, value_type = FilterJoin<Reason>)]
     pub(crate) reason: FilterJoinMerged<Reason>,
+    /// Query by the origin of the record, either `crawl` or `event`. This is a higher-level
+    /// grouping of `reason`, where `crawl` matches the `Crawl`, `CrawlRestored` and
+    /// `CrawlDeleteMarker` reasons and `event` matches all other reasons.
+    #[param(nullable = false, required = false)]
+    pub(crate) origin: Option<Origin>,
     /// Query by the archive status. The archive status can be `DeepArchiveAccess` or `ArchiveAccess`
     /// if the storage class is also `IntelligentTiering`. Repeated parameters with `[]` are joined
     /// with an `or` conditions by default. Use `[or][]` or `[and][]` to explicitly set the joining
@@ -203,6 +329,71 @@ pub struct S3ObjectsFilter {
     /// Use `[or][]` or `[and][]` to explicitly set the joining logic.
     #[param(nullable = false, required = false, value_type = FilterJoin<Uuid>)]
     pub(crate) ingest_id: FilterJoinMerged<Uuid>,
+    /// Query by the server-side encryption algorithm, e.g. `AES256` or `aws:kms`.
+    /// Repeated parameters with `[]` are joined with an `or` conditions by default.
+    /// Use `[or][]` or `[and][]` to explicitly set the joining logic.
+    #[param(nullable = false, required = false, value_type = FilterJoin<String>)]
+    pub(crate) server_side_encryption: FilterJoinMerged<String>,
+    /// Query by the KMS key id used to encrypt the object.
+    /// Repeated parameters with `[]` are joined with an `or` conditions by default.
+    /// Use `[or][]` or `[and][]` to explicitly set the joining logic.
+    #[param(nullable = false, required = false, value_type = FilterJoin<String>)]
+    pub(crate) sse_kms_key_id: FilterJoinMerged<String>,
+    /// Query by whether the object has server-side encryption. Setting this to false will show
+    /// records that are unencrypted or that have an unknown encryption status.
+    #[param(nullable = false, required = false)]
+    pub(crate) is_encrypted: Option<bool>,
+    /// Query records which have an `ingestId` that was assigned by the filemanager because the
+    /// ingest id tag was missing on the object in S3 when it was collected. This is useful to find
+    /// gaps in tagging without querying S3. Setting this to false will show records where the tag
+    /// was present, or where it is unknown whether the tag was present.
+    #[param(nullable = false, required = false)]
+    pub(crate) tag_missing: Option<bool>,
+    /// Query records which are flagged as a sequencer conflict, where another event with the same
+    /// sequencer had a different ETag or size. These are only flagged when the ingester resolves
+    /// sequencer conflicts.
+    #[param(nullable = false, required = false)]
+    pub(crate) is_sequencer_conflict: Option<bool>,
+    /// Query records where the `last_modified_date` and `event_time` diverge by more than this
+    /// number of seconds, in either direction. This is a diagnostic filter which is useful to
+    /// find records affected by clock skew or event reordering. Records which are missing either
+    /// timestamp are not returned.
+    #[param(nullable = false, required = false, minimum = 0)]
+    pub(crate) event_time_divergence: Option<u64>,
+    /// Query current state records with an `event_time` before this date. Because only current
+    /// state records are returned, no subsequent events have occurred for these objects. This is
+    /// useful to find stale objects that may have been forgotten.
+    #[param(nullable = false, required = false, value_type = String, format = DateTime)]
+    pub(crate) stale_before: Option<DateTimeWithTimeZone>,
+    /// Query current state records which are missing metadata that is collected using
+    /// `HeadObject`, i.e. records with a null `storage_class` or `last_modified_date`. These
+    /// records can be targeted for re-collection. Setting this to false will show records that
+    /// have both fields.
+    #[param(nullable = false, required = false)]
+    pub(crate) missing_metadata: Option<bool>,
+    /// Exclude folder placeholders, i.e. zero-byte objects with a key that ends in `/`. Some tools
+    /// create these to represent folders, and they are not real objects. When listing or counting
+    /// records, this defaults to the `FILEMANAGER_API_EXCLUDE_FOLDER_PLACEHOLDERS` option.
+    #[param(nullable = false, required = false)]
+    pub(crate) exclude_folder_placeholders: Option<bool>,
+    /// Query records where the `last_modified_date` is more than this number of days ago.
+    /// Records without a `last_modified_date` are not returned. This can be combined with
+    /// `storageClass` to find old objects which are candidates for a different storage tier.
+    #[param(nullable = false, required = false, minimum = 0)]
+    pub(crate) unmodified_for_days: Option<u64>,
+    /// Query records by the number of distinct versions of their bucket and key, e.g.
+    /// `versionCount=1` for keys with exactly one version, or `versionCount=>1` (URL encoded as
+    /// `versionCount=%3E1`) for keys with more than one version. The `=`, `>`, `>=`, `<` and
+    /// `<=` operators are supported. Delete markers count as versions. The count includes all
+    /// records for the key, so this can be combined with `currentState` to find current objects.
+    #[param(nullable = false, required = false, value_type = String)]
+    pub(crate) version_count: Option<CountComparison>,
+    /// Apply a namespace to the top-level keys of the `attributes` filter. This allows querying
+    /// attributes written with the same `attributeNamespace` on update, e.g.
+    /// `attributeNamespace=team1&attributes[portalRunId]=...` matches the `team1.portalRunId`
+    /// attribute.
+    #[param(nullable = false, required = false)]
+    pub(crate) attribute_namespace: Option<String>,
     /// Query by JSON attributes. Supports nested syntax to access inner
     /// fields, e.g. `attributes[attribute_id]=...`. This only deserializes
     /// into string fields, and does not support other JSON types. E.g.
@@ -212,6 +403,51 @@ pub struct S3ObjectsFilter {
     pub(crate) attributes: Option<Json>,
 }
 
+/// Apply a namespace to an attribute key, e.g. `portalRunId` becomes `team1.portalRunId`.
+pub fn namespace_attribute_key(namespace: &str, key: &str) -> String {
+    format!("{namespace}.{key}")
+}
+
+/// Apply a namespace to the top-level keys of attributes.
+pub fn namespace_attributes(namespace: &str, attributes: Json) -> Json {
+    match attributes {
+        Json::Object(object) => Json::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (namespace_attribute_key(namespace, &key), value))
+                .collect(),
+        ),
+        attributes => attributes,
+    }
+}
+
+impl S3ObjectsFilter {
+    /// Set whether folder placeholders are excluded, if the filter does not already set it.
+    pub fn with_default_exclude_folder_placeholders(mut self, exclude: bool) -> Self {
+        self.exclude_folder_placeholders.get_or_insert(exclude);
+        self
+    }
+
+    /// Get a summary of the fields that are set on this filter, without their values. This is
+    /// safe to log as it does not contain any keys or attributes.
+    pub fn summary(&self) -> String {
+        let Ok(Json::Object(fields)) = serde_json::to_value(self) else {
+            return String::new();
+        };
+
+        fields
+            .into_iter()
+            .filter(|(_, value)| match value {
+                Json::Null => false,
+                Json::Object(object) => !object.is_empty(),
+                _ => true,
+            })
+            .map(|(field, _)| field)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,6 +466,7 @@ mod tests {
         let qs = "\
         eventType=Deleted&\
         key=key1&\
+        keySuffix=.bam&\
         bucket=bucket1&\
         versionId=version_id1&\
         eventTime=1970-01-02T00:00:00Z&\
@@ -240,9 +477,22 @@ mod tests {
         storageClass=IntelligentTiering&\
         isDeleteMarker=true&\
         reason=CreatedPut&\
+        origin=event&\
         archiveStatus=DeepArchiveAccess&\
         isAccessible=true&\
         ingestId=00000000-0000-0000-0000-000000000000&\
+        serverSideEncryption=aws:kms&\
+        sseKmsKeyId=key&\
+        isEncrypted=true&\
+        tagMissing=true&\
+        isSequencerConflict=true&\
+        eventTimeDivergence=60&\
+        staleBefore=1970-01-02T00:00:00Z&\
+        missingMetadata=true&\
+        excludeFolderPlaceholders=true&\
+        unmodifiedForDays=30&\
+        versionCount=%3E%3D2&\
+        attributeNamespace=team1&\
         attributes[attributeId]=id\
         ";
         let params: S3ObjectsFilter = serde_qs::from_str(qs).unwrap();
@@ -252,6 +502,7 @@ mod tests {
             S3ObjectsFilter {
                 event_type: Some(EventType::Deleted),
                 key: vec![Wildcard::new("key1".to_string())].into(),
+                key_suffix: vec![".bam".to_string()].into(),
                 bucket: vec![Wildcard::new("bucket1".to_string())].into(),
                 version_id: vec![Wildcard::new("version_id1".to_string())].into(),
                 event_time: vec![WildcardEither::Or("1970-01-02T00:00:00Z".parse().unwrap())]
@@ -266,9 +517,22 @@ mod tests {
                 storage_class: vec![StorageClass::IntelligentTiering].into(),
                 is_delete_marker: Some(true),
                 reason: vec![Reason::CreatedPut].into(),
+                origin: Some(Origin::Event),
                 archive_status: vec![ArchiveStatus::DeepArchiveAccess].into(),
                 is_accessible: Some(true),
                 ingest_id: vec![Uuid::nil()].into(),
+                server_side_encryption: vec!["aws:kms".to_string()].into(),
+                sse_kms_key_id: vec!["key".to_string()].into(),
+                is_encrypted: Some(true),
+                tag_missing: Some(true),
+                is_sequencer_conflict: Some(true),
+                event_time_divergence: Some(60),
+                stale_before: Some("1970-01-02T00:00:00Z".parse().unwrap()),
+                missing_metadata: Some(true),
+                exclude_folder_placeholders: Some(true),
+                unmodified_for_days: Some(30),
+                version_count: Some(CountComparison::new(Comparison::Gte, 2)),
+                attribute_namespace: Some("team1".to_string()),
                 attributes: Some(json!({"attributeId": "id"}))
             }
         );
@@ -279,6 +543,7 @@ mod tests {
         let qs = "\
         eventType=Created&\
         key[]=key1&key[]=key2&\
+        keySuffix[]=.bam&keySuffix[]=.cram&\
         bucket[]=bucket1&bucket[]=bucket2&\
         versionId[]=version_id1&versionId[]=version_id2&\
         eventTime[]=1970-01-02T00:00:00Z&eventTime[]=1970-01-02T00:00:01Z&\
@@ -303,6 +568,7 @@ mod tests {
         let qs = "\
         eventType=Created&\
         key[and][]=key1&key[and][]=key2&\
+        keySuffix[and][]=.bam&keySuffix[and][]=.cram&\
         bucket[and][]=bucket1&bucket[and][]=bucket2&\
         versionId[and][]=version_id1&versionId[and][]=version_id2&\
         eventTime[and][]=1970-01-02T00:00:00Z&eventTime[and][]=1970-01-02T00:00:01Z&\
@@ -327,6 +593,7 @@ mod tests {
         let qs = "\
         eventType=Created&\
         key[or][]=key1&key[or][]=key2&\
+        keySuffix[or][]=.bam&keySuffix[or][]=.cram&\
         bucket[or][]=bucket1&bucket[or][]=bucket2&\
         versionId[or][]=version_id1&versionId[or][]=version_id2&\
         eventTime[or][]=1970-01-02T00:00:00Z&eventTime[or][]=1970-01-02T00:00:01Z&\
@@ -368,6 +635,11 @@ mod tests {
                     ]
                 )])
                 .into(),
+                key_suffix: HashMap::from_iter(vec![(
+                    join,
+                    vec![".bam".to_string(), ".cram".to_string()]
+                )])
+                .into(),
                 bucket: HashMap::from_iter(vec![(
                     join,
                     vec![
@@ -407,15 +679,71 @@ mod tests {
                     vec![Reason::CreatedPut, Reason::CreatedPost]
                 )])
                 .into(),
+                origin: None,
                 archive_status: HashMap::from_iter(vec![]).into(),
                 is_delete_marker: Some(true),
                 is_accessible: Some(false),
                 ingest_id: HashMap::from_iter(vec![(join, vec![Uuid::nil(), Uuid::max()])]).into(),
+                server_side_encryption: HashMap::from_iter(vec![]).into(),
+                sse_kms_key_id: HashMap::from_iter(vec![]).into(),
+                is_encrypted: None,
+                tag_missing: None,
+                is_sequencer_conflict: None,
+                event_time_divergence: None,
+                stale_before: None,
+                missing_metadata: None,
+                exclude_folder_placeholders: None,
+                unmodified_for_days: None,
+                version_count: None,
+                attribute_namespace: None,
                 attributes: Some(json!({"attributeId": "id1"}))
             }
         );
     }
 
+    #[test]
+    fn parse_count_comparison() {
+        for (input, comparison, count) in [
+            ("1", Comparison::Eq, 1),
+            ("=1", Comparison::Eq, 1),
+            (">1", Comparison::Gt, 1),
+            (">=2", Comparison::Gte, 2),
+            ("<3", Comparison::Lt, 3),
+            ("<=3", Comparison::Lte, 3),
+        ] {
+            let parsed = CountComparison::from_str(input).unwrap();
+            assert_eq!(parsed, CountComparison::new(comparison, count));
+            assert_eq!(
+                CountComparison::from_str(&parsed.to_string()).unwrap(),
+                parsed
+            );
+        }
+
+        assert!(CountComparison::from_str(">").is_err());
+        assert!(CountComparison::from_str("!=1").is_err());
+        assert!(CountComparison::from_str("-1").is_err());
+    }
+
+    #[test]
+    fn filter_summary() {
+        let qs = "key=secret&bucket[]=bucket1&bucket[]=bucket2&isAccessible=true&attributes[attributeId]=id";
+        let params: S3ObjectsFilter = serde_qs::from_str(qs).unwrap();
+
+        assert_eq!(params.summary(), "bucket,key,isAccessible,attributes");
+        assert_eq!(S3ObjectsFilter::default().summary(), "");
+    }
+
+    #[test]
+    fn namespace_attributes_keys() {
+        assert_eq!(
+            namespace_attributes(
+                "team1",
+                json!({"portalRunId": "id", "nested": {"attributeId": "id"}})
+            ),
+            json!({"team1.portalRunId": "id", "team1.nested": {"attributeId": "id"}})
+        );
+    }
+
     #[test]
     fn deserialize_attribute_only_filter() {
         let qs = "key=key&bucket=bucket&attributeId=attributeId&nestedId[attributeId]=wildcard*";
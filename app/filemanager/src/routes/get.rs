@@ -1,27 +1,44 @@
 //! Route logic for get API calls.
 //!
 
+use std::slice;
+
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::types::StorageClass::Standard;
 use axum::extract::{Request, State};
+use axum::http::HeaderMap;
 use axum::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
-use axum::routing::get;
+use axum::routing::{get, post};
 use axum::{Json, Router, extract};
 use axum_extra::extract::WithRejection;
-use sea_orm::{ConnectionTrait, TransactionTrait};
+use sea_orm::ConnectionTrait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use url::Url;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::database::entities::s3_object;
 use crate::database::entities::s3_object::Model as S3;
-use crate::error::Error::ExpectedSomeValue;
-use crate::error::Result;
+use crate::database::entities::sea_orm_active_enums::{ArchiveStatus, StorageClass};
+use crate::error::Error::{ExpectedSomeValue, InvalidQuery};
+use crate::error::{Error, Result};
+use crate::events::aws::StorageClass as AwsStorageClass;
+use crate::events::aws::collecter::Collecter;
+use crate::events::aws::message::quote_e_tag;
 use crate::queries::get::GetQueryBuilder;
 use crate::queries::list::ListQueryBuilder;
 use crate::routes::AppState;
+use crate::routes::backfill::backfill_checksums;
 use crate::routes::error::{ErrorStatusCode, Path, Query};
 use crate::routes::filter::S3ObjectsFilter;
 use crate::routes::filter::wildcard::Wildcard;
 use crate::routes::header::HeaderParser;
-use crate::routes::presign::{PresignedParams, PresignedUrlBuilder};
+use crate::routes::list::{AnnotatedS3, ETagFormatParams, EventCountParams, TimezoneParams};
+use crate::routes::presign::{
+    MAX_PRESIGN_ENTRIES, PresignEntry, PresignEntryResult, PresignedParams, PresignedUrlBuilder,
+    ResponseHeadersConfig,
+};
 
 async fn get_s3_from_connection<C>(
     connection: &C,
@@ -45,37 +62,259 @@ where
     get,
     path = "/s3/{id}",
     responses(
-        (status = OK, description = "The s3_object for the given id", body = S3),
+        (status = OK, description = "The s3_object for the given id", body = AnnotatedS3),
         ErrorStatusCode,
     ),
+    params(EventCountParams, ETagFormatParams, TimezoneParams),
     context_path = "/api/v1",
     tag = "get",
 )]
-pub async fn get_s3_by_id(state: State<AppState>, id: Path<Uuid>) -> Result<Json<S3>> {
-    get_s3_from_connection(state.database_client().connection_ref(), id).await
+pub async fn get_s3_by_id(
+    state: State<AppState>,
+    id: Path<Uuid>,
+    WithRejection(extract::Query(event_count), _): Query<EventCountParams>,
+    WithRejection(extract::Query(e_tag_format), _): Query<ETagFormatParams>,
+    WithRejection(extract::Query(timezone), _): Query<TimezoneParams>,
+) -> Result<Json<AnnotatedS3>> {
+    let timezone = timezone.timezone()?;
+    let connection = state.database_client().read_connection_ref();
+    let Json(response) = get_s3_from_connection(connection, id).await?;
+    let id = response.s3_object_id;
+    backfill_checksums(&state, slice::from_ref(&response));
+    let response = TimezoneParams::format(timezone, e_tag_format.format(response));
+
+    let response = AnnotatedS3::annotate(connection, vec![response], &event_count)
+        .await?
+        .pop()
+        .ok_or_else(|| ExpectedSomeValue(id))?;
+
+    Ok(Json(response))
 }
 
-/// Implementation of presigning a single URL by id.
-async fn presign_url_by_id(
+/// Get only the attributes of an s3_object given it's id. This returns null if the s3_object
+/// has no attributes.
+#[utoipa::path(
+    get,
+    path = "/s3/{id}/attributes",
+    responses(
+        (status = OK, description = "The attributes of the s3_object for the given id", body = Option<Value>),
+        ErrorStatusCode,
+    ),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn get_s3_attributes_by_id(
+    state: State<AppState>,
+    WithRejection(extract::Path(id), _): Path<Uuid>,
+) -> Result<Json<Option<Value>>> {
+    let query = GetQueryBuilder::new(state.database_client().read_connection_ref());
+
+    Ok(Json(
+        query
+            .get_s3_attributes_by_id(id)
+            .await?
+            .ok_or_else(|| ExpectedSomeValue(id))?,
+    ))
+}
+
+/// A field of an s3_object stored in the database compared with the live value in S3.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldComparison<T> {
+    /// The value stored in the database.
+    pub(crate) database: Option<T>,
+    /// The live value in S3.
+    pub(crate) live: Option<T>,
+    /// Whether the database value matches the live value.
+    pub(crate) is_match: bool,
+}
+
+impl<T: PartialEq> FieldComparison<T> {
+    /// Compare a database and live value.
+    pub fn new(database: Option<T>, live: Option<T>) -> Self {
+        let is_match = database == live;
+        Self {
+            database,
+            live,
+            is_match,
+        }
+    }
+
+    /// Get whether the values match.
+    pub fn is_match(&self) -> bool {
+        self.is_match
+    }
+}
+
+/// The fields of an s3_object stored in the database compared with the live object in S3.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveComparison {
+    /// The id of the s3_object.
+    pub(crate) s3_object_id: Uuid,
+    /// The bucket of the object.
+    pub(crate) bucket: String,
+    /// The key of the object.
+    pub(crate) key: String,
+    /// The version id of the object.
+    pub(crate) version_id: String,
+    /// Whether the object exists in S3.
+    pub(crate) exists: bool,
+    /// Whether the object exists and all fields match.
+    pub(crate) is_match: bool,
+    /// The size of the object.
+    pub(crate) size: FieldComparison<i64>,
+    /// The quoted ETag of the object.
+    pub(crate) e_tag: FieldComparison<String>,
+    /// The storage class of the object.
+    pub(crate) storage_class: FieldComparison<StorageClass>,
+    /// The archive status of the object.
+    pub(crate) archive_status: FieldComparison<ArchiveStatus>,
+    /// The ingest id of the record, compared with the ingest id tag on the object.
+    pub(crate) ingest_id: FieldComparison<String>,
+}
+
+impl LiveComparison {
+    /// Get whether the object exists in S3.
+    pub fn exists(&self) -> bool {
+        self.exists
+    }
+
+    /// Get whether all fields match.
+    pub fn is_match(&self) -> bool {
+        self.is_match
+    }
+
+    /// Get the size comparison.
+    pub fn size(&self) -> &FieldComparison<i64> {
+        &self.size
+    }
+
+    /// Get the ETag comparison.
+    pub fn e_tag(&self) -> &FieldComparison<String> {
+        &self.e_tag
+    }
+
+    /// Get the storage class comparison.
+    pub fn storage_class(&self) -> &FieldComparison<StorageClass> {
+        &self.storage_class
+    }
+
+    /// Get the archive status comparison.
+    pub fn archive_status(&self) -> &FieldComparison<ArchiveStatus> {
+        &self.archive_status
+    }
+
+    /// Get the ingest id comparison.
+    pub fn ingest_id(&self) -> &FieldComparison<String> {
+        &self.ingest_id
+    }
+}
+
+/// Compare an s3_object given it's id with the live object in S3. This calls `HeadObject` and
+/// `GetObjectTagging` on the object version, and returns the database and live values of the
+/// size, ETag, storage class, archive status and ingest id tag, along with whether each field
+/// matches. If the object no longer exists in S3, all live values are null.
+#[utoipa::path(
+    get,
+    path = "/s3/{id}/compare-live",
+    responses(
+        (status = OK, description = "The comparison of the s3_object with the live object", body = LiveComparison),
+        ErrorStatusCode,
+    ),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn compare_live_s3_by_id(
     state: State<AppState>,
     id: Path<Uuid>,
-    presigned: Query<PresignedParams>,
-    request: Request,
-    access_key_secret_id: Option<String>,
-) -> Result<Json<Option<Url>>> {
-    let txn = state.database_client().connection_ref().begin().await?;
+) -> Result<Json<LiveComparison>> {
+    let Json(s3) =
+        get_s3_from_connection(state.database_client().read_connection_ref(), id).await?;
+    let client = state.s3_client();
 
-    let Json(response) = get_s3_from_connection(&txn, id).await?;
+    let head = match client
+        .head_object(&s3.key, &s3.bucket, &s3.version_id)
+        .await
+    {
+        // Archived objects cannot have their checksum retrieved, but the rest of the metadata
+        // is still available.
+        Err(err) if Collecter::is_invalid_object_state(&err) => {
+            client
+                .head_object_without_checksum(&s3.key, &s3.bucket, &s3.version_id)
+                .await
+        }
+        head => head,
+    };
+    let head = match head {
+        Ok(head) => Some(head),
+        Err(err) if Collecter::is_not_found(&err) => None,
+        Err(err) => return Err(Error::from((err, "HeadObject".to_string()))),
+    };
+
+    let tag = match head {
+        Some(_) => client
+            .get_object_tagging(&s3.key, &s3.bucket, &s3.version_id)
+            .await
+            .map_err(|err| Error::from((err, "GetObjectTagging".to_string())))?
+            .tag_set
+            .into_iter()
+            .find(|tag| tag.key == state.config().ingester_tag_name())
+            .map(|tag| tag.value),
+        None => None,
+    };
 
+    let exists = head.is_some();
+    let head = head.unwrap_or_else(|| HeadObjectOutput::builder().build());
+
+    // S3 does not return a storage class for standard objects.
+    let live_storage_class = exists
+        .then(|| AwsStorageClass::from_aws(head.storage_class.unwrap_or(Standard)))
+        .flatten()
+        .map(AwsStorageClass::to_database);
+
+    let comparison = LiveComparison {
+        s3_object_id: s3.s3_object_id,
+        bucket: s3.bucket,
+        key: s3.key,
+        version_id: s3.version_id,
+        exists,
+        is_match: false,
+        size: FieldComparison::new(s3.size, head.content_length),
+        e_tag: FieldComparison::new(s3.e_tag.map(quote_e_tag), head.e_tag.map(quote_e_tag)),
+        storage_class: FieldComparison::new(s3.storage_class, live_storage_class),
+        archive_status: FieldComparison::new(
+            s3.archive_status,
+            head.archive_status.and_then(ArchiveStatus::from_aws),
+        ),
+        ingest_id: FieldComparison::new(s3.ingest_id.map(|id| id.to_string()), tag),
+    };
+
+    Ok(Json(LiveComparison {
+        is_match: exists
+            && comparison.size.is_match
+            && comparison.e_tag.is_match
+            && comparison.storage_class.is_match
+            && comparison.archive_status.is_match
+            && comparison.ingest_id.is_match,
+        ..comparison
+    }))
+}
+
+/// Get the record if it is the current, accessible record of its object, which means that it can
+/// be presigned.
+async fn presignable_s3<C>(connection: &C, response: S3) -> Result<Option<S3>>
+where
+    C: ConnectionTrait,
+{
     // If this object is not current or it's not accessible because it's archived, return an
     // empty response.
     if !response.is_current_state || !response.is_accessible {
-        txn.commit().await?;
-        return Ok(Json(None));
+        return Ok(None);
     }
 
     // Check if this represents a current object.
-    let current = ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+    let current = ListQueryBuilder::<_, s3_object::Entity>::new(connection)
         .filter_all(
             S3ObjectsFilter {
                 bucket: vec![Wildcard::new(response.bucket.to_string())].into(),
@@ -89,16 +328,36 @@ async fn presign_url_by_id(
         .all()
         .await?;
 
-    txn.commit().await?;
-
-    let content_type = HeaderParser::new(request.headers()).parse_header(CONTENT_TYPE)?;
-    let content_encoding = HeaderParser::new(request.headers()).parse_header(CONTENT_ENCODING)?;
-
     // If the last object ordered by sequencer is the requested one, then this is a
     // current object.
     if let Some(current) = current.last()
         && current.s3_object_id == response.s3_object_id
     {
+        return Ok(Some(response));
+    }
+
+    Ok(None)
+}
+
+/// Implementation of presigning a single URL by id.
+async fn presign_url_by_id(
+    state: State<AppState>,
+    id: Path<Uuid>,
+    presigned: Query<PresignedParams>,
+    request: Request,
+    access_key_secret_id: Option<String>,
+) -> Result<Json<Option<Url>>> {
+    let txn = state.begin_read().await?;
+
+    let Json(response) = get_s3_from_connection(&txn, id).await?;
+    let response = presignable_s3(&txn, response).await?;
+
+    txn.commit().await?;
+
+    let content_type = HeaderParser::new(request.headers()).parse_header(CONTENT_TYPE)?;
+    let content_encoding = HeaderParser::new(request.headers()).parse_header(CONTENT_ENCODING)?;
+
+    if let Some(response) = response {
         return Ok(Json(
             PresignedUrlBuilder::presign_from_model(
                 &state,
@@ -144,28 +403,141 @@ pub async fn presign_s3_by_id(
     presign_url_by_id(state, id, presigned, request, access_key_secret_id).await
 }
 
+/// Generate AWS presigned URLs for many S3 objects using their `s3_object_id`, where each entry
+/// can override the expiry, content-disposition and filename of its presigned URL. The
+/// `responseContentDisposition` parameter sets the default content-disposition for entries that
+/// do not override it. Each `expiresIn` must be between 1 second and
+/// `FILEMANAGER_API_PRESIGN_EXPIRY`, otherwise the request is rejected. Entries that are not
+/// current, not accessible, or over `FILEMANAGER_API_PRESIGN_LIMIT` have no URL. The response
+/// contains the result of each entry in the same order as the request. At most 1000 entries can
+/// be presigned per request.
+#[utoipa::path(
+    post,
+    path = "/s3/presign",
+    responses(
+        (
+            status = OK,
+            description = "The presigned url for each entry",
+            body = Vec<PresignEntryResult>
+        ),
+        ErrorStatusCode,
+    ),
+    params(PresignedParams),
+    request_body = Vec<PresignEntry>,
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn presign_s3_entries(
+    state: State<AppState>,
+    WithRejection(extract::Query(presigned), _): Query<PresignedParams>,
+    headers: HeaderMap,
+    WithRejection(extract::Json(entries), _): WithRejection<
+        extract::Json<Vec<PresignEntry>>,
+        ErrorStatusCode,
+    >,
+) -> Result<Json<Vec<PresignEntryResult>>> {
+    if entries.len() > MAX_PRESIGN_ENTRIES {
+        return Err(InvalidQuery(format!(
+            "at most {MAX_PRESIGN_ENTRIES} entries can be presigned per request"
+        )));
+    }
+
+    let expiries = entries
+        .iter()
+        .map(|entry| entry.validate(state.config()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let txn = state.begin_read().await?;
+
+    let mut records = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let record = match GetQueryBuilder::new(&txn)
+            .get_s3_by_id(entry.s3_object_id())
+            .await?
+        {
+            Some(record) => presignable_s3(&txn, record).await?,
+            None => None,
+        };
+        records.push(record);
+    }
+
+    txn.commit().await?;
+
+    let content_type = HeaderParser::new(&headers).parse_header(CONTENT_TYPE)?;
+    let content_encoding = HeaderParser::new(&headers).parse_header(CONTENT_ENCODING)?;
+    // Always presign with access key if it's available.
+    let access_key_secret_id = state.config().access_key_secret_id();
+
+    // The builder is reused so that its clients are shared by all entries.
+    let mut builder = PresignedUrlBuilder::new(&state)?;
+    let mut results = Vec::with_capacity(entries.len());
+    for ((entry, expires_in), record) in entries.into_iter().zip(expiries).zip(records) {
+        let url = match record {
+            Some(record) => {
+                builder = builder
+                    .set_object_size(record.size)
+                    .set_expires_in(Some(expires_in));
+                builder
+                    .presign_url(
+                        &record.key,
+                        &record.bucket,
+                        ResponseHeadersConfig::new(
+                            entry.response_content_disposition(
+                                presigned.response_content_disposition(),
+                            ),
+                            content_type.clone(),
+                            content_encoding.clone(),
+                        )
+                        .with_filename(entry.filename().map(|filename| filename.to_string())),
+                        access_key_secret_id,
+                    )
+                    .await?
+            }
+            None => None,
+        };
+
+        results.push(PresignEntryResult::new(entry.s3_object_id(), url));
+    }
+
+    Ok(Json(results))
+}
+
 /// The router for getting object records.
 pub fn get_router() -> Router<AppState> {
     Router::new()
         .route("/s3/{id}", get(get_s3_by_id))
+        .route("/s3/{id}/attributes", get(get_s3_attributes_by_id))
+        .route("/s3/{id}/compare-live", get(compare_live_s3_by_id))
         .route("/s3/presign/{id}", get(presign_s3_by_id))
+        .route("/s3/presign", post(presign_s3_entries))
 }
 
 #[cfg(test)]
 mod tests {
-    use aws_smithy_mocks::{RuleMode, mock_client};
+    use aws_sdk_s3::types;
+    use aws_smithy_mocks::{RuleMode, mock, mock_client};
     use axum::body::Body;
     use axum::http::{Method, StatusCode};
-    use serde_json::Value;
+    use chrono::Duration;
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+    use serde_json::json;
     use sqlx::PgPool;
 
     use crate::clients::aws::s3;
     use crate::database::aws::migration::tests::MIGRATOR;
     use crate::env::Config;
+    use crate::events::aws::collecter::tests::{
+        expected_get_object_tagging, expected_head_object_status, get_tagging_expectation,
+        head_expectation, mock_s3,
+    };
     use crate::queries::EntriesBuilder;
+    use crate::queries::update::tests::{change_attributes, null_attributes};
     use crate::routes::AppState;
+    use crate::routes::error::ErrorResponse;
     use crate::routes::list::tests::mock_get_object;
     use crate::routes::list::tests::{response_from, response_from_get};
+    use crate::routes::pagination::ListResponse;
     use crate::routes::presign::tests::assert_presigned_params;
     use crate::uuid::UuidGenerator;
 
@@ -185,6 +557,176 @@ mod tests {
         assert_eq!(&result, first);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn get_s3_api_e_tag_format(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let mut model = entries[0].clone().into_active_model();
+        model.e_tag = Set(Some("\"0\"".to_string()));
+        model
+            .update(state.database_client().connection_ref())
+            .await
+            .unwrap();
+
+        let id = entries[0].s3_object_id;
+        for (params, expected) in [
+            ("", "\"0\""),
+            ("?eTagFormat=quoted", "\"0\""),
+            ("?eTagFormat=unquoted", "0"),
+        ] {
+            let result: S3 = response_from_get(state.clone(), &format!("/s3/{id}{params}")).await;
+            assert_eq!(result.e_tag.as_deref(), Some(expected));
+        }
+
+        let result: ListResponse<S3> =
+            response_from_get(state, "/s3?bucket=0&key=0&eTagFormat=unquoted").await;
+        assert_eq!(result.results()[0].e_tag.as_deref(), Some("0"));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn get_s3_api_timezone(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let event_time = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap();
+        let mut model = entries[0].clone().into_active_model();
+        model.event_time = Set(Some(event_time));
+        model
+            .update(state.database_client().connection_ref())
+            .await
+            .unwrap();
+
+        let id = entries[0].s3_object_id;
+        for (params, expected) in [
+            ("", "2024-01-01T00:00:00Z"),
+            ("?tz=UTC", "2024-01-01T00:00:00Z"),
+            ("?tz=Australia/Melbourne", "2024-01-01T11:00:00+11:00"),
+            ("?tz=America/New_York", "2023-12-31T19:00:00-05:00"),
+        ] {
+            let result: Value =
+                response_from_get(state.clone(), &format!("/s3/{id}{params}")).await;
+            assert_eq!(result["eventTime"], expected);
+        }
+
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3?bucket=0&key=0&tz=Australia/Melbourne").await;
+        let result = result.results()[0].event_time.unwrap();
+        assert_eq!(result.offset().local_minus_utc(), 11 * 60 * 60);
+        assert_eq!(result, event_time);
+
+        let (status, _) = response_from::<ErrorResponse>(
+            state,
+            &format!("/s3/{id}?tz=Invalid/Timezone"),
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn compare_live_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        for (i, key) in [(0, "key"), (2, "missing")] {
+            let mut model = entries[i].clone().into_active_model();
+            model.bucket = Set("bucket".to_string());
+            model.key = Set(key.to_string());
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let state = state.with_s3_client(mock_s3(&[
+            head_expectation(
+                "key".to_string(),
+                "0".to_string(),
+                HeadObjectOutput::builder()
+                    .content_length(0)
+                    .e_tag("\"0\"")
+                    .storage_class(types::StorageClass::StandardIa)
+                    .build(),
+            ),
+            get_tagging_expectation(
+                "key".to_string(),
+                "0".to_string(),
+                expected_get_object_tagging(entries[0].ingest_id),
+            ),
+            mock!(aws_sdk_s3::Client::head_object)
+                .match_requests(|req| req.key() == Some("missing"))
+                .then_http_response(|| expected_head_object_status(404)),
+        ]));
+
+        let result: LiveComparison = response_from_get(
+            state.clone(),
+            &format!("/s3/{}/compare-live", entries[0].s3_object_id),
+        )
+        .await;
+        assert!(result.exists());
+        assert!(!result.is_match());
+        assert!(result.size().is_match());
+        assert!(result.e_tag().is_match());
+        assert!(result.archive_status().is_match());
+        assert!(result.ingest_id().is_match());
+        assert_eq!(
+            result.storage_class(),
+            &FieldComparison::new(
+                entries[0].storage_class.clone(),
+                Some(StorageClass::StandardIa)
+            )
+        );
+        assert!(!result.storage_class().is_match());
+
+        let result: LiveComparison = response_from_get(
+            state,
+            &format!("/s3/{}/compare-live", entries[2].s3_object_id),
+        )
+        .await;
+        assert!(!result.exists());
+        assert!(!result.is_match());
+        assert_eq!(result.size(), &FieldComparison::new(entries[2].size, None));
+        assert!(!result.ingest_id().is_match());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn get_s3_api_event_count(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_key_divisor(3)
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let first = entries.first().unwrap();
+        let result: AnnotatedS3 = response_from_get(
+            state.clone(),
+            &format!("/s3/{}?includeEventCount=true", first.s3_object_id),
+        )
+        .await;
+        assert_eq!(result.event_count(), Some(2));
+        assert_eq!(result.into_inner().s3_object_id, first.s3_object_id);
+
+        let result: AnnotatedS3 =
+            response_from_get(state, &format!("/s3/{}", first.s3_object_id)).await;
+        assert_eq!(result.event_count(), None);
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn get_non_existent(pool: PgPool) {
         let state = AppState::from_pool(pool).await.unwrap();
@@ -199,6 +741,40 @@ mod tests {
         assert_eq!(status_code, StatusCode::NOT_FOUND);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn get_s3_attributes_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+        let uri = format!("/s3/{}/attributes", entries.s3_objects[0].s3_object_id);
+
+        let attributes = json!({"portalRunId": "1", "nested": {"attributeId": "2"}});
+        change_attributes(
+            state.database_client(),
+            &entries,
+            0,
+            Some(attributes.clone()),
+        )
+        .await;
+        let result: Option<Value> = response_from_get(state.clone(), &uri).await;
+        assert_eq!(result, Some(attributes));
+
+        null_attributes(state.database_client(), &entries, 0).await;
+        let result: Option<Value> = response_from_get(state.clone(), &uri).await;
+        assert_eq!(result, None);
+
+        let (status_code, _) = response_from::<Value>(
+            state,
+            &format!("/s3/{}/attributes", UuidGenerator::generate()),
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status_code, StatusCode::NOT_FOUND);
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn get_presign(pool: PgPool) {
         let client = mock_client!(
@@ -272,6 +848,139 @@ mod tests {
         assert_eq!(result.path(), "/1/2");
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn presign_entries_api(pool: PgPool) {
+        let client = mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                &mock_get_object("2", "1", b""),
+                &mock_get_object("4", "2", b""),
+                &mock_get_object("6", "3", b""),
+            ]
+        );
+
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(s3::Client::new(client));
+
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let missing = UuidGenerator::generate();
+        let body = json!([
+            {
+                "s3ObjectId": entries.s3_objects[2].s3_object_id,
+                "expiresIn": 60,
+                "responseContentDisposition": "attachment",
+                "filename": "a.txt"
+            },
+            { "s3ObjectId": entries.s3_objects[4].s3_object_id },
+            {
+                "s3ObjectId": entries.s3_objects[6].s3_object_id,
+                "expiresIn": 3600,
+                "filename": "b.txt"
+            },
+            // Not accessible because of storage class.
+            { "s3ObjectId": entries.s3_objects[0].s3_object_id },
+            { "s3ObjectId": missing }
+        ]);
+
+        let (status_code, result) = response_from::<Vec<PresignEntryResult>>(
+            state.clone(),
+            "/s3/presign",
+            Method::POST,
+            Body::new(body.to_string()),
+        )
+        .await;
+        assert_eq!(status_code, StatusCode::OK);
+        assert_eq!(
+            result
+                .iter()
+                .map(|result| result.s3_object_id())
+                .collect::<Vec<_>>(),
+            vec![
+                entries.s3_objects[2].s3_object_id,
+                entries.s3_objects[4].s3_object_id,
+                entries.s3_objects[6].s3_object_id,
+                entries.s3_objects[0].s3_object_id,
+                missing
+            ]
+        );
+
+        let url = result[0].url().unwrap();
+        let query = url.query().unwrap();
+        assert!(query.contains("X-Amz-Expires=60&"));
+        assert!(
+            query.contains("response-content-disposition=attachment%3B%20filename%3D%22a.txt%22")
+        );
+        assert_eq!(url.path(), "/1/2");
+
+        let url = result[1].url().unwrap();
+        assert_presigned_params(url.query().unwrap(), "inline");
+        assert_eq!(url.path(), "/2/4");
+
+        let url = result[2].url().unwrap();
+        let query = url.query().unwrap();
+        assert!(query.contains("X-Amz-Expires=3600"));
+        assert!(query.contains("response-content-disposition=inline%3B%20filename%3D%22b.txt%22"));
+        assert_eq!(url.path(), "/3/6");
+
+        assert!(result[3].url().is_none());
+        assert!(result[4].url().is_none());
+
+        // The default content-disposition applies to entries without an override.
+        let body = json!([{ "s3ObjectId": entries.s3_objects[4].s3_object_id }]);
+        let (_, result) = response_from::<Vec<PresignEntryResult>>(
+            state.clone(),
+            "/s3/presign?responseContentDisposition=attachment",
+            Method::POST,
+            Body::new(body.to_string()),
+        )
+        .await;
+        assert_presigned_params(
+            result[0].url().unwrap().query().unwrap(),
+            "attachment%3B%20filename%3D%224%22",
+        );
+
+        // Expiries outside the configured bounds are rejected.
+        let state = state.with_config(Config {
+            api_presign_expiry: Duration::hours(1),
+            ..Default::default()
+        });
+        for expires_in in [0, 3601] {
+            let body = json!([
+                { "s3ObjectId": entries.s3_objects[2].s3_object_id },
+                { "s3ObjectId": entries.s3_objects[4].s3_object_id, "expiresIn": expires_in }
+            ]);
+            let (status_code, _) = response_from::<Value>(
+                state.clone(),
+                "/s3/presign",
+                Method::POST,
+                Body::new(body.to_string()),
+            )
+            .await;
+            assert_eq!(status_code, StatusCode::BAD_REQUEST);
+        }
+
+        // Requests with too many entries are rejected.
+        let body = Value::Array(vec![
+            json!({ "s3ObjectId": entries.s3_objects[2].s3_object_id });
+            MAX_PRESIGN_ENTRIES + 1
+        ]);
+        let (status_code, _) = response_from::<Value>(
+            state,
+            "/s3/presign",
+            Method::POST,
+            Body::new(body.to_string()),
+        )
+        .await;
+        assert_eq!(status_code, StatusCode::BAD_REQUEST);
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn get_presign_different_size(pool: PgPool) {
         let client = mock_client!(
@@ -0,0 +1,153 @@
+//! Route logic for exporting the raw event history of keys.
+//!
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+
+use crate::database::entities::s3_object;
+use crate::database::entities::s3_object::Model as S3;
+use crate::error::Error::InvalidQuery;
+use crate::error::Result;
+use crate::queries::list::ListQueryBuilder;
+use crate::queries::timing::log_slow_query;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::list::WildcardParams;
+use crate::routes::pagination::LimitParams;
+
+/// The maximum number of records that can be returned per call.
+pub const MAX_HISTORY_LIMIT: u64 = 10000;
+
+/// Export the raw event history of the records matching the filter, for example, to investigate
+/// an incident. Unlike the list route, this returns every record, including historical and
+/// non-current records, along with diagnostic fields such as `numberDuplicateEvents` and
+/// `numberReordered`. Records are ordered by bucket and key, and then by sequencer, so the events
+/// of each key are in the order that they occurred. Records with the same sequencer are in the
+/// order that they were ingested. The history is never truncated, so if more records than the
+/// `limit` match the filter, a `BAD_REQUEST` is returned and the filter should be narrowed.
+#[utoipa::path(
+    get,
+    path = "/s3/history",
+    responses(
+        (status = OK, description = "The ordered event history of the matching records", body = Vec<S3>),
+        ErrorStatusCode,
+    ),
+    params(LimitParams<MAX_HISTORY_LIMIT>, WildcardParams, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn history_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(params), _): Query<LimitParams<MAX_HISTORY_LIMIT>>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<S3>>> {
+    let limit = params.limit();
+    let summary = filter_all.summary();
+
+    // Fetch one more record than the limit to find out whether the history would be truncated.
+    let mut records = log_slow_query(
+        state.config().api_slow_query_threshold(),
+        summary,
+        ListQueryBuilder::<_, s3_object::Entity>::new(
+            state.database_client().read_connection_ref(),
+        )
+        .filter_all(filter_all, wildcard.case_sensitive(), false)?
+        .event_history(limit + 1),
+    )
+    .await?;
+
+    if records.len() as u64 > limit {
+        return Err(InvalidQuery(format!(
+            "more than {limit} records match the filter, narrow the filter to export the history"
+        )));
+    }
+    records.truncate(limit as usize);
+
+    Ok(Json(records))
+}
+
+/// The router for event history.
+pub fn history_router() -> Router<AppState> {
+    Router::new().route("/s3/history", get(history_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::list::tests::{response_from, response_from_get};
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn history_api(pool: PgPool) {
+        let state = AppState::from_pool(pool.clone()).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // The events of `key` are ingested in a different order to their sequencers, and
+        // include duplicate and reordered events.
+        for (i, (key, sequencer, duplicates, reordered, current)) in [
+            ("key", "0055AED6DCD90281E3", 2, 0, true),
+            ("key", "0055AED6DCD90281E1", 0, 1, false),
+            ("key", "0055AED6DCD90281E2", 0, 0, false),
+            ("key", "0055AED6DCD90281E2", 0, 0, false),
+            ("other", "0055AED6DCD90281E0", 0, 0, true),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            sqlx::query(
+                "update s3_object set bucket = 'bucket', key = $1, sequencer = $2, \
+                number_duplicate_events = $3, number_reordered = $4, is_current_state = $5 \
+                where s3_object_id = $6",
+            )
+            .bind(key)
+            .bind(sequencer)
+            .bind(duplicates)
+            .bind(reordered)
+            .bind(current)
+            .bind(entries[i].s3_object_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let result: Vec<S3> = response_from_get(state.clone(), "/s3/history?key=key").await;
+        assert_eq!(
+            result.iter().map(|s3| s3.s3_object_id).collect::<Vec<_>>(),
+            [1, 2, 3, 0].map(|i| entries[i].s3_object_id)
+        );
+        // Historical records and the diagnostic counters are included.
+        assert!(result.iter().any(|s3| !s3.is_current_state));
+        assert_eq!(result[0].number_reordered, 1);
+        assert_eq!(result[3].number_duplicate_events, 2);
+
+        let result: Vec<S3> = response_from_get(state.clone(), "/s3/history?bucket=bucket").await;
+        assert_eq!(
+            result.iter().map(|s3| s3.s3_object_id).collect::<Vec<_>>(),
+            [1, 2, 3, 0, 4].map(|i| entries[i].s3_object_id)
+        );
+
+        // A history that would be truncated is rejected.
+        let (status, _) = response_from::<Value>(
+            state,
+            "/s3/history?key=key&limit=3",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
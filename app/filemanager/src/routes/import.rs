@@ -0,0 +1,324 @@
+//! Route logic for importing object metadata from JSON Lines.
+//!
+
+use std::result;
+
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::Json as JsonValue;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database::Ingest;
+use crate::database::entities::sea_orm_active_enums::{
+    ArchiveStatus, EventType, Reason, StorageClass,
+};
+use crate::error::Error::ConfigError;
+use crate::error::Result;
+use crate::events::EventSourceType;
+use crate::events::aws::message::{self, default_version_id, quote_e_tag};
+use crate::events::aws::{
+    FlatS3EventMessage, FlatS3EventMessages, StorageClass as AwsStorageClass,
+    TransposedS3EventMessages,
+};
+use crate::routes::AppState;
+use crate::routes::error::ErrorStatusCode;
+use crate::routes::tenant::TenantScope;
+use crate::uuid::UuidGenerator;
+
+/// A single line of a JSON Lines import, which describes an object from an external source.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ImportRecord {
+    /// The bucket of the object.
+    pub(crate) bucket: String,
+    /// The key of the object.
+    pub(crate) key: String,
+    /// The version id of the object. Defaults to the unversioned `null` version.
+    #[serde(default)]
+    pub(crate) version_id: Option<String>,
+    /// The type of event, either `Created` or `Deleted`. Defaults to `Created`.
+    #[serde(default)]
+    pub(crate) event_type: Option<EventType>,
+    /// The time of the event.
+    #[serde(default)]
+    pub(crate) event_time: Option<DateTime<Utc>>,
+    /// The sequencer of the event. A sequencer is generated if this is not set.
+    #[serde(default)]
+    pub(crate) sequencer: Option<String>,
+    /// The size of the object in bytes.
+    #[serde(default)]
+    pub(crate) size: Option<i64>,
+    /// The ETag of the object.
+    #[serde(default)]
+    pub(crate) e_tag: Option<String>,
+    /// The base64 encoded sha256 checksum of the object.
+    #[serde(default)]
+    pub(crate) sha256: Option<String>,
+    /// The storage class of the object.
+    #[serde(default)]
+    pub(crate) storage_class: Option<StorageClass>,
+    /// The archive status of the object.
+    #[serde(default)]
+    pub(crate) archive_status: Option<ArchiveStatus>,
+    /// The last modified date of the object.
+    #[serde(default)]
+    pub(crate) last_modified_date: Option<DateTime<Utc>>,
+    /// Whether the object is a delete marker.
+    #[serde(default)]
+    pub(crate) is_delete_marker: bool,
+    /// The ingest id of the object.
+    #[serde(default)]
+    pub(crate) ingest_id: Option<Uuid>,
+    /// Attributes to store on the record.
+    #[serde(default)]
+    pub(crate) attributes: Option<JsonValue>,
+}
+
+impl ImportRecord {
+    /// Validate the record and convert it into an event for ingestion.
+    pub fn into_event(self, default_version: &str) -> result::Result<FlatS3EventMessage, String> {
+        if self.bucket.is_empty() {
+            return Err("`bucket` cannot be empty".to_string());
+        }
+        if self.key.is_empty() {
+            return Err("`key` cannot be empty".to_string());
+        }
+        if self.size.is_some_and(|size| size < 0) {
+            return Err("`size` cannot be negative".to_string());
+        }
+        if self
+            .attributes
+            .as_ref()
+            .is_some_and(|attributes| !attributes.is_object())
+        {
+            return Err("`attributes` must be a JSON object".to_string());
+        }
+
+        let (event_type, reason, is_current_state) = match self.event_type {
+            None | Some(EventType::Created) => (message::EventType::Created, Reason::Crawl, true),
+            Some(EventType::Deleted) => (message::EventType::Deleted, Reason::Deleted, false),
+            Some(EventType::Other) => {
+                return Err("`eventType` must be `Created` or `Deleted`".to_string());
+            }
+        };
+
+        Ok(FlatS3EventMessage {
+            s3_object_id: UuidGenerator::generate(),
+            sequencer: self.sequencer,
+            bucket: self.bucket,
+            key: self.key,
+            version_id: self
+                .version_id
+                .unwrap_or_else(|| default_version.to_string()),
+            size: self.size,
+            e_tag: self.e_tag.map(quote_e_tag),
+            sha256: self.sha256,
+            storage_class: self.storage_class.map(AwsStorageClass::from_database),
+            last_modified_date: self.last_modified_date,
+            event_time: self.event_time,
+            event_type,
+            is_delete_marker: self.is_delete_marker,
+            reason,
+            archive_status: self.archive_status,
+            ingest_id: self.ingest_id,
+            is_current_state,
+            attributes: self.attributes,
+            ..Default::default()
+        })
+    }
+}
+
+/// A line of an import which could not be ingested.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportLineError {
+    /// The one-indexed line number.
+    pub(crate) line: usize,
+    /// The reason that the line could not be ingested.
+    pub(crate) error: String,
+}
+
+impl ImportLineError {
+    /// Create a new line error.
+    pub fn new(line: usize, error: String) -> Self {
+        Self { line, error }
+    }
+
+    /// Get the line number.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Get the error.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+/// The result of an import.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    /// The number of lines that were ingested.
+    pub(crate) n_records: usize,
+    /// The lines which could not be ingested.
+    pub(crate) errors: Vec<ImportLineError>,
+}
+
+impl ImportResult {
+    /// Get the number of ingested records.
+    pub fn n_records(&self) -> usize {
+        self.n_records
+    }
+
+    /// Get the line errors.
+    pub fn errors(&self) -> &[ImportLineError] {
+        &self.errors
+    }
+}
+
+/// Parse JSON Lines into events, collecting the errors of malformed lines. Empty lines are
+/// skipped, and lines for buckets which are not visible to the tenant of the current request are
+/// reported as errors.
+pub fn parse_json_lines(
+    body: &str,
+    default_version: &str,
+) -> (Vec<FlatS3EventMessage>, Vec<ImportLineError>) {
+    let mut events = vec![];
+    let mut errors = vec![];
+    for (i, line) in body.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<ImportRecord>(line)
+            .map_err(|err| err.to_string())
+            .and_then(|record| {
+                TenantScope::check_bucket(&record.bucket).map_err(|err| err.to_string())?;
+                record.into_event(default_version)
+            }) {
+            Ok(event) => events.push(event),
+            Err(err) => errors.push(ImportLineError::new(i + 1, err)),
+        }
+    }
+
+    (events, errors)
+}
+
+/// Import object metadata from a JSON Lines body, where each line is a JSON object describing
+/// an object. This can be used to seed records from an external source, and does not call S3.
+/// Valid lines are ingested in the same way as crawled objects, and malformed lines are reported
+/// with their line number without failing the rest of the import.
+#[utoipa::path(
+    post,
+    path = "/s3/import",
+    request_body(content = String, description = "JSON Lines of import records", content_type = "application/jsonl"),
+    responses(
+        (status = OK, description = "The number of imported records and any malformed lines", body = ImportResult),
+        ErrorStatusCode,
+    ),
+    context_path = "/api/v1",
+    tag = "ingest",
+)]
+pub async fn import_s3(state: State<AppState>, body: String) -> Result<Json<ImportResult>> {
+    if state.config().paired_ingest_mode() {
+        return Err(ConfigError(
+            "paired ingest mode is not supported for imports".to_string(),
+        ));
+    }
+
+    let (events, errors) = parse_json_lines(&body, &default_version_id());
+    let n_records = events.len();
+
+    if !events.is_empty() {
+        let mut events = FlatS3EventMessages(events)
+            .replace_default_version_id(state.config().ingester_default_version_id());
+        if state.config().ingester_resolve_sequencer_conflicts() {
+            events = events.resolve_sequencer_conflicts();
+        }
+        let events = events.sort_and_dedup();
+        state
+            .database_client()
+            .ingest(EventSourceType::S3(TransposedS3EventMessages::from(events)))
+            .await?;
+    }
+
+    Ok(Json(ImportResult { n_records, errors }))
+}
+
+/// The router for importing objects.
+pub fn import_router() -> Router<AppState> {
+    Router::new().route("/s3/import", post(import_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::s3_object::Model as S3;
+    use crate::routes::list::tests::{response_from, response_from_get};
+    use crate::routes::pagination::ListResponse;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn import_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+
+        let body = [
+            r#"{"bucket":"bucket","key":"a","size":1,"eTag":"etag","storageClass":"Standard","attributes":{"source":"catalog"}}"#,
+            r#"{"bucket":"bucket","key":"b""#,
+            "",
+            r#"{"bucket":"bucket","key":"c","versionId":"1","lastModifiedDate":"2024-01-01T00:00:00Z"}"#,
+            r#"{"bucket":"","key":"d"}"#,
+            r#"{"bucket":"bucket","key":"e","unknown":1}"#,
+            r#"{"bucket":"bucket","key":"f","eventType":"Deleted"}"#,
+        ]
+        .join("\n");
+
+        let (status, result) = response_from::<ImportResult>(
+            state.clone(),
+            "/s3/import",
+            Method::POST,
+            Body::new(body),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.n_records(), 3);
+        assert_eq!(
+            result
+                .errors()
+                .iter()
+                .map(|err| err.line())
+                .collect::<Vec<_>>(),
+            vec![2, 5, 6]
+        );
+
+        let result: ListResponse<S3> = response_from_get(state.clone(), "/s3").await;
+        let results = result.results();
+        assert_eq!(
+            results.iter().map(|s3| s3.key.as_str()).collect::<Vec<_>>(),
+            vec!["a", "c"]
+        );
+        assert_eq!(results[0].size, Some(1));
+        assert_eq!(results[0].e_tag, Some("\"etag\"".to_string()));
+        assert_eq!(results[0].storage_class, Some(StorageClass::Standard));
+        assert_eq!(results[0].reason, Reason::Crawl);
+        assert_eq!(
+            results[0].attributes,
+            Some(serde_json::json!({"source": "catalog"}))
+        );
+        assert_eq!(results[1].version_id, "1");
+
+        let result: ListResponse<S3> =
+            response_from_get(state, "/s3?currentState=false&key=f").await;
+        assert_eq!(result.results().len(), 1);
+        assert_eq!(result.results()[0].event_type, EventType::Deleted);
+    }
+}
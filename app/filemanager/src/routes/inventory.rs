@@ -0,0 +1,214 @@
+//! Route logic for exporting records as an S3 inventory.
+//!
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Router, extract};
+use axum_extra::extract::WithRejection;
+use futures::channel::mpsc;
+use futures::{SinkExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use crate::database::entities::s3_object;
+use crate::error::{Error, Result};
+use crate::events::aws::inventory::Record;
+use crate::queries::list::ListQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::filter::wildcard::Wildcard;
+use crate::routes::tenant::TenantScope;
+
+/// The size in bytes of CSV chunks that are sent in the response body.
+pub const INVENTORY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The number of CSV chunks that can be buffered before waiting for the response to be read.
+const INVENTORY_CHANNEL_SIZE: usize = 8;
+
+/// Params for exporting an inventory.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct InventoryParams {
+    /// The bucket to export.
+    #[param(nullable = false, required = true)]
+    bucket: String,
+}
+
+impl InventoryParams {
+    /// Create new inventory params.
+    pub fn new(bucket: String) -> Self {
+        Self { bucket }
+    }
+
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+}
+
+/// Write the current records in the bucket to the sender as CSV chunks.
+async fn send_inventory(
+    state: &AppState,
+    filter: S3ObjectsFilter,
+    sender: &mut mpsc::Sender<Result<Vec<u8>>>,
+) -> Result<()> {
+    let mut records = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter, true, true)?
+    .stream()
+    .await?;
+
+    let mut chunk = Vec::with_capacity(INVENTORY_CHUNK_SIZE);
+    while let Some(record) = records.try_next().await? {
+        chunk.extend(Record::from(record).to_csv_row(true)?);
+
+        if chunk.len() >= INVENTORY_CHUNK_SIZE {
+            let full = std::mem::replace(&mut chunk, Vec::with_capacity(INVENTORY_CHUNK_SIZE));
+            if sender.send(Ok(full)).await.is_err() {
+                // The response body was dropped, so there is nothing left to do.
+                return Ok(());
+            }
+        }
+    }
+
+    if !chunk.is_empty() {
+        let _ = sender.send(Ok(chunk)).await;
+    }
+
+    Ok(())
+}
+
+/// Export the current state of a bucket as an S3 Inventory compatible CSV file. The CSV does
+/// not have a header row, and uses the default inventory schema of `Bucket, Key, VersionId,
+/// IsLatest, IsDeleteMarker, Size, LastModifiedDate, ETag, StorageClass`. Records are streamed
+/// from the database, so large buckets do not need to be loaded into memory.
+#[utoipa::path(
+    get,
+    path = "/s3/inventory",
+    responses(
+        (
+            status = OK,
+            description = "The current records in the bucket as an inventory CSV",
+            body = String,
+            content_type = "text/csv"
+        ),
+        ErrorStatusCode,
+    ),
+    params(InventoryParams),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn export_inventory_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(inventory), _): Query<InventoryParams>,
+) -> Result<Response> {
+    let filter = S3ObjectsFilter {
+        bucket: Wildcard::new(inventory.bucket).into(),
+        ..Default::default()
+    };
+
+    let (mut sender, receiver) = mpsc::channel(INVENTORY_CHANNEL_SIZE);
+    tokio::spawn(TenantScope::propagate(async move {
+        if let Err(err) = send_inventory(&state, filter, &mut sender).await {
+            let _ = sender.send(Err::<Vec<u8>, Error>(err)).await;
+        }
+    }));
+
+    Ok(([(CONTENT_TYPE, "text/csv")], Body::from_stream(receiver)).into_response())
+}
+
+/// The router for exporting inventories.
+pub fn inventory_router() -> Router<AppState> {
+    Router::new().route("/s3/inventory", get(export_inventory_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use axum::body::to_bytes;
+    use axum::http::{Request, StatusCode};
+    use flate2::Compression;
+    use flate2::read::GzEncoder;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::events::aws::StorageClass;
+    use crate::events::aws::inventory::{DEFAULT_CSV_MANIFEST, Inventory};
+    use crate::queries::EntriesBuilder;
+    use crate::routes::api_router;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn export_inventory_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let response = api_router(state)
+            .unwrap()
+            .oneshot(
+                Request::builder()
+                    .uri("/s3/inventory?bucket=0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/csv");
+
+        let csv = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let csv = String::from_utf8(csv.to_vec()).unwrap();
+
+        // Only the current record in bucket "0" is exported.
+        let expected = entries
+            .s3_objects
+            .iter()
+            .filter(|s3| s3.bucket == "0" && s3.is_current_state)
+            .map(|s3| {
+                format!(
+                    "\"{}\",\"{}\",\"{}\",\"true\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\"\n",
+                    s3.bucket,
+                    s3.key,
+                    s3.version_id,
+                    s3.is_delete_marker,
+                    s3.size.unwrap(),
+                    s3.last_modified_date
+                        .unwrap()
+                        .format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                    s3.e_tag.as_deref().unwrap().trim_matches('"'),
+                    serde_json::to_value(StorageClass::from_database(
+                        s3.storage_class.clone().unwrap()
+                    ))
+                    .unwrap()
+                    .as_str()
+                    .unwrap(),
+                )
+            })
+            .collect::<String>();
+        assert!(!expected.is_empty());
+        assert_eq!(csv, expected);
+
+        // The CSV can be read as an inventory.
+        let mut gzipped = vec![];
+        GzEncoder::new(csv.as_bytes(), Compression::default())
+            .read_to_end(&mut gzipped)
+            .unwrap();
+        let records = Inventory::with_defaults()
+            .await
+            .parse_csv(Some(DEFAULT_CSV_MANIFEST), &gzipped)
+            .await
+            .unwrap();
+        assert_eq!(records.len(), expected.lines().count());
+    }
+}
@@ -0,0 +1,125 @@
+//! Handling of keys which contain path traversal sequences when keys are interpreted as paths.
+//!
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error::InvalidQuery;
+use crate::error::Result;
+use crate::routes::prefix::PREFIX_DELIMITER;
+
+/// An encoded `/`, which is compared case-insensitively.
+const ENCODED_DELIMITER: &str = "%2f";
+
+/// A path segment which refers to the parent prefix.
+const PARENT_SEGMENT: &str = "..";
+
+/// How to handle keys or prefixes that contain path traversal sequences, such as `..` segments
+/// or encoded slashes, in routes that interpret keys as paths. This applies to the prefix, browse,
+/// drift and presign routes, and is configured using `FILEMANAGER_API_KEY_PATH_MODE`.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyPathMode {
+    /// Use keys as they are.
+    #[default]
+    #[serde(alias = "allow")]
+    Allow,
+    /// Reject keys which contain path traversal sequences.
+    #[serde(alias = "reject")]
+    Reject,
+    /// Canonicalize keys by decoding encoded slashes and resolving `..` segments.
+    #[serde(alias = "canonicalize")]
+    Canonicalize,
+}
+
+impl KeyPathMode {
+    /// Check a key according to the mode, returning the key that should be used as a path.
+    pub fn check<'a>(&self, key: &'a str) -> Result<Cow<'a, str>> {
+        match self {
+            KeyPathMode::Allow => Ok(Cow::Borrowed(key)),
+            _ if !has_path_traversal(key) => Ok(Cow::Borrowed(key)),
+            KeyPathMode::Reject => Err(InvalidQuery(format!(
+                "`{key}` contains a path traversal sequence"
+            ))),
+            KeyPathMode::Canonicalize => Ok(Cow::Owned(canonicalize(key))),
+        }
+    }
+}
+
+/// Whether a key contains a `..` segment or an encoded slash.
+pub fn has_path_traversal(key: &str) -> bool {
+    key.to_ascii_lowercase().contains(ENCODED_DELIMITER)
+        || key
+            .split(PREFIX_DELIMITER)
+            .any(|segment| segment == PARENT_SEGMENT)
+}
+
+/// Canonicalize a key by decoding encoded slashes and resolving `..` segments against the
+/// preceding segments. A `..` segment at the root of the bucket is removed.
+pub fn canonicalize(key: &str) -> String {
+    let mut decoded = String::with_capacity(key.len());
+    let mut rest = key;
+    while let Some(index) = rest.to_ascii_lowercase().find(ENCODED_DELIMITER) {
+        decoded.push_str(&rest[..index]);
+        decoded.push_str(PREFIX_DELIMITER);
+        rest = &rest[index + ENCODED_DELIMITER.len()..];
+    }
+    decoded.push_str(rest);
+
+    let mut segments: Vec<&str> = vec![];
+    let mut parts = decoded.split(PREFIX_DELIMITER).peekable();
+    while let Some(segment) = parts.next() {
+        if segment == PARENT_SEGMENT {
+            segments.pop();
+            // Keep the trailing delimiter of a prefix ending with `..`.
+            if parts.peek().is_none() {
+                segments.push("");
+            }
+        } else {
+            segments.push(segment);
+        }
+    }
+
+    segments.join(PREFIX_DELIMITER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_traversal() {
+        assert!(has_path_traversal("a/../b"));
+        assert!(has_path_traversal("../a"));
+        assert!(has_path_traversal("a/.."));
+        assert!(has_path_traversal("a%2Fb"));
+        assert!(has_path_traversal("a%2fb"));
+
+        assert!(!has_path_traversal("a/b"));
+        assert!(!has_path_traversal("a..b/c"));
+        assert!(!has_path_traversal("a/...//b"));
+        assert!(!has_path_traversal("a%20b"));
+    }
+
+    #[test]
+    fn canonicalize_key() {
+        assert_eq!(canonicalize("a/b/../c"), "a/c");
+        assert_eq!(canonicalize("a/b/../"), "a/");
+        assert_eq!(canonicalize("a/b/.."), "a/");
+        assert_eq!(canonicalize("../../a"), "a");
+        assert_eq!(canonicalize("a%2Fb/c"), "a/b/c");
+        assert_eq!(canonicalize("a/%2e%2e%2F..%2fb"), "a/b");
+        assert_eq!(canonicalize("/a/../b"), "/b");
+        assert_eq!(canonicalize("a//b/"), "a//b/");
+    }
+
+    #[test]
+    fn check_key() {
+        assert_eq!(KeyPathMode::Allow.check("a/../b").unwrap(), "a/../b");
+        assert_eq!(KeyPathMode::Reject.check("a/b").unwrap(), "a/b");
+        assert!(KeyPathMode::Reject.check("a/../b").is_err());
+        assert!(KeyPathMode::Reject.check("a%2F..").is_err());
+        assert_eq!(KeyPathMode::Canonicalize.check("a/../b").unwrap(), "b");
+    }
+}
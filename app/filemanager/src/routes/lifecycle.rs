@@ -0,0 +1,869 @@
+//! Route logic for listing the lifecycle of objects, grouped by their ingest id or by the
+//! storage class transitions of their key, and for the storage class distribution over time.
+//!
+
+use std::collections::HashMap;
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::database::entities::s3_object;
+use crate::database::entities::s3_object::Model as S3;
+use crate::database::entities::sea_orm_active_enums::{ArchiveStatus, StorageClass};
+use crate::error::Error::InvalidQuery;
+use crate::error::Result;
+use crate::queries::list::ListQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::list::WildcardParams;
+use crate::routes::pagination::LimitParams;
+
+/// The maximum number of ingest ids that are returned per call.
+pub const MAX_INGEST_ID_GROUPS: u64 = 1000;
+
+/// The maximum number of keys with storage class transitions that are returned per call.
+pub const MAX_TRANSITION_KEYS: u64 = 1000;
+
+/// The records which share an ingest id, representing the lifecycle of a logical object.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestIdGroup {
+    /// The ingest id shared by the records.
+    pub(crate) ingest_id: Uuid,
+    /// The records with the ingest id, ordered by sequencer.
+    pub(crate) records: Vec<S3>,
+}
+
+impl IngestIdGroup {
+    /// Create a new ingest id group.
+    pub fn new(ingest_id: Uuid, records: Vec<S3>) -> Self {
+        Self { ingest_id, records }
+    }
+
+    /// Get the ingest id.
+    pub fn ingest_id(&self) -> Uuid {
+        self.ingest_id
+    }
+
+    /// Get the records.
+    pub fn records(&self) -> &[S3] {
+        &self.records
+    }
+}
+
+/// The maximum number of periods in a storage class history.
+pub const MAX_HISTORY_PERIODS: i64 = 1000;
+
+/// The length of each period in a storage class history.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HistoryInterval {
+    /// Each period is one day.
+    #[default]
+    Day,
+    /// Each period is one calendar month.
+    Month,
+}
+
+impl HistoryInterval {
+    /// Truncate the date to the start of the period that contains it.
+    pub fn truncate(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            HistoryInterval::Day => date,
+            HistoryInterval::Month => date.with_day(1).expect("first day of month is valid"),
+        }
+    }
+
+    /// The number of periods from the period containing `start` to the period containing `end`,
+    /// inclusive.
+    pub fn n_periods(&self, start: NaiveDate, end: NaiveDate) -> i64 {
+        match self {
+            HistoryInterval::Day => (end - start).num_days() + 1,
+            HistoryInterval::Month => {
+                i64::from(end.year() - start.year()) * 12 + i64::from(end.month())
+                    - i64::from(start.month())
+                    + 1
+            }
+        }
+    }
+
+    /// The length of a period as a Postgres interval.
+    pub fn as_sql_interval(&self) -> &'static str {
+        match self {
+            HistoryInterval::Day => "1 day",
+            HistoryInterval::Month => "1 month",
+        }
+    }
+}
+
+/// Params for the storage class distribution over time.
+#[derive(Debug, Serialize, Deserialize, Default, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct StorageClassHistoryParams {
+    /// The length of each period, either `day` or `month`. Defaults to `day`.
+    #[param(nullable = false, required = false)]
+    interval: HistoryInterval,
+    /// The time to start the history from. Defaults to the earliest `event_time` of the records.
+    #[param(nullable = false, required = false, value_type = String, format = DateTime)]
+    start: Option<DateTimeWithTimeZone>,
+    /// The time to end the history at. Defaults to now.
+    #[param(nullable = false, required = false, value_type = String, format = DateTime)]
+    end: Option<DateTimeWithTimeZone>,
+}
+
+impl StorageClassHistoryParams {
+    /// Create new storage class history params.
+    pub fn new(
+        interval: HistoryInterval,
+        start: Option<DateTimeWithTimeZone>,
+        end: Option<DateTimeWithTimeZone>,
+    ) -> Self {
+        Self {
+            interval,
+            start,
+            end,
+        }
+    }
+
+    /// Get the interval.
+    pub fn interval(&self) -> HistoryInterval {
+        self.interval
+    }
+
+    /// Get the start of the first period and the start of the last period in UTC, using the
+    /// earliest event time if there is no start. This returns an error if the start is after the
+    /// end or if there are more than 1000 periods.
+    pub fn periods(
+        &self,
+        earliest: DateTimeWithTimeZone,
+    ) -> Result<(NaiveDateTime, NaiveDateTime)> {
+        let start = self.start.unwrap_or(earliest).with_timezone(&Utc);
+        let end = self
+            .end
+            .map_or_else(Utc::now, |end| end.with_timezone(&Utc));
+        if start > end {
+            return Err(InvalidQuery("`start` must be before `end`".to_string()));
+        }
+
+        let start = self.interval.truncate(start.date_naive());
+        let end = self.interval.truncate(end.date_naive());
+        if self.interval.n_periods(start, end) > MAX_HISTORY_PERIODS {
+            return Err(InvalidQuery(format!(
+                "the history cannot have more than {MAX_HISTORY_PERIODS} periods"
+            )));
+        }
+
+        Ok((start.and_time(NaiveTime::MIN), end.and_time(NaiveTime::MIN)))
+    }
+}
+
+/// The number and size of objects in a storage class.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClassCount {
+    /// The storage class.
+    pub(crate) storage_class: StorageClass,
+    /// The number of objects in the storage class.
+    pub(crate) count: u64,
+    /// The total size of the objects in the storage class.
+    pub(crate) size: i64,
+}
+
+impl StorageClassCount {
+    /// Create a new storage class count.
+    pub fn new(storage_class: StorageClass, count: u64, size: i64) -> Self {
+        Self {
+            storage_class,
+            count,
+            size,
+        }
+    }
+}
+
+/// The storage classes of objects at the end of a period.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClassPeriod {
+    /// The start of the period.
+    #[schema(value_type = String, format = DateTime)]
+    pub(crate) start: DateTimeWithTimeZone,
+    /// The number and size of objects in each storage class, ordered by storage class.
+    pub(crate) classes: Vec<StorageClassCount>,
+}
+
+impl StorageClassPeriod {
+    /// Create a new storage class period.
+    pub fn new(start: DateTimeWithTimeZone, classes: Vec<StorageClassCount>) -> Self {
+        Self { start, classes }
+    }
+
+    /// Get the start of the period.
+    pub fn start(&self) -> DateTimeWithTimeZone {
+        self.start
+    }
+
+    /// Get the storage class counts.
+    pub fn classes(&self) -> &[StorageClassCount] {
+        &self.classes
+    }
+}
+
+/// The storage class distribution of objects over time.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClassHistory {
+    /// The length of each period.
+    pub(crate) interval: HistoryInterval,
+    /// The periods, ordered by their start.
+    pub(crate) periods: Vec<StorageClassPeriod>,
+}
+
+impl StorageClassHistory {
+    /// Create a new storage class history.
+    pub fn new(interval: HistoryInterval, periods: Vec<StorageClassPeriod>) -> Self {
+        Self { interval, periods }
+    }
+
+    /// Get the periods.
+    pub fn periods(&self) -> &[StorageClassPeriod] {
+        &self.periods
+    }
+}
+
+/// A storage class and archive status that a key was in, starting from a record.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClassTransition {
+    /// The storage class of the key.
+    pub(crate) storage_class: StorageClass,
+    /// The archive status of the key.
+    pub(crate) archive_status: Option<ArchiveStatus>,
+    /// The id of the first record with this storage class and archive status.
+    pub(crate) s3_object_id: Uuid,
+    /// The event time of the first record with this storage class and archive status.
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub(crate) event_time: Option<DateTimeWithTimeZone>,
+}
+
+/// The sequence of storage classes that a key has transitioned through.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClassTransitions {
+    /// The bucket of the key.
+    pub(crate) bucket: String,
+    /// The key.
+    pub(crate) key: String,
+    /// The storage classes of the key, in the order of its events.
+    pub(crate) transitions: Vec<StorageClassTransition>,
+}
+
+impl StorageClassTransitions {
+    /// Find the transitions of a key from its records ordered by sequencer. Consecutive records
+    /// with the same storage class and archive status are merged, and records without a storage
+    /// class, such as deleted events, are skipped.
+    pub fn from_records(bucket: String, key: String, records: Vec<S3>) -> Self {
+        let mut transitions: Vec<StorageClassTransition> = vec![];
+        for record in records {
+            let Some(storage_class) = record.storage_class else {
+                continue;
+            };
+
+            let is_same = transitions.last().is_some_and(|last| {
+                last.storage_class == storage_class && last.archive_status == record.archive_status
+            });
+            if !is_same {
+                transitions.push(StorageClassTransition {
+                    storage_class,
+                    archive_status: record.archive_status,
+                    s3_object_id: record.s3_object_id,
+                    event_time: record.event_time,
+                });
+            }
+        }
+
+        Self {
+            bucket,
+            key,
+            transitions,
+        }
+    }
+
+    /// Get the key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the storage classes and archive statuses in order.
+    pub fn sequence(&self) -> Vec<(StorageClass, Option<ArchiveStatus>)> {
+        self.transitions
+            .iter()
+            .map(|transition| {
+                (
+                    transition.storage_class.clone(),
+                    transition.archive_status.clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// List records grouped by `ingestId`. Each group contains all the records which share an
+/// ingest id, ordered by sequencer, which shows the lifecycle of the logical object, such as
+/// when it was created, deleted, moved or transitioned between storage classes. The filter
+/// selects which ingest ids are returned, and each group includes all records with that ingest
+/// id, even if they do not match the filter. Records without an ingest id are excluded. Groups
+/// are ordered by ingest id, and at most `limit` groups are returned per call.
+#[utoipa::path(
+    get,
+    path = "/s3/ingest-ids",
+    responses(
+        (status = OK, description = "The records grouped by ingest id", body = Vec<IngestIdGroup>),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, LimitParams<MAX_INGEST_ID_GROUPS>, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn list_s3_by_ingest_id(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(group), _): Query<LimitParams<MAX_INGEST_ID_GROUPS>>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<IngestIdGroup>>> {
+    let txn = state.begin_read().await?;
+
+    let ingest_ids = ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+        .filter_all(filter_all, wildcard.case_sensitive(), false)?
+        .distinct_ingest_ids(group.limit())
+        .await?;
+
+    let mut records: HashMap<Uuid, Vec<S3>> = HashMap::new();
+    for record in ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+        .filter_ingest_ids(ingest_ids.clone())
+        .all()
+        .await?
+    {
+        if let Some(ingest_id) = record.ingest_id {
+            records.entry(ingest_id).or_default().push(record);
+        }
+    }
+
+    txn.commit().await?;
+
+    Ok(Json(
+        ingest_ids
+            .into_iter()
+            .map(|ingest_id| {
+                let records = records.remove(&ingest_id).unwrap_or_default();
+                IngestIdGroup::new(ingest_id, records)
+            })
+            .collect(),
+    ))
+}
+
+/// List the storage class transitions of keys. This finds keys where the storage class or
+/// archive status changed across their event history, for example, from `Standard` to
+/// `IntelligentTiering` to `Glacier`, and returns the sequence of storage classes ordered by
+/// sequencer. This can be used to check that lifecycle policies are working. The filter
+/// selects which keys are returned, and each sequence includes all records of the key, even
+/// if they do not match the filter. Keys are ordered by bucket and key, and at most `limit`
+/// keys are returned per call.
+#[utoipa::path(
+    get,
+    path = "/s3/storage-class-transitions",
+    responses(
+        (
+            status = OK,
+            description = "The storage class transitions of each key",
+            body = Vec<StorageClassTransitions>
+        ),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, LimitParams<MAX_TRANSITION_KEYS>, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn list_s3_storage_class_transitions(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(transition), _): Query<LimitParams<MAX_TRANSITION_KEYS>>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<StorageClassTransitions>>> {
+    let txn = state.begin_read().await?;
+
+    let keys = ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+        .filter_all(filter_all, wildcard.case_sensitive(), false)?
+        .filter_storage_class_transitions()
+        .distinct_keys(transition.limit())
+        .await?;
+    if keys.is_empty() {
+        return Ok(Json(vec![]));
+    }
+
+    let mut records: HashMap<(String, String), Vec<S3>> = HashMap::new();
+    for record in ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+        .filter_keys(keys.clone())
+        .all()
+        .await?
+    {
+        records
+            .entry((record.bucket.clone(), record.key.clone()))
+            .or_default()
+            .push(record);
+    }
+
+    txn.commit().await?;
+
+    Ok(Json(
+        keys.into_iter()
+            .map(|(bucket, key)| {
+                let records = records
+                    .remove(&(bucket.clone(), key.clone()))
+                    .unwrap_or_default();
+                StorageClassTransitions::from_records(bucket, key, records)
+            })
+            .collect(),
+    ))
+}
+
+/// Get the storage class distribution of objects over time. For each `day` or `month` period,
+/// each object version is attributed to the storage class of its latest event as of the end of
+/// the period, using the `event_time` of the event history. Versions which are deleted or are
+/// delete markers at the end of a period are not counted. This shows how the storage class mix
+/// evolved, for example, as lifecycle policies transition objects to colder storage. The filter
+/// applies to the records of the event history, and periods are computed in UTC. At most 1000
+/// periods can be returned per call.
+#[utoipa::path(
+    get,
+    path = "/s3/storage-class-history",
+    responses(
+        (
+            status = OK,
+            description = "The storage class distribution over time",
+            body = StorageClassHistory
+        ),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, StorageClassHistoryParams, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn storage_class_history_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(history), _): Query<StorageClassHistoryParams>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<StorageClassHistory>> {
+    let txn = state.begin_read().await?;
+
+    let records = ListQueryBuilder::<_, s3_object::Entity>::new(&txn).filter_all(
+        filter_all,
+        wildcard.case_sensitive(),
+        false,
+    )?;
+    let earliest = match history.start {
+        Some(start) => Some(start),
+        None => records.cloned().min_event_time().await?,
+    };
+    let Some(earliest) = earliest else {
+        return Ok(Json(StorageClassHistory::new(history.interval(), vec![])));
+    };
+
+    let (start, end) = history.periods(earliest)?;
+    let periods = records
+        .storage_class_history(history.interval(), start, end)
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(Json(StorageClassHistory::new(history.interval(), periods)))
+}
+
+/// The router for listing the lifecycle of objects.
+pub fn lifecycle_router() -> Router<AppState> {
+    Router::new()
+        .route("/s3/ingest-ids", get(list_s3_by_ingest_id))
+        .route(
+            "/s3/storage-class-transitions",
+            get(list_s3_storage_class_transitions),
+        )
+        .route("/s3/storage-class-history", get(storage_class_history_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::sea_orm_active_enums::EventType;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::error::ErrorResponse;
+    use crate::routes::list::tests::response_from;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_by_ingest_id_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Records 0, 3 and 4 share an ingest id, and the other records have no ingest id.
+        let (first, second) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        for (i, entry) in entries.iter().enumerate() {
+            let ingest_id = match i {
+                0 | 3 | 4 => Some(first),
+                1 => Some(second),
+                _ => None,
+            };
+            let mut model: s3_object::ActiveModel = entry.clone().into_active_model();
+            model.ingest_id = Set(ingest_id);
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+        let ids = |groups: &[IngestIdGroup]| {
+            groups
+                .iter()
+                .map(|group| {
+                    (
+                        group.ingest_id(),
+                        group
+                            .records()
+                            .iter()
+                            .map(|record| record.s3_object_id)
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Records are grouped by ingest id and ordered by sequencer.
+        let (status, result) = response_from::<Vec<IngestIdGroup>>(
+            state.clone(),
+            "/s3/ingest-ids",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            ids(&result),
+            vec![
+                (
+                    first,
+                    vec![
+                        entries[0].s3_object_id,
+                        entries[3].s3_object_id,
+                        entries[4].s3_object_id
+                    ]
+                ),
+                (second, vec![entries[1].s3_object_id]),
+            ]
+        );
+
+        // The filter selects the ingest ids, and all records with the ingest id are returned.
+        let (_, result) = response_from::<Vec<IngestIdGroup>>(
+            state.clone(),
+            "/s3/ingest-ids?key=3",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(
+            ids(&result),
+            vec![(
+                first,
+                vec![
+                    entries[0].s3_object_id,
+                    entries[3].s3_object_id,
+                    entries[4].s3_object_id
+                ]
+            )]
+        );
+
+        // Records without an ingest id are excluded.
+        let (_, result) = response_from::<Vec<IngestIdGroup>>(
+            state.clone(),
+            "/s3/ingest-ids?key=5",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert!(result.is_empty());
+
+        let (_, result) = response_from::<Vec<IngestIdGroup>>(
+            state,
+            "/s3/ingest-ids?limit=1",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].ingest_id(), first);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_storage_class_transitions_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Records 0 to 4 are events of the same key which transitioned storage classes. The
+        // other records each have a different key with a single storage class.
+        let classes = [
+            (StorageClass::Standard, None),
+            (StorageClass::Standard, None),
+            (StorageClass::IntelligentTiering, None),
+            (
+                StorageClass::IntelligentTiering,
+                Some(ArchiveStatus::DeepArchiveAccess),
+            ),
+            (StorageClass::Glacier, None),
+        ];
+        for (i, (entry, (storage_class, archive_status))) in entries.iter().zip(classes).enumerate()
+        {
+            let mut model: s3_object::ActiveModel = entry.clone().into_active_model();
+            model.bucket = Set("bucket".to_string());
+            model.key = Set("transitioned".to_string());
+            model.is_current_state = Set(i == 4);
+            model.storage_class = Set(Some(storage_class));
+            model.archive_status = Set(archive_status);
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let (status, result) = response_from::<Vec<StorageClassTransitions>>(
+            state.clone(),
+            "/s3/storage-class-transitions",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].key(), "transitioned");
+        assert_eq!(
+            result[0].sequence(),
+            vec![
+                (StorageClass::Standard, None),
+                (StorageClass::IntelligentTiering, None),
+                (
+                    StorageClass::IntelligentTiering,
+                    Some(ArchiveStatus::DeepArchiveAccess)
+                ),
+                (StorageClass::Glacier, None),
+            ]
+        );
+        assert_eq!(
+            result[0]
+                .transitions
+                .iter()
+                .map(|transition| transition.s3_object_id)
+                .collect::<Vec<_>>(),
+            vec![
+                entries[0].s3_object_id,
+                entries[2].s3_object_id,
+                entries[3].s3_object_id,
+                entries[4].s3_object_id
+            ]
+        );
+
+        // The filter selects the keys, and the sequence includes all records of the key.
+        let (_, result) = response_from::<Vec<StorageClassTransitions>>(
+            state.clone(),
+            "/s3/storage-class-transitions?storageClass=Glacier",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].sequence().len(), 4);
+
+        // Keys without transitions are excluded.
+        let (_, result) = response_from::<Vec<StorageClassTransitions>>(
+            state,
+            "/s3/storage-class-transitions?key=5",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert!(result.is_empty());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn storage_class_history_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Key `a` transitions from `Standard` to `Glacier` on the 3rd, key `b` is deleted on the
+        // 4th, and key `c` with a size of 4 is created in the next month.
+        let events = [
+            (
+                "a",
+                EventType::Created,
+                Some(StorageClass::Standard),
+                "2024-01-01T12:00:00Z",
+            ),
+            (
+                "a",
+                EventType::Created,
+                Some(StorageClass::Glacier),
+                "2024-01-03T12:00:00Z",
+            ),
+            (
+                "b",
+                EventType::Created,
+                Some(StorageClass::Standard),
+                "2024-01-02T12:00:00Z",
+            ),
+            ("b", EventType::Deleted, None, "2024-01-04T12:00:00Z"),
+            (
+                "c",
+                EventType::Created,
+                Some(StorageClass::IntelligentTiering),
+                "2024-02-10T12:00:00Z",
+            ),
+        ];
+        for (entry, (key, event_type, storage_class, event_time)) in entries.iter().zip(events) {
+            let mut model: s3_object::ActiveModel = entry.clone().into_active_model();
+            model.bucket = Set("history".to_string());
+            model.key = Set(key.to_string());
+            model.version_id = Set("1".to_string());
+            model.event_type = Set(event_type);
+            model.is_delete_marker = Set(false);
+            model.size = Set(Some(if key == "c" { 4 } else { 1 }));
+            model.storage_class = Set(storage_class);
+            model.event_time = Set(Some(event_time.parse().unwrap()));
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+        let classes = |period: &StorageClassPeriod| {
+            period
+                .classes()
+                .iter()
+                .map(|class| (class.storage_class.clone(), class.count, class.size))
+                .collect::<Vec<_>>()
+        };
+
+        let (status, result) = response_from::<StorageClassHistory>(
+            state.clone(),
+            "/s3/storage-class-history?bucket=history&start=2023-12-31T00:00:00Z&end=2024-01-05T00:00:00Z",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            result
+                .periods()
+                .iter()
+                .map(|period| (period.start().to_rfc3339(), classes(period)))
+                .collect::<Vec<_>>(),
+            vec![
+                ("2023-12-31T00:00:00+00:00".to_string(), vec![]),
+                (
+                    "2024-01-01T00:00:00+00:00".to_string(),
+                    vec![(StorageClass::Standard, 1, 1)]
+                ),
+                (
+                    "2024-01-02T00:00:00+00:00".to_string(),
+                    vec![(StorageClass::Standard, 2, 2)]
+                ),
+                (
+                    "2024-01-03T00:00:00+00:00".to_string(),
+                    vec![
+                        (StorageClass::Glacier, 1, 1),
+                        (StorageClass::Standard, 1, 1)
+                    ]
+                ),
+                (
+                    "2024-01-04T00:00:00+00:00".to_string(),
+                    vec![(StorageClass::Glacier, 1, 1)]
+                ),
+                (
+                    "2024-01-05T00:00:00+00:00".to_string(),
+                    vec![(StorageClass::Glacier, 1, 1)]
+                ),
+            ]
+        );
+
+        // Monthly periods start from the earliest event and end now.
+        let (_, result) = response_from::<StorageClassHistory>(
+            state.clone(),
+            "/s3/storage-class-history?bucket=history&interval=month",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        let today = Utc::now().date_naive();
+        assert_eq!(
+            result.periods().len() as i64,
+            HistoryInterval::Month.n_periods(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), today)
+        );
+        assert_eq!(
+            classes(&result.periods()[0]),
+            vec![(StorageClass::Glacier, 1, 1)]
+        );
+        let latest = vec![
+            (StorageClass::Glacier, 1, 1),
+            (StorageClass::IntelligentTiering, 1, 4),
+        ];
+        assert_eq!(classes(&result.periods()[1]), latest);
+        assert_eq!(classes(result.periods().last().unwrap()), latest);
+
+        let (_, result) = response_from::<StorageClassHistory>(
+            state.clone(),
+            "/s3/storage-class-history?bucket=missing",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert!(result.periods().is_empty());
+
+        let (status, _) = response_from::<ErrorResponse>(
+            state.clone(),
+            "/s3/storage-class-history?start=2024-01-02T00:00:00Z&end=2024-01-01T00:00:00Z",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = response_from::<ErrorResponse>(
+            state,
+            "/s3/storage-class-history?start=2000-01-01T00:00:00Z",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -3,29 +3,356 @@
 
 use axum::extract::{Request, State};
 use axum::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::get;
 use axum::{Json, Router, extract};
 use axum_extra::extract::WithRejection;
+use chrono_tz::Tz;
 use itertools::Itertools;
-use sea_orm::{ConnectionTrait, TransactionTrait};
+use sea_orm::ConnectionTrait;
+use sea_orm::prelude::DateTimeWithTimeZone;
 use serde::{Deserialize, Serialize};
 use serde_json::to_value;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
 use url::Url;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::database::entities::s3_object;
 use crate::database::entities::s3_object::Model as S3;
+use crate::database::entities::sea_orm_active_enums::Reason;
+use crate::error::Error::InvalidQuery;
 use crate::error::Result;
+use crate::queries::get::GetQueryBuilder;
 use crate::queries::list::ListQueryBuilder;
+use crate::queries::timing::log_slow_query;
 use crate::routes::AppState;
+use crate::routes::backfill::backfill_checksums;
+use crate::routes::conditional::ListVersion;
 use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
 use crate::routes::filter::{AttributesOnlyFilter, S3ObjectsFilter};
 use crate::routes::header::HeaderParser;
-use crate::routes::pagination::{ListResponse, Pagination};
+use crate::routes::pagination::{Cursor, CursorParams, LimitParams, ListResponse, Pagination};
 use crate::routes::presign::{PresignedParams, PresignedUrlBuilder};
 
+/// The number of records in the database for each `reason`.
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct ReasonCount(HashMap<Reason, u64>);
+
+impl ReasonCount {
+    /// Create a new reason count.
+    pub fn new(counts: HashMap<Reason, u64>) -> Self {
+        Self(counts)
+    }
+
+    /// Get the number of records for the reason.
+    pub fn get(&self, reason: &Reason) -> Option<u64> {
+        self.0.get(reason).copied()
+    }
+
+    /// Get the inner counts.
+    pub fn into_inner(self) -> HashMap<Reason, u64> {
+        self.0
+    }
+}
+
+/// The number of records in the database for each key depth.
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct KeyDepthCount(BTreeMap<u64, u64>);
+
+impl KeyDepthCount {
+    /// Create a new key depth count.
+    pub fn new(counts: BTreeMap<u64, u64>) -> Self {
+        Self(counts)
+    }
+
+    /// Get the number of records at the depth.
+    pub fn get(&self, depth: u64) -> Option<u64> {
+        self.0.get(&depth).copied()
+    }
+
+    /// Get the inner counts.
+    pub fn into_inner(self) -> BTreeMap<u64, u64> {
+        self.0
+    }
+}
+
+/// A distinct top-level `attributes` key, with the JSON value types that were observed for it.
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributeKey {
+    /// The attribute key.
+    pub(crate) key: String,
+    /// The number of records that have the key.
+    pub(crate) count: u64,
+    /// The number of records for each JSON value type of the key, i.e. `object`, `array`,
+    /// `string`, `number`, `boolean` or `null`.
+    pub(crate) types: BTreeMap<String, u64>,
+}
+
+impl AttributeKey {
+    /// Create a new attribute key.
+    pub fn new(key: String, count: u64, types: BTreeMap<String, u64>) -> Self {
+        Self { key, count, types }
+    }
+
+    /// Get the attribute key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the number of records with the key.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Get the number of records for each value type.
+    pub fn types(&self) -> &BTreeMap<String, u64> {
+        &self.types
+    }
+}
+
+/// A group of attribute keys which are similar to each other, such as `portalRunId` and
+/// `portalRnuId`, which likely means that some of the keys are typos.
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributeKeyGroup {
+    /// The keys of the group, with the most frequent key first.
+    pub(crate) keys: Vec<AttributeKey>,
+    /// The total number of records that have any key of the group.
+    pub(crate) count: u64,
+}
+
+impl AttributeKeyGroup {
+    /// Create a new attribute key group.
+    pub fn new(keys: Vec<AttributeKey>) -> Self {
+        let count = keys.iter().map(|key| key.count).sum();
+        Self { keys, count }
+    }
+
+    /// Get the keys of the group.
+    pub fn keys(&self) -> &[AttributeKey] {
+        &self.keys
+    }
+
+    /// Get the total number of records with any key of the group.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Group keys which are within `max_distance` edits of each other. Keys are grouped
+    /// transitively, and only groups with more than one key are returned, ordered by the total
+    /// number of records. Keys shorter than three times the distance between them are not
+    /// grouped, so that short keys such as `id` and `qc` are not reported.
+    pub fn group(keys: Vec<AttributeKey>, max_distance: usize) -> Vec<Self> {
+        // Find the key that identifies the group of a key.
+        fn root(groups: &mut [usize], mut i: usize) -> usize {
+            while groups[i] != i {
+                groups[i] = groups[groups[i]];
+                i = groups[i];
+            }
+            i
+        }
+
+        // The group that each key belongs to, where a group is identified by one of its keys.
+        let mut groups = (0..keys.len()).collect::<Vec<_>>();
+
+        for (i, j) in (0..keys.len()).tuple_combinations() {
+            let (a, b) = (keys[i].key(), keys[j].key());
+            let distance = edit_distance(a, b);
+            if distance <= max_distance && distance * 3 <= a.len().min(b.len()) {
+                let (i, j) = (root(&mut groups, i), root(&mut groups, j));
+                groups[j] = i;
+            }
+        }
+
+        let roots = (0..keys.len())
+            .map(|i| root(&mut groups, i))
+            .collect::<Vec<_>>();
+        keys.into_iter()
+            .zip(roots)
+            .into_group_map_by(|(_, root)| *root)
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|group| {
+                Self::new(
+                    group
+                        .into_iter()
+                        .map(|(key, _)| key)
+                        .sorted_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)))
+                        .collect(),
+                )
+            })
+            .sorted_by(|a, b| {
+                b.count
+                    .cmp(&a.count)
+                    .then_with(|| a.keys[0].key.cmp(&b.keys[0].key))
+            })
+            .collect()
+    }
+}
+
+/// The number of single character insertions, deletions, substitutions or adjacent
+/// transpositions that are needed to change `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+
+    // The distances of the previous two rows and the current row.
+    let mut before = vec![0; b.len() + 1];
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        (before, previous, current) = (previous, current, before);
+    }
+
+    previous[b.len()]
+}
+
+/// The default maximum edit distance between similar attribute keys.
+pub const DEFAULT_MAX_ATTRIBUTE_KEY_DISTANCE: usize = 2;
+
+/// Params for finding similar attribute keys.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct SimilarAttributeKeysParams {
+    /// The maximum number of single character edits between keys that are grouped together.
+    #[param(nullable = false, required = false, minimum = 1, default = 2)]
+    pub(crate) max_distance: usize,
+}
+
+impl Default for SimilarAttributeKeysParams {
+    fn default() -> Self {
+        Self {
+            max_distance: DEFAULT_MAX_ATTRIBUTE_KEY_DISTANCE,
+        }
+    }
+}
+
+impl SimilarAttributeKeysParams {
+    /// Create new similar attribute keys params.
+    pub fn new(max_distance: usize) -> Self {
+        Self { max_distance }
+    }
+
+    /// Get the maximum edit distance.
+    pub fn max_distance(&self) -> usize {
+        self.max_distance
+    }
+}
+
+/// Params for finding the distinct attribute keys.
+#[derive(Debug, Serialize, Deserialize, Default, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct AttributeKeysParams {
+    /// Only scan this many of the matching records, rather than all of them. This is faster,
+    /// but keys which only appear in records outside the sample are not returned.
+    #[param(nullable = false, required = false, minimum = 1)]
+    pub(crate) sample: Option<u64>,
+}
+
+impl AttributeKeysParams {
+    /// Create new attribute keys params.
+    pub fn new(sample: Option<u64>) -> Self {
+        Self { sample }
+    }
+
+    /// Get the sample size.
+    pub fn sample(&self) -> Option<u64> {
+        self.sample
+    }
+}
+
+/// The number of records in the database which have the same size and e_tag.
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeETagCount {
+    /// The size of the records.
+    pub(crate) size: i64,
+    /// The e_tag of the records.
+    pub(crate) e_tag: String,
+    /// The number of records with the size and e_tag.
+    pub(crate) count: u64,
+}
+
+impl SizeETagCount {
+    /// Create a new size and e_tag count.
+    pub fn new(size: i64, e_tag: String, count: u64) -> Self {
+        Self { size, e_tag, count }
+    }
+
+    /// Get the size.
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    /// Get the e_tag.
+    pub fn e_tag(&self) -> &str {
+        &self.e_tag
+    }
+
+    /// Get the number of records.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// The number of records in the database and their total size for a file extension.
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionCount {
+    /// The lowercase extension including the leading `.`, e.g. `.bam` or `.fastq.gz`. This is
+    /// null for keys without an extension.
+    pub(crate) extension: Option<String>,
+    /// The number of records with the extension.
+    pub(crate) count: u64,
+    /// The total size of the records with the extension in bytes.
+    pub(crate) size: i64,
+}
+
+impl ExtensionCount {
+    /// Create a new extension count.
+    pub fn new(extension: Option<String>, count: u64, size: i64) -> Self {
+        Self {
+            extension,
+            count,
+            size,
+        }
+    }
+
+    /// Get the extension.
+    pub fn extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    /// Get the number of records.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Get the total size.
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+}
+
+/// The maximum number of `(size, e_tag)` pairs that are returned per call.
+pub const MAX_SIZE_E_TAG_LIMIT: u64 = 1000;
+
 /// The return value for count operations showing the number of records in the database.
 #[derive(Debug, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -46,6 +373,170 @@ impl ListCount {
     }
 }
 
+/// An s3_object which can be annotated with additional rollup fields.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotatedS3 {
+    /// The s3_object record.
+    #[serde(flatten)]
+    pub(crate) s3: S3,
+    /// The total number of events recorded for the bucket and key of this record. This is only
+    /// present if `includeEventCount=true` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) event_count: Option<i64>,
+}
+
+impl AnnotatedS3 {
+    /// Create a new annotated s3_object.
+    pub fn new(s3: S3, event_count: Option<i64>) -> Self {
+        Self { s3, event_count }
+    }
+
+    /// Annotate the s3_objects according to the params.
+    pub async fn annotate<C: ConnectionTrait>(
+        connection: &C,
+        objects: Vec<S3>,
+        params: &EventCountParams,
+    ) -> Result<Vec<Self>> {
+        if !params.include_event_count {
+            return Ok(objects.into_iter().map(|s3| Self::new(s3, None)).collect());
+        }
+
+        let counts = GetQueryBuilder::new(connection)
+            .get_event_counts(&objects)
+            .await?;
+        Ok(objects
+            .into_iter()
+            .map(|s3| {
+                let count = counts.get(&s3.s3_object_id).copied();
+                Self::new(s3, count)
+            })
+            .collect())
+    }
+
+    /// Get the s3_object record.
+    pub fn into_inner(self) -> S3 {
+        self.s3
+    }
+
+    /// Get the event count.
+    pub fn event_count(&self) -> Option<i64> {
+        self.event_count
+    }
+}
+
+/// Params for annotating s3_objects with an event count.
+#[derive(Debug, Serialize, Deserialize, Default, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct EventCountParams {
+    /// Annotate each record with the total number of events recorded for its bucket and key,
+    /// regardless of other filters. This is useful to find keys with abnormal event churn,
+    /// for example, due to duplicate event delivery.
+    #[param(nullable = false, required = false, default = false)]
+    pub(crate) include_event_count: bool,
+}
+
+impl EventCountParams {
+    /// Create new event count params.
+    pub fn new(include_event_count: bool) -> Self {
+        Self {
+            include_event_count,
+        }
+    }
+
+    /// Get the include event count flag.
+    pub fn include_event_count(&self) -> bool {
+        self.include_event_count
+    }
+}
+
+/// The format of the ETag in responses.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ETagFormat {
+    /// Return the ETag with surrounding quotes, as it is stored in the database.
+    #[default]
+    Quoted,
+    /// Return the ETag without surrounding quotes.
+    Unquoted,
+}
+
+/// Params for formatting the ETag of s3_objects.
+#[derive(Debug, Serialize, Deserialize, Default, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct ETagFormatParams {
+    /// Whether to return the ETag with or without surrounding quotes. Defaults to `quoted`.
+    #[param(nullable = false, required = false)]
+    pub(crate) e_tag_format: ETagFormat,
+}
+
+impl ETagFormatParams {
+    /// Create new ETag format params.
+    pub fn new(e_tag_format: ETagFormat) -> Self {
+        Self { e_tag_format }
+    }
+
+    /// Get the ETag format.
+    pub fn e_tag_format(&self) -> ETagFormat {
+        self.e_tag_format
+    }
+
+    /// Format the ETag of the s3_object.
+    pub fn format(&self, mut s3: S3) -> S3 {
+        if self.e_tag_format == ETagFormat::Unquoted {
+            s3.e_tag = s3.e_tag.map(|e_tag| e_tag.trim_matches('"').to_string());
+        }
+        s3
+    }
+}
+
+/// Params for rendering the timestamps of s3_objects in a timezone.
+#[derive(Debug, Serialize, Deserialize, Default, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct TimezoneParams {
+    /// The IANA timezone to render timestamps in, e.g. `Australia/Melbourne`. Timestamps are
+    /// stored in UTC, and are returned in UTC by default.
+    #[param(nullable = false, required = false)]
+    tz: Option<String>,
+}
+
+impl TimezoneParams {
+    /// Create new timezone params.
+    pub fn new(tz: Option<String>) -> Self {
+        Self { tz }
+    }
+
+    /// Get the timezone, returning an error if it is not a valid IANA timezone.
+    pub fn timezone(&self) -> Result<Option<Tz>> {
+        self.tz
+            .as_deref()
+            .map(|tz| {
+                tz.parse::<Tz>()
+                    .map_err(|err| InvalidQuery(format!("invalid `tz`: {err}")))
+            })
+            .transpose()
+    }
+
+    /// Render the timestamps of the s3_object in the timezone. This only changes the offset
+    /// of the timestamps, and not the time that they represent.
+    pub fn format(timezone: Option<Tz>, mut s3: S3) -> S3 {
+        if let Some(timezone) = timezone {
+            let render = |date: DateTimeWithTimeZone| date.with_timezone(&timezone).fixed_offset();
+
+            s3.event_time = s3.event_time.map(render);
+            s3.last_modified_date = s3.last_modified_date.map(render);
+            s3.deleted_date = s3.deleted_date.map(render);
+            s3.restore_expiry_date = s3.restore_expiry_date.map(render);
+            s3.updated_at = render(s3.updated_at);
+            s3.ingested_at = s3.ingested_at.map(render);
+        }
+        s3
+    }
+}
+
 /// Params for wildcard requests.
 #[derive(Debug, Serialize, Deserialize, Default, IntoParams)]
 #[serde(default, rename_all = "camelCase")]
@@ -53,7 +544,7 @@ impl ListCount {
 pub struct WildcardParams {
     /// The case sensitivity when using filter operations with a wildcard.
     /// Setting this true means that an SQL `like` statement is used, and false
-    /// means `ilike` is used.
+    /// means `ilike` is used. Bucket filters always use `ilike` when this is false.
     #[serde(default = "default_case_sensitivity")]
     #[param(nullable = false, required = false, default = true)]
     pub(crate) case_sensitive: bool,
@@ -111,27 +602,173 @@ impl ListS3Params {
     }
 }
 
+/// Params for a permanently deleted s3 objects request.
+#[derive(Debug, Serialize, Deserialize, Default, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct DeletedParams {
+    /// Only return objects deleted at or after this date.
+    #[param(nullable = false, required = false, value_type = String, format = DateTime)]
+    from: Option<DateTimeWithTimeZone>,
+    /// Only return objects deleted before this date.
+    #[param(nullable = false, required = false, value_type = String, format = DateTime)]
+    to: Option<DateTimeWithTimeZone>,
+}
+
+impl DeletedParams {
+    /// Create new deleted params.
+    pub fn new(from: Option<DateTimeWithTimeZone>, to: Option<DateTimeWithTimeZone>) -> Self {
+        Self { from, to }
+    }
+
+    /// Get the from date.
+    pub fn from(&self) -> Option<DateTimeWithTimeZone> {
+        self.from
+    }
+
+    /// Get the to date.
+    pub fn to(&self) -> Option<DateTimeWithTimeZone> {
+        self.to
+    }
+}
+
 /// List all s3_objects according to the parameters.
+///
+/// If `FILEMANAGER_API_CONDITIONAL_LIST` is set, responses include an `ETag` header derived from
+/// the matching records, and a `Last-Modified` header with the time that any record was last
+/// updated or removed. If the request has an `If-None-Match` or `If-Modified-Since` header and the
+/// records have not changed since, then `304 Not Modified` is returned without a body.
 #[utoipa::path(
     get,
     path = "/s3",
     responses(
-        (status = OK, description = "The collection of s3_objects", body = ListResponse<S3>),
+        (status = OK, description = "The collection of s3_objects", body = ListResponse<AnnotatedS3>),
+        (status = NOT_MODIFIED, description = "The matching s3_objects have not changed"),
         ErrorStatusCode,
     ),
-    params(Pagination, WildcardParams, ListS3Params, S3ObjectsFilter),
+    params(Pagination, CursorParams, WildcardParams, ListS3Params, EventCountParams, ETagFormatParams, TimezoneParams, S3ObjectsFilter),
     context_path = "/api/v1",
     tag = "list",
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn list_s3(
+    state: State<AppState>,
+    pagination: Query<Pagination>,
+    cursor: Query<CursorParams>,
+    wildcard: Query<WildcardParams>,
+    list: Query<ListS3Params>,
+    WithRejection(extract::Query(event_count), _): Query<EventCountParams>,
+    WithRejection(extract::Query(e_tag_format), _): Query<ETagFormatParams>,
+    WithRejection(extract::Query(timezone), _): Query<TimezoneParams>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+    request: Request,
+) -> Result<Response> {
+    let timezone = timezone.timezone()?;
+    let filter_all = filter_all
+        .with_default_exclude_folder_placeholders(state.config().api_exclude_folder_placeholders());
+
+    // The version is found before listing, so a record changing in between results in a stale
+    // version rather than a stale body, and the next conditional request returns the new body.
+    let mut headers = HeaderMap::new();
+    if state.config().api_conditional_list() {
+        let version = list_s3_version(
+            &state,
+            wildcard.case_sensitive(),
+            list.current_state,
+            S3ObjectsFilter::clone(&filter_all),
+        )
+        .await?;
+        headers = version.headers()?;
+        if version.is_not_modified(request.headers()) {
+            return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+        }
+    }
+
+    let Json(ListResponse {
+        links,
+        pagination,
+        results,
+    }) = log_slow_query(
+        state.config().api_slow_query_threshold(),
+        filter_all.summary(),
+        list_s3_objects(
+            state.clone(),
+            pagination,
+            cursor,
+            wildcard,
+            list,
+            WithRejection(serde_qs::axum::QsQuery(filter_all), PhantomData),
+            request,
+        ),
+    )
+    .await?;
+
+    backfill_checksums(&state, &results);
+
+    let results = results
+        .into_iter()
+        .map(|s3| TimezoneParams::format(timezone, e_tag_format.format(s3)))
+        .collect();
+    let results = AnnotatedS3::annotate(
+        state.database_client().read_connection_ref(),
+        results,
+        &event_count,
+    )
+    .await?;
+
+    Ok((headers, Json(ListResponse::new(links, pagination, results))).into_response())
+}
+
+/// Get the version of the s3_object records matching the parameters.
+async fn list_s3_version(
+    state: &AppState,
+    case_sensitive: bool,
+    current_state: bool,
+    filter_all: S3ObjectsFilter,
+) -> Result<ListVersion> {
+    let txn = state.begin_read().await?;
+
+    let version = ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+        .filter_all(filter_all, case_sensitive, current_state)?
+        .to_list_version()
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(version)
+}
+
+/// List the s3_object records according to the parameters.
+async fn list_s3_objects(
     state: State<AppState>,
     WithRejection(extract::Query(pagination), _): Query<Pagination>,
+    WithRejection(extract::Query(cursor), _): Query<CursorParams>,
     WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
     WithRejection(extract::Query(list), _): Query<ListS3Params>,
     WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
     request: Request,
 ) -> Result<Json<ListResponse<S3>>> {
-    let txn = state.database_client().connection_ref().begin().await?;
+    // Cursors are tied to the filter they were created with, so that changing the filter
+    // between pages is an error rather than silently skipping or repeating records.
+    let cursor = cursor
+        .cursor()
+        .map(|cursor| {
+            if pagination.page().get() != 1 {
+                return Err(InvalidQuery(
+                    "`page` cannot be used with a pagination cursor".to_string(),
+                ));
+            }
+
+            let filter_hash = Cursor::hash_filter(&(
+                &filter_all,
+                wildcard.case_sensitive(),
+                list.current_state(),
+            ))?;
+            Cursor::decode(cursor, filter_hash)
+        })
+        .transpose()?;
+
+    let txn = state.begin_read().await?;
 
     let response = ListQueryBuilder::<_, s3_object::Entity>::new(&txn).filter_all(
         filter_all.clone(),
@@ -154,9 +791,15 @@ pub async fn list_s3(
         WithRejection(serde_qs::axum::QsQuery(filter_all), PhantomData),
     )
     .await?;
-    let response = response
-        .paginate_to_list_response(pagination, url, count.n_records)
-        .await?;
+    let response = if let Some(cursor) = cursor {
+        response
+            .paginate_with_cursor_to_list_response(pagination, cursor, url, count.n_records)
+            .await?
+    } else {
+        response
+            .paginate_to_list_response(pagination, url, count.n_records)
+            .await?
+    };
 
     txn.commit().await?;
 
@@ -179,15 +822,26 @@ pub async fn count_s3(
     state: State<AppState>,
     wildcard: Query<WildcardParams>,
     list: Query<ListS3Params>,
-    filter_all: QsQuery<S3ObjectsFilter>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
 ) -> Result<Json<ListCount>> {
-    count_s3_with_connection(
-        state.database_client().connection_ref(),
-        wildcard,
-        list,
-        filter_all,
+    let filter_all = filter_all
+        .with_default_exclude_folder_placeholders(state.config().api_exclude_folder_placeholders());
+
+    let txn = state.begin_read().await?;
+    let count = log_slow_query(
+        state.config().api_slow_query_threshold(),
+        filter_all.summary(),
+        count_s3_with_connection(
+            &txn,
+            wildcard,
+            list,
+            WithRejection(serde_qs::axum::QsQuery(filter_all), PhantomData),
+        ),
     )
-    .await
+    .await?;
+    txn.commit().await?;
+
+    Ok(count)
 }
 
 async fn count_s3_with_connection<C: ConnectionTrait>(
@@ -205,6 +859,340 @@ async fn count_s3_with_connection<C: ConnectionTrait>(
     Ok(Json(response.to_list_count().await?))
 }
 
+/// Count s3_objects according to the parameters, grouped by `reason`. This returns an object
+/// mapping each `reason` to the number of records, which can be used to monitor the proportion
+/// of records that were sourced from events compared to crawls. Reasons without any records
+/// are not included.
+#[utoipa::path(
+    get,
+    path = "/s3/count/reason",
+    responses(
+        (status = OK, description = "The count of s3 objects for each reason", body = ReasonCount),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, ListS3Params, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn count_s3_by_reason(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<ReasonCount>> {
+    let summary = filter_all.summary();
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), list.current_state)?;
+
+    let counts = log_slow_query(
+        state.config().api_slow_query_threshold(),
+        summary,
+        response.count_by_reason(),
+    )
+    .await?;
+
+    Ok(Json(ReasonCount::new(counts)))
+}
+
+/// Count s3_objects according to the parameters, grouped by key depth. The depth of a key is
+/// the number of `/` characters that it contains, so keys at the root of a bucket have a depth
+/// of zero. This returns an object mapping each depth to the number of records, which can be
+/// used to find layouts that are overly deep or flat. Depths without any records are not
+/// included.
+#[utoipa::path(
+    get,
+    path = "/s3/count/depth",
+    responses(
+        (status = OK, description = "The count of s3 objects for each key depth", body = KeyDepthCount),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, ListS3Params, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn count_s3_by_key_depth(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<KeyDepthCount>> {
+    let summary = filter_all.summary();
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), list.current_state)?;
+
+    let counts = log_slow_query(
+        state.config().api_slow_query_threshold(),
+        summary,
+        response.count_by_key_depth(),
+    )
+    .await?;
+
+    Ok(Json(KeyDepthCount::new(counts)))
+}
+
+/// Count s3_objects according to the parameters, grouped by `size` and `e_tag`. Only pairs which
+/// occur more than once are returned, ordered by the number of records. Records with the same
+/// size and e_tag are likely to have identical content, so this can be used to find duplicates
+/// when the sha256 is not known. Records with the pair can then be listed by filtering on both
+/// `size` and `eTag`.
+#[utoipa::path(
+    get,
+    path = "/s3/count/size-etag",
+    responses(
+        (status = OK, description = "The count of s3 objects for each repeated size and e_tag", body = Vec<SizeETagCount>),
+        ErrorStatusCode,
+    ),
+    params(LimitParams<MAX_SIZE_E_TAG_LIMIT>, WildcardParams, ListS3Params, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn count_s3_by_size_e_tag(
+    state: State<AppState>,
+    WithRejection(extract::Query(size_e_tag), _): Query<LimitParams<MAX_SIZE_E_TAG_LIMIT>>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<SizeETagCount>>> {
+    let summary = filter_all.summary();
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), list.current_state)?;
+
+    Ok(Json(
+        log_slow_query(
+            state.config().api_slow_query_threshold(),
+            summary,
+            response.count_by_size_e_tag(size_e_tag.limit()),
+        )
+        .await?,
+    ))
+}
+
+/// Count s3_objects according to the parameters, grouped by file extension. This returns the
+/// number of records and their total size for each extension, ordered by the count. Extensions
+/// are lowercased, and compression extensions such as `.gz` include the preceding extension, so
+/// `sample.R1.fastq.gz` is counted under `.fastq.gz`. Keys without an extension are counted under
+/// a null extension. Records with an extension can be listed using the `keySuffix` filter.
+#[utoipa::path(
+    get,
+    path = "/s3/count/extension",
+    responses(
+        (status = OK, description = "The count and size of s3 objects for each extension", body = Vec<ExtensionCount>),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, ListS3Params, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn count_s3_by_extension(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<ExtensionCount>>> {
+    let summary = filter_all.summary();
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), list.current_state)?;
+
+    Ok(Json(
+        log_slow_query(
+            state.config().api_slow_query_threshold(),
+            summary,
+            response.count_by_extension(),
+        )
+        .await?,
+    ))
+}
+
+/// Find the distinct top-level keys of the `attributes` of s3_objects according to the parameters.
+/// For each key, this returns the number of records that have the key, and the number of records
+/// for each observed JSON value type. This can be used to discover which attributes exist.
+#[utoipa::path(
+    get,
+    path = "/s3/attributes/keys",
+    responses(
+        (status = OK, description = "The distinct attribute keys", body = Vec<AttributeKey>),
+        ErrorStatusCode,
+    ),
+    params(AttributeKeysParams, WildcardParams, ListS3Params, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn attribute_keys_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(keys), _): Query<AttributeKeysParams>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<AttributeKey>>> {
+    if keys.sample == Some(0) {
+        return Err(InvalidQuery(
+            "`sample` must be greater than zero".to_string(),
+        ));
+    }
+
+    let summary = filter_all.summary();
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), list.current_state)?;
+
+    Ok(Json(
+        log_slow_query(
+            state.config().api_slow_query_threshold(),
+            summary,
+            response.attribute_keys(keys.sample),
+        )
+        .await?,
+    ))
+}
+
+/// Find groups of similar attribute keys, which are likely typos of each other, e.g.
+/// `portalRunId` and `portalRnuId`. Keys are grouped if they are within `maxDistance` edits,
+/// and each group reports the number of records for each key, so that the less frequent keys
+/// can be cleaned up. Filters apply in the same way as finding the distinct attribute keys.
+#[utoipa::path(
+    get,
+    path = "/s3/attributes/keys/similar",
+    responses(
+        (status = OK, description = "The groups of similar attribute keys", body = Vec<AttributeKeyGroup>),
+        ErrorStatusCode,
+    ),
+    params(
+        SimilarAttributeKeysParams,
+        AttributeKeysParams,
+        WildcardParams,
+        ListS3Params,
+        S3ObjectsFilter
+    ),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn similar_attribute_keys_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(similar), _): Query<SimilarAttributeKeysParams>,
+    WithRejection(extract::Query(keys), _): Query<AttributeKeysParams>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<AttributeKeyGroup>>> {
+    if similar.max_distance == 0 {
+        return Err(InvalidQuery(
+            "`maxDistance` must be greater than zero".to_string(),
+        ));
+    }
+
+    let Json(keys) = attribute_keys_s3(
+        state,
+        WithRejection(extract::Query(keys), PhantomData),
+        WithRejection(extract::Query(wildcard), PhantomData),
+        WithRejection(extract::Query(list), PhantomData),
+        WithRejection(serde_qs::axum::QsQuery(filter_all), PhantomData),
+    )
+    .await?;
+
+    Ok(Json(AttributeKeyGroup::group(keys, similar.max_distance)))
+}
+
+/// List permanently deleted s3_objects. This returns `Deleted` events which are not delete
+/// markers, where the bucket and key has no current `Created` record. Objects that were deleted
+/// and later re-created are not returned. Additional filters apply to the `Deleted` events.
+#[utoipa::path(
+    get,
+    path = "/s3/deleted",
+    responses(
+        (status = OK, description = "The collection of permanently deleted s3_objects", body = ListResponse<S3>),
+        ErrorStatusCode,
+    ),
+    params(Pagination, WildcardParams, DeletedParams, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn list_deleted_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(pagination), _): Query<Pagination>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(deleted), _): Query<DeletedParams>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+    request: Request,
+) -> Result<Json<ListResponse<S3>>> {
+    let txn = state.begin_read().await?;
+
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+        .filter_all(filter_all, wildcard.case_sensitive(), false)?
+        .filter_permanently_deleted(deleted.from, deleted.to);
+
+    let url = if let Some(url) = state.config().api_links_url() {
+        url
+    } else {
+        &HeaderParser::parse_host_url(&request, state.use_tls_links())?
+    };
+
+    let url = url.join(&HeaderParser::get_uri_path(&request))?;
+
+    let count = response.cloned().count().await?;
+    let response = response
+        .paginate_to_list_response(pagination, url, count)
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(Json(response))
+}
+
+/// List the latest s3_object for each bucket and key, computed from the record with the highest
+/// sequencer rather than the stored `isCurrentState` flag. This can be compared with the current
+/// state records to find keys where the stored flag is incorrect. Additional filters apply after
+/// the latest record is found.
+#[utoipa::path(
+    get,
+    path = "/s3/latest",
+    responses(
+        (status = OK, description = "The latest s3_object for each bucket and key", body = ListResponse<S3>),
+        ErrorStatusCode,
+    ),
+    params(Pagination, WildcardParams, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn list_latest_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(pagination), _): Query<Pagination>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+    request: Request,
+) -> Result<Json<ListResponse<S3>>> {
+    let txn = state.begin_read().await?;
+
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+        .filter_all(filter_all.clone(), wildcard.case_sensitive(), false)?
+        .filter_latest_per_key(&filter_all, wildcard.case_sensitive())?;
+
+    let url = if let Some(url) = state.config().api_links_url() {
+        url
+    } else {
+        &HeaderParser::parse_host_url(&request, state.use_tls_links())?
+    };
+
+    let url = url.join(&HeaderParser::get_uri_path(&request))?;
+
+    let count = response.cloned().count().await?;
+    let response = response
+        .paginate_to_list_response(pagination, url, count)
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(Json(response))
+}
+
 /// Implementation of the presign URL route.
 async fn presign_url(
     state: State<AppState>,
@@ -223,9 +1211,10 @@ async fn presign_url(
         links,
         pagination,
         results,
-    }) = list_s3(
+    }) = list_s3_objects(
         state.clone(),
         pagination,
+        WithRejection(extract::Query(CursorParams::default()), PhantomData),
         wildcard,
         WithRejection(extract::Query(ListS3Params::new(true)), PhantomData),
         WithRejection(serde_qs::axum::QsQuery(filter_all), PhantomData),
@@ -300,25 +1289,32 @@ pub async fn presign_s3(
 /// can express the same query as `/api/v1/s3/attributes?attributeId=...`. Similar to the
 /// `attributes` filter parameter, nested JSON queries are supported using the bracket notation.
 /// Note that regular filtering parameters, like `key` or `bucket` are not supported on this route.
+/// Conditional requests are supported in the same way as `/api/v1/s3`.
 #[utoipa::path(
     get,
     path = "/s3/attributes",
     responses(
-        (status = OK, description = "The collection of s3_objects", body = ListResponse<S3>),
+        (status = OK, description = "The collection of s3_objects", body = ListResponse<AnnotatedS3>),
+        (status = NOT_MODIFIED, description = "The matching s3_objects have not changed"),
         ErrorStatusCode,
     ),
-    params(Pagination, WildcardParams, ListS3Params, AttributesOnlyFilter),
+    params(Pagination, CursorParams, WildcardParams, ListS3Params, EventCountParams, ETagFormatParams, TimezoneParams, AttributesOnlyFilter),
     context_path = "/api/v1",
     tag = "list",
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn attributes_s3(
     state: State<AppState>,
     pagination: Query<Pagination>,
+    cursor: Query<CursorParams>,
     wildcard: Query<WildcardParams>,
     list: Query<ListS3Params>,
+    event_count: Query<EventCountParams>,
+    e_tag_format: Query<ETagFormatParams>,
+    timezone: Query<TimezoneParams>,
     WithRejection(serde_qs::axum::QsQuery(attributes_only), _): QsQuery<AttributesOnlyFilter>,
     request: Request,
-) -> Result<Json<ListResponse<S3>>> {
+) -> Result<Response> {
     let mut filter = S3ObjectsFilter::from(attributes_only);
 
     // Remove keys with special meaning.
@@ -336,8 +1332,12 @@ pub async fn attributes_s3(
     list_s3(
         state,
         pagination,
+        cursor,
         wildcard,
         list,
+        event_count,
+        e_tag_format,
+        timezone,
         WithRejection(serde_qs::axum::QsQuery(filter), PhantomData),
         request,
     )
@@ -357,10 +1357,22 @@ fn params_keys<T: Serialize>(value: T) -> HashSet<String> {
 /// Return the field names that have a special meaning for the attributes route.
 pub fn attributes_s3_field_names() -> HashSet<String> {
     let pagination = params_keys(Pagination::default());
+    let cursor = params_keys(CursorParams::default());
     let wildcard = params_keys(WildcardParams::default());
     let list = params_keys(ListS3Params::default());
-
-    pagination.into_iter().merge(wildcard).merge(list).collect()
+    let event_count = params_keys(EventCountParams::default());
+    let e_tag_format = params_keys(ETagFormatParams::default());
+    let timezone = params_keys(TimezoneParams::default());
+
+    pagination
+        .into_iter()
+        .merge(cursor)
+        .merge(wildcard)
+        .merge(list)
+        .merge(event_count)
+        .merge(e_tag_format)
+        .merge(timezone)
+        .collect()
 }
 
 /// The router for list objects.
@@ -368,8 +1380,19 @@ pub fn list_router() -> Router<AppState> {
     Router::new()
         .route("/s3", get(list_s3))
         .route("/s3/count", get(count_s3))
+        .route("/s3/count/reason", get(count_s3_by_reason))
+        .route("/s3/count/depth", get(count_s3_by_key_depth))
+        .route("/s3/count/size-etag", get(count_s3_by_size_e_tag))
+        .route("/s3/count/extension", get(count_s3_by_extension))
+        .route("/s3/deleted", get(list_deleted_s3))
+        .route("/s3/latest", get(list_latest_s3))
         .route("/s3/presign", get(presign_s3))
         .route("/s3/attributes", get(attributes_s3))
+        .route("/s3/attributes/keys", get(attribute_keys_s3))
+        .route(
+            "/s3/attributes/keys/similar",
+            get(similar_attribute_keys_s3),
+        )
 }
 
 #[cfg(test)]
@@ -379,44 +1402,240 @@ pub(crate) mod tests {
     use aws_smithy_mocks::{Rule, RuleMode, mock, mock_client};
     use axum::body::Body;
     use axum::body::to_bytes;
-    use axum::http::header::{CONTENT_TYPE, HOST};
-    use axum::http::{Method, Request, StatusCode};
+    use axum::http::header::{
+        CONTENT_TYPE, ETAG, HOST, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    };
+    use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode};
     use percent_encoding::{NON_ALPHANUMERIC, percent_encode};
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel};
     use serde::de::DeserializeOwned;
-    use serde_json::{from_slice, json};
-    use sqlx::PgPool;
+    use serde_json::{Value, from_slice, json};
+    use sqlx::postgres::PgPoolOptions;
+    use sqlx::{Executor, PgPool};
     use std::collections::HashMap;
     use tower::util::ServiceExt;
     use uuid::Uuid;
 
     use crate::clients::aws::s3;
+    use crate::database::Client;
     use crate::database::aws::migration::tests::MIGRATOR;
     use crate::database::entities::sea_orm_active_enums::EventType;
     use crate::env::Config;
     use crate::queries::EntriesBuilder;
     use crate::queries::list::tests::filter_event_type;
     use crate::queries::update::tests::{assert_contains, entries_many};
-    use crate::queries::update::tests::{change_key, change_many};
+    use crate::queries::update::tests::{change_key, change_many, change_size};
     use crate::routes::api_router;
     use crate::routes::pagination::Links;
     use crate::routes::presign::tests::assert_presigned_params;
 
-    use super::*;
+    use super::*;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_shuffle(true)
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let result: ListResponse<S3> = response_from_get(state, "/s3?currentState=false").await;
+        assert_eq!(result.links(), &Links::new(None, None));
+        assert_eq!(result.results(), entries);
+        assert_eq!(result.pagination().count, 10);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_api_read_replica(pool: PgPool) {
+        let state = AppState::from_pool(pool.clone()).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let replica = PgPoolOptions::new()
+            .connect_with((*pool.connect_options()).clone())
+            .await
+            .unwrap();
+        let state =
+            state.with_database_client(Client::from_pool(pool.clone()).with_read_pool(replica));
+
+        // Reads still succeed with the primary closed because they use the replica.
+        pool.close().await;
+
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3?currentState=false").await;
+        assert_eq!(result.results(), entries);
+
+        let result: ListCount = response_from_get(state, "/s3/count?currentState=false").await;
+        assert_eq!(result.n_records(), 10);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_api_conditional(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Conditional requests are not supported by default.
+        let (status, headers) = conditional_response(state.clone(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers.get(ETAG).is_none());
+        assert!(headers.get(LAST_MODIFIED).is_none());
+
+        let state = state.with_config(Config {
+            api_conditional_list: true,
+            ..Default::default()
+        });
+        let (status, headers) = conditional_response(state.clone(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let e_tag = headers.get(ETAG).unwrap().clone();
+        let last_modified = headers.get(LAST_MODIFIED).unwrap().clone();
+
+        // Nothing has changed, so the records are not modified.
+        let (status, headers) =
+            conditional_response(state.clone(), Some((IF_NONE_MATCH, e_tag.clone()))).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert_eq!(headers.get(ETAG), Some(&e_tag));
+        let (status, _) =
+            conditional_response(state.clone(), Some((IF_MODIFIED_SINCE, last_modified))).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        // Updating a record which does not match the filter does not change the version.
+        let mut model = entries[4].clone().into_active_model();
+        model.size = Set(Some(100));
+        model
+            .update(state.database_client().connection_ref())
+            .await
+            .unwrap();
+        let (status, _) =
+            conditional_response(state.clone(), Some((IF_NONE_MATCH, e_tag.clone()))).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        // Updating a matching record changes the version.
+        let mut model = entries[0].clone().into_active_model();
+        model.size = Set(Some(100));
+        model
+            .update(state.database_client().connection_ref())
+            .await
+            .unwrap();
+        let (status, headers) =
+            conditional_response(state.clone(), Some((IF_NONE_MATCH, e_tag.clone()))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(headers.get(ETAG), Some(&e_tag));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_api_conditional_removed(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_conditional_list: true,
+                ..Default::default()
+            });
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Move all changes into the past so that a removal is in a later second.
+        state
+            .database_client()
+            .pool()
+            .execute(
+                "update s3_object set updated_at = '2000-01-01'; \
+                update s3_object_removed set removed_at = '2000-01-01';",
+            )
+            .await
+            .unwrap();
+
+        let (status, headers) = conditional_response(state.clone(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let last_modified = headers.get(LAST_MODIFIED).unwrap().clone();
+        assert_eq!(last_modified, "Sat, 01 Jan 2000 00:00:00 GMT");
+        let (status, _) = conditional_response(
+            state.clone(),
+            Some((IF_MODIFIED_SINCE, last_modified.clone())),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        // Removing a record is a modification, even though it has no `updated_at` afterwards.
+        s3_object::Entity::delete_by_id(entries[0].s3_object_id)
+            .exec(state.database_client().connection_ref())
+            .await
+            .unwrap();
+        let (status, headers) =
+            conditional_response(state, Some((IF_MODIFIED_SINCE, last_modified.clone()))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(headers.get(LAST_MODIFIED), Some(&last_modified));
+    }
 
     #[sqlx::test(migrator = "MIGRATOR")]
-    async fn list_s3_api(pool: PgPool) {
+    async fn list_s3_api_event_count(pool: PgPool) {
         let state = AppState::from_pool(pool).await.unwrap();
         let entries = EntriesBuilder::default()
-            .with_shuffle(true)
+            .with_bucket_divisor(4)
+            .with_key_divisor(4)
+            .with_generate_crawl_entries(false)
             .build(state.database_client())
             .await
             .unwrap()
             .s3_objects;
 
-        let result: ListResponse<S3> = response_from_get(state, "/s3?currentState=false").await;
-        assert_eq!(result.links(), &Links::new(None, None));
-        assert_eq!(result.results(), entries);
-        assert_eq!(result.pagination().count, 10);
+        let result: ListResponse<AnnotatedS3> = response_from_get(
+            state.clone(),
+            "/s3?currentState=false&bucket=0&includeEventCount=true",
+        )
+        .await;
+        assert_eq!(
+            result
+                .results()
+                .iter()
+                .map(|result| (result.s3.s3_object_id, result.event_count()))
+                .collect::<Vec<_>>(),
+            entries[0..4]
+                .iter()
+                .map(|entry| (entry.s3_object_id, Some(4)))
+                .collect::<Vec<_>>()
+        );
+
+        // Other filters do not affect the count.
+        let result: ListResponse<AnnotatedS3> = response_from_get(
+            state.clone(),
+            "/s3?currentState=false&bucket=0&eventType=Created&includeEventCount=true",
+        )
+        .await;
+        assert!(!result.results().is_empty());
+        assert!(
+            result
+                .results()
+                .iter()
+                .all(|result| result.event_count() == Some(4))
+        );
+
+        let result: ListResponse<AnnotatedS3> =
+            response_from_get(state, "/s3?currentState=false&bucket=2").await;
+        assert_eq!(
+            result
+                .results()
+                .iter()
+                .map(|result| (result.s3.s3_object_id, result.event_count()))
+                .collect::<Vec<_>>(),
+            entries[8..10]
+                .iter()
+                .map(|entry| (entry.s3_object_id, None))
+                .collect::<Vec<_>>()
+        );
     }
 
     #[sqlx::test(migrator = "MIGRATOR")]
@@ -690,6 +1909,80 @@ pub(crate) mod tests {
         assert_eq!(result.pagination().count, 5);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_deleted_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // A delete marker is not a permanent delete.
+        let mut model: s3_object::ActiveModel = entries.s3_objects[3].clone().into_active_model();
+        model.is_delete_marker = Set(true);
+        model
+            .update(state.database_client().connection_ref())
+            .await
+            .unwrap();
+        // A deleted key that has been re-created is not a permanent delete.
+        change_key(state.database_client(), &entries, 5, "4".to_string()).await;
+
+        let result: ListResponse<S3> = response_from_get(state.clone(), "/s3/deleted").await;
+        let ids = [1, 7, 9].map(|i| entries.s3_objects[i].s3_object_id);
+        assert_eq!(
+            result
+                .results()
+                .iter()
+                .map(|s3| s3.s3_object_id)
+                .collect::<Vec<_>>(),
+            ids
+        );
+        assert_eq!(result.pagination().count, 3);
+
+        let result: ListResponse<S3> = response_from_get(
+            state,
+            "/s3/deleted?from=1970-01-03T00:00:00Z&to=1970-01-10T00:00:00Z",
+        )
+        .await;
+        assert_eq!(result.results(), vec![entries.s3_objects[7].clone()]);
+        assert_eq!(result.pagination().count, 1);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_latest_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Record 1 has a higher sequencer than record 0 for the same key, but record 0 is stored
+        // as the current state.
+        change_key(state.database_client(), &entries, 1, "0".to_string()).await;
+
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3/latest?bucket=0").await;
+        let latest = result.results();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].s3_object_id, entries.s3_objects[1].s3_object_id);
+        assert!(!latest[0].is_current_state);
+
+        let current: ListResponse<S3> = response_from_get(state.clone(), "/s3?bucket=0").await;
+        assert_eq!(
+            current.results()[0].s3_object_id,
+            entries.s3_objects[0].s3_object_id
+        );
+
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3/latest?key=0&keySuffix=0").await;
+        let latest = result.results();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].s3_object_id, entries.s3_objects[1].s3_object_id);
+
+        let result: ListResponse<S3> = response_from_get(state, "/s3/latest").await;
+        assert_eq!(result.pagination().count, 9);
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn list_s3_multiple_and_filters(pool: PgPool) {
         let state = AppState::from_pool(pool).await.unwrap();
@@ -1099,6 +2392,457 @@ pub(crate) mod tests {
         assert_eq!(result.n_records, 2);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn count_s3_by_reason_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let mut entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        for (i, reason) in [
+            (0, Reason::Crawl),
+            (1, Reason::Crawl),
+            (2, Reason::Crawl),
+            (3, Reason::CrawlRestored),
+            (4, Reason::CreatedPut),
+        ] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.reason = Set(reason);
+            entries.s3_objects[i] = model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let expected = |filter: fn(&&S3) -> bool| {
+            ReasonCount::new(
+                entries
+                    .s3_objects
+                    .iter()
+                    .filter(filter)
+                    .counts_by(|s3| s3.reason.clone())
+                    .into_iter()
+                    .map(|(reason, count)| (reason, count as u64))
+                    .collect(),
+            )
+        };
+
+        let result: ReasonCount =
+            response_from_get(state.clone(), "/s3/count/reason?currentState=false").await;
+        assert_eq!(result, expected(|_| true));
+        assert_eq!(result.get(&Reason::Crawl), Some(3));
+        assert_eq!(result.get(&Reason::CrawlRestored), Some(1));
+
+        let result: ReasonCount = response_from_get(
+            state.clone(),
+            "/s3/count/reason?currentState=false&bucket=0",
+        )
+        .await;
+        assert_eq!(result, expected(|s3| s3.bucket == "0"));
+
+        let result: ReasonCount = response_from_get(state, "/s3/count/reason").await;
+        assert_eq!(
+            result,
+            expected(|s3| s3.is_current_state && !s3.is_delete_marker)
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn count_s3_by_key_depth_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        for (i, key) in [
+            (0, "a/b/c"),
+            (1, "a/b/d"),
+            (2, "a/e"),
+            (3, "/f"),
+            (4, "a/b/c/d/"),
+        ] {
+            change_key(state.database_client(), &entries, i, key.to_string()).await;
+        }
+
+        let result: KeyDepthCount =
+            response_from_get(state.clone(), "/s3/count/depth?currentState=false").await;
+        assert_eq!(
+            result,
+            KeyDepthCount::new(BTreeMap::from_iter([(0, 5), (1, 2), (2, 2), (4, 1)]))
+        );
+
+        let result: KeyDepthCount =
+            response_from_get(state.clone(), "/s3/count/depth?currentState=false&bucket=0").await;
+        assert_eq!(result, KeyDepthCount::new(BTreeMap::from_iter([(2, 2)])));
+
+        let result: KeyDepthCount = response_from_get(state, "/s3/count/depth?key=a*").await;
+        assert_eq!(result.get(2), Some(1));
+        assert_eq!(result.get(4), Some(1));
+        assert_eq!(result.get(1), Some(1));
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn count_s3_by_size_e_tag_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Records 0, 1 and 3 share a size and e_tag, and record 2 only shares the size.
+        for (i, size, e_tag) in [
+            (0, 1, "e_tag"),
+            (1, 1, "e_tag"),
+            (2, 1, "other"),
+            (3, 1, "e_tag"),
+        ] {
+            let mut model = entries[i].clone().into_active_model();
+            model.size = Set(Some(size));
+            model.e_tag = Set(Some(e_tag.to_string()));
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3?currentState=false&size=1&eTag=e_tag").await;
+        assert_eq!(
+            result
+                .results()
+                .iter()
+                .map(|s3| s3.s3_object_id)
+                .collect::<Vec<_>>(),
+            vec![
+                entries[0].s3_object_id,
+                entries[1].s3_object_id,
+                entries[3].s3_object_id
+            ]
+        );
+
+        let result: Vec<SizeETagCount> =
+            response_from_get(state.clone(), "/s3/count/size-etag?currentState=false").await;
+        assert_eq!(result, vec![SizeETagCount::new(1, "e_tag".to_string(), 3)]);
+
+        // Only records 0 and 2 are current, which don't share an e_tag.
+        let result: Vec<SizeETagCount> =
+            response_from_get(state.clone(), "/s3/count/size-etag").await;
+        assert!(result.is_empty());
+
+        let result: Vec<SizeETagCount> =
+            response_from_get(state, "/s3/count/size-etag?currentState=false&limit=0").await;
+        assert!(result.is_empty());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn count_s3_by_extension_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // The size of each record is its index, and records 6 to 9 have keys without an extension.
+        for (i, key) in [
+            (0, "a/sample.R1.FASTQ.GZ"),
+            (1, "a/sample.R2.fastq.gz"),
+            (2, "b/sample.bam"),
+            (3, "b/sample.bam.bai"),
+            (4, "c.d/.hidden"),
+            (5, "c/x.bam"),
+        ] {
+            change_key(state.database_client(), &entries, i, key.to_string()).await;
+        }
+
+        let ids = |result: ListResponse<S3>| {
+            result
+                .results()
+                .iter()
+                .map(|s3| s3.s3_object_id)
+                .collect::<Vec<_>>()
+        };
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3?currentState=false&keySuffix=.fastq.gz").await;
+        assert_eq!(ids(result), vec![entries.s3_objects[1].s3_object_id]);
+
+        let result: ListResponse<S3> = response_from_get(
+            state.clone(),
+            "/s3?currentState=false&keySuffix=.fastq.gz&caseSensitive=false",
+        )
+        .await;
+        assert_eq!(
+            ids(result),
+            vec![
+                entries.s3_objects[0].s3_object_id,
+                entries.s3_objects[1].s3_object_id
+            ]
+        );
+
+        let result: ListResponse<S3> = response_from_get(
+            state.clone(),
+            "/s3?currentState=false&keySuffix[]=.bam&keySuffix[]=.bai",
+        )
+        .await;
+        assert_eq!(
+            ids(result),
+            vec![
+                entries.s3_objects[2].s3_object_id,
+                entries.s3_objects[3].s3_object_id,
+                entries.s3_objects[5].s3_object_id
+            ]
+        );
+
+        // Wildcard characters in the suffix are matched literally.
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3?currentState=false&keySuffix=.ba_").await;
+        assert!(result.results().is_empty());
+
+        let result: Vec<ExtensionCount> =
+            response_from_get(state.clone(), "/s3/count/extension?currentState=false").await;
+        assert_eq!(
+            result,
+            vec![
+                ExtensionCount::new(None, 5, 4 + 6 + 7 + 8 + 9),
+                ExtensionCount::new(Some(".bam".to_string()), 2, 2 + 5),
+                ExtensionCount::new(Some(".fastq.gz".to_string()), 2, 1),
+                ExtensionCount::new(Some(".bai".to_string()), 1, 3),
+            ]
+        );
+
+        let result: Vec<ExtensionCount> = response_from_get(
+            state,
+            "/s3/count/extension?currentState=false&keySuffix=.gz",
+        )
+        .await;
+        assert_eq!(
+            result,
+            vec![ExtensionCount::new(Some(".fastq.gz".to_string()), 1, 1)]
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn exclude_folder_placeholders_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Only a zero-byte object with a trailing slash is a folder placeholder.
+        change_key(state.database_client(), &entries, 0, "a/".to_string()).await;
+        change_key(state.database_client(), &entries, 1, "b/".to_string()).await;
+        change_key(state.database_client(), &entries, 2, "c/empty".to_string()).await;
+        change_size(state.database_client(), &entries, 2, Some(0)).await;
+
+        let ids = |result: ListResponse<S3>| {
+            result
+                .results()
+                .iter()
+                .map(|s3| s3.s3_object_id)
+                .collect::<Vec<_>>()
+        };
+        let expected = entries.s3_objects[1..]
+            .iter()
+            .map(|s3| s3.s3_object_id)
+            .collect::<Vec<_>>();
+
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3?currentState=false").await;
+        assert_eq!(result.results().len(), 10);
+        let result: ListResponse<S3> = response_from_get(
+            state.clone(),
+            "/s3?currentState=false&excludeFolderPlaceholders=true",
+        )
+        .await;
+        assert_eq!(ids(result), expected);
+
+        // The config sets the default, which the request can override.
+        let state = state.with_config(Config {
+            api_exclude_folder_placeholders: true,
+            ..Default::default()
+        });
+        let result: ListResponse<S3> =
+            response_from_get(state.clone(), "/s3?currentState=false").await;
+        assert_eq!(ids(result), expected);
+        let result: ListCount =
+            response_from_get(state.clone(), "/s3/count?currentState=false").await;
+        assert_eq!(result.n_records(), 9);
+        let result: ListCount = response_from_get(
+            state,
+            "/s3/count?currentState=false&excludeFolderPlaceholders=false",
+        )
+        .await;
+        assert_eq!(result.n_records(), 10);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn attribute_keys_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        for (i, attributes) in [
+            (
+                0,
+                json!({"portalRunId": "0", "sequencing": {"runId": "0"}, "qc": true}),
+            ),
+            (2, json!({"portalRunId": 2, "sequencing": null})),
+            (4, json!({"portalRunId": "4", "libraries": ["L1", "L2"]})),
+            (6, json!(["not", "an", "object"])),
+        ] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.attributes = Set(Some(attributes));
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let result: Vec<AttributeKey> =
+            response_from_get(state.clone(), "/s3/attributes/keys").await;
+        assert_eq!(
+            result,
+            vec![
+                AttributeKey::new(
+                    "attributeId".to_string(),
+                    1,
+                    BTreeMap::from([("string".to_string(), 1)])
+                ),
+                AttributeKey::new(
+                    "libraries".to_string(),
+                    1,
+                    BTreeMap::from([("array".to_string(), 1)])
+                ),
+                AttributeKey::new(
+                    "nestedId".to_string(),
+                    1,
+                    BTreeMap::from([("object".to_string(), 1)])
+                ),
+                AttributeKey::new(
+                    "portalRunId".to_string(),
+                    3,
+                    BTreeMap::from([("number".to_string(), 1), ("string".to_string(), 2)])
+                ),
+                AttributeKey::new(
+                    "qc".to_string(),
+                    1,
+                    BTreeMap::from([("boolean".to_string(), 1)])
+                ),
+                AttributeKey::new(
+                    "sequencing".to_string(),
+                    2,
+                    BTreeMap::from([("null".to_string(), 1), ("object".to_string(), 1)])
+                ),
+            ]
+        );
+
+        // Filters apply before finding the keys.
+        let result: Vec<AttributeKey> = response_from_get(
+            state.clone(),
+            "/s3/attributes/keys?bucket=0&currentState=false",
+        )
+        .await;
+        assert_eq!(
+            result.iter().map(|key| key.key()).collect::<Vec<_>>(),
+            vec!["attributeId", "nestedId", "portalRunId", "qc", "sequencing"]
+        );
+        assert_eq!(result[2].count(), 1);
+
+        // A sample only scans some of the records.
+        let result: Vec<AttributeKey> =
+            response_from_get(state.clone(), "/s3/attributes/keys?sample=1").await;
+        assert!(result.iter().all(|key| key.count() == 1));
+
+        let (status, _) = response_from::<Value>(
+            state,
+            "/s3/attributes/keys?sample=0",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn similar_attribute_keys_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        for (i, attributes) in [
+            (0, json!({"portalRunId": "0"})),
+            (2, json!({"portalRunId": "2"})),
+            (4, json!({"portalRnuId": "4", "qc": true})),
+            (6, json!({"portalrunid": "6", "qa": true})),
+        ] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.attributes = Set(Some(attributes));
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let key = |key: &str, count| {
+            AttributeKey::new(
+                key.to_string(),
+                count,
+                BTreeMap::from([("string".to_string(), count)]),
+            )
+        };
+
+        // Short keys such as `qc` and `qa` are not grouped.
+        let result: Vec<AttributeKeyGroup> =
+            response_from_get(state.clone(), "/s3/attributes/keys/similar").await;
+        assert_eq!(
+            result,
+            vec![AttributeKeyGroup::new(vec![
+                key("portalRunId", 2),
+                key("portalRnuId", 1),
+                key("portalrunid", 1),
+            ])]
+        );
+        assert_eq!(result[0].count(), 4);
+
+        let result: Vec<AttributeKeyGroup> =
+            response_from_get(state.clone(), "/s3/attributes/keys/similar?maxDistance=1").await;
+        assert_eq!(
+            result,
+            vec![AttributeKeyGroup::new(vec![
+                key("portalRunId", 2),
+                key("portalRnuId", 1),
+            ])]
+        );
+
+        let (status, _) = response_from::<Value>(
+            state,
+            "/s3/attributes/keys/similar?maxDistance=0",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn attribute_key_edit_distance() {
+        assert_eq!(edit_distance("portalRunId", "portalRunId"), 0);
+        assert_eq!(edit_distance("portalRunId", "portalRnuId"), 1);
+        assert_eq!(edit_distance("portalRunId", "portalRun"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "key"), 3);
+    }
+
     pub(crate) fn mock_get_object(
         key: &'static str,
         bucket: &'static str,
@@ -1154,4 +2898,24 @@ pub(crate) mod tests {
             .await
             .1
     }
+
+    async fn conditional_response(
+        state: AppState,
+        header: Option<(HeaderName, HeaderValue)>,
+    ) -> (StatusCode, HeaderMap) {
+        let mut request = Request::builder()
+            .uri("/s3?bucket=0&currentState=false")
+            .header(HOST, "example.com");
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+
+        let response = api_router(state)
+            .unwrap()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        (response.status(), response.headers().clone())
+    }
 }
@@ -8,8 +8,9 @@ use crate::database::entities::s3_crawl::Model as Crawl;
 use axum::http::HeaderValue;
 use axum::http::header::InvalidHeaderName;
 use axum::http::method::InvalidMethod;
-use axum::{Extension, Json, Router};
+use axum::{Extension, Json, Router, middleware};
 use chrono::Duration;
+use sea_orm::{DatabaseTransaction, TransactionTrait};
 use serde_qs::axum::QsQueryConfig;
 use sqlx::PgPool;
 use tokio::sync::Mutex;
@@ -23,24 +24,65 @@ use crate::database;
 use crate::env::Config;
 use crate::error::Error::{ApiConfigurationError, CrawlError};
 use crate::error::Result;
+use crate::queries::timing::set_statement_timeout;
+use crate::routes::audit::audit_router;
+use crate::routes::backfill::{BackfillSink, QueueBackfillSink};
+use crate::routes::collect::collect_router;
 use crate::routes::crawl::crawl_router;
+use crate::routes::diff::diff_router;
+use crate::routes::drift::drift_router;
 use crate::routes::error::fallback;
+use crate::routes::explain::explain_router;
+use crate::routes::export::export_router;
 use crate::routes::get::*;
+use crate::routes::history::history_router;
+use crate::routes::import::import_router;
 use crate::routes::ingest::ingest_router;
+use crate::routes::inventory::inventory_router;
+use crate::routes::lifecycle::lifecycle_router;
 use crate::routes::list::*;
 use crate::routes::openapi::swagger_ui;
+use crate::routes::prefix::prefix_router;
+use crate::routes::preflight::preflight_router;
+use crate::routes::presence::presence_router;
+use crate::routes::region::{BucketRegions, region_router};
+use crate::routes::sequencer::sequencer_router;
+use crate::routes::stats::stats_router;
+use crate::routes::tenant::tenant_scope;
+use crate::routes::tiering::tiering_router;
 use crate::routes::update::update_router;
 
+pub mod audit;
+pub mod backfill;
+pub mod collect;
+pub mod conditional;
 pub mod crawl;
+pub mod diff;
+pub mod drift;
 pub mod error;
+pub mod explain;
+pub mod export;
 pub mod filter;
 pub mod get;
 pub mod header;
+pub mod history;
+pub mod import;
 pub mod ingest;
+pub mod inventory;
+pub mod key_path;
+pub mod lifecycle;
 pub mod list;
 pub mod openapi;
 pub mod pagination;
+pub mod prefix;
+pub mod preflight;
+pub mod presence;
 pub mod presign;
+pub mod region;
+pub mod sequencer;
+pub mod stats;
+pub mod tenant;
+pub mod tiering;
 pub mod update;
 
 /// The join handle crawl task.
@@ -57,6 +99,8 @@ pub struct AppState {
     use_tls_links: bool,
     params_field_names: Arc<HashSet<String>>,
     crawl_task: Arc<Mutex<Option<CrawlTask>>>,
+    checksum_backfill: Arc<dyn BackfillSink>,
+    bucket_regions: BucketRegions,
 }
 
 impl AppState {
@@ -69,6 +113,12 @@ impl AppState {
         secrets_manager_client: Arc<secrets_manager::Client>,
         use_tls_links: bool,
     ) -> Self {
+        let s3_client = Arc::new(
+            Arc::unwrap_or_clone(s3_client)
+                .with_default_version_id(config.ingester_default_version_id())
+                .with_max_concurrency(config.s3_max_concurrency()),
+        );
+
         Self {
             database_client,
             config,
@@ -78,6 +128,8 @@ impl AppState {
             use_tls_links,
             params_field_names: Arc::new(attributes_s3_field_names()),
             crawl_task: Arc::new(Mutex::new(None)),
+            checksum_backfill: Arc::new(QueueBackfillSink::default()),
+            bucket_regions: Default::default(),
         }
     }
 
@@ -101,6 +153,11 @@ impl AppState {
 
     /// Modify the config.
     pub fn with_config(mut self, config: Config) -> Self {
+        self.s3_client = Arc::new(
+            Arc::unwrap_or_clone(self.s3_client)
+                .with_default_version_id(config.ingester_default_version_id())
+                .with_max_concurrency(config.s3_max_concurrency()),
+        );
         self.config = Arc::new(config);
         self
     }
@@ -113,7 +170,23 @@ impl AppState {
 
     /// Modify the s3 client.
     pub fn with_s3_client(mut self, client: s3::Client) -> Self {
-        self.s3_client = Arc::new(client);
+        self.s3_client = Arc::new(
+            client
+                .with_default_version_id(self.config.ingester_default_version_id())
+                .with_max_concurrency(self.config.s3_max_concurrency()),
+        );
+        self
+    }
+
+    /// Modify the sqs client.
+    pub fn with_sqs_client(mut self, client: sqs::Client) -> Self {
+        self.sqs_client = Arc::new(client);
+        self
+    }
+
+    /// Modify the sink which receives checksum backfills.
+    pub fn with_checksum_backfill(mut self, sink: Arc<dyn BackfillSink>) -> Self {
+        self.checksum_backfill = sink;
         self
     }
 
@@ -148,6 +221,32 @@ impl AppState {
         &self.sqs_client
     }
 
+    /// Get the sink which receives checksum backfills.
+    pub fn checksum_backfill(&self) -> &dyn BackfillSink {
+        self.checksum_backfill.as_ref()
+    }
+
+    /// Get the region of a bucket. This is fetched once per bucket and cached afterwards.
+    pub async fn bucket_region(&self, bucket: &str) -> Result<String> {
+        self.bucket_regions
+            .get_or_fetch(&self.s3_client, bucket)
+            .await
+    }
+
+    /// Begin a transaction on the read connection, with the configured statement timeout.
+    pub async fn begin_read(&self) -> Result<DatabaseTransaction> {
+        let txn = self.database_client.read_connection_ref().begin().await?;
+        set_statement_timeout(&txn, self.config.api_statement_timeout()).await?;
+        Ok(txn)
+    }
+
+    /// Begin a transaction on the primary connection, with the configured statement timeout.
+    pub async fn begin(&self) -> Result<DatabaseTransaction> {
+        let txn = self.database_client.connection_ref().begin().await?;
+        set_statement_timeout(&txn, self.config.api_statement_timeout()).await?;
+        Ok(txn)
+    }
+
     /// Get the links TLS setting.
     pub fn use_tls_links(&self) -> bool {
         self.use_tls_links
@@ -228,6 +327,24 @@ pub fn api_router(state: AppState) -> Result<Router> {
         .merge(list_router())
         .merge(update_router())
         .merge(crawl_router())
+        .merge(collect_router())
+        .merge(tiering_router())
+        .merge(diff_router())
+        .merge(explain_router())
+        .merge(inventory_router())
+        .merge(export_router())
+        .merge(prefix_router())
+        .merge(region_router())
+        .merge(audit_router())
+        .merge(preflight_router())
+        .merge(presence_router())
+        .merge(import_router())
+        .merge(lifecycle_router())
+        .merge(stats_router())
+        .merge(sequencer_router())
+        .merge(history_router())
+        .merge(drift_router())
+        .layer(middleware::from_fn_with_state(state.clone(), tenant_scope))
         .layer(Extension(QsQueryConfig::new().config(
             serde_qs::Config::new().use_form_encoding(true).max_depth(5),
         )))
@@ -10,20 +10,38 @@ use utoipa::{Modify, OpenApi, ToSchema, openapi};
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::database::entities::s3_crawl::Model as Crawl;
+use crate::database::entities::s3_crawl_schedule::Model as CrawlSchedule;
 use crate::database::entities::s3_object::Model as S3;
 use crate::database::entities::sea_orm_active_enums::ArchiveStatus;
 use crate::database::entities::sea_orm_active_enums::EventType;
 use crate::database::entities::sea_orm_active_enums::Reason;
 use crate::database::entities::sea_orm_active_enums::StorageClass;
+use crate::routes::audit::*;
+use crate::routes::collect::*;
 use crate::routes::crawl::*;
+use crate::routes::diff::*;
+use crate::routes::drift::*;
 use crate::routes::error::ErrorResponse;
+use crate::routes::explain::*;
+use crate::routes::export::*;
 use crate::routes::filter::wildcard::Wildcard;
 use crate::routes::filter::*;
 use crate::routes::get::*;
+use crate::routes::history::*;
+use crate::routes::import::*;
 use crate::routes::ingest::*;
+use crate::routes::inventory::*;
+use crate::routes::lifecycle::*;
 use crate::routes::list::*;
 use crate::routes::pagination::*;
-use crate::routes::presign::ContentDisposition;
+use crate::routes::prefix::*;
+use crate::routes::preflight::*;
+use crate::routes::presence::*;
+use crate::routes::presign::{ContentDisposition, PresignEntry, PresignEntryResult};
+use crate::routes::region::*;
+use crate::routes::sequencer::*;
+use crate::routes::stats::*;
+use crate::routes::tiering::*;
 use crate::routes::update::*;
 
 /// The path to the swagger ui.
@@ -56,17 +74,58 @@ pub struct Uuid(pub uuid::Uuid);
         list_s3,
         presign_s3,
         attributes_s3,
+        attribute_keys_s3,
+        similar_attribute_keys_s3,
         get_s3_by_id,
+        get_s3_attributes_by_id,
+        compare_live_s3_by_id,
         presign_s3_by_id,
+        presign_s3_entries,
         count_s3,
+        count_s3_by_reason,
+        count_s3_by_key_depth,
+        count_s3_by_size_e_tag,
+        count_s3_by_extension,
+        list_deleted_s3,
+        list_latest_s3,
+        tiering_s3,
+        diff_s3,
+        explain_s3,
+        export_inventory_s3,
+        export_parquet_s3,
+        export_to_s3,
+        list_s3_prefixes,
+        browse_s3,
+        get_bucket_region,
+        audit_accessibility_s3,
+        presign_preflight_s3,
+        import_s3,
+        list_s3_by_ingest_id,
+        list_s3_storage_class_transitions,
+        storage_class_history_s3,
+        ingestion_stats,
+        sequencer_anomalies_s3,
+        history_s3,
+        drift_s3,
+        key_presence_s3,
         ingest_from_sqs,
         update_s3_attributes,
         update_s3_collection_attributes,
+        validate_s3_attributes,
+        bulk_update_s3_attributes,
+        bulk_update_s3_storage_class,
+        collect_s3,
+        requeue_s3,
+        retry_tags_s3,
         crawl_s3,
         crawl_sync_s3,
         list_crawl_s3,
         count_crawl_s3,
-        get_crawl_s3_by_id
+        get_crawl_s3_by_id,
+        cancel_crawl_s3,
+        schedule_crawl_s3,
+        list_due_crawl_s3,
+        normalize_sequencers_s3
     ),
     components(
         schemas(
@@ -77,19 +136,30 @@ pub struct Uuid(pub uuid::Uuid);
             EventType,
             ErrorResponse,
             ListCount,
+            ReasonCount,
+            KeyDepthCount,
+            SizeETagCount,
+            ExtensionCount,
+            AttributeKey,
+            AttributeKeyGroup,
             IngestCount,
             DateTimeWithTimeZone,
             Wildcard,
             Json,
             ListResponse<Url>,
-            ListResponse<S3>,
+            ListResponse<AnnotatedS3>,
+            AnnotatedS3,
+            LiveComparison,
             ContentDisposition,
+            PresignEntry,
+            PresignEntryResult,
             PaginatedResponse,
             Pagination,
             Links,
             PatchBody,
             Patch,
             Join,
+            Origin,
             FilterJoin<Wildcard>,
             FilterJoin<StorageClass>,
             FilterJoin<i64>,
@@ -98,7 +168,56 @@ pub struct Uuid(pub uuid::Uuid);
             FilterJoin<ArchiveStatus>,
             FilterJoin<CrawlStatus>,
             Crawl,
-            CrawlRequest
+            CrawlRequest,
+            CrawlSchedule,
+            CrawlScheduleRequest,
+            CollectResult,
+            RequeueResult,
+            TagRetryResult,
+            BulkAttributes,
+            BulkAttributesResult,
+            StorageClassCorrection,
+            StorageClassCorrectionResult,
+            TieringRecommendation,
+            BucketDiff,
+            ChangedObject,
+            CurrentStateExplanation,
+            ExplainedEvent,
+            PrefixListing,
+            PrefixObject,
+            PrefixSource,
+            ETagFormat,
+            BrowseChild,
+            BrowseListing,
+            BucketRegion,
+            AccessibilityAudit,
+            LiveAccessibility,
+            AccessibilityDisagreement,
+            PresignPreflightReport,
+            PresignPreflight,
+            PreflightFailure,
+            ExportFormat,
+            ExportDestination,
+            ExportResult,
+            ImportRecord,
+            ImportLineError,
+            ImportResult,
+            IngestIdGroup,
+            StorageClassTransition,
+            StorageClassTransitions,
+            HistoryInterval,
+            StorageClassCount,
+            StorageClassPeriod,
+            StorageClassHistory,
+            IngestionStats,
+            SequencerReport,
+            SequencerAnomaly,
+            SequencerAnomalyKind,
+            DriftReport,
+            DriftedChild,
+            DriftKind,
+            KeyPresence,
+            NormalizeSequencersResult
         )
     ),
     modifiers(&SecurityAddon),
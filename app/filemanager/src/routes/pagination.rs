@@ -4,11 +4,17 @@
 use std::num::NonZeroU64;
 use std::result;
 
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
 use serde::{Deserialize, Deserializer, Serialize};
 use url::Url;
+use utoipa::openapi::Required;
+use utoipa::openapi::path::{Parameter, ParameterBuilder, ParameterIn};
+use utoipa::openapi::schema::{KnownFormat, ObjectBuilder, SchemaFormat, Type};
 use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 
-use crate::error::Error::OverflowError;
+use crate::error::Error::{InvalidQuery, OverflowError};
 use crate::error::{Error, Result};
 
 /// The response type for list operations.
@@ -24,7 +30,7 @@ pub struct ListResponse<M> {
 }
 
 /// The paginated links to the next and previous page.
-#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Links {
     /// The previous page link.
@@ -104,6 +110,39 @@ impl<M> ListResponse<M> {
         ))
     }
 
+    /// Create a list response from the results and next cursor. The next link continues from the
+    /// cursor, and there is no previous link because cursors only move forward.
+    pub fn from_next_cursor(
+        pagination: Pagination,
+        results: Vec<M>,
+        next_cursor: Option<String>,
+        page_link: Url,
+        count: u64,
+    ) -> Result<Self> {
+        let next = next_cursor.map(|next_cursor| {
+            let query_params = page_link
+                .query_pairs()
+                .filter(|(key, _)| key != "page" && key != "cursor")
+                .collect::<Vec<_>>();
+
+            let mut page_link = page_link.clone();
+            page_link.set_query(None);
+
+            page_link.query_pairs_mut().extend_pairs(query_params);
+            page_link
+                .query_pairs_mut()
+                .append_pair("cursor", &next_cursor);
+
+            page_link
+        });
+
+        Ok(Self::new(
+            Links::new(None, next),
+            PaginatedResponse::new(count, pagination),
+            results,
+        ))
+    }
+
     /// Get the links.
     pub fn links(&self) -> &Links {
         &self.links
@@ -189,6 +228,183 @@ impl Pagination {
     }
 }
 
+/// Cursor query parameters for list operations that support keyset pagination.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams, Eq, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct CursorParams {
+    /// An opaque cursor used to fetch pages with keyset pagination instead of page numbers.
+    /// Set this to an empty value to fetch the first page, and follow the `next` link to fetch
+    /// the following pages. A cursor can only be used with the same filter that created it.
+    #[param(nullable = false, required = false)]
+    pub(crate) cursor: Option<String>,
+}
+
+impl CursorParams {
+    /// Create new cursor params.
+    pub fn new(cursor: Option<String>) -> Self {
+        Self { cursor }
+    }
+
+    /// Get the cursor.
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+}
+
+/// Query parameters for operations which return at most `MAX` results per call rather than
+/// paginating them. The limit defaults to `MAX`, and larger limits are capped at `MAX`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LimitParams<const MAX: u64> {
+    limit: u64,
+}
+
+impl<const MAX: u64> Default for LimitParams<MAX> {
+    fn default() -> Self {
+        Self { limit: MAX }
+    }
+}
+
+impl<const MAX: u64> LimitParams<MAX> {
+    /// Create new limit params.
+    pub fn new(limit: u64) -> Self {
+        Self { limit }
+    }
+
+    /// Get the limit, capped at the maximum.
+    pub fn limit(&self) -> u64 {
+        self.limit.min(MAX)
+    }
+}
+
+/// Serde cannot derive implementations for const generics, so this deserializes the optional
+/// limit and uses `MAX` if it is missing.
+impl<'de, const MAX: u64> Deserialize<'de> for LimitParams<MAX> {
+    fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Limit {
+            limit: Option<u64>,
+        }
+
+        let Limit { limit } = Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            limit: limit.unwrap_or(MAX),
+        })
+    }
+}
+
+/// The derive macro cannot use the const generic in the parameter schema, so the default and
+/// maximum are set here.
+impl<const MAX: u64> IntoParams for LimitParams<MAX> {
+    fn into_params(parameter_in_provider: impl Fn() -> Option<ParameterIn>) -> Vec<Parameter> {
+        let schema = ObjectBuilder::new()
+            .schema_type(Type::Integer)
+            .format(Some(SchemaFormat::KnownFormat(KnownFormat::Int64)))
+            .default(Some(MAX.into()))
+            .minimum(Some(0))
+            .maximum(Some(MAX));
+
+        vec![
+            ParameterBuilder::new()
+                .name("limit")
+                .parameter_in(parameter_in_provider().unwrap_or(ParameterIn::Query))
+                .description(Some(format!(
+                    "The maximum number of results to return. This is capped at {MAX} per call."
+                )))
+                .required(Required::False)
+                .schema(Some(schema))
+                .build(),
+        ]
+    }
+}
+
+/// The position of the last record on a page, which the next page starts after.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CursorPosition {
+    pub(crate) sequencer: Option<String>,
+    pub(crate) s3_object_id: Uuid,
+}
+
+impl CursorPosition {
+    /// Create a new cursor position.
+    pub fn new(sequencer: Option<String>, s3_object_id: Uuid) -> Self {
+        Self {
+            sequencer,
+            s3_object_id,
+        }
+    }
+}
+
+/// A keyset pagination cursor. This is encoded as opaque base64 and contains a hash of the filter
+/// that it was created with, so that it cannot be used to continue a different query.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Cursor {
+    filter_hash: String,
+    after: Option<CursorPosition>,
+}
+
+impl Cursor {
+    /// Create a new cursor.
+    pub fn new(filter_hash: String, after: Option<CursorPosition>) -> Self {
+        Self { filter_hash, after }
+    }
+
+    /// Hash the filter parameters that a cursor is created with.
+    pub fn hash_filter<T: Serialize>(filter: &T) -> Result<String> {
+        let filter = serde_json::to_vec(filter)?;
+        Ok(hex::encode(md5::compute(filter).0))
+    }
+
+    /// Decode a cursor and check that it was created with the same filter hash. An empty cursor
+    /// starts from the beginning of the collection.
+    pub fn decode(cursor: &str, filter_hash: String) -> Result<Self> {
+        if cursor.is_empty() {
+            return Ok(Self::new(filter_hash, None));
+        }
+
+        let decoded: Self = BASE64_URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|cursor| serde_json::from_slice(&cursor).ok())
+            .ok_or_else(|| InvalidQuery("invalid pagination cursor".to_string()))?;
+
+        if decoded.filter_hash != filter_hash {
+            return Err(InvalidQuery(
+                "the pagination cursor was created with a different filter, \
+                start again from an empty cursor"
+                    .to_string(),
+            ));
+        }
+
+        Ok(decoded)
+    }
+
+    /// Encode the cursor as opaque base64.
+    pub fn encode(&self) -> Result<String> {
+        Ok(BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?))
+    }
+
+    /// Get the filter hash.
+    pub fn filter_hash(&self) -> &str {
+        &self.filter_hash
+    }
+
+    /// Get the position that the page starts after.
+    pub fn after(&self) -> Option<&CursorPosition> {
+        self.after.as_ref()
+    }
+
+    /// Create the cursor for the next page, which starts after the position.
+    pub fn next(&self, after: CursorPosition) -> Self {
+        Self::new(self.filter_hash.clone(), Some(after))
+    }
+}
+
 /// The default page size.
 const DEFAULT_ROWS_PER_PAGE: u64 = 1000;
 
@@ -302,6 +518,100 @@ mod tests {
         assert_eq!(status_code, StatusCode::BAD_REQUEST);
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_api_paginate_cursor(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_shuffle(true)
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        let mut results = vec![];
+        let mut next = Some("/s3?currentState=false&rowsPerPage=3&cursor=".to_string());
+        while let Some(uri) = next {
+            let result: ListResponse<S3Object> = response_from_get(state.clone(), &uri).await;
+            assert_eq!(result.links().previous, None);
+            assert_eq!(result.pagination().count, 10);
+
+            results.extend(result.results().to_vec());
+            next = result.links().next.as_ref().map(|next| {
+                assert!(next.query_pairs().all(|(key, _)| key != "page"));
+                format!("{}?{}", next.path(), next.query().unwrap())
+            });
+        }
+
+        assert_eq!(results, entries);
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_api_paginate_cursor_different_filter(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let result: ListResponse<S3Object> = response_from_get(
+            state.clone(),
+            "/s3?currentState=false&bucket=0&rowsPerPage=1&cursor=",
+        )
+        .await;
+        let next = result.links().next.clone().unwrap();
+        let cursor = next
+            .query_pairs()
+            .find_map(|(key, value)| (key == "cursor").then(|| value.to_string()))
+            .unwrap();
+
+        // The same filter can continue from the cursor.
+        let (status_code, _) = response_from::<ListResponse<S3Object>>(
+            state.clone(),
+            &format!("/s3?currentState=false&bucket=0&rowsPerPage=1&cursor={cursor}"),
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status_code, StatusCode::OK);
+
+        // A different filter, case sensitivity or current state rejects the cursor.
+        for uri in [
+            format!("/s3?currentState=false&bucket=1&rowsPerPage=1&cursor={cursor}"),
+            format!("/s3?currentState=false&rowsPerPage=1&cursor={cursor}"),
+            format!("/s3?currentState=false&bucket=0&caseSensitive=false&cursor={cursor}"),
+            format!("/s3?bucket=0&rowsPerPage=1&cursor={cursor}"),
+            "/s3?currentState=false&bucket=0&cursor=invalid".to_string(),
+            format!("/s3?currentState=false&bucket=0&page=2&cursor={cursor}"),
+        ] {
+            let (status_code, _) =
+                response_from::<ErrorResponse>(state.clone(), &uri, Method::GET, Body::empty())
+                    .await;
+            assert_eq!(status_code, StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[test]
+    fn cursor_encode_decode() {
+        let hash = Cursor::hash_filter(&("filter", true)).unwrap();
+        let cursor = Cursor::new(
+            hash.clone(),
+            Some(CursorPosition::new(Some("1".to_string()), Uuid::default())),
+        );
+
+        let encoded = cursor.encode().unwrap();
+        assert_eq!(Cursor::decode(&encoded, hash.clone()).unwrap(), cursor);
+        assert_eq!(
+            Cursor::decode("", hash.clone()).unwrap(),
+            Cursor::new(hash, None)
+        );
+
+        let other = Cursor::hash_filter(&("filter", false)).unwrap();
+        assert!(matches!(
+            Cursor::decode(&encoded, other),
+            Err(InvalidQuery(_))
+        ));
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn list_s3_api_paginate_existing_no_page_size(pool: PgPool) {
         let state = AppState::from_pool(pool).await.unwrap();
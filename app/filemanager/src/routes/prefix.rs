@@ -0,0 +1,784 @@
+//! Route logic for listing the child prefixes and objects under a prefix.
+//!
+
+use aws_sdk_s3::operation::list_object_versions::ListObjectVersionsOutput;
+use axum::extract::{Request, State};
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use url::Url;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::Error::{ConversionError, OverflowError};
+use crate::error::Result;
+use crate::events::aws::FlatS3EventMessage;
+use crate::queries::prefix::PrefixQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, Query};
+use crate::routes::header::HeaderParser;
+use crate::routes::pagination::{Links, ListResponse, PaginatedResponse, Pagination};
+use crate::routes::tenant::TenantScope;
+
+/// The delimiter used to split keys into prefixes.
+pub const PREFIX_DELIMITER: &str = "/";
+
+/// Where to list prefixes from, either the filemanager `database` or `s3`.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PrefixSource {
+    /// List prefixes from the current state of records in the database.
+    #[default]
+    Database,
+    /// List prefixes directly from S3.
+    S3,
+}
+
+/// Params for listing prefixes.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct PrefixParams {
+    /// The bucket to list.
+    #[param(nullable = false, required = true)]
+    bucket: String,
+    /// The prefix to list the children of. This should usually end with a `/`. By default, the
+    /// children at the root of the bucket are listed.
+    #[serde(default)]
+    #[param(nullable = false, required = false)]
+    prefix: Option<String>,
+    /// Whether to list from the database or from S3. Defaults to the database.
+    #[serde(default)]
+    #[param(nullable = false, required = false)]
+    source: PrefixSource,
+}
+
+impl PrefixParams {
+    /// Create new prefix params.
+    pub fn new(bucket: String, prefix: Option<String>, source: PrefixSource) -> Self {
+        Self {
+            bucket,
+            prefix,
+            source,
+        }
+    }
+
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Get the prefix.
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Get the source.
+    pub fn source(&self) -> PrefixSource {
+        self.source
+    }
+}
+
+/// An object directly under a prefix.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefixObject {
+    /// The key of the object.
+    pub(crate) key: String,
+    /// The version id of the object.
+    pub(crate) version_id: String,
+    /// The size of the object.
+    pub(crate) size: Option<i64>,
+    /// The ETag of the object.
+    pub(crate) e_tag: Option<String>,
+}
+
+impl PrefixObject {
+    /// Get the key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+/// The immediate child prefixes and objects under a prefix.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefixListing {
+    /// Links to the next and previous page.
+    #[serde(default)]
+    pub(crate) links: Links,
+    /// The pagination response component.
+    #[serde(default)]
+    pub(crate) pagination: PaginatedResponse,
+    /// The child prefixes, including the trailing `/`.
+    pub(crate) prefixes: Vec<String>,
+    /// The objects directly under the prefix.
+    pub(crate) objects: Vec<PrefixObject>,
+}
+
+impl PrefixListing {
+    /// Get a page of the listing, skipping `offset` children and keeping at most `limit`
+    /// children. Child prefixes are ordered before objects. This also returns the total number
+    /// of children in the listing.
+    pub fn page(self, offset: u64, limit: u64) -> Result<(Self, u64)> {
+        let to_usize =
+            |value: u64| usize::try_from(value).map_err(|err| ConversionError(err.to_string()));
+        let (offset, limit) = (to_usize(offset)?, to_usize(limit)?);
+
+        let n_children = self.prefixes.len() + self.objects.len();
+        let n_prefixes = self.prefixes.len();
+        let prefixes = self
+            .prefixes
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect::<Vec<_>>();
+        let objects = self
+            .objects
+            .into_iter()
+            .skip(offset.saturating_sub(n_prefixes))
+            .take(limit - prefixes.len())
+            .collect();
+
+        Ok((
+            Self {
+                prefixes,
+                objects,
+                ..Default::default()
+            },
+            n_children as u64,
+        ))
+    }
+
+    /// Set the links and pagination of the listing for the page.
+    pub fn with_page(
+        mut self,
+        pagination: Pagination,
+        page_link: Url,
+        n_children: u64,
+    ) -> Result<Self> {
+        let n_page = (self.prefixes.len() + self.objects.len()) as u64;
+        let n_seen = pagination
+            .offset()?
+            .checked_mul(pagination.rows_per_page())
+            .and_then(|offset| offset.checked_add(n_page))
+            .ok_or_else(|| OverflowError)?;
+        let next_page = if n_seen < n_children {
+            Some(
+                pagination
+                    .page()
+                    .checked_add(1)
+                    .ok_or_else(|| OverflowError)?,
+            )
+        } else {
+            None
+        };
+
+        let response = ListResponse::<()>::from_next_page(
+            pagination,
+            vec![],
+            next_page,
+            page_link,
+            n_children,
+        )?;
+        self.links = response.links;
+        self.pagination = response.pagination;
+
+        Ok(self)
+    }
+
+    /// Get the child prefixes.
+    pub fn prefixes(&self) -> &[String] {
+        &self.prefixes
+    }
+
+    /// Get the objects.
+    pub fn objects(&self) -> &[PrefixObject] {
+        &self.objects
+    }
+}
+
+/// Params for browsing records under a prefix.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct BrowseParams {
+    /// The bucket to browse.
+    #[param(nullable = false, required = true)]
+    bucket: String,
+    /// The prefix to browse the children of. This should usually end with a `/`. By default, the
+    /// children at the root of the bucket are browsed.
+    #[serde(default)]
+    #[param(nullable = false, required = false)]
+    prefix: Option<String>,
+}
+
+impl BrowseParams {
+    /// Create new browse params.
+    pub fn new(bucket: String, prefix: Option<String>) -> Self {
+        Self { bucket, prefix }
+    }
+
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Get the prefix.
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+}
+
+/// The records grouped under a child prefix or object.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowseChild {
+    /// The child prefix including the trailing `/`, or the key of the object.
+    pub(crate) name: String,
+    /// The number of distinct keys.
+    pub(crate) n_keys: u64,
+    /// The number of records, including records which are not current.
+    pub(crate) n_records: u64,
+    /// The number of current records.
+    pub(crate) n_current: u64,
+    /// The number of records which are delete markers.
+    pub(crate) n_delete_markers: u64,
+    /// The total size of the current records in bytes.
+    pub(crate) current_size: i64,
+}
+
+impl BrowseChild {
+    /// Create a new browse child.
+    pub fn new(
+        name: String,
+        n_keys: u64,
+        n_records: u64,
+        n_current: u64,
+        n_delete_markers: u64,
+        current_size: i64,
+    ) -> Self {
+        Self {
+            name,
+            n_keys,
+            n_records,
+            n_current,
+            n_delete_markers,
+            current_size,
+        }
+    }
+
+    /// Get the name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The records under a prefix, grouped by child prefix and object.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowseListing {
+    /// The child prefixes.
+    pub(crate) prefixes: Vec<BrowseChild>,
+    /// The objects directly under the prefix.
+    pub(crate) objects: Vec<BrowseChild>,
+}
+
+impl BrowseListing {
+    /// Get the child prefixes.
+    pub fn prefixes(&self) -> &[BrowseChild] {
+        &self.prefixes
+    }
+
+    /// Get the objects.
+    pub fn objects(&self) -> &[BrowseChild] {
+        &self.objects
+    }
+}
+
+/// List the child prefixes of a prefix directly from S3 using a `/` delimiter.
+pub async fn list_s3_prefix_children(
+    state: &AppState,
+    bucket: &str,
+    prefix: &str,
+) -> Result<PrefixListing> {
+    TenantScope::check_bucket(bucket)?;
+
+    let output = state
+        .s3_client()
+        .list_objects(
+            bucket,
+            Some(prefix.to_string()),
+            Some(PREFIX_DELIMITER.to_string()),
+        )
+        .await?;
+
+    Ok(s3_prefix_children(state, output))
+}
+
+/// Convert a `ListObjectVersions` output using a `/` delimiter into the child prefixes and the
+/// latest version of each child object.
+pub fn s3_prefix_children(state: &AppState, output: ListObjectVersionsOutput) -> PrefixListing {
+    let prefixes = output
+        .common_prefixes
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|prefix| prefix.prefix)
+        .collect();
+    let objects = output
+        .versions
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|object| object.is_latest.is_some_and(|latest| latest))
+        .map(|object| {
+            let object = FlatS3EventMessage::from_object_version(
+                object,
+                state.s3_client().default_version_id(),
+                Utc::now(),
+            );
+
+            PrefixObject {
+                key: object.key,
+                version_id: object.version_id,
+                size: object.size,
+                e_tag: object.e_tag,
+            }
+        })
+        .collect();
+
+    PrefixListing {
+        prefixes,
+        objects,
+        ..Default::default()
+    }
+}
+
+/// List the immediate child prefixes and objects under a prefix, splitting keys on `/`. This
+/// is useful for browsing a bucket like a file system. By default, the current state of records
+/// in the database is used, excluding keys where the current record is a delete marker. Set
+/// `source=s3` to list directly from S3 instead. Children are paginated with child prefixes
+/// ordered before objects.
+/// See [`KeyPathMode`](crate::routes::key_path::KeyPathMode) for how prefixes are checked.
+#[utoipa::path(
+    get,
+    path = "/s3/prefixes",
+    responses(
+        (status = OK, description = "The child prefixes and objects", body = PrefixListing),
+        ErrorStatusCode,
+    ),
+    params(Pagination, PrefixParams),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn list_s3_prefixes(
+    state: State<AppState>,
+    WithRejection(extract::Query(pagination), _): Query<Pagination>,
+    WithRejection(extract::Query(params), _): Query<PrefixParams>,
+    request: Request,
+) -> Result<Json<PrefixListing>> {
+    let prefix = state
+        .config()
+        .api_key_path_mode()
+        .check(params.prefix.as_deref().unwrap_or_default())?;
+
+    let limit = pagination.rows_per_page();
+    let offset = pagination
+        .offset()?
+        .checked_mul(limit)
+        .ok_or_else(|| OverflowError)?;
+    let (listing, n_children) = match params.source {
+        PrefixSource::Database => {
+            PrefixQueryBuilder::new(state.database_client().read_connection_ref())
+                .list_children_page(&params.bucket, &prefix, offset, Some(limit))
+                .await?
+        }
+        PrefixSource::S3 => list_s3_prefix_children(&state, &params.bucket, &prefix)
+            .await?
+            .page(offset, limit)?,
+    };
+
+    let url = if let Some(url) = state.config().api_links_url() {
+        url
+    } else {
+        &HeaderParser::parse_host_url(&request, state.use_tls_links())?
+    };
+    let url = url.join(&HeaderParser::get_uri_path(&request))?;
+
+    Ok(Json(listing.with_page(pagination, url, n_children)?))
+}
+
+/// Browse the records under a prefix, grouped by the next path segment after splitting keys on
+/// `/`. This returns the number of keys, records and current records, and the size of current
+/// records for each child prefix and object. Unlike listing prefixes, this includes records which
+/// are not current, such as deleted objects. This only uses records in the database.
+/// See [`KeyPathMode`](crate::routes::key_path::KeyPathMode) for how prefixes are checked.
+#[utoipa::path(
+    get,
+    path = "/s3/browse",
+    responses(
+        (status = OK, description = "The records grouped by child prefix and object", body = BrowseListing),
+        ErrorStatusCode,
+    ),
+    params(BrowseParams),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn browse_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(params), _): Query<BrowseParams>,
+) -> Result<Json<BrowseListing>> {
+    let prefix = state
+        .config()
+        .api_key_path_mode()
+        .check(params.prefix.as_deref().unwrap_or_default())?;
+
+    Ok(Json(
+        PrefixQueryBuilder::new(state.database_client().read_connection_ref())
+            .browse(&params.bucket, &prefix)
+            .await?,
+    ))
+}
+
+/// The router for listing prefixes.
+pub fn prefix_router() -> Router<AppState> {
+    Router::new()
+        .route("/s3/prefixes", get(list_s3_prefixes))
+        .route("/s3/browse", get(browse_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use aws_sdk_s3::types::{CommonPrefix, ObjectVersion};
+    use aws_smithy_mocks::mock;
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::s3_object;
+    use crate::env::Config;
+    use crate::events::aws::collecter::tests::mock_s3;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::key_path::KeyPathMode;
+    use crate::routes::list::tests::{response_from, response_from_get};
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_prefixes_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_bucket_divisor(10)
+            .with_generate_crawl_entries(false)
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Even entries are current, and odd entries are deleted.
+        for (i, key) in [
+            (0, "a/b/c/1"),
+            (2, "a/b/2"),
+            (4, "a/d/3"),
+            (6, "a/4"),
+            (8, "e/5"),
+            (1, "a/f/6"),
+        ] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.key = Set(key.to_string());
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let result: PrefixListing =
+            response_from_get(state.clone(), "/s3/prefixes?bucket=0&prefix=a/").await;
+        assert_eq!(result.prefixes(), ["a/b/".to_string(), "a/d/".to_string()]);
+        assert_eq!(
+            result.objects().iter().map(|o| o.key()).collect::<Vec<_>>(),
+            ["a/4"]
+        );
+
+        let result: PrefixListing =
+            response_from_get(state.clone(), "/s3/prefixes?bucket=0&prefix=a/b/").await;
+        assert_eq!(result.prefixes(), ["a/b/c/".to_string()]);
+        assert_eq!(
+            result.objects().iter().map(|o| o.key()).collect::<Vec<_>>(),
+            ["a/b/2"]
+        );
+
+        // The root of the bucket.
+        let result: PrefixListing = response_from_get(state.clone(), "/s3/prefixes?bucket=0").await;
+        assert_eq!(result.prefixes(), ["a/".to_string(), "e/".to_string()]);
+        assert!(result.objects().is_empty());
+
+        // Children are paginated with prefixes before objects.
+        let result: PrefixListing = response_from_get(
+            state.clone(),
+            "/s3/prefixes?bucket=0&prefix=a/&rowsPerPage=2",
+        )
+        .await;
+        assert_eq!(result.prefixes(), ["a/b/".to_string(), "a/d/".to_string()]);
+        assert!(result.objects().is_empty());
+        assert_eq!(result.pagination.count, 3);
+        assert_ne!(result.links, Links::new(None, None));
+
+        let result: PrefixListing = response_from_get(
+            state.clone(),
+            "/s3/prefixes?bucket=0&prefix=a/&rowsPerPage=2&page=2",
+        )
+        .await;
+        assert!(result.prefixes().is_empty());
+        assert_eq!(
+            result.objects().iter().map(|o| o.key()).collect::<Vec<_>>(),
+            ["a/4"]
+        );
+        assert_eq!(result.pagination.count, 3);
+
+        // Keys where the current record is a delete marker are excluded.
+        let mut model: s3_object::ActiveModel = entries.s3_objects[6].clone().into_active_model();
+        model.is_delete_marker = Set(true);
+        model
+            .update(state.database_client().connection_ref())
+            .await
+            .unwrap();
+
+        let result: PrefixListing =
+            response_from_get(state, "/s3/prefixes?bucket=0&prefix=a/").await;
+        assert_eq!(result.prefixes(), ["a/b/".to_string(), "a/d/".to_string()]);
+        assert!(result.objects().is_empty());
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn browse_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_bucket_divisor(10)
+            .with_generate_crawl_entries(false)
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Even entries are current, and odd entries are deleted.
+        for (i, key, size) in [
+            (0, "a/b/1", 1),
+            (1, "a/b/1", 5),
+            (2, "a/b/2", 2),
+            (4, "a/c/3", 3),
+            (3, "a/d/4", 4),
+            (6, "a/5", 6),
+            (8, "e/6", 8),
+        ] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.key = Set(key.to_string());
+            model.size = Set(Some(size));
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        let result: BrowseListing =
+            response_from_get(state.clone(), "/s3/browse?bucket=0&prefix=a/").await;
+        assert_eq!(
+            result,
+            BrowseListing {
+                prefixes: vec![
+                    BrowseChild::new("a/b/".to_string(), 2, 3, 2, 0, 3),
+                    BrowseChild::new("a/c/".to_string(), 1, 1, 1, 0, 3),
+                    // Only deleted records are under this prefix.
+                    BrowseChild::new("a/d/".to_string(), 1, 1, 0, 0, 0),
+                ],
+                objects: vec![BrowseChild::new("a/5".to_string(), 1, 1, 1, 0, 6)],
+            }
+        );
+
+        let result: BrowseListing = response_from_get(state, "/s3/browse?bucket=0").await;
+        assert_eq!(
+            result
+                .prefixes()
+                .iter()
+                .map(|child| child.name())
+                .collect::<Vec<_>>(),
+            ["a/", "e/"]
+        );
+        assert_eq!(
+            result
+                .objects()
+                .iter()
+                .map(|child| child.name())
+                .collect::<Vec<_>>(),
+            ["5", "7", "9"]
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn browse_s3_api_key_path(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .with_bucket_divisor(10)
+            .with_generate_crawl_entries(false)
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        for (i, key) in [(0, "a/1"), (2, "a/b/2"), (4, "a/../3")] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.key = Set(key.to_string());
+            model
+                .update(state.database_client().connection_ref())
+                .await
+                .unwrap();
+        }
+
+        // Prefixes are used as they are by default.
+        let result: PrefixListing =
+            response_from_get(state.clone(), "/s3/prefixes?bucket=0&prefix=a/../").await;
+        assert_eq!(
+            result.objects().iter().map(|o| o.key()).collect::<Vec<_>>(),
+            ["a/../3"]
+        );
+
+        // Prefixes with path traversal sequences are rejected if configured.
+        let state = state.with_config(Config {
+            api_key_path_mode: KeyPathMode::Reject,
+            ..Default::default()
+        });
+        for prefix in ["a/../", "a/b/..", "a%252Fb/"] {
+            let (status, _): (_, Value) = response_from(
+                state.clone(),
+                &format!("/s3/browse?bucket=0&prefix={prefix}"),
+                Method::GET,
+                Body::empty(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let (status, _): (_, Value) = response_from(
+                state.clone(),
+                &format!("/s3/prefixes?bucket=0&prefix={prefix}"),
+                Method::GET,
+                Body::empty(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        let state = state.with_config(Config {
+            api_key_path_mode: KeyPathMode::Canonicalize,
+            ..Default::default()
+        });
+
+        // The prefix resolves to the parent prefix.
+        let result: BrowseListing =
+            response_from_get(state.clone(), "/s3/browse?bucket=0&prefix=a/b/../").await;
+        assert_eq!(
+            result
+                .prefixes()
+                .iter()
+                .map(|child| child.name())
+                .collect::<Vec<_>>(),
+            ["a/../", "a/b/"]
+        );
+        assert_eq!(
+            result
+                .objects()
+                .iter()
+                .map(|child| child.name())
+                .collect::<Vec<_>>(),
+            ["a/1"]
+        );
+
+        // Encoded slashes are decoded.
+        let result: PrefixListing =
+            response_from_get(state.clone(), "/s3/prefixes?bucket=0&prefix=a%252Fb/").await;
+        assert!(result.prefixes().is_empty());
+        assert_eq!(
+            result.objects().iter().map(|o| o.key()).collect::<Vec<_>>(),
+            ["a/b/2"]
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_prefixes_api_s3(pool: PgPool) {
+        let client = mock_s3(&[mock!(aws_sdk_s3::Client::list_object_versions)
+            .match_requests(|req| {
+                req.bucket() == Some("bucket")
+                    && req.prefix() == Some("a/")
+                    && req.delimiter() == Some("/")
+            })
+            .then_output(|| {
+                ListObjectVersionsOutput::builder()
+                    .common_prefixes(CommonPrefix::builder().prefix("a/b/").build())
+                    .common_prefixes(CommonPrefix::builder().prefix("a/d/").build())
+                    .versions(
+                        ObjectVersion::builder()
+                            .key("a/4")
+                            .version_id("version_id")
+                            .size(4)
+                            .is_latest(true)
+                            .build(),
+                    )
+                    .versions(
+                        ObjectVersion::builder()
+                            .key("a/4")
+                            .version_id("previous_version_id")
+                            .is_latest(false)
+                            .build(),
+                    )
+                    .build()
+            })]);
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(client);
+
+        let result: PrefixListing =
+            response_from_get(state, "/s3/prefixes?bucket=bucket&prefix=a/&source=s3").await;
+        assert_eq!(
+            result,
+            PrefixListing {
+                links: Links::new(None, None),
+                pagination: PaginatedResponse::new(3, Pagination::default()),
+                prefixes: vec!["a/b/".to_string(), "a/d/".to_string()],
+                objects: vec![PrefixObject {
+                    key: "a/4".to_string(),
+                    version_id: "version_id".to_string(),
+                    size: Some(4),
+                    e_tag: None,
+                }],
+            }
+        );
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn list_s3_prefixes_api_s3_tenant(pool: PgPool) {
+        let rule = mock!(aws_sdk_s3::Client::list_object_versions)
+            .then_output(|| ListObjectVersionsOutput::builder().build());
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(mock_s3(slice::from_ref(&rule)));
+
+        // Buckets outside the tenant are not listed from S3.
+        let (status, _): (_, Value) =
+            TenantScope::new("tenant".to_string(), vec!["bucket".to_string()])
+                .scope(response_from(
+                    state,
+                    "/s3/prefixes?bucket=other&prefix=a/&source=s3",
+                    Method::GET,
+                    Body::empty(),
+                ))
+                .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(rule.num_calls(), 0);
+    }
+}
@@ -0,0 +1,308 @@
+//! Route logic for checking whether objects can be presigned before distributing URLs.
+//!
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database::entities::s3_object;
+use crate::database::entities::sea_orm_active_enums::StorageClass;
+use crate::error::Result;
+use crate::events::aws::collecter::MAX_COLLECT_CONCURRENCY;
+use crate::queries::list::ListQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::list::WildcardParams;
+use crate::routes::pagination::LimitParams;
+
+/// The maximum number of records that are checked per call.
+pub const MAX_PREFLIGHT_LIMIT: u64 = 1000;
+
+/// A reason that an object would not produce a usable presigned URL.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+pub enum PreflightFailure {
+    /// The object is in archive storage and has not been restored.
+    Archived,
+    /// The object is not accessible for a reason other than archive storage.
+    NotAccessible,
+    /// The object is larger than the configured presign limit.
+    OverSizeLimit,
+    /// The key contains a path traversal sequence that is rejected by the API.
+    KeyRejected,
+    /// The bucket is in a different region to the one URLs are presigned for.
+    WrongRegion,
+    /// The region of the bucket could not be determined.
+    UnknownRegion,
+}
+
+/// The preflight result for a single record.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignPreflight {
+    /// The id of the record.
+    pub(crate) s3_object_id: Uuid,
+    /// The bucket of the object.
+    pub(crate) bucket: String,
+    /// The key of the object.
+    pub(crate) key: String,
+    /// The version id of the object.
+    pub(crate) version_id: String,
+    /// Whether the object passed all checks.
+    pub(crate) passed: bool,
+    /// The checks that the object failed.
+    pub(crate) failures: Vec<PreflightFailure>,
+}
+
+impl PresignPreflight {
+    /// Create a preflight result from a record and the checks it failed.
+    pub fn new(record: s3_object::Model, failures: Vec<PreflightFailure>) -> Self {
+        Self {
+            s3_object_id: record.s3_object_id,
+            bucket: record.bucket,
+            key: record.key,
+            version_id: record.version_id,
+            passed: failures.is_empty(),
+            failures,
+        }
+    }
+
+    /// Get the s3_object_id.
+    pub fn s3_object_id(&self) -> Uuid {
+        self.s3_object_id
+    }
+
+    /// Whether the object passed all checks.
+    pub fn passed(&self) -> bool {
+        self.passed
+    }
+
+    /// Get the checks that the object failed.
+    pub fn failures(&self) -> &[PreflightFailure] {
+        &self.failures
+    }
+}
+
+/// The pass/fail report of a presign preflight.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignPreflightReport {
+    /// The number of records that passed.
+    pub(crate) n_passed: u64,
+    /// The number of records that failed.
+    pub(crate) n_failed: u64,
+    /// The result for each checked record.
+    pub(crate) results: Vec<PresignPreflight>,
+}
+
+impl PresignPreflightReport {
+    /// Create a report from the results of each record.
+    pub fn new(results: Vec<PresignPreflight>) -> Self {
+        let n_passed = results.iter().filter(|result| result.passed).count() as u64;
+
+        Self {
+            n_passed,
+            n_failed: results.len() as u64 - n_passed,
+            results,
+        }
+    }
+
+    /// Get the number of records that passed.
+    pub fn n_passed(&self) -> u64 {
+        self.n_passed
+    }
+
+    /// Get the number of records that failed.
+    pub fn n_failed(&self) -> u64 {
+        self.n_failed
+    }
+
+    /// Get the result for each checked record.
+    pub fn results(&self) -> &[PresignPreflight] {
+        &self.results
+    }
+}
+
+/// Check a record against the same conditions that are used when presigning, without
+/// generating a URL.
+async fn preflight(state: &AppState, record: &s3_object::Model) -> Vec<PreflightFailure> {
+    let mut failures = vec![];
+
+    if !record.is_accessible {
+        let is_archived = matches!(
+            record.storage_class,
+            Some(StorageClass::Glacier) | Some(StorageClass::DeepArchive)
+        ) || record.archive_status.is_some();
+
+        if is_archived {
+            failures.push(PreflightFailure::Archived);
+        } else {
+            failures.push(PreflightFailure::NotAccessible);
+        }
+    }
+
+    if let (Some(size), Some(limit)) = (record.size, state.config().api_presign_limit())
+        && u64::try_from(size).unwrap_or_default() > limit
+    {
+        failures.push(PreflightFailure::OverSizeLimit);
+    }
+
+    if state
+        .config()
+        .api_key_path_mode()
+        .check(&record.key)
+        .is_err()
+    {
+        failures.push(PreflightFailure::KeyRejected);
+    }
+
+    // The region is only checked if the client region is known.
+    if let Some(client_region) = state.s3_client().region() {
+        match state.bucket_region(&record.bucket).await {
+            Ok(region) if region != client_region => failures.push(PreflightFailure::WrongRegion),
+            Ok(_) => {}
+            Err(_) => failures.push(PreflightFailure::UnknownRegion),
+        }
+    }
+
+    failures
+}
+
+/// Check whether current records matching the filter can be presigned, without generating
+/// any URLs. Each object is checked to be accessible and not in archive storage, within the
+/// presign size limit, to have a key that is not rejected, and to be in the same region as
+/// the one URLs are presigned for. Returns a pass/fail report for each record. At most `limit`
+/// records are checked per call.
+#[utoipa::path(
+    get,
+    path = "/s3/presign/preflight",
+    responses(
+        (
+            status = OK,
+            description = "The pass/fail report for each record",
+            body = PresignPreflightReport
+        ),
+        ErrorStatusCode,
+    ),
+    params(WildcardParams, LimitParams<MAX_PREFLIGHT_LIMIT>, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn presign_preflight_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(params), _): Query<LimitParams<MAX_PREFLIGHT_LIMIT>>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<PresignPreflightReport>> {
+    let records = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), true)?
+    .paginate(0, params.limit())
+    .await?
+    .all()
+    .await?;
+
+    let results = stream::iter(records)
+        .map(|record| async {
+            let failures = preflight(&state, &record).await;
+            PresignPreflight::new(record, failures)
+        })
+        .buffered(MAX_COLLECT_CONCURRENCY)
+        .collect()
+        .await;
+
+    Ok(Json(PresignPreflightReport::new(results)))
+}
+
+/// The router for presign preflight checks.
+pub fn preflight_router() -> Router<AppState> {
+    Router::new().route("/s3/presign/preflight", get(presign_preflight_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_s3::operation::get_bucket_location::GetBucketLocationOutput;
+    use aws_sdk_s3::types::BucketLocationConstraint;
+    use aws_smithy_mocks::mock;
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::events::aws::collecter::tests::mock_s3;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::list::tests::response_from;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn presign_preflight_s3_api(pool: PgPool) {
+        let client = mock_s3(&[
+            mock!(aws_sdk_s3::Client::get_bucket_location)
+                .match_requests(|req| req.bucket() == Some("1"))
+                .then_output(|| {
+                    GetBucketLocationOutput::builder()
+                        .location_constraint(BucketLocationConstraint::ApSoutheast2)
+                        .build()
+                }),
+            mock!(aws_sdk_s3::Client::get_bucket_location)
+                .match_requests(|req| req.bucket() != Some("1"))
+                .then_output(|| GetBucketLocationOutput::builder().build()),
+        ]);
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(client);
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Record 0 is in `DeepArchive` storage, and record 2 is in a bucket in another region.
+        assert_eq!(entries[0].storage_class, Some(StorageClass::DeepArchive));
+
+        let (status, result) = response_from::<PresignPreflightReport>(
+            state.clone(),
+            "/s3/presign/preflight",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.n_passed(), 3);
+        assert_eq!(result.n_failed(), 2);
+        assert_eq!(
+            result
+                .results()
+                .iter()
+                .filter(|result| !result.passed())
+                .map(|result| (result.s3_object_id(), result.failures().to_vec()))
+                .collect::<Vec<_>>(),
+            vec![
+                (entries[0].s3_object_id, vec![PreflightFailure::Archived]),
+                (entries[2].s3_object_id, vec![PreflightFailure::WrongRegion]),
+            ]
+        );
+
+        // Filters and the limit restrict the checked records.
+        let (_, result) = response_from::<PresignPreflightReport>(
+            state,
+            "/s3/presign/preflight?key=0&limit=1",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(result.results().len(), 1);
+        assert!(!result.results()[0].passed());
+        assert_eq!(
+            result.results()[0].failures(),
+            &[PreflightFailure::Archived]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Route logic for comparing a list of keys with the stored records.
+//!
+
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::database::entities::s3_object;
+use crate::error::Error::InvalidQuery;
+use crate::error::Result;
+use crate::queries::list::ListQueryBuilder;
+use crate::queries::timing::log_slow_query;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::list::{ListS3Params, WildcardParams};
+
+/// The maximum number of keys that can be compared in a single request.
+pub const MAX_PRESENCE_KEYS: usize = 10000;
+
+/// The maximum number of extra keys that are returned per call.
+pub const MAX_EXTRA_KEYS_LIMIT: u64 = 1000;
+
+/// Params for paginating the extra keys when comparing a list of keys with the stored records.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct KeyPresenceParams {
+    /// Only return extra keys which sort after this key. Use the `nextExtraAfter` of the
+    /// previous response to fetch the next page of extra keys.
+    #[param(nullable = false, required = false)]
+    extra_after: Option<String>,
+    /// The maximum number of extra keys to return, between 1 and 1000 keys per call.
+    #[param(
+        nullable = false,
+        required = false,
+        default = 1000,
+        minimum = 1,
+        maximum = 1000
+    )]
+    extra_limit: u64,
+}
+
+impl Default for KeyPresenceParams {
+    fn default() -> Self {
+        Self {
+            extra_after: None,
+            extra_limit: MAX_EXTRA_KEYS_LIMIT,
+        }
+    }
+}
+
+impl KeyPresenceParams {
+    /// Create new key presence params.
+    pub fn new(extra_after: Option<String>, extra_limit: u64) -> Self {
+        Self {
+            extra_after,
+            extra_limit,
+        }
+    }
+
+    /// Get the key that extra keys sort after.
+    pub fn extra_after(&self) -> Option<&str> {
+        self.extra_after.as_deref()
+    }
+
+    /// Get the limit of extra keys, capped at the maximum.
+    pub fn extra_limit(&self) -> u64 {
+        self.extra_limit.clamp(1, MAX_EXTRA_KEYS_LIMIT)
+    }
+}
+
+/// The keys which are present, missing or extra when comparing a list of keys with the records
+/// that match a filter.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyPresence {
+    /// Keys in the list which have a matching record.
+    pub(crate) present: Vec<String>,
+    /// Keys in the list which do not have a matching record.
+    pub(crate) missing: Vec<String>,
+    /// Keys of matching records which are not in the list, in order. At most `extraLimit` keys
+    /// are returned.
+    pub(crate) extra: Vec<String>,
+    /// The key to pass as `extraAfter` to fetch the next page of extra keys, if there are more.
+    pub(crate) next_extra_after: Option<String>,
+}
+
+impl KeyPresence {
+    /// Get the keys which have a matching record.
+    pub fn present(&self) -> &[String] {
+        &self.present
+    }
+
+    /// Get the keys which do not have a matching record.
+    pub fn missing(&self) -> &[String] {
+        &self.missing
+    }
+
+    /// Get the keys of matching records which are not in the list.
+    pub fn extra(&self) -> &[String] {
+        &self.extra
+    }
+
+    /// Get the key to fetch the next page of extra keys after.
+    pub fn next_extra_after(&self) -> Option<&str> {
+        self.next_extra_after.as_deref()
+    }
+}
+
+/// Compare a list of keys with the keys of the records that match the filter. This returns the
+/// keys which are present in both, the keys which are missing from the matching records, and the
+/// extra keys of matching records which are not in the list. The comparison is performed in the
+/// database, and duplicate keys are only returned once. Use the filter to restrict the records,
+/// e.g. by bucket or key prefix, otherwise every record is an extra key unless it is in the list.
+/// At most 10000 keys can be compared per request, and the extra keys are paginated using
+/// `extraAfter` and `extraLimit`.
+#[utoipa::path(
+    post,
+    path = "/s3/keys/presence",
+    responses(
+        (status = OK, description = "The presence of the keys", body = KeyPresence),
+        ErrorStatusCode,
+    ),
+    params(KeyPresenceParams, WildcardParams, ListS3Params, S3ObjectsFilter),
+    request_body = Vec<String>,
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn key_presence_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(params), _): Query<KeyPresenceParams>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(list), _): Query<ListS3Params>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+    WithRejection(extract::Json(keys), _): WithRejection<
+        extract::Json<Vec<String>>,
+        ErrorStatusCode,
+    >,
+) -> Result<Json<KeyPresence>> {
+    if keys.len() > MAX_PRESENCE_KEYS {
+        return Err(InvalidQuery(format!(
+            "at most {MAX_PRESENCE_KEYS} keys can be compared per request"
+        )));
+    }
+
+    let summary = filter_all.summary();
+    let response = ListQueryBuilder::<_, s3_object::Entity>::new(
+        state.database_client().read_connection_ref(),
+    )
+    .filter_all(filter_all, wildcard.case_sensitive(), list.current_state())?;
+
+    Ok(Json(
+        log_slow_query(
+            state.config().api_slow_query_threshold(),
+            summary,
+            response.key_presence(keys, params.extra_after(), params.extra_limit()),
+        )
+        .await?,
+    ))
+}
+
+/// The router for comparing keys with the stored records.
+pub fn presence_router() -> Router<AppState> {
+    Router::new().route("/s3/keys/presence", post(key_presence_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::list::tests::response_from;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn key_presence_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Bucket 1 contains keys 2 and 3.
+        let body = json!(["2", "2", "4", "missing"]);
+        let (status, presence) = response_from::<KeyPresence>(
+            state.clone(),
+            "/s3/keys/presence?bucket=1&currentState=false",
+            Method::POST,
+            Body::new(body.to_string()),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(presence.present(), ["2"]);
+        assert_eq!(presence.missing(), ["4", "missing"]);
+        assert_eq!(presence.extra(), ["3"]);
+
+        // An empty list has no present or missing keys.
+        let (_, presence) = response_from::<KeyPresence>(
+            state.clone(),
+            "/s3/keys/presence?bucket=1&currentState=false",
+            Method::POST,
+            Body::new(json!([]).to_string()),
+        )
+        .await;
+
+        assert!(presence.present().is_empty());
+        assert!(presence.missing().is_empty());
+        assert_eq!(presence.extra(), ["2", "3"]);
+        assert_eq!(presence.next_extra_after(), None);
+
+        // The extra keys are paginated.
+        let (_, presence) = response_from::<KeyPresence>(
+            state.clone(),
+            "/s3/keys/presence?bucket=1&currentState=false&extraLimit=1",
+            Method::POST,
+            Body::new(json!(["missing"]).to_string()),
+        )
+        .await;
+
+        assert_eq!(presence.missing(), ["missing"]);
+        assert_eq!(presence.extra(), ["2"]);
+        assert_eq!(presence.next_extra_after(), Some("2"));
+
+        let (_, presence) = response_from::<KeyPresence>(
+            state.clone(),
+            "/s3/keys/presence?bucket=1&currentState=false&extraLimit=1&extraAfter=2",
+            Method::POST,
+            Body::new(json!(["missing"]).to_string()),
+        )
+        .await;
+
+        assert_eq!(presence.missing(), ["missing"]);
+        assert_eq!(presence.extra(), ["3"]);
+        assert_eq!(presence.next_extra_after(), None);
+
+        // Requests with too many keys are rejected.
+        let (status, _) = response_from::<Value>(
+            state,
+            "/s3/keys/presence?bucket=1",
+            Method::POST,
+            Body::new(json!(vec!["key"; MAX_PRESENCE_KEYS + 1]).to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -7,15 +7,20 @@ use reqwest::ClientBuilder;
 use serde::{Deserialize, Serialize};
 use url::Url;
 use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 
 use crate::clients::aws::s3::ResponseHeaders;
 use crate::clients::aws::secrets_manager::SecretsManagerCredentials;
 use crate::clients::aws::{config, s3};
 use crate::database::entities::s3_object;
-use crate::error::Error::PresignedUrlError;
+use crate::env::Config;
+use crate::error::Error::{InvalidQuery, PresignedUrlError};
 use crate::error::Result;
 use crate::routes::AppState;
 
+/// The maximum number of entries that can be presigned in a single request.
+pub const MAX_PRESIGN_ENTRIES: usize = 1000;
+
 /// Parameters for presigned URL routes.
 #[derive(Serialize, Deserialize, Debug, Default, IntoParams)]
 #[serde(default, rename_all = "camelCase")]
@@ -54,11 +59,124 @@ pub enum ContentDisposition {
     Attachment,
 }
 
+/// An entry for presigning a single object with individual settings.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignEntry {
+    /// The id of the record to presign.
+    s3_object_id: Uuid,
+    /// The expiry of the presigned URL in seconds. This must be greater than zero and at most
+    /// `FILEMANAGER_API_PRESIGN_EXPIRY`, which is used by default.
+    #[serde(default)]
+    expires_in: Option<u64>,
+    /// Override the content-disposition for this presigned URL.
+    #[serde(default)]
+    response_content_disposition: Option<ContentDisposition>,
+    /// Override the filename used in the content-disposition for this presigned URL. By default,
+    /// the key is used for `attachment`, and no filename is set for `inline`.
+    #[serde(default)]
+    filename: Option<String>,
+}
+
+impl PresignEntry {
+    /// Create a new presign entry.
+    pub fn new(
+        s3_object_id: Uuid,
+        expires_in: Option<u64>,
+        response_content_disposition: Option<ContentDisposition>,
+        filename: Option<String>,
+    ) -> Self {
+        Self {
+            s3_object_id,
+            expires_in,
+            response_content_disposition,
+            filename,
+        }
+    }
+
+    /// Get the id of the record.
+    pub fn s3_object_id(&self) -> Uuid {
+        self.s3_object_id
+    }
+
+    /// Get the content-disposition for this entry, using the default if it is not overridden.
+    pub fn response_content_disposition(&self, default: ContentDisposition) -> ContentDisposition {
+        self.response_content_disposition.unwrap_or(default)
+    }
+
+    /// Get the filename override.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// Validate the entry against the config, returning the expiry of the presigned URL.
+    pub fn validate(&self, config: &Config) -> Result<Duration> {
+        if let Some(filename) = &self.filename
+            && (filename.is_empty()
+                || filename.contains(['"', '\\'])
+                || filename.contains(char::is_control))
+        {
+            return Err(InvalidQuery(format!(
+                "invalid filename `{filename}` for `{}`",
+                self.s3_object_id
+            )));
+        }
+
+        let max = config.api_presign_expiry();
+        let Some(expires_in) = self.expires_in else {
+            return Ok(max);
+        };
+
+        i64::try_from(expires_in)
+            .ok()
+            .and_then(Duration::try_seconds)
+            .filter(|expires_in| *expires_in > Duration::zero() && *expires_in <= max)
+            .ok_or_else(|| {
+                InvalidQuery(format!(
+                    "`expiresIn` for `{}` must be between 1 and {} seconds",
+                    self.s3_object_id,
+                    max.num_seconds()
+                ))
+            })
+    }
+}
+
+/// The presigned URL for a presign entry.
+#[derive(Debug, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignEntryResult {
+    /// The id of the record.
+    s3_object_id: Uuid,
+    /// The presigned URL. This is not set if the record is not current, not accessible, or over
+    /// the presign limit.
+    #[schema(value_type = Option<String>)]
+    url: Option<Url>,
+}
+
+impl PresignEntryResult {
+    /// Create a new presign entry result.
+    pub fn new(s3_object_id: Uuid, url: Option<Url>) -> Self {
+        Self { s3_object_id, url }
+    }
+
+    /// Get the id of the record.
+    pub fn s3_object_id(&self) -> Uuid {
+        self.s3_object_id
+    }
+
+    /// Get the presigned URL.
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+}
+
 /// A builder for presigned urls.
 pub struct PresignedUrlBuilder<'a> {
     state: &'a AppState,
     http_client: reqwest::Client,
+    access_key_client: Option<(String, s3::Client)>,
     object_size: Option<i64>,
+    expires_in: Option<Duration>,
 }
 
 /// Config for response headers.
@@ -67,6 +185,7 @@ pub struct ResponseHeadersConfig {
     content_disposition: ContentDisposition,
     content_type: Option<String>,
     content_encoding: Option<String>,
+    filename: Option<String>,
 }
 
 impl ResponseHeadersConfig {
@@ -80,8 +199,15 @@ impl ResponseHeadersConfig {
             content_disposition,
             content_type,
             content_encoding,
+            filename: None,
         }
     }
+
+    /// Override the filename used in the content-disposition.
+    pub fn with_filename(mut self, filename: Option<String>) -> Self {
+        self.filename = filename;
+        self
+    }
 }
 
 impl<'a> PresignedUrlBuilder<'a> {
@@ -92,7 +218,9 @@ impl<'a> PresignedUrlBuilder<'a> {
             http_client: ClientBuilder::new()
                 .build()
                 .map_err(|err| PresignedUrlError(err.to_string()))?,
+            access_key_client: None,
             object_size: None,
+            expires_in: None,
         })
     }
 
@@ -102,8 +230,15 @@ impl<'a> PresignedUrlBuilder<'a> {
         self
     }
 
+    /// Construct with an expiry that overrides `FILEMANAGER_API_PRESIGN_EXPIRY`.
+    pub fn set_expires_in(mut self, expires_in: Option<Duration>) -> Self {
+        self.expires_in = expires_in;
+        self
+    }
+
     /// Create a presigned url using the key and bucket. This will not create a URL if the size
-    /// is over the limit, and will instead return `None`.
+    /// is over the limit or the key is rejected because it contains a path traversal sequence,
+    /// and will instead return `None`.
     pub async fn presign_url(
         &mut self,
         key: &str,
@@ -121,28 +256,40 @@ impl<'a> PresignedUrlBuilder<'a> {
             true
         };
 
+        // Keys with path traversal sequences are not presigned if they are rejected, otherwise
+        // the checked key is only used for the attachment filename and the object is still
+        // presigned using the original key.
+        let key_filename = match self.state.config().api_key_path_mode().check(key) {
+            Ok(filename) => filename,
+            Err(_) => return Ok(None),
+        };
+
         if less_than_limit {
-            let content_disposition = match response_headers.content_disposition {
-                ContentDisposition::Inline => "inline",
-                ContentDisposition::Attachment => &format!("attachment; filename=\"{key}\""),
+            let content_disposition = match (
+                response_headers.content_disposition,
+                response_headers.filename,
+            ) {
+                (ContentDisposition::Inline, None) => "inline".to_string(),
+                (ContentDisposition::Inline, Some(filename)) => {
+                    format!("inline; filename=\"{filename}\"")
+                }
+                (ContentDisposition::Attachment, filename) => format!(
+                    "attachment; filename=\"{}\"",
+                    filename.as_deref().unwrap_or(&key_filename)
+                ),
             };
             let headers = ResponseHeaders::new(
-                content_disposition.to_string(),
+                content_disposition,
                 response_headers.content_type,
                 response_headers.content_encoding,
             );
-            let expires_in = self.state.config().api_presign_expiry();
+            let expires_in = self
+                .expires_in
+                .unwrap_or_else(|| self.state.config().api_presign_expiry());
 
             // Grab the secret if it is configured.
             let client = if let Some(secret) = access_key_secret_id {
-                // Construct a new client to use only once-off for pre-signing.
-                let config = config::Config::from_provider(
-                    SecretsManagerCredentials::new(secret, self.state.secrets_manager_client())
-                        .await?,
-                )
-                .await
-                .load();
-                &s3::Client::new(aws_sdk_s3::Client::new(&config))
+                self.access_key_client(secret).await?
             } else {
                 self.state.s3_client()
             };
@@ -175,6 +322,28 @@ impl<'a> PresignedUrlBuilder<'a> {
         }
     }
 
+    /// Get the client which presigns using the access key of the secret. The client is created
+    /// once and reused for all URLs presigned by this builder with the same secret.
+    async fn access_key_client(&mut self, secret: &str) -> Result<&s3::Client> {
+        let client = match self.access_key_client.take() {
+            Some((existing, client)) if existing == secret => client,
+            _ => {
+                let config = config::Config::from_provider(
+                    SecretsManagerCredentials::new(secret, self.state.secrets_manager_client())
+                        .await?,
+                )
+                .await
+                .load();
+                s3::Client::new(aws_sdk_s3::Client::new(&config))
+            }
+        };
+
+        Ok(&self
+            .access_key_client
+            .insert((secret.to_string(), client))
+            .1)
+    }
+
     /// Test that the URL works.
     async fn test_url(&self, request: PresignedRequest) -> Option<PresignedRequest> {
         self.http_client
@@ -235,6 +404,7 @@ pub(crate) mod tests {
     use super::*;
     use crate::clients::aws::s3;
     use crate::env::Config;
+    use crate::routes::key_path::KeyPathMode;
     use crate::routes::list::tests::mock_get_object;
     use aws_smithy_mocks::{RuleMode, mock_client};
     use chrono::Duration;
@@ -391,6 +561,38 @@ pub(crate) mod tests {
         assert!(url.is_none());
     }
 
+    #[sqlx::test]
+    async fn presign_key_path(pool: PgPool) {
+        let client = s3::Client::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[&mock_get_object("0", "1", b""),]
+        ));
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(client)
+            .with_config(Config {
+                api_key_path_mode: KeyPathMode::Reject,
+                ..Default::default()
+            });
+
+        for key in ["a/../0", "a%2F0"] {
+            let mut builder = PresignedUrlBuilder::new(&state).unwrap();
+            let url = builder
+                .presign_url(
+                    key,
+                    "1",
+                    ResponseHeadersConfig::new(ContentDisposition::Attachment, None, None),
+                    None,
+                )
+                .await
+                .unwrap();
+
+            assert!(url.is_none());
+        }
+    }
+
     #[sqlx::test]
     async fn presign_expiry(pool: PgPool) {
         let client = s3::Client::new(mock_client!(
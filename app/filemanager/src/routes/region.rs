@@ -0,0 +1,282 @@
+//! Route logic and caching for bucket regions.
+//!
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aws_sdk_s3::types::BucketLocationConstraint;
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, OnceCell};
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+use crate::clients::aws::s3;
+use crate::error::{Error, Result};
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, Path};
+use crate::routes::tenant::TenantScope;
+
+/// The region of buckets which don't have a location constraint.
+pub const DEFAULT_BUCKET_REGION: &str = "us-east-1";
+
+/// How long a bucket region is cached for before it is fetched again.
+pub const BUCKET_REGION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The maximum number of bucket regions that are cached.
+pub const MAX_BUCKET_REGIONS: usize = 1000;
+
+/// A cached bucket region, which is fetched at most once.
+type CachedRegion = (Arc<OnceCell<String>>, Instant);
+
+/// A cache of bucket regions, which are fetched using `GetBucketLocation` once per bucket
+/// and reused afterwards. Regions expire after the `BUCKET_REGION_TTL`, and the least recently
+/// fetched region is evicted when the cache is full.
+#[derive(Debug, Clone)]
+pub struct BucketRegions {
+    regions: Arc<Mutex<HashMap<String, CachedRegion>>>,
+    max_regions: usize,
+}
+
+impl Default for BucketRegions {
+    fn default() -> Self {
+        Self::new(MAX_BUCKET_REGIONS)
+    }
+}
+
+impl BucketRegions {
+    /// Create a cache which holds at most `max_regions` bucket regions.
+    pub fn new(max_regions: usize) -> Self {
+        Self {
+            regions: Default::default(),
+            max_regions,
+        }
+    }
+
+    /// Get the region of the bucket, fetching it if it is not cached. Concurrent calls for the
+    /// same bucket wait for a single fetch. Regions that fail to be fetched are not cached.
+    pub async fn get_or_fetch(&self, client: &s3::Client, bucket: &str) -> Result<String> {
+        let cell = self.cell(bucket).await;
+
+        let region = cell
+            .get_or_try_init(|| async {
+                let location = client.get_bucket_location(bucket).await?;
+                Ok::<_, Error>(Self::region_from_constraint(location.location_constraint()))
+            })
+            .await
+            .cloned();
+
+        if region.is_err() {
+            let mut regions = self.regions.lock().await;
+            if regions
+                .get(bucket)
+                .is_some_and(|(cached, _)| Arc::ptr_eq(cached, &cell) && !cached.initialized())
+            {
+                regions.remove(bucket);
+            }
+        }
+
+        region
+    }
+
+    /// Get the cell of the bucket, removing expired regions and making space for the bucket if
+    /// it is not cached.
+    async fn cell(&self, bucket: &str) -> Arc<OnceCell<String>> {
+        let mut regions = self.regions.lock().await;
+        let now = Instant::now();
+        regions.retain(|_, (_, fetched)| now.duration_since(*fetched) < BUCKET_REGION_TTL);
+
+        if !regions.contains_key(bucket) && regions.len() >= self.max_regions {
+            let oldest = regions
+                .iter()
+                .min_by_key(|(_, (_, fetched))| *fetched)
+                .map(|(bucket, _)| bucket.to_string());
+            if let Some(oldest) = oldest {
+                regions.remove(&oldest);
+            }
+        }
+
+        regions
+            .entry(bucket.to_string())
+            .or_insert_with(|| (Default::default(), now))
+            .0
+            .clone()
+    }
+
+    /// Get the region of a bucket from its location constraint. An empty constraint means the
+    /// bucket is in `us-east-1`, and the legacy `EU` constraint means `eu-west-1`.
+    pub fn region_from_constraint(constraint: Option<&BucketLocationConstraint>) -> String {
+        match constraint.map(|constraint| constraint.as_str()) {
+            None | Some("") => DEFAULT_BUCKET_REGION.to_string(),
+            Some("EU") => "eu-west-1".to_string(),
+            Some(region) => region.to_string(),
+        }
+    }
+}
+
+/// The region of a bucket.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketRegion {
+    /// The bucket name.
+    pub(crate) bucket: String,
+    /// The region of the bucket.
+    pub(crate) region: String,
+}
+
+impl BucketRegion {
+    /// Create a new bucket region.
+    pub fn new(bucket: String, region: String) -> Self {
+        Self { bucket, region }
+    }
+
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Get the region.
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+}
+
+/// Get the region of a bucket. The region is fetched from S3 the first time that a bucket is
+/// requested, and cached afterwards.
+#[utoipa::path(
+    get,
+    path = "/s3/region/{bucket}",
+    responses(
+        (status = OK, description = "The region of the bucket", body = BucketRegion),
+        ErrorStatusCode,
+    ),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn get_bucket_region(
+    state: State<AppState>,
+    WithRejection(extract::Path(bucket), _): Path<String>,
+) -> Result<Json<BucketRegion>> {
+    TenantScope::check_bucket(&bucket)?;
+
+    let region = state.bucket_region(&bucket).await?;
+
+    Ok(Json(BucketRegion::new(bucket, region)))
+}
+
+/// The router for bucket regions.
+pub fn region_router() -> Router<AppState> {
+    Router::new().route("/s3/region/{bucket}", get(get_bucket_region))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use aws_sdk_s3::operation::get_bucket_location::GetBucketLocationOutput;
+    use aws_smithy_mocks::mock;
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::events::aws::collecter::tests::{expected_head_object_status, mock_s3};
+    use crate::routes::list::tests::response_from;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn get_bucket_region_api(pool: PgPool) {
+        let rule = mock!(aws_sdk_s3::Client::get_bucket_location)
+            .match_requests(|req| req.bucket() == Some("bucket"))
+            .then_output(|| {
+                GetBucketLocationOutput::builder()
+                    .location_constraint(BucketLocationConstraint::ApSoutheast2)
+                    .build()
+            });
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_s3_client(mock_s3(slice::from_ref(&rule)));
+
+        for _ in 0..2 {
+            let (status, result) = response_from::<BucketRegion>(
+                state.clone(),
+                "/s3/region/bucket",
+                Method::GET,
+                Body::empty(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                result,
+                BucketRegion::new("bucket".to_string(), "ap-southeast-2".to_string())
+            );
+        }
+
+        // The region is only fetched once, and reused for the second request.
+        assert_eq!(rule.num_calls(), 1);
+        assert_eq!(
+            state.bucket_region("bucket").await.unwrap(),
+            "ap-southeast-2"
+        );
+        assert_eq!(rule.num_calls(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_regions_eviction() {
+        let rule = mock!(aws_sdk_s3::Client::get_bucket_location)
+            .then_output(|| GetBucketLocationOutput::builder().build());
+        let client = mock_s3(slice::from_ref(&rule));
+        let regions = BucketRegions::new(1);
+
+        regions.get_or_fetch(&client, "a").await.unwrap();
+        regions.get_or_fetch(&client, "a").await.unwrap();
+        assert_eq!(rule.num_calls(), 1);
+
+        // The cache is full, so the region of `a` is evicted and fetched again.
+        regions.get_or_fetch(&client, "b").await.unwrap();
+        regions.get_or_fetch(&client, "a").await.unwrap();
+        assert_eq!(rule.num_calls(), 3);
+
+        // Regions are fetched again after they expire.
+        tokio::time::advance(BUCKET_REGION_TTL).await;
+        regions.get_or_fetch(&client, "a").await.unwrap();
+        assert_eq!(rule.num_calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn bucket_regions_error() {
+        let rule = mock!(aws_sdk_s3::Client::get_bucket_location)
+            .then_http_response(|| expected_head_object_status(404));
+        let client = mock_s3(slice::from_ref(&rule));
+        let regions = BucketRegions::default();
+
+        // Failed fetches are not cached.
+        assert!(regions.get_or_fetch(&client, "a").await.is_err());
+        assert!(regions.regions.lock().await.is_empty());
+    }
+
+    #[test]
+    fn region_from_constraint() {
+        assert_eq!(
+            BucketRegions::region_from_constraint(None),
+            DEFAULT_BUCKET_REGION
+        );
+        assert_eq!(
+            BucketRegions::region_from_constraint(Some(&BucketLocationConstraint::from(""))),
+            DEFAULT_BUCKET_REGION
+        );
+        assert_eq!(
+            BucketRegions::region_from_constraint(Some(&BucketLocationConstraint::Eu)),
+            "eu-west-1"
+        );
+        assert_eq!(
+            BucketRegions::region_from_constraint(Some(&BucketLocationConstraint::UsWest2)),
+            "us-west-2"
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Route logic for detecting sequencer anomalies of keys.
+//!
+
+use std::cmp::Ordering;
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use itertools::Itertools;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::database::entities::s3_object;
+use crate::error::Error::InvalidQuery;
+use crate::error::Result;
+use crate::events::aws::message::{compare_sequencers, sequencer_gap};
+use crate::queries::list::ListQueryBuilder;
+use crate::queries::timing::log_slow_query;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::list::WildcardParams;
+use crate::routes::pagination::LimitParams;
+
+/// The maximum number of records that are inspected per call.
+pub const MAX_SEQUENCER_LIMIT: u64 = 10000;
+
+/// Params for detecting sequencer anomalies.
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct SequencerParams {
+    /// Report consecutive sequencers of a key which differ by at least this amount, as a
+    /// hexadecimal number. By default, gaps are not reported.
+    #[param(nullable = false, required = false)]
+    min_gap: Option<String>,
+}
+
+impl SequencerParams {
+    /// Create new sequencer params.
+    pub fn new(min_gap: Option<String>) -> Self {
+        Self { min_gap }
+    }
+
+    /// Get the minimum gap, returning an error if it is not a hexadecimal number.
+    pub fn min_gap(&self) -> Result<Option<u128>> {
+        self.min_gap
+            .as_deref()
+            .map(|min_gap| {
+                u128::from_str_radix(min_gap, 16)
+                    .map_err(|err| InvalidQuery(format!("invalid `minGap`: {err}")))
+            })
+            .transpose()
+    }
+}
+
+/// A record with an AWS-native sequencer.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SequencedRecord {
+    bucket: String,
+    key: String,
+    s3_object_id: Uuid,
+    sequencer: String,
+    event_time: DateTimeWithTimeZone,
+}
+
+impl SequencedRecord {
+    /// Create a new sequenced record.
+    pub fn new(
+        bucket: String,
+        key: String,
+        s3_object_id: Uuid,
+        sequencer: String,
+        event_time: DateTimeWithTimeZone,
+    ) -> Self {
+        Self {
+            bucket,
+            key,
+            s3_object_id,
+            sequencer,
+            event_time,
+        }
+    }
+}
+
+/// The kind of sequencer anomaly.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+pub enum SequencerAnomalyKind {
+    /// The sequencer is smaller than the sequencer of an event which occurred earlier.
+    NonMonotonic,
+    /// The sequencer is larger than the previous sequencer by at least the minimum gap.
+    Gap,
+}
+
+/// An anomaly between two consecutive events of a key, ordered by event time.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SequencerAnomaly {
+    /// The kind of anomaly.
+    pub(crate) kind: SequencerAnomalyKind,
+    /// The id of the earlier record.
+    pub(crate) previous_s3_object_id: Uuid,
+    /// The sequencer of the earlier record.
+    pub(crate) previous_sequencer: String,
+    /// The id of the later record.
+    pub(crate) s3_object_id: Uuid,
+    /// The sequencer of the later record.
+    pub(crate) sequencer: String,
+    /// The difference between the sequencers as a hexadecimal number, if it could be computed.
+    pub(crate) gap: Option<String>,
+}
+
+impl SequencerAnomaly {
+    /// Get the kind of anomaly.
+    pub fn kind(&self) -> SequencerAnomalyKind {
+        self.kind
+    }
+
+    /// Get the id of the earlier record.
+    pub fn previous_s3_object_id(&self) -> Uuid {
+        self.previous_s3_object_id
+    }
+
+    /// Get the id of the later record.
+    pub fn s3_object_id(&self) -> Uuid {
+        self.s3_object_id
+    }
+
+    /// Get the difference between the sequencers.
+    pub fn gap(&self) -> Option<&str> {
+        self.gap.as_deref()
+    }
+}
+
+/// The sequencer anomalies of a key.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SequencerReport {
+    /// The bucket of the object.
+    pub(crate) bucket: String,
+    /// The key of the object.
+    pub(crate) key: String,
+    /// The anomalies of the key, ordered by event time.
+    pub(crate) anomalies: Vec<SequencerAnomaly>,
+}
+
+impl SequencerReport {
+    /// Find the sequencer anomalies of records which are ordered by bucket, key and event time.
+    /// Only keys with anomalies are reported. Events which occurred at the same time cannot be
+    /// ordered, so they are not reported as non-monotonic.
+    pub fn from_records(records: Vec<SequencedRecord>, min_gap: Option<u128>) -> Vec<Self> {
+        records
+            .into_iter()
+            .chunk_by(|record| (record.bucket.clone(), record.key.clone()))
+            .into_iter()
+            .filter_map(|((bucket, key), records)| {
+                let anomalies = records
+                    .tuple_windows()
+                    .filter_map(|(previous, record)| {
+                        let gap = sequencer_gap(&previous.sequencer, &record.sequencer);
+                        let kind = match compare_sequencers(&record.sequencer, &previous.sequencer)
+                        {
+                            Ordering::Less if record.event_time > previous.event_time => {
+                                SequencerAnomalyKind::NonMonotonic
+                            }
+                            Ordering::Greater
+                                if min_gap.is_some_and(|min_gap| {
+                                    gap.is_some_and(|gap| gap >= min_gap)
+                                }) =>
+                            {
+                                SequencerAnomalyKind::Gap
+                            }
+                            _ => return None,
+                        };
+
+                        Some(SequencerAnomaly {
+                            kind,
+                            previous_s3_object_id: previous.s3_object_id,
+                            previous_sequencer: previous.sequencer,
+                            s3_object_id: record.s3_object_id,
+                            sequencer: record.sequencer,
+                            gap: gap.map(|gap| format!("{gap:X}")),
+                        })
+                    })
+                    .collect_vec();
+
+                (!anomalies.is_empty()).then_some(Self {
+                    bucket,
+                    key,
+                    anomalies,
+                })
+            })
+            .collect()
+    }
+
+    /// Get the bucket.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Get the key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the anomalies.
+    pub fn anomalies(&self) -> &[SequencerAnomaly] {
+        &self.anomalies
+    }
+}
+
+/// Detect sequencer anomalies for each key. The AWS-native sequencers of a key should increase
+/// with the event time, so this compares consecutive events of each key ordered by event time,
+/// and reports keys where a sequencer is smaller than an earlier sequencer, or where the
+/// sequencer increases by at least `minGap`. These can indicate events that were missed or
+/// ingested out of order. Records without an event time, and generated sequencers are not
+/// inspected. Additional filters can be used to restrict the records, e.g. by bucket.
+#[utoipa::path(
+    get,
+    path = "/s3/sequencers/anomalies",
+    responses(
+        (status = OK, description = "The keys with sequencer anomalies", body = Vec<SequencerReport>),
+        ErrorStatusCode,
+    ),
+    params(LimitParams<MAX_SEQUENCER_LIMIT>, SequencerParams, WildcardParams, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn sequencer_anomalies_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(limit), _): Query<LimitParams<MAX_SEQUENCER_LIMIT>>,
+    WithRejection(extract::Query(params), _): Query<SequencerParams>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<Vec<SequencerReport>>> {
+    let min_gap = params.min_gap()?;
+    let summary = filter_all.summary();
+
+    let records = log_slow_query(
+        state.config().api_slow_query_threshold(),
+        summary,
+        ListQueryBuilder::<_, s3_object::Entity>::new(
+            state.database_client().read_connection_ref(),
+        )
+        .filter_all(filter_all, wildcard.case_sensitive(), false)?
+        .sequencers(limit.limit()),
+    )
+    .await?;
+
+    Ok(Json(SequencerReport::from_records(records, min_gap)))
+}
+
+/// The router for sequencer anomalies.
+pub fn sequencer_router() -> Router<AppState> {
+    Router::new().route("/s3/sequencers/anomalies", get(sequencer_anomalies_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::error::ErrorResponse;
+    use crate::routes::list::tests::{response_from, response_from_get};
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn sequencer_anomalies_api(pool: PgPool) {
+        let state = AppState::from_pool(pool.clone()).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // The second event of `key` has a smaller sequencer than the first, and the third event
+        // jumps ahead. The events of `other` are in order.
+        let sequencers = [
+            "0055AED6DCD90281E4",
+            "0055AED6DCD90281E3",
+            "0055AED6DCD90281F0",
+            "0055AED6DCD90281E4",
+            "0055AED6DCD90281E5",
+        ];
+        for (i, (key, minutes)) in [
+            ("key", 1),
+            ("key", 2),
+            ("key", 3),
+            ("other", 1),
+            ("other", 2),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            sqlx::query(
+                "update s3_object set bucket = 'bucket', key = $1, sequencer = $2, \
+                event_time = '2024-01-01T00:00:00Z'::timestamptz + make_interval(mins => $3), \
+                is_current_state = false where s3_object_id = $4",
+            )
+            .bind(key)
+            .bind(sequencers[i])
+            .bind(minutes)
+            .bind(entries[i].s3_object_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let anomaly = |kind, previous: usize, record: usize, gap: &str| SequencerAnomaly {
+            kind,
+            previous_s3_object_id: entries[previous].s3_object_id,
+            previous_sequencer: sequencers[previous].to_string(),
+            s3_object_id: entries[record].s3_object_id,
+            sequencer: sequencers[record].to_string(),
+            gap: Some(gap.to_string()),
+        };
+        let non_monotonic = anomaly(SequencerAnomalyKind::NonMonotonic, 0, 1, "1");
+
+        let result: Vec<SequencerReport> =
+            response_from_get(state.clone(), "/s3/sequencers/anomalies?bucket=bucket").await;
+        assert_eq!(
+            result,
+            vec![SequencerReport {
+                bucket: "bucket".to_string(),
+                key: "key".to_string(),
+                anomalies: vec![non_monotonic.clone()],
+            }]
+        );
+
+        let result: Vec<SequencerReport> = response_from_get(
+            state.clone(),
+            "/s3/sequencers/anomalies?bucket=bucket&minGap=a",
+        )
+        .await;
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].anomalies(),
+            [non_monotonic, anomaly(SequencerAnomalyKind::Gap, 1, 2, "D")]
+        );
+
+        let (status, _) = response_from::<ErrorResponse>(
+            state,
+            "/s3/sequencers/anomalies?minGap=invalid",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,275 @@
+//! Route logic for ingestion statistics.
+//!
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::database::entities::s3_object;
+use crate::error::Error::InvalidQuery;
+use crate::error::Result;
+use crate::queries::list::ListQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::list::WildcardParams;
+
+/// The default window that statistics are computed over.
+pub const DEFAULT_STATS_WINDOW: &str = "24h";
+
+/// Params for ingestion statistics.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct StatsParams {
+    /// The rolling window before now to compute statistics over, e.g. `1h` or `7days`.
+    #[param(nullable = false, required = false, default = "24h")]
+    window: String,
+}
+
+impl Default for StatsParams {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_STATS_WINDOW.to_string(),
+        }
+    }
+}
+
+impl StatsParams {
+    /// Create new stats params.
+    pub fn new(window: String) -> Self {
+        Self { window }
+    }
+
+    /// Get the window in seconds, returning an error if it is not a valid non-zero duration.
+    pub fn window(&self) -> Result<u64> {
+        let window = humantime::parse_duration(&self.window)
+            .map_err(|err| InvalidQuery(format!("invalid `window`: {err}")))?
+            .as_secs();
+
+        if window == 0 {
+            return Err(InvalidQuery(
+                "`window` must be at least one second".to_string(),
+            ));
+        }
+
+        Ok(window)
+    }
+}
+
+/// Ingestion statistics over a rolling window.
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestionStats {
+    /// The window that the statistics were computed over in seconds.
+    window_seconds: u64,
+    /// The number of records ingested within the window.
+    n_ingested: u64,
+    /// The average number of seconds between the `event_time` of a record and when it was
+    /// ingested. This is null if no records with an `event_time` were ingested.
+    average_lag_seconds: Option<f64>,
+    /// The number of records which were flagged with an ETag mismatch or a sequencer conflict.
+    n_errors: u64,
+    /// The proportion of ingested records which were flagged with an error.
+    error_rate: f64,
+    /// The number of `Created` records without an `ingest_id`.
+    n_null_ingest_id: u64,
+    /// The proportion of ingested `Created` records without an `ingest_id`.
+    null_ingest_id_rate: f64,
+}
+
+impl IngestionStats {
+    /// Create ingestion stats from the counts of records.
+    pub fn new(
+        window_seconds: u64,
+        n_ingested: u64,
+        average_lag_seconds: Option<f64>,
+        n_errors: u64,
+        n_created: u64,
+        n_null_ingest_id: u64,
+    ) -> Self {
+        let rate = |n: u64, total: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                n as f64 / total as f64
+            }
+        };
+
+        Self {
+            window_seconds,
+            n_ingested,
+            average_lag_seconds,
+            n_errors,
+            error_rate: rate(n_errors, n_ingested),
+            n_null_ingest_id,
+            null_ingest_id_rate: rate(n_null_ingest_id, n_created),
+        }
+    }
+
+    /// Get the window in seconds.
+    pub fn window_seconds(&self) -> u64 {
+        self.window_seconds
+    }
+
+    /// Get the number of records ingested within the window.
+    pub fn n_ingested(&self) -> u64 {
+        self.n_ingested
+    }
+
+    /// Get the average ingestion lag in seconds.
+    pub fn average_lag_seconds(&self) -> Option<f64> {
+        self.average_lag_seconds
+    }
+
+    /// Get the number of records flagged with an error.
+    pub fn n_errors(&self) -> u64 {
+        self.n_errors
+    }
+
+    /// Get the proportion of records flagged with an error.
+    pub fn error_rate(&self) -> f64 {
+        self.error_rate
+    }
+
+    /// Get the number of `Created` records without an `ingest_id`.
+    pub fn n_null_ingest_id(&self) -> u64 {
+        self.n_null_ingest_id
+    }
+
+    /// Get the proportion of `Created` records without an `ingest_id`.
+    pub fn null_ingest_id_rate(&self) -> f64 {
+        self.null_ingest_id_rate
+    }
+}
+
+/// Compute ingestion statistics for records which were ingested within a rolling window before
+/// now. This returns the number of ingested records, the average lag between the `event_time`
+/// and ingestion, the rate of records flagged with an ETag mismatch or sequencer conflict, and
+/// the rate of `Created` records without an `ingest_id`. Records ingested before ingestion times
+/// were recorded are not included. Additional filters can be used to restrict the records,
+/// e.g. by bucket.
+#[utoipa::path(
+    get,
+    path = "/stats",
+    responses(
+        (status = OK, description = "The ingestion statistics over the window", body = IngestionStats),
+        ErrorStatusCode,
+    ),
+    params(StatsParams, WildcardParams, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "get",
+)]
+pub async fn ingestion_stats(
+    state: State<AppState>,
+    WithRejection(extract::Query(stats), _): Query<StatsParams>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+) -> Result<Json<IngestionStats>> {
+    let window = stats.window()?;
+
+    Ok(Json(
+        ListQueryBuilder::<_, s3_object::Entity>::new(
+            state.database_client().read_connection_ref(),
+        )
+        .filter_all(filter_all, wildcard.case_sensitive(), false)?
+        .ingestion_stats(window)
+        .await?,
+    ))
+}
+
+/// The router for ingestion statistics.
+pub fn stats_router() -> Router<AppState> {
+    Router::new().route("/stats", get(ingestion_stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::error::ErrorResponse;
+    use crate::routes::list::tests::response_from;
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn ingestion_stats_api(pool: PgPool) {
+        let state = AppState::from_pool(pool.clone()).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+
+        // Records 0 to 4 were ingested recently with a lag of 10 seconds, and the rest were
+        // ingested two days ago. Of the recent `Created` records 0, 2 and 4, record 0 is flagged
+        // and record 2 has no ingest id.
+        for (i, entry) in entries.iter().enumerate() {
+            let ingested_at = if i < 5 { "30 minutes" } else { "2 days" };
+            sqlx::query(&format!(
+                "update s3_object set ingested_at = now() - interval '{ingested_at}', \
+                event_time = now() - interval '{ingested_at}' - interval '10 seconds' \
+                where s3_object_id = $1"
+            ))
+            .bind(entry.s3_object_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query("update s3_object set is_e_tag_mismatch = true where s3_object_id = $1")
+            .bind(entries[0].s3_object_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("update s3_object set ingest_id = null where s3_object_id = $1")
+            .bind(entries[2].s3_object_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (status, result) =
+            response_from::<IngestionStats>(state.clone(), "/stats", Method::GET, Body::empty())
+                .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            result,
+            IngestionStats::new(24 * 60 * 60, 5, Some(10.0), 1, 3, 1)
+        );
+        assert_eq!(result.error_rate(), 0.2);
+
+        let (_, result) = response_from::<IngestionStats>(
+            state.clone(),
+            "/stats?window=7days",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(result.n_ingested(), 10);
+        assert_eq!(result.average_lag_seconds(), Some(10.0));
+
+        let (_, result) = response_from::<IngestionStats>(
+            state.clone(),
+            "/stats?window=1h&bucket=0",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(result.n_ingested(), 2);
+        assert_eq!(result.n_errors(), 1);
+
+        let (status, _) = response_from::<ErrorResponse>(
+            state,
+            "/stats?window=invalid",
+            Method::GET,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
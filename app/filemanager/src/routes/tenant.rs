@@ -0,0 +1,317 @@
+//! Scoping of requests to the buckets of a tenant.
+//!
+
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use futures::future::Either;
+use sea_orm::ColumnTrait;
+use sea_orm::sea_query::SimpleExpr;
+
+use crate::error::Error::TenantError;
+use crate::error::Result;
+use crate::routes::AppState;
+
+tokio::task_local! {
+    /// The tenant scope of the current request.
+    static TENANT_SCOPE: TenantScope;
+}
+
+/// The tenant of a request, and the buckets that it is allowed to see. When a request runs
+/// within a tenant scope, the query builders restrict every query to the buckets of the tenant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantScope {
+    tenant: String,
+    buckets: Arc<[String]>,
+}
+
+impl TenantScope {
+    /// Create a new tenant scope.
+    pub fn new(tenant: String, buckets: Vec<String>) -> Self {
+        Self {
+            tenant,
+            buckets: buckets.into(),
+        }
+    }
+
+    /// Get the tenant scope of the current request, if there is one.
+    pub fn current() -> Option<Self> {
+        TENANT_SCOPE.try_with(Clone::clone).ok()
+    }
+
+    /// Run the future within this tenant scope.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        TENANT_SCOPE.scope(self, future).await
+    }
+
+    /// Wrap the future so that it runs within the tenant scope of the current request. This
+    /// should be used for any task that is spawned by a request and which queries the database.
+    pub fn propagate<F: Future>(future: F) -> impl Future<Output = F::Output> {
+        match Self::current() {
+            Some(scope) => Either::Left(TENANT_SCOPE.scope(scope, future)),
+            None => Either::Right(future),
+        }
+    }
+
+    /// Create a condition which restricts the bucket column to the buckets of the tenant of the
+    /// current request. There is no condition if the request is not scoped to a tenant.
+    pub fn bucket_condition<C: ColumnTrait>(column: C) -> Option<SimpleExpr> {
+        Self::current().map(|scope| column.is_in(scope.buckets.iter()))
+    }
+
+    /// Check that the bucket is visible to the tenant of the current request, if there is one.
+    pub fn check_bucket(bucket: &str) -> Result<()> {
+        match Self::current() {
+            Some(scope) if !scope.contains(bucket) => Err(TenantError(format!(
+                "bucket `{}` is not accessible to tenant `{}`",
+                bucket, scope.tenant
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the bucket belongs to the tenant.
+    pub fn contains(&self, bucket: &str) -> bool {
+        self.buckets.iter().any(|allowed| allowed == bucket)
+    }
+
+    /// Get the tenant.
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    /// Get the buckets of the tenant.
+    pub fn buckets(&self) -> &[String] {
+        &self.buckets
+    }
+}
+
+/// Middleware which runs a request within the scope of the tenant identified by the
+/// `FILEMANAGER_API_TENANT_HEADER` header. This has no effect if no tenants are configured
+/// using `FILEMANAGER_API_TENANT_BUCKETS`. Otherwise, requests which are missing the header or
+/// which identify an unknown tenant are rejected.
+pub async fn tenant_scope(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let tenants = state.config().api_tenant_buckets();
+    if tenants.is_empty() {
+        return Ok(next.run(request).await);
+    }
+
+    let header = state.config().api_tenant_header();
+    let tenant = request
+        .headers()
+        .get(header)
+        .ok_or_else(|| TenantError(format!("missing `{header}` header")))?
+        .to_str()
+        .map_err(|err| TenantError(err.to_string()))?;
+    let buckets = tenants
+        .get(tenant)
+        .ok_or_else(|| TenantError(format!("unknown tenant `{tenant}`")))?;
+
+    let scope = TenantScope::new(tenant.to_string(), buckets.clone());
+    Ok(scope.scope(next.run(request)).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::body::{Body, to_bytes};
+    use axum::http::header::HOST;
+    use axum::http::{Method, Request, StatusCode};
+    use sea_orm::{EntityTrait, QueryFilter, QueryTrait};
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::s3_object;
+    use crate::database::entities::s3_object::Model as S3;
+    use crate::env::Config;
+    use crate::queries::EntriesBuilder;
+    use crate::routes::api_router;
+    use crate::routes::list::ListCount;
+    use crate::routes::pagination::ListResponse;
+
+    #[tokio::test]
+    async fn bucket_condition() {
+        let query = |scope: Option<TenantScope>| async move {
+            let build = || {
+                s3_object::Entity::find()
+                    .apply_if(
+                        TenantScope::bucket_condition(s3_object::Column::Bucket),
+                        QueryFilter::filter,
+                    )
+                    .build(sea_orm::DbBackend::Postgres)
+                    .to_string()
+            };
+
+            match scope {
+                Some(scope) => scope.scope(async { build() }).await,
+                None => build(),
+            }
+        };
+
+        assert!(!query(None).await.contains("WHERE"));
+        assert!(
+            query(Some(TenantScope::new(
+                "a".to_string(),
+                vec!["0".to_string(), "1".to_string()]
+            )))
+            .await
+            .ends_with(r#"WHERE "s3_object"."bucket" IN ('0', '1')"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn check_bucket() {
+        assert!(TenantScope::check_bucket("0").is_ok());
+
+        let scope = TenantScope::new("a".to_string(), vec!["0".to_string()]);
+        scope
+            .scope(async {
+                assert!(TenantScope::check_bucket("0").is_ok());
+                assert!(TenantScope::check_bucket("1").is_err());
+
+                // Spawned tasks only see the scope if it is propagated.
+                let propagated =
+                    tokio::spawn(TenantScope::propagate(async { TenantScope::current() }));
+                let unscoped = tokio::spawn(async { TenantScope::current() });
+
+                assert_eq!(
+                    propagated.await.unwrap().map(|scope| scope.tenant),
+                    Some("a".to_string())
+                );
+                assert_eq!(unscoped.await.unwrap(), None);
+            })
+            .await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tenant_scope_api(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_tenant_buckets: HashMap::from_iter([
+                    ("a".to_string(), vec!["0".to_string(), "1".to_string()]),
+                    ("b".to_string(), vec!["2".to_string()]),
+                ]),
+                ..Default::default()
+            });
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap()
+            .s3_objects;
+        let visible = |buckets: &[&str]| {
+            entries
+                .iter()
+                .filter(|entry| buckets.contains(&entry.bucket.as_str()))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let (status, result) =
+            response::<ListResponse<S3>>(&state, None, "/s3?currentState=false").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(result.is_none());
+        let (status, _) =
+            response::<ListResponse<S3>>(&state, Some("c"), "/s3?currentState=false").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, result) =
+            response::<ListResponse<S3>>(&state, Some("a"), "/s3?currentState=false").await;
+        assert_eq!(result.unwrap().results(), visible(&["0", "1"]));
+        let (_, result) =
+            response::<ListResponse<S3>>(&state, Some("b"), "/s3?currentState=false").await;
+        assert_eq!(result.unwrap().results(), visible(&["2"]));
+
+        // Filtering on another tenant's bucket never returns its records.
+        let (_, result) =
+            response::<ListResponse<S3>>(&state, Some("b"), "/s3?currentState=false&bucket=0")
+                .await;
+        assert!(result.unwrap().results().is_empty());
+        let (_, result) =
+            response::<ListCount>(&state, Some("b"), "/s3/count?currentState=false").await;
+        assert_eq!(result.unwrap().n_records(), 2);
+
+        let other = visible(&["0"])[0].s3_object_id;
+        let (status, _) = response::<S3>(&state, Some("b"), &format!("/s3/{other}")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, result) = response::<S3>(&state, Some("a"), &format!("/s3/{other}")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result.unwrap().s3_object_id, other);
+
+        // Updates only apply to the tenant's records.
+        let (status, _) = response_with::<Value>(
+            &state,
+            Some("b"),
+            Method::PATCH,
+            &format!("/s3/{other}"),
+            Body::new(r#"[{"op":"add","path":"/tenant","value":"b"}]"#.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (_, result) = response::<S3>(&state, Some("a"), &format!("/s3/{other}")).await;
+        assert!(
+            result
+                .unwrap()
+                .attributes
+                .is_none_or(|attributes| attributes.get("tenant").is_none())
+        );
+
+        // Routes which take a bucket directly reject buckets of other tenants.
+        let (status, _) = response::<Value>(&state, Some("b"), "/s3/explain?bucket=0&key=0").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = response::<Value>(&state, Some("b"), "/s3/prefixes?bucket=0").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = response::<Value>(&state, Some("b"), "/s3/prefixes?bucket=2").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    async fn response<T: DeserializeOwned>(
+        state: &AppState,
+        tenant: Option<&str>,
+        uri: &str,
+    ) -> (StatusCode, Option<T>) {
+        response_with(state, tenant, Method::GET, uri, Body::empty()).await
+    }
+
+    async fn response_with<T: DeserializeOwned>(
+        state: &AppState,
+        tenant: Option<&str>,
+        method: Method,
+        uri: &str,
+        body: Body,
+    ) -> (StatusCode, Option<T>) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(HOST, "example.com")
+            .header("content-type", "application/json");
+        if let Some(tenant) = tenant {
+            request = request.header("x-tenant-id", tenant);
+        }
+
+        let response = api_router(state.clone())
+            .unwrap()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (
+            status,
+            (status == StatusCode::OK).then(|| serde_json::from_slice(&bytes).unwrap()),
+        )
+    }
+}
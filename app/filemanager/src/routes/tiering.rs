@@ -0,0 +1,289 @@
+//! Route logic for storage tiering recommendations.
+//!
+
+use axum::extract::{Request, State};
+use axum::routing::get;
+use axum::{Json, Router, extract};
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::database::entities::s3_object;
+use crate::database::entities::s3_object::Model as S3;
+use crate::env::Config;
+use crate::error::Result;
+use crate::queries::list::ListQueryBuilder;
+use crate::routes::AppState;
+use crate::routes::error::{ErrorStatusCode, QsQuery, Query};
+use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::header::HeaderParser;
+use crate::routes::list::WildcardParams;
+use crate::routes::pagination::{ListResponse, Pagination};
+
+/// The default number of days that an object must be unmodified to be a tiering candidate.
+pub const DEFAULT_TIERING_MIN_AGE_DAYS: u64 = 30;
+
+/// The default size in bytes that an object must be larger than to be a tiering candidate. This
+/// is 128 KiB, which is the minimum size of objects that are auto-tiered by `IntelligentTiering`.
+pub const DEFAULT_TIERING_MIN_SIZE: i64 = 128 * 1024;
+
+/// The number of bytes in a GB used for pricing.
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Params for a tiering recommendation request.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct TieringParams {
+    /// Only include objects where the `last_modified_date` is more than this number of days ago.
+    #[param(nullable = false, required = false, default = 30, minimum = 0)]
+    min_age_days: u64,
+    /// Only include objects which are larger than this size in bytes. Defaults to 128 KiB, which
+    /// is the minimum size of objects that are auto-tiered by `IntelligentTiering`.
+    #[param(nullable = false, required = false, default = 131072, minimum = 0)]
+    min_size: i64,
+}
+
+impl Default for TieringParams {
+    fn default() -> Self {
+        Self {
+            min_age_days: DEFAULT_TIERING_MIN_AGE_DAYS,
+            min_size: DEFAULT_TIERING_MIN_SIZE,
+        }
+    }
+}
+
+impl TieringParams {
+    /// Create new tiering params.
+    pub fn new(min_age_days: u64, min_size: i64) -> Self {
+        Self {
+            min_age_days,
+            min_size,
+        }
+    }
+
+    /// Get the minimum age in days.
+    pub fn min_age_days(&self) -> u64 {
+        self.min_age_days
+    }
+
+    /// Get the minimum size.
+    pub fn min_size(&self) -> i64 {
+        self.min_size
+    }
+}
+
+/// The tiering recommendation, containing candidate objects and estimated monthly savings.
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TieringRecommendation {
+    /// The total number of candidate objects.
+    n_objects: u64,
+    /// The total size of the candidate objects in bytes.
+    total_size: i64,
+    /// The estimated monthly cost of storing the candidates in the `Standard` storage class.
+    standard_monthly_cost: f64,
+    /// The estimated monthly savings of moving the candidates to `IntelligentTiering`.
+    intelligent_tiering_monthly_savings: f64,
+    /// The estimated monthly savings of moving the candidates to `Glacier`.
+    glacier_monthly_savings: f64,
+    /// The candidate objects.
+    candidates: ListResponse<S3>,
+}
+
+impl TieringRecommendation {
+    /// Create a tiering recommendation, estimating savings using the prices in the config.
+    pub fn new(
+        config: &Config,
+        n_objects: u64,
+        total_size: i64,
+        candidates: ListResponse<S3>,
+    ) -> Self {
+        let size_gb = total_size as f64 / BYTES_PER_GB;
+        let standard_monthly_cost = size_gb * config.api_standard_price_per_gb();
+
+        Self {
+            n_objects,
+            total_size,
+            standard_monthly_cost,
+            intelligent_tiering_monthly_savings: standard_monthly_cost
+                - size_gb * config.api_intelligent_tiering_price_per_gb(),
+            glacier_monthly_savings: standard_monthly_cost
+                - size_gb * config.api_glacier_price_per_gb(),
+            candidates,
+        }
+    }
+
+    /// Get the number of candidate objects.
+    pub fn n_objects(&self) -> u64 {
+        self.n_objects
+    }
+
+    /// Get the total size of the candidate objects.
+    pub fn total_size(&self) -> i64 {
+        self.total_size
+    }
+
+    /// Get the estimated monthly cost in the `Standard` storage class.
+    pub fn standard_monthly_cost(&self) -> f64 {
+        self.standard_monthly_cost
+    }
+
+    /// Get the estimated monthly savings of moving to `IntelligentTiering`.
+    pub fn intelligent_tiering_monthly_savings(&self) -> f64 {
+        self.intelligent_tiering_monthly_savings
+    }
+
+    /// Get the estimated monthly savings of moving to `Glacier`.
+    pub fn glacier_monthly_savings(&self) -> f64 {
+        self.glacier_monthly_savings
+    }
+
+    /// Get the candidate objects.
+    pub fn candidates(&self) -> &ListResponse<S3> {
+        &self.candidates
+    }
+}
+
+/// Recommend objects to move to a cheaper storage class. This finds current `Standard` tier
+/// objects that have not been modified for `minAgeDays` and are larger than `minSize`, and
+/// estimates the monthly savings of moving them to `IntelligentTiering` or `Glacier`. Prices
+/// per GB are set using the API configuration. Additional filters can be used to restrict the
+/// candidates, e.g. by bucket or key.
+#[utoipa::path(
+    get,
+    path = "/s3/tiering",
+    responses(
+        (status = OK, description = "The tiering recommendation", body = TieringRecommendation),
+        ErrorStatusCode,
+    ),
+    params(Pagination, WildcardParams, TieringParams, S3ObjectsFilter),
+    context_path = "/api/v1",
+    tag = "list",
+)]
+pub async fn tiering_s3(
+    state: State<AppState>,
+    WithRejection(extract::Query(pagination), _): Query<Pagination>,
+    WithRejection(extract::Query(wildcard), _): Query<WildcardParams>,
+    WithRejection(extract::Query(tiering), _): Query<TieringParams>,
+    WithRejection(serde_qs::axum::QsQuery(filter_all), _): QsQuery<S3ObjectsFilter>,
+    request: Request,
+) -> Result<Json<TieringRecommendation>> {
+    let txn = state.begin_read().await?;
+
+    let candidates = ListQueryBuilder::<_, s3_object::Entity>::new(&txn)
+        .filter_all(filter_all, wildcard.case_sensitive(), false)?
+        .filter_tiering_candidates(tiering.min_age_days, tiering.min_size);
+
+    let url = if let Some(url) = state.config().api_links_url() {
+        url
+    } else {
+        &HeaderParser::parse_host_url(&request, state.use_tls_links())?
+    };
+
+    let url = url.join(&HeaderParser::get_uri_path(&request))?;
+
+    let n_objects = candidates.cloned().count().await?;
+    let total_size = candidates.cloned().total_size().await?;
+    let candidates = candidates
+        .paginate_to_list_response(pagination, url, n_objects)
+        .await?;
+
+    txn.commit().await?;
+
+    Ok(Json(TieringRecommendation::new(
+        state.config(),
+        n_objects,
+        total_size,
+        candidates,
+    )))
+}
+
+/// The router for tiering recommendations.
+pub fn tiering_router() -> Router<AppState> {
+    Router::new().route("/s3/tiering", get(tiering_s3))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::database::aws::migration::tests::MIGRATOR;
+    use crate::database::entities::sea_orm_active_enums::StorageClass;
+    use crate::queries::EntriesBuilder;
+    use crate::queries::update::tests::change_last_modified_date;
+    use crate::routes::list::tests::response_from_get;
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn tiering_s3_api(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_standard_price_per_gb: 1.0,
+                api_intelligent_tiering_price_per_gb: 0.5,
+                api_glacier_price_per_gb: 0.25,
+                ..Default::default()
+            });
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let gb = BYTES_PER_GB as i64;
+        let connection = state.database_client().connection_ref();
+        for (i, size, storage_class) in [
+            (0, 2 * gb, StorageClass::Standard),
+            (2, 2 * gb, StorageClass::Standard),
+            // Too small.
+            (4, 1024, StorageClass::Standard),
+            // Not in the standard tier.
+            (6, 2 * gb, StorageClass::Glacier),
+            (8, 4 * gb, StorageClass::Standard),
+        ] {
+            let mut model: s3_object::ActiveModel =
+                entries.s3_objects[i].clone().into_active_model();
+            model.size = Set(Some(size));
+            model.storage_class = Set(Some(storage_class));
+            model.update(connection).await.unwrap();
+        }
+        // Recently modified.
+        change_last_modified_date(
+            state.database_client(),
+            &entries,
+            2,
+            Some((Utc::now() - Duration::days(1)).into()),
+        )
+        .await;
+
+        let result: TieringRecommendation =
+            response_from_get(state.clone(), "/s3/tiering?rowsPerPage=1").await;
+        assert_eq!(result.n_objects(), 2);
+        assert_eq!(result.total_size(), 6 * gb);
+        assert_eq!(result.standard_monthly_cost(), 6.0);
+        assert_eq!(result.intelligent_tiering_monthly_savings(), 3.0);
+        assert_eq!(result.glacier_monthly_savings(), 4.5);
+        assert_eq!(
+            result
+                .candidates()
+                .results()
+                .iter()
+                .map(|s3| s3.s3_object_id)
+                .collect::<Vec<_>>(),
+            vec![entries.s3_objects[0].s3_object_id]
+        );
+        assert_eq!(result.candidates().pagination().count, 2);
+
+        let result: TieringRecommendation = response_from_get(
+            state,
+            "/s3/tiering?minAgeDays=0&minSize=0&bucket[]=0&bucket[]=1&bucket[]=2",
+        )
+        .await;
+        assert_eq!(result.n_objects(), 3);
+        assert_eq!(result.total_size(), 4 * gb + 1024);
+    }
+}
@@ -1,23 +1,29 @@
 use crate::clients::aws::s3::Client;
 use crate::database::entities::s3_object;
 use crate::database::entities::s3_object::Model as S3;
+use crate::database::entities::sea_orm_active_enums::StorageClass;
 use crate::env::Config;
-use crate::error::Error::{ExpectedSomeValue, QueryError};
+use crate::error::Error::{ExpectedSomeValue, InvalidQuery, QueryError};
 use crate::error::{Error, Result};
+use crate::queries::timing::log_slow_query;
 use crate::queries::update::UpdateQueryBuilder;
 use crate::routes::AppState;
 use crate::routes::error::{ErrorStatusCode, Json, Path, QsQuery, Query};
-use crate::routes::filter::S3ObjectsFilter;
+use crate::routes::filter::{S3ObjectsFilter, namespace_attribute_key};
 use crate::routes::list::{ListS3Params, WildcardParams};
 use aws_sdk_s3::types::{Tag, Tagging};
 use axum::extract::State;
-use axum::routing::patch;
+use axum::routing::{patch, post};
 use axum::{Router, extract};
 use axum_extra::extract::WithRejection;
-use json_patch::PatchOperation;
-use sea_orm::TransactionTrait;
+use json_patch::jsonptr::PointerBuf;
+use json_patch::{AddOperation, PatchOperation};
+use sea_orm::{ActiveEnum, ConnectionTrait, TransactionTrait};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::slice;
 use std::str::FromStr;
+use tracing::trace;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
@@ -37,6 +43,33 @@ pub struct UpdateIngestIdParams {
     update_tag: bool,
 }
 
+/// Params for namespacing attribute keys.
+#[derive(Debug, Serialize, Deserialize, Default, IntoParams)]
+#[serde(default, rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct AttributeNamespaceParams {
+    /// Apply a namespace to the top-level keys of the attributes patch, so that attributes
+    /// written by different teams do not collide. E.g. with `attributeNamespace=team1`, a patch
+    /// with the path `/portalRunId` updates the `team1.portalRunId` attribute. The same
+    /// `attributeNamespace` can be used when filtering to query namespaced attributes.
+    #[param(nullable = false, required = false)]
+    attribute_namespace: Option<String>,
+}
+
+impl AttributeNamespaceParams {
+    /// Create new attribute namespace params.
+    pub fn new(attribute_namespace: Option<String>) -> Self {
+        Self {
+            attribute_namespace,
+        }
+    }
+
+    /// Get the attribute namespace.
+    pub fn attribute_namespace(&self) -> Option<&str> {
+        self.attribute_namespace.as_deref()
+    }
+}
+
 /// The attributes to update for the request. This updates attributes according to JSON patch.
 /// See [JSON patch](https://jsonpatch.com/) and [RFC6902](https://datatracker.ietf.org/doc/html/rfc6902/).
 ///
@@ -122,6 +155,118 @@ impl PatchBody {
         }
     }
 
+    /// Apply a namespace to the top-level key of each path in an attributes patch. This has no
+    /// effect on an `ingestId` patch.
+    pub fn with_attribute_namespace(self, namespace: Option<&str>) -> Result<Self> {
+        let Some(namespace) = namespace else {
+            return Ok(self);
+        };
+
+        let namespace_patch = |patch: Patch| {
+            let operations = patch
+                .into_inner()
+                .0
+                .into_iter()
+                .map(|mut operation| {
+                    match &mut operation {
+                        PatchOperation::Add(op) => {
+                            op.path = Self::namespace_path(namespace, &op.path)?
+                        }
+                        PatchOperation::Remove(op) => {
+                            op.path = Self::namespace_path(namespace, &op.path)?
+                        }
+                        PatchOperation::Replace(op) => {
+                            op.path = Self::namespace_path(namespace, &op.path)?
+                        }
+                        PatchOperation::Move(op) => {
+                            op.from = Self::namespace_path(namespace, &op.from)?;
+                            op.path = Self::namespace_path(namespace, &op.path)?;
+                        }
+                        PatchOperation::Copy(op) => {
+                            op.from = Self::namespace_path(namespace, &op.from)?;
+                            op.path = Self::namespace_path(namespace, &op.path)?;
+                        }
+                        PatchOperation::Test(op) => {
+                            op.path = Self::namespace_path(namespace, &op.path)?
+                        }
+                    }
+
+                    Ok(operation)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok::<_, Error>(Patch::new(json_patch::Patch(operations)))
+        };
+
+        Ok(match self {
+            PatchBody::NestedAttributes { attributes } => PatchBody::NestedAttributes {
+                attributes: namespace_patch(attributes)?,
+            },
+            PatchBody::UnnestedAttributes(attributes) => {
+                PatchBody::UnnestedAttributes(namespace_patch(attributes)?)
+            }
+            ingest_id => ingest_id,
+        })
+    }
+
+    /// Get the top-level attribute keys that are set by an attributes patch. This does not
+    /// include keys that are only removed or tested, and is empty for an `ingestId` patch.
+    pub fn attribute_keys(&self) -> Vec<String> {
+        if let PatchBody::NestedIngestId { .. } = self {
+            return vec![];
+        }
+
+        let mut keys: Vec<String> = self
+            .get_ref()
+            .0
+            .iter()
+            .filter_map(|operation| match operation {
+                PatchOperation::Add(op) => Some(&op.path),
+                PatchOperation::Replace(op) => Some(&op.path),
+                PatchOperation::Move(op) => Some(&op.path),
+                PatchOperation::Copy(op) => Some(&op.path),
+                PatchOperation::Remove(_) | PatchOperation::Test(_) => None,
+            })
+            .filter_map(|path| path.first().map(|key| key.decoded().to_string()))
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        keys
+    }
+
+    /// Whether an attributes patch sets the root path, which replaces all attributes rather
+    /// than setting individual keys.
+    pub fn sets_root_path(&self) -> bool {
+        if let PatchBody::NestedIngestId { .. } = self {
+            return false;
+        }
+
+        self.get_ref()
+            .0
+            .iter()
+            .filter_map(|operation| match operation {
+                PatchOperation::Add(op) => Some(&op.path),
+                PatchOperation::Replace(op) => Some(&op.path),
+                PatchOperation::Move(op) => Some(&op.path),
+                PatchOperation::Copy(op) => Some(&op.path),
+                PatchOperation::Remove(_) | PatchOperation::Test(_) => None,
+            })
+            .any(|path| path.first().is_none())
+    }
+
+    /// Apply a namespace to the first token of a JSON pointer path.
+    fn namespace_path(namespace: &str, path: &PointerBuf) -> Result<PointerBuf> {
+        let (key, rest) = path.split_front().ok_or_else(|| {
+            InvalidQuery("cannot apply an attribute namespace to the root path".to_string())
+        })?;
+
+        let mut path = rest.to_buf();
+        path.push_front(namespace_attribute_key(namespace, &key.decoded()));
+
+        Ok(path)
+    }
+
     /// Extract the ingest id if this is an ingest id patch.
     pub fn extract_ingest_id(&self) -> Result<Option<Uuid>> {
         let inner = self.get_ref();
@@ -162,13 +307,29 @@ impl PatchBody {
         Ok(to_update)
     }
 
-    /// Updates the tags in S3 with the specific ingest id.
+    /// Updates the tags in S3 with the specific ingest id. If conditional tag writes are enabled,
+    /// the current tags are fetched first, and nothing is written if the tag already matches.
     pub async fn update_s3_tag(
         client: &Client,
         config: &Config,
         model: &s3_object::Model,
         ingest_id: Uuid,
     ) -> Result<()> {
+        if config.api_conditional_tag_writes() {
+            let tagging = client
+                .get_object_tagging(&model.key, &model.bucket, &model.version_id)
+                .await?;
+            let ingest_id = ingest_id.to_string();
+            if tagging
+                .tag_set()
+                .iter()
+                .any(|tag| tag.key() == config.ingester_tag_name() && tag.value() == ingest_id)
+            {
+                trace!(key = %model.key, bucket = %model.bucket, "skipping matching tag write");
+                return Ok(());
+            }
+        }
+
         client
             .put_object_tagging(
                 &model.key,
@@ -206,6 +367,76 @@ pub async fn update_s3_tags(
     Ok(())
 }
 
+/// Check that a patch does not set any attribute keys that are denied in the config. Patches
+/// which set the root path are rejected, because they replace all attributes, including denied
+/// keys. This runs before the patch is applied.
+pub fn verify_attribute_keys(state: &AppState, patch: &PatchBody) -> Result<()> {
+    let denied = state.config().api_denied_attribute_keys();
+    if denied.is_empty() {
+        return Ok(());
+    }
+
+    if patch.sets_root_path() {
+        return Err(InvalidQuery(
+            "the root path cannot be patched when attribute keys are denied".to_string(),
+        ));
+    }
+
+    let keys = patch
+        .attribute_keys()
+        .into_iter()
+        .filter(|key| denied.contains(key))
+        .collect::<Vec<_>>();
+    if !keys.is_empty() {
+        return Err(InvalidQuery(format!(
+            "attribute keys cannot be set using the API: {}",
+            keys.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check that the serialized attributes of updated records do not exceed the maximum size set
+/// in the config.
+pub fn verify_attribute_size(state: &AppState, updated: &[S3]) -> Result<()> {
+    let max_size = state.config().api_max_attributes_size();
+    for model in updated {
+        let Some(attributes) = model.attributes.as_ref() else {
+            continue;
+        };
+
+        let size = u64::try_from(serde_json::to_vec(attributes)?.len())?;
+        if size > max_size {
+            return Err(InvalidQuery(format!(
+                "attributes for `{}` would be {size} bytes, which exceeds the maximum of \
+                {max_size} bytes",
+                model.s3_object_id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that updated attributes keep a consistent JSON type within each bucket, if this is
+/// enabled in the config.
+pub async fn verify_attribute_types<C: ConnectionTrait>(
+    state: &AppState,
+    connection: &C,
+    keys: &[String],
+    updated: &[S3],
+) -> Result<()> {
+    if state.config().api_enforce_attribute_types() {
+        UpdateQueryBuilder::<_, s3_object::Entity>::verify_attribute_types(
+            connection, keys, updated,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
 /// Update the s3_object attributes using a JSON patch request.
 #[utoipa::path(
     patch,
@@ -218,7 +449,7 @@ pub async fn update_s3_tags(
         ),
         ErrorStatusCode,
     ),
-    params(UpdateIngestIdParams),
+    params(UpdateIngestIdParams, AttributeNamespaceParams),
     request_body = PatchBody,
     context_path = "/api/v1",
     tag = "update",
@@ -227,14 +458,18 @@ pub async fn update_s3_attributes(
     state: State<AppState>,
     WithRejection(extract::Path(id), _): Path<Uuid>,
     WithRejection(extract::Query(ingest_id_params), _): Query<UpdateIngestIdParams>,
+    WithRejection(extract::Query(namespace), _): Query<AttributeNamespaceParams>,
     WithRejection(extract::Json(patch), _): Json<PatchBody>,
 ) -> Result<extract::Json<S3>> {
-    let txn = state.database_client().connection_ref().begin().await?;
+    let patch = patch.with_attribute_namespace(namespace.attribute_namespace())?;
+    verify_attribute_keys(&state, &patch)?;
+    let txn = state.begin().await?;
 
     let ingest_id = match patch {
         PatchBody::NestedIngestId { .. } => patch.extract_ingest_id()?,
         _ => None,
     };
+    let keys = patch.attribute_keys();
 
     let result = UpdateQueryBuilder::<_, s3_object::Entity>::new(&txn)
         .for_id(id)
@@ -244,6 +479,9 @@ pub async fn update_s3_attributes(
         .await?
         .ok_or_else(|| ExpectedSomeValue(id))?;
 
+    verify_attribute_size(&state, slice::from_ref(&result))?;
+    verify_attribute_types(&state, &txn, &keys, slice::from_ref(&result)).await?;
+
     update_s3_tags(&state, &ingest_id_params, ingest_id, &result).await?;
 
     txn.commit().await?;
@@ -252,7 +490,8 @@ pub async fn update_s3_attributes(
 }
 
 /// Update the attributes for a collection of s3_objects using a JSON patch request.
-/// This updates all attributes matching the filter params with the same JSON patch.
+/// This updates all attributes matching the filter params with the same JSON patch. If
+/// `attributeNamespace` is set, it applies to both the `attributes` filter and the patch.
 #[utoipa::path(
     patch,
     path = "/s3",
@@ -277,20 +516,31 @@ pub async fn update_s3_collection_attributes(
     WithRejection(extract::Query(ingest_id_params), _): Query<UpdateIngestIdParams>,
     WithRejection(extract::Json(patch), _): Json<PatchBody>,
 ) -> Result<extract::Json<Vec<S3>>> {
-    let txn = state.database_client().connection_ref().begin().await?;
+    // The namespace applies to both the attributes filter and the patch.
+    let patch = patch.with_attribute_namespace(filter_all.attribute_namespace.as_deref())?;
+    verify_attribute_keys(&state, &patch)?;
+    let txn = state.begin().await?;
 
     let ingest_id = match patch {
         PatchBody::NestedIngestId { .. } => patch.extract_ingest_id()?,
         _ => None,
     };
+    let keys = patch.attribute_keys();
 
+    let summary = filter_all.summary();
     let results = UpdateQueryBuilder::<_, s3_object::Entity>::new(&txn).filter_all(
         filter_all,
         wildcard.case_sensitive(),
         list.current_state(),
     )?;
 
-    let results = results.update_s3_attributes(patch).await?.all().await?;
+    let results = log_slow_query(state.config().api_slow_query_threshold(), summary, async {
+        results.update_s3_attributes(patch).await?.all().await
+    })
+    .await?;
+
+    verify_attribute_size(&state, &results)?;
+    verify_attribute_types(&state, &txn, &keys, &results).await?;
 
     for result in &results {
         update_s3_tags(&state, &ingest_id_params, ingest_id, result).await?;
@@ -301,11 +551,384 @@ pub async fn update_s3_collection_attributes(
     Ok(extract::Json(results))
 }
 
+/// Validate a JSON patch against the s3_object without applying it. This returns the record as
+/// it would be after the update, or a `BAD_REQUEST` if the patch fails, for example, because a
+/// `test` operation did not match. The record in the database is not updated.
+#[utoipa::path(
+    post,
+    path = "/s3/{id}/patch-validate",
+    responses(
+        (
+            status = OK,
+            description = "The s3_object with the patch applied, without it being updated",
+            body = S3
+        ),
+        ErrorStatusCode,
+    ),
+    params(AttributeNamespaceParams),
+    request_body = PatchBody,
+    context_path = "/api/v1",
+    tag = "update",
+)]
+pub async fn validate_s3_attributes(
+    state: State<AppState>,
+    WithRejection(extract::Path(id), _): Path<Uuid>,
+    WithRejection(extract::Query(namespace), _): Query<AttributeNamespaceParams>,
+    WithRejection(extract::Json(patch), _): Json<PatchBody>,
+) -> Result<extract::Json<S3>> {
+    let patch = patch.with_attribute_namespace(namespace.attribute_namespace())?;
+    let result =
+        UpdateQueryBuilder::<_, s3_object::Entity>::new(state.database_client().connection_ref())
+            .for_id(id)
+            .validate_s3_attributes(patch)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ExpectedSomeValue(id))?;
+
+    Ok(extract::Json(result))
+}
+
+/// An entry for setting attributes on the current record of an object.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkAttributes {
+    /// The bucket of the object.
+    bucket: String,
+    /// The key of the object.
+    key: String,
+    /// The version id of the object. By default, any current version of the object is updated.
+    #[serde(default)]
+    version_id: Option<String>,
+    /// The attributes to add to the record. Each top-level key is added using a JSON patch `add`
+    /// operation, replacing existing values under the same key.
+    attributes: Value,
+}
+
+impl BulkAttributes {
+    /// Create a new bulk attributes entry.
+    pub fn new(bucket: String, key: String, version_id: Option<String>, attributes: Value) -> Self {
+        Self {
+            bucket,
+            key,
+            version_id,
+            attributes,
+        }
+    }
+
+    /// Convert the attributes into a patch that adds each top-level key.
+    pub fn to_patch(&self) -> Result<PatchBody> {
+        let Value::Object(attributes) = &self.attributes else {
+            return Err(InvalidQuery(
+                "expected a JSON object for bulk attributes".to_string(),
+            ));
+        };
+
+        let operations = attributes
+            .iter()
+            .map(|(key, value)| {
+                PatchOperation::Add(AddOperation {
+                    path: PointerBuf::from_tokens([key]),
+                    value: value.clone(),
+                })
+            })
+            .collect();
+
+        Ok(PatchBody::new(Patch::new(json_patch::Patch(operations))))
+    }
+}
+
+/// The result of setting attributes for a single bulk attributes entry.
+#[derive(Debug, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkAttributesResult {
+    /// The bucket of the entry.
+    bucket: String,
+    /// The key of the entry.
+    key: String,
+    /// The version id of the entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version_id: Option<String>,
+    /// The ids of the records that were updated.
+    s3_object_ids: Vec<Uuid>,
+    /// Whether the attributes were set on at least one record.
+    success: bool,
+    /// The reason that setting the attributes failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BulkAttributesResult {
+    /// Create a result from the entry and the updated records.
+    pub fn new(entry: BulkAttributes, updated: Result<Vec<S3>>) -> Self {
+        let (s3_object_ids, error) = match updated {
+            Ok(updated) if updated.is_empty() => (
+                vec![],
+                Some("no current record found for the entry".to_string()),
+            ),
+            Ok(updated) => (
+                updated.into_iter().map(|s3| s3.s3_object_id).collect(),
+                None,
+            ),
+            Err(err) => (vec![], Some(err.to_string())),
+        };
+
+        Self {
+            bucket: entry.bucket,
+            key: entry.key,
+            version_id: entry.version_id,
+            s3_object_ids,
+            success: error.is_none(),
+            error,
+        }
+    }
+
+    /// Get the ids of the records that were updated.
+    pub fn s3_object_ids(&self) -> &[Uuid] {
+        &self.s3_object_ids
+    }
+
+    /// Whether the attributes were set.
+    pub fn is_success(&self) -> bool {
+        self.success
+    }
+
+    /// Get the error.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Set attributes on the current records of many objects from a mapping of objects to attributes.
+/// Each entry is applied as a JSON patch `add` operation for each top-level attribute key, to the
+/// current record matching the bucket, key and optional version id. All entries are applied in
+/// one transaction, and a failure on one entry does not affect the others. The response contains
+/// the result of each entry in the same order as the request.
+#[utoipa::path(
+    post,
+    path = "/s3/attributes/bulk",
+    responses(
+        (
+            status = OK,
+            description = "The result of setting attributes for each entry",
+            body = Vec<BulkAttributesResult>
+        ),
+        ErrorStatusCode,
+    ),
+    params(AttributeNamespaceParams),
+    request_body = Vec<BulkAttributes>,
+    context_path = "/api/v1",
+    tag = "update",
+)]
+pub async fn bulk_update_s3_attributes(
+    state: State<AppState>,
+    WithRejection(extract::Query(namespace), _): Query<AttributeNamespaceParams>,
+    WithRejection(extract::Json(entries), _): Json<Vec<BulkAttributes>>,
+) -> Result<extract::Json<Vec<BulkAttributesResult>>> {
+    let txn = state.begin().await?;
+
+    let mut results = Vec::with_capacity(entries.len());
+    for entry in entries {
+        // Each entry runs in a savepoint so that a failure does not abort the other entries.
+        let savepoint = txn.begin().await?;
+
+        let updated = async {
+            let patch = entry
+                .to_patch()?
+                .with_attribute_namespace(namespace.attribute_namespace())?;
+            verify_attribute_keys(&state, &patch)?;
+
+            // The object is matched exactly, so that keys containing wildcard characters only
+            // update their own records.
+            let keys = patch.attribute_keys();
+            let updated = UpdateQueryBuilder::<_, s3_object::Entity>::new(&savepoint)
+                .for_object(&entry.bucket, &entry.key, entry.version_id.as_deref())
+                .filter_all(S3ObjectsFilter::default(), true, true)?
+                .update_s3_attributes(patch)
+                .await?
+                .all()
+                .await?;
+
+            verify_attribute_size(&state, &updated)?;
+            verify_attribute_types(&state, &savepoint, &keys, &updated).await?;
+
+            Ok(updated)
+        }
+        .await;
+
+        match updated {
+            Ok(_) => savepoint.commit().await?,
+            Err(_) => savepoint.rollback().await?,
+        }
+
+        results.push(BulkAttributesResult::new(entry, updated));
+    }
+
+    txn.commit().await?;
+
+    Ok(extract::Json(results))
+}
+
+/// An entry for correcting the storage class of the current record of an object version.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClassCorrection {
+    /// The bucket of the object.
+    bucket: String,
+    /// The key of the object.
+    key: String,
+    /// The version id of the object.
+    version_id: String,
+    /// The storage class to set on the record. This must be a valid `StorageClass`, otherwise
+    /// the entry is rejected.
+    storage_class: String,
+}
+
+impl StorageClassCorrection {
+    /// Create a new storage class correction entry.
+    pub fn new(bucket: String, key: String, version_id: String, storage_class: String) -> Self {
+        Self {
+            bucket,
+            key,
+            version_id,
+            storage_class,
+        }
+    }
+
+    /// Validate the storage class of the entry.
+    pub fn to_storage_class(&self) -> Result<StorageClass> {
+        StorageClass::try_from_value(&self.storage_class)
+            .map_err(|_| InvalidQuery(format!("invalid storage class `{}`", self.storage_class)))
+    }
+}
+
+/// The result of correcting the storage class for a single storage class correction entry.
+#[derive(Debug, Deserialize, Serialize, ToSchema, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClassCorrectionResult {
+    /// The bucket of the entry.
+    bucket: String,
+    /// The key of the entry.
+    key: String,
+    /// The version id of the entry.
+    version_id: String,
+    /// The ids of the records that were updated.
+    s3_object_ids: Vec<Uuid>,
+    /// Whether the storage class was set on at least one record.
+    success: bool,
+    /// The reason that setting the storage class failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl StorageClassCorrectionResult {
+    /// Create a result from the entry and the updated records.
+    pub fn new(entry: StorageClassCorrection, updated: Result<Vec<S3>>) -> Self {
+        let (s3_object_ids, error) = match updated {
+            Ok(updated) if updated.is_empty() => (
+                vec![],
+                Some("no current record found for the entry".to_string()),
+            ),
+            Ok(updated) => (
+                updated.into_iter().map(|s3| s3.s3_object_id).collect(),
+                None,
+            ),
+            Err(err) => (vec![], Some(err.to_string())),
+        };
+
+        Self {
+            bucket: entry.bucket,
+            key: entry.key,
+            version_id: entry.version_id,
+            s3_object_ids,
+            success: error.is_none(),
+            error,
+        }
+    }
+
+    /// Get the ids of the records that were updated.
+    pub fn s3_object_ids(&self) -> &[Uuid] {
+        &self.s3_object_ids
+    }
+
+    /// Whether the storage class was set.
+    pub fn is_success(&self) -> bool {
+        self.success
+    }
+
+    /// Get the error.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Correct the storage class on the current records of many object versions, for example, when
+/// a storage class transition was missed by S3 events and the correct storage class is known
+/// from another source. This updates the records directly without creating new events. All
+/// entries are applied in one transaction, and an invalid storage class or a failure on one entry
+/// does not affect the others. The response contains the result of each entry in the same order
+/// as the request.
+#[utoipa::path(
+    post,
+    path = "/s3/storage-class/bulk",
+    responses(
+        (
+            status = OK,
+            description = "The result of correcting the storage class for each entry",
+            body = Vec<StorageClassCorrectionResult>
+        ),
+        ErrorStatusCode,
+    ),
+    request_body = Vec<StorageClassCorrection>,
+    context_path = "/api/v1",
+    tag = "update",
+)]
+pub async fn bulk_update_s3_storage_class(
+    state: State<AppState>,
+    WithRejection(extract::Json(entries), _): Json<Vec<StorageClassCorrection>>,
+) -> Result<extract::Json<Vec<StorageClassCorrectionResult>>> {
+    let txn = state.begin().await?;
+
+    let mut results = Vec::with_capacity(entries.len());
+    for entry in entries {
+        // Each entry runs in a savepoint so that a failure does not abort the other entries.
+        let savepoint = txn.begin().await?;
+
+        let updated = async {
+            let storage_class = entry.to_storage_class()?;
+
+            // The object is matched exactly, so that keys containing wildcard characters only
+            // update their own records.
+            UpdateQueryBuilder::<_, s3_object::Entity>::new(&savepoint)
+                .for_object(&entry.bucket, &entry.key, Some(&entry.version_id))
+                .filter_all(S3ObjectsFilter::default(), true, true)?
+                .update_s3_storage_class(storage_class)
+                .all()
+                .await
+        }
+        .await;
+
+        match updated {
+            Ok(_) => savepoint.commit().await?,
+            Err(_) => savepoint.rollback().await?,
+        }
+
+        results.push(StorageClassCorrectionResult::new(entry, updated));
+    }
+
+    txn.commit().await?;
+
+    Ok(extract::Json(results))
+}
+
 /// The router for updating objects.
 pub fn update_router() -> Router<AppState> {
     Router::new()
         .route("/s3/{id}", patch(update_s3_attributes))
+        .route("/s3/{id}/patch-validate", post(validate_s3_attributes))
         .route("/s3", patch(update_s3_collection_attributes))
+        .route("/s3/attributes/bulk", post(bulk_update_s3_attributes))
+        .route("/s3/storage-class/bulk", post(bulk_update_s3_storage_class))
 }
 
 #[cfg(test)]
@@ -325,10 +948,14 @@ mod tests {
         assert_correct_records, assert_model_contains, assert_wildcard_update,
         change_attribute_entries, change_attributes, change_many, update_ingest_ids,
     };
-    use crate::routes::list::tests::response_from;
+    use crate::routes::list::tests::{response_from, response_from_get};
+    use crate::routes::pagination::ListResponse;
     use crate::uuid::UuidGenerator;
+    use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingOutput;
     use aws_sdk_s3::operation::put_object_tagging::PutObjectTaggingOutput;
     use aws_smithy_mocks::mock;
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
     use std::sync::Arc;
 
     #[sqlx::test(migrator = "MIGRATOR")]
@@ -400,6 +1027,58 @@ mod tests {
         assert_correct_records(state.database_client(), entries).await;
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn validate_attribute_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let mut entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        change_attributes(
+            state.database_client(),
+            &entries,
+            0,
+            Some(json!({"attributeId": "1"})),
+        )
+        .await;
+        let uri = format!("/s3/{}/patch-validate", entries.s3_objects[0].s3_object_id);
+
+        let patch = json!([
+            { "op": "test", "path": "/attributeId", "value": "1" },
+            { "op": "add", "path": "/anotherAttribute", "value": "anotherAttribute" },
+        ]);
+        let (status, result) = response_from::<S3>(
+            state.clone(),
+            &uri,
+            Method::POST,
+            Body::new(patch.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            result.attributes,
+            Some(json!({"attributeId": "1", "anotherAttribute": "anotherAttribute"}))
+        );
+
+        let patch = json!([
+            { "op": "test", "path": "/attributeId", "value": "2" },
+            { "op": "add", "path": "/anotherAttribute", "value": "anotherAttribute" },
+        ]);
+        let (status, _) = response_from::<Value>(
+            state.clone(),
+            &uri,
+            Method::POST,
+            Body::new(patch.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Nothing is expected to change.
+        change_attribute_entries(&mut entries, 0, json!({"attributeId": "1"}));
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn update_collection_attributes_api_add_nested(pool: PgPool) {
         let state = AppState::from_pool(pool).await.unwrap();
@@ -451,6 +1130,500 @@ mod tests {
         assert_correct_records(state.database_client(), entries).await;
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn update_attributes_api_namespace(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let mut entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        change_attributes(
+            state.database_client(),
+            &entries,
+            0,
+            Some(json!({"attributeId": "1"})),
+        )
+        .await;
+
+        // Two teams write the same attribute key without colliding.
+        for namespace in ["team1", "team2"] {
+            let patch = json!([
+                { "op": "add", "path": "/portalRunId", "value": namespace },
+            ]);
+            let (status, _) = response_from::<S3>(
+                state.clone(),
+                &format!(
+                    "/s3/{}?attributeNamespace={namespace}",
+                    entries.s3_objects[0].s3_object_id
+                ),
+                Method::PATCH,
+                Body::new(patch.to_string()),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        // The namespace applies to both the filter and the patch.
+        let patch = json!([
+            { "op": "test", "path": "/portalRunId", "value": "team1" },
+            { "op": "add", "path": "/status", "value": "complete" },
+        ]);
+        let (_, s3_objects) = response_from::<Vec<S3>>(
+            state.clone(),
+            "/s3?currentState=false&attributeNamespace=team1&attributes[portalRunId]=team1",
+            Method::PATCH,
+            Body::new(patch.to_string()),
+        )
+        .await;
+
+        change_attribute_entries(
+            &mut entries,
+            0,
+            json!({
+                "attributeId": "1",
+                "team1.portalRunId": "team1",
+                "team1.status": "complete",
+                "team2.portalRunId": "team2"
+            }),
+        );
+        assert_model_contains(&s3_objects, &entries.s3_objects, 0..1);
+
+        let result: ListResponse<S3> = response_from_get(
+            state.clone(),
+            "/s3?currentState=false&attributeNamespace=team2&attributes[portalRunId]=team2",
+        )
+        .await;
+        assert_eq!(result.results(), &entries.s3_objects[0..1]);
+
+        let result: ListResponse<S3> = response_from_get(
+            state.clone(),
+            "/s3?currentState=false&attributeNamespace=team2&attributes[portalRunId]=team1",
+        )
+        .await;
+        assert!(result.results().is_empty());
+
+        // Patching the root path is not supported with a namespace.
+        let patch = json!([{ "op": "add", "path": "", "value": {} }]);
+        let (status, _) = response_from::<Value>(
+            state.clone(),
+            &format!(
+                "/s3/{}?attributeNamespace=team1",
+                entries.s3_objects[0].s3_object_id
+            ),
+            Method::PATCH,
+            Body::new(patch.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn update_attributes_api_enforce_types(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_enforce_attribute_types: true,
+                ..Default::default()
+            });
+        let mut entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // Records 0 and 1 are in bucket "0", and record 2 is in bucket "1".
+        change_attributes(
+            state.database_client(),
+            &entries,
+            0,
+            Some(json!({"runNumber": 1})),
+        )
+        .await;
+        change_attribute_entries(&mut entries, 0, json!({"runNumber": 1}));
+
+        for (i, value, expected) in [
+            // A string conflicts with the number in the same bucket.
+            (1, json!("2"), StatusCode::BAD_REQUEST),
+            // Other buckets and matching types are allowed.
+            (2, json!("2"), StatusCode::OK),
+            (1, json!(2), StatusCode::OK),
+        ] {
+            let patch = json!([{ "op": "add", "path": "/runNumber", "value": value }]);
+            let (status, _) = response_from::<Value>(
+                state.clone(),
+                &format!("/s3/{}", entries.s3_objects[i].s3_object_id),
+                Method::PATCH,
+                Body::new(patch.to_string()),
+            )
+            .await;
+            assert_eq!(status, expected);
+
+            if expected == StatusCode::BAD_REQUEST {
+                assert_correct_records(state.database_client(), entries.clone()).await;
+            }
+        }
+
+        entries.s3_objects[1].attributes.as_mut().unwrap()["runNumber"] = json!(2);
+        entries.s3_objects[2].attributes.as_mut().unwrap()["runNumber"] = json!("2");
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn update_attributes_api_max_size(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_max_attributes_size: 128,
+                ..Default::default()
+            });
+        let mut entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let patch = json!([{ "op": "add", "path": "/project", "value": "a".repeat(128) }]);
+        let (status, _) = response_from::<Value>(
+            state.clone(),
+            &format!("/s3/{}", entries.s3_objects[0].s3_object_id),
+            Method::PATCH,
+            Body::new(patch.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_correct_records(state.database_client(), entries.clone()).await;
+
+        let patch = json!([{ "op": "add", "path": "/project", "value": "a" }]);
+        let (status, _) = response_from::<Value>(
+            state.clone(),
+            &format!("/s3/{}", entries.s3_objects[0].s3_object_id),
+            Method::PATCH,
+            Body::new(patch.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        entries.s3_objects[0].attributes.as_mut().unwrap()["project"] = json!("a");
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn update_attributes_api_denied_keys(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_denied_attribute_keys: vec!["attributeId".to_string(), "reserved".to_string()],
+                ..Default::default()
+            });
+        let mut entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        for patch in [
+            json!([{ "op": "add", "path": "/reserved", "value": "a" }]),
+            json!([{ "op": "add", "path": "/attributeId/0", "value": "a" }]),
+            json!([{ "op": "copy", "from": "/project", "path": "/reserved" }]),
+            json!([{ "op": "add", "path": "", "value": { "reserved": "a" } }]),
+            json!([{ "op": "add", "path": "", "value": {} }]),
+            json!([{ "op": "copy", "from": "/attributeId", "path": "" }]),
+            json!([
+                { "op": "add", "path": "/project", "value": "a" },
+                { "op": "add", "path": "/reserved", "value": "a" }
+            ]),
+        ] {
+            let (status, _) = response_from::<Value>(
+                state.clone(),
+                &format!("/s3/{}", entries.s3_objects[0].s3_object_id),
+                Method::PATCH,
+                Body::new(patch.to_string()),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let (status, _) = response_from::<Value>(
+                state.clone(),
+                "/s3?currentState=false&bucket=0",
+                Method::PATCH,
+                Body::new(patch.to_string()),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+        assert_correct_records(state.database_client(), entries.clone()).await;
+
+        // Denied keys can still be tested.
+        let patch = json!([
+            { "op": "test", "path": "/attributeId", "value": "0" },
+            { "op": "add", "path": "/project", "value": "a" }
+        ]);
+        let (status, _) = response_from::<Value>(
+            state.clone(),
+            &format!("/s3/{}", entries.s3_objects[0].s3_object_id),
+            Method::PATCH,
+            Body::new(patch.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        entries.s3_objects[0].attributes.as_mut().unwrap()["project"] = json!("a");
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn bulk_update_attributes_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let current = entries
+            .s3_objects
+            .iter()
+            .filter(|s3| s3.is_current_state)
+            .take(2)
+            .cloned()
+            .collect::<Vec<_>>();
+        let body = json!([
+            {
+                "bucket": current[0].bucket,
+                "key": current[0].key,
+                "attributes": { "portalRunId": "1" }
+            },
+            {
+                "bucket": current[1].bucket,
+                "key": current[1].key,
+                "versionId": current[1].version_id,
+                "attributes": { "portalRunId": "2", "nested": { "attributeId": "3" } }
+            },
+            {
+                "bucket": current[1].bucket,
+                "key": "missing",
+                "attributes": { "portalRunId": "4" }
+            },
+            {
+                "bucket": current[1].bucket,
+                "key": current[1].key,
+                "attributes": "not an object"
+            },
+        ]);
+
+        let (status, results) = response_from::<Vec<BulkAttributesResult>>(
+            state.clone(),
+            "/s3/attributes/bulk",
+            Method::POST,
+            Body::new(body.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            results.iter().map(|r| r.is_success()).collect::<Vec<_>>(),
+            vec![true, true, false, false]
+        );
+        assert_eq!(results[0].s3_object_ids(), [current[0].s3_object_id]);
+        assert_eq!(results[1].s3_object_ids(), [current[1].s3_object_id]);
+        assert!(results[2].error().is_some());
+        assert!(results[3].error().is_some());
+
+        let mut entries = entries;
+        for (s3, attributes) in [
+            (&current[0], json!({ "portalRunId": "1" })),
+            (
+                &current[1],
+                json!({ "portalRunId": "2", "nested": { "attributeId": "3" } }),
+            ),
+        ] {
+            let i = entries
+                .s3_objects
+                .iter()
+                .position(|entry| entry.s3_object_id == s3.s3_object_id)
+                .unwrap();
+            let mut expected = s3.attributes.clone().unwrap_or_else(|| json!({}));
+            json_patch::merge(&mut expected, &attributes);
+            change_attribute_entries(&mut entries, i, expected);
+        }
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn bulk_update_attributes_wildcard_key(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let mut entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // A key containing wildcard characters should only match itself.
+        let i = entries
+            .s3_objects
+            .iter()
+            .position(|s3| s3.is_current_state)
+            .unwrap();
+        let mut model = entries.s3_objects[i].clone().into_active_model();
+        model.key = Set("*%".to_string());
+        entries.s3_objects[i] = model
+            .update(state.database_client().connection_ref())
+            .await
+            .unwrap();
+
+        let body = json!([
+            {
+                "bucket": entries.s3_objects[i].bucket,
+                "key": "*%",
+                "attributes": { "portalRunId": "1" }
+            },
+        ]);
+        let (status, results) = response_from::<Vec<BulkAttributesResult>>(
+            state.clone(),
+            "/s3/attributes/bulk",
+            Method::POST,
+            Body::new(body.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            results[0].s3_object_ids(),
+            [entries.s3_objects[i].s3_object_id]
+        );
+
+        let mut expected = entries.s3_objects[i]
+            .attributes
+            .clone()
+            .unwrap_or_else(|| json!({}));
+        json_patch::merge(&mut expected, &json!({ "portalRunId": "1" }));
+        change_attribute_entries(&mut entries, i, expected);
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn bulk_update_storage_class_api(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let current = entries
+            .s3_objects
+            .iter()
+            .filter(|s3| s3.is_current_state)
+            .take(3)
+            .cloned()
+            .collect::<Vec<_>>();
+        let body = json!([
+            {
+                "bucket": current[0].bucket,
+                "key": current[0].key,
+                "versionId": current[0].version_id,
+                "storageClass": "Glacier"
+            },
+            {
+                "bucket": current[1].bucket,
+                "key": current[1].key,
+                "versionId": current[1].version_id,
+                "storageClass": "DeepArchive"
+            },
+            {
+                "bucket": current[2].bucket,
+                "key": current[2].key,
+                "versionId": current[2].version_id,
+                "storageClass": "NotAStorageClass"
+            },
+            {
+                "bucket": current[2].bucket,
+                "key": current[2].key,
+                "versionId": "missing",
+                "storageClass": "Glacier"
+            },
+        ]);
+
+        let (status, results) = response_from::<Vec<StorageClassCorrectionResult>>(
+            state.clone(),
+            "/s3/storage-class/bulk",
+            Method::POST,
+            Body::new(body.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            results.iter().map(|r| r.is_success()).collect::<Vec<_>>(),
+            vec![true, true, false, false]
+        );
+        assert_eq!(results[0].s3_object_ids(), [current[0].s3_object_id]);
+        assert_eq!(results[1].s3_object_ids(), [current[1].s3_object_id]);
+        assert!(results[2].error().unwrap().contains("NotAStorageClass"));
+        assert!(results[3].error().is_some());
+
+        // Only the storage class, and the accessibility derived from it, of the corrected
+        // records changes, and no records are added.
+        let mut entries = entries;
+        for (s3, storage_class) in [
+            (&current[0], StorageClass::Glacier),
+            (&current[1], StorageClass::DeepArchive),
+        ] {
+            let entry = entries
+                .s3_objects
+                .iter_mut()
+                .find(|entry| entry.s3_object_id == s3.s3_object_id)
+                .unwrap();
+            entry.storage_class = Some(storage_class);
+            entry.is_accessible = false;
+        }
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn bulk_update_storage_class_wildcard_key(pool: PgPool) {
+        let state = AppState::from_pool(pool).await.unwrap();
+        let mut entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        // A key containing wildcard characters should only match itself.
+        let i = entries
+            .s3_objects
+            .iter()
+            .position(|s3| s3.is_current_state)
+            .unwrap();
+        let mut model = entries.s3_objects[i].clone().into_active_model();
+        model.key = Set("*%".to_string());
+        entries.s3_objects[i] = model
+            .update(state.database_client().connection_ref())
+            .await
+            .unwrap();
+
+        let body = json!([
+            {
+                "bucket": entries.s3_objects[i].bucket,
+                "key": "*%",
+                "versionId": entries.s3_objects[i].version_id,
+                "storageClass": "Glacier"
+            },
+        ]);
+        let (status, results) = response_from::<Vec<StorageClassCorrectionResult>>(
+            state.clone(),
+            "/s3/storage-class/bulk",
+            Method::POST,
+            Body::new(body.to_string()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            results[0].s3_object_ids(),
+            [entries.s3_objects[i].s3_object_id]
+        );
+
+        entries.s3_objects[i].storage_class = Some(StorageClass::Glacier);
+        entries.s3_objects[i].is_accessible = false;
+        assert_correct_records(state.database_client(), entries).await;
+    }
+
     #[sqlx::test(migrator = "MIGRATOR")]
     async fn update_collection_attributes_api(pool: PgPool) {
         let state = AppState::from_pool(pool).await.unwrap();
@@ -1020,6 +2193,68 @@ mod tests {
         assert_correct_records(client, entries).await;
     }
 
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn update_ingest_id_s3_tags_conditional(pool: PgPool) {
+        let state = AppState::from_pool(pool)
+            .await
+            .unwrap()
+            .with_config(Config {
+                api_conditional_tag_writes: true,
+                ..Default::default()
+            });
+        let entries = EntriesBuilder::default()
+            .build(state.database_client())
+            .await
+            .unwrap();
+
+        let patch = json!({
+            "ingestId": [
+                { "op": "add", "path": "/", "value": "00000000-0000-0000-0000-000000000000" },
+            ]
+        });
+
+        for (current, expected_writes) in
+            [("00000000-0000-0000-0000-000000000000", 0), ("other", 1)]
+        {
+            let get_rule = mock!(aws_sdk_s3::Client::get_object_tagging)
+                .match_requests(|req| {
+                    req.key() == Some("2")
+                        && req.bucket() == Some("1")
+                        && req.version_id() == Some("2")
+                })
+                .then_output(move || {
+                    GetObjectTaggingOutput::builder()
+                        .tag_set(
+                            Tag::builder()
+                                .key("ingest_id")
+                                .value(current)
+                                .build()
+                                .unwrap(),
+                        )
+                        .build()
+                        .unwrap()
+                });
+            let put_rule = mock!(aws_sdk_s3::Client::put_object_tagging)
+                .then_output(|| PutObjectTaggingOutput::builder().version_id("2").build());
+            let state = state
+                .clone()
+                .with_s3_client(mock_s3(&[get_rule.clone(), put_rule.clone()]));
+
+            let (status, _) = response_from::<S3>(
+                state,
+                &format!("/s3/{}?updateTag=true", entries.s3_objects[2].s3_object_id),
+                Method::PATCH,
+                Body::new(patch.to_string()),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+
+            // The tag is only written if it doesn't already match.
+            assert_eq!(get_rule.num_calls(), 1);
+            assert_eq!(put_rule.num_calls(), expected_writes);
+        }
+    }
+
     fn mock_put_object_tagging() -> Client {
         mock_s3(&[mock!(aws_sdk_s3::Client::put_object_tagging)
             .match_requests(move |req| {
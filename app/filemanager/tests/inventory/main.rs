@@ -5,8 +5,10 @@ use aws_sdk_s3::primitives::ByteStream;
 use aws_smithy_mocks::{Rule, RuleMode, mock, mock_client};
 
 use filemanager::clients::aws::s3::Client;
-use filemanager::events::aws::StorageClass;
+use filemanager::database::entities::sea_orm_active_enums::Reason;
+use filemanager::events::aws::crawl::Crawl;
 use filemanager::events::aws::inventory::{Inventory, Record};
+use filemanager::events::aws::{FlatS3EventMessage, FlatS3EventMessages, StorageClass};
 
 /// Create mocks for the inventory manifest and inventory files.
 macro_rules! base_mocks {
@@ -70,6 +72,36 @@ async fn parse_records(client: aws_sdk_s3::Client, bucket: &str, file: &str) ->
     inventory.parse_manifest_key(file, bucket).await.unwrap()
 }
 
+/// Crawl an inventory and assert that the messages match the records.
+async fn assert_crawl(client: aws_sdk_s3::Client, file: &str, expected: Vec<Record>) {
+    let result = Crawl::new(Client::new(client))
+        .crawl_from_inventory(&format!("s3://filemanager-inventory-test/{file}"))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(
+        result
+            .iter()
+            .all(|message| message.reason == Reason::Crawl && message.event_time.is_some())
+    );
+    // Ids and event times are generated, so they are not compared.
+    let normalize = |messages: Vec<FlatS3EventMessage>| {
+        messages
+            .into_iter()
+            .map(|message| FlatS3EventMessage {
+                s3_object_id: Default::default(),
+                event_time: None,
+                ..message
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        normalize(result),
+        normalize(FlatS3EventMessages::from(expected).into_inner())
+    );
+}
+
 fn expected_records() -> Vec<Record> {
     let no_version_records = expected_records_no_version();
     vec![
@@ -264,3 +296,29 @@ async fn parquet_with_manifest() {
     .await;
     assert_eq!(result, expected_records());
 }
+
+#[tokio::test]
+async fn csv_crawl_from_inventory() {
+    let client = mock_client_for_inventory!(
+        "filemanager-inventory-test",
+        "csv_inventory.csv.gz",
+        "csv_inventory_manifest.json"
+    );
+    assert_crawl(client, "csv_inventory_manifest.json", expected_records()).await;
+}
+
+#[tokio::test]
+async fn parquet_crawl_from_inventory() {
+    let client = mock_client_for_inventory!(
+        "filemanager-inventory-test",
+        "parquet_inventory.parquet",
+        "parquet_inventory_manifest.json",
+        "parquet_inventory_manifest.checksum"
+    );
+    assert_crawl(
+        client,
+        "parquet_inventory_manifest.checksum",
+        expected_records(),
+    )
+    .await;
+}
@@ -171,6 +171,7 @@ export class FileManagerStack extends GitStack {
       securityGroup: this.securityGroup,
       buckets: [...props.ingestBuckets, ...props.inventoryBuckets],
       role: this.ingestRole,
+      ingestQueue: this.queue,
       ...props,
     });
 
@@ -3,6 +3,7 @@ import * as fn from './function';
 import { DatabaseProps } from './function';
 import { BucketProps } from './ingest';
 import { PolicyStatement } from 'aws-cdk-lib/aws-iam';
+import { IQueue } from 'aws-cdk-lib/aws-sqs';
 import { FILEMANAGER_INGEST_ID_TAG_NAME } from '../constants';
 
 /**
@@ -12,6 +13,10 @@ export type ApiFunctionProps = fn.FunctionPropsConfigurable &
   DatabaseProps &
   BucketProps & {
     accessKeySecretArn: string;
+    /**
+     * The ingest queue which records that failed collection are re-queued to.
+     */
+    ingestQueue?: IQueue;
   };
 
 /**
@@ -28,6 +33,7 @@ export class ApiFunction extends fn.Function {
         AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH: 'true',
         FILEMANAGER_ACCESS_KEY_SECRET_ID: props.accessKeySecretArn,
         FILEMANAGER_INGESTER_TAG_NAME: FILEMANAGER_INGEST_ID_TAG_NAME,
+        ...(props.ingestQueue && { FILEMANAGER_SQS_URL: props.ingestQueue.queueUrl }),
         ...props.environment,
       },
       ...props,
@@ -40,5 +46,8 @@ export class ApiFunction extends fn.Function {
         resources: [`${props.accessKeySecretArn}-*`],
       })
     );
+
+    // Allow re-queueing records to the ingest queue.
+    props.ingestQueue?.grantSendMessages(this.role);
   }
 }
@@ -64,6 +64,9 @@ export class IngestFunction extends Function {
         batchSize: 10000,
         maxBatchingWindow: Duration.seconds(30),
         maxConcurrency: 10,
+        // Re-collect messages with records that fail to be collected are reported individually,
+        // so that only those messages are retried.
+        reportBatchItemFailures: true,
       });
       this.function.addEventSource(eventSource);
     });